@@ -2,12 +2,29 @@ use crate::{
     decode_xml_bytes, load_or_build_cbeta_index_cli, resolve_cbeta_path_cli, slice_text_cli,
     SliceArgs,
 };
+use daizo_core::juan_map::attribute_juans;
 use daizo_core::path_resolver::cbeta_root;
 use daizo_core::text_utils::highlight_text;
 use daizo_core::{
     cbeta_grep, extract_cbeta_juan, extract_text, extract_text_opts, list_heads_cbeta,
 };
 
+/// " [juan 001 · 序品第一]" for a match, or "" when neither is known.
+fn match_place(m: &daizo_core::GrepMatch) -> String {
+    let parts: Vec<String> = m
+        .juan_number
+        .as_deref()
+        .map(|n| format!("juan {}", n))
+        .into_iter()
+        .chain(m.section.clone())
+        .collect();
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", parts.join(" · "))
+    }
+}
+
 pub fn cbeta_title_search(query: &str, limit: usize, json: bool) -> anyhow::Result<()> {
     let idx = load_or_build_cbeta_index_cli();
     let hits = super::super::best_match(&idx, query, limit);
@@ -151,6 +168,7 @@ pub fn cbeta_fetch(args: &crate::Commands) -> anyhow::Result<()> {
                 "returnedEnd": slice.end_bound(text.len(), sliced.len()),
                 "truncated": (sliced.len() as u64) < (text.len() as u64),
                 "sourcePath": path.to_string_lossy(),
+                "license": daizo_core::license::license_for_path("cbeta", &path),
                 "extractionMethod": extraction_method,
                 "partMatched": part_matched,
                 "headingsTotal": heads.len(),
@@ -202,9 +220,10 @@ pub fn cbeta_pipeline(args: &crate::Commands) -> anyhow::Result<()> {
         let q = if looks_like_regex {
             query.clone()
         } else {
-            daizo_core::text_utils::ws_cjk_variant_fuzzy_regex_literal(&query)
+            daizo_core::text_utils::ws_cjk_variant_fuzzy_regex_literal(query)
         };
-        let results = cbeta_grep(&root, &q, *max_results, *max_matches_per_file);
+        let mut results = cbeta_grep(&root, &q, *max_results, *max_matches_per_file);
+        attribute_juans(&mut results);
         let mut summary = format!(
             "Found {} files with matches for '{}':\n\n",
             results.len(),
@@ -221,8 +240,9 @@ pub fn cbeta_pipeline(args: &crate::Commands) -> anyhow::Result<()> {
             summary.push_str(&format!("   {} matches\n", result.total_matches));
             for (j, m) in result.matches.iter().enumerate().take(2) {
                 summary.push_str(&format!(
-                    "   Match {}: ...{}...\n",
+                    "   Match {}{}: ...{}...\n",
                     j + 1,
+                    match_place(m),
                     m.context.chars().take(100).collect::<String>()
                 ));
             }
@@ -230,7 +250,8 @@ pub fn cbeta_pipeline(args: &crate::Commands) -> anyhow::Result<()> {
                 if let Some(ln) = m.line_number {
                     suggestions.push(serde_json::json!({
                     "cmd": "daizo-cli cbeta-fetch --id <ID> --line-number <LN> --context-before <B> --context-after <A>",
-                    "id": result.file_id, "lineNumber": ln, "contextBefore": context_before, "contextAfter": context_after
+                    "id": result.file_id, "lineNumber": ln, "contextBefore": context_before, "contextAfter": context_after,
+                    "juan": m.juan_number, "section": m.section
                 }));
                 }
             }
@@ -451,18 +472,28 @@ pub fn cbeta_search(
     query: &str,
     max_results: usize,
     max_matches_per_file: usize,
+    no_variants: bool,
+    notes_only: bool,
     json: bool,
 ) -> anyhow::Result<()> {
     let looks_like_regex = query.chars().any(|c| ".+*?[](){}|\\".contains(c));
     let q = if looks_like_regex {
         query.to_string()
+    } else if no_variants {
+        regex::escape(query)
     } else {
         daizo_core::text_utils::ws_cjk_variant_fuzzy_regex_literal(query)
     };
-    let results = cbeta_grep(&cbeta_root(), &q, max_results, max_matches_per_file);
+    let mut results = if notes_only {
+        daizo_core::cbeta_grep_notes(&cbeta_root(), &q, max_results, max_matches_per_file)
+    } else {
+        cbeta_grep(&cbeta_root(), &q, max_results, max_matches_per_file)
+    };
+    attribute_juans(&mut results);
     if json {
         let meta = serde_json::json!({
             "searchPattern": q,
+            "notesOnly": notes_only,
             "totalFiles": results.len(),
             "results": results,
             "hint": "Use cbeta-fetch with the file_id and recommended parts to get full content"
@@ -488,8 +519,9 @@ pub fn cbeta_search(
             );
             for (j, m) in result.matches.iter().enumerate().take(2) {
                 println!(
-                    "   Match {}: ...{}...",
+                    "   Match {}{}: ...{}...",
                     j + 1,
+                    match_place(m),
                     m.context.chars().take(100).collect::<String>()
                 );
             }
@@ -0,0 +1,48 @@
+use crate::cmd::export::resolve_file;
+use crate::decode_xml_bytes;
+use daizo_core::cite::{cite as build_citation, Target};
+use std::path::Path;
+
+pub fn cite(args: &crate::Commands) -> anyhow::Result<()> {
+    if let crate::Commands::Cite {
+        source,
+        id,
+        part,
+        lb,
+        line_number,
+        reference,
+        format,
+    } = args
+    {
+        let source = source.to_lowercase();
+        let (path, _, index) = resolve_file(&source, Some(id), None)?;
+        let xml = decode_xml_bytes(&std::fs::read(&path)?);
+        let entry = index.iter().find(|e| Path::new(&e.path) == path);
+        let target = Target {
+            part: part.as_deref(),
+            lb: lb.as_deref(),
+            line: *line_number,
+            reference: reference.as_deref(),
+        };
+        let c = build_citation(
+            &source,
+            id,
+            entry,
+            &xml,
+            &target,
+            &daizo_core::provenance::today_utc(),
+        );
+        let csl = || serde_json::to_string_pretty(&serde_json::json!([c.csl_json()]));
+        match format.as_str() {
+            "plain" => println!("{}", c.plain()),
+            "bibtex" => println!("{}", c.bibtex()),
+            "csl-json" => println!("{}", csl()?),
+            "all" => println!("{}\n\n{}\n\n{}", c.plain(), c.bibtex(), csl()?),
+            f => anyhow::bail!(
+                "unknown --format '{}': use plain, bibtex, csl-json or all",
+                f
+            ),
+        }
+    }
+    Ok(())
+}
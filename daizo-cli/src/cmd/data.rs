@@ -0,0 +1,128 @@
+use daizo_core::grep_cache::{self, GrepCache};
+use daizo_core::path_resolver::{cache_dir, cbeta_root, sarit_root, tipitaka_root};
+use daizo_core::repo::{self, PullReport};
+use daizo_core::{GrepResult, IndexEntry};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// `data-update`: pull each git corpus, then bring its index and cached searches up to date
+/// for just the files that changed.
+pub fn data_update(source: &str) -> anyhow::Result<()> {
+    let src = source.to_lowercase();
+    let corpora: [(&str, PathBuf); 3] = [
+        ("cbeta", cbeta_root()),
+        ("tipitaka", tipitaka_root()),
+        ("sarit", sarit_root()),
+    ];
+    if src != "all" && !corpora.iter().any(|(c, _)| *c == src) {
+        anyhow::bail!(
+            "unknown --source '{}' (cbeta | tipitaka | sarit | all)",
+            source
+        );
+    }
+    let mut summary = serde_json::Map::new();
+    for (corpus, root) in corpora.iter() {
+        if (src != "all" && src != *corpus) || !root.exists() {
+            continue;
+        }
+        let index = cache_dir().join(format!("{}-index.json", corpus));
+        // Only an index that matched the old checkout can be patched; a stale one stays stale.
+        let index_current = repo::index_is_current(&index, root) == Some(true);
+        eprintln!("[data-update] pulling {}", corpus);
+        let Some(report) = repo::pull(root) else {
+            eprintln!("[error] {}: git pull failed", corpus);
+            summary.insert(
+                corpus.to_string(),
+                serde_json::json!({"error": "git pull failed"}),
+            );
+            continue;
+        };
+        for c in &report.changed {
+            eprintln!("  {} {}", c.status, c.path);
+        }
+        let changed: Vec<PathBuf> = report
+            .changed
+            .iter()
+            .map(|c| report.checkout.join(&c.path))
+            .collect();
+        let index_state = if !index.exists() {
+            serde_json::json!("not built")
+        } else if report.changed.is_empty() {
+            serde_json::json!("unchanged")
+        } else if !index_current {
+            serde_json::json!("stale (run index-rebuild)")
+        } else {
+            update_index_file(corpus, root, &index, &changed)?
+        };
+        let grep = carry_over_searches(corpus, root, &report, &changed);
+        summary.insert(
+            corpus.to_string(),
+            serde_json::json!({
+                "from": report.from,
+                "to": report.to,
+                "changed": report.changed,
+                "index": index_state,
+                "grepCache": {"kept": grep.0, "dropped": grep.1},
+            }),
+        );
+    }
+    println!("{}", serde_json::to_string(&summary)?);
+    Ok(())
+}
+
+/// Re-index the changed files of `corpus` in its cached index and record the new revision.
+fn update_index_file(
+    corpus: &str,
+    root: &Path,
+    index: &Path,
+    changed: &[PathBuf],
+) -> anyhow::Result<serde_json::Value> {
+    let mut entries: Vec<IndexEntry> = serde_json::from_slice(&std::fs::read(index)?)?;
+    let Some((removed, added)) = daizo_core::update_index(corpus, root, &mut entries, changed)
+    else {
+        return Ok(serde_json::json!("not updated"));
+    };
+    std::fs::write(index, serde_json::to_vec(&entries)?)?;
+    repo::record_index_revision(index, root);
+    Ok(serde_json::json!({"removed": removed, "added": added, "count": entries.len()}))
+}
+
+/// Keep the cached searches of the old commit that the pull cannot have changed: none of
+/// their hits is in a changed file and the changed files have no hits of their own.
+fn carry_over_searches(
+    corpus: &str,
+    root: &Path,
+    report: &PullReport,
+    changed: &[PathBuf],
+) -> (usize, usize) {
+    let (Some(from), Some(to)) = (&report.from, &report.to) else {
+        return (0, 0);
+    };
+    if from == to {
+        return (0, 0);
+    }
+    let changed_set: HashSet<String> = changed
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    // The changed files a whole-corpus search of `corpus` reads.
+    let searched = daizo_core::grep_paths(corpus, root);
+    let rescan: Vec<PathBuf> = changed
+        .iter()
+        .filter(|p| searched.binary_search(p).is_ok())
+        .cloned()
+        .collect();
+    let cache = GrepCache::new(grep_cache::default_dir(), grep_cache::DEFAULT_MAX_ENTRIES);
+    cache.carry_over(corpus, from, to, |key, results: &[GrepResult]| {
+        if results.iter().any(|r| changed_set.contains(&r.file_path)) {
+            return false;
+        }
+        rescan.is_empty()
+            || match corpus {
+                "cbeta" => daizo_core::cbeta_grep_scoped(&rescan, &key.query, 1, 1),
+                "tipitaka" => daizo_core::tipitaka_grep_scoped(&rescan, &key.query, 1, 1, false),
+                _ => daizo_core::grep_scoped(&rescan, &key.query, 1, 1),
+            }
+            .is_empty()
+    })
+}
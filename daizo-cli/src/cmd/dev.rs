@@ -0,0 +1,57 @@
+use daizo_core::fixtures::{fixtures_dir, FIXTURES};
+use daizo_core::progress::Counter;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Download the curated fixture files (see `daizo_core::fixtures::FIXTURES`) into `dir`
+/// (default: `DAIZO_FIXTURES` or `fixtures/` at the workspace root). Existing files are kept
+/// unless `force` is set; `corpus` limits the download to one corpus. Downloads follow the
+/// politeness policy (robots.txt, per-host spacing) like every other remote request.
+pub fn fetch_fixtures(
+    dir: Option<PathBuf>,
+    corpus: Option<&str>,
+    force: bool,
+) -> anyhow::Result<()> {
+    let dir = dir.unwrap_or_else(fixtures_dir);
+    let wanted: Vec<_> = FIXTURES
+        .iter()
+        .filter(|f| corpus.is_none_or(|c| c.eq_ignore_ascii_case(f.corpus)))
+        .collect();
+    if wanted.is_empty() {
+        anyhow::bail!("no fixtures for corpus: {}", corpus.unwrap_or(""));
+    }
+    let counter = Counter::new("fetch fixtures", "files", wanted.len());
+    let mut failed = Vec::new();
+    for f in wanted {
+        let dest = dir.join(f.path);
+        if dest.is_file() && !force {
+            println!("[skip] {} (exists)", f.path);
+            counter.tick();
+            continue;
+        }
+        let res = crate::http::get(f.url, Duration::from_secs(60)).and_then(|r| {
+            r.error_for_status()
+                .and_then(|r| r.bytes())
+                .map_err(|e| e.to_string())
+        });
+        match res {
+            Ok(bytes) => {
+                if let Some(parent) = dest.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&dest, &bytes)?;
+                println!("[ok] {} ({} bytes; {})", f.path, bytes.len(), f.covers);
+            }
+            Err(e) => {
+                eprintln!("[error] {}: {}", f.url, e);
+                failed.push(f.path);
+            }
+        }
+        counter.tick();
+    }
+    println!("fixtures: {}", dir.display());
+    if !failed.is_empty() {
+        anyhow::bail!("{} fixture(s) failed: {}", failed.len(), failed.join(", "));
+    }
+    Ok(())
+}
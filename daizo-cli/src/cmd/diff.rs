@@ -0,0 +1,77 @@
+use crate::cmd::export::load_work;
+use daizo_core::analysis::plain_text_from_content;
+use daizo_core::diff::{diff, DiffOptions};
+
+/// Plain text of a work (or of one CBETA juan), as the fetch tools extract it.
+fn work_text(source: &str, id: &str, part: Option<&str>) -> anyhow::Result<(String, String)> {
+    let (info, sources) = load_work(source, Some(id), None)?;
+    let mut text = String::new();
+    for (name, content) in &sources {
+        let is_xml = name.to_ascii_lowercase().ends_with(".xml");
+        if let Some(t) = plain_text_from_content(source, content, is_xml, part, false) {
+            text.push_str(&t);
+            // A juan lives in one file of a multi-volume work.
+            if part.is_some() {
+                break;
+            }
+        }
+    }
+    if text.trim().is_empty() {
+        anyhow::bail!(
+            "no text for {}{}",
+            id,
+            part.map(|p| format!(" juan {}", p)).unwrap_or_default()
+        );
+    }
+    Ok((info.title, text))
+}
+
+pub fn text_diff(args: &crate::Commands) -> anyhow::Result<()> {
+    if let crate::Commands::TextDiff {
+        source,
+        a,
+        b,
+        source_b,
+        part_a,
+        part_b,
+        keep_punctuation,
+        no_variants,
+        context,
+        json,
+    } = args
+    {
+        let source = source.to_lowercase();
+        let source_b = source_b.as_deref().unwrap_or(&source).to_lowercase();
+        let (title_a, text_a) = work_text(&source, a, part_a.as_deref())?;
+        let (title_b, text_b) = work_text(&source_b, b, part_b.as_deref())?;
+        let opts = DiffOptions {
+            ignore_punctuation: !keep_punctuation,
+            fold_variants: !no_variants,
+            ..Default::default()
+        };
+        let d = diff(&text_a, &text_b, &opts);
+        if *json {
+            let out = serde_json::json!({
+                "a": {"source": source, "id": a, "part": part_a, "title": title_a, "chars": d.a_len},
+                "b": {"source": source_b, "id": b, "part": part_b, "title": title_b, "chars": d.b_len},
+                "similarity": d.similarity(),
+                "equalChars": d.equal,
+                "anchors": d.anchors,
+                "hunks": d.hunks,
+            });
+            println!("{}", serde_json::to_string_pretty(&out)?);
+        } else {
+            println!("--- {} {} ({} chars)", a, title_a, d.a_len);
+            println!("+++ {} {} ({} chars)", b, title_b, d.b_len);
+            println!(
+                "similarity {:.3}, {} changes, {} anchors",
+                d.similarity(),
+                d.changes().count(),
+                d.anchors.len()
+            );
+            println!();
+            println!("{}", d.render(*context));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use crate::{
+    load_or_build_cbeta_index_cli, load_or_build_gretil_index_cli,
+    load_or_build_muktabodha_index_cli, load_or_build_sarit_index_cli,
+    load_or_build_tipitaka_index_cli,
+};
+use daizo_core::embeddings::{self, index_paths};
+
+/// Embed the passages of a collection and store the index searched by `semantic_search`.
+pub fn embed_index(args: &crate::Commands) -> anyhow::Result<()> {
+    if let crate::Commands::EmbedIndex {
+        source,
+        collection,
+        backend,
+        passage_chars,
+        batch,
+    } = args
+    {
+        let source = source.to_lowercase();
+        let index = match source.as_str() {
+            "cbeta" => load_or_build_cbeta_index_cli(),
+            "tipitaka" => load_or_build_tipitaka_index_cli(),
+            "gretil" => load_or_build_gretil_index_cli(),
+            "sarit" => load_or_build_sarit_index_cli(),
+            "muktabodha" => load_or_build_muktabodha_index_cli(),
+            _ => anyhow::bail!("unknown source: {}", source),
+        };
+        let name = collection.as_deref().unwrap_or("all");
+        let Some(c) = daizo_core::reading::resolve(&source, name) else {
+            anyhow::bail!("unknown --collection '{}'", name);
+        };
+        let entries = daizo_core::reading::members(&source, &c, &index);
+        if entries.is_empty() {
+            anyhow::bail!("no texts in {}", c.label);
+        }
+        let embedder = embeddings::configured(backend.as_deref()).map_err(anyhow::Error::msg)?;
+        let idx = embeddings::build(
+            &source,
+            name,
+            &entries,
+            embedder.as_ref(),
+            *passage_chars,
+            *batch,
+        )
+        .map_err(anyhow::Error::msg)?;
+        if idx.is_empty() {
+            anyhow::bail!("no text to embed in {}", c.label);
+        }
+        let (meta, vectors) = index_paths(&source);
+        idx.save(&meta, &vectors)?;
+        eprintln!(
+            "[embed-index] {}: {} files, {} passages, model {} ({} dims) -> {}",
+            c.label,
+            entries.len(),
+            idx.len(),
+            idx.model,
+            idx.dims,
+            meta.display()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,459 @@
+use crate::{
+    decode_xml_bytes, load_or_build_cbeta_index_cli, load_or_build_gretil_index_cli,
+    load_or_build_muktabodha_index_cli, load_or_build_sarit_index_cli,
+    load_or_build_tipitaka_index_cli, resolve_cbeta_path_cli, resolve_gretil_path_cli,
+    resolve_muktabodha_path_cli, resolve_sarit_path_cli, resolve_tipitaka_path,
+};
+use daizo_core::export::{export_work, WorkInfo};
+use daizo_core::path_resolver::{
+    cbeta_root, gretil_root, muktabodha_root, sarit_root, tipitaka_root,
+};
+use daizo_core::tei_text::{GaijiMode, LineBreaks, TextFormat, TextOptions};
+use daizo_core::IndexEntry;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// CBETA work number without its part letter: `0220a` → `0220`.
+fn work_number(nnum: &str) -> &str {
+    nnum.trim_end_matches(|c: char| c.is_ascii_alphabetic())
+}
+
+/// Whether a CBETA id names one file of a work (`T05n0220a`) rather than the work (`T0220`).
+fn is_file_id(id: Option<&str>) -> bool {
+    static FILE_ID: OnceLock<Regex> = OnceLock::new();
+    let re = FILE_ID.get_or_init(|| Regex::new(r"^[A-Za-z]+\d+n\d").unwrap());
+    id.is_some_and(|i| re.is_match(i))
+}
+
+/// Files of the CBETA work `entry` belongs to, in id order, so a work spanning several
+/// volumes (T0220) is exported whole.
+fn work_files(entry: &IndexEntry, index: &[IndexEntry]) -> Vec<PathBuf> {
+    let meta = |e: &IndexEntry, k: &str| e.meta.as_ref().and_then(|m| m.get(k)).cloned();
+    let (canon, nnum) = (
+        meta(entry, "canon"),
+        meta(entry, "nnum").unwrap_or_default(),
+    );
+    let mut siblings: Vec<&IndexEntry> = index
+        .iter()
+        .filter(|e| {
+            meta(e, "canon") == canon
+                && meta(e, "nnum").is_some_and(|n| work_number(&n) == work_number(&nnum))
+        })
+        .collect();
+    siblings.sort_by(|a, b| a.id.cmp(&b.id));
+    siblings.iter().map(|e| PathBuf::from(&e.path)).collect()
+}
+
+/// The file `id` (or the best title match for `query`) names in `source`, with the corpus
+/// root and index.
+pub(crate) fn resolve_file(
+    source: &str,
+    id: Option<&str>,
+    query: Option<&str>,
+) -> anyhow::Result<(PathBuf, PathBuf, Vec<IndexEntry>)> {
+    let (path, root, index): (PathBuf, PathBuf, Vec<IndexEntry>) = match source {
+        "cbeta" => (
+            resolve_cbeta_path_cli(id, query),
+            cbeta_root(),
+            load_or_build_cbeta_index_cli(),
+        ),
+        "tipitaka" => (
+            resolve_tipitaka_path(id, query),
+            tipitaka_root(),
+            load_or_build_tipitaka_index_cli(),
+        ),
+        "gretil" => (
+            resolve_gretil_path_cli(id, query),
+            gretil_root(),
+            load_or_build_gretil_index_cli(),
+        ),
+        "sarit" => (
+            resolve_sarit_path_cli(id, query),
+            sarit_root(),
+            load_or_build_sarit_index_cli(),
+        ),
+        "muktabodha" => (
+            resolve_muktabodha_path_cli(id, query),
+            muktabodha_root(),
+            load_or_build_muktabodha_index_cli(),
+        ),
+        _ => anyhow::bail!("unknown source: {}", source),
+    };
+    if path.as_os_str().is_empty() || !path.exists() {
+        anyhow::bail!(
+            "text not found: {}",
+            id.or(query).unwrap_or("specify --id or --query")
+        );
+    }
+    Ok((path, root, index))
+}
+
+/// The work `id` (or the best title match for `query`) names in `source`: front-matter
+/// details and its files as (name, contents), in reading order.
+pub(crate) fn load_work(
+    source: &str,
+    id: Option<&str>,
+    query: Option<&str>,
+) -> anyhow::Result<(WorkInfo, Vec<(String, String)>)> {
+    let (path, root, index) = resolve_file(source, id, query)?;
+    let entry = index.iter().find(|e| Path::new(&e.path) == path);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (files, work_id) = match (source, entry) {
+        ("cbeta", Some(e)) if !is_file_id(id) => {
+            let m = e.meta.clone().unwrap_or_default();
+            let work_id = format!(
+                "{}{}",
+                m.get("canon").map(String::as_str).unwrap_or_default(),
+                work_number(m.get("nnum").map(String::as_str).unwrap_or_default())
+            );
+            (work_files(e, &index), work_id)
+        }
+        _ => (vec![path.clone()], stem.clone()),
+    };
+    let info = WorkInfo {
+        corpus: source.to_string(),
+        id: work_id,
+        title: entry.map(|e| e.title.clone()).unwrap_or(stem),
+        author: entry
+            .and_then(|e| e.meta.as_ref())
+            .and_then(|m| m.get("author").cloned()),
+        snapshot: daizo_core::provenance::git_snapshot(&root),
+        license: daizo_core::license::corpus_license(source).map(|l| l.license.to_string()),
+        tool: format!("daizo-cli {}", env!("CARGO_PKG_VERSION")),
+        exported: daizo_core::provenance::today_utc(),
+    };
+    let mut sources = Vec::with_capacity(files.len());
+    for f in &files {
+        let name = f
+            .file_name()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        sources.push((name, decode_xml_bytes(&std::fs::read(f)?)));
+    }
+    Ok((info, sources))
+}
+
+/// `--format`, `--line-breaks`, `--gaiji-mode` and notes as converter options.
+pub(crate) fn text_options(
+    format: &str,
+    line_breaks: Option<&str>,
+    gaiji_mode: &str,
+    include_notes: bool,
+) -> anyhow::Result<TextOptions> {
+    Ok(TextOptions {
+        include_notes,
+        line_breaks: match line_breaks {
+            Some(s) => LineBreaks::parse(s)
+                .ok_or_else(|| anyhow::anyhow!("unknown --line-breaks '{}'", s))?,
+            None => LineBreaks::None,
+        },
+        gaiji: GaijiMode::parse(gaiji_mode)
+            .ok_or_else(|| anyhow::anyhow!("unknown --gaiji-mode '{}'", gaiji_mode))?,
+        format: TextFormat::parse(format)
+            .ok_or_else(|| anyhow::anyhow!("unknown --format '{}'", format))?,
+    })
+}
+
+pub fn export(args: &crate::Commands) -> anyhow::Result<()> {
+    if let crate::Commands::Export {
+        source,
+        id,
+        query,
+        format,
+        out,
+        include_notes,
+        line_breaks,
+        gaiji_mode,
+    } = args
+    {
+        let source = source.to_lowercase();
+        let opts = text_options(format, line_breaks.as_deref(), gaiji_mode, *include_notes)?;
+        let (info, sources) = load_work(&source, id.as_deref(), query.as_deref())?;
+        let written = export_work(&info, &sources, &opts);
+        std::fs::create_dir_all(out)?;
+        for f in &written {
+            std::fs::write(out.join(&f.name), f.content())?;
+        }
+        println!(
+            "{}",
+            serde_json::json!({
+                "id": info.id,
+                "title": info.title,
+                "source": source,
+                "out": out,
+                "sources": sources.iter().map(|(n, _)| n).collect::<Vec<_>>(),
+                "files": written.iter().map(|f| &f.name).collect::<Vec<_>>(),
+            })
+        );
+    }
+    Ok(())
+}
+
+/// Works named by `--id` (in order) or the first `limit` of a reading-list collection.
+fn epub_works(
+    source: &str,
+    ids: &[String],
+    query: Option<&str>,
+    collection: Option<&str>,
+    limit: usize,
+) -> anyhow::Result<(Option<String>, Vec<String>)> {
+    let Some(name) = collection else {
+        if ids.is_empty() && query.is_none() {
+            anyhow::bail!("specify --id, --query or --collection");
+        }
+        return Ok((None, ids.to_vec()));
+    };
+    let Some(c) = daizo_core::reading::resolve(source, name) else {
+        anyhow::bail!("unknown collection '{}'", name);
+    };
+    let index = match source {
+        "cbeta" => load_or_build_cbeta_index_cli(),
+        "tipitaka" => load_or_build_tipitaka_index_cli(),
+        "gretil" => load_or_build_gretil_index_cli(),
+        "sarit" => load_or_build_sarit_index_cli(),
+        "muktabodha" => load_or_build_muktabodha_index_cli(),
+        _ => anyhow::bail!("unknown source: {}", source),
+    };
+    let mut works: Vec<String> = Vec::new();
+    for e in daizo_core::reading::members(source, &c, &index) {
+        // One entry per work: the volumes of a CBETA work are gathered by load_work.
+        let id = match (source, e.meta.as_ref()) {
+            ("cbeta", Some(m)) => format!(
+                "{}{}",
+                m.get("canon").map(String::as_str).unwrap_or_default(),
+                work_number(m.get("nnum").map(String::as_str).unwrap_or_default())
+            ),
+            _ => e.id.clone(),
+        };
+        if !works.contains(&id) {
+            works.push(id);
+        }
+        if works.len() == limit {
+            break;
+        }
+    }
+    if works.is_empty() {
+        anyhow::bail!("collection '{}' has no works", c.label);
+    }
+    Ok((Some(c.label), works))
+}
+
+pub fn epub(args: &crate::Commands) -> anyhow::Result<()> {
+    use daizo_core::epub::{language, write_epub, Book, Chapter, Section};
+    if let crate::Commands::Epub {
+        source,
+        id,
+        query,
+        collection,
+        limit,
+        out,
+        title,
+        no_notes,
+        gaiji_mode,
+    } = args
+    {
+        let source = source.to_lowercase();
+        let opts = text_options("markdown", None, gaiji_mode, !*no_notes)?;
+        let (label, ids) =
+            epub_works(&source, id, query.as_deref(), collection.as_deref(), *limit)?;
+        let mut works = Vec::new();
+        if ids.is_empty() {
+            works.push(load_work(&source, None, query.as_deref())?);
+        }
+        for i in &ids {
+            works.push(load_work(&source, Some(i), None)?);
+        }
+        let mut sections = Vec::new();
+        let mut creators: Vec<String> = Vec::new();
+        let mut files = Vec::new();
+        for (info, sources) in &works {
+            let chapters: Vec<Chapter> = export_work(info, sources, &opts)
+                .into_iter()
+                .map(|f| Chapter {
+                    title: match (f.juan_title, f.juan) {
+                        (Some(t), _) => t,
+                        (None, Some(n)) => format!("{} ({})", info.title, n),
+                        (None, None) => info.title.clone(),
+                    },
+                    markdown: f.body,
+                })
+                .collect();
+            if chapters.is_empty() {
+                continue;
+            }
+            if let Some(a) = info.author.as_ref().filter(|a| !creators.contains(a)) {
+                creators.push(a.clone());
+            }
+            files.extend(sources.iter().map(|(n, _)| n.clone()));
+            sections.push(Section {
+                title: info.title.clone(),
+                chapters,
+            });
+        }
+        let Some((first, _)) = works.first() else {
+            anyhow::bail!("nothing to write");
+        };
+        let ids: Vec<&str> = works.iter().map(|(w, _)| w.id.as_str()).collect();
+        let license = daizo_core::license::corpus_license(&source);
+        let book = Book {
+            title: title.clone().or(label).unwrap_or_else(|| {
+                let titles: Vec<&str> = works.iter().map(|(w, _)| w.title.as_str()).collect();
+                titles.join(" · ")
+            }),
+            identifier: format!("urn:daizo:{}:{}", source, ids.join("+")),
+            language: language(&source).to_string(),
+            creators,
+            rights: license.map(|l| format!("{}. {}", l.license, l.attribution)),
+            source: Some(match &first.snapshot {
+                Some(s) => format!(
+                    "{} {} (snapshot {})",
+                    source,
+                    files.join(", "),
+                    &s[..s.len().min(12)]
+                ),
+                None => format!("{} {}", source, files.join(", ")),
+            }),
+            modified: first.exported.clone(),
+            sections,
+        };
+        let out = out
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(format!("{}.epub", ids.join("_"))));
+        if let Some(dir) = out.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        write_epub(&book, std::io::BufWriter::new(std::fs::File::create(&out)?))?;
+        println!(
+            "{}",
+            serde_json::json!({
+                "title": book.title,
+                "ids": ids,
+                "source": source,
+                "out": out,
+                "chapters": book.sections.iter().map(|s| s.chapters.len()).sum::<usize>(),
+            })
+        );
+    }
+    Ok(())
+}
+
+/// One JSONL record of `dump`.
+#[derive(serde::Serialize)]
+struct DumpRecord<'a> {
+    id: &'a str,
+    file: &'a str,
+    title: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    juan: Option<&'a str>,
+    #[serde(rename = "juanTitle", skip_serializing_if = "Option::is_none")]
+    juan_title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author: Option<&'a str>,
+    text: &'a str,
+    chars: usize,
+}
+
+pub fn dump(args: &crate::Commands) -> anyhow::Result<()> {
+    use std::io::Write;
+    if let crate::Commands::Dump {
+        source,
+        canon,
+        format,
+        out,
+        include_notes,
+        gaiji_mode,
+    } = args
+    {
+        if !format.eq_ignore_ascii_case("jsonl") {
+            anyhow::bail!("unknown --format '{}' (expected jsonl)", format);
+        }
+        let source = source.to_lowercase();
+        let opts = text_options("text", None, gaiji_mode, *include_notes)?;
+        let index = match source.as_str() {
+            "cbeta" => load_or_build_cbeta_index_cli(),
+            "tipitaka" => load_or_build_tipitaka_index_cli(),
+            "gretil" => load_or_build_gretil_index_cli(),
+            "sarit" => load_or_build_sarit_index_cli(),
+            "muktabodha" => load_or_build_muktabodha_index_cli(),
+            _ => anyhow::bail!("unknown source: {}", source),
+        };
+        let name = canon.as_deref().unwrap_or("all");
+        let Some(c) = daizo_core::reading::resolve(&source, name) else {
+            anyhow::bail!("unknown --canon '{}'", name);
+        };
+        let entries = daizo_core::reading::members(&source, &c, &index);
+        if entries.is_empty() {
+            anyhow::bail!("no texts in {}", c.label);
+        }
+        let mut w: Box<dyn Write> = match out {
+            Some(p) => Box::new(std::io::BufWriter::new(std::fs::File::create(p)?)),
+            None => Box::new(std::io::BufWriter::new(std::io::stdout().lock())),
+        };
+        let (mut records, mut chars) = (0usize, 0usize);
+        for e in &entries {
+            let path = Path::new(&e.path);
+            let Ok(bytes) = std::fs::read(path) else {
+                continue;
+            };
+            let file = path
+                .file_name()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let meta = e.meta.clone().unwrap_or_default();
+            let info = WorkInfo {
+                corpus: source.clone(),
+                id: match source.as_str() {
+                    "cbeta" => format!(
+                        "{}{}",
+                        meta.get("canon").map(String::as_str).unwrap_or_default(),
+                        work_number(meta.get("nnum").map(String::as_str).unwrap_or_default())
+                    ),
+                    _ => e.id.clone(),
+                },
+                title: e.title.clone(),
+                author: meta.get("author").cloned(),
+                ..Default::default()
+            };
+            let sources = [(file.clone(), decode_xml_bytes(&bytes))];
+            for part in export_work(&info, &sources, &opts) {
+                let text = part.body.trim();
+                let rec = DumpRecord {
+                    id: &info.id,
+                    file: &file,
+                    title: &info.title,
+                    juan: part.juan.as_deref(),
+                    juan_title: part.juan_title.as_deref(),
+                    author: info.author.as_deref(),
+                    text,
+                    chars: text.chars().count(),
+                };
+                let res = serde_json::to_writer(&mut w, &rec)
+                    .map_err(std::io::Error::from)
+                    .and_then(|_| w.write_all(b"\n"));
+                match res {
+                    // The reader of the pipe went away (e.g. `| head`).
+                    Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => return Ok(()),
+                    r => r?,
+                }
+                records += 1;
+                chars += rec.chars;
+            }
+        }
+        match w.flush() {
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => return Ok(()),
+            r => r?,
+        }
+        eprintln!(
+            "[dump] {}: {} files, {} records, {} chars",
+            c.label,
+            entries.len(),
+            records,
+            chars
+        );
+    }
+    Ok(())
+}
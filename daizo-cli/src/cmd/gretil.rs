@@ -138,6 +138,7 @@ pub fn gretil_fetch(args: &crate::Commands) -> anyhow::Result<()> {
                 "returnedEnd": slice.end_bound(text.len(), sliced.len()),
                 "truncated": (sliced.len() as u64) < (text.len() as u64),
                 "sourcePath": path.to_string_lossy(),
+                "license": daizo_core::license::license_for_path("gretil", &path),
                 "extractionMethod": extraction_method,
                 "headingsTotal": heads.len(),
                 "headingsPreview": heads.into_iter().take(*headings_limit).collect::<Vec<_>>(),
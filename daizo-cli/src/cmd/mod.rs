@@ -1,6 +1,13 @@
 pub mod cbeta;
+pub mod cite;
+pub mod data;
+pub mod dev;
+pub mod diff;
+pub mod embed;
+pub mod export;
 pub mod gretil;
 pub mod muktabodha;
 pub mod sarit;
 pub mod sat;
+pub mod stats;
 pub mod tipitaka;
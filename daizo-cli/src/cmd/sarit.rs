@@ -140,6 +140,7 @@ pub fn sarit_fetch(args: &crate::Commands) -> anyhow::Result<()> {
                 "returnedEnd": slice.end_bound(text.len(), sliced.len()),
                 "truncated": (sliced.len() as u64) < (text.len() as u64),
                 "sourcePath": path.to_string_lossy(),
+                "license": daizo_core::license::license_for_path("sarit", &path),
                 "extractionMethod": extraction_method,
                 "headingsTotal": heads.len(),
                 "headingsPreview": heads.into_iter().take(*headings_limit).collect::<Vec<_>>(),
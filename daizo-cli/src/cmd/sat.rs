@@ -1,10 +1,12 @@
-use crate::{slice_text_cli, SliceArgs};
+use crate::{http, slice_text_cli_bounds, SliceArgs};
 use daizo_core::path_resolver::cache_dir;
+use daizo_core::remote::{RemoteSource, SearchQuery, SAT};
 use daizo_core::text_utils::{
     find_highlight_positions, is_subsequence, jaccard, normalized, token_jaccard,
     ws_cjk_variant_fuzzy_regex_literal,
 };
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub(crate) struct SatHit {
@@ -13,16 +15,8 @@ pub(crate) struct SatHit {
     pub snippet: String,
 }
 
-pub(crate) fn http_client() -> &'static reqwest::blocking::Client {
-    use std::sync::OnceLock;
-    static CLI: OnceLock<reqwest::blocking::Client> = OnceLock::new();
-    CLI.get_or_init(|| {
-        reqwest::blocking::Client::builder()
-            .timeout(std::time::Duration::from_secs(15))
-            .build()
-            .unwrap()
-    })
-}
+/// Timeout of each SAT request.
+const SAT_TIMEOUT: Duration = Duration::from_secs(15);
 
 pub(crate) fn cache_path_for(url: &str) -> PathBuf {
     let mut hasher = sha1::Sha1::new();
@@ -42,7 +36,7 @@ pub(crate) fn sat_fetch_cli(url: &str) -> String {
     }
     let mut backoff = 500u64;
     for _ in 0..3 {
-        let res = http_client().get(url).send();
+        let res = http::get(url, SAT_TIMEOUT);
         if let Ok(r) = res {
             if r.status().is_success() {
                 if let Ok(html) = r.text() {
@@ -69,42 +63,22 @@ pub(crate) fn sat_extract_text(html: &str) -> String {
     out.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
-pub(crate) fn sat_wrap7_build_url(
-    q: &str,
-    rows: usize,
-    offs: usize,
-    fields: &str,
-    fq: &Vec<String>,
-) -> String {
-    let base = "https://21dzk.l.u-tokyo.ac.jp/SAT2018/wrap7.php";
-    let mut url = format!(
-        "{}?regex=off&q={}&rows={}&offs={}&schop=AND",
-        base,
-        urlencoding::encode(q),
-        rows,
-        offs
-    );
-    if !fields.trim().is_empty() {
-        url.push_str(&format!("&fl={}", urlencoding::encode(fields)));
-    }
-    for f in fq {
-        if !f.trim().is_empty() {
-            url.push_str(&format!("&fq={}", urlencoding::encode(f)));
-        }
-    }
-    url
-}
-
 pub(crate) fn sat_wrap7_search_json(
     q: &str,
     rows: usize,
     offs: usize,
     fields: &str,
-    fq: &Vec<String>,
+    fq: &[String],
 ) -> Option<serde_json::Value> {
-    let url = sat_wrap7_build_url(q, rows, offs, fields, fq);
+    let url = SAT.search_url(&SearchQuery {
+        query: q.to_string(),
+        rows,
+        offset: offs,
+        fields: Some(fields.to_string()),
+        filters: fq.to_vec(),
+    });
     for _ in 0..2 {
-        if let Ok(r) = http_client().get(&url).send() {
+        if let Ok(r) = http::get(&url, SAT_TIMEOUT) {
             if r.status().is_success() {
                 if let Ok(txt) = r.text() {
                     if let Ok(json) = serde_json::from_str::<serde_json::Value>(&txt) {
@@ -132,13 +106,6 @@ fn sat_wrap7_ensure_fields(fields: &str, required: &[&str]) -> String {
     out.join(",")
 }
 
-pub(crate) fn sat_detail_build_url(useid: &str) -> String {
-    format!(
-        "https://21dzk.l.u-tokyo.ac.jp/SAT2018/satdb2018pre.php?mode=detail&ob=1&mode2=2&useid={}",
-        urlencoding::encode(useid)
-    )
-}
-
 pub(crate) fn title_score(title: &str, query: &str) -> f32 {
     let a = normalized(title);
     let b = normalized(query);
@@ -198,6 +165,7 @@ fn sat_candidate_order(docs: &[serde_json::Value], query: &str) -> Vec<(usize, &
     out
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn sat_search(
     query: &str,
     rows: usize,
@@ -233,7 +201,7 @@ pub fn sat_search(
                 }
                 let chosen = &docs[best_idx];
                 let useid = chosen.get("startid").and_then(|v| v.as_str()).unwrap_or("");
-                let url = sat_detail_build_url(useid);
+                let url = SAT.detail_url(useid);
                 let t = sat_fetch_cli(&url);
                 let start = start_char.unwrap_or(0);
                 let args = SliceArgs {
@@ -243,7 +211,8 @@ pub fn sat_search(
                     end_char: None,
                     max_chars,
                 };
-                let sliced = slice_text_cli(&t, &args);
+                let (sliced, total, returned_start, returned_end) =
+                    slice_text_cli_bounds(&t, &args);
                 if json {
                     let count = w
                         .get("response")
@@ -252,9 +221,10 @@ pub fn sat_search(
                         .unwrap_or(0);
                     let meta = serde_json::json!({
                         "totalLength": t.len(),
-                        "returnedStart": start,
-                        "returnedEnd": args.end_bound(t.len(), sliced.len()),
-                        "truncated": (sliced.len() as u64) < (t.len() as u64),
+                        "totalChars": total,
+                        "returnedStart": returned_start,
+                        "returnedEnd": returned_end,
+                        "truncated": returned_end < total,
                         "sourceUrl": url,
                         "extractionMethod": "sat-detail-extract",
                         "search": {"rows": rows, "offs": offs, "fl": fields, "fq": fq, "count": count},
@@ -327,7 +297,7 @@ pub fn sat_search(
     }
     let chosen = &docs[best_idx];
     let useid = chosen.get("startid").and_then(|v| v.as_str()).unwrap_or("");
-    let url = sat_detail_build_url(useid);
+    let url = SAT.detail_url(useid);
     let t = sat_fetch_cli(&url);
     let start = start_char.unwrap_or(0);
     let args = SliceArgs {
@@ -337,13 +307,14 @@ pub fn sat_search(
         end_char: None,
         max_chars,
     };
-    let sliced = slice_text_cli(&t, &args);
+    let (sliced, total, returned_start, returned_end) = slice_text_cli_bounds(&t, &args);
     if json {
         let meta = serde_json::json!({
             "totalLength": t.len(),
-            "returnedStart": start,
-            "returnedEnd": args.end_bound(t.len(), sliced.len()),
-            "truncated": (sliced.len() as u64) < (t.len() as u64),
+            "totalChars": total,
+            "returnedStart": returned_start,
+            "returnedEnd": returned_end,
+            "truncated": returned_end < total,
             "sourceUrl": url,
             "extractionMethod": "sat-detail-extract",
             "search": {"rows": rows, "offs": offs, "fl": fields, "fq": fq, "count": wrap.get("response").and_then(|r| r.get("numFound")).and_then(|x| x.as_u64()).unwrap_or(0)},
@@ -360,9 +331,9 @@ pub fn sat_search(
         eprintln!(
             "[meta] url={} total={} start={} returned={} chosen_title={} score={}",
             url,
-            t.len(),
-            start,
-            sliced.len(),
+            total,
+            returned_start,
+            returned_end - returned_start,
             chosen.get("fascnm").and_then(|v| v.as_str()).unwrap_or(""),
             best_sc
         );
@@ -378,7 +349,7 @@ pub fn sat_fetch(
     json: bool,
 ) -> anyhow::Result<()> {
     let url_final = if let Some(uid) = useid {
-        sat_detail_build_url(uid)
+        SAT.detail_url(uid)
     } else {
         url.cloned().unwrap_or_default()
     };
@@ -391,13 +362,14 @@ pub fn sat_fetch(
         end_char: None,
         max_chars,
     };
-    let sliced = slice_text_cli(&t, &args);
+    let (sliced, total, returned_start, returned_end) = slice_text_cli_bounds(&t, &args);
     if json {
         let meta = serde_json::json!({
             "totalLength": t.len(),
-            "returnedStart": start,
-            "returnedEnd": args.end_bound(t.len(), sliced.len()),
-            "truncated": (sliced.len() as u64) < (t.len() as u64),
+            "totalChars": total,
+            "returnedStart": returned_start,
+            "returnedEnd": returned_end,
+            "truncated": returned_end < total,
             "sourceUrl": url_final,
             "extractionMethod": "sat-detail-extract"
         });
@@ -418,7 +390,7 @@ pub fn sat_detail(
     max_chars: Option<usize>,
     _json: bool,
 ) -> anyhow::Result<()> {
-    let url = sat_detail_build_url(useid);
+    let url = SAT.detail_url(useid);
     let t = sat_fetch_cli(&url);
     let start = start_char.unwrap_or(0);
     let args = SliceArgs {
@@ -428,12 +400,13 @@ pub fn sat_detail(
         end_char: None,
         max_chars,
     };
-    let sliced = slice_text_cli(&t, &args);
+    let (sliced, total, returned_start, returned_end) = slice_text_cli_bounds(&t, &args);
     let meta = serde_json::json!({
         "totalLength": t.len(),
-        "returnedStart": start,
-        "returnedEnd": args.end_bound(t.len(), sliced.len()),
-        "truncated": (sliced.len() as u64) < (t.len() as u64),
+        "totalChars": total,
+        "returnedStart": returned_start,
+        "returnedEnd": returned_end,
+        "truncated": returned_end < total,
         "sourceUrl": url,
         "extractionMethod": "sat-detail-extract"
     });
@@ -445,6 +418,7 @@ pub fn sat_detail(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn sat_pipeline(
     query: &str,
     rows: usize,
@@ -484,7 +458,7 @@ pub fn sat_pipeline(
                 if useid.is_empty() {
                     continue;
                 }
-                let cand_url = sat_detail_build_url(useid);
+                let cand_url = SAT.detail_url(useid);
                 let cand_t = sat_fetch_cli(&cand_url);
                 if nq.is_empty() || normalized(&cand_t).contains(&nq) {
                     chosen_i = i;
@@ -532,7 +506,7 @@ pub fn sat_pipeline(
                 end_char: None,
                 max_chars,
             };
-            let sliced = slice_text_cli(&t, &args);
+            let (sliced, total, returned_start, returned_end) = slice_text_cli_bounds(&t, &args);
             if json {
                 let count = jsonv
                     .get("response")
@@ -541,9 +515,10 @@ pub fn sat_pipeline(
                     .unwrap_or(0);
                 let meta = serde_json::json!({
                     "totalLength": t.len(),
-                    "returnedStart": start,
-                    "returnedEnd": args.end_bound(t.len(), sliced.len()),
-                    "truncated": (sliced.len() as u64) < (t.len() as u64),
+                    "totalChars": total,
+                    "returnedStart": returned_start,
+                    "returnedEnd": returned_end,
+                    "truncated": returned_end < total,
                     "sourceUrl": url,
                     "extractionMethod": "sat-detail-extract",
                     "search": {"rows": rows, "offs": offs, "flRequested": fields, "flUsed": fields_used, "fq": fq, "count": count},
@@ -637,7 +612,7 @@ pub(crate) fn parse_sat_search_html(
         });
     }
     if titles_only {
-        let nq = normalized(&q);
+        let nq = normalized(q);
         let mut filtered: Vec<SatHit> = out
             .into_iter()
             .filter(|h| normalized(&h.title).contains(&nq))
@@ -666,7 +641,7 @@ pub(crate) fn sat_search_results_cli(
         base, urlencoding::encode(q), offs, rows);
     let mut backoff = 500u64;
     for _ in 0..3 {
-        if let Ok(r) = http_client().get(&url).send() {
+        if let Ok(r) = http::get(&url, SAT_TIMEOUT) {
             if let Ok(html) = r.text() {
                 return parse_sat_search_html(&html, q, rows, offs, exact, titles_only);
             }
@@ -0,0 +1,123 @@
+use crate::{
+    resolve_cbeta_path_cli, resolve_gretil_path_cli, resolve_muktabodha_path_cli,
+    resolve_sarit_path_cli, resolve_tipitaka_path,
+};
+use daizo_core::analysis::{
+    division_paths, plain_text_for_source, stats_for_paths, CorpusStats, FreqCounter, FreqItem,
+    StatsOptions,
+};
+use daizo_core::path_resolver::{
+    cbeta_root, gretil_root, muktabodha_root, sarit_root, tipitaka_root,
+};
+use std::path::PathBuf;
+
+pub fn corpus_stats(args: &crate::Commands) -> anyhow::Result<()> {
+    if let crate::Commands::Stats {
+        source,
+        id,
+        query,
+        part,
+        division,
+        top,
+        hapax_limit,
+        include_notes,
+        json,
+    } = args
+    {
+        let source = source.to_lowercase();
+        let opts = StatsOptions {
+            top: *top,
+            hapax_limit: *hapax_limit,
+        };
+        let (scope, stats) = if let Some(div) = division.as_deref() {
+            let root = match source.as_str() {
+                "cbeta" => cbeta_root(),
+                "tipitaka" => tipitaka_root(),
+                "gretil" => gretil_root(),
+                "sarit" => sarit_root(),
+                "muktabodha" => muktabodha_root(),
+                _ => anyhow::bail!("unknown source: {}", source),
+            };
+            let paths = division_paths(&source, &root, div);
+            if paths.is_empty() {
+                anyhow::bail!("no files found for division '{}' in {}", div, source);
+            }
+            (
+                format!("division {}", div),
+                stats_for_paths(&source, &paths, *include_notes, &opts),
+            )
+        } else {
+            let (id, query) = (id.as_deref(), query.as_deref());
+            let path: PathBuf = match source.as_str() {
+                "cbeta" => resolve_cbeta_path_cli(id, query),
+                "tipitaka" => resolve_tipitaka_path(id, query),
+                "gretil" => resolve_gretil_path_cli(id, query),
+                "sarit" => resolve_sarit_path_cli(id, query),
+                "muktabodha" => resolve_muktabodha_path_cli(id, query),
+                _ => anyhow::bail!("unknown source: {}", source),
+            };
+            if path.as_os_str().is_empty() || !path.exists() {
+                anyhow::bail!("text not found (specify --id, --query or --division)");
+            }
+            let part = if source == "cbeta" {
+                part.as_deref()
+            } else {
+                None
+            };
+            let Some(text) = plain_text_for_source(&source, &path, part, *include_notes) else {
+                anyhow::bail!("could not extract text from {}", path.display());
+            };
+            let mut counter = FreqCounter::new();
+            counter.add_text(&text);
+            let stem = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let scope = match part {
+                Some(p) => format!("{} juan {}", id.unwrap_or(&stem), p),
+                None => id.unwrap_or(&stem).to_string(),
+            };
+            (scope, counter.finish(&opts))
+        };
+        if *json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&serde_json::json!({
+                    "source": source,
+                    "scope": scope,
+                    "stats": stats,
+                }))?
+            );
+        } else {
+            print_stats(&scope, &source, &stats);
+        }
+    }
+    Ok(())
+}
+
+fn print_stats(scope: &str, source: &str, s: &CorpusStats) {
+    let list = |items: &[FreqItem]| {
+        items
+            .iter()
+            .map(|f| format!("{} {}", f.term, f.count))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    println!("{} ({}; texts: {})", scope, source, s.texts);
+    println!(
+        "chars:    {} total, {} distinct",
+        s.total_chars, s.distinct_chars
+    );
+    println!(
+        "tokens:   {} total, {} distinct",
+        s.total_tokens, s.distinct_tokens
+    );
+    println!("hapax:    {}", s.hapax_count);
+    println!("top chars:    {}", list(&s.top_chars));
+    println!("top tokens:   {}", list(&s.top_tokens));
+    println!("top bigrams:  {}", list(&s.top_bigrams));
+    println!("top trigrams: {}", list(&s.top_trigrams));
+    if !s.hapax.is_empty() {
+        println!("hapax list:   {}", s.hapax.join(" "));
+    }
+}
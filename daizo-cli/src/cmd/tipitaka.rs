@@ -86,7 +86,7 @@ pub fn tipitaka_fetch(args: &crate::Commands) -> anyhow::Result<()> {
         };
         if text.trim().is_empty() {
             if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-                if let Some(cand) = super::super::find_tipitaka_content_for_base(&stem) {
+                if let Some(cand) = super::super::find_tipitaka_content_for_base(stem) {
                     if cand != path {
                         let xml2 = std::fs::read(&cand)
                             .map(|b| decode_xml_bytes(&b))
@@ -175,6 +175,7 @@ pub fn tipitaka_fetch(args: &crate::Commands) -> anyhow::Result<()> {
                 "returnedEnd": slice.end_bound(text.len(), sliced.len()),
                 "truncated": (sliced.len() as u64) < (text.len() as u64),
                 "sourcePath": path.to_string_lossy(),
+                "license": daizo_core::license::license_for_path("tipitaka", &path),
                 "extractionMethod": if head_query.is_some() { "head-query" } else if head_index.is_some() { "head-index" } else { "full" },
                 "headingsTotal": heads.len(),
                 "headingsPreview": heads.clone().into_iter().take(*headings_limit).collect::<Vec<_>>(),
@@ -200,6 +201,7 @@ pub fn tipitaka_search(
     query: &str,
     max_results: usize,
     max_matches_per_file: usize,
+    fold_diacritics: bool,
     json: bool,
 ) -> anyhow::Result<()> {
     let looks_like_regex = query.chars().any(|c| ".+*?[](){}|\\".contains(c));
@@ -208,7 +210,11 @@ pub fn tipitaka_search(
     } else {
         query.to_string()
     };
-    let results = tipitaka_grep(&tipitaka_root(), &q, max_results, max_matches_per_file);
+    let results = if fold_diacritics {
+        daizo_core::tipitaka_grep_folded(&tipitaka_root(), &q, max_results, max_matches_per_file)
+    } else {
+        tipitaka_grep(&tipitaka_root(), &q, max_results, max_matches_per_file)
+    };
     if json {
         let meta = serde_json::json!({
             "searchPattern": q,
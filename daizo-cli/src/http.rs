@@ -0,0 +1,52 @@
+// ============ Remote HTTP ============
+//
+// Every request the CLI makes to a remote host (SAT, fixture downloads) goes through `get`,
+// which follows `daizo_core::politeness` like the server does: the host's robots.txt is read
+// once a day and obeyed, and requests to it are spaced and capped per minute.
+
+use daizo_core::politeness::{self, Policy};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+fn client() -> &'static reqwest::blocking::Client {
+    static CLIENT: OnceLock<reqwest::blocking::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::blocking::Client::builder()
+            .user_agent(Policy::current().user_agent)
+            .connect_timeout(Duration::from_secs(5))
+            .build()
+            .expect("reqwest client")
+    })
+}
+
+fn sleep_until(at: Instant) {
+    std::thread::sleep(at.saturating_duration_since(Instant::now()));
+}
+
+/// GET `url` within the politeness policy, giving up after `timeout`.
+pub(crate) fn get(url: &str, timeout: Duration) -> Result<reqwest::blocking::Response, String> {
+    let policy = Policy::current();
+    let gate = politeness::shared();
+    if let Some(robots) = gate.robots_due(url, &policy, Instant::now())? {
+        sleep_until(gate.reserve(&robots.host, &policy, Instant::now()));
+        let body = client()
+            .get(&robots.url)
+            .timeout(timeout)
+            .send()
+            .ok()
+            .filter(|r| r.status().is_success())
+            .and_then(|r| r.text().ok());
+        gate.set_robots(
+            &robots.host,
+            body.as_deref(),
+            &policy.user_agent,
+            Instant::now(),
+        );
+    }
+    sleep_until(gate.admit(url, &policy, Instant::now())?);
+    client()
+        .get(url)
+        .timeout(timeout)
+        .send()
+        .map_err(|e| e.to_string())
+}
@@ -5,6 +5,7 @@ use daizo_core::path_resolver::{
     resolve_muktabodha_by_id, resolve_muktabodha_path_direct, resolve_sarit_by_id,
     resolve_sarit_path_direct, resolve_tipitaka_by_id, sarit_root, tipitaka_root,
 };
+use daizo_core::progress::{Progress, Reporter};
 use daizo_core::text_utils::compute_match_score_sanskrit;
 use daizo_core::text_utils::{compute_match_score_precomputed, normalized, PrecomputedQuery};
 use daizo_core::{
@@ -14,9 +15,10 @@ use daizo_core::{
 use serde::Serialize;
 use std::env;
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+mod http;
 mod regex_utils;
 //
 
@@ -50,6 +52,10 @@ enum Commands {
         /// Override HOME/.daizo base
         #[arg(long)]
         base: Option<PathBuf>,
+        /// Check out only these CBETA canons (e.g. T,X); more can be added by running
+        /// init again. Default: the whole of xml-p5
+        #[arg(long, value_delimiter = ',')]
+        canons: Vec<String>,
     },
     /// Search GRETIL and optionally auto-fetch contexts or full text (pipeline)
     GretilPipeline {
@@ -375,6 +381,11 @@ enum Commands {
         #[arg(long, default_value_t = false)]
         json: bool,
     },
+    /// Contributor tools (test fixtures)
+    Dev {
+        #[command(subcommand)]
+        command: DevCommands,
+    },
     /// Print CLI version
     Version {},
     /// Diagnose install and data directories
@@ -382,6 +393,12 @@ enum Commands {
         /// Verbose output
         #[arg(long, default_value_t = false)]
         verbose: bool,
+        /// Check each canon of the git corpora against HEAD (file count + sample hashes)
+        #[arg(long, default_value_t = false)]
+        verify: bool,
+        /// With --verify: restore missing or damaged files and finish interrupted clones
+        #[arg(long, default_value_t = false)]
+        repair: bool,
     },
     /// Uninstall binaries from $DAIZO_DIR/bin (and optionally data/cache)
     Uninstall {
@@ -742,10 +759,249 @@ enum Commands {
         #[arg(long, default_value = "all")]
         source: String,
     },
-    /// Extract plain text from an XML file path (reads from stdin XML if --path omitted)
+    /// Register a shorthand name in the user alias file (DAIZO_ALIASES or ~/.daizo/aliases.toml)
+    AliasAdd {
+        /// Name to type in searches (e.g. 法華)
+        alias: String,
+        /// What it stands for: a work id or title; several are searched as alternatives
+        #[arg(required = true)]
+        expansions: Vec<String>,
+    },
+    /// Write plain-text copies of corpus files under ~/.daizo/cache/plain for searches to use
+    PlainBuild {
+        /// Source: cbeta | tipitaka | gretil | sarit | muktabodha | all
+        #[arg(long, default_value = "all")]
+        source: String,
+        /// Rewrite copies that are already up to date
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+    /// Pull the git corpora and update their indexes and cached searches for the changed files
+    DataUpdate {
+        /// Source: cbeta | tipitaka | sarit | all
+        #[arg(long, default_value = "all")]
+        source: String,
+    },
+    /// Per-tool call counts, latencies, output sizes and cache hit rates from the MCP
+    /// server's request log (recorded with DAIZO_METRICS=1)
+    Metrics {
+        /// Only this tool
+        #[arg(long)]
+        tool: Option<String>,
+        /// Output JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+        /// Delete the request log
+        #[arg(long, default_value_t = false)]
+        reset: bool,
+    },
+    /// Token/character frequency, bigram/trigram counts and hapax list for a text, juan or division
+    Stats {
+        /// Corpus: cbeta | tipitaka | gretil | sarit | muktabodha
+        #[arg(long, default_value = "cbeta")]
+        source: String,
+        /// Work id (e.g. T0262)
+        #[arg(long)]
+        id: Option<String>,
+        /// Title query (used when --id is omitted)
+        #[arg(long)]
+        query: Option<String>,
+        /// CBETA juan number (e.g. 001)
+        #[arg(long)]
+        part: Option<String>,
+        /// Whole division: CBETA canon/volume (T, T08) or file-stem prefix for other corpora
+        #[arg(long)]
+        division: Option<String>,
+        /// Entries per top-N list
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+        /// Maximum hapax legomena listed
+        #[arg(long, default_value_t = 50)]
+        hapax_limit: usize,
+        /// Include notes (CBETA)
+        #[arg(long, default_value_t = false)]
+        include_notes: bool,
+        /// Output JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Convert TEI XML to plain text or Markdown, streaming (reads stdin if --path is omitted or `-`)
     ExtractText {
         #[arg(long)]
         path: Option<PathBuf>,
+        /// Keep <note> text (inline `[注] ...`, footnotes in Markdown)
+        #[arg(long, default_value_t = false)]
+        include_notes: bool,
+        /// none (one line), paragraph (blank line between blocks) or lb (also a line per <lb/>);
+        /// Markdown defaults to paragraph
+        #[arg(long)]
+        line_breaks: Option<String>,
+        /// unicode, normal, composition, ref ([CB00178]) or drop
+        #[arg(long, default_value = "unicode")]
+        gaiji_mode: String,
+        /// text or markdown
+        #[arg(long, default_value = "text")]
+        format: String,
+    },
+    /// Write a whole work as text or Markdown files (one per CBETA juan) with YAML front matter
+    Export {
+        /// Corpus: cbeta | tipitaka | gretil | sarit | muktabodha
+        #[arg(long, default_value = "cbeta")]
+        source: String,
+        /// Work id (e.g. T0262; T0220 takes in every volume, T05n0220a one file)
+        #[arg(long)]
+        id: Option<String>,
+        /// Title query (used when --id is omitted)
+        #[arg(long)]
+        query: Option<String>,
+        /// md (Markdown) or text
+        #[arg(long, default_value = "md")]
+        format: String,
+        /// Output directory (created if missing; existing files are overwritten)
+        #[arg(long, default_value = ".")]
+        out: PathBuf,
+        /// Keep <note> text (inline `[注] ...`, footnotes in Markdown)
+        #[arg(long, default_value_t = false)]
+        include_notes: bool,
+        /// none, paragraph or lb (see extract-text)
+        #[arg(long)]
+        line_breaks: Option<String>,
+        /// unicode, normal, composition, ref ([CB00178]) or drop
+        #[arg(long, default_value = "unicode")]
+        gaiji_mode: String,
+    },
+    /// Build an EPUB 3 book from one or more works or a reading-list collection
+    Epub {
+        /// Corpus: cbeta | tipitaka | gretil | sarit | muktabodha
+        #[arg(long, default_value = "cbeta")]
+        source: String,
+        /// Work id; repeat for several works, each becoming a part of the book
+        #[arg(long)]
+        id: Vec<String>,
+        /// Title query (used when --id is omitted)
+        #[arg(long)]
+        query: Option<String>,
+        /// Reading-list collection (阿含部, T08, MN, ...; see reading_list)
+        #[arg(long)]
+        collection: Option<String>,
+        /// Maximum works taken from --collection
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Output file (default: <id>.epub in the current directory)
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Book title (default: the work title, or the collection name)
+        #[arg(long)]
+        title: Option<String>,
+        /// Leave out <note> text (kept as footnotes by default)
+        #[arg(long, default_value_t = false)]
+        no_notes: bool,
+        /// unicode, normal, composition, ref ([CB00178]) or drop
+        #[arg(long, default_value = "unicode")]
+        gaiji_mode: String,
+    },
+    /// Write a corpus as JSON Lines, one record per CBETA juan or per file (id, title, juan, text, chars)
+    Dump {
+        /// Corpus: cbeta | tipitaka | gretil | sarit | muktabodha
+        #[arg(long, default_value = "cbeta")]
+        source: String,
+        /// Canon, volume or collection to dump (T, T08, 阿含部; MN, or a file-stem prefix
+        /// elsewhere); default: the whole corpus
+        #[arg(long)]
+        canon: Option<String>,
+        /// Output format (jsonl)
+        #[arg(long, default_value = "jsonl")]
+        format: String,
+        /// Output file (default: stdout)
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Keep <note> text inline after `[注]`
+        #[arg(long, default_value_t = false)]
+        include_notes: bool,
+        /// unicode, normal, composition, ref ([CB00178]) or drop
+        #[arg(long, default_value = "unicode")]
+        gaiji_mode: String,
+    },
+    /// Character-level diff of two texts (e.g. --a T0235 --b T0236), aligned on unique runs
+    /// the two share; changes shown as [-deleted-]{+inserted+}
+    TextDiff {
+        /// Corpus of both texts: cbeta | tipitaka | gretil | sarit | muktabodha
+        #[arg(long, default_value = "cbeta")]
+        source: String,
+        /// Id of the first text
+        #[arg(long)]
+        a: String,
+        /// Id of the second text
+        #[arg(long)]
+        b: String,
+        /// Corpus of the second text when it differs from --source
+        #[arg(long)]
+        source_b: Option<String>,
+        /// CBETA juan of the first text (e.g. 001)
+        #[arg(long)]
+        part_a: Option<String>,
+        /// CBETA juan of the second text
+        #[arg(long)]
+        part_b: Option<String>,
+        /// Compare punctuation and whitespace too
+        #[arg(long, default_value_t = false)]
+        keep_punctuation: bool,
+        /// Treat variant characters (經/経) as different
+        #[arg(long, default_value_t = false)]
+        no_variants: bool,
+        /// Characters of unchanged text kept around each change
+        #[arg(long, default_value_t = 20)]
+        context: usize,
+        /// Output JSON (hunks with char offsets, anchors)
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Citation of a text or passage: Taishō style for CBETA, PTS volume and page for the
+    /// Tipiṭaka, the source edition for GRETIL, SARIT and Muktabodha
+    Cite {
+        /// Corpus: cbeta | tipitaka | gretil | sarit | muktabodha
+        #[arg(long, default_value = "cbeta")]
+        source: String,
+        /// Text id (e.g. T0262, s0201m.mul)
+        #[arg(long)]
+        id: String,
+        /// CBETA juan to cite (e.g. 001)
+        #[arg(long)]
+        part: Option<String>,
+        /// CBETA line to cite (e.g. 0001c14)
+        #[arg(long)]
+        lb: Option<String>,
+        /// XML line of the passage (Tipiṭaka: cites the PTS page it falls on)
+        #[arg(long)]
+        line_number: Option<usize>,
+        /// Canonical reference put before the page (e.g. "MN 10")
+        #[arg(long)]
+        reference: Option<String>,
+        /// plain | bibtex | csl-json | all
+        #[arg(long, default_value = "plain")]
+        format: String,
+    },
+    /// Embed the passages of a corpus for the `semantic_search` MCP tool (stored under
+    /// ~/.daizo/cache/embeddings; one index per corpus, replaced on each run)
+    EmbedIndex {
+        /// Corpus: cbeta | tipitaka | gretil | sarit | muktabodha
+        #[arg(long, default_value = "cbeta")]
+        source: String,
+        /// Canon, volume or collection to embed (T, T08, 阿含部; MN, or a file-stem prefix
+        /// elsewhere); default: the whole corpus
+        #[arg(long)]
+        collection: Option<String>,
+        /// lexical (local feature hashing: shared vocabulary, not meaning) or http (an
+        /// embedding model at the OpenAI-compatible endpoint DAIZO_EMBED_URL); default:
+        /// DAIZO_EMBED_BACKEND, then [embeddings] backend, then lexical
+        #[arg(long)]
+        backend: Option<String>,
+        /// Characters of text per passage
+        #[arg(long, default_value_t = daizo_core::embeddings::PASSAGE_CHARS)]
+        passage_chars: usize,
+        /// Passages per embedding request
+        #[arg(long, default_value_t = 64)]
+        batch: usize,
     },
     /// Search CBETA corpus (content-based)
     CbetaSearch {
@@ -758,6 +1014,12 @@ enum Commands {
         /// Maximum matches per file
         #[arg(long, default_value_t = 5)]
         max_matches_per_file: usize,
+        /// Match literal queries exactly, without expanding variant characters (观/觀/観)
+        #[arg(long, default_value_t = false)]
+        no_variants: bool,
+        /// Search only inside <note> content (editorial and variant notes)
+        #[arg(long, default_value_t = false)]
+        notes_only: bool,
         /// Output JSON
         #[arg(long, default_value_t = false)]
         json: bool,
@@ -773,26 +1035,40 @@ enum Commands {
         /// Maximum matches per file
         #[arg(long, default_value_t = 5)]
         max_matches_per_file: usize,
+        /// Ignore Pāli diacritics (panna matches paññā)
+        #[arg(long, default_value_t = false)]
+        fold_diacritics: bool,
         /// Output JSON
         #[arg(long, default_value_t = false)]
         json: bool,
     },
 }
 
+#[derive(Subcommand, Debug)]
+enum DevCommands {
+    /// Download a small curated subset of each corpus (gaiji, UTF-16, verse, notes) used by the test suite
+    FetchFixtures {
+        /// Target directory (default: $DAIZO_FIXTURES or fixtures/ in the workspace)
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        /// Only this corpus: cbeta | tipitaka | gretil
+        #[arg(long)]
+        corpus: Option<String>,
+        /// Download again even if the file exists
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+}
+
 #[derive(Serialize)]
 struct IndexResult<'a> {
     count: usize,
     out: &'a str,
 }
 
+/// Same directory the MCP server uses (see `daizo_core::path_resolver::daizo_home`).
 fn default_daizo() -> PathBuf {
-    if let Ok(p) = std::env::var("DAIZO_DIR") {
-        return PathBuf::from(p);
-    }
-    std::env::var_os("HOME")
-        .map(PathBuf::from)
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join(".daizo")
+    daizo_core::path_resolver::daizo_home()
 }
 
 fn ensure_dir(p: &PathBuf) -> anyhow::Result<()> {
@@ -887,12 +1163,48 @@ fn run(cmd: &str, args: &[&str], cwd: Option<&PathBuf>) -> bool {
     result
 }
 
+/// Files hashed per canon by `doctor --verify`.
+const DOCTOR_SAMPLES: usize = 5;
+
+/// Progress bar on stderr for clones and index builds; only drawn when stderr is a terminal.
+fn terminal_progress() -> Option<Reporter> {
+    if !std::io::stderr().is_terminal() {
+        return None;
+    }
+    Some(Reporter::new(|p: &Progress| {
+        let mut line = format!("\r\x1b[K[{}] ", p.task);
+        match p.total {
+            Some(t) if t > 0 => {
+                line.push_str(&format!("{:3}% ({}/{})", p.done * 100 / t, p.done, t))
+            }
+            _ => line.push_str(&p.done.to_string()),
+        }
+        if let Some(b) = p.bytes {
+            line.push_str(&format!(", {:.1} MiB", b as f64 / (1024.0 * 1024.0)));
+        }
+        if p.total == Some(p.done) {
+            line.push('\n');
+        }
+        let mut err = std::io::stderr().lock();
+        let _ = err.write_all(line.as_bytes());
+        let _ = err.flush();
+    }))
+}
+
 fn main() -> anyhow::Result<()> {
-    // Initialize optional repo policy from env (rate limits / future robots compliance)
+    // Initialize optional repo policy from env (pacing of git clones and fetches; HTTP
+    // requests follow daizo_core::politeness)
     daizo_core::repo::init_policy_from_env();
     let cli = Cli::parse();
+    match terminal_progress() {
+        Some(r) => r.scope(|| run_command(cli)),
+        None => run_command(cli),
+    }
+}
+
+fn run_command(cli: Cli) -> anyhow::Result<()> {
     match cli.command {
-        Commands::Init { base } => {
+        Commands::Init { base, canons } => {
             // Display startup message with colored output
             eprintln!("\x1b[33m📥 First-time setup requires downloading Buddhist texts. This may take several minutes... / 初回起動時はお経のダウンロードに時間がかかります。しばらくお待ちください... / 首次啟動需要下載佛經文本，可能需要幾分鐘時間...\x1b[0m");
 
@@ -900,9 +1212,30 @@ fn main() -> anyhow::Result<()> {
             ensure_dir(&base_dir)?;
             // ensure data via shared helpers
             let cbeta_dir = base_dir.join("xml-p5");
-            if !daizo_core::repo::ensure_cbeta_data_at(&cbeta_dir) {
+            let canons: Vec<String> = canons
+                .iter()
+                .map(|c| c.trim().to_uppercase())
+                .filter(|c| !c.is_empty())
+                .collect();
+            if let Some(bad) = canons.iter().find(|c| !daizo_core::repo::valid_canon(c)) {
+                anyhow::bail!("invalid canon '{}' (expected e.g. T, X, J)", bad);
+            }
+            let cbeta_ok = if canons.is_empty() {
+                daizo_core::repo::ensure_cbeta_data_at(&cbeta_dir)
+            } else if !cbeta_dir.exists() {
+                daizo_core::repo::clone_cbeta_sparse(&cbeta_dir, &canons)
+            } else {
+                if daizo_core::repo::sparse_dirs(&cbeta_dir).is_none() {
+                    eprintln!("[init] CBETA is a full checkout; every canon is already present");
+                }
+                daizo_core::repo::add_cbeta_canons(&cbeta_dir, &canons)
+            };
+            if !cbeta_ok {
                 anyhow::bail!("failed to ensure CBETA data");
             }
+            if let Some(dirs) = daizo_core::repo::sparse_dirs(&cbeta_dir) {
+                eprintln!("[init] CBETA canons checked out: {}", dirs.join(","));
+            }
             let tipitaka_dir = base_dir.join("tipitaka-xml");
             if !daizo_core::repo::ensure_tipitaka_data_at(&tipitaka_dir) {
                 anyhow::bail!("failed to ensure Tipitaka data");
@@ -932,6 +1265,9 @@ fn main() -> anyhow::Result<()> {
             fs::write(&cbeta_out, serde_json::to_vec(&cbeta_entries)?)?;
             fs::write(&tipitaka_out, serde_json::to_vec(&tipitaka_entries)?)?;
             fs::write(&sarit_out, serde_json::to_vec(&sarit_entries)?)?;
+            daizo_core::repo::record_index_revision(&cbeta_out, &cbeta_dir);
+            daizo_core::repo::record_index_revision(&tipitaka_out, &tipitaka_dir);
+            daizo_core::repo::record_index_revision(&sarit_out, &sarit_dir);
             println!(
                 "[init] cbeta-index: {} ({} entries)",
                 cbeta_out.to_string_lossy(),
@@ -1265,6 +1601,7 @@ fn main() -> anyhow::Result<()> {
                 fs::create_dir_all(parent)?;
             }
             fs::write(&outp, serde_json::to_vec(&entries)?)?;
+            daizo_core::repo::record_index_revision(&outp, &base);
             println!(
                 "{}",
                 serde_json::to_string(&IndexResult {
@@ -1291,6 +1628,7 @@ fn main() -> anyhow::Result<()> {
                 fs::create_dir_all(parent)?;
             }
             fs::write(&outp, serde_json::to_vec(&entries)?)?;
+            daizo_core::repo::record_index_revision(&outp, &base);
             println!(
                 "{}",
                 serde_json::to_string(&IndexResult {
@@ -1328,6 +1666,7 @@ fn main() -> anyhow::Result<()> {
                 fs::create_dir_all(parent)?;
             }
             fs::write(&outp, serde_json::to_vec(&entries)?)?;
+            daizo_core::repo::record_index_revision(&outp, &base);
             println!(
                 "{}",
                 serde_json::to_string(&IndexResult {
@@ -1348,6 +1687,7 @@ fn main() -> anyhow::Result<()> {
                 fs::create_dir_all(parent)?;
             }
             fs::write(&outp, serde_json::to_vec(&entries)?)?;
+            daizo_core::repo::record_index_revision(&outp, &base);
             println!(
                 "{}",
                 serde_json::to_string(&IndexResult {
@@ -1487,32 +1827,158 @@ fn main() -> anyhow::Result<()> {
             summary.insert("rebuilt".to_string(), serde_json::json!(rebuilt));
             println!("{}", serde_json::to_string(&summary)?);
         }
-        Commands::ExtractText { path } => {
-            let xml = if let Some(p) = path {
-                fs::read_to_string(p)?
+        Commands::AliasAdd { alias, expansions } => {
+            let (file, exp) = daizo_core::aliases::add_user_alias(&alias, expansions)
+                .map_err(|e| anyhow::anyhow!(e))?;
+            println!(
+                "{}",
+                serde_json::json!({
+                    "alias": exp.alias,
+                    "expansions": exp.expansions,
+                    "file": file,
+                })
+            );
+            eprintln!(
+                "[alias] a running server picks this up after daizo_aliases_reload or a restart"
+            );
+        }
+        Commands::PlainBuild { source, force } => {
+            let src = source.to_lowercase();
+            let corpora: [(&str, PathBuf); 5] = [
+                ("cbeta", cbeta_root()),
+                ("tipitaka", tipitaka_root()),
+                ("gretil", gretil_root()),
+                ("sarit", sarit_root()),
+                ("muktabodha", muktabodha_root()),
+            ];
+            if src != "all" && !corpora.iter().any(|(c, _)| *c == src) {
+                anyhow::bail!("unknown --source '{}'", source);
+            }
+            let mut summary = serde_json::Map::new();
+            for (corpus, root) in corpora.iter() {
+                if (src != "all" && src != *corpus) || !root.exists() {
+                    continue;
+                }
+                let paths = daizo_core::grep_paths(corpus, root);
+                let report = daizo_core::plain::build(&format!("plain {}", corpus), &paths, force);
+                summary.insert(corpus.to_string(), serde_json::to_value(&report)?);
+            }
+            // Cached search results were found in the XML; let the next searches use the copies.
+            let _ = daizo_core::grep_cache::GrepCache::new(
+                daizo_core::grep_cache::default_dir(),
+                daizo_core::grep_cache::DEFAULT_MAX_ENTRIES,
+            )
+            .clear();
+            summary.insert(
+                "dir".to_string(),
+                serde_json::json!(daizo_core::plain::default_dir()),
+            );
+            println!("{}", serde_json::to_string(&summary)?);
+        }
+        Commands::DataUpdate { source } => {
+            cmd_data::data_update(&source)?;
+        }
+        Commands::Metrics { tool, json, reset } => {
+            let dir = daizo_core::metrics::default_dir();
+            if reset {
+                daizo_core::metrics::clear(&dir)?;
+                eprintln!("[metrics] removed the request log under {}", dir.display());
+                return Ok(());
+            }
+            let mut records = daizo_core::metrics::read_log(&dir);
+            if let Some(t) = &tool {
+                records.retain(|r| &r.tool == t);
+            }
+            let summary = daizo_core::metrics::summarize(&records);
+            if json {
+                println!("{}", serde_json::to_string(&summary)?);
             } else {
-                let mut s = String::new();
-                io::stdin().read_to_string(&mut s)?;
-                s
+                print!("{}", daizo_core::metrics::render(&summary));
+            }
+        }
+        Commands::Stats { .. } => {
+            cmd_stats::corpus_stats(&cli.command)?;
+        }
+        Commands::Export { .. } => {
+            cmd_export::export(&cli.command)?;
+        }
+        Commands::Epub { .. } => {
+            cmd_export::epub(&cli.command)?;
+        }
+        Commands::Dump { .. } => {
+            cmd_export::dump(&cli.command)?;
+        }
+        Commands::TextDiff { .. } => {
+            cmd_diff::text_diff(&cli.command)?;
+        }
+        Commands::Cite { .. } => {
+            cmd_cite::cite(&cli.command)?;
+        }
+        Commands::EmbedIndex { .. } => {
+            cmd_embed::embed_index(&cli.command)?;
+        }
+        Commands::ExtractText {
+            path,
+            include_notes,
+            line_breaks,
+            gaiji_mode,
+            format,
+        } => {
+            use daizo_core::tei_text::{convert, GaijiMode, LineBreaks, TextFormat, TextOptions};
+            let opts = TextOptions {
+                include_notes,
+                line_breaks: match &line_breaks {
+                    Some(s) => LineBreaks::parse(s)
+                        .ok_or_else(|| anyhow::anyhow!("unknown --line-breaks '{}'", s))?,
+                    None => LineBreaks::None,
+                },
+                gaiji: GaijiMode::parse(&gaiji_mode)
+                    .ok_or_else(|| anyhow::anyhow!("unknown --gaiji-mode '{}'", gaiji_mode))?,
+                format: TextFormat::parse(&format)
+                    .ok_or_else(|| anyhow::anyhow!("unknown --format '{}'", format))?,
+            };
+            let out = io::BufWriter::new(io::stdout().lock());
+            let res = match path.as_deref().filter(|p| *p != Path::new("-")) {
+                Some(p) => convert(io::BufReader::new(fs::File::open(p)?), out, &opts),
+                None => convert(io::stdin().lock(), out, &opts),
             };
-            let t = extract_text(&xml);
-            println!("{}", t);
+            match res {
+                // The reader of the pipe went away (e.g. `| head`).
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+                r => r?,
+            }
         }
         Commands::CbetaSearch {
             query,
             max_results,
             max_matches_per_file,
+            no_variants,
+            notes_only,
             json,
         } => {
-            cmd_cbeta::cbeta_search(&query, max_results, max_matches_per_file, json)?;
+            cmd_cbeta::cbeta_search(
+                &query,
+                max_results,
+                max_matches_per_file,
+                no_variants,
+                notes_only,
+                json,
+            )?;
         }
         Commands::TipitakaSearch {
             query,
             max_results,
             max_matches_per_file,
+            fold_diacritics,
             json,
         } => {
-            cmd_tipitaka::tipitaka_search(&query, max_results, max_matches_per_file, json)?;
+            cmd_tipitaka::tipitaka_search(
+                &query,
+                max_results,
+                max_matches_per_file,
+                fold_diacritics,
+                json,
+            )?;
         }
         Commands::Update { git, yes } => {
             // Build the cargo install command (owned strings)
@@ -1549,10 +2015,19 @@ fn main() -> anyhow::Result<()> {
                 eprintln!("Use --git <repo-url> to install from GitHub; add --yes to execute.");
             }
         }
+        Commands::Dev { command } => match command {
+            DevCommands::FetchFixtures { dir, corpus, force } => {
+                cmd_dev::fetch_fixtures(dir, corpus.as_deref(), force)?;
+            }
+        },
         Commands::Version {} => {
             println!("daizo-cli {}", env!("CARGO_PKG_VERSION"));
         }
-        Commands::Doctor { verbose } => {
+        Commands::Doctor {
+            verbose,
+            verify,
+            repair,
+        } => {
             let base = default_daizo();
             let bin = base.join("bin");
             let cli = bin.join("daizo-cli");
@@ -1605,6 +2080,38 @@ fn main() -> anyhow::Result<()> {
                     "MISSING (will download on install)"
                 }
             );
+            println!("revisions:");
+            for (name, root) in [
+                ("cbeta", cbeta_root()),
+                ("tipitaka", tipitaka_root()),
+                ("gretil", gretil_root()),
+                ("sarit", sarit_root()),
+                ("muktabodha", muktabodha_root()),
+            ] {
+                let Some(rev) = daizo_core::repo::corpus_revision(&root) else {
+                    continue;
+                };
+                let index = cache_dir().join(format!("{}-index.json", name));
+                let index_state = if !index.exists() {
+                    "not built"
+                } else {
+                    match daizo_core::repo::index_is_current(&index, &root) {
+                        Some(true) => "current",
+                        Some(false) => "STALE (run index-rebuild)",
+                        None => "revision unknown",
+                    }
+                };
+                println!(
+                    " - {}: {} (cloned {}); index {}",
+                    name,
+                    rev.commit
+                        .as_deref()
+                        .map(|c| &c[..c.len().min(12)])
+                        .unwrap_or("no git checkout"),
+                    rev.cloned.as_deref().unwrap_or("?"),
+                    index_state
+                );
+            }
             println!(
                 "cache: {}",
                 if cache.exists() {
@@ -1627,6 +2134,70 @@ fn main() -> anyhow::Result<()> {
                     );
                 }
             }
+            if verify || repair {
+                println!("verify:");
+                let mut damaged = false;
+                for (name, root) in [
+                    ("cbeta", cbeta_root()),
+                    ("tipitaka", tipitaka_root()),
+                    ("sarit", sarit_root()),
+                ] {
+                    if !root.exists() {
+                        continue;
+                    }
+                    let mut checks = daizo_core::repo::verify_checkout(&root, DOCTOR_SAMPLES);
+                    let broken = checks
+                        .as_ref()
+                        .map(|c| c.iter().any(|c| !c.ok()))
+                        .unwrap_or(daizo_core::repo::is_incomplete_clone(&root));
+                    if broken && repair {
+                        println!(" - {}: repairing", name);
+                        daizo_core::repo::repair_checkout(&root);
+                        checks = daizo_core::repo::verify_checkout(&root, DOCTOR_SAMPLES);
+                    }
+                    let Some(checks) = checks else {
+                        if daizo_core::repo::is_incomplete_clone(&root) {
+                            damaged = true;
+                            println!(" - {}: INCOMPLETE clone (no commit checked out)", name);
+                        } else {
+                            println!(" - {}: not a git checkout; skipped", name);
+                        }
+                        continue;
+                    };
+                    let bad: Vec<_> = checks.iter().filter(|c| !c.ok()).collect();
+                    let files: usize = checks.iter().map(|c| c.present).sum();
+                    if bad.is_empty() {
+                        println!(" - {}: OK ({} dirs, {} files)", name, checks.len(), files);
+                        continue;
+                    }
+                    damaged = true;
+                    println!(
+                        " - {}: {} of {} dirs DAMAGED",
+                        name,
+                        bad.len(),
+                        checks.len()
+                    );
+                    for c in bad {
+                        println!(
+                            "   {}: {}/{} files, {} of {} sampled differ{}",
+                            c.canon,
+                            c.present,
+                            c.expected,
+                            c.mismatched.len(),
+                            c.sampled,
+                            c.mismatched
+                                .first()
+                                .map(|p| format!(" (e.g. {})", p))
+                                .unwrap_or_default()
+                        );
+                    }
+                }
+                if damaged && !repair {
+                    println!(
+                        "run `daizo-cli doctor --verify --repair` to restore the missing files"
+                    );
+                }
+            }
         }
         Commands::Uninstall { purge } => {
             let base = default_daizo();
@@ -1702,13 +2273,10 @@ fn topk_insert<'a>(
             }
         }
     }
-    let mut pos = top.len();
-    for i in 0..top.len() {
-        if scored_cmp(&cand, &top[i]) == std::cmp::Ordering::Less {
-            pos = i;
-            break;
-        }
-    }
+    let pos = top
+        .iter()
+        .position(|t| scored_cmp(&cand, t) == std::cmp::Ordering::Less)
+        .unwrap_or(top.len());
     if pos == top.len() {
         top.push(cand);
     } else {
@@ -1753,11 +2321,7 @@ pub(crate) fn load_or_build_tipitaka_index_cli() -> Vec<daizo_core::IndexEntry>
     if let Ok(b) = std::fs::read(&out) {
         if let Ok(mut v) = serde_json::from_slice::<Vec<daizo_core::IndexEntry>>(&b) {
             v.retain(|e| !e.path.ends_with(".toc.xml"));
-            let missing = v
-                .iter()
-                .take(10)
-                .filter(|e| !std::path::Path::new(&e.path).exists())
-                .count();
+            let fresh = daizo_core::repo::index_is_fresh(&out, &tipitaka_root(), &v);
             let lacks_meta = v.iter().take(10).any(|e| e.meta.is_none());
             let lacks_heads = v.iter().take(20).any(|e| {
                 e.meta
@@ -1769,7 +2333,7 @@ pub(crate) fn load_or_build_tipitaka_index_cli() -> Vec<daizo_core::IndexEntry>
                 e.meta
                     .as_ref()
                     .and_then(|m| m.get("indexVersion"))
-                    .map(|s| s.as_str() != "tipitaka_index_v2")
+                    .map(|s| s.as_str() != "tipitaka_index_v4")
                     .unwrap_or(true)
             });
             let lacks_composite = v.iter().take(50).any(|e| {
@@ -1782,7 +2346,7 @@ pub(crate) fn load_or_build_tipitaka_index_cli() -> Vec<daizo_core::IndexEntry>
                 false
             });
             if !v.is_empty()
-                && missing == 0
+                && fresh
                 && !lacks_meta
                 && !lacks_heads
                 && !lacks_ver
@@ -1796,6 +2360,7 @@ pub(crate) fn load_or_build_tipitaka_index_cli() -> Vec<daizo_core::IndexEntry>
     entries.retain(|e| !e.path.ends_with(".toc.xml"));
     let _ = std::fs::create_dir_all(cache_dir());
     let _ = std::fs::write(&out, serde_json::to_vec(&entries).unwrap_or_default());
+    daizo_core::repo::record_index_revision(&out, &tipitaka_root());
     entries
 }
 
@@ -1803,12 +2368,8 @@ pub(crate) fn load_or_build_cbeta_index_cli() -> Vec<daizo_core::IndexEntry> {
     let out = cache_dir().join("cbeta-index.json");
     if let Ok(b) = std::fs::read(&out) {
         if let Ok(v) = serde_json::from_slice::<Vec<daizo_core::IndexEntry>>(&b) {
-            let missing = v
-                .iter()
-                .take(10)
-                .filter(|e| !std::path::Path::new(&e.path).exists())
-                .count();
-            if !v.is_empty() && missing == 0 {
+            let fresh = daizo_core::repo::index_is_fresh(&out, &cbeta_root(), &v);
+            if !v.is_empty() && fresh {
                 return v;
             }
         }
@@ -1816,6 +2377,7 @@ pub(crate) fn load_or_build_cbeta_index_cli() -> Vec<daizo_core::IndexEntry> {
     let entries = build_index(&cbeta_root(), None);
     let _ = std::fs::create_dir_all(cache_dir());
     let _ = std::fs::write(&out, serde_json::to_vec(&entries).unwrap_or_default());
+    daizo_core::repo::record_index_revision(&out, &cbeta_root());
     entries
 }
 
@@ -1823,12 +2385,17 @@ pub(crate) fn load_or_build_gretil_index_cli() -> Vec<daizo_core::IndexEntry> {
     let out = cache_dir().join("gretil-index.json");
     if let Ok(b) = std::fs::read(&out) {
         if let Ok(v) = serde_json::from_slice::<Vec<daizo_core::IndexEntry>>(&b) {
-            let missing = v
-                .iter()
-                .take(10)
-                .filter(|e| !std::path::Path::new(&e.path).exists())
-                .count();
-            if !v.is_empty() && missing == 0 {
+            let fresh = daizo_core::repo::index_is_fresh(&out, &gretil_root(), &v);
+            // v2 added duplicate groups (dupGroup/dupMembers), v3 text sizes, v4 language/genre/
+            // source/creationDate; older indexes are rebuilt.
+            let lacks_ver = v.iter().take(10).any(|e| {
+                e.meta
+                    .as_ref()
+                    .and_then(|m| m.get("indexVersion"))
+                    .map(|s| s.as_str() != "gretil_index_v4")
+                    .unwrap_or(true)
+            });
+            if !v.is_empty() && fresh && !lacks_ver {
                 return v;
             }
         }
@@ -1836,6 +2403,7 @@ pub(crate) fn load_or_build_gretil_index_cli() -> Vec<daizo_core::IndexEntry> {
     let entries = build_gretil_index(&gretil_root());
     let _ = std::fs::create_dir_all(cache_dir());
     let _ = std::fs::write(&out, serde_json::to_vec(&entries).unwrap_or_default());
+    daizo_core::repo::record_index_revision(&out, &gretil_root());
     entries
 }
 
@@ -1843,12 +2411,8 @@ pub(crate) fn load_or_build_sarit_index_cli() -> Vec<daizo_core::IndexEntry> {
     let out = cache_dir().join("sarit-index.json");
     if let Ok(b) = std::fs::read(&out) {
         if let Ok(v) = serde_json::from_slice::<Vec<daizo_core::IndexEntry>>(&b) {
-            let missing = v
-                .iter()
-                .take(10)
-                .filter(|e| !std::path::Path::new(&e.path).exists())
-                .count();
-            if !v.is_empty() && missing == 0 {
+            let fresh = daizo_core::repo::index_is_fresh(&out, &sarit_root(), &v);
+            if !v.is_empty() && fresh {
                 return v;
             }
         }
@@ -1856,6 +2420,7 @@ pub(crate) fn load_or_build_sarit_index_cli() -> Vec<daizo_core::IndexEntry> {
     let entries = build_sarit_index(&sarit_root());
     let _ = std::fs::create_dir_all(cache_dir());
     let _ = std::fs::write(&out, serde_json::to_vec(&entries).unwrap_or_default());
+    daizo_core::repo::record_index_revision(&out, &sarit_root());
     entries
 }
 
@@ -1863,12 +2428,8 @@ pub(crate) fn load_or_build_muktabodha_index_cli() -> Vec<daizo_core::IndexEntry
     let out = cache_dir().join("muktabodha-index.json");
     if let Ok(b) = std::fs::read(&out) {
         if let Ok(v) = serde_json::from_slice::<Vec<daizo_core::IndexEntry>>(&b) {
-            let missing = v
-                .iter()
-                .take(10)
-                .filter(|e| !std::path::Path::new(&e.path).exists())
-                .count();
-            if !v.is_empty() && missing == 0 {
+            let fresh = daizo_core::repo::index_is_fresh(&out, &muktabodha_root(), &v);
+            if !v.is_empty() && fresh {
                 return v;
             }
         }
@@ -1876,6 +2437,7 @@ pub(crate) fn load_or_build_muktabodha_index_cli() -> Vec<daizo_core::IndexEntry
     let entries = build_muktabodha_index(&muktabodha_root());
     let _ = std::fs::create_dir_all(cache_dir());
     let _ = std::fs::write(&out, serde_json::to_vec(&entries).unwrap_or_default());
+    daizo_core::repo::record_index_revision(&out, &muktabodha_root());
     entries
 }
 
@@ -2086,40 +2648,23 @@ impl SliceArgs {
     }
 }
 pub(crate) fn slice_text_cli(text: &str, args: &SliceArgs) -> String {
-    let default_max = 8000usize;
-    // Treat indices as character positions, not bytes
-    let total_chars = text.chars().count();
-    let start_char = std::cmp::min(args.start().unwrap_or(0), total_chars);
-    let end_char = if let (Some(p), Some(ps)) = (args.page, args.page_size) {
-        Some(p * ps + ps)
-    } else if let Some(e) = args.end_char {
-        Some(e)
-    } else if let Some(mc) = args.max_chars {
-        Some(start_char + mc)
-    } else {
-        None
+    slice_text_cli_bounds(text, args).0
+}
+
+/// Like [`slice_text_cli`], with the char counts `_meta` reports: the slice, the text's total
+/// chars and the slice's start and end char offsets.
+pub(crate) fn slice_text_cli_bounds(text: &str, args: &SliceArgs) -> (String, usize, usize, usize) {
+    // The CLI has no output budget.
+    let opts = daizo_core::fetch::FetchOptions {
+        start_char: args.start_char,
+        end_char: args.end_char,
+        page: args.page.zip(args.page_size),
+        max_chars: args.max_chars,
+        budget: usize::MAX,
+        ..Default::default()
     };
-    let end_char = end_char
-        .map(|e| std::cmp::min(e, total_chars))
-        .unwrap_or_else(|| std::cmp::min(start_char + default_max, total_chars));
-    if start_char >= end_char {
-        return String::new();
-    }
-    // Convert char indices to byte indices
-    let s_byte = text
-        .char_indices()
-        .nth(start_char)
-        .map(|(b, _)| b)
-        .unwrap_or(text.len());
-    let e_byte = text
-        .char_indices()
-        .nth(end_char)
-        .map(|(b, _)| b)
-        .unwrap_or(text.len());
-    if s_byte > e_byte {
-        return String::new();
-    }
-    text[s_byte..e_byte].to_string()
+    let span = daizo_core::fetch::slice(text, &opts);
+    (span.text, span.total, span.start, span.end)
 }
 
 pub(crate) fn decode_xml_bytes(bytes: &[u8]) -> String {
@@ -2208,6 +2753,7 @@ pub(crate) fn sniff_xml_encoding(head: &[u8]) -> Option<String> {
 //
 mod cmd;
 use cmd::{
-    cbeta as cmd_cbeta, gretil as cmd_gretil, muktabodha as cmd_muktabodha, sarit as cmd_sarit,
-    tipitaka as cmd_tipitaka,
+    cbeta as cmd_cbeta, cite as cmd_cite, data as cmd_data, dev as cmd_dev, diff as cmd_diff,
+    embed as cmd_embed, export as cmd_export, gretil as cmd_gretil, muktabodha as cmd_muktabodha,
+    sarit as cmd_sarit, stats as cmd_stats, tipitaka as cmd_tipitaka,
 };
@@ -0,0 +1,440 @@
+use crate::path_resolver::daizo_home;
+use crate::text_utils::ws_cjk_variant_fuzzy_regex_literal;
+use crate::IndexEntry;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use unicode_normalization::UnicodeNormalization;
+
+/// Names of well-known works, by corpus and work (`data/work_aliases.toml`).
+const WORK_ALIASES: &str = include_str!("../data/work_aliases.toml");
+
+/// User-defined abbreviations (syllabus shorthand, lab sigla) mapped to one or more expansions.
+/// Keys are matched case-insensitively against the whole query.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AliasTable {
+    entries: BTreeMap<String, (String, Vec<String>)>,
+    pub source: Option<PathBuf>,
+}
+
+/// Result of expanding a query through the alias table.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AliasExpansion {
+    pub alias: String,
+    pub expansions: Vec<String>,
+}
+
+impl AliasExpansion {
+    /// The first expansion, used where only a single query string makes sense (title search, online APIs).
+    pub fn canonical(&self) -> &str {
+        self.expansions
+            .first()
+            .map(|s| s.as_str())
+            .unwrap_or(&self.alias)
+    }
+
+    /// Regex alternation matching the alias itself and every expansion, each as a fuzzy literal.
+    pub fn regex_alternation(&self) -> String {
+        let mut parts: Vec<String> = Vec::with_capacity(self.expansions.len() + 1);
+        for t in std::iter::once(&self.alias).chain(self.expansions.iter()) {
+            let p = ws_cjk_variant_fuzzy_regex_literal(t);
+            if !parts.contains(&p) {
+                parts.push(p);
+            }
+        }
+        format!("(?:{})", parts.join("|"))
+    }
+}
+
+fn alias_key(s: &str) -> String {
+    s.trim().nfc().collect::<String>().to_lowercase()
+}
+
+impl AliasTable {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn insert(&mut self, alias: &str, expansions: Vec<String>) {
+        let alias = alias.trim();
+        let exps: Vec<String> = expansions
+            .into_iter()
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty() && e != alias)
+            .collect();
+        if alias.is_empty() || exps.is_empty() {
+            return;
+        }
+        let entry = self
+            .entries
+            .entry(alias_key(alias))
+            .or_insert_with(|| (alias.to_string(), Vec::new()));
+        for e in exps {
+            if !entry.1.contains(&e) {
+                entry.1.push(e);
+            }
+        }
+    }
+
+    /// Expansion for a whole query, if the query is a known alias.
+    pub fn expand(&self, query: &str) -> Option<AliasExpansion> {
+        self.entries
+            .get(&alias_key(query))
+            .map(|(alias, exps)| AliasExpansion {
+                alias: alias.clone(),
+                expansions: exps.clone(),
+            })
+    }
+
+    /// Alias → expansions, in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.entries
+            .values()
+            .map(|(a, e)| (a.as_str(), e.as_slice()))
+    }
+
+    /// Parse TOML: either top-level `alias = "..." | ["...", ...]` pairs or the same under `[aliases]`.
+    pub fn from_toml_str(s: &str) -> Result<Self, String> {
+        let v: toml::Table = s.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        let table = match v.get("aliases") {
+            Some(toml::Value::Table(t)) => t.clone(),
+            _ => v,
+        };
+        let mut out = AliasTable::default();
+        for (k, val) in table.iter() {
+            let exps: Vec<String> = match val {
+                toml::Value::String(s) => vec![s.clone()],
+                toml::Value::Array(a) => a
+                    .iter()
+                    .filter_map(|x| x.as_str().map(|s| s.to_string()))
+                    .collect(),
+                _ => continue,
+            };
+            out.insert(k, exps);
+        }
+        Ok(out)
+    }
+
+    /// Parse the flat YAML subset used for alias files:
+    /// `alias: value`, `alias: [a, b]`, or `alias:` followed by `- item` lines.
+    /// An optional top-level `aliases:` key is accepted as a wrapper.
+    pub fn from_yaml_str(s: &str) -> Result<Self, String> {
+        fn unquote(s: &str) -> String {
+            let t = s.trim();
+            if t.len() >= 2
+                && ((t.starts_with('"') && t.ends_with('"'))
+                    || (t.starts_with('\'') && t.ends_with('\'')))
+            {
+                t[1..t.len() - 1].to_string()
+            } else {
+                t.to_string()
+            }
+        }
+        fn strip_comment(line: &str) -> &str {
+            match line.find(" #") {
+                Some(i) => &line[..i],
+                None if line.trim_start().starts_with('#') => "",
+                None => line,
+            }
+        }
+        let mut out = AliasTable::default();
+        let mut pending: Option<(String, Vec<String>)> = None;
+        for (ln, raw) in s.lines().enumerate() {
+            let line = strip_comment(raw).trim_end();
+            let t = line.trim();
+            if t.is_empty() || t == "---" {
+                continue;
+            }
+            if let Some(item) = t.strip_prefix("- ") {
+                match pending.as_mut() {
+                    Some((_, v)) => v.push(unquote(item)),
+                    None => return Err(format!("line {}: list item without key", ln + 1)),
+                }
+                continue;
+            }
+            let Some((k, v)) = t.split_once(':') else {
+                return Err(format!("line {}: expected 'alias: expansion'", ln + 1));
+            };
+            if let Some((pk, pv)) = pending.take() {
+                out.insert(&pk, pv);
+            }
+            let key = unquote(k);
+            let v = v.trim();
+            if key == "aliases" && v.is_empty() && !line.starts_with(char::is_whitespace) {
+                continue;
+            }
+            if v.is_empty() {
+                pending = Some((key, Vec::new()));
+            } else if let Some(inner) = v.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+                out.insert(&key, inner.split(',').map(unquote).collect());
+            } else {
+                out.insert(&key, vec![unquote(v)]);
+            }
+        }
+        if let Some((pk, pv)) = pending.take() {
+            out.insert(&pk, pv);
+        }
+        Ok(out)
+    }
+
+    /// Top-level `"alias" = ["expansion", …]` lines, readable by [`Self::from_toml_str`].
+    pub fn to_toml_string(&self) -> String {
+        self.iter()
+            .map(|(a, exps)| {
+                let mut t = toml::Table::new();
+                t.insert(a.to_string(), toml::Value::from(exps.to_vec()));
+                toml::to_string(&t).unwrap_or_default()
+            })
+            .collect()
+    }
+
+    /// `alias:` followed by `- expansion` lines, readable by [`Self::from_yaml_str`].
+    pub fn to_yaml_string(&self) -> String {
+        // Quoted without escapes, as from_yaml_str only strips the quotes.
+        let mut out = String::new();
+        for (a, exps) in self.iter() {
+            out.push_str(&format!("'{}':\n", a));
+            for e in exps {
+                out.push_str(&format!("  - '{}'\n", e));
+            }
+        }
+        out
+    }
+
+    /// Load an alias file; the format is chosen by extension (.toml, .yaml/.yml).
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let s = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let mut t = match ext.as_str() {
+            "yaml" | "yml" => Self::from_yaml_str(&s),
+            _ => Self::from_toml_str(&s),
+        }
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+        t.source = Some(path.to_path_buf());
+        Ok(t)
+    }
+}
+
+/// The shipped names for the works of `corpus`: work key → names.
+pub fn work_aliases(corpus: &str) -> Option<&'static BTreeMap<String, Vec<String>>> {
+    static TABLE: OnceLock<BTreeMap<String, BTreeMap<String, Vec<String>>>> = OnceLock::new();
+    TABLE
+        .get_or_init(|| {
+            let t: toml::Table = WORK_ALIASES.parse().unwrap_or_default();
+            t.into_iter()
+                .filter_map(|(corpus, works)| {
+                    let works = works
+                        .as_table()?
+                        .iter()
+                        .map(|(k, v)| {
+                            let names = v
+                                .as_array()
+                                .map(|a| {
+                                    a.iter()
+                                        .filter_map(|x| x.as_str().map(str::to_string))
+                                        .collect()
+                                })
+                                .unwrap_or_default();
+                            (k.to_ascii_uppercase(), names)
+                        })
+                        .collect();
+                    Some((corpus, works))
+                })
+                .collect()
+        })
+        .get(corpus)
+}
+
+/// Key of `e` in the shipped table: `T0262` for CBETA (canon and number, without the part
+/// letter of `0220a`), the file stem for the Tipiṭaka.
+fn work_key(corpus: &str, e: &IndexEntry) -> Option<String> {
+    match corpus {
+        "cbeta" => {
+            let meta = e.meta.as_ref()?;
+            let nnum = meta
+                .get("nnum")?
+                .trim_end_matches(|c: char| c.is_alphabetic());
+            Some(format!("{}{}", meta.get("canon")?, nnum).to_ascii_uppercase())
+        }
+        _ => Some(e.id.to_ascii_uppercase()),
+    }
+}
+
+/// Append the shipped names of each work to its `alias` meta field (space-separated, like
+/// the aliases the Tipiṭaka index derives itself).
+pub fn merge_work_aliases(corpus: &str, entries: &mut [IndexEntry]) {
+    let Some(table) = work_aliases(corpus) else {
+        return;
+    };
+    for e in entries.iter_mut() {
+        let Some(names) = work_key(corpus, e).and_then(|k| table.get(&k)) else {
+            continue;
+        };
+        let meta = e.meta.get_or_insert_with(BTreeMap::new);
+        let alias = meta.entry("alias".to_string()).or_default();
+        for n in names {
+            if !alias.is_empty() {
+                alias.push(' ');
+            }
+            alias.push_str(n);
+        }
+    }
+}
+
+/// Alias file location: `DAIZO_ALIASES` if set, else the first of
+/// `~/.daizo/aliases.toml`, `aliases.yaml`, `aliases.yml` that exists.
+pub fn aliases_path() -> Option<PathBuf> {
+    if let Ok(p) = std::env::var("DAIZO_ALIASES") {
+        if !p.trim().is_empty() {
+            return Some(PathBuf::from(p));
+        }
+    }
+    let home = daizo_home();
+    ["aliases.toml", "aliases.yaml", "aliases.yml"]
+        .iter()
+        .map(|n| home.join(n))
+        .find(|p| p.exists())
+}
+
+/// Register `alias` in the user alias file, creating `~/.daizo/aliases.toml` when there is
+/// none. A new alias is appended so the file keeps its comments; more expansions for an
+/// existing one rewrite the file. Returns the file and the alias as it now expands.
+pub fn add_user_alias(
+    alias: &str,
+    expansions: Vec<String>,
+) -> Result<(PathBuf, AliasExpansion), String> {
+    let path = aliases_path().unwrap_or_else(|| daizo_home().join("aliases.toml"));
+    let mut table = if path.exists() {
+        AliasTable::load(&path)?
+    } else {
+        AliasTable::default()
+    };
+    let existed = table.expand(alias).is_some();
+    table.insert(alias, expansions.clone());
+    let Some(exp) = table.expand(alias) else {
+        return Err("an alias needs a name and at least one expansion".to_string());
+    };
+    let yaml = matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("yaml" | "yml")
+    );
+    let io = |e: std::io::Error| format!("{}: {}", path.display(), e);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(io)?;
+    }
+    if existed {
+        let text = if yaml {
+            table.to_yaml_string()
+        } else {
+            table.to_toml_string()
+        };
+        std::fs::write(&path, text).map_err(io)?;
+    } else {
+        let mut one = AliasTable::default();
+        one.insert(alias, expansions);
+        let line = if yaml {
+            one.to_yaml_string()
+        } else {
+            one.to_toml_string()
+        };
+        let mut f = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(io)?;
+        f.write_all(line.as_bytes()).map_err(io)?;
+    }
+    Ok((path, exp))
+}
+
+/// Load the user alias file, returning an empty table when none is configured.
+pub fn load_user_aliases() -> Result<AliasTable, String> {
+    match aliases_path() {
+        Some(p) if p.exists() => AliasTable::load(&p),
+        Some(p) => Err(format!("alias file not found: {}", p.display())),
+        None => Ok(AliasTable::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml_and_yaml_forms() {
+        let t = AliasTable::from_toml_str(
+            "[aliases]\nHS = \"般若波羅蜜多心經\"\nMMK = [\"Mūlamadhyamakakārikā\", \"中論\"]\n",
+        )
+        .unwrap();
+        assert_eq!(t.len(), 2);
+        let e = t.expand(" mmk ").unwrap();
+        assert_eq!(e.alias, "MMK");
+        assert_eq!(e.canonical(), "Mūlamadhyamakakārikā");
+
+        let y = AliasTable::from_yaml_str(
+            "# syllabus\naliases:\n  HS: 般若波羅蜜多心經\n  Lotus:\n    - 妙法蓮華經\n    - 'Saddharmapuṇḍarīka'\n  MMK: [中論, 中頌]\n",
+        )
+        .unwrap();
+        assert_eq!(y.len(), 3);
+        assert_eq!(
+            y.expand("lotus").unwrap().expansions,
+            vec!["妙法蓮華經", "Saddharmapuṇḍarīka"]
+        );
+        assert_eq!(y.expand("MMK").unwrap().expansions, vec!["中論", "中頌"]);
+        assert!(y.expand("unknown").is_none());
+    }
+
+    #[test]
+    fn written_tables_read_back_and_work_aliases_merge() {
+        let mut t = AliasTable::default();
+        t.insert(
+            "Lotus, big",
+            vec!["妙法蓮華經".into(), "Saddharma \"puṇḍarīka\"".into()],
+        );
+        for back in [
+            AliasTable::from_toml_str(&t.to_toml_string()).unwrap(),
+            AliasTable::from_yaml_str(&t.to_yaml_string()).unwrap(),
+        ] {
+            assert_eq!(
+                back.expand("lotus, big").map(|e| e.expansions),
+                t.expand("lotus, big").map(|e| e.expansions)
+            );
+        }
+
+        let entry = |id: &str, nnum: &str| IndexEntry {
+            id: id.to_string(),
+            title: String::new(),
+            path: String::new(),
+            meta: Some(
+                [("canon", "T"), ("nnum", nnum)]
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            ),
+        };
+        let mut idx = vec![entry("T09n0262", "0262"), entry("T07n0220o", "0220o")];
+        merge_work_aliases("cbeta", &mut idx);
+        let alias = |i: usize| idx[i].meta.as_ref().unwrap()["alias"].clone();
+        assert!(alias(0).contains("Lotus Sutra") && alias(0).contains("法華経"));
+        assert!(alias(1).contains("大般若経"));
+    }
+
+    #[test]
+    fn alternation_covers_alias_and_expansions() {
+        let mut t = AliasTable::default();
+        t.insert("HS", vec!["心經".into()]);
+        let re = regex::Regex::new(&t.expand("HS").unwrap().regex_alternation()).unwrap();
+        assert!(re.is_match("般若心經"));
+        assert!(re.is_match("HS"));
+    }
+}
@@ -0,0 +1,319 @@
+use crate::{
+    collect_xml_paths, extract_cbeta_juan_plain, extract_cbeta_plain_from_xml, extract_text,
+};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Limits applied when turning raw counts into a report.
+#[derive(Clone, Debug)]
+pub struct StatsOptions {
+    /// Number of entries kept in each top-N list.
+    pub top: usize,
+    /// Maximum number of hapax legomena listed (the full count is always reported).
+    pub hapax_limit: usize,
+}
+
+impl Default for StatsOptions {
+    fn default() -> Self {
+        Self {
+            top: 20,
+            hapax_limit: 50,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FreqItem {
+    pub term: String,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CorpusStats {
+    pub texts: usize,
+    pub total_chars: usize,
+    pub total_tokens: usize,
+    pub distinct_chars: usize,
+    pub distinct_tokens: usize,
+    pub top_chars: Vec<FreqItem>,
+    pub top_tokens: Vec<FreqItem>,
+    pub top_bigrams: Vec<FreqItem>,
+    pub top_trigrams: Vec<FreqItem>,
+    pub hapax_count: usize,
+    pub hapax: Vec<String>,
+}
+
+fn is_cjk_ideograph(c: char) -> bool {
+    matches!(c as u32,
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x2FA1F)
+}
+
+/// Split text into token segments. CJK ideographs are single-character tokens;
+/// other scripts are split into lowercase words (letters, digits and combining marks).
+/// Punctuation and line breaks end a segment so that n-grams do not span them.
+pub fn tokenize_segments(text: &str) -> Vec<Vec<String>> {
+    let mut segs: Vec<Vec<String>> = Vec::new();
+    let mut cur: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let flush_word = |word: &mut String, cur: &mut Vec<String>| {
+        if !word.is_empty() {
+            cur.push(word.to_lowercase());
+            word.clear();
+        }
+    };
+    for c in text.chars() {
+        if is_cjk_ideograph(c) {
+            flush_word(&mut word, &mut cur);
+            cur.push(c.to_string());
+        } else if c.is_alphanumeric() || unicode_normalization::char::is_combining_mark(c) {
+            word.push(c);
+        } else if c.is_whitespace() && c != '\n' {
+            flush_word(&mut word, &mut cur);
+        } else {
+            flush_word(&mut word, &mut cur);
+            if !cur.is_empty() {
+                segs.push(std::mem::take(&mut cur));
+            }
+        }
+    }
+    flush_word(&mut word, &mut cur);
+    if !cur.is_empty() {
+        segs.push(cur);
+    }
+    segs
+}
+
+fn join_gram(toks: &[String]) -> String {
+    let cjk = toks
+        .iter()
+        .all(|t| t.chars().count() == 1 && t.chars().all(is_cjk_ideograph));
+    if cjk {
+        toks.concat()
+    } else {
+        toks.join(" ")
+    }
+}
+
+/// Mergeable frequency counts; feed texts with `add_text` and call `finish` for a report.
+#[derive(Clone, Debug, Default)]
+pub struct FreqCounter {
+    texts: usize,
+    total_chars: usize,
+    total_tokens: usize,
+    chars: HashMap<char, usize>,
+    tokens: HashMap<String, usize>,
+    bigrams: HashMap<String, usize>,
+    trigrams: HashMap<String, usize>,
+}
+
+impl FreqCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_text(&mut self, text: &str) {
+        self.texts += 1;
+        for c in text.chars() {
+            if c.is_alphanumeric() {
+                self.total_chars += 1;
+                *self.chars.entry(c).or_default() += 1;
+            }
+        }
+        for seg in tokenize_segments(text) {
+            self.total_tokens += seg.len();
+            for (i, t) in seg.iter().enumerate() {
+                *self.tokens.entry(t.clone()).or_default() += 1;
+                if i + 2 <= seg.len() {
+                    *self.bigrams.entry(join_gram(&seg[i..i + 2])).or_default() += 1;
+                }
+                if i + 3 <= seg.len() {
+                    *self.trigrams.entry(join_gram(&seg[i..i + 3])).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    pub fn merge(mut self, other: FreqCounter) -> FreqCounter {
+        self.texts += other.texts;
+        self.total_chars += other.total_chars;
+        self.total_tokens += other.total_tokens;
+        for (k, v) in other.chars {
+            *self.chars.entry(k).or_default() += v;
+        }
+        for (k, v) in other.tokens {
+            *self.tokens.entry(k).or_default() += v;
+        }
+        for (k, v) in other.bigrams {
+            *self.bigrams.entry(k).or_default() += v;
+        }
+        for (k, v) in other.trigrams {
+            *self.trigrams.entry(k).or_default() += v;
+        }
+        self
+    }
+
+    pub fn finish(&self, opts: &StatsOptions) -> CorpusStats {
+        fn top_n<K: ToString>(m: &HashMap<K, usize>, n: usize) -> Vec<FreqItem> {
+            let mut v: Vec<FreqItem> = m
+                .iter()
+                .map(|(k, c)| FreqItem {
+                    term: k.to_string(),
+                    count: *c,
+                })
+                .collect();
+            v.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.term.cmp(&b.term)));
+            v.truncate(n);
+            v
+        }
+        let mut hapax: Vec<String> = self
+            .tokens
+            .iter()
+            .filter(|(_, c)| **c == 1)
+            .map(|(k, _)| k.clone())
+            .collect();
+        let hapax_count = hapax.len();
+        hapax.sort();
+        hapax.truncate(opts.hapax_limit);
+        CorpusStats {
+            texts: self.texts,
+            total_chars: self.total_chars,
+            total_tokens: self.total_tokens,
+            distinct_chars: self.chars.len(),
+            distinct_tokens: self.tokens.len(),
+            top_chars: top_n(&self.chars, opts.top),
+            top_tokens: top_n(&self.tokens, opts.top),
+            top_bigrams: top_n(&self.bigrams, opts.top),
+            top_trigrams: top_n(&self.trigrams, opts.top),
+            hapax_count,
+            hapax,
+        }
+    }
+}
+
+pub(crate) fn read_text_file(path: &Path) -> Option<String> {
+    let bytes = std::fs::read(path).ok()?;
+    let (enc, bom_len) = encoding_rs::Encoding::for_bom(&bytes).unwrap_or((encoding_rs::UTF_8, 0));
+    let (s, _, _) = enc.decode(&bytes[bom_len..]);
+    Some(s.into_owned())
+}
+
+/// Plain text of one file, using the same extraction as fetch (shared by stats and concordance).
+/// `source` is a corpus name (cbeta, tipitaka, gretil, sarit, muktabodha);
+/// `part` selects a CBETA juan.
+pub fn plain_text_for_source(
+    source: &str,
+    path: &Path,
+    part: Option<&str>,
+    include_notes: bool,
+) -> Option<String> {
+    let raw = read_text_file(path)?;
+    let is_xml = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .map(|e| e.eq_ignore_ascii_case("xml"))
+        .unwrap_or(false);
+    plain_text_from_content(source, &raw, is_xml, part, include_notes)
+}
+
+/// [`plain_text_for_source`] for contents already read.
+pub fn plain_text_from_content(
+    source: &str,
+    raw: &str,
+    is_xml: bool,
+    part: Option<&str>,
+    include_notes: bool,
+) -> Option<String> {
+    if source == "cbeta" {
+        return match part {
+            Some(p) => extract_cbeta_juan_plain(raw, p, include_notes),
+            None => Some(extract_cbeta_plain_from_xml(raw, include_notes)),
+        };
+    }
+    if is_xml {
+        Some(extract_text(raw))
+    } else {
+        Some(raw.to_string())
+    }
+}
+
+/// Files of a canon division under `root`. For CBETA the division is a canon code ("T")
+/// or a volume ("T08"); for other corpora it is a file-stem prefix (e.g. "s01" for Tipitaka DN).
+pub fn division_paths(source: &str, root: &Path, division: &str) -> Vec<PathBuf> {
+    let div = division.trim();
+    if div.is_empty() {
+        return Vec::new();
+    }
+    if source == "cbeta" {
+        let dir = root.join(
+            div.chars()
+                .take_while(|c| c.is_ascii_alphabetic())
+                .collect::<String>(),
+        );
+        let vol = dir.join(div);
+        let base = if vol.is_dir() { vol } else { dir };
+        return collect_xml_paths(&base, |_, name| name.ends_with(".xml"));
+    }
+    let low = div.to_lowercase();
+    collect_xml_paths(root, |_, name| {
+        !name.ends_with(".toc.xml")
+            && (name.ends_with(".xml") || name.ends_with(".txt"))
+            && name.to_lowercase().starts_with(&low)
+    })
+}
+
+/// Count frequencies over many files in parallel.
+pub fn stats_for_paths(
+    source: &str,
+    paths: &[PathBuf],
+    include_notes: bool,
+    opts: &StatsOptions,
+) -> CorpusStats {
+    crate::scan::run(|| {
+        paths
+            .par_iter()
+            .fold(FreqCounter::new, |mut acc, p| {
+                if let Some(t) = plain_text_for_source(source, p, None, include_notes) {
+                    acc.add_text(&t);
+                }
+                acc
+            })
+            .reduce(FreqCounter::new, FreqCounter::merge)
+    })
+    .finish(opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cjk_and_roman_tokens() {
+        let segs = tokenize_segments("如是我聞。一時 Evaṃ me sutaṃ");
+        assert_eq!(segs[0], vec!["如", "是", "我", "聞"]);
+        assert_eq!(segs[1], vec!["一", "時", "evaṃ", "me", "sutaṃ"]);
+    }
+
+    #[test]
+    fn counts_ngrams_and_hapax() {
+        let mut c = FreqCounter::new();
+        c.add_text("色即是空，空即是色");
+        let s = c.finish(&StatsOptions::default());
+        assert_eq!(s.total_tokens, 8);
+        assert_eq!(s.top_tokens[0].count, 2);
+        assert!(s
+            .top_bigrams
+            .iter()
+            .any(|f| f.term == "即是" && f.count == 2));
+        assert!(s.top_trigrams.iter().any(|f| f.term == "色即是"));
+        assert_eq!(s.hapax_count, 0);
+
+        let mut d = FreqCounter::new();
+        d.add_text("evaṃ me sutaṃ");
+        let merged = c.merge(d).finish(&StatsOptions::default());
+        assert_eq!(merged.texts, 2);
+        assert_eq!(merged.hapax_count, 3);
+        assert!(merged.top_bigrams.iter().any(|f| f.term == "evaṃ me"));
+    }
+}
@@ -0,0 +1,246 @@
+//! Annotations and bookmarks: user notes attached to a char range of a text.
+//!
+//! Each annotation names a corpus, a text id (and CBETA juan), and a `startChar..endChar`
+//! range in the same extracted text that `*_fetch` slices, so the range can be fetched back.
+//! A bookmark is an annotation without a note. The store is one JSON Lines file,
+//! `~/.daizo/annotations/annotations.jsonl`: adding appends a line, so annotations written
+//! from several sessions accumulate, and the file can be read or edited by hand.
+
+use crate::path_resolver::daizo_home;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Annotation {
+    /// Short hex id, stable once written.
+    pub id: String,
+    pub source: String,
+    pub text_id: String,
+    /// CBETA juan the range is counted in (e.g. "001"); None for the whole text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part: Option<String>,
+    pub start_char: usize,
+    pub end_char: usize,
+    /// The text of the range when the annotation was made.
+    #[serde(default)]
+    pub quote: String,
+    #[serde(default)]
+    pub note: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Creation time, UTC (YYYY-MM-DDTHH:MM:SSZ).
+    pub created: String,
+}
+
+impl Annotation {
+    pub fn is_bookmark(&self) -> bool {
+        self.note.trim().is_empty()
+    }
+
+    /// Whether the annotation overlaps `start..end` (an empty range matches at its position).
+    pub fn overlaps(&self, start: usize, end: usize) -> bool {
+        self.start_char < end.max(start + 1) && start < self.end_char.max(self.start_char + 1)
+    }
+
+    /// Case-insensitive match of `q` in the note, quote or a tag.
+    pub fn matches(&self, q: &str) -> bool {
+        let q = q.to_lowercase();
+        self.note.to_lowercase().contains(&q)
+            || self.quote.to_lowercase().contains(&q)
+            || self.tags.iter().any(|t| t.to_lowercase().contains(&q))
+    }
+}
+
+/// Which annotations to list; unset fields match everything.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    pub source: Option<String>,
+    pub text_id: Option<String>,
+    pub part: Option<String>,
+    /// Char range the annotation has to overlap.
+    pub range: Option<(usize, usize)>,
+    pub tag: Option<String>,
+    /// Free-text query (see [`Annotation::matches`]).
+    pub query: Option<String>,
+}
+
+impl Filter {
+    pub fn accepts(&self, a: &Annotation) -> bool {
+        self.source.as_ref().is_none_or(|s| *s == a.source)
+            && self.text_id.as_ref().is_none_or(|t| *t == a.text_id)
+            && self
+                .part
+                .as_ref()
+                .is_none_or(|p| a.part.as_ref() == Some(p))
+            && self.range.is_none_or(|(s, e)| a.overlaps(s, e))
+            && self
+                .tag
+                .as_ref()
+                .is_none_or(|t| a.tags.iter().any(|x| x.eq_ignore_ascii_case(t)))
+            && self.query.as_ref().is_none_or(|q| a.matches(q))
+    }
+}
+
+/// Where annotations are kept: `~/.daizo/annotations`.
+pub fn annotations_dir() -> PathBuf {
+    daizo_home().join("annotations")
+}
+
+pub struct Store {
+    path: PathBuf,
+}
+
+impl Store {
+    pub fn open(dir: &Path) -> Self {
+        Store {
+            path: dir.join("annotations.jsonl"),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every annotation, in the order written. Lines that do not parse are skipped.
+    pub fn all(&self) -> Vec<Annotation> {
+        std::fs::read_to_string(&self.path)
+            .map(|s| {
+                s.lines()
+                    .filter(|l| !l.trim().is_empty())
+                    .filter_map(|l| serde_json::from_str(l).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Annotations accepted by `f`, ordered by text and position.
+    pub fn list(&self, f: &Filter) -> Vec<Annotation> {
+        let mut out: Vec<Annotation> = self.all().into_iter().filter(|a| f.accepts(a)).collect();
+        out.sort_by(|a, b| {
+            (&a.source, &a.text_id, &a.part, a.start_char, &a.created).cmp(&(
+                &b.source,
+                &b.text_id,
+                &b.part,
+                b.start_char,
+                &b.created,
+            ))
+        });
+        out
+    }
+
+    /// Append `a`, giving it an id if it has none. Returns the annotation as stored.
+    pub fn add(&self, mut a: Annotation) -> Result<Annotation, String> {
+        if a.end_char < a.start_char {
+            return Err(format!(
+                "endChar {} is before startChar {}",
+                a.end_char, a.start_char
+            ));
+        }
+        if a.id.is_empty() {
+            let taken: Vec<String> = self.all().into_iter().map(|x| x.id).collect();
+            let mut salt = 0u32;
+            a.id = loop {
+                let id = make_id(&a, salt);
+                if !taken.contains(&id) {
+                    break id;
+                }
+                salt += 1;
+            };
+        }
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        let line = serde_json::to_string(&a).map_err(|e| e.to_string())?;
+        let mut f = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        writeln!(f, "{}", line).map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        Ok(a)
+    }
+}
+
+fn make_id(a: &Annotation, salt: u32) -> String {
+    let mut h = Sha1::new();
+    h.update(
+        format!(
+            "{}\0{}\0{:?}\0{}\0{}\0{}\0{}\0{}",
+            a.source, a.text_id, a.part, a.start_char, a.end_char, a.note, a.created, salt
+        )
+        .as_bytes(),
+    );
+    format!("{:x}", h.finalize())[..8].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ann(text_id: &str, start: usize, end: usize, note: &str, tags: &[&str]) -> Annotation {
+        Annotation {
+            source: "cbeta".into(),
+            text_id: text_id.into(),
+            start_char: start,
+            end_char: end,
+            quote: "如是我聞".into(),
+            note: note.into(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            created: "2026-10-17T00:00:00Z".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn annotations_accumulate_and_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::open(dir.path());
+        assert!(store.all().is_empty());
+        let a = store
+            .add(ann("T0262", 100, 120, "Opening formula", &["formula"]))
+            .unwrap();
+        assert_eq!(a.id.len(), 8);
+        let b = store.add(ann("T0262", 10, 14, "", &[])).unwrap();
+        assert!(b.is_bookmark() && b.id != a.id);
+        store
+            .add(ann("T0235", 0, 4, "Diamond opening", &[]))
+            .unwrap();
+        assert!(store.add(ann("T0235", 9, 4, "", &[])).is_err());
+
+        // A second handle on the same file sees everything, sorted by text and position.
+        let again = Store::open(dir.path());
+        assert_eq!(again.all().len(), 3);
+        let lotus = again.list(&Filter {
+            text_id: Some("T0262".into()),
+            ..Default::default()
+        });
+        assert_eq!(
+            lotus.iter().map(|x| x.start_char).collect::<Vec<_>>(),
+            [10, 100]
+        );
+        let around = again.list(&Filter {
+            range: Some((115, 200)),
+            ..Default::default()
+        });
+        assert_eq!(around, [a]);
+        let tagged = again.list(&Filter {
+            tag: Some("FORMULA".into()),
+            ..Default::default()
+        });
+        assert_eq!(tagged.len(), 1);
+        let found = again.list(&Filter {
+            query: Some("opening".into()),
+            ..Default::default()
+        });
+        assert_eq!(found.len(), 2);
+        let quoted = again.list(&Filter {
+            query: Some("我聞".into()),
+            source: Some("tipitaka".into()),
+            ..Default::default()
+        });
+        assert!(quoted.is_empty());
+    }
+}
@@ -0,0 +1,225 @@
+//! The DILA Authority Database (Dharma Drum Institute of Liberal Arts): persons (`A000439`)
+//! and places (`PL000000012345`) of Buddhist history, with dates and alternative names.
+//!
+//! This module builds the lookup URLs and reads the responses; the server does the requests
+//! and caching. A lookup is by authority id where the text gives one (CBETA's `key`/`ref`),
+//! else by name. Responses are read leniently: a JSON(P) record, a list of records (bare or
+//! under `data`/`results`/`items`) or an object of records keyed by id, with the field names
+//! of either authority type.
+
+use crate::entity::{normalize_name, EntityKind};
+use serde::Serialize;
+use serde_json::Value;
+
+/// The API endpoint (`DAIZO_DILA_AUTHORITY_URL` overrides it in the server).
+pub const DEFAULT_API: &str = "https://authority.dila.edu.tw/api/";
+
+/// An authority record, normalized across person and place entries.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthorityRecord {
+    pub id: String,
+    pub name: String,
+    pub alt_names: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub born: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub died: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynasty: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub long: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub district: Option<String>,
+    /// The record's page on the authority site.
+    pub url: String,
+}
+
+/// The authority type of an entity kind; only persons and places have authorities.
+pub fn authority_type(kind: EntityKind) -> Option<&'static str> {
+    match kind {
+        EntityKind::Person => Some("person"),
+        EntityKind::Place => Some("place"),
+        _ => None,
+    }
+}
+
+/// Whether `key` looks like a DILA id of `kind` (`A` + digits, `PL` + digits).
+pub fn is_authority_id(kind: EntityKind, key: &str) -> bool {
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    match kind {
+        EntityKind::Person => key.strip_prefix('A').is_some_and(digits),
+        EntityKind::Place => key.strip_prefix("PL").is_some_and(digits),
+        _ => false,
+    }
+}
+
+/// The lookup URL for an entity: by `key` when it is a DILA id, else by `name`.
+pub fn lookup_url(api: &str, kind: EntityKind, key: Option<&str>, name: &str) -> Option<String> {
+    let ty = authority_type(kind)?;
+    let sep = if api.contains('?') { '&' } else { '?' };
+    Some(match key.filter(|k| is_authority_id(kind, k)) {
+        Some(k) => format!("{}{}type={}&id={}", api, sep, ty, urlencoding::encode(k)),
+        None => format!(
+            "{}{}type={}&term={}",
+            api,
+            sep,
+            ty,
+            urlencoding::encode(name.trim())
+        ),
+    })
+}
+
+/// The page of record `id` on the authority site.
+pub fn record_url(kind: EntityKind, id: &str) -> String {
+    format!(
+        "https://authority.dila.edu.tw/{}/?fromInner={}",
+        authority_type(kind).unwrap_or("person"),
+        id
+    )
+}
+
+fn field<'a>(v: &'a Value, keys: &[&str]) -> Option<&'a Value> {
+    keys.iter().find_map(|k| v.get(*k)).filter(|v| !v.is_null())
+}
+
+fn text_field(v: &Value, keys: &[&str]) -> Option<String> {
+    let s = match field(v, keys)? {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(n) => n.to_string(),
+        _ => return None,
+    };
+    (!s.is_empty()).then_some(s)
+}
+
+fn number_field(v: &Value, keys: &[&str]) -> Option<f64> {
+    match field(v, keys)? {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Alternative names: an array, or a string listing them.
+fn names_field(v: &Value, keys: &[&str]) -> Vec<String> {
+    let raw: Vec<String> = match field(v, keys) {
+        Some(Value::Array(a)) => a
+            .iter()
+            .filter_map(|x| x.as_str().map(str::to_string))
+            .collect(),
+        Some(Value::String(s)) => s
+            .split([',', '，', ';', '；', '、', '|'])
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    };
+    let mut out: Vec<String> = Vec::new();
+    for n in raw.iter().map(|n| n.trim()).filter(|n| !n.is_empty()) {
+        if !out.iter().any(|o| o == n) {
+            out.push(n.to_string());
+        }
+    }
+    out
+}
+
+fn record(kind: EntityKind, v: &Value, id_hint: Option<&str>) -> Option<AuthorityRecord> {
+    let id = text_field(v, &["authorityID", "authorityId", "id", "ID"])
+        .or_else(|| id_hint.map(str::to_string))?;
+    let name = text_field(v, &["name", "title", "primaryName"])?;
+    let alt_names = names_field(v, &["names", "altNames", "otherNames", "alias", "aliases"])
+        .into_iter()
+        .filter(|n| *n != name)
+        .collect();
+    Some(AuthorityRecord {
+        url: record_url(kind, &id),
+        id,
+        name,
+        alt_names,
+        born: text_field(v, &["bornDateBegin", "born", "birth", "birthYear"]),
+        died: text_field(v, &["diedDateBegin", "died", "death", "deathYear"]),
+        dynasty: text_field(v, &["dynasty"]),
+        lat: number_field(v, &["lat", "latitude"]),
+        long: number_field(v, &["long", "lng", "longitude"]),
+        district: text_field(v, &["districtHistorical", "district", "districtModern"]),
+    })
+}
+
+/// The records in an API response `body` (JSON or JSONP).
+pub fn parse_response(kind: EntityKind, body: &str) -> Vec<AuthorityRecord> {
+    let (Some(start), Some(end)) = (body.find(['{', '[']), body.rfind(['}', ']'])) else {
+        return Vec::new();
+    };
+    let Ok(v) = serde_json::from_str::<Value>(&body[start..=end]) else {
+        return Vec::new();
+    };
+    let v = field(&v, &["data", "results", "items"]).unwrap_or(&v);
+    match v {
+        Value::Array(a) => a.iter().filter_map(|r| record(kind, r, None)).collect(),
+        Value::Object(_) if field(v, &["name", "title", "primaryName"]).is_some() => {
+            record(kind, v, None).into_iter().collect()
+        }
+        Value::Object(o) => o
+            .iter()
+            .filter(|(_, r)| r.is_object())
+            .filter_map(|(id, r)| record(kind, r, Some(id)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The record an entity refers to: the one with its id, else the first whose name or
+/// alternative names include one of its `forms`.
+pub fn best_match<'a>(
+    records: &'a [AuthorityRecord],
+    key: Option<&str>,
+    forms: &[String],
+) -> Option<&'a AuthorityRecord> {
+    if let Some(r) = key.and_then(|k| records.iter().find(|r| r.id == k)) {
+        return Some(r);
+    }
+    let forms: Vec<String> = forms.iter().map(|f| normalize_name(f)).collect();
+    records.iter().find(|r| {
+        std::iter::once(&r.name)
+            .chain(&r.alt_names)
+            .any(|n| forms.contains(&normalize_name(n)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookups_by_id_or_name_and_lenient_records() {
+        assert_eq!(
+            lookup_url(DEFAULT_API, EntityKind::Person, Some("A000439"), "羅什").as_deref(),
+            Some("https://authority.dila.edu.tw/api/?type=person&id=A000439")
+        );
+        assert_eq!(
+            lookup_url("http://x/q?f=json", EntityKind::Place, Some("loc1"), "長安").as_deref(),
+            Some("http://x/q?f=json&type=place&term=%E9%95%B7%E5%AE%89")
+        );
+        assert_eq!(lookup_url(DEFAULT_API, EntityKind::Org, None, "x"), None);
+
+        let by_id = r#"cb({"A000439": {"authorityID": "A000439", "name": "鳩摩羅什",
+            "names": "羅什,童壽,鳩摩羅什", "bornDateBegin": "344", "diedDateBegin": "413",
+            "dynasty": "後秦"}})"#;
+        let recs = parse_response(EntityKind::Person, by_id);
+        assert_eq!(recs.len(), 1);
+        assert_eq!(recs[0].alt_names, ["羅什", "童壽"]);
+        assert_eq!(recs[0].died.as_deref(), Some("413"));
+        assert_eq!(
+            recs[0].url,
+            "https://authority.dila.edu.tw/person/?fromInner=A000439"
+        );
+
+        let search = r#"{"data": [{"id": "PL1", "name": "長安城"},
+            {"id": "PL000000012345", "name": "西安", "names": ["長安"], "lat": "34.26", "long": 108.94}]}"#;
+        let recs = parse_response(EntityKind::Place, search);
+        let m = best_match(&recs, None, &["長安".to_string()]).unwrap();
+        assert_eq!((m.id.as_str(), m.lat), ("PL000000012345", Some(34.26)));
+        assert!(parse_response(EntityKind::Place, "no results").is_empty());
+    }
+}
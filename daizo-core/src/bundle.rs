@@ -0,0 +1,256 @@
+//! Research bundles: a named record of tool calls and what they returned.
+//!
+//! A bundle keeps each call's tool and arguments, a hash of its text output (with the text
+//! itself, so quoted passages can be read back), the ids and offsets it reported, and the
+//! commit of every local corpus at recording time. Replaying the calls later and comparing
+//! hashes shows whether a quotation still matches the corpus.
+
+use crate::path_resolver::daizo_home;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// `_meta` keys copied into a step: what was matched and which part of it was returned.
+const REF_KEYS: &[&str] = &[
+    "matchedId",
+    "sourcePath",
+    "sourceUrl",
+    "returnedStart",
+    "returnedEnd",
+    "totalChars",
+    "searchPattern",
+    "totalFiles",
+];
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Bundle {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Recording date, UTC (YYYY-MM-DD).
+    pub created: String,
+    /// Tool name and version that recorded the bundle.
+    pub tool: String,
+    /// Commit of each local corpus checkout at recording time.
+    pub snapshots: BTreeMap<String, String>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Step {
+    pub tool: String,
+    pub arguments: Value,
+    /// SHA-1 of the text output.
+    pub text_hash: String,
+    pub text: String,
+    /// Ids, offsets and patterns the call reported (subset of its `_meta`).
+    #[serde(default)]
+    pub refs: BTreeMap<String, Value>,
+    /// File ids of search results, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub result_ids: Vec<String>,
+}
+
+/// Outcome of replaying one step.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StepCheck {
+    pub step: usize,
+    pub tool: String,
+    /// `same`, `changed` or `error`.
+    pub status: &'static str,
+    /// First character at which the new text differs from the recorded one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_difference: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub fn hash_text(text: &str) -> String {
+    let mut h = Sha1::new();
+    h.update(text.as_bytes());
+    format!("{:x}", h.finalize())
+}
+
+impl Step {
+    /// Record a call from its text output and `_meta`.
+    pub fn new(tool: &str, arguments: Value, text: &str, meta: Option<&Value>) -> Self {
+        let mut refs = BTreeMap::new();
+        let mut result_ids = Vec::new();
+        if let Some(m) = meta {
+            for k in REF_KEYS {
+                if let Some(v) = m.get(*k).filter(|v| !v.is_null()) {
+                    refs.insert(k.to_string(), v.clone());
+                }
+            }
+            if let Some(rs) = m.get("results").and_then(|v| v.as_array()) {
+                result_ids = rs
+                    .iter()
+                    .filter_map(|r| r.get("file_id").or_else(|| r.get("id")))
+                    .filter_map(|v| v.as_str())
+                    .map(String::from)
+                    .collect();
+            }
+        }
+        Step {
+            tool: tool.to_string(),
+            arguments,
+            text_hash: hash_text(text),
+            text: text.to_string(),
+            refs,
+            result_ids,
+        }
+    }
+
+    /// Compare a replay's text (or error) with the recording.
+    pub fn check(&self, step: usize, replay: Result<&str, String>) -> StepCheck {
+        let (status, first_difference, error) = match replay {
+            Err(e) => ("error", None, Some(e)),
+            Ok(t) if hash_text(t) == self.text_hash => ("same", None, None),
+            Ok(t) => {
+                let at = self
+                    .text
+                    .chars()
+                    .zip(t.chars())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                ("changed", Some(at), None)
+            }
+        };
+        StepCheck {
+            step,
+            tool: self.tool.clone(),
+            status,
+            first_difference,
+            error,
+        }
+    }
+}
+
+/// Where bundles are kept: `~/.daizo/bundles`.
+pub fn bundles_dir() -> PathBuf {
+    daizo_home().join("bundles")
+}
+
+/// Bundle names become file names, so only letters, digits, `-`, `_` and `.` are allowed.
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn file_in(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    if !valid_name(name) {
+        return Err(format!(
+            "invalid bundle name '{}': use letters, digits, '-', '_' or '.'",
+            name
+        ));
+    }
+    Ok(dir.join(format!("{}.json", name)))
+}
+
+impl Bundle {
+    pub fn save(&self, dir: &Path) -> Result<PathBuf, String> {
+        let path = file_in(dir, &self.name)?;
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let s = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(&path, s).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(path)
+    }
+
+    pub fn load(dir: &Path, name: &str) -> Result<Bundle, String> {
+        let path = file_in(dir, name)?;
+        let s = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::from_str(&s).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Corpora whose commit now differs from the recording (`(corpus, recorded, now)`).
+    pub fn moved_snapshots(
+        &self,
+        now: &BTreeMap<String, String>,
+    ) -> Vec<(String, String, Option<String>)> {
+        self.snapshots
+            .iter()
+            .filter(|(c, s)| now.get(*c) != Some(*s))
+            .map(|(c, s)| (c.clone(), s.clone(), now.get(c).cloned()))
+            .collect()
+    }
+}
+
+/// Names of the bundles in `dir`, sorted.
+pub fn list(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .map(|rd| {
+            rd.flatten()
+                .filter_map(|e| {
+                    let p = e.path();
+                    (p.extension().and_then(|s| s.to_str()) == Some("json"))
+                        .then(|| p.file_stem()?.to_str().map(String::from))?
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn bundles_round_trip_and_flag_changed_text() {
+        let meta = json!({
+            "matchedId": "T0262",
+            "returnedStart": 0,
+            "returnedEnd": 4,
+            "nextCursor": null,
+            "results": [{"file_id": "T09n0262"}],
+        });
+        let step = Step::new(
+            "cbeta_fetch",
+            json!({"id": "T0262"}),
+            "如是我聞",
+            Some(&meta),
+        );
+        assert_eq!(step.refs["matchedId"], "T0262");
+        assert!(!step.refs.contains_key("nextCursor"));
+        assert_eq!(step.result_ids, ["T09n0262"]);
+
+        let b = Bundle {
+            name: "lotus-ch1".into(),
+            note: None,
+            created: "2026-10-17".into(),
+            tool: "daizo-mcp test".into(),
+            snapshots: BTreeMap::from([("cbeta".to_string(), "abc".to_string())]),
+            steps: vec![step],
+        };
+        let dir = tempfile::tempdir().unwrap();
+        b.save(dir.path()).unwrap();
+        assert_eq!(Bundle::load(dir.path(), "lotus-ch1").unwrap(), b);
+        assert_eq!(list(dir.path()), ["lotus-ch1"]);
+        assert!(Bundle::load(dir.path(), "../etc/passwd").is_err());
+
+        let s = &b.steps[0];
+        assert_eq!(s.check(0, Ok("如是我聞")).status, "same");
+        let c = s.check(0, Ok("如是我闻"));
+        assert_eq!((c.status, c.first_difference), ("changed", Some(3)));
+        assert_eq!(s.check(0, Err("gone".into())).status, "error");
+
+        let now = BTreeMap::from([("cbeta".to_string(), "def".to_string())]);
+        assert_eq!(
+            b.moved_snapshots(&now),
+            [(
+                "cbeta".to_string(),
+                "abc".to_string(),
+                Some("def".to_string())
+            )]
+        );
+    }
+}
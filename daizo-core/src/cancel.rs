@@ -0,0 +1,63 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Shared flag used to abort a long-running search.
+///
+/// The grep functions read the token bound to the calling thread (see [`CancelToken::scope`])
+/// once on entry and hand it to their rayon workers, which stop opening new files after it
+/// is cancelled. A cancelled search returns whatever it had collected (usually nothing).
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+thread_local! {
+    static CURRENT: RefCell<Option<CancelToken>> = const { RefCell::new(None) };
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Whether `other` is a clone of this token (not merely in the same state).
+    pub fn is_same(&self, other: &CancelToken) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Run `f` with this token bound to the current thread.
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        let prev = CURRENT.with(|c| c.replace(Some(self.clone())));
+        let out = f();
+        CURRENT.with(|c| *c.borrow_mut() = prev);
+        out
+    }
+
+    /// The token bound to the current thread, or a fresh one that is never cancelled.
+    pub fn current() -> Self {
+        CURRENT.with(|c| c.borrow().clone()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope_binds_and_restores() {
+        let t = CancelToken::new();
+        assert!(!CancelToken::current().is_cancelled());
+        t.scope(|| {
+            t.cancel();
+            assert!(CancelToken::current().is_cancelled());
+        });
+        assert!(!CancelToken::current().is_cancelled());
+    }
+}
@@ -0,0 +1,590 @@
+//! Citations of a text in the style its field uses: Taishō (`T 262, 9:1c14–62b1`) for
+//! CBETA, PTS volume and page (`MN 10 (M I 55)`) for the Tipiṭaka, and the source
+//! attribution GRETIL asks for, with the edition details the index records.
+//!
+//! [`cite`] gathers the parts into a [`Citation`], which renders as a plain reference
+//! ([`Citation::plain`]), a BibTeX entry ([`Citation::bibtex`]) or CSL-JSON
+//! ([`Citation::csl_json`]).
+
+use crate::juan_map::JuanMap;
+use crate::taisho_ref::TaishoRef;
+use crate::IndexEntry;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+
+/// Which part of the text is cited; all unset cites the whole text.
+#[derive(Clone, Debug, Default)]
+pub struct Target<'a> {
+    /// CBETA juan (e.g. "001").
+    pub part: Option<&'a str>,
+    /// CBETA `<lb n>` (e.g. "0001c14").
+    pub lb: Option<&'a str>,
+    /// 1-based XML line, for the PTS page of a Tipiṭaka passage.
+    pub line: Option<usize>,
+    /// Canonical citation of the passage (`MN 10`), put before its page.
+    pub reference: Option<&'a str>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Citation {
+    pub source: String,
+    pub id: String,
+    pub title: String,
+    /// Translators or authors, as the index gives them.
+    pub authors: Vec<String>,
+    /// Translation or composition period (`後秦`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period: Option<String>,
+    /// The reference in the corpus's own style: `T 262, 9:1c14–62b1`, `MN 10 (M I 55)`.
+    pub reference: String,
+    /// Printed edition or series (`Taishō shinshū daizōkyō 大正新脩大藏經`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub series: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<String>,
+    /// Text number in the series (`262`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<String>,
+    /// Pages cited (`1c14–62b1`, `M I 55`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pages: Option<String>,
+    /// Where the e-text says it comes from (GRETIL / SARIT `<sourceDesc>`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_statement: Option<String>,
+    /// Digital publisher (`CBETA`, `GRETIL`).
+    pub publisher: String,
+    pub url: String,
+    /// Access date, UTC (YYYY-MM-DD).
+    pub accessed: String,
+}
+
+/// CBETA canons with the names of their printed editions.
+const CANONS: &[(&str, &str)] = &[
+    ("T", "Taishō shinshū daizōkyō 大正新脩大藏經"),
+    ("X", "Manji shinsan dai Nihon zokuzōkyō 卍新纂大日本續藏經"),
+    ("J", "Jiaxing dazangjing 嘉興大藏經"),
+    ("K", "Goryeo daejanggyeong 高麗大藏經"),
+    ("A", "Zhaocheng jinzang 趙城金藏"),
+    ("L", "Qianlong dazangjing 乾隆大藏經"),
+];
+
+/// PTS abbreviations of the books cited by PTS volume and page.
+const PTS: &[(&str, &str)] = &[
+    ("DN", "D"),
+    ("MN", "M"),
+    ("SN", "S"),
+    ("AN", "A"),
+    ("Khp", "Khp"),
+    ("Dhp", "Dhp"),
+    ("Ud", "Ud"),
+    ("Iti", "It"),
+    ("Snp", "Sn"),
+    ("Thag", "Th"),
+    ("Thig", "Thī"),
+];
+
+fn roman(n: u32) -> String {
+    const R: &[(u32, &str)] = &[(10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I")];
+    let mut n = n;
+    let mut s = String::new();
+    for (v, r) in R {
+        while n >= *v {
+            s.push_str(r);
+            n -= v;
+        }
+    }
+    s
+}
+
+/// `0196b12` → `196b12`.
+fn short_lb(lb: &str) -> String {
+    let page: String = lb.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let rest = &lb[page.len()..];
+    let (col, line) = rest.split_at(rest.chars().next().map(char::len_utf8).unwrap_or(0));
+    let line = line.trim_start_matches('0');
+    format!(
+        "{}{}{}",
+        page.trim_start_matches('0'),
+        col,
+        if line.is_empty() && !col.is_empty() {
+            "0"
+        } else {
+            line
+        }
+    )
+}
+
+/// First and last `<lb n>` of the canon's own edition in `xml`.
+fn lb_range(xml: &str, canon: &str) -> Option<(String, String)> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"<lb\b[^>]*>").unwrap());
+    static N: OnceLock<Regex> = OnceLock::new();
+    let n_re = N.get_or_init(|| Regex::new(r#"\b(n|ed)="([^"]*)""#).unwrap());
+    let mut lbs = re.find_iter(xml).filter_map(|m| {
+        let (mut n, mut ed) = (None, None);
+        for c in n_re.captures_iter(m.as_str()) {
+            match &c[1] {
+                "n" => n = Some(c[2].to_string()),
+                _ => ed = Some(c[2].to_string()),
+            }
+        }
+        n.filter(|_| ed.as_deref().is_none_or(|e| e == canon))
+    });
+    let first = lbs.next()?;
+    let last = lbs.last().unwrap_or_else(|| first.clone());
+    Some((first, last))
+}
+
+/// PTS page markers (`<pb ed="P" n="1.0055"/>`) as (XML line, volume, page).
+fn pts_pages(xml: &str) -> Vec<(usize, u32, u32)> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r#"<pb\b[^>]*\bed="P"[^>]*\bn="(\d+)\.(\d+)""#).unwrap());
+    xml.lines()
+        .enumerate()
+        .flat_map(|(i, l)| {
+            re.captures_iter(l)
+                .filter_map(|c| Some((i + 1, c[1].parse().ok()?, c[2].parse().ok()?)))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn meta<'a>(entry: Option<&'a IndexEntry>, key: &str) -> Option<&'a str> {
+    entry?
+        .meta
+        .as_ref()?
+        .get(key)
+        .map(String::as_str)
+        .filter(|v| !v.trim().is_empty())
+}
+
+/// Build the citation of `id` in `source` from its index entry and file contents.
+pub fn cite(
+    source: &str,
+    id: &str,
+    entry: Option<&IndexEntry>,
+    xml: &str,
+    target: &Target,
+    accessed: &str,
+) -> Citation {
+    let stem = entry
+        .and_then(|e| std::path::Path::new(&e.path).file_stem())
+        .and_then(|s| s.to_str())
+        .unwrap_or(id)
+        .trim_end_matches(".xml")
+        .to_string();
+    let authors: Vec<String> = ["translator", "author", "editor"]
+        .iter()
+        .find_map(|k| meta(entry, k))
+        .map(|v| {
+            v.split(['・', '|'])
+                .map(|a| a.trim().to_string())
+                .filter(|a| !a.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let license = crate::license::corpus_license(source);
+    let mut c = Citation {
+        source: source.to_string(),
+        id: id.to_string(),
+        title: entry
+            .map(|e| e.title.clone())
+            .unwrap_or_else(|| stem.clone()),
+        authors,
+        period: entry
+            .and_then(crate::dating::work_date_for_entry)
+            .map(|d| d.label),
+        publisher: license
+            .as_ref()
+            .map(|l| l.name.to_string())
+            .unwrap_or_else(|| source.to_string()),
+        url: license
+            .as_ref()
+            .map(|l| l.url.to_string())
+            .unwrap_or_default(),
+        accessed: accessed.to_string(),
+        ..Default::default()
+    };
+    match source {
+        "cbeta" => cite_cbeta(&mut c, &stem, xml, target),
+        "tipitaka" => cite_pts(&mut c, &stem, xml, target),
+        _ => {
+            let (_, statement) = crate::license::tei_header_statements(xml);
+            c.source_statement = statement.filter(|s| !s.is_empty());
+            c.reference = target.reference.map(String::from).unwrap_or_default();
+            if source == "gretil" {
+                c.publisher = "GRETIL, SUB Göttingen".to_string();
+                c.url = format!(
+                    "https://gretil.sub.uni-goettingen.de/gretil/corpustei/{}.xml",
+                    stem
+                );
+            }
+        }
+    }
+    c
+}
+
+fn cite_cbeta(c: &mut Citation, stem: &str, xml: &str, target: &Target) {
+    c.publisher = "CBETA".to_string();
+    let Some(r) = crate::taisho_ref::parse(stem) else {
+        return;
+    };
+    c.series = Some(
+        CANONS
+            .iter()
+            .find(|(k, _)| *k == r.canon)
+            .map(|(_, n)| n.to_string())
+            .unwrap_or_else(|| format!("CBETA {}", r.canon)),
+    );
+    c.volume = r.volume.map(|v| v.to_string());
+    c.number = Some(r.number.trim_start_matches('0').to_string());
+    let span = match (target.lb, target.part) {
+        (Some(lb), _) => Some((lb.to_string(), lb.to_string())),
+        (None, Some(p)) => {
+            let map = JuanMap::build(xml);
+            map.get(p)
+                .and_then(|j| Some((j.first_lb.clone()?, j.last_lb.clone()?)))
+        }
+        (None, None) => lb_range(xml, &r.canon),
+    };
+    let head = TaishoRef {
+        number: r.number.trim_start_matches('0').to_string(),
+        page: None,
+        column: None,
+        line: None,
+        ..r.clone()
+    };
+    c.reference = head.canonical();
+    if let Some((first, last)) = span {
+        let pages = if first == last {
+            short_lb(&first)
+        } else {
+            format!("{}–{}", short_lb(&first), short_lb(&last))
+        };
+        c.reference.push_str(&format!(":{}", pages));
+        c.pages = Some(pages);
+        c.url = format!("https://cbetaonline.dila.edu.tw/zh/{}_p{}", stem, first);
+    }
+}
+
+fn cite_pts(c: &mut Citation, stem: &str, xml: &str, target: &Target) {
+    c.series = Some("Chaṭṭha Saṅgāyana Tipiṭaka".to_string());
+    let book = crate::pali_ref::book_for_stem(stem);
+    let head = target
+        .reference
+        .map(String::from)
+        .or_else(|| book.map(String::from))
+        .unwrap_or_default();
+    let pts = book
+        .and_then(|b| PTS.iter().find(|(k, _)| *k == b))
+        .map(|p| p.1);
+    let pages = pts_pages(xml);
+    let at = |i: usize| {
+        let (_, v, p) = pages[i];
+        (v, p)
+    };
+    let span = match target.line {
+        Some(line) => pages
+            .iter()
+            .rposition(|(l, _, _)| *l <= line)
+            .or((!pages.is_empty()).then_some(0))
+            .map(|i| (at(i), at(i))),
+        None => (!pages.is_empty()).then(|| (at(0), at(pages.len() - 1))),
+    };
+    c.reference = head;
+    if let (Some(abbr), Some(((v1, p1), (v2, p2)))) = (pts, span) {
+        let pages = if (v1, p1) == (v2, p2) {
+            format!("{} {} {}", abbr, roman(v1), p1)
+        } else if v1 == v2 {
+            format!("{} {} {}–{}", abbr, roman(v1), p1, p2)
+        } else {
+            format!("{} {} {}–{} {}", abbr, roman(v1), p1, roman(v2), p2)
+        };
+        c.reference = if c.reference.is_empty() {
+            pages.clone()
+        } else {
+            format!("{} ({})", c.reference, pages)
+        };
+        c.volume = Some(roman(v1));
+        c.pages = Some(pages);
+    }
+}
+
+impl Citation {
+    /// One-paragraph reference for pasting into a text.
+    pub fn plain(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.authors.is_empty() {
+            let mut a = self.authors.join("; ");
+            if let Some(p) = &self.period {
+                a.push_str(&format!(" ({})", p));
+            }
+            parts.push(a);
+        }
+        parts.push(self.title.clone());
+        if !self.reference.is_empty() && self.reference != self.title {
+            parts.push(self.reference.clone());
+        }
+        if let Some(s) = &self.series {
+            parts.push(match &self.volume {
+                Some(v) if self.source == "cbeta" => format!("{}, vol. {}", s, v),
+                _ => s.clone(),
+            });
+        }
+        if let Some(s) = &self.source_statement {
+            parts.push(s.trim_end_matches('.').to_string());
+        }
+        let mut online = self.publisher.clone();
+        if !self.url.is_empty() {
+            online.push_str(&format!(", {}", self.url));
+        }
+        online.push_str(&format!(" (accessed {})", self.accessed));
+        parts.push(online);
+        let mut s = parts
+            .iter()
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+            .collect::<Vec<_>>()
+            .join(". ");
+        s.push('.');
+        s
+    }
+
+    /// BibTeX key: the id and cited page, letters and digits only.
+    fn key(&self) -> String {
+        let mut k: String = self
+            .id
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect();
+        if let Some(p) = &self.pages {
+            let p: String = p
+                .split('–')
+                .next()
+                .unwrap_or("")
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .collect();
+            if !p.is_empty() {
+                k.push('_');
+                k.push_str(&p);
+            }
+        }
+        k
+    }
+
+    pub fn bibtex(&self) -> String {
+        let esc = |s: &str| s.replace(['{', '}'], "");
+        let mut fields: Vec<(&str, String)> = vec![("title", esc(&self.title))];
+        if !self.authors.is_empty() {
+            fields.push((
+                "author",
+                self.authors
+                    .iter()
+                    .map(|a| format!("{{{}}}", esc(a)))
+                    .collect::<Vec<_>>()
+                    .join(" and "),
+            ));
+        }
+        let opt = [
+            ("series", &self.series),
+            ("volume", &self.volume),
+            ("number", &self.number),
+        ];
+        for (k, v) in opt {
+            if let Some(v) = v {
+                fields.push((k, esc(v)));
+            }
+        }
+        if let Some(p) = &self.pages {
+            fields.push(("pages", esc(&p.replace('–', "--"))));
+        }
+        fields.push(("publisher", esc(&self.publisher)));
+        let mut note = vec![self.reference.clone()];
+        note.extend(self.period.clone());
+        note.extend(self.source_statement.clone());
+        note.retain(|n| !n.is_empty());
+        if !note.is_empty() {
+            fields.push(("note", esc(&note.join("; "))));
+        }
+        if !self.url.is_empty() {
+            fields.push(("url", self.url.clone()));
+        }
+        fields.push(("urldate", self.accessed.clone()));
+        let body: Vec<String> = fields
+            .iter()
+            .map(|(k, v)| format!("  {} = {{{}}},", k, v))
+            .collect();
+        format!("@book{{{},\n{}\n}}", self.key(), body.join("\n"))
+    }
+
+    pub fn csl_json(&self) -> Value {
+        let mut v = json!({
+            "id": self.key(),
+            "type": "book",
+            "title": self.title,
+            "publisher": self.publisher,
+        });
+        if !self.authors.is_empty() {
+            v["author"] = json!(self
+                .authors
+                .iter()
+                .map(|a| json!({"literal": a}))
+                .collect::<Vec<_>>());
+        }
+        if let Some(s) = &self.series {
+            v["collection-title"] = json!(s);
+        }
+        if let Some(x) = &self.volume {
+            v["volume"] = json!(x);
+        }
+        if let Some(x) = &self.number {
+            v["number"] = json!(x);
+        }
+        if let Some(x) = &self.pages {
+            v["page"] = json!(x);
+        }
+        let mut note = vec![self.reference.clone()];
+        note.extend(self.period.clone());
+        note.extend(self.source_statement.clone());
+        note.retain(|n| !n.is_empty());
+        if !note.is_empty() {
+            v["note"] = json!(note.join("; "));
+        }
+        if !self.url.is_empty() {
+            v["URL"] = json!(self.url);
+        }
+        let parts: Vec<u32> = self
+            .accessed
+            .split('-')
+            .filter_map(|x| x.parse().ok())
+            .collect();
+        if parts.len() == 3 {
+            v["accessed"] = json!({"date-parts": [parts]});
+        }
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn entry(id: &str, title: &str, path: &str, meta: &[(&str, &str)]) -> IndexEntry {
+        IndexEntry {
+            id: id.into(),
+            title: title.into(),
+            path: path.into(),
+            meta: Some(
+                meta.iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect::<BTreeMap<_, _>>(),
+            ),
+        }
+    }
+
+    #[test]
+    fn taisho_citation_spans_the_juan_or_line() {
+        let xml = r#"<TEI><text><body>
+<lb n="0848c07" ed="T"/><milestone unit="juan" n="1"/>
+<lb n="0848c08" ed="T"/>如是我聞
+<lb n="0849a02" ed="T"/>一時佛在
+</body></text></TEI>"#;
+        let e = entry(
+            "T0251",
+            "般若波羅蜜多心經",
+            "T/T08/T08n0251.xml",
+            &[("translator", "唐 玄奘譯")],
+        );
+        let c = cite(
+            "cbeta",
+            "T0251",
+            Some(&e),
+            xml,
+            &Target::default(),
+            "2026-10-17",
+        );
+        assert_eq!(c.reference, "T 251, 8:848c7–849a2");
+        assert_eq!(c.authors, ["唐 玄奘譯"]);
+        assert_eq!(
+            c.url,
+            "https://cbetaonline.dila.edu.tw/zh/T08n0251_p0848c07"
+        );
+        assert!(c.plain().starts_with("唐 玄奘譯"));
+        assert!(c
+            .plain()
+            .contains("Taishō shinshū daizōkyō 大正新脩大藏經, vol. 8"));
+
+        let at = Target {
+            lb: Some("0849a02"),
+            ..Default::default()
+        };
+        let c = cite("cbeta", "T0251", Some(&e), xml, &at, "2026-10-17");
+        assert_eq!(c.reference, "T 251, 8:849a2");
+        let bib = c.bibtex();
+        assert!(bib.starts_with("@book{T0251_849a2,"));
+        assert!(bib.contains("  volume = {8},") && bib.contains("  urldate = {2026-10-17},"));
+        let csl = c.csl_json();
+        assert_eq!(csl["number"], "251");
+        assert_eq!(csl["accessed"]["date-parts"], json!([[2026, 10, 17]]));
+    }
+
+    #[test]
+    fn pts_citation_uses_the_page_before_the_line() {
+        let xml = "<p>\n<pb ed=\"P\" n=\"1.0055\"/>Evaṃ me sutaṃ\n<pb ed=\"P\" n=\"1.0056\"/>ekaṃ samayaṃ\n</p>";
+        let e = entry(
+            "s0201m.mul",
+            "Mūlapaṇṇāsapāḷi",
+            "tipitaka/s0201m.mul.xml",
+            &[],
+        );
+        let whole = cite(
+            "tipitaka",
+            "s0201m.mul",
+            Some(&e),
+            xml,
+            &Target::default(),
+            "2026-10-17",
+        );
+        assert_eq!(whole.reference, "MN (M I 55–56)");
+        let at = Target {
+            line: Some(3),
+            reference: Some("MN 10"),
+            ..Default::default()
+        };
+        let c = cite("tipitaka", "s0201m.mul", Some(&e), xml, &at, "2026-10-17");
+        assert_eq!(c.reference, "MN 10 (M I 56)");
+        assert_eq!(c.volume.as_deref(), Some("I"));
+    }
+
+    #[test]
+    fn gretil_citation_carries_the_source_statement() {
+        let xml = "<TEI><teiHeader><fileDesc><sourceDesc><p>Based on the ed. by E.H. Johnston, Lahore 1936.</p></sourceDesc></fileDesc></teiHeader></TEI>";
+        let e = entry(
+            "sa_azvaghoSa-buddhacarita",
+            "Buddhacarita",
+            "gretil/sa_azvaghoSa-buddhacarita.xml",
+            &[("author", "Aśvaghoṣa")],
+        );
+        let c = cite(
+            "gretil",
+            &e.id,
+            Some(&e),
+            xml,
+            &Target::default(),
+            "2026-10-17",
+        );
+        assert_eq!(
+            c.source_statement.as_deref(),
+            Some("Based on the ed. by E.H. Johnston, Lahore 1936.")
+        );
+        assert_eq!(
+            c.plain(),
+            "Aśvaghoṣa. Buddhacarita. Based on the ed. by E.H. Johnston, Lahore 1936. GRETIL, SUB Göttingen, https://gretil.sub.uni-goettingen.de/gretil/corpustei/sa_azvaghoSa-buddhacarita.xml (accessed 2026-10-17)."
+        );
+    }
+}
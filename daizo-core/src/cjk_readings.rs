@@ -0,0 +1,131 @@
+//! Romanized readings of CJK titles, so a CBETA title can be found by its pinyin
+//! (`miaofa lianhua jing`) or its Japanese reading (`hokekyō`).
+//!
+//! Readings are read character by character from a shipped table (`data/readings.txt`).
+//! That gets the Mandarin right and the Japanese close: sound changes across characters
+//! (法華 hokke, not hōke) are not derived, which the fuzzy title ranking absorbs.
+
+use crate::titles::{script_of, META_PINYIN, META_ROMAJI};
+use crate::variants::variant_table;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+const READINGS: &str = include_str!("../data/readings.txt");
+
+/// A romanization [`reading`] can produce.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scheme {
+    /// Mandarin, pinyin without tone marks.
+    Pinyin,
+    /// Japanese on reading, Hepburn.
+    Romaji,
+}
+
+fn table() -> &'static HashMap<char, (&'static str, &'static str)> {
+    static TABLE: OnceLock<HashMap<char, (&'static str, &'static str)>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        READINGS
+            .lines()
+            .filter(|l| !l.starts_with('#'))
+            .filter_map(|l| {
+                let mut it = l.split_whitespace();
+                let c = it.next()?.chars().next()?;
+                Some((c, (it.next()?, it.next()?)))
+            })
+            .collect()
+    })
+}
+
+/// Reading of `s`, one syllable per character separated by spaces. Characters missing from
+/// the table are kept as they are, other text too; `None` when no character could be read.
+pub fn reading(s: &str, scheme: Scheme) -> Option<String> {
+    let table = table();
+    let mut out: Vec<String> = Vec::new();
+    let mut read_any = false;
+    // Whether the last word is a run of Latin letters and digits still being extended.
+    let mut in_run = false;
+    for ch in s.chars() {
+        let hit = table
+            .get(&ch)
+            .or_else(|| table.get(&variant_table().canonical(ch)));
+        match hit {
+            Some((pinyin, romaji)) => {
+                read_any = true;
+                in_run = false;
+                out.push(match scheme {
+                    Scheme::Pinyin => pinyin.to_string(),
+                    Scheme::Romaji => romaji.to_string(),
+                });
+            }
+            None if ch.is_alphanumeric() => {
+                let latin = ch.is_ascii_alphanumeric();
+                match out.last_mut() {
+                    Some(last) if latin && in_run => last.push(ch),
+                    _ => out.push(ch.to_string()),
+                }
+                in_run = latin;
+            }
+            None => in_run = false,
+        }
+    }
+    read_any.then(|| out.join(" "))
+}
+
+/// Record the readings of a CJK `title` and of the CJK names among the entry's aliases
+/// (`法華経` for 妙法蓮華經), separated by ` · `.
+pub fn record(meta: &mut BTreeMap<String, String>, title: &str) {
+    if script_of(title) != "Hani" {
+        return;
+    }
+    let aliases = meta.get("alias").map(String::as_str).unwrap_or_default();
+    let names: Vec<&str> = std::iter::once(title)
+        .chain(
+            aliases
+                .split_whitespace()
+                .filter(|n| script_of(n) == "Hani"),
+        )
+        .collect();
+    let found: Vec<(&str, String)> = [(META_PINYIN, Scheme::Pinyin), (META_ROMAJI, Scheme::Romaji)]
+        .into_iter()
+        .filter_map(|(key, scheme)| {
+            let mut readings: Vec<String> = Vec::new();
+            for r in names.iter().filter_map(|n| reading(n, scheme)) {
+                if !readings.contains(&r) {
+                    readings.push(r);
+                }
+            }
+            (!readings.is_empty()).then(|| (key, readings.join(" · ")))
+        })
+        .collect();
+    for (key, value) in found {
+        meta.insert(key.to_string(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_and_aliases_are_read() {
+        assert_eq!(
+            reading("妙法蓮華經", Scheme::Pinyin).as_deref(),
+            Some("miao fa lian hua jing")
+        );
+        assert_eq!(
+            reading("妙法蓮華経", Scheme::Romaji).as_deref(),
+            Some("myō hō ren ke kyō")
+        );
+        assert_eq!(
+            reading("佛說T0001經", Scheme::Pinyin).as_deref(),
+            Some("fo shuo T0001 jing")
+        );
+        assert_eq!(reading("abc", Scheme::Pinyin), None);
+
+        let mut meta = BTreeMap::new();
+        meta.insert("alias".to_string(), "法華経 法華經 Lotus Sutra".to_string());
+        record(&mut meta, "妙法蓮華經");
+        assert_eq!(meta[META_PINYIN], "miao fa lian hua jing · fa hua jing");
+        assert_eq!(meta[META_ROMAJI], "myō hō ren ke kyō · hō ke kyō");
+    }
+}
@@ -0,0 +1,287 @@
+//! Witness collation of CBETA apparatus: the text as one edition reads it.
+//!
+//! CBETA encodes the Taishō apparatus as `<app><lem wit="…">…</lem><rdg wit="…">…</rdg></app>`.
+//! The extractors read the `lem`; [`collate`] rewrites the XML so that each `app` holds the
+//! reading of a chosen witness instead (its `rdg`, or the `lem` when no `rdg` names it), and
+//! the result goes through the usual extraction. `wit` values are either literal sigla
+//! (`【宋】【元】【明】`) or references (`#wit2`) to `<witness xml:id="wit2">【宋】</witness>`
+//! in the header's `<listWit>`.
+
+use crate::{attr_val, local_name};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+/// A place where the witness departs from the base text.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Reading {
+    /// `n` of the `app` (CBETA's apparatus number, e.g. "0001001").
+    pub n: Option<String>,
+    /// The last `<lb n>` before it.
+    pub lb: Option<String>,
+    /// Byte offset of the `app` in the source XML.
+    pub offset: usize,
+    /// Base text (lem) and the witness's reading, as plain text; empty for an omission.
+    pub lem: String,
+    pub reading: String,
+}
+
+/// The rewritten XML and the readings applied.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Collation {
+    pub xml: String,
+    pub readings: Vec<Reading>,
+}
+
+/// `宋` or `【宋】` → `【宋】`.
+pub fn normalize_siglum(s: &str) -> String {
+    let t = s.trim().trim_start_matches('【').trim_end_matches('】');
+    format!("【{}】", t)
+}
+
+fn witness_ids(xml: &str) -> HashMap<String, String> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r#"<witness\b[^>]*\bxml:id="([^"]+)"[^>]*>([^<]*)</witness>"#).unwrap()
+    });
+    re.captures_iter(xml)
+        .map(|c| (c[1].to_string(), c[2].trim().to_string()))
+        .collect()
+}
+
+/// Sigla named by a `wit` attribute.
+fn sigla(wit: &str, ids: &HashMap<String, String>) -> Vec<String> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"【[^】]+】").unwrap());
+    let mut out = Vec::new();
+    for tok in wit.split_whitespace() {
+        let label = match tok.strip_prefix('#') {
+            Some(id) => ids.get(id).map(String::as_str).unwrap_or(tok),
+            None => tok,
+        };
+        out.extend(re.find_iter(label).map(|m| m.as_str().to_string()));
+    }
+    out
+}
+
+/// Byte range of a `lem` or `rdg`'s content, and its sigla.
+struct Child {
+    is_lem: bool,
+    inner: (usize, usize),
+    sigla: Vec<String>,
+}
+
+struct App {
+    start: usize,
+    end: usize,
+    n: Option<String>,
+    lb: Option<String>,
+    children: Vec<Child>,
+}
+
+/// Top-level `app` elements of `xml` (nested ones stay inside their parent's children).
+fn apps(xml: &str, ids: &HashMap<String, String>) -> Vec<App> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().check_end_names = false;
+    let mut buf = Vec::new();
+    let mut out = Vec::new();
+    let mut cur: Option<App> = None;
+    let mut depth = 0usize;
+    let mut open: Option<Child> = None;
+    let mut lb: Option<String> = None;
+    loop {
+        let pos = reader.buffer_position() as usize;
+        let ev = match reader.read_event_into(&mut buf) {
+            Ok(Event::Eof) | Err(_) => break,
+            Ok(ev) => ev,
+        };
+        let after = reader.buffer_position() as usize;
+        match ev {
+            Event::Start(e) | Event::Empty(e) if cur.is_none() => {
+                let name = local_name(e.name().as_ref()).to_vec();
+                if name == b"lb" {
+                    lb = attr_val(&e, b"n").map(|v| v.into_owned());
+                } else if name == b"app" && after > pos && xml.as_bytes()[after - 2] != b'/' {
+                    cur = Some(App {
+                        start: pos,
+                        end: after,
+                        n: attr_val(&e, b"n").map(|v| v.into_owned()),
+                        lb: lb.clone(),
+                        children: Vec::new(),
+                    });
+                    depth = 0;
+                }
+            }
+            Event::Start(e) => {
+                let name = local_name(e.name().as_ref()).to_vec();
+                if depth == 0 && (name == b"lem" || name == b"rdg") {
+                    open = Some(Child {
+                        is_lem: name == b"lem",
+                        inner: (after, after),
+                        sigla: sigla(&attr_val(&e, b"wit").unwrap_or_default(), ids),
+                    });
+                }
+                depth += 1;
+            }
+            Event::Empty(e) => {
+                let name = local_name(e.name().as_ref()).to_vec();
+                if depth == 0 && (name == b"lem" || name == b"rdg") {
+                    if let Some(a) = cur.as_mut() {
+                        a.children.push(Child {
+                            is_lem: name == b"lem",
+                            inner: (after, after),
+                            sigla: sigla(&attr_val(&e, b"wit").unwrap_or_default(), ids),
+                        });
+                    }
+                }
+            }
+            Event::End(e) => {
+                let Some(a) = cur.as_mut() else {
+                    continue;
+                };
+                if depth == 0 {
+                    if local_name(e.name().as_ref()) == b"app" {
+                        a.end = after;
+                        out.extend(cur.take());
+                    }
+                    continue;
+                }
+                depth -= 1;
+                if depth == 0 {
+                    if let Some(mut c) = open.take() {
+                        c.inner.1 = pos;
+                        a.children.push(c);
+                    }
+                }
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+    out
+}
+
+/// Every siglum the apparatus of `xml` names, with the number of `app`s naming it.
+pub fn witnesses(xml: &str) -> Vec<(String, usize)> {
+    let ids = witness_ids(xml);
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    fn walk(xml: &str, ids: &HashMap<String, String>, counts: &mut BTreeMap<String, usize>) {
+        for a in apps(xml, ids) {
+            let mut named: Vec<&String> = a.children.iter().flat_map(|c| &c.sigla).collect();
+            named.sort();
+            named.dedup();
+            for s in named {
+                *counts.entry(s.clone()).or_default() += 1;
+            }
+            for c in &a.children {
+                walk(&xml[c.inner.0..c.inner.1], ids, counts);
+            }
+        }
+    }
+    walk(xml, &ids, &mut counts);
+    let mut out: Vec<(String, usize)> = counts.into_iter().collect();
+    out.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    out
+}
+
+/// Rewrite `xml` to the readings of `witness` (`宋` or `【宋】`).
+pub fn collate(xml: &str, witness: &str) -> Collation {
+    let siglum = normalize_siglum(witness);
+    let ids = witness_ids(xml);
+    let gaiji = crate::cbeta_gaiji_map_fast(xml);
+    let mut readings = Vec::new();
+    let out = rewrite(xml, 0, &siglum, &ids, &gaiji, &mut readings);
+    Collation { xml: out, readings }
+}
+
+fn rewrite(
+    xml: &str,
+    base: usize,
+    siglum: &str,
+    ids: &HashMap<String, String>,
+    gaiji: &HashMap<String, String>,
+    readings: &mut Vec<Reading>,
+) -> String {
+    let mut out = String::with_capacity(xml.len());
+    let mut last = 0;
+    for a in apps(xml, ids) {
+        out.push_str(&xml[last..a.start]);
+        last = a.end;
+        let lem = a.children.iter().find(|c| c.is_lem);
+        let chosen = a
+            .children
+            .iter()
+            .find(|c| !c.is_lem && c.sigla.iter().any(|s| s == siglum))
+            .or(lem);
+        let Some(chosen) = chosen else {
+            continue;
+        };
+        let inner = &xml[chosen.inner.0..chosen.inner.1];
+        if !chosen.is_lem {
+            let plain = |x: &str| crate::extract_cbeta_plain_from_snippet(x, gaiji, false);
+            readings.push(Reading {
+                n: a.n.clone(),
+                lb: a.lb.clone(),
+                offset: base + a.start,
+                lem: lem
+                    .map(|l| plain(&xml[l.inner.0..l.inner.1]))
+                    .unwrap_or_default(),
+                reading: plain(inner),
+            });
+        }
+        out.push_str(&rewrite(
+            inner,
+            base + chosen.inner.0,
+            siglum,
+            ids,
+            gaiji,
+            readings,
+        ));
+    }
+    out.push_str(&xml[last..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn witness_readings_replace_the_lemma() {
+        let xml = r##"<TEI><teiHeader><listWit><witness xml:id="wit1">【大】</witness><witness xml:id="wit2">【宋】</witness><witness xml:id="wit3">【元】</witness></listWit></teiHeader>
+<text><body><p><lb n="0001a01"/>如是我聞一時佛<app n="0001001"><lem wit="#wit1">住</lem><rdg wit="#wit2 #wit3">在</rdg></app>舍衛國</p>
+<p><lb n="0001a02"/>與大比丘<app n="0001002"><lem wit="【大】">眾</lem><rdg wit="【宋】"><space quantity="0"/></rdg><rdg wit="【明】">僧</rdg></app>俱
+<app><lem>千<app><lem>二</lem><rdg wit="【宋】">三</rdg></app>百</lem><rdg wit="【元】">千百</rdg></app></p></body></text></TEI>"##;
+        let w = witnesses(xml);
+        assert_eq!(w[0], ("【宋】".to_string(), 3));
+        assert!(w.contains(&("【大】".to_string(), 2)) && w.contains(&("【明】".to_string(), 1)));
+
+        let song = collate(xml, "宋");
+        let text = crate::extract_cbeta_plain_from_xml(&song.xml, false);
+        assert!(text.contains("如是我聞一時佛在舍衛國"), "{}", text);
+        assert!(text.contains("與大比丘俱") && text.contains("千三百"));
+        assert_eq!(song.readings.len(), 3);
+        assert_eq!(
+            song.readings[0],
+            Reading {
+                n: Some("0001001".into()),
+                lb: Some("0001a01".into()),
+                offset: xml.find("<app").unwrap(),
+                lem: "住".into(),
+                reading: "在".into(),
+            }
+        );
+        assert_eq!(song.readings[1].reading, "");
+        assert!(xml[song.readings[2].offset..].starts_with("<app><lem>二"));
+
+        let yuan = collate(xml, "【元】");
+        let text = crate::extract_cbeta_plain_from_xml(&yuan.xml, false);
+        assert!(text.contains("佛在舍衛") && text.contains("比丘眾俱") && text.contains("千百"));
+        let base = collate(xml, "大");
+        assert!(base.readings.is_empty());
+        assert!(crate::extract_cbeta_plain_from_xml(&base.xml, false).contains("佛住舍衛"));
+    }
+}
@@ -0,0 +1,171 @@
+//! User collections: named lists of texts, juans or sections to read together.
+//!
+//! Unlike the reading lists of [`crate::reading`], which are fixed divisions of a canon, a
+//! collection here is put together by the user — the translations of one sūtra, a root text
+//! with its commentaries, the chapters quoted in a paper — and kept as
+//! `~/.daizo/collections/<name>.json`. Each item names a corpus and a text id, optionally
+//! narrowed to a juan (`part`) or a section by heading (`section`), and maps to one
+//! `<corpus>_fetch` call.
+
+use crate::path_resolver::daizo_home;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Item {
+    pub source: String,
+    pub id: String,
+    /// CBETA juan or other part (e.g. "001").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part: Option<String>,
+    /// Heading of a section, matched as `*_fetch` `headQuery` (e.g. "方便品").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub section: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl Item {
+    /// The fetch call that reads the item: tool name and arguments (CBETA as plain text).
+    pub fn fetch_call(&self) -> (String, Value) {
+        let mut args = json!({"id": self.id});
+        if self.source == "cbeta" {
+            args["format"] = json!("plain");
+        }
+        if let Some(p) = &self.part {
+            args["part"] = json!(p);
+        }
+        if let Some(s) = &self.section {
+            args["headQuery"] = json!(s);
+        }
+        (format!("{}_fetch", self.source), args)
+    }
+
+    /// `T0262`, `T0262 part 002`, `T0262 §方便品`.
+    pub fn label(&self) -> String {
+        let mut s = self.id.clone();
+        if let Some(p) = &self.part {
+            s.push_str(&format!(" part {}", p));
+        }
+        if let Some(h) = &self.section {
+            s.push_str(&format!(" §{}", h));
+        }
+        s
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Collection {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Creation date, UTC (YYYY-MM-DD).
+    pub created: String,
+    pub items: Vec<Item>,
+}
+
+impl Collection {
+    /// Append the items not already in the collection; returns how many were added.
+    pub fn add(&mut self, items: impl IntoIterator<Item = Item>) -> usize {
+        let before = self.items.len();
+        for it in items {
+            let dup = self.items.iter().any(|x| {
+                x.source == it.source
+                    && x.id == it.id
+                    && x.part == it.part
+                    && x.section == it.section
+            });
+            if !dup {
+                self.items.push(it);
+            }
+        }
+        self.items.len() - before
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<PathBuf, String> {
+        let path = file_in(dir, &self.name)?;
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let s = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(&path, s).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(path)
+    }
+
+    pub fn load(dir: &Path, name: &str) -> Result<Collection, String> {
+        let path = file_in(dir, name)?;
+        let s = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::from_str(&s).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// Where collections are kept: `~/.daizo/collections`.
+pub fn collections_dir() -> PathBuf {
+    daizo_home().join("collections")
+}
+
+/// Collection names follow the bundle rules (see [`crate::bundle::valid_name`]).
+pub fn exists(dir: &Path, name: &str) -> bool {
+    file_in(dir, name).is_ok_and(|p| p.exists())
+}
+
+fn file_in(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    if !crate::bundle::valid_name(name) {
+        return Err(format!(
+            "invalid collection name '{}': use letters, digits, '-', '_' or '.'",
+            name
+        ));
+    }
+    Ok(dir.join(format!("{}.json", name)))
+}
+
+/// Names of the collections in `dir`, sorted.
+pub fn list(dir: &Path) -> Vec<String> {
+    crate::bundle::list(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, part: Option<&str>, section: Option<&str>) -> Item {
+        Item {
+            source: "cbeta".into(),
+            id: id.into(),
+            part: part.map(String::from),
+            section: section.map(String::from),
+            note: None,
+        }
+    }
+
+    #[test]
+    fn collections_round_trip_without_duplicates() {
+        let mut c = Collection {
+            name: "diamond".into(),
+            note: Some("Vajracchedikā translations".into()),
+            created: "2026-10-17".into(),
+            items: vec![],
+        };
+        let added = c.add([
+            item("T0235", None, None),
+            item("T0236", Some("001"), None),
+            item("T0235", None, None),
+        ]);
+        assert_eq!(added, 2);
+        assert_eq!(c.add([item("T0262", None, Some("方便品"))]), 1);
+
+        let (tool, args) = c.items[2].fetch_call();
+        assert_eq!(tool, "cbeta_fetch");
+        assert_eq!(
+            args,
+            json!({"id": "T0262", "format": "plain", "headQuery": "方便品"})
+        );
+        assert_eq!(c.items[1].label(), "T0236 part 001");
+
+        let dir = tempfile::tempdir().unwrap();
+        c.save(dir.path()).unwrap();
+        assert!(exists(dir.path(), "diamond") && !exists(dir.path(), "lotus"));
+        assert_eq!(Collection::load(dir.path(), "diamond").unwrap(), c);
+        assert_eq!(list(dir.path()), ["diamond"]);
+        assert!(Collection::load(dir.path(), "../diamond").is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! Colophons, scribal and donation records and the rest of a text's back matter.
+//!
+//! [`list_colophons`] walks the `<text>` of a TEI file for the parts that sit outside the
+//! running text: `<trailer>` (the closing title, often with a 題記), `<cb:docNumber>`,
+//! elements typed as colophons (`type`/`cb:type` containing `colophon`) and each division of
+//! `<back>`. The apparatus and reading divisions of the back matter are left to
+//! [`crate::notes`] and [`crate::collate`].
+
+use crate::{attr_val, local_name};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::Serialize;
+
+/// Where a [`Colophon`] comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ColophonKind {
+    Trailer,
+    DocNumber,
+    Colophon,
+    Back,
+}
+
+impl ColophonKind {
+    pub const NAMES: &'static [&'static str] = &["trailer", "docNumber", "colophon", "back"];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "trailer" => Some(ColophonKind::Trailer),
+            "docnumber" => Some(ColophonKind::DocNumber),
+            "colophon" => Some(ColophonKind::Colophon),
+            "back" => Some(ColophonKind::Back),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ColophonKind::Trailer => "trailer",
+            ColophonKind::DocNumber => "docNumber",
+            ColophonKind::Colophon => "colophon",
+            ColophonKind::Back => "back",
+        }
+    }
+}
+
+/// Back-matter divisions that hold notes and variant readings rather than records.
+const APPARATUS_TYPES: &[&str] = &["apparatus", "rdg", "notes", "note", "app"];
+
+/// Elements whose content is not part of a record's text.
+const SKIPPED: &[&[u8]] = &[b"note", b"rdg", b"mulu", b"fw", b"figDesc"];
+
+/// A colophon, trailer, document number or back-matter division.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Colophon {
+    pub kind: ColophonKind,
+    /// Element name (`trailer`, `div`, `p`, …).
+    pub element: String,
+    /// `type` (or `cb:type`) attribute.
+    #[serde(rename = "type")]
+    pub div_type: Option<String>,
+    /// The first `<head>` of the element, kept out of `text`.
+    pub head: Option<String>,
+    /// Whitespace-normalized text; notes and variant readings left out.
+    pub text: String,
+    /// 1-based XML line where the element starts.
+    pub line: usize,
+    /// Byte offset of the element in the XML.
+    pub offset: usize,
+    /// The `<lb n>` the element starts on: one that opens it, else the last before it (for
+    /// back matter, the first inside it).
+    pub lb: Option<String>,
+}
+
+fn element_type(e: &quick_xml::events::BytesStart) -> Option<String> {
+    attr_val(e, b"type")
+        .or_else(|| attr_val(e, b"cb:type"))
+        .map(|v| v.into_owned())
+}
+
+fn normalize(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The colophons, trailers, document numbers and back-matter divisions of `xml`, in document
+/// order, empty ones left out. The `teiHeader` is not read.
+pub fn list_colophons(xml: &str) -> Vec<Colophon> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().check_end_names = false;
+    let mut buf = Vec::new();
+    let mut out: Vec<Colophon> = Vec::new();
+    let mut lb: Option<String> = None;
+    let mut in_text = false;
+    // Depth inside <back>: 1 directly in it.
+    let mut back = 0usize;
+    // Depth inside the record being collected, and inside a skipped element or its head.
+    let mut depth = 0usize;
+    let mut skip = 0usize;
+    let mut head = 0usize;
+    let mut line = 1usize;
+    let mut line_pos = 0usize;
+    loop {
+        let pos = reader.buffer_position() as usize;
+        line += xml.as_bytes()[line_pos..pos.min(xml.len())]
+            .iter()
+            .filter(|b| **b == b'\n')
+            .count();
+        line_pos = pos.min(xml.len());
+        let ev = reader.read_event_into(&mut buf);
+        match &ev {
+            Ok(Event::Empty(e)) if local_name(e.name().as_ref()) == b"lb" => {
+                if let Some(n) = attr_val(e, b"n") {
+                    lb = Some(n.into_owned());
+                    // A record that opens with an <lb> starts on that line.
+                    if let Some(c) = out
+                        .last_mut()
+                        .filter(|c| depth > 0 && (c.lb.is_none() || c.text.trim().is_empty()))
+                    {
+                        c.lb = lb.clone();
+                    }
+                }
+            }
+            Ok(Event::Start(e)) => {
+                let name_owned = e.name().as_ref().to_owned();
+                let name = local_name(&name_owned);
+                if back > 0 {
+                    back += 1;
+                }
+                if depth > 0 {
+                    depth += 1;
+                    if skip > 0 || SKIPPED.contains(&name) {
+                        skip += 1;
+                    } else if head > 0
+                        || (name == b"head" && out.last().is_some_and(|c| c.head.is_none()))
+                    {
+                        head += 1;
+                    }
+                } else if name == b"text" {
+                    in_text = true;
+                } else if name == b"back" && in_text {
+                    back = 1;
+                } else if in_text {
+                    let ty = element_type(e);
+                    let kind = if back == 2 {
+                        let apparatus = ty.as_deref().is_some_and(|t| {
+                            APPARATUS_TYPES.contains(&t.to_ascii_lowercase().as_str())
+                        });
+                        (!apparatus && !SKIPPED.contains(&name)).then_some(ColophonKind::Back)
+                    } else if name == b"trailer" {
+                        Some(ColophonKind::Trailer)
+                    } else if name == b"docNumber" {
+                        Some(ColophonKind::DocNumber)
+                    } else if name == b"colophon"
+                        || ty
+                            .as_deref()
+                            .is_some_and(|t| t.to_ascii_lowercase().contains("colophon"))
+                    {
+                        Some(ColophonKind::Colophon)
+                    } else {
+                        None
+                    };
+                    if let Some(kind) = kind {
+                        depth = 1;
+                        out.push(Colophon {
+                            kind,
+                            element: String::from_utf8_lossy(name).into_owned(),
+                            div_type: ty,
+                            head: None,
+                            text: String::new(),
+                            line,
+                            offset: pos,
+                            lb: (kind != ColophonKind::Back).then(|| lb.clone()).flatten(),
+                        });
+                    }
+                }
+            }
+            Ok(Event::Text(t)) if depth > 0 && skip == 0 => {
+                if let Some(c) = out.last_mut() {
+                    let t = t.decode().unwrap_or_default();
+                    if head > 0 {
+                        c.head.get_or_insert_with(String::new).push_str(&t);
+                    } else {
+                        c.text.push_str(&t);
+                    }
+                }
+            }
+            Ok(Event::End(_)) => {
+                back = back.saturating_sub(1);
+                if depth > 0 {
+                    depth -= 1;
+                    if skip > 0 {
+                        skip -= 1;
+                    } else {
+                        head = head.saturating_sub(1);
+                    }
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    out.into_iter()
+        .filter_map(|mut c| {
+            c.text = normalize(&c.text);
+            c.head = c.head.map(|h| normalize(&h)).filter(|h| !h.is_empty());
+            (!c.text.is_empty() || c.head.is_some()).then_some(c)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailers_doc_numbers_and_back_matter_are_listed() {
+        let xml = r##"<TEI><teiHeader><trailer>header</trailer></teiHeader><text><body>
+<cb:docNumber>No. 1</cb:docNumber>
+<p><lb n="0010a01"/>如是我聞</p>
+<trailer><lb n="0010a02"/>長阿含經卷第一<note place="inline">宋本</note></trailer>
+<p cb:type="colophon"><lb n="0010a03"/>天平十二年 光明皇后願文</p>
+</body><back>
+<cb:div type="apparatus"><head>校註</head><p><note n="0010001">一時＝一時佛【宋】</note></p></cb:div>
+<cb:div type="w"><head>施主</head><p><lb n="0010a04"/>比丘某甲 助刊</p></cb:div>
+</back></text></TEI>"##;
+        let colophons = list_colophons(xml);
+        let got: Vec<_> = colophons
+            .iter()
+            .map(|c| {
+                (
+                    c.kind.as_str(),
+                    c.element.as_str(),
+                    c.head.as_deref(),
+                    c.text.as_str(),
+                    c.line,
+                    c.lb.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            got,
+            [
+                ("docNumber", "docNumber", None, "No. 1", 2, None),
+                (
+                    "trailer",
+                    "trailer",
+                    None,
+                    "長阿含經卷第一",
+                    4,
+                    Some("0010a02")
+                ),
+                (
+                    "colophon",
+                    "p",
+                    None,
+                    "天平十二年 光明皇后願文",
+                    5,
+                    Some("0010a03")
+                ),
+                (
+                    "back",
+                    "div",
+                    Some("施主"),
+                    "比丘某甲 助刊",
+                    8,
+                    Some("0010a04")
+                ),
+            ]
+        );
+        assert_eq!(
+            ColophonKind::parse("docnumber"),
+            Some(ColophonKind::DocNumber)
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use crate::analysis::plain_text_for_source;
+use crate::GrepResult;
+use rayon::prelude::*;
+use regex::Regex;
+use serde::Serialize;
+use std::path::Path;
+
+/// Ordering of concordance lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KwicSort {
+    /// Corpus order (file, then position in file).
+    Position,
+    /// Alphabetical by the right context (what follows the keyword).
+    Right,
+    /// Alphabetical by the left context read backwards from the keyword.
+    Left,
+}
+
+impl KwicSort {
+    pub fn parse(s: &str) -> KwicSort {
+        match s.to_ascii_lowercase().as_str() {
+            "right" | "r" => KwicSort::Right,
+            "left" | "l" => KwicSort::Left,
+            _ => KwicSort::Position,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct KwicOptions {
+    /// Context window on each side, in characters.
+    pub window: usize,
+    /// Maximum lines kept per file.
+    pub max_per_file: usize,
+    /// Drop all whitespace from contexts (CJK text); otherwise runs collapse to one space.
+    pub strip_whitespace: bool,
+}
+
+impl Default for KwicOptions {
+    fn default() -> Self {
+        Self {
+            window: 20,
+            max_per_file: 50,
+            strip_whitespace: false,
+        }
+    }
+}
+
+/// One keyword-in-context line.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KwicLine {
+    pub file_id: String,
+    pub title: String,
+    /// Character offset of the keyword in the extracted plain text (usable as fetch startChar).
+    pub char_offset: usize,
+    pub left: String,
+    pub keyword: String,
+    pub right: String,
+}
+
+fn clean_context(s: &str, strip: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut in_ws = false;
+    for c in s.chars() {
+        if c.is_whitespace() {
+            if !strip && !in_ws {
+                out.push(' ');
+            }
+            in_ws = true;
+        } else {
+            out.push(c);
+            in_ws = false;
+        }
+    }
+    out
+}
+
+fn last_chars(s: &str, n: usize) -> &str {
+    match s.char_indices().rev().nth(n.saturating_sub(1)) {
+        Some((i, _)) if n > 0 => &s[i..],
+        _ if n == 0 => "",
+        _ => s,
+    }
+}
+
+fn first_chars(s: &str, n: usize) -> &str {
+    match s.char_indices().nth(n) {
+        Some((i, _)) => &s[..i],
+        None => s,
+    }
+}
+
+/// Keyword-in-context lines for every match of `re` in `text`.
+/// The window is measured after whitespace cleanup so that line breaks do not eat context.
+pub fn kwic_in_text(
+    text: &str,
+    re: &Regex,
+    opts: &KwicOptions,
+) -> Vec<(usize, String, String, String)> {
+    let mut out = Vec::new();
+    let mut char_pos = 0usize;
+    let mut byte_pos = 0usize;
+    // Whitespace cleanup can shrink the text, so take a generous raw slice and trim afterwards.
+    let raw_span = opts.window.saturating_mul(4).max(16);
+    for m in re.find_iter(text) {
+        if out.len() >= opts.max_per_file {
+            break;
+        }
+        if m.start() == m.end() {
+            continue;
+        }
+        char_pos += text[byte_pos..m.start()].chars().count();
+        byte_pos = m.start();
+        let left_raw = last_chars(&text[..m.start()], raw_span);
+        let right_raw = first_chars(&text[m.end()..], raw_span);
+        let left = clean_context(left_raw, opts.strip_whitespace);
+        let right = clean_context(right_raw, opts.strip_whitespace);
+        out.push((
+            char_pos,
+            last_chars(&left, opts.window).to_string(),
+            clean_context(m.as_str(), opts.strip_whitespace),
+            first_chars(&right, opts.window).to_string(),
+        ));
+    }
+    out
+}
+
+/// Build concordance lines for the files found by a grep pass.
+/// Each file is re-read through the fetch extraction so contexts are plain text, not XML.
+pub fn concordance_for_results(
+    source: &str,
+    results: &[GrepResult],
+    re: &Regex,
+    opts: &KwicOptions,
+) -> Vec<KwicLine> {
+    let per_file: Vec<Vec<KwicLine>> = crate::scan::run(|| {
+        results
+            .par_iter()
+            .map(|r| {
+                let Some(text) =
+                    plain_text_for_source(source, Path::new(&r.file_path), None, false)
+                else {
+                    return Vec::new();
+                };
+                kwic_in_text(&text, re, opts)
+                    .into_iter()
+                    .map(|(off, left, keyword, right)| KwicLine {
+                        file_id: r.file_id.clone(),
+                        title: r.title.clone(),
+                        char_offset: off,
+                        left,
+                        keyword,
+                        right,
+                    })
+                    .collect()
+            })
+            .collect()
+    });
+    per_file.into_iter().flatten().collect()
+}
+
+/// Sort lines in place; ties fall back to corpus order.
+pub fn sort_kwic(lines: &mut [KwicLine], sort: KwicSort) {
+    match sort {
+        KwicSort::Position => {}
+        KwicSort::Right => lines.sort_by(|a, b| {
+            a.right
+                .cmp(&b.right)
+                .then_with(|| a.keyword.cmp(&b.keyword))
+        }),
+        KwicSort::Left => lines
+            .sort_by_cached_key(|l| (l.left.chars().rev().collect::<String>(), l.keyword.clone())),
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// CSV with a header row: fileId,charOffset,left,keyword,right,title.
+pub fn kwic_to_csv(lines: &[KwicLine]) -> String {
+    let mut out = String::from("fileId,charOffset,left,keyword,right,title\n");
+    for l in lines {
+        out.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            csv_field(&l.file_id),
+            l.char_offset,
+            csv_field(&l.left),
+            csv_field(&l.keyword),
+            csv_field(&l.right),
+            csv_field(&l.title)
+        ));
+    }
+    out
+}
+
+/// Aligned plain-text rendering: left context right-aligned, keyword in brackets.
+pub fn kwic_to_text(lines: &[KwicLine], window: usize) -> String {
+    let mut out = String::new();
+    for l in lines {
+        let pad = window.saturating_sub(l.left.chars().count());
+        out.push_str(&format!(
+            "{}{} 【{}】 {}  ({}@{})\n",
+            " ".repeat(pad),
+            l.left,
+            l.keyword,
+            l.right,
+            l.file_id,
+            l.char_offset
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(left: &str, right: &str) -> KwicLine {
+        KwicLine {
+            file_id: "T0001".into(),
+            title: String::new(),
+            char_offset: 0,
+            left: left.into(),
+            keyword: "空".into(),
+            right: right.into(),
+        }
+    }
+
+    #[test]
+    fn windows_and_offsets() {
+        let re = Regex::new("空").unwrap();
+        let opts = KwicOptions {
+            window: 3,
+            strip_whitespace: true,
+            ..Default::default()
+        };
+        let got = kwic_in_text("色不異空，\n空不異色", &re, &opts);
+        assert_eq!(got.len(), 2);
+        assert_eq!(got[0], (3, "色不異".into(), "空".into(), "，空不".into()));
+        assert_eq!(got[1].0, 6);
+        assert_eq!(got[1].1, "異空，");
+    }
+
+    #[test]
+    fn sorting_and_csv() {
+        let mut v = vec![line("甲乙", "丙"), line("乙甲", "乙"), line("丙甲", "甲")];
+        sort_kwic(&mut v, KwicSort::Right);
+        let rights: Vec<&str> = v.iter().map(|l| l.right.as_str()).collect();
+        assert_eq!(rights, vec!["丙", "乙", "甲"]);
+        sort_kwic(&mut v, KwicSort::Left);
+        let lefts: Vec<&str> = v.iter().map(|l| l.left.as_str()).collect();
+        assert_eq!(lefts, vec!["甲乙", "丙甲", "乙甲"]);
+        let csv = kwic_to_csv(&[line("a,b", "c\"d")]);
+        assert!(csv.ends_with("T0001,0,\"a,b\",空,\"c\"\"d\",\n"));
+    }
+}
@@ -0,0 +1,278 @@
+use crate::path_resolver::daizo_home;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Server settings read from `config.toml` (`DAIZO_CONFIG`, else `~/.daizo/config.toml`).
+/// Environment variables still win over the file; the file only replaces built-in defaults.
+/// [`reload`] swaps the settings in place, so a running server picks up edits.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub scoring: Scoring,
+    pub output: Output,
+    pub prefetch: Prefetch,
+    pub grep_cache: GrepCache,
+    pub warmup: Warmup,
+    pub scan: Scan,
+    pub embeddings: Embeddings,
+    pub network: Network,
+    pub metrics: Metrics,
+    pub log: Log,
+    /// Corpus registry keyed by corpus (`cbeta`, `tipitaka`, `gretil`, `sarit`, `muktabodha`).
+    pub corpora: BTreeMap<String, CorpusConfig>,
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+/// Title-search scoring weights.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Scoring {
+    /// Default `minScore` of `daizo_resolve`.
+    pub min_score: f32,
+    /// Bonus added to Taishō (T) works in CBETA title search and search relevance.
+    pub taisho_bias: f32,
+    /// Score given to a work whose author/translator/editor matches the query.
+    pub person_match: f32,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Scoring {
+            min_score: 0.1,
+            taisho_bias: 0.02,
+            person_match: 0.93,
+        }
+    }
+}
+
+/// Output defaults; unset values fall back to the env var, then the built-in default.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Output {
+    pub max_chars: Option<usize>,
+    /// Largest `maxChars` a single call may ask for (default 100000).
+    pub max_chars_limit: Option<usize>,
+    pub snippet_len: Option<usize>,
+    pub auto_files: Option<usize>,
+    pub auto_matches: Option<usize>,
+    /// Highlight marker preset (`arrows`, `markdown-bold`, `html-mark`, `brackets`, `none`).
+    pub highlight_style: Option<String>,
+    pub highlight_prefix: Option<String>,
+    pub highlight_suffix: Option<String>,
+    pub snippet_prefix: Option<String>,
+    pub snippet_suffix: Option<String>,
+    /// Same values as `DAIZO_PROVENANCE` (`on`, `always`, `off`).
+    pub provenance: Option<String>,
+    /// Keep a copy of `structuredContent` in `_meta` (`DAIZO_META_MIRROR`; default true).
+    pub meta_mirror: Option<bool>,
+}
+
+/// Background extraction of the next juan or part while a client pages through a work
+/// (`DAIZO_PREFETCH`, `DAIZO_PREFETCH_MAX_MB`, `DAIZO_PREFETCH_INTERVAL_MS`).
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Prefetch {
+    /// Default true.
+    pub enabled: Option<bool>,
+    /// Memory cap of the prefetched texts in MiB (default 64).
+    pub max_mb: Option<usize>,
+    /// Least time between two prefetches in milliseconds (default 200).
+    pub min_interval_ms: Option<u64>,
+}
+
+/// On-disk cache of whole-corpus search results (`DAIZO_GREP_CACHE`,
+/// `DAIZO_GREP_CACHE_MAX_ENTRIES`).
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct GrepCache {
+    /// Default true.
+    pub enabled: Option<bool>,
+    /// Searches kept per corpus (default 500).
+    pub max_entries: Option<usize>,
+}
+
+/// Loading indexes and reading recently fetched files right after `initialize`
+/// (`DAIZO_WARMUP`, `DAIZO_WARMUP_HOT_FILES`).
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Warmup {
+    /// Default false.
+    pub enabled: Option<bool>,
+    /// Recently fetched files remembered and read in (default 64).
+    pub hot_files: Option<usize>,
+}
+
+/// Per-tool request log and metrics (`DAIZO_METRICS`).
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Metrics {
+    /// Default false.
+    pub enabled: Option<bool>,
+}
+
+/// Server log `daizo-mcp.log` (`DAIZO_LOG`, `DAIZO_LOG_FORMAT`, `DAIZO_LOG_MAX_MB`).
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Log {
+    /// Level or filter directive (`warn`, `debug`, `daizo_mcp=trace`). Default `warn`.
+    pub level: Option<String>,
+    /// `text` or `json`. Default `text`.
+    pub format: Option<String>,
+    /// Size in MiB at which the log is rotated. Default 16.
+    pub max_mb: Option<u64>,
+    /// Rotated files kept (`daizo-mcp.log.1` …). Default 3.
+    pub keep: Option<usize>,
+}
+
+/// Resources a corpus scan (search, statistics) may take (`DAIZO_THREADS`,
+/// `DAIZO_SCAN_IO_MBPS`, `DAIZO_SCAN_NICE`).
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Scan {
+    /// Worker threads (default: one per core).
+    pub threads: Option<usize>,
+    /// Read rate cap in MiB/s (default: none).
+    pub io_mbps: Option<u64>,
+    /// Run scan workers at low CPU and IO priority (Linux; default false).
+    pub nice: Option<bool>,
+}
+
+/// Backend of vector search (`DAIZO_EMBED_BACKEND`, `DAIZO_EMBED_URL`, `DAIZO_EMBED_MODEL`;
+/// the API key is only read from `DAIZO_EMBED_API_KEY`).
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Embeddings {
+    /// `lexical` (default; `hash` is the same) or `http`.
+    pub backend: Option<String>,
+    /// OpenAI-compatible embeddings endpoint (`http://localhost:8080/v1/embeddings`).
+    pub url: Option<String>,
+    pub model: Option<String>,
+}
+
+/// Requests to remote sources (SAT, Jodo Shu Zensho, Adarshah, BUDA) (`DAIZO_HTTP_CONCURRENCY`,
+/// `DAIZO_HTTP_INTERVAL_MS`, `DAIZO_HTTP_MAX_PER_MINUTE`, `DAIZO_HTTP_USER_AGENT`,
+/// `DAIZO_HTTP_ROBOTS`); see [`crate::politeness`].
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Network {
+    /// Requests in flight at once, over all hosts (default 4).
+    pub concurrency: Option<usize>,
+    /// Least time between two requests to the same host in milliseconds (default 500).
+    pub host_interval_ms: Option<u64>,
+    /// Requests to the same host in any 60 seconds; 0 for no cap (default 60).
+    pub max_per_minute: Option<u32>,
+    /// User-Agent sent with every request.
+    pub user_agent: Option<String>,
+    /// Fetch and obey each host's robots.txt (default true).
+    pub robots: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct CorpusConfig {
+    /// Directory searched for this corpus instead of the one under the daizo home
+    /// (for tipitaka, the `romn` directory itself).
+    pub root: Option<PathBuf>,
+}
+
+pub fn config_path() -> PathBuf {
+    match std::env::var("DAIZO_CONFIG") {
+        Ok(p) if !p.is_empty() => PathBuf::from(p),
+        _ => daizo_home().join("config.toml"),
+    }
+}
+
+impl Config {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        toml::from_str(s).map_err(|e| e.to_string())
+    }
+
+    /// Read `path`; a missing file gives the defaults.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let s = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut c = Config::parse(&s).map_err(|e| format!("{}: {}", path.display(), e))?;
+        c.source = Some(path.to_path_buf());
+        Ok(c)
+    }
+
+    pub fn corpus_root(&self, corpus: &str) -> Option<PathBuf> {
+        self.corpora.get(corpus).and_then(|c| c.root.clone())
+    }
+}
+
+static CURRENT: OnceLock<RwLock<Arc<Config>>> = OnceLock::new();
+
+fn slot() -> &'static RwLock<Arc<Config>> {
+    CURRENT.get_or_init(|| {
+        let c = Config::load(&config_path()).unwrap_or_else(|e| {
+            eprintln!("[daizo-config] {} (using defaults)", e);
+            Config::default()
+        });
+        RwLock::new(Arc::new(c))
+    })
+}
+
+/// Settings in effect (loaded on first use).
+pub fn current() -> Arc<Config> {
+    slot().read().map(|c| c.clone()).unwrap_or_default()
+}
+
+/// Re-read the config file. On a parse error the previous settings stay in effect.
+pub fn reload() -> Result<Arc<Config>, String> {
+    let c = Arc::new(Config::load(&config_path())?);
+    if let Ok(mut g) = slot().write() {
+        *g = c.clone();
+    }
+    Ok(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_files_keep_defaults() {
+        let c = Config::parse(
+            r#"
+[scoring]
+taisho_bias = 0.05
+
+[output]
+max_chars = 12000
+highlight_prefix = "**"
+
+[prefetch]
+max_mb = 16
+
+[grep_cache]
+enabled = false
+
+[warmup]
+enabled = true
+
+[corpora.cbeta]
+root = "/data/xml-p5"
+"#,
+        )
+        .unwrap();
+        assert_eq!(c.scoring.taisho_bias, 0.05);
+        assert_eq!(c.scoring.min_score, 0.1);
+        assert_eq!(c.output.max_chars, Some(12000));
+        assert_eq!(c.output.snippet_len, None);
+        assert_eq!(c.prefetch.max_mb, Some(16));
+        assert_eq!(c.prefetch.enabled, None);
+        assert_eq!(c.grep_cache.enabled, Some(false));
+        assert_eq!(c.grep_cache.max_entries, None);
+        assert_eq!(c.warmup.enabled, Some(true));
+        assert_eq!(c.warmup.hot_files, None);
+        assert_eq!(c.corpus_root("cbeta"), Some(PathBuf::from("/data/xml-p5")));
+        assert_eq!(c.corpus_root("gretil"), None);
+        assert!(Config::parse("[scoring]\nmin_score = \"x\"").is_err());
+    }
+}
@@ -0,0 +1,292 @@
+use crate::{GrepResult, IndexEntry};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Normalized date range of a work (translation/composition period).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct WorkDate {
+    /// Canonical period label (e.g. "後秦" for "姚秦"), or the year span for numeric dates.
+    pub label: String,
+    pub start_year: i32,
+    pub end_year: i32,
+}
+
+/// Chinese dynasty/period prefixes used in CBETA bylines: (alias, canonical label, start, end).
+/// Aliases are matched by longest prefix, so "東晉" wins over "晉" and "姚秦" maps to "後秦".
+static PERIODS: &[(&str, &str, i32, i32)] = &[
+    ("後漢", "後漢", 25, 220),
+    ("東漢", "後漢", 25, 220),
+    ("曹魏", "曹魏", 220, 265),
+    ("吳", "吳", 222, 280),
+    ("西晉", "西晉", 265, 316),
+    ("東晉", "東晉", 317, 420),
+    ("前秦", "前秦", 351, 394),
+    ("苻秦", "前秦", 351, 394),
+    ("後秦", "後秦", 384, 417),
+    ("姚秦", "後秦", 384, 417),
+    ("西秦", "西秦", 385, 431),
+    ("乞伏秦", "西秦", 385, 431),
+    ("北涼", "北涼", 397, 439),
+    ("劉宋", "劉宋", 420, 479),
+    ("南齊", "南齊", 479, 502),
+    ("蕭齊", "南齊", 479, 502),
+    ("北魏", "北魏", 386, 534),
+    ("元魏", "北魏", 386, 534),
+    ("後魏", "北魏", 386, 534),
+    ("東魏", "東魏", 534, 550),
+    ("西魏", "西魏", 535, 557),
+    ("北齊", "北齊", 550, 577),
+    ("高齊", "北齊", 550, 577),
+    ("北周", "北周", 557, 581),
+    ("宇文周", "北周", 557, 581),
+    ("梁", "梁", 502, 557),
+    ("陳", "陳", 557, 589),
+    ("隋", "隋", 581, 618),
+    ("唐", "唐", 618, 907),
+    ("後唐", "後唐", 923, 937),
+    ("後晉", "後晉", 936, 947),
+    ("後周", "後周", 951, 960),
+    ("南唐", "南唐", 937, 976),
+    ("吳越", "吳越", 907, 978),
+    ("北宋", "宋", 960, 1279),
+    ("南宋", "宋", 960, 1279),
+    ("宋", "宋", 960, 1279),
+    ("遼", "遼", 916, 1125),
+    ("金", "金", 1115, 1234),
+    ("西夏", "西夏", 1038, 1227),
+    ("元", "元", 1271, 1368),
+    ("明", "明", 1368, 1644),
+    ("清", "清", 1644, 1912),
+    ("民國", "民國", 1912, 1949),
+    ("晉", "晉", 265, 420),
+    ("魏", "曹魏", 220, 265),
+    ("秦", "後秦", 384, 417),
+    ("漢", "後漢", 25, 220),
+    ("齊", "南齊", 479, 502),
+];
+
+fn lookup_period(token: &str) -> Option<WorkDate> {
+    let mut best: Option<(&str, &str, i32, i32)> = None;
+    for &(alias, label, s, e) in PERIODS {
+        if token.starts_with(alias) && best.map(|b| alias.len() > b.0.len()).unwrap_or(true) {
+            best = Some((alias, label, s, e));
+        }
+    }
+    best.map(|(_, label, s, e)| WorkDate {
+        label: label.to_string(),
+        start_year: s,
+        end_year: e,
+    })
+}
+
+/// Parse a byline such as "姚秦 鳩摩羅什譯" or "唐 玄奘譯" into a dynasty range.
+pub fn parse_byline_period(byline: &str) -> Option<WorkDate> {
+    for part in byline.split(['・', '|', ';', '；']) {
+        let t = part.trim();
+        if t.is_empty() {
+            continue;
+        }
+        let head = t.split_whitespace().next().unwrap_or(t);
+        if let Some(d) = lookup_period(head) {
+            return Some(d);
+        }
+    }
+    None
+}
+
+/// Parse a numeric date/year string ("1234", "c. 1200-1250", "-150") into a range.
+/// A dash directly after a digit is a range separator; otherwise it marks a BCE year.
+pub fn parse_year_range(s: &str) -> Option<WorkDate> {
+    let re = regex::Regex::new(r"\d+").ok()?;
+    let mut years: Vec<i32> = Vec::new();
+    for m in re.find_iter(s) {
+        let before = &s[..m.start()];
+        let neg = before.ends_with('-')
+            && !before[..before.len() - 1]
+                .chars()
+                .last()
+                .map(|c| c.is_ascii_digit())
+                .unwrap_or(false);
+        let digits = m.as_str();
+        if digits.len() > 4 || (!neg && digits.len() < 3) {
+            continue;
+        }
+        if let Ok(n) = digits.parse::<i32>() {
+            years.push(if neg { -n } else { n });
+        }
+        if years.len() == 2 {
+            break;
+        }
+    }
+    let first = *years.first()?;
+    let last = *years.get(1).unwrap_or(&first);
+    Some(WorkDate {
+        label: if first == last {
+            first.to_string()
+        } else {
+            format!("{}-{}", first, last)
+        },
+        start_year: first.min(last),
+        end_year: first.max(last),
+    })
+}
+
+/// Best-effort date range for an index entry: bylines (translator/author/respAll) first, then `date`.
+pub fn work_date_for_entry(entry: &IndexEntry) -> Option<WorkDate> {
+    let meta = entry.meta.as_ref()?;
+    for k in ["translator", "author", "respAll"] {
+        if let Some(v) = meta.get(k) {
+            if let Some(d) = parse_byline_period(v) {
+                return Some(d);
+            }
+        }
+    }
+    meta.get("date").and_then(|d| parse_year_range(d))
+}
+
+/// One row of a usage-over-time profile.
+#[derive(Clone, Debug, Serialize)]
+pub struct TemporalBucket {
+    pub label: String,
+    pub start_year: Option<i32>,
+    pub end_year: Option<i32>,
+    pub works: usize,
+    pub matches: usize,
+    pub work_ids: Vec<String>,
+}
+
+/// Bucket grep results by the date ranges of the containing works.
+/// `by_century` groups by the century of the range midpoint instead of by period label.
+/// Undated works are collected in a trailing "undated" bucket.
+pub fn temporal_profile(
+    results: &[GrepResult],
+    index: &[IndexEntry],
+    by_century: bool,
+) -> Vec<TemporalBucket> {
+    let mut by_key: HashMap<&str, &IndexEntry> = HashMap::with_capacity(index.len() * 2);
+    for e in index {
+        by_key.insert(e.id.as_str(), e);
+        by_key.insert(e.path.as_str(), e);
+    }
+    let mut buckets: BTreeMap<(i32, String), TemporalBucket> = BTreeMap::new();
+    let mut undated = TemporalBucket {
+        label: "undated".to_string(),
+        start_year: None,
+        end_year: None,
+        works: 0,
+        matches: 0,
+        work_ids: Vec::new(),
+    };
+    for r in results {
+        let date = by_key
+            .get(r.file_id.as_str())
+            .or_else(|| by_key.get(r.file_path.as_str()))
+            .and_then(|e| work_date_for_entry(e));
+        let Some(d) = date else {
+            undated.works += 1;
+            undated.matches += r.total_matches;
+            undated.work_ids.push(r.file_id.clone());
+            continue;
+        };
+        let (key, label, s, e) = if by_century {
+            let mid = (d.start_year + d.end_year).div_euclid(2);
+            let c0 = mid.div_euclid(100) * 100;
+            (c0, format!("{}s", c0), c0, c0 + 99)
+        } else {
+            (d.start_year, d.label.clone(), d.start_year, d.end_year)
+        };
+        let b = buckets
+            .entry((key, label.clone()))
+            .or_insert_with(|| TemporalBucket {
+                label,
+                start_year: Some(s),
+                end_year: Some(e),
+                works: 0,
+                matches: 0,
+                work_ids: Vec::new(),
+            });
+        b.works += 1;
+        b.matches += r.total_matches;
+        b.work_ids.push(r.file_id.clone());
+    }
+    let mut out: Vec<TemporalBucket> = buckets.into_values().collect();
+    if undated.works > 0 {
+        out.push(undated);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FetchHints;
+
+    fn entry(id: &str, author: &str) -> IndexEntry {
+        let mut meta = BTreeMap::new();
+        meta.insert("author".to_string(), author.to_string());
+        IndexEntry {
+            id: id.into(),
+            title: id.into(),
+            path: String::new(),
+            meta: Some(meta),
+        }
+    }
+
+    fn hit(id: &str, n: usize) -> GrepResult {
+        GrepResult {
+            file_path: String::new(),
+            file_id: id.into(),
+            title: id.into(),
+            matches: Vec::new(),
+            total_matches: n,
+            fetch_hints: FetchHints {
+                recommended_parts: Vec::new(),
+                total_content_size: None,
+                structure_info: Vec::new(),
+                ..Default::default()
+            },
+            score: None,
+            titles: None,
+        }
+    }
+
+    #[test]
+    fn byline_periods() {
+        let d = parse_byline_period("姚秦 鳩摩羅什譯").unwrap();
+        assert_eq!((d.label.as_str(), d.start_year), ("後秦", 384));
+        assert_eq!(
+            parse_byline_period("東晉 佛陀跋陀羅譯").unwrap().label,
+            "東晉"
+        );
+        assert_eq!(parse_byline_period("唐 玄奘譯").unwrap().end_year, 907);
+        assert!(parse_byline_period("失譯").is_none());
+        let y = parse_year_range("1200-1250").unwrap();
+        assert_eq!((y.start_year, y.end_year), (1200, 1250));
+        assert_eq!(parse_year_range("-150").unwrap().start_year, -150);
+    }
+
+    #[test]
+    fn profile_buckets_in_chronological_order() {
+        let idx = vec![
+            entry("T0262", "姚秦 鳩摩羅什譯"),
+            entry("T0220", "唐 玄奘譯"),
+            entry("T0223", "後秦 鳩摩羅什譯"),
+            entry("T9999", "失譯"),
+        ];
+        let res = vec![
+            hit("T0220", 7),
+            hit("T0262", 3),
+            hit("T0223", 2),
+            hit("T9999", 1),
+        ];
+        let prof = temporal_profile(&res, &idx, false);
+        let labels: Vec<&str> = prof.iter().map(|b| b.label.as_str()).collect();
+        assert_eq!(labels, vec!["後秦", "唐", "undated"]);
+        assert_eq!(prof[0].works, 2);
+        assert_eq!(prof[0].matches, 5);
+        let cent = temporal_profile(&res, &idx, true);
+        assert_eq!(cent[0].label, "400s");
+        assert_eq!(cent[0].works, 2);
+        assert_eq!(cent[0].matches, 5);
+    }
+}
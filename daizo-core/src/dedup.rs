@@ -0,0 +1,196 @@
+use crate::text_utils::{
+    detect_sanskrit_scheme, normalized_sanskrit, transliterate_sanskrit, SanskritScheme,
+};
+use crate::IndexEntry;
+
+/// Sketch size (number of smallest shingle hashes kept per text).
+const SKETCH_K: usize = 128;
+/// Shingle width in bytes of the folded text.
+const SHINGLE: usize = 8;
+/// Estimated Jaccard similarity above which two files are treated as the same work.
+pub const DUPLICATE_THRESHOLD: f32 = 0.5;
+
+/// Content fingerprint of a corpus file: a bottom-k MinHash sketch of the folded body text
+/// plus the numbers used to pick the richest copy of a work.
+#[derive(Clone, Debug, Default)]
+pub struct ContentSignature {
+    /// Length of the folded body text in bytes.
+    pub chars: usize,
+    /// Structural elements (div, head, lg, l, p, note) in the body.
+    pub elements: usize,
+    sketch: Vec<u64>,
+}
+
+impl ContentSignature {
+    /// Larger is richer: more text first, markup as a tie-breaker of sorts.
+    pub fn richness(&self) -> usize {
+        self.chars + 20 * self.elements
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in bytes {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    h
+}
+
+/// Fold body text so that encodings of the same work compare equal: Harvard-Kyoto, SLP1
+/// and Devanagari are converted to IAST, then case, diacritics, spacing and punctuation are
+/// dropped (IAST `kṣetra`, Harvard-Kyoto `kSetra` and `क्षेत्र` all become `ksetra`).
+pub fn fold_for_comparison(text: &str) -> String {
+    match detect_sanskrit_scheme(text) {
+        SanskritScheme::Iast => normalized_sanskrit(text),
+        from => normalized_sanskrit(&transliterate_sanskrit(text, from, SanskritScheme::Iast)),
+    }
+}
+
+/// Build the signature of a file's body text.
+pub fn content_signature(body_text: &str, elements: usize) -> ContentSignature {
+    let folded = fold_for_comparison(body_text);
+    let bytes = folded.as_bytes();
+    let mut hashes: Vec<u64> = if bytes.len() < SHINGLE {
+        vec![fnv1a(bytes)]
+    } else {
+        bytes.windows(SHINGLE).map(fnv1a).collect()
+    };
+    hashes.sort_unstable();
+    hashes.dedup();
+    hashes.truncate(SKETCH_K);
+    ContentSignature {
+        chars: bytes.len(),
+        elements,
+        sketch: hashes,
+    }
+}
+
+/// Estimated Jaccard similarity of two signatures (bottom-k estimator).
+pub fn similarity(a: &ContentSignature, b: &ContentSignature) -> f32 {
+    if a.sketch.is_empty() || b.sketch.is_empty() {
+        return 0.0;
+    }
+    // The k smallest hashes of the union, and how many of them both sketches contain.
+    let (mut i, mut j, mut seen, mut shared) = (0usize, 0usize, 0usize, 0usize);
+    while seen < SKETCH_K && (i < a.sketch.len() || j < b.sketch.len()) {
+        match (a.sketch.get(i), b.sketch.get(j)) {
+            (Some(x), Some(y)) if x == y => {
+                shared += 1;
+                i += 1;
+                j += 1;
+            }
+            (Some(x), Some(y)) if x < y => i += 1,
+            (Some(_), None) => i += 1,
+            _ => j += 1,
+        }
+        seen += 1;
+    }
+    shared as f32 / seen as f32
+}
+
+/// Group signatures whose similarity reaches `threshold` (single linkage).
+/// Only groups with two or more members are returned; indices are in input order.
+pub fn group_duplicates(sigs: &[ContentSignature], threshold: f32) -> Vec<Vec<usize>> {
+    let mut parent: Vec<usize> = (0..sigs.len()).collect();
+    fn find(parent: &mut [usize], mut x: usize) -> usize {
+        while parent[x] != x {
+            parent[x] = parent[parent[x]];
+            x = parent[x];
+        }
+        x
+    }
+    for i in 0..sigs.len() {
+        for j in i + 1..sigs.len() {
+            // Very different lengths cannot reach the threshold; skip the sketch walk.
+            let (a, b) = (sigs[i].chars.max(1), sigs[j].chars.max(1));
+            if (a.min(b) as f32) / (a.max(b) as f32) < threshold {
+                continue;
+            }
+            if similarity(&sigs[i], &sigs[j]) >= threshold {
+                let (ri, rj) = (find(&mut parent, i), find(&mut parent, j));
+                parent[ri.max(rj)] = ri.min(rj);
+            }
+        }
+    }
+    let mut groups: std::collections::BTreeMap<usize, Vec<usize>> = Default::default();
+    for i in 0..sigs.len() {
+        let r = find(&mut parent, i);
+        groups.entry(r).or_default().push(i);
+    }
+    groups.into_values().filter(|g| g.len() > 1).collect()
+}
+
+/// Record duplicate groups in index metadata: every entry gets `contentChars`/`richness`,
+/// and members of a group get `dupGroup` (id of the richest copy) and `dupMembers`.
+pub fn annotate_duplicates(entries: &mut [IndexEntry], sigs: &[ContentSignature]) {
+    for (e, s) in entries.iter_mut().zip(sigs) {
+        let m = e.meta.get_or_insert_with(Default::default);
+        m.insert("contentChars".to_string(), s.chars.to_string());
+        m.insert("richness".to_string(), s.richness().to_string());
+    }
+    for group in group_duplicates(sigs, DUPLICATE_THRESHOLD) {
+        let richest = *group
+            .iter()
+            .max_by_key(|&&i| (sigs[i].richness(), std::cmp::Reverse(i)))
+            .unwrap();
+        let rich_id = entries[richest].id.clone();
+        let members = group
+            .iter()
+            .map(|&i| entries[i].id.clone())
+            .collect::<Vec<_>>()
+            .join(" | ");
+        for &i in &group {
+            let m = entries[i].meta.get_or_insert_with(Default::default);
+            m.insert("dupGroup".to_string(), rich_id.clone());
+            m.insert("dupMembers".to_string(), members.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERSE: &str =
+        "dharmakṣetre kurukṣetre samavetā yuyutsavaḥ māmakāḥ pāṇḍavāś caiva kim akurvata saṃjaya";
+
+    #[test]
+    fn same_work_in_other_encodings_is_similar() {
+        let iast = content_signature(VERSE, 2);
+        let hk = content_signature(
+            "dharmakSetre kurukSetre samavetA yuyutsavaH mAmakAH pANDavAz caiva kim akurvata saMjaya",
+            0,
+        );
+        let deva = content_signature(
+            &transliterate_sanskrit(VERSE, SanskritScheme::Iast, SanskritScheme::Devanagari),
+            0,
+        );
+        assert!(similarity(&iast, &hk) > 0.8);
+        assert!(similarity(&iast, &deva) > 0.8);
+        let other = content_signature("atha yogānuśāsanam yogaś cittavṛttinirodhaḥ", 0);
+        assert!(similarity(&iast, &other) < 0.2);
+    }
+
+    #[test]
+    fn groups_pick_the_richest_copy() {
+        let mk = |id: &str| IndexEntry {
+            id: id.to_string(),
+            title: id.to_string(),
+            path: format!("/x/{}.xml", id),
+            meta: None,
+        };
+        let mut entries = vec![mk("a"), mk("b"), mk("c")];
+        let sigs = vec![
+            content_signature(VERSE, 1),
+            content_signature("atha yogānuśāsanam yogaś cittavṛttinirodhaḥ", 3),
+            content_signature(VERSE, 9),
+        ];
+        annotate_duplicates(&mut entries, &sigs);
+        let g = |i: usize, k: &str| entries[i].meta.as_ref().unwrap().get(k).cloned();
+        assert_eq!(g(0, "dupGroup").as_deref(), Some("c"));
+        assert_eq!(g(2, "dupMembers").as_deref(), Some("a | c"));
+        assert_eq!(g(1, "dupGroup"), None);
+        assert!(g(1, "richness").is_some());
+    }
+}
@@ -0,0 +1,376 @@
+//! Character-level diff of two texts, such as two translations of one sūtra or a root text
+//! and its commentary.
+//!
+//! Whole translations are too long for a plain LCS table, so the texts are first aligned on
+//! anchors: runs of [`DiffOptions::anchor_len`] characters that occur exactly once in each
+//! text, chained in order by a longest increasing subsequence and extended as far as the
+//! texts agree. Only the gaps between anchors are diffed character by character. Offsets
+//! are char offsets into the texts as given, which for extracted plain text are the
+//! `startChar` values of the fetch tools.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use unicode_normalization::char::is_combining_mark;
+
+/// What is compared.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Skip whitespace and punctuation (CBETA punctuation is editorial, not in the witness).
+    pub ignore_punctuation: bool,
+    /// Compare variant characters as equal (`經` / `経`).
+    pub fold_variants: bool,
+    /// Length of the unique runs used as anchors.
+    pub anchor_len: usize,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            ignore_punctuation: true,
+            fold_variants: true,
+            anchor_len: 4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Equal,
+    Delete,
+    Insert,
+    Replace,
+}
+
+impl Change {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Change::Equal => "equal",
+            Change::Delete => "delete",
+            Change::Insert => "insert",
+            Change::Replace => "replace",
+        }
+    }
+}
+
+/// A stretch of the alignment; ranges are char offsets, end exclusive.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hunk {
+    pub change: Change,
+    pub a: (usize, usize),
+    pub b: (usize, usize),
+    pub a_text: String,
+    pub b_text: String,
+}
+
+/// Where the two texts were pinned together, and for how many compared characters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Anchor {
+    pub a: usize,
+    pub b: usize,
+    pub len: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextDiff {
+    pub hunks: Vec<Hunk>,
+    pub anchors: Vec<Anchor>,
+    /// Compared characters of each text (after skipping punctuation).
+    pub a_len: usize,
+    pub b_len: usize,
+    /// Compared characters the texts share.
+    pub equal: usize,
+}
+
+impl TextDiff {
+    /// Dice coefficient of the compared characters: 1.0 for identical texts.
+    pub fn similarity(&self) -> f32 {
+        if self.a_len + self.b_len == 0 {
+            return 1.0;
+        }
+        2.0 * self.equal as f32 / (self.a_len + self.b_len) as f32
+    }
+
+    /// Hunks that are not equal.
+    pub fn changes(&self) -> impl Iterator<Item = &Hunk> {
+        self.hunks.iter().filter(|h| h.change != Change::Equal)
+    }
+
+    /// Inline rendering: `[-deleted-]{+inserted+}`, equal stretches longer than
+    /// `2 * context` characters shortened to their ends around ` … `.
+    pub fn render(&self, context: usize) -> String {
+        let mut out = String::new();
+        for h in &self.hunks {
+            match h.change {
+                Change::Equal => {
+                    let cs: Vec<char> = h.a_text.chars().collect();
+                    if cs.len() > 2 * context + 3 {
+                        out.extend(&cs[..context]);
+                        out.push_str(" … ");
+                        out.extend(&cs[cs.len() - context..]);
+                    } else {
+                        out.push_str(&h.a_text);
+                    }
+                }
+                _ => {
+                    if !h.a_text.is_empty() {
+                        out.push_str(&format!("[-{}-]", h.a_text));
+                    }
+                    if !h.b_text.is_empty() {
+                        out.push_str(&format!("{{+{}+}}", h.b_text));
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Compared characters of `text` with their char offsets.
+fn units(text: &str, opts: &DiffOptions) -> (Vec<char>, Vec<usize>) {
+    let table = opts.fold_variants.then(crate::variants::variant_table);
+    let mut keys = Vec::new();
+    let mut offsets = Vec::new();
+    for (i, c) in text.chars().enumerate() {
+        if opts.ignore_punctuation && !(c.is_alphanumeric() || is_combining_mark(c)) {
+            continue;
+        }
+        keys.push(table.map(|t| t.canonical(c)).unwrap_or(c));
+        offsets.push(i);
+    }
+    (keys, offsets)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Same,
+    Del,
+    Ins,
+}
+
+/// Unique-run anchors of `a` and `b`, in order in both.
+fn anchor_chain(a: &[char], b: &[char], k: usize) -> Vec<(usize, usize)> {
+    if k == 0 || a.len() < k || b.len() < k {
+        return Vec::new();
+    }
+    let mut in_a: HashMap<&[char], (u32, usize)> = HashMap::new();
+    for (i, w) in a.windows(k).enumerate() {
+        let e = in_a.entry(w).or_insert((0, i));
+        e.0 += 1;
+    }
+    let mut in_b: HashMap<&[char], (u32, usize)> = HashMap::new();
+    for (i, w) in b.windows(k).enumerate() {
+        let e = in_b.entry(w).or_insert((0, i));
+        e.0 += 1;
+    }
+    let mut pairs: Vec<(usize, usize)> = in_a
+        .iter()
+        .filter(|(_, (n, _))| *n == 1)
+        .filter_map(|(w, (_, i))| match in_b.get(w) {
+            Some((1, j)) => Some((*i, *j)),
+            _ => None,
+        })
+        .collect();
+    pairs.sort_unstable();
+    // Longest increasing subsequence of the b positions (patience sorting).
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev: Vec<Option<usize>> = vec![None; pairs.len()];
+    for (i, &(_, bj)) in pairs.iter().enumerate() {
+        let pos = tails.partition_point(|&t| pairs[t].1 < bj);
+        if pos > 0 {
+            prev[i] = Some(tails[pos - 1]);
+        }
+        if pos == tails.len() {
+            tails.push(i);
+        } else {
+            tails[pos] = i;
+        }
+    }
+    let mut chain = Vec::new();
+    let mut cur = tails.last().copied();
+    while let Some(i) = cur {
+        chain.push(pairs[i]);
+        cur = prev[i];
+    }
+    chain.reverse();
+    chain
+}
+
+/// Cells of the LCS table above which a gap is reported as one replacement.
+const MAX_CELLS: usize = 4_000_000;
+
+/// Edit script between two short sequences (LCS, common prefix and suffix first).
+fn gap_steps(a: &[char], b: &[char], out: &mut Vec<Step>) {
+    let pre = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let (a2, b2) = (&a[pre..], &b[pre..]);
+    let suf = a2
+        .iter()
+        .rev()
+        .zip(b2.iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a3, b3) = (&a2[..a2.len() - suf], &b2[..b2.len() - suf]);
+    out.extend(std::iter::repeat_n(Step::Same, pre));
+    let (n, m) = (a3.len(), b3.len());
+    if n == 0 || m == 0 || n * m > MAX_CELLS {
+        out.extend(std::iter::repeat_n(Step::Del, n));
+        out.extend(std::iter::repeat_n(Step::Ins, m));
+    } else {
+        // lcs[i][j]: LCS length of a3[i..] and b3[j..]
+        let w = m + 1;
+        let mut lcs = vec![0u32; (n + 1) * w];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i * w + j] = if a3[i] == b3[j] {
+                    lcs[(i + 1) * w + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * w + j].max(lcs[i * w + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n && j < m {
+            if a3[i] == b3[j] {
+                out.push(Step::Same);
+                i += 1;
+                j += 1;
+            } else if lcs[(i + 1) * w + j] >= lcs[i * w + j + 1] {
+                out.push(Step::Del);
+                i += 1;
+            } else {
+                out.push(Step::Ins);
+                j += 1;
+            }
+        }
+        out.extend(std::iter::repeat_n(Step::Del, n - i));
+        out.extend(std::iter::repeat_n(Step::Ins, m - j));
+    }
+    out.extend(std::iter::repeat_n(Step::Same, suf));
+}
+
+/// Diff `a` against `b`.
+pub fn diff(a: &str, b: &str, opts: &DiffOptions) -> TextDiff {
+    let (ka, oa) = units(a, opts);
+    let (kb, ob) = units(b, opts);
+    let mut steps = Vec::with_capacity(ka.len().max(kb.len()));
+    let mut anchors = Vec::new();
+    let (mut pa, mut pb) = (0, 0);
+    for (ia, ib) in anchor_chain(&ka, &kb, opts.anchor_len) {
+        // Skip anchors inside a run already matched.
+        if ia < pa || ib < pb {
+            continue;
+        }
+        gap_steps(&ka[pa..ia], &kb[pb..ib], &mut steps);
+        let len = ka[ia..]
+            .iter()
+            .zip(&kb[ib..])
+            .take_while(|(x, y)| x == y)
+            .count();
+        anchors.push(Anchor {
+            a: oa[ia],
+            b: ob[ib],
+            len,
+        });
+        steps.extend(std::iter::repeat_n(Step::Same, len));
+        (pa, pb) = (ia + len, ib + len);
+    }
+    gap_steps(&ka[pa..], &kb[pb..], &mut steps);
+
+    let (ca, cb): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // Char range of units [i, j) of one text: from the first unit up to the next unit, so
+    // that skipped punctuation stays with the text before it.
+    let span = |offs: &[usize], total: usize, i: usize, j: usize| -> (usize, usize) {
+        if i == j {
+            let at = offs.get(i).copied().unwrap_or(total);
+            return (at, at);
+        }
+        let start = if i == 0 { 0 } else { offs[i] };
+        (start, offs.get(j).copied().unwrap_or(total))
+    };
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let (mut i, mut j, mut s) = (0, 0, 0);
+    let mut equal = 0;
+    while s < steps.len() {
+        let same = steps[s] == Step::Same;
+        let (i0, j0) = (i, j);
+        while s < steps.len() && (steps[s] == Step::Same) == same {
+            match steps[s] {
+                Step::Same => {
+                    i += 1;
+                    j += 1;
+                }
+                Step::Del => i += 1,
+                Step::Ins => j += 1,
+            }
+            s += 1;
+        }
+        let change = match (same, i > i0, j > j0) {
+            (true, _, _) => Change::Equal,
+            (false, true, true) => Change::Replace,
+            (false, true, false) => Change::Delete,
+            _ => Change::Insert,
+        };
+        if same {
+            equal += i - i0;
+        }
+        let ra = span(&oa, ca.len(), i0, i);
+        let rb = span(&ob, cb.len(), j0, j);
+        hunks.push(Hunk {
+            change,
+            a: ra,
+            b: rb,
+            a_text: ca[ra.0..ra.1].iter().collect(),
+            b_text: if same {
+                String::new()
+            } else {
+                cb[rb.0..rb.1].iter().collect()
+            },
+        });
+    }
+    TextDiff {
+        hunks,
+        anchors,
+        a_len: ka.len(),
+        b_len: kb.len(),
+        equal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translations_align_on_anchors() {
+        let a = "如是我聞。一時佛在舍衛國祇樹給孤獨園，與大比丘眾千二百五十人俱。";
+        let b = "如是我聞：一時，佛住舍衛國祇樹給孤独園，與大比丘衆千二百五十人俱";
+        let d = diff(a, b, &DiffOptions::default());
+        let changes: Vec<(Change, &str, &str)> = d
+            .changes()
+            .map(|h| (h.change, h.a_text.as_str(), h.b_text.as_str()))
+            .collect();
+        // Punctuation and the variants 独/獨 and 衆/眾 are not differences.
+        assert_eq!(changes, [(Change::Replace, "在", "住")]);
+        let r = d
+            .hunks
+            .iter()
+            .find(|h| h.change == Change::Replace)
+            .unwrap();
+        assert_eq!((r.a, r.b), ((8, 9), (9, 10)));
+        assert!(!d.anchors.is_empty() && d.anchors[0].a == 0);
+        assert!(d.similarity() > 0.95 && d.similarity() < 1.0);
+        assert_eq!(d.render(2), "如是 … 時佛[-在-]{+住+}舍衛 … 俱。");
+
+        let strict = DiffOptions {
+            ignore_punctuation: false,
+            fold_variants: false,
+            ..Default::default()
+        };
+        let d = diff("evaṃ me sutaṃ", "evam me sutaṃ ekaṃ", &strict);
+        let kinds: Vec<Change> = d.changes().map(|h| h.change).collect();
+        assert_eq!(kinds, [Change::Replace, Change::Insert]);
+        assert_eq!(d.changes().last().unwrap().b_text, " ekaṃ");
+        assert_eq!(diff("", "", &strict).similarity(), 1.0);
+    }
+}
@@ -0,0 +1,750 @@
+//! Vector search: passages of a corpus embedded as vectors and searched by similarity.
+//!
+//! `daizo-cli embed-index` cuts each text into passages of a few hundred characters, embeds
+//! them with an [`Embedder`] and stores a [`VectorIndex`] in `~/.daizo/cache/embeddings/`.
+//! What "similar" means depends on the backend:
+//!
+//! - `lexical` (default, also accepted as `hash`): feature hashing of Han character
+//!   uni/bigrams and of words and their trigrams elsewhere. No model and no network, and no
+//!   notion of meaning: it finds passages that share vocabulary, not paraphrases.
+//! - `http` (feature `http-embeddings`): any OpenAI-compatible `/v1/embeddings` endpoint
+//!   (`DAIZO_EMBED_URL`, `DAIZO_EMBED_MODEL`, `DAIZO_EMBED_API_KEY`). Only an embedding
+//!   model served this way — a hosted API, or a local model behind text-embeddings-inference,
+//!   Ollama or llama.cpp — compares passages by meaning. Its requests are spaced like every
+//!   other remote request ([`crate::politeness`]).
+//!
+//! The index is an inverted file: k-means centroids over the passage vectors, each with the
+//! list of passages nearest to it. A query is compared with the centroids and only the
+//! passages of the `nprobe` closest lists are scored.
+
+use crate::fetch_cache::write_atomic;
+use crate::juan_map::JuanMap;
+use crate::path_resolver::cache_dir;
+#[cfg(feature = "http-embeddings")]
+use crate::politeness::{self, Policy};
+use crate::{progress, IndexEntry};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "http-embeddings")]
+use std::time::Instant;
+
+/// Dimensions of the lexical backend.
+pub const HASH_DIMS: usize = 256;
+/// Default passage length in characters of plain text.
+pub const PASSAGE_CHARS: usize = 400;
+/// Default number of inverted lists probed per query.
+pub const NPROBE: usize = 8;
+
+/// Turns texts into vectors. An index remembers [`Embedder::model`] and is only queried
+/// through an embedder of the same model.
+pub trait Embedder: Send + Sync {
+    fn model(&self) -> String;
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String>;
+}
+
+fn fnv1a(s: &str) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in s.bytes() {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    h
+}
+
+fn is_han(c: char) -> bool {
+    matches!(c as u32, 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x3FFFF)
+}
+
+fn normalize(v: &mut [f32]) {
+    let n = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if n > 0.0 {
+        v.iter_mut().for_each(|x| *x /= n);
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// The lexical backend: signed feature hashing of characters and words (see the module
+/// docs). Its model is `hash-<dims>`.
+pub struct LexicalEmbedder {
+    pub dims: usize,
+}
+
+impl LexicalEmbedder {
+    fn features(text: &str) -> Vec<(String, f32)> {
+        let mut out = Vec::new();
+        let mut han: Vec<char> = Vec::new();
+        let mut word = String::new();
+        let flush_word = |word: &mut String, out: &mut Vec<(String, f32)>| {
+            let w = crate::fold::fold_diacritics(&word.to_lowercase());
+            let cs: Vec<char> = format!("^{}$", w).chars().collect();
+            if cs.len() > 4 {
+                for t in cs.windows(3) {
+                    out.push((t.iter().collect(), 0.5));
+                }
+            }
+            if !w.is_empty() {
+                out.push((w, 1.0));
+            }
+            word.clear();
+        };
+        for c in text.chars().chain(std::iter::once(' ')) {
+            if is_han(c) {
+                if !word.is_empty() {
+                    flush_word(&mut word, &mut out);
+                }
+                out.push((c.to_string(), 0.5));
+                if let Some(p) = han.last() {
+                    out.push(([*p, c].iter().collect(), 1.0));
+                }
+                han.push(c);
+            } else if c.is_alphanumeric() {
+                han.clear();
+                word.push(c);
+            } else {
+                han.clear();
+                if !word.is_empty() {
+                    flush_word(&mut word, &mut out);
+                }
+            }
+        }
+        out
+    }
+
+    fn embed_one(&self, text: &str) -> Vec<f32> {
+        let mut counts: HashMap<String, f32> = HashMap::new();
+        for (f, w) in Self::features(text) {
+            *counts.entry(f).or_default() += w;
+        }
+        let mut v = vec![0f32; self.dims];
+        for (f, w) in counts {
+            let h = fnv1a(&f);
+            let sign = if h >> 63 == 0 { 1.0 } else { -1.0 };
+            // Sublinear term frequency, so a repeated formula does not dominate a passage.
+            v[(h % self.dims as u64) as usize] += sign * (1.0 + w.ln_1p());
+        }
+        normalize(&mut v);
+        v
+    }
+}
+
+impl Embedder for LexicalEmbedder {
+    fn model(&self) -> String {
+        format!("hash-{}", self.dims)
+    }
+
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        Ok(texts.par_iter().map(|t| self.embed_one(t)).collect())
+    }
+}
+
+/// An OpenAI-compatible embeddings endpoint.
+#[cfg(feature = "http-embeddings")]
+pub struct HttpEmbedder {
+    pub url: String,
+    pub model: String,
+    pub api_key: Option<String>,
+    client: reqwest::blocking::Client,
+}
+
+#[cfg(feature = "http-embeddings")]
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingItem>,
+}
+
+#[cfg(feature = "http-embeddings")]
+#[derive(Deserialize)]
+struct EmbeddingItem {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[cfg(feature = "http-embeddings")]
+impl HttpEmbedder {
+    pub fn new(url: &str, model: &str, api_key: Option<String>) -> Self {
+        HttpEmbedder {
+            url: url.to_string(),
+            model: model.to_string(),
+            api_key,
+            client: reqwest::blocking::Client::builder()
+                .user_agent(Policy::current().user_agent)
+                .timeout(std::time::Duration::from_secs(120))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Wait for the endpoint's turn under the politeness policy, reading its robots.txt
+    /// first when due.
+    fn wait_turn(&self) -> Result<(), String> {
+        let policy = Policy::current();
+        let gate = politeness::shared();
+        if let Some(robots) = gate.robots_due(&self.url, &policy, Instant::now())? {
+            sleep_until(gate.reserve(&robots.host, &policy, Instant::now()));
+            let body = self
+                .client
+                .get(&robots.url)
+                .send()
+                .ok()
+                .filter(|r| r.status().is_success())
+                .and_then(|r| r.text().ok());
+            gate.set_robots(
+                &robots.host,
+                body.as_deref(),
+                &policy.user_agent,
+                Instant::now(),
+            );
+        }
+        sleep_until(gate.admit(&self.url, &policy, Instant::now())?);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "http-embeddings")]
+fn sleep_until(at: Instant) {
+    std::thread::sleep(at.saturating_duration_since(Instant::now()));
+}
+
+#[cfg(feature = "http-embeddings")]
+impl Embedder for HttpEmbedder {
+    fn model(&self) -> String {
+        self.model.clone()
+    }
+
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        self.wait_turn()
+            .map_err(|e| format!("{}: {}", self.url, e))?;
+        let mut req = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::json!({"model": self.model, "input": texts}).to_string());
+        if let Some(k) = &self.api_key {
+            req = req.bearer_auth(k);
+        }
+        let resp = req.send().map_err(|e| format!("{}: {}", self.url, e))?;
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().unwrap_or_default();
+            return Err(format!(
+                "{}: HTTP {}: {}",
+                self.url,
+                status,
+                body.chars().take(200).collect::<String>()
+            ));
+        }
+        let body = resp.bytes().map_err(|e| format!("{}: {}", self.url, e))?;
+        let mut r: EmbeddingResponse =
+            serde_json::from_slice(&body).map_err(|e| format!("{}: {}", self.url, e))?;
+        if r.data.len() != texts.len() {
+            return Err(format!(
+                "{}: {} embeddings for {} inputs",
+                self.url,
+                r.data.len(),
+                texts.len()
+            ));
+        }
+        r.data.sort_by_key(|d| d.index);
+        Ok(r.data
+            .into_iter()
+            .map(|d| {
+                let mut v = d.embedding;
+                normalize(&mut v);
+                v
+            })
+            .collect())
+    }
+}
+
+fn setting(env: &str, conf: Option<String>) -> Option<String> {
+    std::env::var(env)
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .or(conf)
+}
+
+/// The embedder configured by `DAIZO_EMBED_BACKEND` / `[embeddings] backend`, or `backend`
+/// when given (`lexical` or `http`).
+pub fn configured(backend: Option<&str>) -> Result<Box<dyn Embedder>, String> {
+    let conf = crate::config::current().embeddings.clone();
+    let backend = backend
+        .map(str::to_string)
+        .or_else(|| setting("DAIZO_EMBED_BACKEND", conf.backend.clone()))
+        .unwrap_or_else(|| "lexical".to_string())
+        .to_lowercase();
+    match backend.as_str() {
+        "lexical" | "hash" => Ok(Box::new(LexicalEmbedder { dims: HASH_DIMS })),
+        "http" => {
+            let model = setting("DAIZO_EMBED_MODEL", conf.model.clone())
+                .ok_or("the http backend needs DAIZO_EMBED_MODEL or [embeddings] model")?;
+            http_embedder(&model)
+        }
+        other => Err(format!(
+            "unknown embedding backend '{}' (lexical, http)",
+            other
+        )),
+    }
+}
+
+#[cfg(feature = "http-embeddings")]
+fn http_embedder(model: &str) -> Result<Box<dyn Embedder>, String> {
+    let conf = crate::config::current().embeddings.clone();
+    let url = setting("DAIZO_EMBED_URL", conf.url)
+        .ok_or("the http backend needs DAIZO_EMBED_URL or [embeddings] url")?;
+    Ok(Box::new(HttpEmbedder::new(
+        &url,
+        model,
+        setting("DAIZO_EMBED_API_KEY", None),
+    )))
+}
+
+#[cfg(not(feature = "http-embeddings"))]
+fn http_embedder(_model: &str) -> Result<Box<dyn Embedder>, String> {
+    Err("the http backend needs daizo-core built with the http-embeddings feature".to_string())
+}
+
+/// The embedder that queries an index built with `model`.
+pub fn for_model(model: &str) -> Result<Box<dyn Embedder>, String> {
+    match model
+        .strip_prefix("hash-")
+        .and_then(|d| d.parse::<usize>().ok())
+    {
+        Some(dims) => Ok(Box::new(LexicalEmbedder { dims })),
+        None => http_embedder(model),
+    }
+}
+
+/// Whether an index of `model` compares passages lexically (the `lexical` backend) rather
+/// than through an embedding model.
+pub fn is_lexical(model: &str) -> bool {
+    model.starts_with("hash-")
+}
+
+/// Whether an index of `model` is queried over the network.
+pub fn is_remote(model: &str) -> bool {
+    !is_lexical(model)
+}
+
+/// Where a passage is, in the terms `*_fetch` takes (`id` + `lineNumber`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PassageRef {
+    pub id: String,
+    pub title: String,
+    /// 1-based line of the source file where the passage starts.
+    pub line: usize,
+    /// CBETA juan ("001").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub juan: Option<String>,
+    /// The passage's opening characters.
+    pub preview: String,
+}
+
+/// Characters of text a line carries once its tags are dropped.
+fn text_len(line: &str) -> usize {
+    let mut in_tag = false;
+    let mut n = 0;
+    for c in line.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag && !c.is_whitespace() => n += 1,
+            _ => {}
+        }
+    }
+    n
+}
+
+/// Cut a source file into passages of about `chars` characters, whole lines each. XML is
+/// read from its body on (the TEI header is skipped), without notes.
+pub fn passages(
+    corpus: &str,
+    entry: &IndexEntry,
+    content: &str,
+    chars: usize,
+) -> Vec<(PassageRef, String)> {
+    let is_xml = entry.path.to_ascii_lowercase().ends_with(".xml");
+    let gaiji = if is_xml {
+        crate::cbeta_gaiji_map_fast(content)
+    } else {
+        HashMap::new()
+    };
+    let juans = (corpus == "cbeta").then(|| JuanMap::build(content));
+    let lines: Vec<&str> = content.lines().collect();
+    let start = if is_xml {
+        lines
+            .iter()
+            .position(|l| l.contains("</teiHeader>"))
+            .map(|i| i + 1)
+            .unwrap_or(0)
+    } else {
+        0
+    };
+    let mut out = Vec::new();
+    let emit = |from: usize, to: usize, out: &mut Vec<(PassageRef, String)>| {
+        // Line by line: the extractor trims text at tag boundaries, which would run the last
+        // word of one line into the first of the next.
+        let text = lines[from..to]
+            .iter()
+            .map(|l| {
+                if is_xml {
+                    crate::extract_cbeta_plain_from_snippet(l, &gaiji, false)
+                } else {
+                    l.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if text.is_empty() {
+            return;
+        }
+        out.push((
+            PassageRef {
+                id: entry.id.clone(),
+                title: entry.title.clone(),
+                line: from + 1,
+                juan: juans
+                    .as_ref()
+                    .and_then(|m| m.juan_at_line(from + 1))
+                    .map(|s| s.n.clone()),
+                preview: text.chars().take(60).collect(),
+            },
+            text,
+        ));
+    };
+    let (mut from, mut acc) = (start, 0);
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        acc += if is_xml {
+            text_len(line)
+        } else {
+            line.chars().filter(|c| !c.is_whitespace()).count()
+        };
+        if acc >= chars {
+            emit(from, i + 1, &mut out);
+            from = i + 1;
+            acc = 0;
+        }
+    }
+    if from < lines.len() {
+        emit(from, lines.len(), &mut out);
+    }
+    out
+}
+
+/// A passage found by [`VectorIndex::search`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Hit {
+    pub passage: PassageRef,
+    pub score: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexFile {
+    model: String,
+    dims: usize,
+    collection: String,
+    passages: Vec<PassageRef>,
+    centroids: Vec<Vec<f32>>,
+    lists: Vec<Vec<u32>>,
+}
+
+/// Passage vectors with their inverted lists.
+pub struct VectorIndex {
+    pub model: String,
+    pub dims: usize,
+    /// Collection the index covers ("T08", "DN", "all").
+    pub collection: String,
+    pub passages: Vec<PassageRef>,
+    vectors: Vec<f32>,
+    centroids: Vec<Vec<f32>>,
+    lists: Vec<Vec<u32>>,
+}
+
+/// Index files of a corpus: metadata (JSON) and vectors (little-endian f32).
+pub fn index_paths(corpus: &str) -> (PathBuf, PathBuf) {
+    let dir = cache_dir().join("embeddings");
+    (
+        dir.join(format!("{}.json", corpus)),
+        dir.join(format!("{}.f32", corpus)),
+    )
+}
+
+impl VectorIndex {
+    /// Cluster `vectors` (one per passage, unit length) into about √n lists.
+    pub fn new(
+        model: String,
+        dims: usize,
+        collection: String,
+        passages: Vec<PassageRef>,
+        vectors: Vec<Vec<f32>>,
+    ) -> Self {
+        let n = vectors.len();
+        // Below a few thousand passages a full scan is as fast as probing.
+        let k = if n < 2048 {
+            1
+        } else {
+            ((n as f64).sqrt() as usize).min(4096)
+        };
+        let mut centroids: Vec<Vec<f32>> = (0..k).map(|i| vectors[i * n / k].clone()).collect();
+        let mut assign = vec![0u32; n];
+        for round in 0..8 {
+            if k == 1 {
+                break;
+            }
+            assign = vectors
+                .par_iter()
+                .map(|v| nearest(&centroids, v) as u32)
+                .collect();
+            if round == 7 {
+                break;
+            }
+            let mut sums = vec![vec![0f32; dims]; k];
+            for (v, a) in vectors.iter().zip(&assign) {
+                sums[*a as usize]
+                    .iter_mut()
+                    .zip(v)
+                    .for_each(|(s, x)| *s += x);
+            }
+            for (c, mut s) in centroids.iter_mut().zip(sums) {
+                // An empty list keeps its centroid.
+                if s.iter().any(|x| *x != 0.0) {
+                    normalize(&mut s);
+                    *c = s;
+                }
+            }
+        }
+        let mut lists = vec![Vec::new(); k];
+        for (i, a) in assign.iter().enumerate() {
+            lists[*a as usize].push(i as u32);
+        }
+        VectorIndex {
+            model,
+            dims,
+            collection,
+            passages,
+            vectors: vectors.into_iter().flatten().collect(),
+            centroids,
+            lists,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.passages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passages.is_empty()
+    }
+
+    fn vector(&self, i: usize) -> &[f32] {
+        &self.vectors[i * self.dims..(i + 1) * self.dims]
+    }
+
+    /// The `limit` passages closest to `query`, best first, scanning the `nprobe` lists whose
+    /// centroids are closest.
+    pub fn search(&self, query: &[f32], limit: usize, nprobe: usize) -> Vec<Hit> {
+        if query.len() != self.dims {
+            return Vec::new();
+        }
+        let mut q = query.to_vec();
+        normalize(&mut q);
+        let mut order: Vec<(usize, f32)> = self
+            .centroids
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (i, dot(c, &q)))
+            .collect();
+        order.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let mut scored: Vec<(usize, f32)> = order
+            .iter()
+            .take(nprobe.max(1))
+            .flat_map(|(l, _)| self.lists[*l].iter())
+            .map(|&i| (i as usize, dot(self.vector(i as usize), &q)))
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        scored
+            .into_iter()
+            .take(limit)
+            .map(|(i, score)| Hit {
+                passage: self.passages[i].clone(),
+                score,
+            })
+            .collect()
+    }
+
+    pub fn save(&self, meta: &Path, vectors: &Path) -> std::io::Result<()> {
+        if let Some(dir) = meta.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let bytes: Vec<u8> = self.vectors.iter().flat_map(|x| x.to_le_bytes()).collect();
+        write_atomic(vectors, &bytes)?;
+        let file = IndexFile {
+            model: self.model.clone(),
+            dims: self.dims,
+            collection: self.collection.clone(),
+            passages: self.passages.clone(),
+            centroids: self.centroids.clone(),
+            lists: self.lists.clone(),
+        };
+        write_atomic(meta, &serde_json::to_vec(&file)?)
+    }
+
+    pub fn load(meta: &Path, vectors: &Path) -> Result<Self, String> {
+        let b = std::fs::read(meta).map_err(|e| format!("{}: {}", meta.display(), e))?;
+        let f: IndexFile =
+            serde_json::from_slice(&b).map_err(|e| format!("{}: {}", meta.display(), e))?;
+        let raw = std::fs::read(vectors).map_err(|e| format!("{}: {}", vectors.display(), e))?;
+        if raw.len() != f.passages.len() * f.dims * 4 {
+            return Err(format!(
+                "{}: size does not match {}",
+                vectors.display(),
+                meta.display()
+            ));
+        }
+        Ok(VectorIndex {
+            model: f.model,
+            dims: f.dims,
+            collection: f.collection,
+            passages: f.passages,
+            vectors: raw
+                .chunks_exact(4)
+                .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect(),
+            centroids: f.centroids,
+            lists: f.lists,
+        })
+    }
+}
+
+fn nearest(centroids: &[Vec<f32>], v: &[f32]) -> usize {
+    centroids
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (i, dot(c, v)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+/// Embed every passage of `entries`, `batch` passages per request.
+pub fn build(
+    corpus: &str,
+    collection: &str,
+    entries: &[&IndexEntry],
+    embedder: &dyn Embedder,
+    chars: usize,
+    batch: usize,
+) -> Result<VectorIndex, String> {
+    let counter = progress::Counter::new(&format!("passages {}", corpus), "files", entries.len());
+    let cut: Vec<(PassageRef, String)> = crate::scan::run(|| {
+        entries
+            .par_iter()
+            .flat_map_iter(|e| {
+                counter.tick();
+                crate::analysis::read_text_file(Path::new(&e.path))
+                    .map(|c| passages(corpus, e, &c, chars))
+                    .unwrap_or_default()
+            })
+            .collect()
+    });
+    let (refs, texts): (Vec<PassageRef>, Vec<String>) = cut.into_iter().unzip();
+    let counter = progress::Counter::new(
+        &format!("embed {}", corpus),
+        "batches",
+        texts.len().div_ceil(batch.max(1)),
+    );
+    let mut vectors = Vec::with_capacity(texts.len());
+    for chunk in texts.chunks(batch.max(1)) {
+        vectors.extend(embedder.embed(chunk)?);
+        counter.tick();
+    }
+    let dims = vectors.first().map(|v| v.len()).unwrap_or(0);
+    if let Some(v) = vectors.iter().find(|v| v.len() != dims) {
+        return Err(format!(
+            "embedder returned {} and {} dimensions",
+            dims,
+            v.len()
+        ));
+    }
+    Ok(VectorIndex::new(
+        embedder.model(),
+        dims,
+        collection.to_string(),
+        refs,
+        vectors,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passages_are_found_by_shared_vocabulary() {
+        let xml = "<TEI><teiHeader><title>x</title></teiHeader>\n<text><body>\n\
+<p><lb n=\"0001a01\"/>觀自在菩薩行深般若波羅蜜多時</p>\n\
+<p><lb n=\"0001a02\"/>照見五蘊皆空度一切苦厄<note>校勘</note></p>\n\
+<p><lb n=\"0001a03\"/>如是我聞一時佛住王舍城</p>\n\
+<p><lb n=\"0001a04\"/>耆闍崛山中與大比丘眾</p>\n</body></text></TEI>";
+        let entry = IndexEntry {
+            id: "T0001".into(),
+            title: "試".into(),
+            path: "/x/T01n0001.xml".into(),
+            meta: None,
+        };
+        let p = passages("cbeta", &entry, xml, 20);
+        assert_eq!(p.len(), 2, "{:?}", p);
+        assert_eq!(p[0].0.line, 2);
+        assert_eq!(
+            p[0].1,
+            "觀自在菩薩行深般若波羅蜜多時 照見五蘊皆空度一切苦厄"
+        );
+        assert_eq!(p[1].0.line, 5);
+
+        let e = LexicalEmbedder { dims: HASH_DIMS };
+        assert_eq!(e.model(), "hash-256");
+        let (refs, texts): (Vec<_>, Vec<_>) = p.into_iter().unzip();
+        let vectors = e.embed(&texts).unwrap();
+        assert!((dot(&vectors[0], &vectors[0]) - 1.0).abs() < 1e-5);
+        let idx = VectorIndex::new(e.model(), HASH_DIMS, "T".into(), refs, vectors);
+        let q = e.embed(&["般若波羅蜜".to_string()]).unwrap();
+        let hits = idx.search(&q[0], 2, NPROBE);
+        assert_eq!(hits[0].passage.line, 2);
+        assert!(hits[0].score > hits[1].score);
+
+        let dir = tempfile::tempdir().unwrap();
+        let (m, v) = (dir.path().join("c.json"), dir.path().join("c.f32"));
+        idx.save(&m, &v).unwrap();
+        let back = VectorIndex::load(&m, &v).unwrap();
+        assert_eq!(back.len(), 2);
+        assert_eq!(back.search(&q[0], 1, 1)[0].passage.line, 2);
+        assert!(for_model("hash-64").is_ok() && !is_remote("hash-64"));
+        std::fs::write(&v, [0u8; 4]).unwrap();
+        assert!(VectorIndex::load(&m, &v).is_err());
+
+        // Enough passages for several lists: each still finds itself first.
+        let texts: Vec<String> = (0..2100)
+            .map(|i| format!("sutta {} vagga {}", i, i % 7))
+            .collect();
+        let small = LexicalEmbedder { dims: 32 };
+        let vectors = small.embed(&texts).unwrap();
+        let refs = (0..texts.len())
+            .map(|i| PassageRef {
+                id: "x".into(),
+                title: String::new(),
+                line: i + 1,
+                juan: None,
+                preview: texts[i].clone(),
+            })
+            .collect();
+        let big = VectorIndex::new(small.model(), 32, "all".into(), refs, vectors.clone());
+        assert!(big.lists.len() > 1 && big.lists.iter().map(Vec::len).sum::<usize>() == 2100);
+        assert_eq!(big.search(&vectors[1234], 1, NPROBE)[0].passage.line, 1235);
+    }
+}
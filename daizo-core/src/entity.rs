@@ -0,0 +1,297 @@
+//! Person, place and organization names tagged in a TEI text.
+//!
+//! [`mentions`] walks the `<text>` of a file for `<persName>`, `<placeName>`, `<geogName>`,
+//! `<orgName>` and typed `<name>` elements — with or without a prefix, so CBETA's `cb:`
+//! elements count — and [`tally`] groups the mentions into [`Entity`] records: by authority
+//! key (`key`, or `ref` without its `#`) where there is one, else by normalized name. The
+//! `teiHeader` (editors, publishers) and variant readings are not read.
+
+use crate::{attr_val, local_name};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::Serialize;
+use std::collections::HashMap;
+use unicode_normalization::UnicodeNormalization;
+
+/// What a name refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityKind {
+    Person,
+    Place,
+    Org,
+    Other,
+}
+
+impl EntityKind {
+    pub const NAMES: &'static [&'static str] = &["person", "place", "org", "other"];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "person" | "persname" => Some(EntityKind::Person),
+            "place" | "placename" => Some(EntityKind::Place),
+            "org" | "orgname" => Some(EntityKind::Org),
+            "other" => Some(EntityKind::Other),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntityKind::Person => "person",
+            EntityKind::Place => "place",
+            EntityKind::Org => "org",
+            EntityKind::Other => "other",
+        }
+    }
+
+    /// The kind of an element named `name` (local name) with `type` `ty`; `None` for
+    /// elements that do not name anything.
+    fn of(name: &[u8], ty: Option<&str>) -> Option<Self> {
+        match name {
+            b"persName" => Some(EntityKind::Person),
+            b"placeName" | b"geogName" => Some(EntityKind::Place),
+            b"orgName" => Some(EntityKind::Org),
+            b"name" => Some(ty.and_then(EntityKind::parse).unwrap_or(EntityKind::Other)),
+            _ => None,
+        }
+    }
+}
+
+/// One tagged name in the text.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Mention {
+    pub kind: EntityKind,
+    /// The name as written, whitespace-normalized.
+    pub name: String,
+    /// Authority key: `key`, else `ref` without a leading `#`.
+    pub key: Option<String>,
+    /// 1-based XML line.
+    pub line: usize,
+    /// Byte offset of the element in the XML.
+    pub offset: usize,
+    /// The last `<lb n>` before the mention.
+    pub lb: Option<String>,
+}
+
+/// Where an entity is first named.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FirstOccurrence {
+    pub line: usize,
+    pub offset: usize,
+    pub lb: Option<String>,
+}
+
+/// The mentions of one person, place or organization.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entity {
+    pub kind: EntityKind,
+    /// The most frequent form (the first of equally frequent ones).
+    pub name: String,
+    pub key: Option<String>,
+    /// Every form the entity is written in, in order of first appearance.
+    pub forms: Vec<String>,
+    pub count: usize,
+    pub first: FirstOccurrence,
+}
+
+/// Elements whose content is not the text itself.
+const SKIPPED: &[&[u8]] = &[b"teiHeader", b"rdg", b"fw", b"mulu", b"figDesc"];
+
+/// The name used to group mentions without a key: NFC, punctuation trimmed, lowercase.
+pub fn normalize_name(s: &str) -> String {
+    s.nfc()
+        .collect::<String>()
+        .trim_matches(|c: char| {
+            c.is_whitespace() || c.is_ascii_punctuation() || "，。、；：「」『』（）".contains(c)
+        })
+        .to_lowercase()
+}
+
+fn authority_key(e: &BytesStart) -> Option<String> {
+    attr_val(e, b"key")
+        .or_else(|| attr_val(e, b"ref"))
+        .map(|v| v.trim().trim_start_matches('#').to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Every tagged name of `xml` in document order. A name nested in another (a place in a
+/// person's epithet) is its own mention.
+pub fn mentions(xml: &str) -> Vec<Mention> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().check_end_names = false;
+    let mut buf = Vec::new();
+    let mut out: Vec<Mention> = Vec::new();
+    // Indexes into `out` of the names being read, innermost last, with their depth.
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut lb: Option<String> = None;
+    let mut depth = 0usize;
+    let mut skip = 0usize;
+    let mut line = 1usize;
+    let mut line_pos = 0usize;
+    loop {
+        let pos = reader.buffer_position() as usize;
+        line += xml.as_bytes()[line_pos..pos.min(xml.len())]
+            .iter()
+            .filter(|b| **b == b'\n')
+            .count();
+        line_pos = pos.min(xml.len());
+        let ev = reader.read_event_into(&mut buf);
+        match &ev {
+            Ok(Event::Empty(e)) if skip == 0 && local_name(e.name().as_ref()) == b"lb" => {
+                if let Some(n) = attr_val(e, b"n") {
+                    lb = Some(n.into_owned());
+                }
+            }
+            Ok(Event::Start(e)) => {
+                depth += 1;
+                let name_owned = e.name().as_ref().to_owned();
+                let name = local_name(&name_owned);
+                if skip > 0 || SKIPPED.contains(&name) {
+                    skip += 1;
+                } else {
+                    let ty = attr_val(e, b"type").or_else(|| attr_val(e, b"cb:type"));
+                    if let Some(kind) = EntityKind::of(name, ty.as_deref()) {
+                        open.push((out.len(), depth));
+                        out.push(Mention {
+                            kind,
+                            name: String::new(),
+                            key: authority_key(e),
+                            line,
+                            offset: pos,
+                            lb: lb.clone(),
+                        });
+                    }
+                }
+            }
+            Ok(Event::Text(t)) if skip == 0 && !open.is_empty() => {
+                let t = t.decode().unwrap_or_default();
+                for (i, _) in &open {
+                    out[*i].name.push_str(&t);
+                }
+            }
+            Ok(Event::End(_)) => {
+                skip = skip.saturating_sub(1);
+                if open.last().is_some_and(|(_, d)| *d == depth) {
+                    open.pop();
+                }
+                depth = depth.saturating_sub(1);
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    out.into_iter()
+        .filter_map(|mut m| {
+            m.name = m.name.split_whitespace().collect::<Vec<_>>().join(" ");
+            (!normalize_name(&m.name).is_empty()).then_some(m)
+        })
+        .collect()
+}
+
+/// Group `mentions` into entities, most mentioned first (then by first occurrence). A mention
+/// without a key joins the keyed entity of its kind written the same way, if there is one.
+pub fn tally(mentions: &[Mention]) -> Vec<Entity> {
+    let mut keyed: HashMap<(EntityKind, String), &str> = HashMap::new();
+    for m in mentions {
+        if let Some(k) = &m.key {
+            keyed.entry((m.kind, normalize_name(&m.name))).or_insert(k);
+        }
+    }
+    // (kind, key or normalized name) → entity, and the count of each of its forms.
+    let mut by_id: HashMap<(EntityKind, String), (Entity, Vec<usize>)> = HashMap::new();
+    for m in mentions {
+        let key = m.key.clone().or_else(|| {
+            keyed
+                .get(&(m.kind, normalize_name(&m.name)))
+                .map(|k| k.to_string())
+        });
+        let id = key.clone().unwrap_or_else(|| normalize_name(&m.name));
+        let (e, form_counts) = by_id.entry((m.kind, id)).or_insert_with(|| {
+            (
+                Entity {
+                    kind: m.kind,
+                    name: m.name.clone(),
+                    key,
+                    forms: Vec::new(),
+                    count: 0,
+                    first: FirstOccurrence {
+                        line: m.line,
+                        offset: m.offset,
+                        lb: m.lb.clone(),
+                    },
+                },
+                Vec::new(),
+            )
+        });
+        e.count += 1;
+        match e.forms.iter().position(|f| *f == m.name) {
+            Some(i) => form_counts[i] += 1,
+            None => {
+                e.forms.push(m.name.clone());
+                form_counts.push(1);
+            }
+        }
+    }
+    let mut out: Vec<Entity> = by_id
+        .into_values()
+        .map(|(mut e, form_counts)| {
+            let best = form_counts
+                .iter()
+                .enumerate()
+                .max_by_key(|(i, n)| (**n, std::cmp::Reverse(*i)))
+                .map_or(0, |(i, _)| i);
+            e.name = e.forms[best].clone();
+            e
+        })
+        .collect();
+    out.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then(a.first.offset.cmp(&b.first.offset))
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_grouped_by_key_or_form() {
+        let xml = r##"<TEI><teiHeader><respStmt><persName>Editor</persName></respStmt></teiHeader><text><body>
+<p><lb n="0001a01"/>佛在<placeName key="PL000000012345">舍衛國</placeName>，
+<persName key="A000439">舍利弗</persName>白佛言</p>
+<p><lb n="0001a02"/><persName ref="#A000439">舍利子</persName>與<cb:persName>目連</cb:persName>
+<name type="person">舍利弗</name>往<name type="place"> 舍衛國 </name><app><lem>王</lem><rdg><persName>阿難</persName></rdg></app></p>
+</body></text></TEI>"##;
+        let ms = mentions(xml);
+        assert_eq!(ms.len(), 6);
+        assert_eq!(ms[0].lb.as_deref(), Some("0001a01"));
+        let entities = tally(&ms);
+        let got: Vec<(&str, &str, Option<&str>, usize, usize)> = entities
+            .iter()
+            .map(|e| {
+                (
+                    e.kind.as_str(),
+                    e.name.as_str(),
+                    e.key.as_deref(),
+                    e.count,
+                    e.first.line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            got,
+            [
+                ("person", "舍利弗", Some("A000439"), 3, 3),
+                ("place", "舍衛國", Some("PL000000012345"), 2, 2),
+                ("person", "目連", None, 1, 4),
+            ]
+        );
+    }
+}
@@ -0,0 +1,334 @@
+//! EPUB 3 books from rendered texts.
+//!
+//! Chapters arrive as the Markdown [`crate::tei_text`] writes (headings, verse quotes, list
+//! items, emphasis and `[^n]` footnotes); [`markdown_to_xhtml`] turns that subset into XHTML
+//! and [`write_epub`] packages the chapters with a navigation document, a stylesheet whose
+//! font stacks follow the language of the text, and the OPF metadata. The `mimetype` entry is
+//! stored first, as the OCF container requires; the others are deflated.
+
+use regex::Regex;
+use std::io::{self, Seek, Write};
+use std::sync::OnceLock;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// One chapter: a juan, or a whole file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chapter {
+    pub title: String,
+    pub markdown: String,
+}
+
+/// The chapters of one work; a book made from a reading list has several.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    pub title: String,
+    pub chapters: Vec<Chapter>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Book {
+    pub title: String,
+    /// Unique identifier, e.g. `urn:daizo:cbeta:T0262`.
+    pub identifier: String,
+    /// BCP 47 language tag of the text ([`language`]).
+    pub language: String,
+    pub creators: Vec<String>,
+    pub rights: Option<String>,
+    /// Where the text came from (corpus, files, snapshot).
+    pub source: Option<String>,
+    /// Last modification date (YYYY-MM-DD).
+    pub modified: String,
+    pub sections: Vec<Section>,
+}
+
+/// Language tag for the texts of a corpus: classical Chinese for CBETA, Pāli for the
+/// Tipitaka, Sanskrit otherwise.
+pub fn language(corpus: &str) -> &'static str {
+    match corpus {
+        "cbeta" => "zh-Hant",
+        "tipitaka" => "pi",
+        _ => "sa",
+    }
+}
+
+const STYLE: &str = r#"body { margin: 0 5%; line-height: 1.8; }
+:lang(zh), :lang(ja) {
+  font-family: "Noto Serif CJK TC", "Source Han Serif TC", "Noto Serif TC", "Songti TC", "PMingLiU", serif;
+}
+:lang(pi), :lang(sa) {
+  font-family: "Gentium Plus", "Charis SIL", "Noto Serif", "Times New Roman", serif;
+}
+h1, h2, h3, h4, h5, h6 { line-height: 1.4; }
+blockquote { margin: 1em 0 1em 2em; }
+aside.footnotes { margin-top: 2em; font-size: 0.9em; }
+nav ol { list-style: none; }
+"#;
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escape a line and render emphasis and footnote references.
+fn inline(s: &str) -> String {
+    static RULES: OnceLock<[(Regex, &str); 3]> = OnceLock::new();
+    let rules = RULES.get_or_init(|| {
+        [
+            (Regex::new(r"\*\*(.+?)\*\*").unwrap(), "<strong>$1</strong>"),
+            (Regex::new(r"\*(.+?)\*").unwrap(), "<em>$1</em>"),
+            (
+                Regex::new(r"\[\^(\d+)\]").unwrap(),
+                r##"<a epub:type="noteref" href="#fn-$1" id="fnref-$1"><sup>$1</sup></a>"##,
+            ),
+        ]
+    });
+    rules.iter().fold(escape(s), |t, (re, rep)| {
+        re.replace_all(&t, *rep).into_owned()
+    })
+}
+
+/// XHTML body content for Markdown as [`crate::tei_text`] writes it.
+pub fn markdown_to_xhtml(md: &str) -> String {
+    static NOTE: OnceLock<Regex> = OnceLock::new();
+    let note = NOTE.get_or_init(|| Regex::new(r"^\[\^(\d+)\]:\s*(.*)$").unwrap());
+    let mut out = String::new();
+    let mut notes = Vec::new();
+    for block in md.split("\n\n") {
+        let lines: Vec<&str> = block.lines().filter(|l| !l.trim().is_empty()).collect();
+        if lines.is_empty() {
+            continue;
+        }
+        if lines.iter().all(|l| note.is_match(l)) {
+            for l in &lines {
+                let c = note.captures(l).unwrap();
+                notes.push(format!(
+                    "<aside epub:type=\"footnote\" id=\"fn-{}\"><p><a href=\"#fnref-{}\">{}</a>. {}</p></aside>\n",
+                    &c[1], &c[1], &c[1], inline(&c[2])
+                ));
+            }
+            continue;
+        }
+        let first = lines[0];
+        let level = first.chars().take_while(|c| *c == '#').count();
+        if lines.len() == 1 && (1..=6).contains(&level) && first[level..].starts_with(' ') {
+            out.push_str(&format!(
+                "<h{l}>{}</h{l}>\n",
+                inline(first[level..].trim()),
+                l = level
+            ));
+        } else if lines.iter().all(|l| l.starts_with('>')) {
+            let body: Vec<String> = lines
+                .iter()
+                .map(|l| inline(l.trim_start_matches('>').trim()))
+                .collect();
+            out.push_str(&format!(
+                "<blockquote><p>{}</p></blockquote>\n",
+                body.join("<br/>")
+            ));
+        } else if lines.iter().all(|l| l.starts_with("- ")) {
+            out.push_str("<ul>\n");
+            for l in &lines {
+                out.push_str(&format!("<li>{}</li>\n", inline(&l[2..])));
+            }
+            out.push_str("</ul>\n");
+        } else {
+            let body: Vec<String> = lines.iter().map(|l| inline(l.trim())).collect();
+            out.push_str(&format!("<p>{}</p>\n", body.join("<br/>")));
+        }
+    }
+    if !notes.is_empty() {
+        out.push_str("<aside class=\"footnotes\">\n");
+        out.extend(notes);
+        out.push_str("</aside>\n");
+    }
+    out
+}
+
+fn xhtml_page(lang: &str, title: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" xml:lang=\"{l}\" lang=\"{l}\">\n\
+         <head><meta charset=\"UTF-8\"/><title>{t}</title><link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/></head>\n\
+         <body>\n{b}</body>\n</html>\n",
+        l = lang,
+        t = escape(title),
+        b = body
+    )
+}
+
+/// File name of chapter `i` (0-based, across sections).
+fn chapter_file(i: usize) -> String {
+    format!("c{:03}.xhtml", i + 1)
+}
+
+fn nav(book: &Book) -> String {
+    let mut body =
+        String::from("<nav epub:type=\"toc\" id=\"toc\">\n<h1>目次 · Contents</h1>\n<ol>\n");
+    let mut i = 0;
+    let nested = book.sections.len() > 1;
+    for s in &book.sections {
+        if nested {
+            body.push_str(&format!(
+                "<li><a href=\"{}\">{}</a>\n<ol>\n",
+                chapter_file(i),
+                escape(&s.title)
+            ));
+        }
+        for c in &s.chapters {
+            body.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                chapter_file(i),
+                escape(&c.title)
+            ));
+            i += 1;
+        }
+        if nested {
+            body.push_str("</ol>\n</li>\n");
+        }
+    }
+    body.push_str("</ol>\n</nav>\n");
+    xhtml_page(&book.language, &book.title, &body)
+}
+
+fn package(book: &Book, chapters: usize) -> String {
+    let mut meta = vec![
+        format!(
+            "<dc:identifier id=\"book-id\">{}</dc:identifier>",
+            escape(&book.identifier)
+        ),
+        format!("<dc:title>{}</dc:title>", escape(&book.title)),
+        format!("<dc:language>{}</dc:language>", escape(&book.language)),
+    ];
+    for c in &book.creators {
+        meta.push(format!("<dc:creator>{}</dc:creator>", escape(c)));
+    }
+    if let Some(r) = &book.rights {
+        meta.push(format!("<dc:rights>{}</dc:rights>", escape(r)));
+    }
+    if let Some(s) = &book.source {
+        meta.push(format!("<dc:source>{}</dc:source>", escape(s)));
+    }
+    meta.push(format!(
+        "<meta property=\"dcterms:modified\">{}T00:00:00Z</meta>",
+        book.modified
+    ));
+    // Apple Books otherwise replaces the stylesheet's fonts with its own.
+    meta.push("<meta property=\"ibooks:specified-fonts\">true</meta>".to_string());
+    let mut manifest = vec![
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>".to_string(),
+        "<item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>".to_string(),
+    ];
+    let mut spine = Vec::new();
+    for i in 0..chapters {
+        manifest.push(format!(
+            "<item id=\"c{n}\" href=\"{f}\" media-type=\"application/xhtml+xml\"/>",
+            n = i + 1,
+            f = chapter_file(i)
+        ));
+        spine.push(format!("<itemref idref=\"c{}\"/>", i + 1));
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\" xml:lang=\"{l}\" \
+         prefix=\"ibooks: http://vocabulary.itunes.apple.com/rdf/ibooks/vocabulary-extensions-1.0/\">\n\
+         <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n{m}\n</metadata>\n\
+         <manifest>\n{f}\n</manifest>\n<spine>\n{s}\n</spine>\n</package>\n",
+        l = escape(&book.language),
+        m = meta.join("\n"),
+        f = manifest.join("\n"),
+        s = spine.join("\n")
+    )
+}
+
+const CONTAINER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+<rootfiles><rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/></rootfiles>\n\
+</container>\n";
+
+/// Write `book` as an EPUB 3 archive.
+pub fn write_epub<W: Write + Seek>(book: &Book, out: W) -> io::Result<()> {
+    let mut zip = ZipWriter::new(out);
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let add = |zip: &mut ZipWriter<W>, name: &str, data: &[u8]| -> io::Result<()> {
+        zip.start_file(name, deflated)?;
+        zip.write_all(data)
+    };
+    // The mimetype entry must come first, uncompressed.
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+    add(&mut zip, "META-INF/container.xml", CONTAINER.as_bytes())?;
+    let mut i = 0;
+    for s in &book.sections {
+        for c in &s.chapters {
+            let body = format!(
+                "<section epub:type=\"chapter\">\n{}</section>\n",
+                markdown_to_xhtml(&c.markdown)
+            );
+            let page = xhtml_page(&book.language, &c.title, &body);
+            add(
+                &mut zip,
+                &format!("OEBPS/{}", chapter_file(i)),
+                page.as_bytes(),
+            )?;
+            i += 1;
+        }
+    }
+    add(&mut zip, "OEBPS/nav.xhtml", nav(book).as_bytes())?;
+    add(&mut zip, "OEBPS/style.css", STYLE.as_bytes())?;
+    add(&mut zip, "OEBPS/content.opf", package(book, i).as_bytes())?;
+    zip.finish()?.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_becomes_xhtml_in_a_valid_archive() {
+        let md = "妙法蓮華經卷第一\n\n# 序品第一\n\n如是我聞[^1] *一時*\n\n> 諸法從緣生\n> 亦從因緣滅\n\n- a & b\n\n[^1]: 宋本無";
+        let x = markdown_to_xhtml(md);
+        assert!(x.starts_with("<p>妙法蓮華經卷第一</p>\n<h1>序品第一</h1>\n"));
+        assert!(x.contains("如是我聞<a epub:type=\"noteref\" href=\"#fn-1\" id=\"fnref-1\"><sup>1</sup></a> <em>一時</em>"));
+        assert!(x.contains("<blockquote><p>諸法從緣生<br/>亦從因緣滅</p></blockquote>"));
+        assert!(x.contains("<li>a &amp; b</li>"));
+        assert!(x.contains("<aside epub:type=\"footnote\" id=\"fn-1\"><p><a href=\"#fnref-1\">1</a>. 宋本無</p></aside>"));
+
+        let book = Book {
+            title: "妙法蓮華經".into(),
+            identifier: "urn:daizo:cbeta:T0262".into(),
+            language: language("cbeta").into(),
+            modified: "2026-01-01".into(),
+            sections: vec![Section {
+                title: "妙法蓮華經".into(),
+                chapters: vec![Chapter {
+                    title: "卷第一".into(),
+                    markdown: md.into(),
+                }],
+            }],
+            ..Default::default()
+        };
+        let mut buf = io::Cursor::new(Vec::new());
+        write_epub(&book, &mut buf).unwrap();
+        let buf = buf.into_inner();
+        // mimetype is the first entry, stored, at offset 30.
+        assert_eq!(&buf[30..38], b"mimetype");
+        assert_eq!(&buf[38..58], b"application/epub+zip");
+        let mut zip = zip::ZipArchive::new(io::Cursor::new(buf)).unwrap();
+        assert_eq!(zip.len(), 6);
+        assert_eq!(
+            zip.by_index(0).unwrap().compression(),
+            CompressionMethod::Stored
+        );
+        let mut read = |name: &str| {
+            let mut s = String::new();
+            io::Read::read_to_string(&mut zip.by_name(name).unwrap(), &mut s).unwrap();
+            s
+        };
+        assert!(read("OEBPS/content.opf").contains("<dc:language>zh-Hant</dc:language>"));
+        assert!(read("OEBPS/nav.xhtml").contains("<li><a href=\"c001.xhtml\">卷第一</a></li>"));
+    }
+}
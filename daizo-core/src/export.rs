@@ -0,0 +1,220 @@
+//! Whole-work export: a work rendered by the TEI converter ([`crate::tei_text`]) into one
+//! file per CBETA juan, or one file per source file in other corpora. Each file opens with a
+//! YAML front-matter block naming the work, the part and where the text came from.
+
+use crate::juan_map::JuanMap;
+use crate::tei_text::{convert_str, TextFormat, TextOptions};
+
+/// What the front matter says about the work as a whole.
+#[derive(Clone, Debug, Default)]
+pub struct WorkInfo {
+    pub corpus: String,
+    /// Work id (`T0262`); also the file-name stem.
+    pub id: String,
+    pub title: String,
+    /// Translator or author statement from the index, used when a juan has no byline.
+    pub author: Option<String>,
+    /// Commit of the corpus checkout.
+    pub snapshot: Option<String>,
+    pub license: Option<String>,
+    /// Exporting tool and version (`daizo-cli 0.6.1`).
+    pub tool: String,
+    /// Export date (YYYY-MM-DD).
+    pub exported: String,
+}
+
+/// One output file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExportFile {
+    /// File name: `T0262_001.md` for a CBETA juan, `T0262.md` for a whole file.
+    pub name: String,
+    /// Juan number ("001").
+    pub juan: Option<String>,
+    /// Heading of the juan (`<jhead>`).
+    pub juan_title: Option<String>,
+    /// YAML front matter, ending in a blank line.
+    pub front_matter: String,
+    /// The rendered text.
+    pub body: String,
+}
+
+impl ExportFile {
+    /// What is written to disk: front matter, then the text.
+    pub fn content(&self) -> String {
+        format!("{}{}", self.front_matter, self.body)
+    }
+}
+
+/// File extension for a format.
+pub fn extension(format: TextFormat) -> &'static str {
+    match format {
+        TextFormat::Markdown => "md",
+        TextFormat::Text => "txt",
+    }
+}
+
+/// YAML front matter; values are written as double-quoted scalars.
+pub fn front_matter(fields: &[(&str, Option<String>)]) -> String {
+    let mut s = String::from("---\n");
+    for (k, v) in fields {
+        if let Some(v) = v.as_deref().filter(|v| !v.is_empty()) {
+            s.push_str(&format!(
+                "{}: {}\n",
+                k,
+                serde_json::to_string(v).unwrap_or_default()
+            ));
+        }
+    }
+    s.push_str("---\n\n");
+    s
+}
+
+fn header_fields(
+    info: &WorkInfo,
+    source: &str,
+    juan: Option<(&str, usize)>,
+    juan_title: Option<String>,
+    byline: Option<String>,
+) -> Vec<(&'static str, Option<String>)> {
+    vec![
+        ("title", Some(info.title.clone())),
+        ("id", Some(info.id.clone())),
+        ("corpus", Some(info.corpus.clone())),
+        ("juan", juan.map(|(n, _)| n.to_string())),
+        ("juanCount", juan.map(|(_, c)| c.to_string())),
+        ("juanTitle", juan_title),
+        ("author", byline.or_else(|| info.author.clone())),
+        ("source", Some(source.to_string())),
+        ("snapshot", info.snapshot.clone()),
+        ("license", info.license.clone()),
+        ("exported", Some(info.exported.clone())),
+        ("generator", Some(info.tool.clone())),
+    ]
+}
+
+/// Render the XML files of a work (in order; a CBETA work may span several volumes) into
+/// export files. `sources` pairs each file's name, as recorded in the front matter, with its
+/// contents; files not named `.xml` (MUKTABODHA plain text) are copied as they are.
+pub fn export_work(
+    info: &WorkInfo,
+    sources: &[(String, String)],
+    opts: &TextOptions,
+) -> Vec<ExportFile> {
+    let ext = extension(opts.format);
+    // Only CBETA marks juans; other corpora export each file whole.
+    let maps: Vec<JuanMap> = sources
+        .iter()
+        .map(|(_, xml)| match info.corpus.as_str() {
+            "cbeta" => JuanMap::build(xml),
+            _ => JuanMap::default(),
+        })
+        .collect();
+    let juan_count: usize = maps.iter().map(|m| m.len()).sum();
+    let mut out = Vec::new();
+    for ((source, xml), map) in sources.iter().zip(&maps) {
+        if map.is_empty() {
+            let name = if sources.len() == 1 {
+                format!("{}.{}", info.id, ext)
+            } else {
+                format!("{}_{}.{}", info.id, out.len() + 1, ext)
+            };
+            let fields = header_fields(info, source, None, None, None);
+            out.push(ExportFile {
+                name,
+                juan: None,
+                juan_title: None,
+                front_matter: front_matter(&fields),
+                body: if source.ends_with(".xml") {
+                    convert_str(xml, opts)
+                } else {
+                    xml.clone()
+                },
+            });
+            continue;
+        }
+        // The charDecl in the header resolves the gaiji of every juan.
+        let header = xml
+            .find("</teiHeader>")
+            .map(|i| &xml[..i + "</teiHeader>".len()])
+            .unwrap_or("");
+        for span in &map.juans {
+            let part = format!("{}{}", header, &xml[span.region_start()..span.end]);
+            let byline = (!span.bylines.is_empty()).then(|| {
+                span.bylines
+                    .iter()
+                    .map(|b| b.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" / ")
+            });
+            let fields = header_fields(
+                info,
+                source,
+                Some((&span.n, juan_count)),
+                span.title.clone(),
+                byline,
+            );
+            out.push(ExportFile {
+                name: format!("{}_{}.{}", info.id, span.n, ext),
+                juan: Some(span.n.clone()),
+                juan_title: span.title.clone(),
+                front_matter: front_matter(&fields),
+                body: convert_str(&part, opts),
+            });
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn juans_become_files_with_front_matter() {
+        let xml = r##"<TEI><teiHeader><charDecl><char xml:id="CB00178"><mapping type="unicode">U+24D36</mapping></char></charDecl></teiHeader>
+<text><body>
+<milestone unit="juan" n="1"/><juan fun="open" n="1"><jhead>經卷上</jhead></juan>
+<p><lb n="0001a01"/>如是<g ref="#CB00178"/>聞</p>
+<juan fun="close" n="1"/>
+<milestone unit="juan" n="2"/><juan fun="open" n="2"><jhead>經卷下</jhead></juan>
+<byline cb:type="translator">失譯</byline>
+<p><lb n="0002a01"/>歡喜奉行</p>
+<juan fun="close" n="2"/>
+</body></text></TEI>"##;
+        let info = WorkInfo {
+            corpus: "cbeta".into(),
+            id: "T0001".into(),
+            title: "某經: 試".into(),
+            author: Some("姚秦 鳩摩羅什譯".into()),
+            ..Default::default()
+        };
+        let opts = TextOptions {
+            format: TextFormat::Markdown,
+            ..Default::default()
+        };
+        let files = export_work(&info, &[("T01n0001.xml".into(), xml.into())], &opts);
+        let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["T0001_001.md", "T0001_002.md"]);
+        let first = files[0].content();
+        assert!(
+            first.starts_with("---\ntitle: \"某經: 試\"\nid: \"T0001\""),
+            "{}",
+            first
+        );
+        assert!(first.contains(
+            "juan: \"001\"\njuanCount: \"2\"\njuanTitle: \"經卷上\"\nauthor: \"姚秦 鳩摩羅什譯\""
+        ));
+        assert!(first.contains("如是\u{24D36}聞") && !first.contains("歡喜"));
+        assert!(files[1].front_matter.contains("author: \"失譯\""));
+
+        let info = WorkInfo {
+            corpus: "gretil".into(),
+            id: "buddhacarita".into(),
+            ..Default::default()
+        };
+        let src = ("a.xml".to_string(), "<TEI><p>buddha</p></TEI>".to_string());
+        let whole = export_work(&info, &[src], &TextOptions::default());
+        assert_eq!(whole[0].name, "buddhacarita.txt");
+        assert!(whole[0].content().ends_with("---\n\nbuddha\n"));
+    }
+}
@@ -0,0 +1,421 @@
+//! What every fetch does with the text it extracted.
+//!
+//! The fetch tools differ in how they find and extract a text; after that they all slice it
+//! (`startChar`/`endChar`, `startLine`/`endLine`, `page`/`pageSize`, capped by the call's
+//! budget and optionally snapped to sentences) and highlight the slice, with one pattern or
+//! several, each with its own markers. [`FetchOptions`] reads
+//! those arguments once and [`apply_fetch_pipeline`] runs the steps, so an option or a fix
+//! lands in one place for every corpus.
+
+use crate::highlight::{HighlightSpec, Highlighter};
+use crate::text_utils::{to_whitespace_fuzzy_literal, HighlightPos};
+use crate::{sentence, tokens};
+use serde_json::Value;
+
+/// Slice length when the call sets neither `endChar`, `maxChars`, `maxTokens` nor a page.
+pub const DEFAULT_SLICE_CHARS: usize = 8000;
+/// How far (chars) `snap: "sentence"` moves a slice bound to reach a sentence boundary.
+pub const SNAP_WINDOW: usize = 200;
+/// Lines around `lineNumber` without `contextBefore`/`contextAfter`/`contextLines`.
+pub const CONTEXT_BEFORE: usize = 10;
+pub const CONTEXT_AFTER: usize = 100;
+
+/// The slicing and highlighting arguments of a fetch call.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FetchOptions {
+    pub start_char: Option<usize>,
+    pub end_char: Option<usize>,
+    pub start_line: Option<usize>,
+    pub end_line: Option<usize>,
+    /// `page` and `pageSize` in chars. None with `pageBy`, whose pages are cut from the XML
+    /// before extraction.
+    pub page: Option<(usize, usize)>,
+    pub max_chars: Option<usize>,
+    pub max_tokens: Option<usize>,
+    /// The call's char budget: `maxChars` within its ceiling, else the configured default.
+    pub budget: usize,
+    /// Read to the end of the text (still within the budget).
+    pub full: bool,
+    /// `snap: "sentence"`.
+    pub snap_sentences: bool,
+    pub highlight: Option<String>,
+    pub highlight_regex: bool,
+    /// An array `highlight`: patterns with their own markers.
+    pub highlights: Vec<HighlightSpec>,
+    pub line_number: Option<usize>,
+    pub context_before: usize,
+    pub context_after: usize,
+}
+
+impl FetchOptions {
+    /// The options in a call's `args`; `budget` is the call's char budget, which depends on
+    /// server settings.
+    pub fn from_args(args: &Value, budget: usize) -> Self {
+        let num = |k: &str| args.get(k).and_then(|v| v.as_u64()).map(|x| x as usize);
+        let flag = |k: &str| args.get(k).and_then(|v| v.as_bool()).unwrap_or(false);
+        let context = num("contextLines");
+        FetchOptions {
+            start_char: num("startChar"),
+            end_char: num("endChar"),
+            start_line: num("startLine"),
+            end_line: num("endLine"),
+            page: num("page")
+                .zip(num("pageSize"))
+                .filter(|_| args.get("pageBy").is_none()),
+            max_chars: num("maxChars"),
+            max_tokens: num("maxTokens"),
+            budget,
+            full: flag("full"),
+            snap_sentences: args.get("snap").and_then(|v| v.as_str()) == Some("sentence"),
+            highlight: args
+                .get("highlight")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .map(String::from),
+            highlight_regex: flag("highlightRegex"),
+            highlights: args
+                .get("highlight")
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(highlight_spec).collect())
+                .unwrap_or_default(),
+            line_number: num("lineNumber"),
+            context_before: num("contextBefore").or(context).unwrap_or(CONTEXT_BEFORE),
+            context_after: num("contextAfter").or(context).unwrap_or(CONTEXT_AFTER),
+        }
+    }
+
+    /// `lineNumber` with the lines to take before and after it.
+    pub fn line_context(&self) -> Option<(usize, usize, usize)> {
+        self.line_number
+            .map(|n| (n, self.context_before, self.context_after))
+    }
+
+    /// Whether the call positions or sizes its slice itself.
+    pub fn has_slice(&self) -> bool {
+        self.start_char.is_some()
+            || self.end_char.is_some()
+            || self.start_line.is_some()
+            || self.end_line.is_some()
+            || self.page.is_some()
+            || self.max_chars.is_some()
+    }
+
+    /// The patterns to highlight: the array `highlight`, else the single pattern with the
+    /// call's markers. A literal with whitespace in it matches any run of whitespace
+    /// (extraction reflows lines).
+    pub fn highlight_specs(&self) -> Vec<HighlightSpec> {
+        let single = self.highlight.as_ref().map(|p| HighlightSpec {
+            pattern: p.clone(),
+            regex: self.highlight_regex,
+            ..Default::default()
+        });
+        let specs = if self.highlights.is_empty() {
+            single.into_iter().collect()
+        } else {
+            self.highlights.clone()
+        };
+        specs
+            .into_iter()
+            .map(|mut h| {
+                if !h.regex && h.pattern.chars().any(char::is_whitespace) {
+                    h.pattern = to_whitespace_fuzzy_literal(&h.pattern);
+                    h.regex = true;
+                }
+                h
+            })
+            .collect()
+    }
+
+    /// First char of the slice: `startChar`, else the start of `startLine`, else the page.
+    fn start(&self, text: &str) -> usize {
+        match self.page {
+            Some((p, ps)) if !self.full => p * ps,
+            _ => self
+                .start_char
+                .or_else(|| self.start_line.map(|l| char_at_line(text, l)))
+                .unwrap_or(0),
+        }
+    }
+
+    /// `endChar`, else the end of `endLine` (its newline included).
+    fn end(&self, text: &str) -> Option<usize> {
+        self.end_char
+            .or_else(|| self.end_line.map(|l| char_at_line(text, l + 1)))
+    }
+}
+
+/// The part of a fetch's extracted text that is returned, as char offsets into that text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FetchSlice {
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+    pub total: usize,
+    /// Where the requested range ends: `endChar` or the page end, else the end of the text.
+    /// Past `end` when the budget cut it short.
+    pub requested_end: usize,
+    /// 1-based line (of the extracted text) on which the next slice begins.
+    pub next_line: usize,
+}
+
+impl FetchSlice {
+    pub fn new(text: &str, slice: String, start: usize, end: usize, total: usize) -> Self {
+        FetchSlice {
+            text: slice,
+            start,
+            end,
+            total,
+            requested_end: total,
+            next_line: line_at_char(text, end),
+        }
+    }
+}
+
+/// A sliced and highlighted fetch.
+#[derive(Clone, Debug)]
+pub struct Fetched {
+    pub span: FetchSlice,
+    /// The slice with its highlight markers.
+    pub text: String,
+    /// Highlighted matches, as char offsets into the slice.
+    pub highlights: Vec<HighlightPos>,
+    /// The same matches by pattern, in the order of [`FetchOptions::highlight_specs`].
+    pub by_pattern: Vec<Vec<HighlightPos>>,
+}
+
+/// An entry of an array `highlight`: a pattern string, or `{pattern, regex, prefix, suffix}`.
+pub fn highlight_spec(v: &Value) -> Option<HighlightSpec> {
+    if let Some(p) = v.as_str() {
+        return (!p.is_empty()).then(|| HighlightSpec {
+            pattern: p.to_string(),
+            ..Default::default()
+        });
+    }
+    let text = |k: &str| v.get(k).and_then(|x| x.as_str()).map(String::from);
+    Some(HighlightSpec {
+        pattern: text("pattern").filter(|p| !p.is_empty())?,
+        regex: v.get("regex").and_then(|x| x.as_bool()).unwrap_or(false),
+        prefix: text("prefix"),
+        suffix: text("suffix"),
+    })
+}
+
+/// Slice `text` as `opts` asks, always within the budget (`budget`, and `max_tokens` when
+/// set), then move the bounds onto sentence boundaries with `snap_sentences` (an end that
+/// would have to move past the cap moves back instead, and a `startChar` is never moved).
+/// `end` is exactly where a continuation has to start.
+pub fn slice(text: &str, opts: &FetchOptions) -> FetchSlice {
+    let start = opts.start(text);
+    let end_char = opts.end(text);
+    let len = if opts.full {
+        usize::MAX
+    } else if let Some((_, ps)) = opts.page {
+        ps
+    } else if let Some(e) = end_char {
+        e.saturating_sub(start)
+    } else if let Some(n) = opts.max_chars {
+        n
+    } else if opts.max_tokens.is_some() {
+        usize::MAX
+    } else {
+        DEFAULT_SLICE_CHARS
+    };
+    let mut cap = len.min(opts.budget);
+    if let Some(t) = opts.max_tokens {
+        let from = text
+            .char_indices()
+            .nth(start)
+            .map_or(text.len(), |(b, _)| b);
+        cap = cap.min(tokens::chars_within(&text[from..], t).max(1));
+    }
+    let (mut sliced, total, mut start, mut end) = slice_text_bounds(text, start, cap);
+    if opts.snap_sentences {
+        let (s, e) = if opts.start_char.is_some() {
+            // An explicit start (a cursor's among them) stays put, so continuations move on;
+            // only the end snaps, back to a boundary inside the slice.
+            let e = sentence::boundary_before(text, start, end, SNAP_WINDOW).unwrap_or(end);
+            (start, e)
+        } else {
+            let (s, mut e) = sentence::snap(text, start, end, SNAP_WINDOW);
+            // Snapping forward must not take the slice past its length or budget.
+            let limit = s.saturating_add(cap);
+            if e > limit {
+                e = sentence::boundary_before(text, s, limit, SNAP_WINDOW).unwrap_or(limit);
+            }
+            (s, e)
+        };
+        if (s, e) != (start, end) {
+            (sliced, _, start, end) = slice_text_bounds(text, s, e - s);
+        }
+    }
+    let mut span = FetchSlice::new(text, sliced, start, end, total);
+    if !opts.full && (opts.page.is_some() || end_char.is_some()) {
+        span.requested_end = start.saturating_add(len).min(total);
+    }
+    span
+}
+
+/// Slice `text` and highlight the slice, with `highlighter`'s markers where a pattern has
+/// none of its own.
+pub fn apply_fetch_pipeline(text: &str, opts: &FetchOptions, highlighter: &Highlighter) -> Fetched {
+    let span = slice(text, opts);
+    let (decorated, by_pattern) = highlighter.apply_many(&span.text, &opts.highlight_specs());
+    let mut highlights: Vec<HighlightPos> = by_pattern.concat();
+    highlights.sort_by_key(|p| p.start_char);
+    Fetched {
+        span,
+        text: decorated,
+        highlights,
+        by_pattern,
+    }
+}
+
+/// `max_chars` chars of `text` from char `start_char`, with the text's char count and the
+/// slice's char bounds (both clamped to the text).
+pub fn slice_text_bounds(
+    text: &str,
+    start_char: usize,
+    max_chars: usize,
+) -> (String, usize, usize, usize) {
+    let total_chars = text.chars().count();
+    let effective_start = std::cmp::min(start_char, total_chars);
+    let target_end = effective_start.saturating_add(max_chars);
+    let effective_end = std::cmp::min(target_end, total_chars);
+
+    let start_byte = if effective_start == total_chars {
+        text.len()
+    } else {
+        text.char_indices()
+            .nth(effective_start)
+            .map(|(idx, _)| idx)
+            .unwrap_or(text.len())
+    };
+    let end_byte = if effective_end == total_chars {
+        text.len()
+    } else {
+        text.char_indices()
+            .nth(effective_end)
+            .map(|(idx, _)| idx)
+            .unwrap_or(text.len())
+    };
+
+    let slice = if start_byte <= end_byte {
+        text[start_byte..end_byte].to_string()
+    } else {
+        String::new()
+    };
+
+    (slice, total_chars, effective_start, effective_end)
+}
+
+/// Char offset at which 1-based line `line` of `text` begins; the end of the text past the
+/// last line.
+pub fn char_at_line(text: &str, line: usize) -> usize {
+    if line <= 1 {
+        return 0;
+    }
+    let mut chars = 0usize;
+    let mut seen = 1usize;
+    for c in text.chars() {
+        chars += 1;
+        if c == '\n' {
+            seen += 1;
+            if seen == line {
+                return chars;
+            }
+        }
+    }
+    chars
+}
+
+/// 1-based line number of char offset `pos` in `text`.
+pub fn line_at_char(text: &str, pos: usize) -> usize {
+    let byte = text
+        .char_indices()
+        .nth(pos)
+        .map(|(b, _)| b)
+        .unwrap_or(text.len());
+    text[..byte].matches('\n').count() + 1
+}
+
+/// Char offsets at which the lines of chars `start..end` of `text` begin (the first is
+/// `start`).
+pub fn line_starts(text: &str, start: usize, end: usize) -> Vec<usize> {
+    let mut starts = vec![start];
+    for (i, c) in text
+        .chars()
+        .skip(start)
+        .take(end.saturating_sub(start))
+        .enumerate()
+    {
+        if c == '\n' && start + i + 1 < end {
+            starts.push(start + i + 1);
+        }
+    }
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn options_read_once_and_run_in_order() {
+        let text = "如是我聞。\n一時佛在舍衛國。\n爾時世尊 告諸比丘。";
+        let args = json!({
+            "startLine": 2, "maxChars": 12, "snap": "sentence",
+            "highlight": "世尊 告", "highlightStyle": "brackets", "contextLines": 3
+        });
+        let opts = FetchOptions::from_args(&args, 6000);
+        assert_eq!(opts.line_context(), None);
+        assert_eq!((opts.context_before, opts.context_after), (3, 3));
+        assert!(opts.has_slice());
+        let out = apply_fetch_pipeline(text, &opts, &Highlighter::style("brackets").unwrap());
+        assert_eq!(out.span.start, 6);
+        // Twelve chars end inside the last sentence; the snap takes them back to the full stop.
+        assert_eq!(out.span.text, "一時佛在舍衛國。\n");
+        assert!(out.highlights.is_empty());
+        let all = FetchOptions {
+            start_line: None,
+            max_chars: None,
+            ..opts
+        };
+        let out = apply_fetch_pipeline(text, &all, &Highlighter::style("brackets").unwrap());
+        assert!(out.text.ends_with("爾時⟦世尊 告⟧諸比丘。"));
+        assert_eq!(out.highlights.len(), 1);
+        // `page` is in chars only without `pageBy`; the budget caps every request.
+        let paged =
+            FetchOptions::from_args(&json!({"page": 1, "pageSize": 4, "pageBy": "head"}), 2);
+        assert_eq!(
+            (paged.page, slice(text, &paged).text.as_str()),
+            (None, "如是")
+        );
+        // An array highlights each pattern with its own markers.
+        let many = FetchOptions::from_args(
+            &json!({"highlight": ["如是", {"pattern": "世尊 告", "prefix": "<", "suffix": ">"}, 7]}),
+            6000,
+        );
+        assert_eq!(many.highlights.len(), 2);
+        let out = apply_fetch_pipeline(text, &many, &Highlighter::style("brackets").unwrap());
+        assert!(out.text.starts_with("⟦如是⟧我聞"));
+        assert!(out.text.contains("爾時<世尊 告>諸比丘"));
+        assert_eq!(
+            out.by_pattern.iter().map(Vec::len).collect::<Vec<_>>(),
+            [1, 1]
+        );
+    }
+
+    #[test]
+    fn sentence_snap_stays_within_the_budget() {
+        let text = "如是我聞一時佛在舍衛國。與大比丘眾。";
+        let opts = FetchOptions::from_args(&json!({"snap": "sentence"}), 10);
+        // The only boundary after the start is two chars past the budget: the end stays put.
+        let span = slice(text, &opts);
+        assert_eq!((span.start, span.end), (0, 10));
+        assert_eq!(span.text, "如是我聞一時佛在舍衛");
+        // A boundary within the budget is still snapped back to.
+        let opts = FetchOptions::from_args(&json!({"snap": "sentence"}), 14);
+        assert_eq!(slice(text, &opts).end, 12);
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Write a file atomically: the bytes go to a temp file in the same directory which is then
+/// renamed over `path`, so readers never observe a partially written cache entry.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    std::fs::create_dir_all(dir)?;
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+    tmp.write_all(bytes)?;
+    tmp.as_file().sync_all()?;
+    tmp.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// The result of an in-flight call as seen by its waiters.
+enum State<V> {
+    Pending,
+    Done(V),
+    /// The leader panicked; waiters retry the call themselves.
+    Abandoned,
+}
+
+type Slot<V> = Arc<(Mutex<State<V>>, Condvar)>;
+
+/// Coalesces concurrent requests for the same key: the first caller runs the work,
+/// later callers block until it finishes and receive a clone of its result.
+pub struct Coalescer<V: Clone> {
+    inflight: Mutex<HashMap<String, Slot<V>>>,
+}
+
+impl<V: Clone> Default for Coalescer<V> {
+    fn default() -> Self {
+        Self {
+            inflight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// Releases the leader's key and wakes its waiters when it returns or unwinds; a slot still
+/// pending at that point is marked abandoned. The key is gone before anyone wakes, so a
+/// retrying waiter never finds the abandoned slot again.
+struct Release<'a, V: Clone> {
+    coalescer: &'a Coalescer<V>,
+    key: &'a str,
+    slot: &'a Slot<V>,
+}
+
+impl<V: Clone> Drop for Release<'_, V> {
+    fn drop(&mut self) {
+        let (lock, cvar) = &**self.slot;
+        let mut map = self
+            .coalescer
+            .inflight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if map.get(self.key).is_some_and(|s| Arc::ptr_eq(s, self.slot)) {
+            map.remove(self.key);
+        }
+        let mut state = lock.lock().unwrap_or_else(|e| e.into_inner());
+        if matches!(*state, State::Pending) {
+            *state = State::Abandoned;
+        }
+        cvar.notify_all();
+    }
+}
+
+impl<V: Clone> Coalescer<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `work` for `key`, or wait for the call already running it. If that call panics,
+    /// the waiters run the work again (one of them leading).
+    pub fn run(&self, key: &str, work: impl FnOnce() -> V) -> V {
+        loop {
+            let (slot, leader) = {
+                let mut map = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+                match map.get(key) {
+                    Some(s) => (s.clone(), false),
+                    None => {
+                        let s: Slot<V> = Arc::new((Mutex::new(State::Pending), Condvar::new()));
+                        map.insert(key.to_string(), s.clone());
+                        (s, true)
+                    }
+                }
+            };
+            let (lock, cvar) = &*slot;
+            if leader {
+                let _release = Release {
+                    coalescer: self,
+                    key,
+                    slot: &slot,
+                };
+                let v = work();
+                *lock.lock().unwrap_or_else(|e| e.into_inner()) = State::Done(v.clone());
+                return v;
+            }
+            let mut guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+            while matches!(*guard, State::Pending) {
+                guard = cvar.wait(guard).unwrap_or_else(|e| e.into_inner());
+            }
+            if let State::Done(v) = &*guard {
+                return v.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn concurrent_callers_share_one_fetch() {
+        let c: Arc<Coalescer<String>> = Arc::new(Coalescer::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let start = Arc::new(std::sync::Barrier::new(8));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let c = c.clone();
+                let calls = calls.clone();
+                let start = start.clone();
+                std::thread::spawn(move || {
+                    start.wait();
+                    c.run("https://example.org/a", || {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        std::thread::sleep(Duration::from_millis(100));
+                        "body".to_string()
+                    })
+                })
+            })
+            .collect();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), "body");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        // Once finished, the key is released and a new call runs again.
+        assert_eq!(
+            c.run("https://example.org/a", || "again".to_string()),
+            "again"
+        );
+    }
+
+    #[test]
+    fn waiters_retry_when_the_leader_panics() {
+        let c: Arc<Coalescer<String>> = Arc::new(Coalescer::new());
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let leader = {
+            let c = c.clone();
+            std::thread::spawn(move || {
+                c.run("https://example.org/a", || {
+                    started_tx.send(()).unwrap();
+                    std::thread::sleep(Duration::from_millis(100));
+                    panic!("fetch failed");
+                })
+            })
+        };
+        started_rx.recv().unwrap();
+        let waiter = {
+            let c = c.clone();
+            std::thread::spawn(move || c.run("https://example.org/a", || "retried".to_string()))
+        };
+        assert!(leader.join().is_err());
+        assert_eq!(waiter.join().unwrap(), "retried");
+        assert!(c.inflight.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn atomic_write_replaces_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let p = dir.path().join("sub").join("x.txt");
+        write_atomic(&p, b"one").unwrap();
+        write_atomic(&p, b"two").unwrap();
+        assert_eq!(std::fs::read_to_string(&p).unwrap(), "two");
+        assert_eq!(std::fs::read_dir(p.parent().unwrap()).unwrap().count(), 1);
+    }
+}
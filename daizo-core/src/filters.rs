@@ -0,0 +1,374 @@
+//! Restricting searches to index entries by their metadata.
+//!
+//! A filter is checked against an [`IndexEntry`]'s `meta`; the search tools turn the entries
+//! that pass into the set of files to grep, and title searches skip the others while ranking.
+
+use crate::text_utils::normalized;
+use crate::IndexEntry;
+use serde::Serialize;
+
+/// Leading digits of a sutra number: `0220a` → 220.
+fn number_prefix(s: &str) -> Option<u32> {
+    let digits: String = s
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+fn meta<'a>(e: &'a IndexEntry, key: &str) -> Option<&'a str> {
+    e.meta.as_ref()?.get(key).map(|s| s.as_str())
+}
+
+/// Parse `220`, `1-99` or `0220-0250` into an inclusive range of sutra numbers.
+pub fn parse_number_range(s: &str) -> Option<(u32, u32)> {
+    let (a, b) = match s.split_once('-') {
+        Some((a, b)) => (number_prefix(a)?, number_prefix(b)?),
+        None => {
+            let n = number_prefix(s)?;
+            (n, n)
+        }
+    };
+    (a <= b).then_some((a, b))
+}
+
+/// CBETA entries by canon, sutra number and number of juans. Serializes with the names of
+/// the tool arguments.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CbetaFilter {
+    /// Canon codes (`T`, `X`, `J`, …); empty allows all.
+    #[serde(rename = "canon")]
+    pub canons: Vec<String>,
+    /// Inclusive range of `nnum`.
+    #[serde(rename = "nnumRange")]
+    pub nnum: Option<(u32, u32)>,
+    #[serde(rename = "juanCountMin")]
+    pub juan_min: Option<u32>,
+    #[serde(rename = "juanCountMax")]
+    pub juan_max: Option<u32>,
+}
+
+impl CbetaFilter {
+    pub fn is_empty(&self) -> bool {
+        *self == CbetaFilter::default()
+    }
+
+    pub fn matches(&self, e: &IndexEntry) -> bool {
+        if !self.canons.is_empty() {
+            let canon = meta(e, "canon").unwrap_or("");
+            if !self.canons.iter().any(|c| c.eq_ignore_ascii_case(canon)) {
+                return false;
+            }
+        }
+        if let Some((lo, hi)) = self.nnum {
+            match meta(e, "nnum").and_then(number_prefix) {
+                Some(n) if (lo..=hi).contains(&n) => {}
+                _ => return false,
+            }
+        }
+        if self.juan_min.is_some() || self.juan_max.is_some() {
+            let juans = meta(e, "juanCount")
+                .and_then(|j| j.parse::<u32>().ok())
+                .unwrap_or(0);
+            if self.juan_min.is_some_and(|m| juans < m) || self.juan_max.is_some_and(|m| juans > m)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Piṭaka of a VRI file stem: `vin01m.mul` → `vinaya`, `s0201a.att` → `sutta`,
+/// `abh03t.tik` → `abhidhamma`. None for the extra-canonical (`e…`) files.
+pub fn tipitaka_basket(stem: &str) -> Option<&'static str> {
+    let s = stem.to_ascii_lowercase();
+    if s.starts_with("vin") {
+        Some("vinaya")
+    } else if s.starts_with("abh") {
+        Some("abhidhamma")
+    } else if s.starts_with('s') && s[1..].starts_with(|c: char| c.is_ascii_digit()) {
+        Some("sutta")
+    } else {
+        None
+    }
+}
+
+/// Edition layer of a VRI file stem: `mul` (root text), `att` (aṭṭhakathā), `tik` (ṭīkā) or
+/// `nrf` (other).
+pub fn tipitaka_edition(stem: &str) -> Option<String> {
+    let (_, ext) = stem.rsplit_once('.')?;
+    Some(ext.to_ascii_lowercase())
+}
+
+/// Nikāya code of a Sutta Piṭaka file stem (`s01…` → `DN` … `s05…` → `KN`).
+pub fn tipitaka_nikaya_code(stem: &str) -> Option<&'static str> {
+    let code = match stem.get(..3)?.to_ascii_lowercase().as_str() {
+        "s01" => "DN",
+        "s02" => "MN",
+        "s03" => "SN",
+        "s04" => "AN",
+        "s05" => "KN",
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Tipiṭaka entries by basket, nikāya and edition layer, read from the VRI file name and
+/// the `nikaya` field of the index. Each list allows any of its values; empty allows all.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TipitakaFilter {
+    /// `vinaya`, `sutta` or `abhidhamma`.
+    pub basket: Vec<String>,
+    /// A code (`DN`, `MN`, `SN`, `AN`, `KN`) or part of the nikāya's name (`Dīgha`,
+    /// `majjhima`), matched without diacritics.
+    pub nikaya: Vec<String>,
+    /// `mul`, `att` or `tik`.
+    pub edition: Vec<String>,
+}
+
+impl TipitakaFilter {
+    pub const BASKETS: [&'static str; 3] = ["vinaya", "sutta", "abhidhamma"];
+    pub const EDITIONS: [&'static str; 3] = ["mul", "att", "tik"];
+
+    pub fn is_empty(&self) -> bool {
+        *self == TipitakaFilter::default()
+    }
+
+    pub fn matches(&self, e: &IndexEntry) -> bool {
+        let stem = e.id.as_str();
+        if !self.basket.is_empty() {
+            let Some(b) = tipitaka_basket(stem) else {
+                return false;
+            };
+            if !self.basket.iter().any(|x| x.eq_ignore_ascii_case(b)) {
+                return false;
+            }
+        }
+        if !self.edition.is_empty() {
+            let Some(ed) = tipitaka_edition(stem) else {
+                return false;
+            };
+            if !self.edition.iter().any(|x| x.eq_ignore_ascii_case(&ed)) {
+                return false;
+            }
+        }
+        if !self.nikaya.is_empty() {
+            let code = tipitaka_nikaya_code(stem);
+            let name = meta(e, "nikaya").map(normalized).unwrap_or_default();
+            let hit = self.nikaya.iter().any(|x| {
+                if code.is_some_and(|c| c.eq_ignore_ascii_case(x)) {
+                    return true;
+                }
+                let nx = normalized(x);
+                !nx.is_empty() && name.contains(&nx)
+            });
+            if !hit {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Parse a century or an inclusive range of centuries: `5`, `4-6`, `-3` (3rd century BCE),
+/// `-3--1`. There is no century 0.
+pub fn parse_century_range(s: &str) -> Option<(i32, i32)> {
+    let re = regex::Regex::new(r"^\s*(-?\d+)\s*(?:(?:-|–|\.\.)\s*(-?\d+))?\s*$").ok()?;
+    let caps = re.captures(s)?;
+    let a: i32 = caps[1].parse().ok()?;
+    let b: i32 = match caps.get(2) {
+        Some(m) => m.as_str().parse().ok()?,
+        None => a,
+    };
+    (a != 0 && b != 0 && a <= b).then_some((a, b))
+}
+
+/// Years covered by a century: 5 → 401..=500, -2 → -200..=-101.
+fn century_years(c: i32) -> (i32, i32) {
+    if c > 0 {
+        ((c - 1) * 100 + 1, c * 100)
+    } else {
+        (c * 100, c * 100 + 99)
+    }
+}
+
+/// Whether `haystack` contains `needle`, both without case, diacritics or punctuation.
+fn folded_contains(haystack: &str, needle: &str) -> bool {
+    let n = normalized(needle);
+    !n.is_empty() && normalized(haystack).contains(&n)
+}
+
+/// GRETIL entries by author, genre and century of composition. The century comes from the
+/// header's `creation` date, so undated works never pass a century filter.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct GretilFilter {
+    /// Part of the author's name (`author` or a `respStmt` entry).
+    pub author: Vec<String>,
+    /// Part of the genre, keywords or classification.
+    pub genre: Vec<String>,
+    /// Inclusive range of centuries, negative for BCE.
+    pub century: Option<(i32, i32)>,
+}
+
+impl GretilFilter {
+    pub fn is_empty(&self) -> bool {
+        *self == GretilFilter::default()
+    }
+
+    pub fn matches(&self, e: &IndexEntry) -> bool {
+        let any_field = |wanted: &[String], keys: &[&str]| {
+            wanted.is_empty()
+                || keys
+                    .iter()
+                    .filter_map(|k| meta(e, k))
+                    .any(|v| wanted.iter().any(|w| folded_contains(v, w)))
+        };
+        if !any_field(&self.author, &["author", "respAll"]) {
+            return false;
+        }
+        if !any_field(&self.genre, &["genre", "keywords", "classCode", "catRef"]) {
+            return false;
+        }
+        if let Some((lo, hi)) = self.century {
+            let Some(d) = meta(e, "creationDate").and_then(crate::dating::parse_year_range) else {
+                return false;
+            };
+            let (start, end) = (century_years(lo).0, century_years(hi).1);
+            if d.end_year < start || d.start_year > end {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, canon: &str, nnum: &str, juans: &str) -> IndexEntry {
+        IndexEntry {
+            id: id.to_string(),
+            title: id.to_string(),
+            path: format!("/x/{}.xml", id),
+            meta: Some(
+                [("canon", canon), ("nnum", nnum), ("juanCount", juans)]
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            ),
+        }
+    }
+
+    #[test]
+    fn cbeta_filter_checks_meta() {
+        assert_eq!(parse_number_range("0220-0250"), Some((220, 250)));
+        assert_eq!(parse_number_range("262"), Some((262, 262)));
+        assert_eq!(parse_number_range("9-1"), None);
+        assert_eq!(parse_number_range("a-b"), None);
+
+        let heart = entry("T08n0251", "T", "0251", "1");
+        let prajna = entry("T05n0220a", "T", "0220a", "200");
+        let xu = entry("X01n0001", "X", "0001", "3");
+        let f = CbetaFilter {
+            canons: vec!["t".into()],
+            nnum: Some((200, 260)),
+            ..Default::default()
+        };
+        assert!(f.matches(&heart) && f.matches(&prajna) && !f.matches(&xu));
+        let f = CbetaFilter {
+            juan_min: Some(2),
+            juan_max: Some(10),
+            ..Default::default()
+        };
+        assert!(!f.matches(&heart) && !f.matches(&prajna) && f.matches(&xu));
+        assert!(CbetaFilter::default().is_empty());
+    }
+
+    #[test]
+    fn tipitaka_filter_reads_file_names() {
+        let stem_entry = |id: &str, nikaya: &str| IndexEntry {
+            id: id.to_string(),
+            title: id.to_string(),
+            path: format!("/x/{}.xml", id),
+            meta: Some(
+                [("nikaya".to_string(), nikaya.to_string())]
+                    .into_iter()
+                    .collect(),
+            ),
+        };
+        let dn = stem_entry("s0101m.mul", "Dīghanikāyo");
+        let dn_att = stem_entry("s0101a.att", "Dīghanikāye");
+        let vin = stem_entry("vin01m.mul", "Vinayapiṭake");
+        let extra = stem_entry("e0101n.nrf", "");
+        let f = TipitakaFilter {
+            edition: vec!["mul".into()],
+            ..Default::default()
+        };
+        assert!(f.matches(&dn) && !f.matches(&dn_att) && f.matches(&vin) && !f.matches(&extra));
+        let f = TipitakaFilter {
+            basket: vec!["Sutta".into()],
+            ..Default::default()
+        };
+        assert!(f.matches(&dn) && f.matches(&dn_att) && !f.matches(&vin));
+        for n in ["dn", "digha"] {
+            let f = TipitakaFilter {
+                nikaya: vec![n.into()],
+                edition: vec!["att".into()],
+                ..Default::default()
+            };
+            assert!(!f.matches(&dn) && f.matches(&dn_att) && !f.matches(&vin));
+        }
+    }
+
+    #[test]
+    fn gretil_filter_uses_creation_date() {
+        assert_eq!(parse_century_range("4-6"), Some((4, 6)));
+        assert_eq!(parse_century_range("-3--1"), Some((-3, -1)));
+        assert_eq!(parse_century_range("0"), None);
+        let gretil = |author: &str, genre: &str, date: Option<&str>| IndexEntry {
+            id: "x".to_string(),
+            title: "x".to_string(),
+            path: "/x/x.xml".to_string(),
+            meta: Some(
+                [
+                    ("author", Some(author)),
+                    ("genre", Some(genre)),
+                    ("creationDate", date),
+                ]
+                .into_iter()
+                .filter_map(|(k, v)| Some((k.to_string(), v?.to_string())))
+                .collect(),
+            ),
+        };
+        let bc = gretil("Aśvaghoṣa", "kāvya", Some("0080-0150"));
+        let undated = gretil("Nāgārjuna", "śāstra", None);
+        let f = GretilFilter {
+            author: vec!["asvaghosa".into()],
+            ..Default::default()
+        };
+        assert!(f.matches(&bc) && !f.matches(&undated));
+        let f = GretilFilter {
+            genre: vec!["Sastra".into()],
+            ..Default::default()
+        };
+        assert!(!f.matches(&bc) && f.matches(&undated));
+        let f = GretilFilter {
+            century: Some((2, 2)),
+            ..Default::default()
+        };
+        assert!(f.matches(&bc) && !f.matches(&undated));
+        let f = GretilFilter {
+            century: Some((-2, 1)),
+            ..Default::default()
+        };
+        assert!(f.matches(&bc));
+        let f = GretilFilter {
+            century: Some((3, 4)),
+            ..Default::default()
+        };
+        assert!(!f.matches(&bc));
+    }
+}
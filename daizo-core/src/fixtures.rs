@@ -0,0 +1,139 @@
+use std::path::PathBuf;
+
+/// One small corpus file used as a parsing fixture.
+#[derive(Clone, Debug)]
+pub struct Fixture {
+    pub corpus: &'static str,
+    /// Destination relative to the fixtures dir; mirrors the `~/.daizo` layout so that
+    /// `DAIZO_DIR=<fixtures dir>` works with the CLI and the MCP server.
+    pub path: &'static str,
+    pub url: &'static str,
+    /// What the file exercises (gaiji, notes, UTF-16, verse, ...).
+    pub covers: &'static str,
+}
+
+/// Curated subset fetched by `daizo-cli dev fetch-fixtures`. Files are kept small; each one is
+/// chosen for a parsing feature. SARIT and MUKTABODHA are not listed: the former has no small
+/// stable sample yet and the latter is distributed as registration-only zip archives.
+pub static FIXTURES: &[Fixture] = &[
+    Fixture {
+        corpus: "cbeta",
+        path: "xml-p5/T/T08/T08n0251.xml",
+        url: "https://raw.githubusercontent.com/cbeta-org/xml-p5/master/T/T08/T08n0251.xml",
+        covers: "short sutra, apparatus notes, byline",
+    },
+    Fixture {
+        corpus: "cbeta",
+        path: "xml-p5/T/T12/T12n0366.xml",
+        url: "https://raw.githubusercontent.com/cbeta-org/xml-p5/master/T/T12/T12n0366.xml",
+        covers: "gaiji (<g ref>), dhāraṇī, variant notes",
+    },
+    Fixture {
+        corpus: "tipitaka",
+        path: "tipitaka-xml/romn/s0501m.mul.xml",
+        url: "https://raw.githubusercontent.com/VipassanaTech/tipitaka-xml/master/romn/s0501m.mul.xml",
+        covers: "UTF-16 encoding, prose and verse",
+    },
+    Fixture {
+        corpus: "tipitaka",
+        path: "tipitaka-xml/romn/s0502m.mul.xml",
+        url: "https://raw.githubusercontent.com/VipassanaTech/tipitaka-xml/master/romn/s0502m.mul.xml",
+        covers: "UTF-16 encoding, gāthā verse (Dhammapada)",
+    },
+    Fixture {
+        corpus: "gretil",
+        path: "GRETIL/1_sanskr/tei/sa_prajJApAramitAhRdayasUtra.xml",
+        url: "https://gretil.sub.uni-goettingen.de/gretil/corpustei/sa_prajJApAramitAhRdayasUtra.xml",
+        covers: "TEI header availability, IAST prose",
+    },
+    Fixture {
+        corpus: "gretil",
+        path: "GRETIL/1_sanskr/tei/sa_bhagavadgItA.xml",
+        url: "https://gretil.sub.uni-goettingen.de/gretil/corpustei/sa_bhagavadgItA.xml",
+        covers: "verse (<lg>/<l>), IAST diacritics",
+    },
+];
+
+/// Fixtures directory: `DAIZO_FIXTURES`, else `fixtures/` at the workspace root.
+pub fn fixtures_dir() -> PathBuf {
+    if let Ok(p) = std::env::var("DAIZO_FIXTURES") {
+        if !p.is_empty() {
+            return PathBuf::from(p);
+        }
+    }
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .map(|p| p.join("fixtures"))
+        .unwrap_or_else(|| PathBuf::from("fixtures"))
+}
+
+/// Fixtures of `corpus` that have been downloaded, as absolute paths.
+pub fn available(corpus: &str) -> Vec<PathBuf> {
+    let dir = fixtures_dir();
+    FIXTURES
+        .iter()
+        .filter(|f| f.corpus == corpus)
+        .map(|f| dir.join(f.path))
+        .filter(|p| p.is_file())
+        .collect()
+}
+
+// These tests run against real corpus files and are skipped until
+// `daizo-cli dev fetch-fixtures` has been run.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cbeta_fixtures_parse() {
+        for p in available("cbeta") {
+            let xml = std::fs::read_to_string(&p).unwrap();
+            let plain = crate::extract_cbeta_plain_from_xml(&xml, false);
+            assert!(!plain.trim().is_empty(), "{}", p.display());
+            assert!(
+                !plain.contains("<g "),
+                "unresolved gaiji in {}",
+                p.display()
+            );
+            assert!(!crate::list_heads_cbeta(&xml).is_empty() || plain.len() < 2000);
+        }
+        let root = fixtures_dir().join("xml-p5");
+        if root.is_dir() {
+            let idx = crate::build_cbeta_index(&root);
+            assert_eq!(idx.len(), available("cbeta").len());
+            assert!(idx.iter().all(|e| !e.title.is_empty()));
+        }
+    }
+
+    #[test]
+    fn tipitaka_fixtures_decode_utf16() {
+        let root = fixtures_dir().join("tipitaka-xml").join("romn");
+        if available("tipitaka").is_empty() {
+            return;
+        }
+        let idx = crate::build_tipitaka_index(&root);
+        assert!(!idx.is_empty());
+        for p in available("tipitaka") {
+            let text = crate::read_file_with_encoding(&p).expect("decodable");
+            assert!(text.contains("<p"), "{}", p.display());
+            assert!(!crate::extract_text(&text).contains('\u{FFFD}'));
+        }
+    }
+
+    #[test]
+    fn gretil_fixtures_have_text_and_titles() {
+        for p in available("gretil") {
+            let xml = std::fs::read_to_string(&p).unwrap();
+            assert!(
+                !crate::extract_text(&xml).trim().is_empty(),
+                "{}",
+                p.display()
+            );
+        }
+        let root = fixtures_dir().join("GRETIL").join("1_sanskr").join("tei");
+        if root.is_dir() {
+            let idx = crate::build_gretil_index(&root);
+            assert!(idx.iter().all(|e| e.meta.is_some() && !e.title.is_empty()));
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::fetch_cache::write_atomic;
+use crate::path_resolver::cache_dir;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use unicode_normalization::char::{decompose_canonical, is_combining_mark};
+
+/// Push the diacritic-folded form of `c`: ASCII is kept as is (so regex syntax and XML
+/// markup survive), other characters are decomposed and their combining marks dropped
+/// (`ā` → `a`, `ñ` → `n`, `ṃ` → `m`).
+fn push_folded(c: char, out: &mut String) {
+    if c.is_ascii() {
+        out.push(c);
+        return;
+    }
+    decompose_canonical(c, |d| {
+        if !is_combining_mark(d) {
+            out.push(d);
+        }
+    });
+}
+
+/// Fold diacritics in a string (`paññā` → `panna`). Line breaks are preserved, so line
+/// numbers in the folded text match the original.
+pub fn fold_diacritics(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        push_folded(c, &mut out);
+    }
+    out
+}
+
+/// Map a byte span in `fold_diacritics(orig)` back to a byte span in `orig`.
+pub fn original_span(orig: &str, fstart: usize, fend: usize) -> (usize, usize) {
+    let mut f = 0usize;
+    let mut start: Option<usize> = None;
+    let mut tmp = String::new();
+    for (i, c) in orig.char_indices() {
+        if start.is_none() && f >= fstart {
+            start = Some(i);
+        }
+        if f >= fend {
+            return (start.unwrap_or(i), i);
+        }
+        tmp.clear();
+        push_folded(c, &mut tmp);
+        f += tmp.len();
+    }
+    (start.unwrap_or(orig.len()), orig.len())
+}
+
+pub(crate) fn shadow_path(dir: &Path, src: &Path) -> PathBuf {
+    let mut h = DefaultHasher::new();
+    src.to_string_lossy().hash(&mut h);
+    let stem = src.file_stem().and_then(|s| s.to_str()).unwrap_or("file");
+    dir.join(format!("{}-{:016x}.txt", stem, h.finish()))
+}
+
+/// Diacritic-folded shadow of a corpus file, cached under `cache/folded/`.
+/// The cached copy is reused while it is newer than the source file.
+pub fn folded_shadow(src: &Path, content: &str) -> String {
+    let sp = shadow_path(&cache_dir().join("folded"), src);
+    let fresh = match (std::fs::metadata(&sp), std::fs::metadata(src)) {
+        (Ok(s), Ok(o)) => match (s.modified(), o.modified()) {
+            (Ok(sm), Ok(om)) => sm >= om,
+            _ => false,
+        },
+        _ => false,
+    };
+    if fresh {
+        if let Ok(s) = std::fs::read_to_string(&sp) {
+            return s;
+        }
+    }
+    let folded = fold_diacritics(content);
+    let _ = write_atomic(&sp, folded.as_bytes());
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folds_pali_and_keeps_ascii() {
+        assert_eq!(fold_diacritics("Paññā saṃvāra"), "Panna samvara");
+        assert_eq!(
+            fold_diacritics("<p rend=\"x\">ḷ\nṭ</p>"),
+            "<p rend=\"x\">l\nt</p>"
+        );
+        assert_eq!(fold_diacritics(r"pa\S+"), r"pa\S+");
+    }
+
+    #[test]
+    fn maps_folded_span_back() {
+        let orig = "ayaṃ paññā ca";
+        let folded = fold_diacritics(orig);
+        let s = folded.find("panna").unwrap();
+        let (a, b) = original_span(orig, s, s + "panna".len());
+        assert_eq!(&orig[a..b], "paññā");
+        let (a, b) = original_span(orig, 0, folded.len());
+        assert_eq!(&orig[a..b], orig);
+    }
+}
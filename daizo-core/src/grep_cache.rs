@@ -0,0 +1,288 @@
+//! On-disk cache of corpus search results.
+//!
+//! A search over a whole corpus walks thousands of files, and clients often repeat the same
+//! query (a retry, a second page, another session). [`GrepCache`] stores the results as JSON
+//! under `~/.daizo/cache/grep/<corpus>/<revision>/`, keyed by the normalized query and the
+//! result limits. The revision is the HEAD commit of the corpus checkout, so pulling new data
+//! starts a new directory and the entries of the old commit are removed on the next store
+//! (`daizo-cli data-update` carries the unaffected ones over with [`GrepCache::carry_over`]).
+//! Corpora that are not git checkouts have no revision and are never cached.
+
+use crate::bundle::hash_text;
+use crate::fetch_cache::write_atomic;
+use crate::path_resolver::cache_dir;
+use crate::provenance::git_snapshot;
+use crate::GrepResult;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
+
+/// Default number of cached searches kept per corpus.
+pub const DEFAULT_MAX_ENTRIES: usize = 500;
+
+/// Where the cache lives by default: `~/.daizo/cache/grep`.
+pub fn default_dir() -> PathBuf {
+    cache_dir().join("grep")
+}
+
+/// Revision of the corpus under `root`: its git HEAD commit.
+pub fn revision(root: &Path) -> Option<String> {
+    git_snapshot(root)
+}
+
+/// The query as it is keyed: NFC, surrounding whitespace removed.
+pub fn normalize_query(q: &str) -> String {
+    q.trim().nfc().collect()
+}
+
+/// What a search was run with; stored next to the results so a hash collision cannot
+/// return another query's hits.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrepKey {
+    pub corpus: String,
+    pub query: String,
+    pub max_results: usize,
+    pub max_matches_per_file: usize,
+}
+
+impl GrepKey {
+    pub fn new(corpus: &str, query: &str, max_results: usize, max_matches_per_file: usize) -> Self {
+        GrepKey {
+            corpus: corpus.to_string(),
+            query: normalize_query(query),
+            max_results,
+            max_matches_per_file,
+        }
+    }
+
+    fn file_name(&self) -> String {
+        let k = format!(
+            "{}\u{0}{}\u{0}{}\u{0}{}",
+            self.corpus, self.query, self.max_results, self.max_matches_per_file
+        );
+        format!("{}.json", hash_text(&k))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    key: GrepKey,
+    revision: String,
+    results: Vec<GrepResult>,
+}
+
+pub struct GrepCache {
+    dir: PathBuf,
+    max_entries: usize,
+}
+
+/// Commits become directory names; anything but a plain hex id is hashed first.
+fn revision_dir(revision: &str) -> String {
+    if !revision.is_empty() && revision.chars().all(|c| c.is_ascii_hexdigit()) {
+        revision.to_string()
+    } else {
+        hash_text(revision)
+    }
+}
+
+impl GrepCache {
+    pub fn new(dir: PathBuf, max_entries: usize) -> Self {
+        GrepCache { dir, max_entries }
+    }
+
+    fn corpus_dir(&self, corpus: &str) -> PathBuf {
+        self.dir.join(corpus)
+    }
+
+    fn path(&self, key: &GrepKey, revision: &str) -> PathBuf {
+        self.corpus_dir(&key.corpus)
+            .join(revision_dir(revision))
+            .join(key.file_name())
+    }
+
+    /// Cached results of `key` at `revision`.
+    pub fn get(&self, key: &GrepKey, revision: &str) -> Option<Vec<GrepResult>> {
+        let path = self.path(key, revision);
+        let e = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str::<Entry>(&s).ok())
+            .filter(|e| e.key == *key && e.revision == revision);
+        let Some(e) = e else {
+            crate::metrics::cache_miss("grep");
+            return None;
+        };
+        crate::metrics::cache_hit("grep");
+        // Pruning goes by modification time, so a hit counts as a use.
+        if let Ok(f) = std::fs::File::options().append(true).open(&path) {
+            let _ = f.set_modified(std::time::SystemTime::now());
+        }
+        Some(e.results)
+    }
+
+    /// Store the results of `key` at `revision`, dropping entries of other revisions of the
+    /// corpus and the least recently used ones beyond the cap.
+    pub fn put(
+        &self,
+        key: &GrepKey,
+        revision: &str,
+        results: &[GrepResult],
+    ) -> std::io::Result<()> {
+        if self.max_entries == 0 {
+            return Ok(());
+        }
+        let corpus_dir = self.corpus_dir(&key.corpus);
+        let current = revision_dir(revision);
+        if let Ok(rd) = std::fs::read_dir(&corpus_dir) {
+            for e in rd.flatten() {
+                if e.file_name().to_str() != Some(current.as_str()) {
+                    let _ = std::fs::remove_dir_all(e.path());
+                }
+            }
+        }
+        let entry = Entry {
+            key: key.clone(),
+            revision: revision.to_string(),
+            results: results.to_vec(),
+        };
+        let bytes = serde_json::to_vec(&entry)?;
+        write_atomic(&self.path(key, revision), &bytes)?;
+        self.prune(&corpus_dir.join(current));
+        Ok(())
+    }
+
+    fn prune(&self, dir: &Path) {
+        let Ok(rd) = std::fs::read_dir(dir) else {
+            return;
+        };
+        let mut files: Vec<(std::time::SystemTime, PathBuf)> = rd
+            .flatten()
+            .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+            .collect();
+        if files.len() <= self.max_entries {
+            return;
+        }
+        files.sort();
+        let excess = files.len() - self.max_entries;
+        for (_, p) in files.into_iter().take(excess) {
+            let _ = std::fs::remove_file(p);
+        }
+    }
+
+    /// Move the cached searches of `corpus` from revision `from` to `to` after the checkout
+    /// was pulled, keeping those `keep` accepts (searches the changed files cannot affect)
+    /// and dropping the rest. Returns (kept, dropped).
+    pub fn carry_over(
+        &self,
+        corpus: &str,
+        from: &str,
+        to: &str,
+        mut keep: impl FnMut(&GrepKey, &[GrepResult]) -> bool,
+    ) -> (usize, usize) {
+        let old_dir = self.corpus_dir(corpus).join(revision_dir(from));
+        let Ok(rd) = std::fs::read_dir(&old_dir) else {
+            return (0, 0);
+        };
+        let (mut kept, mut dropped) = (0, 0);
+        for e in rd.flatten() {
+            let entry = std::fs::read_to_string(e.path())
+                .ok()
+                .and_then(|s| serde_json::from_str::<Entry>(&s).ok())
+                .filter(|en| en.revision == from && en.key.corpus == corpus);
+            let Some(mut entry) = entry else {
+                dropped += 1;
+                continue;
+            };
+            if !keep(&entry.key, &entry.results) {
+                dropped += 1;
+                continue;
+            }
+            entry.revision = to.to_string();
+            let moved = serde_json::to_vec(&entry)
+                .ok()
+                .is_some_and(|b| write_atomic(&self.path(&entry.key, to), &b).is_ok());
+            if moved {
+                kept += 1;
+            } else {
+                dropped += 1;
+            }
+        }
+        if revision_dir(from) != revision_dir(to) {
+            let _ = std::fs::remove_dir_all(&old_dir);
+        }
+        (kept, dropped)
+    }
+
+    /// Remove every cached search.
+    pub fn clear(&self) -> std::io::Result<()> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(id: &str) -> GrepResult {
+        GrepResult {
+            file_id: id.into(),
+            total_matches: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn entries_follow_the_corpus_revision() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = GrepCache::new(dir.path().to_path_buf(), 2);
+        let k = GrepKey::new("cbeta", " 色即是空 ", 20, 5);
+        assert_eq!(k, GrepKey::new("cbeta", "色即是空", 20, 5));
+
+        cache.put(&k, "aaa111", &[result("T08n0251")]).unwrap();
+        let hit = cache.get(&k, "aaa111").unwrap();
+        assert_eq!(hit[0].file_id, "T08n0251");
+        // Another limit is another search.
+        assert!(cache
+            .get(&GrepKey::new("cbeta", "色即是空", 10, 5), "aaa111")
+            .is_none());
+
+        // A new commit misses, and storing under it removes the old revision.
+        assert!(cache.get(&k, "bbb222").is_none());
+        cache.put(&k, "bbb222", &[]).unwrap();
+        assert!(!dir.path().join("cbeta").join("aaa111").exists());
+        assert!(cache.get(&k, "bbb222").unwrap().is_empty());
+
+        for q in ["a", "b", "c"] {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            cache
+                .put(&GrepKey::new("cbeta", q, 20, 5), "bbb222", &[])
+                .unwrap();
+        }
+        let n = std::fs::read_dir(dir.path().join("cbeta").join("bbb222"))
+            .unwrap()
+            .count();
+        assert_eq!(n, 2);
+        assert!(cache
+            .get(&GrepKey::new("cbeta", "c", 20, 5), "bbb222")
+            .is_some());
+
+        // After a pull, searches the changed files cannot affect move to the new commit.
+        let (kept, dropped) = cache.carry_over("cbeta", "bbb222", "ccc333", |k, _| k.query == "c");
+        assert_eq!((kept, dropped), (1, 1));
+        assert!(!dir.path().join("cbeta").join("bbb222").exists());
+        assert!(cache
+            .get(&GrepKey::new("cbeta", "c", 20, 5), "ccc333")
+            .is_some());
+        assert!(cache
+            .get(&GrepKey::new("cbeta", "b", 20, 5), "ccc333")
+            .is_none());
+
+        cache.clear().unwrap();
+        assert!(cache
+            .get(&GrepKey::new("cbeta", "c", 20, 5), "ccc333")
+            .is_none());
+    }
+}
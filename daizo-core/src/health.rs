@@ -0,0 +1,154 @@
+//! Figures for a status report: how big the caches are, how many entries an index file
+//! holds, when it was written and how much memory the process uses. Everything here only
+//! reads metadata (or skims JSON) so a status call stays cheap next to a search.
+
+use crate::provenance::datetime_utc;
+use serde::Serialize;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// Size of one directory under the cache root.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CacheUsage {
+    pub name: String,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Files and bytes below `dir`, following no symlinks; zero when it does not exist.
+pub fn dir_usage(dir: &Path) -> (u64, u64) {
+    let Ok(rd) = std::fs::read_dir(dir) else {
+        return (0, 0);
+    };
+    let mut files = 0;
+    let mut bytes = 0;
+    for e in rd.flatten() {
+        let Ok(ft) = e.file_type() else {
+            continue;
+        };
+        if ft.is_dir() {
+            let (f, b) = dir_usage(&e.path());
+            files += f;
+            bytes += b;
+        } else if ft.is_file() {
+            files += 1;
+            bytes += e.metadata().map(|m| m.len()).unwrap_or(0);
+        }
+    }
+    (files, bytes)
+}
+
+/// One entry per subdirectory of `cache` (`grep`, `sat`, `folded`, …) plus `indexes` for
+/// the index files at its top level, largest first.
+pub fn cache_usage(cache: &Path) -> Vec<CacheUsage> {
+    let Ok(rd) = std::fs::read_dir(cache) else {
+        return Vec::new();
+    };
+    let mut top = CacheUsage {
+        name: "indexes".to_string(),
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    for e in rd.flatten() {
+        let Ok(ft) = e.file_type() else {
+            continue;
+        };
+        if ft.is_dir() {
+            let (files, bytes) = dir_usage(&e.path());
+            out.push(CacheUsage {
+                name: e.file_name().to_string_lossy().into_owned(),
+                files,
+                bytes,
+            });
+        } else if ft.is_file() {
+            top.files += 1;
+            top.bytes += e.metadata().map(|m| m.len()).unwrap_or(0);
+        }
+    }
+    if top.files > 0 {
+        out.push(top);
+    }
+    out.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+    out
+}
+
+/// Number of entries in an index cache file, counted without building the entries.
+pub fn index_entry_count(index: &Path) -> Option<usize> {
+    let f = std::fs::File::open(index).ok()?;
+    let v: Vec<serde::de::IgnoredAny> = serde_json::from_reader(std::io::BufReader::new(f)).ok()?;
+    Some(v.len())
+}
+
+/// When `path` was last written, as `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn modified_utc(path: &Path) -> Option<String> {
+    let t = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(datetime_utc(t.duration_since(UNIX_EPOCH).ok()?.as_secs()))
+}
+
+/// Resident memory of this process in bytes (Linux `/proc`); None elsewhere.
+pub fn resident_bytes() -> Option<u64> {
+    let s = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kb: u64 = s
+        .lines()
+        .find_map(|l| l.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+/// `1536` → `1.5 KiB`.
+pub fn human_bytes(n: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if n < 1024 {
+        return format!("{} B", n);
+    }
+    let mut v = n as f64 / 1024.0;
+    let mut u = 0;
+    while v >= 1024.0 && u + 1 < UNITS.len() {
+        v /= 1024.0;
+        u += 1;
+    }
+    format!("{:.1} {}", v, UNITS[u])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_usage_counts_each_subdirectory() {
+        let dir = tempfile::tempdir().unwrap();
+        let c = dir.path();
+        std::fs::write(c.join("cbeta-index.json"), r#"[{"a":1},{"b":[2,3]},{}]"#).unwrap();
+        std::fs::create_dir_all(c.join("grep").join("cbeta").join("abc")).unwrap();
+        std::fs::write(
+            c.join("grep").join("cbeta").join("abc").join("x.json"),
+            "12345",
+        )
+        .unwrap();
+        std::fs::create_dir_all(c.join("sat")).unwrap();
+
+        let u = cache_usage(c);
+        let names: Vec<&str> = u.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["indexes", "grep", "sat"]);
+        assert_eq!((u[1].files, u[1].bytes), (1, 5));
+        assert_eq!((u[2].files, u[2].bytes), (0, 0));
+        assert!(cache_usage(&c.join("missing")).is_empty());
+
+        assert_eq!(index_entry_count(&c.join("cbeta-index.json")), Some(3));
+        assert_eq!(index_entry_count(&c.join("none.json")), None);
+        assert!(modified_utc(&c.join("cbeta-index.json"))
+            .unwrap()
+            .ends_with('Z'));
+
+        assert_eq!(human_bytes(512), "512 B");
+        assert_eq!(human_bytes(1536), "1.5 KiB");
+        assert_eq!(human_bytes(3 * 1024 * 1024), "3.0 MiB");
+        if cfg!(target_os = "linux") {
+            assert!(resident_bytes().unwrap() > 0);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Highlight markers.
+//!
+//! Fetch and search tools wrap matches of `highlight` in a prefix and suffix. The historical
+//! `>>> ` / ` <<<` reads well as plain text, but a `>>>` at the start of a line is a nested
+//! block quote to a Markdown renderer. Clients pick one of the [`PRESETS`] by name instead of
+//! spelling markers out; `none` leaves the text untouched and reports only the positions.
+//! Several patterns can be highlighted at once, each with its own markers ([`HighlightSpec`]).
+
+use crate::text_utils::{highlight_text, HighlightPos};
+use regex::Regex;
+
+/// Preset names with their prefix and suffix. The first is the default.
+pub const PRESETS: &[(&str, &str, &str)] = &[
+    ("arrows", ">>> ", " <<<"),
+    ("markdown-bold", "**", "**"),
+    ("html-mark", "<mark>", "</mark>"),
+    ("brackets", "⟦", "⟧"),
+    ("none", "", ""),
+];
+
+/// Name of the default preset.
+pub const DEFAULT_STYLE: &str = "arrows";
+
+/// Prefix and suffix of the preset `name` (case-insensitive).
+pub fn preset(name: &str) -> Option<(&'static str, &'static str)> {
+    let name = name.trim();
+    PRESETS
+        .iter()
+        .find(|(n, _, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, p, s)| (*p, *s))
+}
+
+/// A pattern to highlight with its own markers; a marker left `None` is the call's.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HighlightSpec {
+    pub pattern: String,
+    pub regex: bool,
+    pub prefix: Option<String>,
+    pub suffix: Option<String>,
+}
+
+/// The markers a call highlights with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Highlighter {
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl Default for Highlighter {
+    fn default() -> Self {
+        Highlighter::style(DEFAULT_STYLE).unwrap_or_else(|| Highlighter {
+            prefix: String::new(),
+            suffix: String::new(),
+        })
+    }
+}
+
+impl Highlighter {
+    /// The markers of preset `name`.
+    pub fn style(name: &str) -> Option<Self> {
+        preset(name).map(|(p, s)| Highlighter {
+            prefix: p.to_string(),
+            suffix: s.to_string(),
+        })
+    }
+
+    /// Markers from, in order of precedence: explicit `prefix`/`suffix`, the preset `style`,
+    /// then `fallback`. Each marker is taken separately, so a prefix alone keeps the style's
+    /// suffix. An unknown style counts as none given.
+    pub fn resolve(
+        style: Option<&str>,
+        prefix: Option<String>,
+        suffix: Option<String>,
+        fallback: &Highlighter,
+    ) -> Self {
+        let base = style
+            .and_then(Highlighter::style)
+            .unwrap_or_else(|| fallback.clone());
+        Highlighter {
+            prefix: prefix.unwrap_or(base.prefix),
+            suffix: suffix.unwrap_or(base.suffix),
+        }
+    }
+
+    /// Whether the markers leave the text as it is.
+    pub fn positions_only(&self) -> bool {
+        self.prefix.is_empty() && self.suffix.is_empty()
+    }
+
+    /// `text` with the matches of `pattern` wrapped, the number of matches and their positions
+    /// (character offsets into `text`).
+    pub fn apply(
+        &self,
+        text: &str,
+        pattern: &str,
+        is_regex: bool,
+    ) -> (String, usize, Vec<HighlightPos>) {
+        highlight_text(text, pattern, is_regex, &self.prefix, &self.suffix)
+    }
+
+    /// `text` with the matches of every pattern in `specs` wrapped in that pattern's markers,
+    /// and the positions (character offsets into `text`) of each pattern's matches. Where
+    /// matches of different patterns overlap, the one starting first is kept (the earlier
+    /// pattern on a tie). An invalid regex matches nothing.
+    pub fn apply_many(
+        &self,
+        text: &str,
+        specs: &[HighlightSpec],
+    ) -> (String, Vec<Vec<HighlightPos>>) {
+        let mut found: Vec<(usize, usize, usize)> = Vec::new();
+        for (i, spec) in specs.iter().enumerate() {
+            if spec.pattern.is_empty() {
+                continue;
+            }
+            if spec.regex {
+                if let Ok(re) = Regex::new(&spec.pattern) {
+                    found.extend(re.find_iter(text).map(|m| (m.start(), m.end(), i)));
+                }
+            } else {
+                found.extend(
+                    text.match_indices(spec.pattern.as_str())
+                        .map(|(b, m)| (b, b + m.len(), i)),
+                );
+            }
+        }
+        found.sort_by_key(|&(b, _, i)| (b, i));
+        let mut out = String::with_capacity(text.len());
+        let mut groups = vec![Vec::new(); specs.len()];
+        let (mut at, mut chars) = (0usize, 0usize);
+        for (b, e, i) in found {
+            if b < at {
+                continue;
+            }
+            chars += text[at..b].chars().count();
+            let len = text[b..e].chars().count();
+            groups[i].push(HighlightPos {
+                start_char: chars,
+                end_char: chars + len,
+            });
+            out.push_str(&text[at..b]);
+            out.push_str(specs[i].prefix.as_deref().unwrap_or(&self.prefix));
+            out.push_str(&text[b..e]);
+            out.push_str(specs[i].suffix.as_deref().unwrap_or(&self.suffix));
+            (at, chars) = (e, chars + len);
+        }
+        out.push_str(&text[at..]);
+        (out, groups)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_and_explicit_markers_combine() {
+        let d = Highlighter::default();
+        assert_eq!((d.prefix.as_str(), d.suffix.as_str()), (">>> ", " <<<"));
+        let md = Highlighter::style("Markdown-Bold").unwrap();
+        assert_eq!(md.apply("色即是空", "空", false).0, "色即是**空**");
+        assert!(Highlighter::style("rainbow").is_none());
+
+        let h = Highlighter::resolve(Some("html-mark"), None, Some("</em>".into()), &d);
+        assert_eq!(
+            h.apply("a b a", "a", false).0,
+            "<mark>a</em> b <mark>a</em>"
+        );
+        let h = Highlighter::resolve(Some("rainbow"), None, None, &md);
+        assert_eq!(h, md);
+
+        let none = Highlighter::style("none").unwrap();
+        assert!(none.positions_only());
+        let (text, n, pos) = none.apply("空空", "空", false);
+        assert_eq!((text.as_str(), n), ("空空", 2));
+        assert_eq!((pos[1].start_char, pos[1].end_char), (1, 2));
+    }
+
+    #[test]
+    fn several_patterns_keep_their_own_markers() {
+        let h = Highlighter::style("brackets").unwrap();
+        let specs = [
+            HighlightSpec {
+                pattern: "色即是空".into(),
+                ..Default::default()
+            },
+            HighlightSpec {
+                pattern: "空.".into(),
+                regex: true,
+                prefix: Some("<b>".into()),
+                suffix: Some("</b>".into()),
+            },
+        ];
+        let (text, groups) = h.apply_many("色即是空，空即是色", &specs);
+        // The second pattern's first match overlaps the first pattern's and is dropped.
+        assert_eq!(text, "⟦色即是空⟧，<b>空即</b>是色");
+        assert_eq!(groups[0].len(), 1);
+        assert_eq!((groups[1][0].start_char, groups[1][0].end_char), (5, 7));
+        // One spec without markers is the single-pattern highlight.
+        let one = [HighlightSpec {
+            pattern: "a".into(),
+            ..Default::default()
+        }];
+        assert_eq!(
+            h.apply_many("a b a", &one).0,
+            h.apply("a b a", "a", false).0
+        );
+    }
+}
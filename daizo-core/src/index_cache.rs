@@ -0,0 +1,190 @@
+//! In-process cache of a corpus index.
+//!
+//! Deserializing a large index file takes long enough to notice on every search, so a server
+//! keeps the entries in an [`IndexCache`] and hands out shared references. The file's
+//! modification time and size are noted when it is loaded; when either changes (a rebuild by
+//! `index_rebuild` or by another process) the next call loads the file again. Values computed
+//! from the entries, such as normalized title haystacks, live in a [`Derived`] that is
+//! recomputed whenever the index it came from has been replaced.
+
+use crate::timing::Phase;
+use crate::IndexEntry;
+use std::path::Path;
+use std::sync::{Arc, RwLock, Weak};
+use std::time::SystemTime;
+
+/// Entries of a loaded index, shared between the cache and the calls using it.
+pub type Index = Arc<Vec<IndexEntry>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Stamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+fn stamp(file: &Path) -> Option<Stamp> {
+    let m = std::fs::metadata(file).ok()?;
+    Some(Stamp {
+        modified: m.modified().ok()?,
+        len: m.len(),
+    })
+}
+
+struct Slot {
+    entries: Index,
+    stamp: Option<Stamp>,
+}
+
+#[derive(Default)]
+pub struct IndexCache {
+    slot: RwLock<Option<Slot>>,
+}
+
+impl IndexCache {
+    pub const fn new() -> Self {
+        IndexCache {
+            slot: RwLock::new(None),
+        }
+    }
+
+    /// The cached entries while `file` is as it was when they were loaded; otherwise `load`
+    /// runs (callers arriving meanwhile wait for it) and its entries are kept against the
+    /// file as `load` left it.
+    pub fn get_or_load(&self, file: &Path, load: impl FnOnce() -> Vec<IndexEntry>) -> Index {
+        let _t = Phase::IndexLoad.start();
+        let now = stamp(file);
+        {
+            let g = self.slot.read().unwrap_or_else(|e| e.into_inner());
+            if let Some(s) = g.as_ref().filter(|s| s.stamp == now) {
+                crate::metrics::cache_hit("index");
+                return s.entries.clone();
+            }
+        }
+        let mut g = self.slot.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(s) = g.as_ref().filter(|s| s.stamp == stamp(file)) {
+            crate::metrics::cache_hit("index");
+            return s.entries.clone();
+        }
+        crate::metrics::cache_miss("index");
+        let entries = Arc::new(load());
+        *g = Some(Slot {
+            entries: entries.clone(),
+            stamp: stamp(file),
+        });
+        entries
+    }
+
+    /// Replace the entries with ones just written to `file`.
+    pub fn store(&self, file: &Path, entries: Vec<IndexEntry>) -> Index {
+        let entries = Arc::new(entries);
+        *self.slot.write().unwrap_or_else(|e| e.into_inner()) = Some(Slot {
+            entries: entries.clone(),
+            stamp: stamp(file),
+        });
+        entries
+    }
+
+    /// The entries loaded so far, without looking at the file.
+    pub fn loaded(&self) -> Option<Index> {
+        let g = self.slot.read().unwrap_or_else(|e| e.into_inner());
+        g.as_ref().map(|s| s.entries.clone())
+    }
+}
+
+/// A value computed from the entries of an [`IndexCache`].
+pub struct Derived<T> {
+    slot: RwLock<Option<DerivedSlot<T>>>,
+}
+
+/// The value and the index it was computed from.
+type DerivedSlot<T> = (Weak<Vec<IndexEntry>>, Arc<T>);
+
+impl<T> Default for Derived<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Derived<T> {
+    pub const fn new() -> Self {
+        Derived {
+            slot: RwLock::new(None),
+        }
+    }
+
+    /// The value for `entries`, computed by `make` the first time. None when `entries` are
+    /// not the ones `cache` currently holds (a subset, or an index since replaced).
+    pub fn get(
+        &self,
+        cache: &IndexCache,
+        entries: &[IndexEntry],
+        make: impl FnOnce(&[IndexEntry]) -> T,
+    ) -> Option<Arc<T>> {
+        // While a Weak upgrades, the Vec it points to is alive, so equal data pointers mean
+        // the same index and not a new one allocated where a dropped one was.
+        let same = |idx: &Index| std::ptr::eq(idx.as_slice(), entries);
+        {
+            let g = self.slot.read().unwrap_or_else(|e| e.into_inner());
+            if let Some((w, v)) = g.as_ref() {
+                if w.upgrade().is_some_and(|i| same(&i)) {
+                    return Some(v.clone());
+                }
+            }
+        }
+        let current = cache.loaded().filter(same)?;
+        let v = Arc::new(make(&current));
+        *self.slot.write().unwrap_or_else(|e| e.into_inner()) =
+            Some((Arc::downgrade(&current), v.clone()));
+        Some(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str) -> IndexEntry {
+        IndexEntry {
+            id: id.into(),
+            title: id.into(),
+            path: format!("/c/{id}.xml"),
+            meta: None,
+        }
+    }
+
+    #[test]
+    fn index_reloads_when_its_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("cbeta-index.json");
+        std::fs::write(&file, "[1]").unwrap();
+        let cache = IndexCache::new();
+        let mut loads = 0;
+        let a = cache.get_or_load(&file, || {
+            loads += 1;
+            vec![entry("T0001")]
+        });
+        let b = cache.get_or_load(&file, || {
+            loads += 1;
+            vec![]
+        });
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(loads, 1);
+
+        let lens = Derived::new();
+        let h1 = lens.get(&cache, &a, |e| e.len()).unwrap();
+        assert!(Arc::ptr_eq(&h1, &lens.get(&cache, &a, |_| 0).unwrap()));
+        assert!(lens.get(&cache, &a[..0], |e| e.len()).is_none());
+
+        // A rewrite of a different size is seen on the next call.
+        std::fs::write(&file, "[1,2]").unwrap();
+        let c = cache.get_or_load(&file, || vec![entry("T0001"), entry("T0002")]);
+        assert_eq!(c.len(), 2);
+        assert_eq!(*lens.get(&cache, &c, |e| e.len()).unwrap(), 2);
+        // The old entries are still usable by whoever holds them, but no longer current.
+        assert!(lens.get(&cache, &a, |e| e.len()).is_none());
+
+        let d = cache.store(&file, vec![entry("T0003")]);
+        assert!(Arc::ptr_eq(&d, &cache.loaded().unwrap()));
+        assert!(Arc::ptr_eq(&d, &cache.get_or_load(&file, Vec::new)));
+    }
+}
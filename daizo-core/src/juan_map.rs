@@ -0,0 +1,648 @@
+use crate::{attr_val, local_name, GrepResult};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// One juan (fascicle) of a CBETA file, located by byte offsets into the XML.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JuanSpan {
+    /// Juan number, zero-padded to three digits ("001").
+    pub n: String,
+    /// Offset of `<juan fun="open">` (or of the milestone when the file has no open tag).
+    pub start: usize,
+    /// End of the juan body: the `<juan fun="close">` tag, else the next juan, else `</body>`.
+    pub end: usize,
+    /// Offset of `<milestone unit="juan">`, which usually precedes the open tag.
+    pub milestone: Option<usize>,
+    /// 1-based XML line of the first marker (milestone or open tag).
+    pub start_line: usize,
+    /// Heading text from `<jhead>` inside the open tag.
+    pub title: Option<String>,
+    pub first_lb: Option<String>,
+    pub last_lb: Option<String>,
+    /// `<pb n>` page anchors inside the juan, in document order.
+    pub pages: Vec<String>,
+    /// `<byline>` statements (translator, author, compiler …) of this juan.
+    pub bylines: Vec<Byline>,
+}
+
+/// A `<byline>` statement, e.g. `姚秦 三藏法師 鳩摩羅什 譯` with `cb:type="translator"`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Byline {
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    pub text: String,
+}
+
+impl JuanSpan {
+    /// First byte of the juan region, including a leading milestone.
+    pub fn region_start(&self) -> usize {
+        self.milestone
+            .map(|m| m.min(self.start))
+            .unwrap_or(self.start)
+    }
+}
+
+/// A section heading in the body: a `<cb:mulu>` entry or a `<head>`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct HeadMark {
+    /// 1-based XML line of the element.
+    pub line: usize,
+    pub title: String,
+    /// Nesting level, 1 outermost: `<cb:mulu level>`, else the depth of enclosing `<div>`s.
+    pub level: usize,
+}
+
+/// Where a line of the XML sits: its juan and the headings enclosing it, outermost first.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineContext {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub juan: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub juan_title: Option<String>,
+    pub heads: Vec<String>,
+    /// The juan and the heading chain joined for citing, e.g. `卷第三 > 譬喻品第三`.
+    pub label: String,
+}
+
+/// Juan structure of a CBETA file, built in one pass over the XML.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct JuanMap {
+    pub juans: Vec<JuanSpan>,
+    /// Headings in document order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub heads: Vec<HeadMark>,
+}
+
+/// Normalize a juan number for lookup ("1" and "001" are the same juan).
+pub fn normalize_juan_n(n: &str) -> String {
+    format!("{:0>3}", n.trim())
+}
+
+fn line_of(line_starts: &[usize], off: usize) -> usize {
+    match line_starts.binary_search(&off) {
+        Ok(i) => i + 1,
+        Err(i) => i,
+    }
+}
+
+impl JuanMap {
+    pub fn build(xml: &str) -> JuanMap {
+        let mut line_starts = vec![0usize];
+        line_starts.extend(
+            xml.bytes()
+                .enumerate()
+                .filter(|(_, b)| *b == b'\n')
+                .map(|(i, _)| i + 1),
+        );
+
+        let mut reader = Reader::from_str(xml);
+        reader.config_mut().trim_text_start = true;
+        reader.config_mut().trim_text_end = true;
+        let mut buf = Vec::new();
+        let mut juans: Vec<JuanSpan> = Vec::new();
+        let mut closes: Vec<Option<usize>> = Vec::new();
+        let mut cur: Option<usize> = None;
+        let mut body_start: Option<usize> = None;
+        let mut body_end: Option<usize> = None;
+        // Depth inside an open <juan> element, and inside its <jhead>.
+        let mut juan_depth = 0usize;
+        let mut jhead_depth = 0usize;
+        let mut jhead_buf = String::new();
+        // Depth inside a <byline>; bylines before the first juan marker are kept aside.
+        let mut byline_depth = 0usize;
+        let mut byline = Byline {
+            kind: None,
+            text: String::new(),
+        };
+        let mut orphan_bylines: Vec<Byline> = Vec::new();
+        // Depth inside a body heading (<cb:mulu> or <head>), and where it started.
+        let mut head_depth = 0usize;
+        let mut head_line = 0usize;
+        let mut head_buf = String::new();
+        let mut head_level = 1usize;
+        let mut heads: Vec<HeadMark> = Vec::new();
+        let mut div_depth = 0usize;
+
+        let span_for = |juans: &mut Vec<JuanSpan>,
+                        closes: &mut Vec<Option<usize>>,
+                        n: &str,
+                        off: usize|
+         -> usize {
+            let key = normalize_juan_n(n);
+            if let Some(i) = juans.iter().position(|j| j.n == key) {
+                return i;
+            }
+            juans.push(JuanSpan {
+                n: key,
+                start: off,
+                end: off,
+                milestone: None,
+                start_line: line_of(&line_starts, off),
+                title: None,
+                first_lb: None,
+                last_lb: None,
+                pages: Vec::new(),
+                bylines: Vec::new(),
+            });
+            closes.push(None);
+            juans.len() - 1
+        };
+
+        loop {
+            let pos = reader.buffer_position() as usize;
+            let ev = reader.read_event_into(&mut buf);
+            let (e, is_empty) = match &ev {
+                Ok(Event::Start(e)) => (Some(e.clone()), false),
+                Ok(Event::Empty(e)) => (Some(e.clone()), true),
+                _ => (None, false),
+            };
+            if let Some(e) = e {
+                let name_owned = e.name().as_ref().to_owned();
+                let name = local_name(&name_owned);
+                if name == b"div" && !is_empty {
+                    div_depth += 1;
+                }
+                match name {
+                    b"body" => body_start = Some(pos),
+                    b"milestone" => {
+                        if attr_val(&e, b"unit").as_deref() == Some("juan") {
+                            let n = attr_val(&e, b"n")
+                                .map(|v| v.into_owned())
+                                .unwrap_or_else(|| (juans.len() + 1).to_string());
+                            let i = span_for(&mut juans, &mut closes, &n, pos);
+                            juans[i].milestone.get_or_insert(pos);
+                            cur = Some(i);
+                        }
+                    }
+                    b"juan" => {
+                        let fun = attr_val(&e, b"fun").map(|v| v.to_ascii_lowercase());
+                        if fun.as_deref() == Some("close") {
+                            if let Some(i) = cur {
+                                closes[i].get_or_insert(pos);
+                            }
+                        } else {
+                            let n = attr_val(&e, b"n")
+                                .map(|v| v.into_owned())
+                                .or_else(|| cur.map(|i| juans[i].n.clone()))
+                                .unwrap_or_else(|| (juans.len() + 1).to_string());
+                            let i = span_for(&mut juans, &mut closes, &n, pos);
+                            if juans[i].milestone.is_some() {
+                                juans[i].start = pos;
+                            }
+                            cur = Some(i);
+                            if !is_empty {
+                                juan_depth = 1;
+                            }
+                        }
+                        if !is_empty && fun.as_deref() == Some("close") {
+                            juan_depth = 0;
+                        }
+                    }
+                    b"byline" if byline_depth == 0 && !is_empty => {
+                        byline_depth = 1;
+                        byline = Byline {
+                            kind: attr_val(&e, b"cb:type")
+                                .or_else(|| attr_val(&e, b"type"))
+                                .map(|v| v.into_owned()),
+                            text: String::new(),
+                        };
+                    }
+                    b"jhead" if juan_depth > 0 && !is_empty => {
+                        jhead_depth = 1;
+                        jhead_buf.clear();
+                    }
+                    b"mulu" | b"head"
+                        if body_start.is_some()
+                            && !is_empty
+                            && byline_depth == 0
+                            && jhead_depth == 0
+                            && head_depth == 0 =>
+                    {
+                        head_depth = 1;
+                        // Trimmed whitespace before the tag is part of this event.
+                        let ws = xml[pos..].len() - xml[pos..].trim_start().len();
+                        head_line = line_of(&line_starts, pos + ws);
+                        head_level = attr_val(&e, b"level")
+                            .filter(|_| name == b"mulu")
+                            .and_then(|v| v.trim().parse().ok())
+                            .unwrap_or(div_depth)
+                            .max(1);
+                        head_buf.clear();
+                    }
+                    b"lb" => {
+                        if let (Some(i), Some(n)) = (cur, attr_val(&e, b"n")) {
+                            let n = n.into_owned();
+                            juans[i].first_lb.get_or_insert_with(|| n.clone());
+                            juans[i].last_lb = Some(n);
+                        }
+                    }
+                    b"pb" => {
+                        if let (Some(i), Some(n)) = (cur, attr_val(&e, b"n")) {
+                            juans[i].pages.push(n.into_owned());
+                        }
+                    }
+                    _ => {
+                        if !is_empty {
+                            if byline_depth > 0 {
+                                byline_depth += 1;
+                            } else if jhead_depth > 0 {
+                                jhead_depth += 1;
+                            } else if head_depth > 0 {
+                                head_depth += 1;
+                            } else if juan_depth > 0 {
+                                juan_depth += 1;
+                            }
+                        }
+                    }
+                }
+                buf.clear();
+                continue;
+            }
+            match ev {
+                Ok(Event::End(e)) => {
+                    let name_owned = e.name().as_ref().to_owned();
+                    let name = local_name(&name_owned);
+                    if name == b"body" {
+                        body_end = Some(pos);
+                    }
+                    if name == b"div" {
+                        div_depth = div_depth.saturating_sub(1);
+                    }
+                    if byline_depth > 0 {
+                        byline_depth -= 1;
+                        if byline_depth == 0 {
+                            let b = Byline {
+                                kind: byline.kind.take(),
+                                text: byline.text.split_whitespace().collect::<Vec<_>>().join(" "),
+                            };
+                            if !b.text.is_empty() {
+                                match cur {
+                                    Some(i) => juans[i].bylines.push(b),
+                                    None => orphan_bylines.push(b),
+                                }
+                            }
+                        }
+                    } else if jhead_depth > 0 {
+                        jhead_depth -= 1;
+                        if jhead_depth == 0 {
+                            let t = jhead_buf.split_whitespace().collect::<Vec<_>>().join("");
+                            if let (Some(i), false) = (cur, t.is_empty()) {
+                                juans[i].title.get_or_insert(t);
+                            }
+                        }
+                    } else if head_depth > 0 {
+                        head_depth -= 1;
+                        if head_depth == 0 {
+                            let t = head_buf.split_whitespace().collect::<Vec<_>>().join(" ");
+                            // A <head> repeating the <cb:mulu> just before it adds nothing.
+                            if !t.is_empty() && heads.last().map(|h| &h.title) != Some(&t) {
+                                heads.push(HeadMark {
+                                    line: head_line,
+                                    title: t,
+                                    level: head_level,
+                                });
+                            }
+                        }
+                    } else {
+                        juan_depth = juan_depth.saturating_sub(1);
+                    }
+                }
+                Ok(Event::Text(t)) if byline_depth > 0 => {
+                    byline.text.push_str(&t.decode().unwrap_or_default());
+                }
+                Ok(Event::Text(t)) if jhead_depth > 0 => {
+                    jhead_buf.push_str(&t.decode().unwrap_or_default());
+                }
+                Ok(Event::Text(t)) if head_depth > 0 => {
+                    head_buf.push_str(&t.decode().unwrap_or_default());
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        let body_end = body_end.unwrap_or(xml.len());
+        if juans.is_empty() {
+            // No juan markers at all: the whole body is one juan.
+            let start = body_start.unwrap_or(0);
+            return JuanMap {
+                juans: vec![JuanSpan {
+                    n: normalize_juan_n("1"),
+                    start,
+                    end: body_end,
+                    milestone: None,
+                    start_line: line_of(&line_starts, start),
+                    title: None,
+                    first_lb: None,
+                    last_lb: None,
+                    pages: Vec::new(),
+                    bylines: orphan_bylines,
+                }],
+                heads,
+            };
+        }
+        // A byline ahead of the first juan marker (front matter) opens the first juan.
+        if !orphan_bylines.is_empty() {
+            orphan_bylines.append(&mut juans[0].bylines);
+            juans[0].bylines = orphan_bylines;
+        }
+        let next_starts: Vec<usize> = juans
+            .iter()
+            .skip(1)
+            .map(|j| j.region_start())
+            .chain(std::iter::once(body_end))
+            .collect();
+        for (i, j) in juans.iter_mut().enumerate() {
+            j.end = closes[i].unwrap_or(next_starts[i]).max(j.start);
+        }
+        JuanMap { juans, heads }
+    }
+
+    pub fn len(&self) -> usize {
+        self.juans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.juans.is_empty()
+    }
+
+    /// Look up a juan by number ("1", "001").
+    pub fn get(&self, part: &str) -> Option<&JuanSpan> {
+        let key = normalize_juan_n(part);
+        self.juans.iter().find(|j| j.n == key)
+    }
+
+    /// The juan whose region contains the byte offset; text after a close tag
+    /// (colophons, notes) belongs to the preceding juan.
+    pub fn juan_at_offset(&self, off: usize) -> Option<&JuanSpan> {
+        self.juans.iter().rev().find(|j| j.region_start() <= off)
+    }
+
+    /// The juan containing a 1-based XML line (as reported by grep).
+    pub fn juan_at_line(&self, line: usize) -> Option<&JuanSpan> {
+        self.juans.iter().rev().find(|j| j.start_line <= line)
+    }
+
+    /// The nearest heading at or before a 1-based XML line.
+    pub fn head_at_line(&self, line: usize) -> Option<&HeadMark> {
+        self.heads.iter().rev().find(|h| h.line <= line)
+    }
+
+    /// The headings enclosing a 1-based XML line, outermost first: each heading up to the line
+    /// closes the ones at its level and deeper.
+    pub fn head_chain_at_line(&self, line: usize) -> Vec<&HeadMark> {
+        let mut chain: Vec<&HeadMark> = Vec::new();
+        for h in self.heads.iter().take_while(|h| h.line <= line) {
+            while chain.last().is_some_and(|c| c.level >= h.level) {
+                chain.pop();
+            }
+            chain.push(h);
+        }
+        chain
+    }
+
+    /// The juan and heading chain of a 1-based XML line; None when the file has neither.
+    pub fn context_at_line(&self, line: usize) -> Option<LineContext> {
+        // A file without juan markers is one untitled juan, which says nothing.
+        let juan = self
+            .juan_at_line(line)
+            .filter(|j| self.juans.len() > 1 || j.title.is_some());
+        let heads: Vec<String> = self
+            .head_chain_at_line(line)
+            .into_iter()
+            .map(|h| h.title.clone())
+            .collect();
+        if juan.is_none() && heads.is_empty() {
+            return None;
+        }
+        // `妙法蓮華經卷第三` cites as `卷第三`.
+        let juan_label = juan.map(|j| match j.title.as_deref().and_then(|t| t.rfind('卷')) {
+            Some(at) => j.title.as_deref().unwrap_or_default()[at..].to_string(),
+            None => format!("卷{}", j.n.trim_start_matches('0')),
+        });
+        let label = juan_label
+            .into_iter()
+            .chain(heads.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(" > ");
+        Some(LineContext {
+            juan: juan.map(|j| j.n.clone()),
+            juan_title: juan.and_then(|j| j.title.clone()),
+            heads,
+            label,
+        })
+    }
+
+    /// Previous and next juan numbers, for navigation.
+    pub fn neighbors(&self, part: &str) -> (Option<&str>, Option<&str>) {
+        let key = normalize_juan_n(part);
+        let Some(i) = self.juans.iter().position(|j| j.n == key) else {
+            return (None, None);
+        };
+        (
+            i.checked_sub(1).map(|p| self.juans[p].n.as_str()),
+            self.juans.get(i + 1).map(|j| j.n.as_str()),
+        )
+    }
+}
+
+type JuanMapCache = HashMap<PathBuf, (Option<SystemTime>, u64, Arc<JuanMap>)>;
+
+static JUAN_MAP_CACHE: OnceLock<Mutex<JuanMapCache>> = OnceLock::new();
+const JUAN_MAP_CACHE_CAP: usize = 512;
+
+fn file_stamp(path: &Path) -> (Option<SystemTime>, u64) {
+    std::fs::metadata(path)
+        .map(|m| (m.modified().ok(), m.len()))
+        .unwrap_or((None, 0))
+}
+
+/// Juan map for a file, rebuilt only when the file's mtime or size changes.
+/// `xml` may be passed when the caller already holds the file contents.
+pub fn juan_map_cached(path: &Path, xml: Option<&str>) -> Option<Arc<JuanMap>> {
+    let cache = JUAN_MAP_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    let stamp = file_stamp(path);
+    if let Some((mt, len, m)) = cache.lock().unwrap_or_else(|e| e.into_inner()).get(path) {
+        if (*mt, *len) == stamp {
+            return Some(m.clone());
+        }
+    }
+    let map = Arc::new(match xml {
+        Some(x) => JuanMap::build(x),
+        None => JuanMap::build(&std::fs::read_to_string(path).ok()?),
+    });
+    let mut guard = cache.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.len() >= JUAN_MAP_CACHE_CAP {
+        guard.clear();
+    }
+    guard.insert(path.to_path_buf(), (stamp.0, stamp.1, map.clone()));
+    Some(map)
+}
+
+/// Fill `juan_number` (and, when grep left it empty, `section` with the nearest heading) on
+/// CBETA grep matches from their XML line numbers, and list the juans that contain matches
+/// as the result's recommended fetch parts.
+pub fn attribute_juans(results: &mut [GrepResult]) {
+    results.par_iter_mut().for_each(|r| {
+        let Some(map) = juan_map_cached(Path::new(&r.file_path), None) else {
+            return;
+        };
+        for m in r.matches.iter_mut() {
+            if let Some(j) = m.line_number.and_then(|ln| map.juan_at_line(ln)) {
+                if !r.fetch_hints.recommended_parts.contains(&j.n) {
+                    r.fetch_hints.recommended_parts.push(j.n.clone());
+                }
+                m.juan_number = Some(j.n.clone());
+            }
+            if m.section.is_none() {
+                m.section = m
+                    .line_number
+                    .and_then(|ln| map.head_at_line(ln))
+                    .map(|h| h.title.clone());
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GrepMatch;
+
+    const XML: &str = r#"<TEI><text><body>
+<milestone unit="juan" n="1"/><lb n="0001a01"/><juan fun="open" n="001"><jhead>妙法蓮華經卷第一</jhead></juan>
+<pb n="0001b"/><lb n="0001b01"/>如是我聞
+<juan fun="close" n="001"><jhead>卷第一</jhead></juan>
+<lb n="0001b02"/>音釋
+<milestone unit="juan" n="2"/><juan fun="open" n="002"><jhead>卷第二</jhead></juan>
+<byline cb:type="translator">姚秦 三藏法師 鳩摩羅什 譯</byline>
+<lb n="0010a01"/>爾時世尊
+</body></text></TEI>"#;
+
+    #[test]
+    fn builds_spans_titles_and_anchors() {
+        let m = JuanMap::build(XML);
+        assert_eq!(m.len(), 2);
+        let j1 = m.get("1").unwrap();
+        assert_eq!(j1.title.as_deref(), Some("妙法蓮華經卷第一"));
+        assert_eq!(j1.first_lb.as_deref(), Some("0001a01"));
+        assert_eq!(j1.last_lb.as_deref(), Some("0001b02"));
+        assert_eq!(j1.pages, vec!["0001b"]);
+        assert!(XML[j1.start..].starts_with("<juan fun=\"open\""));
+        assert!(XML[j1.end..].starts_with("<juan fun=\"close\""));
+        assert!(j1.milestone.unwrap() < j1.start);
+        let j2 = m.get("002").unwrap();
+        assert!(XML[j2.end..].starts_with("</body>"));
+        assert_eq!(j2.start_line, 6);
+        assert_eq!(m.juan_at_line(5).unwrap().n, "001");
+        assert_eq!(m.juan_at_line(7).unwrap().n, "002");
+        assert_eq!(m.neighbors("001"), (None, Some("002")));
+        assert!(j1.bylines.is_empty());
+        assert_eq!(
+            j2.bylines,
+            vec![Byline {
+                kind: Some("translator".to_string()),
+                text: "姚秦 三藏法師 鳩摩羅什 譯".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn a_line_gets_its_juan_and_heading_chain() {
+        let xml = r#"<TEI><text><body>
+<milestone unit="juan" n="3"/><juan fun="open" n="3"><jhead>妙法蓮華經卷第三</jhead></juan>
+<div><head>化城喻品第七</head>
+<div><head>偈</head>
+<p>大通智勝佛</p>
+</div>
+<p>爾時佛告諸比丘</p>
+</div>
+<div><cb:mulu level="1">五百弟子受記品第八</cb:mulu>
+<p>爾時富樓那</p></div>
+</body></text></TEI>"#;
+        let m = JuanMap::build(xml);
+        let levels: Vec<usize> = m.heads.iter().map(|h| h.level).collect();
+        assert_eq!(levels, [1, 2, 1]);
+        assert_eq!(
+            m.context_at_line(5).unwrap().label,
+            "卷第三 > 化城喻品第七 > 偈"
+        );
+        assert_eq!(m.context_at_line(11).unwrap().heads, ["五百弟子受記品第八"]);
+        assert!(
+            JuanMap::build("<TEI><text><body><p>x</p></body></text></TEI>")
+                .context_at_line(1)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn matches_get_their_juan_and_nearest_heading() {
+        let xml = r#"<TEI><teiHeader><head>header</head></teiHeader><text><body>
+<juan fun="open" n="1"><jhead>卷第一</jhead></juan>
+<cb:mulu type="品" level="1">序品第一</cb:mulu><head>序品第一</head>
+<lb n="0001a05"/>如是我聞
+<milestone unit="juan" n="2"/><juan fun="open" n="2"><jhead>卷第二</jhead></juan>
+<cb:mulu type="品" level="1">方便品第二</cb:mulu>
+<lb n="0005c01"/>爾時世尊
+</body></text></TEI>"#;
+        let m = JuanMap::build(xml);
+        let titles: Vec<&str> = m.heads.iter().map(|h| h.title.as_str()).collect();
+        assert_eq!(titles, ["序品第一", "方便品第二"]);
+        assert!(m.head_at_line(2).is_none());
+        let ctx = m.context_at_line(7).unwrap();
+        assert_eq!(ctx.label, "卷第二 > 方便品第二");
+        assert_eq!(ctx.juan.as_deref(), Some("002"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("T09n0262.xml");
+        std::fs::write(&path, xml).unwrap();
+        let at = |line: usize| GrepMatch {
+            line_number: Some(line),
+            ..Default::default()
+        };
+        let mut rs = vec![GrepResult {
+            file_path: path.to_string_lossy().into_owned(),
+            matches: vec![at(4), at(7)],
+            ..Default::default()
+        }];
+        attribute_juans(&mut rs);
+        let got: Vec<(Option<&str>, Option<&str>)> = rs[0]
+            .matches
+            .iter()
+            .map(|m| (m.juan_number.as_deref(), m.section.as_deref()))
+            .collect();
+        assert_eq!(
+            got,
+            [
+                (Some("001"), Some("序品第一")),
+                (Some("002"), Some("方便品第二"))
+            ]
+        );
+        assert_eq!(rs[0].fetch_hints.recommended_parts, ["001", "002"]);
+    }
+
+    #[test]
+    fn falls_back_to_milestones_or_whole_body() {
+        let m = JuanMap::build(
+            r#"<body><milestone unit="juan" n="3"/><p>甲</p><milestone unit="juan" n="4"/><p>乙</p></body>"#,
+        );
+        let ns: Vec<&str> = m.juans.iter().map(|j| j.n.as_str()).collect();
+        assert_eq!(ns, vec!["003", "004"]);
+        let whole = JuanMap::build("<TEI><body><p>甲</p></body></TEI>");
+        assert_eq!(whole.len(), 1);
+        assert_eq!(whole.juans[0].n, "001");
+        let front = JuanMap::build(
+            r#"<body><byline>唐 玄奘譯</byline><juan fun="open" n="1"/><p>甲</p></body>"#,
+        );
+        assert_eq!(front.juans[0].bylines[0].text, "唐 玄奘譯");
+    }
+}
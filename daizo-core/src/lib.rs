@@ -1,3 +1,4 @@
+use crate::cancel::CancelToken;
 use quick_xml::events::{BytesStart, Event};
 use quick_xml::Reader;
 use rayon::prelude::*;
@@ -5,23 +6,88 @@ use serde::Serialize;
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, OnceLock};
 use unicode_normalization::UnicodeNormalization;
 
-use encoding_rs;
+use crate::juan_map::JuanMap;
+use crate::matcher::QueryMatcher;
+use crate::timing::Phase;
 use grep_matcher::Matcher;
-use grep_regex::RegexMatcherBuilder;
 use grep_searcher::sinks::UTF8;
 use grep_searcher::{BinaryDetection, SearcherBuilder};
 use ignore::WalkBuilder;
 use serde::Deserialize;
 
+pub mod aliases;
+pub mod analysis;
+pub mod annotations;
+pub mod authority;
+pub mod bundle;
+pub mod cancel;
+pub mod cite;
+pub mod cjk_readings;
+pub mod collate;
+pub mod collections;
+pub mod colophon;
+pub mod concordance;
+pub mod config;
+pub mod dating;
+pub mod dedup;
+pub mod diff;
+pub mod embeddings;
+pub mod entity;
+pub mod epub;
+pub mod export;
+pub mod fetch;
+pub mod fetch_cache;
+pub mod filters;
+pub mod fixtures;
+pub mod fold;
+pub mod grep_cache;
+pub mod health;
+pub mod highlight;
+pub mod index_cache;
+pub mod juan_map;
+pub mod license;
+pub mod match_ids;
+pub mod matcher;
+pub mod metrics;
+pub mod multi;
+pub mod notes;
+pub mod paging;
+pub mod pali_ref;
 pub mod path_resolver;
+pub mod pattern;
+pub mod plain;
+pub mod politeness;
+pub mod prefetch;
+pub mod progress;
+pub mod provenance;
+pub mod query;
+pub mod quotations;
+pub mod reading;
+pub mod relevance;
+pub mod remote;
 pub mod repo;
+pub mod scan;
+pub mod sentence;
+pub mod similar;
+pub mod sizes;
+pub mod snippets;
+pub mod taisho_ref;
+pub mod tei_text;
 pub mod text_utils;
+pub mod timing;
+pub mod title_rank;
+pub mod titles;
+pub mod tokens;
+pub mod variants;
+pub mod warmup;
+pub mod window;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct IndexEntry {
@@ -32,14 +98,14 @@ pub struct IndexEntry {
     pub meta: Option<BTreeMap<String, String>>, // optional metadata (e.g., for Tipitaka)
 }
 
-fn stem_from(path: &Path) -> String {
+pub(crate) fn stem_from(path: &Path) -> String {
     path.file_stem()
         .and_then(|s| s.to_str())
         .unwrap_or("")
         .to_string()
 }
 
-fn local_name<'a>(name: &'a [u8]) -> &'a [u8] {
+fn local_name(name: &[u8]) -> &[u8] {
     match name.rsplit(|b| *b == b':').next() {
         Some(n) => n,
         None => name,
@@ -47,11 +113,9 @@ fn local_name<'a>(name: &'a [u8]) -> &'a [u8] {
 }
 
 fn attr_val<'a>(e: &'a BytesStart<'a>, key: &[u8]) -> Option<Cow<'a, str>> {
-    for a in e.attributes().with_checks(false) {
-        if let Ok(a) = a {
-            if a.key.as_ref() == key {
-                return String::from_utf8(a.value.into_owned()).ok().map(Cow::Owned);
-            }
+    for a in e.attributes().with_checks(false).flatten() {
+        if a.key.as_ref() == key {
+            return String::from_utf8(a.value.into_owned()).ok().map(Cow::Owned);
         }
     }
     None
@@ -86,8 +150,10 @@ fn collect_xml_paths(root: &Path, filter: impl Fn(&Path, &str) -> bool + Sync) -
     out
 }
 
+type XmlPathsCache = OnceLock<Mutex<HashMap<PathBuf, Arc<Vec<PathBuf>>>>>;
+
 fn collect_xml_paths_cached(
-    cache: &'static OnceLock<Mutex<HashMap<PathBuf, Arc<Vec<PathBuf>>>>>,
+    cache: &'static XmlPathsCache,
     root: &Path,
     filter: impl Fn(&Path, &str) -> bool + Sync,
 ) -> Arc<Vec<PathBuf>> {
@@ -103,15 +169,11 @@ fn collect_xml_paths_cached(
     arc
 }
 
-static XML_PATHS_ALL_CACHE: OnceLock<Mutex<HashMap<PathBuf, Arc<Vec<PathBuf>>>>> = OnceLock::new();
-static CBETA_XML_PATHS_EXCLUDE_T_CACHE: OnceLock<Mutex<HashMap<PathBuf, Arc<Vec<PathBuf>>>>> =
-    OnceLock::new();
-static TIPITAKA_XML_PATHS_CACHE: OnceLock<Mutex<HashMap<PathBuf, Arc<Vec<PathBuf>>>>> =
-    OnceLock::new();
-static SARIT_XML_PATHS_CACHE: OnceLock<Mutex<HashMap<PathBuf, Arc<Vec<PathBuf>>>>> =
-    OnceLock::new();
-static MUKTABODHA_PATHS_CACHE: OnceLock<Mutex<HashMap<PathBuf, Arc<Vec<PathBuf>>>>> =
-    OnceLock::new();
+static XML_PATHS_ALL_CACHE: XmlPathsCache = OnceLock::new();
+static CBETA_XML_PATHS_EXCLUDE_T_CACHE: XmlPathsCache = OnceLock::new();
+static TIPITAKA_XML_PATHS_CACHE: XmlPathsCache = OnceLock::new();
+static SARIT_XML_PATHS_CACHE: XmlPathsCache = OnceLock::new();
+static MUKTABODHA_PATHS_CACHE: XmlPathsCache = OnceLock::new();
 
 fn is_sarit_xml(path: &Path, name: &str) -> bool {
     if !name.ends_with(".xml") {
@@ -158,10 +220,12 @@ pub fn build_index(root: &Path, glob_hint: Option<&str>) -> Vec<IndexEntry> {
         }
         true
     });
+    let counter = progress::Counter::new("index", "files", paths.len());
 
     paths
         .par_iter()
         .filter_map(|p| {
+            counter.tick();
             let f = File::open(p).ok()?;
             let mut reader = Reader::from_reader(BufReader::new(f));
             reader.config_mut().trim_text_start = true;
@@ -204,11 +268,9 @@ pub fn build_index(root: &Path, glob_hint: Option<&str>) -> Vec<IndexEntry> {
                             in_head = true;
                             head_buf.clear();
                         }
-                        if name == b"title" {
-                            if path_stack.iter().any(|n| n.as_slice() == b"jhead") {
-                                in_jhead_title = true;
-                                jhead_buf.clear();
-                            }
+                        if name == b"title" && path_stack.iter().any(|n| n.as_slice() == b"jhead") {
+                            in_jhead_title = true;
+                            jhead_buf.clear();
                         }
                     }
                     Ok(Event::End(e)) => {
@@ -289,11 +351,18 @@ pub fn build_index(root: &Path, glob_hint: Option<&str>) -> Vec<IndexEntry> {
 /// SARIT 用: リポジトリ内の TEI P5 テキストをインデックス化（スキーマ/生成物を除外）。
 /// ID はファイル stem を採用（xml:id は揺れがあるため）。
 pub fn build_sarit_index(root: &Path) -> Vec<IndexEntry> {
-    let paths = collect_xml_paths(root, |path, name| is_sarit_xml(path, name));
+    let paths = collect_xml_paths(root, is_sarit_xml);
+    index_sarit_files(&paths)
+}
+
+/// SARIT index entries for `paths`, the per-file part of [`build_sarit_index`].
+fn index_sarit_files(paths: &[PathBuf]) -> Vec<IndexEntry> {
+    let counter = progress::Counter::new("index sarit", "files", paths.len());
 
     paths
         .par_iter()
         .filter_map(|p| {
+            counter.tick();
             let f = File::open(p).ok()?;
             let mut reader = Reader::from_reader(BufReader::new(f));
             reader.config_mut().trim_text_start = true;
@@ -301,13 +370,14 @@ pub fn build_sarit_index(root: &Path) -> Vec<IndexEntry> {
             let mut buf = Vec::new();
 
             let mut xml_id: Option<String> = None;
-            let mut titles: Vec<(bool, String)> = Vec::new(); // (is_main, text)
+            let mut titles: Vec<(bool, String, Option<String>)> = Vec::new(); // (is_main, text, lang)
             let mut author: Option<String> = None;
             let mut editor: Option<String> = None;
 
             let mut path_stack: Vec<Vec<u8>> = Vec::new();
             let mut in_title = false;
             let mut cur_title_is_main = false;
+            let mut cur_title_lang: Option<String> = None;
             let mut title_buf = String::new();
             let mut in_author = false;
             let mut author_buf = String::new();
@@ -341,6 +411,7 @@ pub fn build_sarit_index(root: &Path) -> Vec<IndexEntry> {
                             cur_title_is_main = attr_val(&e, b"type")
                                 .map(|v| v.to_ascii_lowercase().contains("main"))
                                 .unwrap_or(false);
+                            cur_title_lang = attr_val(&e, b"xml:lang").map(|v| v.to_string());
                         }
                         if lname.as_slice() == b"author"
                             && path_stack.iter().any(|n| n.as_slice() == b"titleStmt")
@@ -363,7 +434,7 @@ pub fn build_sarit_index(root: &Path) -> Vec<IndexEntry> {
                         if lname == b"title" && in_title {
                             let t = title_buf.split_whitespace().collect::<Vec<_>>().join(" ");
                             if !t.is_empty() {
-                                titles.push((cur_title_is_main, t));
+                                titles.push((cur_title_is_main, t, cur_title_lang.take()));
                             }
                             in_title = false;
                             cur_title_is_main = false;
@@ -413,9 +484,9 @@ pub fn build_sarit_index(root: &Path) -> Vec<IndexEntry> {
             let id = stem_from(p);
             let title = titles
                 .iter()
-                .find(|(is_main, _)| *is_main)
+                .find(|(is_main, _, _)| *is_main)
                 .or_else(|| titles.first())
-                .map(|(_, s)| s.trim().to_string())
+                .map(|(_, s, _)| s.trim().to_string())
                 .filter(|s| !s.is_empty())
                 .unwrap_or_else(|| id.clone());
             let abs = std::fs::canonicalize(p).unwrap_or_else(|_| p.clone());
@@ -430,7 +501,16 @@ pub fn build_sarit_index(root: &Path) -> Vec<IndexEntry> {
             if let Some(ed) = editor {
                 meta.insert("editor".to_string(), ed);
             }
-            meta.insert("indexVersion".to_string(), "sarit_index_v1".to_string());
+            let text_chars = std::fs::read_to_string(p)
+                .map(|x| extract_text(&x).chars().count())
+                .unwrap_or(0);
+            sizes::record(&mut meta, p, text_chars, &[]);
+            meta.insert("indexVersion".to_string(), "sarit_index_v3".to_string());
+            let english = titles
+                .iter()
+                .find(|(_, _, l)| l.as_deref().is_some_and(|l| l.starts_with("en")))
+                .map(|(_, s, _)| s.clone());
+            titles::record(&mut meta, &title, english, None);
 
             Some(IndexEntry {
                 id,
@@ -446,11 +526,13 @@ pub fn build_sarit_index(root: &Path) -> Vec<IndexEntry> {
 /// - XML: teiHeader/titleStmt/title を優先的に拾う（無ければ stem）
 /// - TXT: タイトルは stem
 pub fn build_muktabodha_index(root: &Path) -> Vec<IndexEntry> {
-    let paths = collect_xml_paths(root, |path, name| is_muktabodha_file(path, name));
+    let paths = collect_xml_paths(root, is_muktabodha_file);
+    let counter = progress::Counter::new("index muktabodha", "files", paths.len());
 
     paths
         .par_iter()
         .filter_map(|p| {
+            counter.tick();
             let ext = p.extension().and_then(|s| s.to_str()).unwrap_or("");
             let id = stem_from(p);
             let abs = std::fs::canonicalize(p).unwrap_or_else(|_| p.clone());
@@ -502,13 +584,11 @@ pub fn build_muktabodha_index(root: &Path) -> Vec<IndexEntry> {
                             }
                             path_stack.pop();
                         }
-                        Ok(Event::Text(t)) => {
-                            if in_title && title.is_none() {
-                                let t = t.decode().unwrap_or_default().into_owned();
-                                let tt = t.split_whitespace().collect::<Vec<_>>().join(" ");
-                                if !tt.trim().is_empty() {
-                                    title = Some(tt);
-                                }
+                        Ok(Event::Text(t)) if in_title && title.is_none() => {
+                            let t = t.decode().unwrap_or_default().into_owned();
+                            let tt = t.split_whitespace().collect::<Vec<_>>().join(" ");
+                            if !tt.trim().is_empty() {
+                                title = Some(tt);
                             }
                         }
                         Ok(Event::Eof) => break,
@@ -521,9 +601,13 @@ pub fn build_muktabodha_index(root: &Path) -> Vec<IndexEntry> {
 
                 let title = title.unwrap_or_else(|| id.clone());
                 let mut meta: BTreeMap<String, String> = BTreeMap::new();
+                let text_chars = std::fs::read_to_string(p)
+                    .map(|x| extract_text(&x).chars().count())
+                    .unwrap_or(0);
+                sizes::record(&mut meta, p, text_chars, &[]);
                 meta.insert(
                     "indexVersion".to_string(),
-                    "muktabodha_index_v1".to_string(),
+                    "muktabodha_index_v2".to_string(),
                 );
                 meta.insert("ext".to_string(), "xml".to_string());
 
@@ -535,9 +619,13 @@ pub fn build_muktabodha_index(root: &Path) -> Vec<IndexEntry> {
                 })
             } else {
                 let mut meta: BTreeMap<String, String> = BTreeMap::new();
+                let text_chars = std::fs::read_to_string(p)
+                    .map(|x| x.chars().count())
+                    .unwrap_or(0);
+                sizes::record(&mut meta, p, text_chars, &[]);
                 meta.insert(
                     "indexVersion".to_string(),
-                    "muktabodha_index_v1".to_string(),
+                    "muktabodha_index_v2".to_string(),
                 );
                 meta.insert("ext".to_string(), "txt".to_string());
                 Some(IndexEntry {
@@ -554,10 +642,12 @@ pub fn build_muktabodha_index(root: &Path) -> Vec<IndexEntry> {
 // GRETIL 用: TEI ヘッダ（titleStmt/author/editor/respStmt/publisher/date）と本文<head>からメタ情報を抽出
 pub fn build_gretil_index(root: &Path) -> Vec<IndexEntry> {
     let paths = collect_xml_paths(root, |_, name| name.ends_with(".xml"));
+    let counter = progress::Counter::new("index gretil", "files", paths.len());
 
-    paths
+    let (mut entries, sigs): (Vec<IndexEntry>, Vec<dedup::ContentSignature>) = paths
         .par_iter()
         .filter_map(|p| {
+            counter.tick();
             let f = File::open(p).ok()?;
             let mut reader = Reader::from_reader(BufReader::new(f));
             reader.config_mut().trim_text_start = true;
@@ -574,6 +664,10 @@ pub fn build_gretil_index(root: &Path) -> Vec<IndexEntry> {
             let mut date: Option<String> = None;
             let mut idno: Option<String> = None;
             let mut heads: Vec<String> = Vec::new();
+            let mut language: Option<String> = None;
+            let mut root_lang: Option<String> = None;
+            let mut creation_date: Option<String> = None;
+            let mut source = String::new();
 
             let mut path_stack: Vec<Vec<u8>> = Vec::new();
             let mut in_title_header = false;
@@ -599,29 +693,53 @@ pub fn build_gretil_index(root: &Path) -> Vec<IndexEntry> {
 
             loop {
                 match reader.read_event_into(&mut buf) {
-                    Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                    Ok(ev @ (Event::Start(_) | Event::Empty(_))) => {
+                        let (Event::Start(e) | Event::Empty(e)) = &ev else {
+                            unreachable!()
+                        };
                         let name_owned = e.name().as_ref().to_owned();
                         let lname = local_name(&name_owned).to_vec();
                         if id.is_none() {
-                            if let Some(v) = attr_val(&e, b"xml:id") {
+                            if let Some(v) = attr_val(e, b"xml:id") {
                                 id = Some(v.to_string());
                             }
                         }
+                        if matches!(lname.as_slice(), b"TEI" | b"text") && root_lang.is_none() {
+                            root_lang = attr_val(e, b"xml:lang").map(|v| v.to_string());
+                        }
+                        // langUsage/language: the ident code, else the element text below
+                        if lname.as_slice() == b"language" && language.is_none() {
+                            language = attr_val(e, b"ident")
+                                .map(|v| v.trim().to_string())
+                                .filter(|v| !v.is_empty());
+                        }
+                        // profileDesc/creation/date: when the work was composed
+                        if lname.as_slice() == b"date"
+                            && creation_date.is_none()
+                            && path_stack.iter().any(|n| n.as_slice() == b"creation")
+                        {
+                            let span: Vec<String> = [b"when".as_slice(), b"notBefore", b"notAfter"]
+                                .iter()
+                                .filter_map(|k| attr_val(e, k).map(|v| v.to_string()))
+                                .collect();
+                            if !span.is_empty() {
+                                creation_date = Some(span.join("-"));
+                            }
+                        }
                         path_stack.push(lname.clone());
+                        // An empty element has no End event to pop it.
+                        let empty = matches!(ev, Event::Empty(_));
 
                         // titleStmt/title
-                        if lname.as_slice() == b"title" {
-                            if path_stack.iter().any(|n| n.as_slice() == b"titleStmt")
-                                && path_stack.iter().any(|n| n.as_slice() == b"teiHeader")
-                            {
-                                in_title_header = true;
-                                // prefer type="main"
-                                if let Some(t) =
-                                    attr_val(&e, b"type").map(|v| v.to_ascii_lowercase())
-                                {
-                                    if t.contains("main") {
-                                        in_title_main = true;
-                                    }
+                        if lname.as_slice() == b"title"
+                            && path_stack.iter().any(|n| n.as_slice() == b"titleStmt")
+                            && path_stack.iter().any(|n| n.as_slice() == b"teiHeader")
+                        {
+                            in_title_header = true;
+                            // prefer type="main"
+                            if let Some(t) = attr_val(e, b"type").map(|v| v.to_ascii_lowercase()) {
+                                if t.contains("main") {
+                                    in_title_main = true;
                                 }
                             }
                         }
@@ -662,6 +780,9 @@ pub fn build_gretil_index(root: &Path) -> Vec<IndexEntry> {
                         {
                             in_resp_name = true;
                         }
+                        if empty {
+                            path_stack.pop();
+                        }
                     }
                     Ok(Event::End(e)) => {
                         let name_owned = e.name().as_ref().to_owned();
@@ -703,14 +824,14 @@ pub fn build_gretil_index(root: &Path) -> Vec<IndexEntry> {
                             // 役割ベースで翻訳者を推測
                             let role = cur_resp_role.to_lowercase();
                             let names = cur_resp_names.join("・");
-                            if translator.is_none() && !names.is_empty() {
-                                if role.contains("transl")
+                            if translator.is_none()
+                                && !names.is_empty()
+                                && (role.contains("transl")
                                     || role.contains("translator")
                                     || role.contains("translation")
-                                    || role.contains("trl")
-                                {
-                                    translator = Some(names.clone());
-                                }
+                                    || role.contains("trl"))
+                            {
+                                translator = Some(names.clone());
                             }
                             // respAll 収集
                             if !names.is_empty() {
@@ -750,6 +871,23 @@ pub fn build_gretil_index(root: &Path) -> Vec<IndexEntry> {
                         if in_idno && idno.is_none() && !s.is_empty() {
                             idno = Some(s.to_string());
                         }
+                        let within = |n: &[u8]| path_stack.iter().any(|x| x.as_slice() == n);
+                        if !s.is_empty() {
+                            if language.is_none()
+                                && path_stack.last().is_some_and(|n| n == b"language")
+                            {
+                                language = Some(s.to_string());
+                            }
+                            if creation_date.is_none() && within(b"creation") {
+                                creation_date = Some(s.to_string());
+                            }
+                            if within(b"sourceDesc") && source.len() < 400 {
+                                if !source.is_empty() && s.starts_with(char::is_alphanumeric) {
+                                    source.push(' ');
+                                }
+                                source.push_str(s);
+                            }
+                        }
                         if in_head {
                             head_buf.push_str(&tx);
                         }
@@ -802,6 +940,19 @@ pub fn build_gretil_index(root: &Path) -> Vec<IndexEntry> {
             if let Some(v) = idno {
                 meta_map.insert("idno".to_string(), v);
             }
+            if let Some(v) = creation_date {
+                meta_map.insert("creationDate".to_string(), v);
+            }
+            let source = source.split_whitespace().collect::<Vec<_>>().join(" ");
+            if !source.is_empty() {
+                meta_map.insert(
+                    "source".to_string(),
+                    source.chars().take(200).collect::<String>(),
+                );
+            }
+            if let Some(v) = language.or(root_lang).or_else(|| gretil_folder_language(p)) {
+                meta_map.insert("language".to_string(), v);
+            }
             if !heads.is_empty() {
                 meta_map.insert(
                     "headsPreview".to_string(),
@@ -827,11 +978,26 @@ pub fn build_gretil_index(root: &Path) -> Vec<IndexEntry> {
             let mut in_classcode = false;
             let mut class_codes: Vec<String> = Vec::new();
             let mut cat_refs: Vec<String> = Vec::new();
+            // body text and structure, for duplicate detection
+            let mut header_depth = 0usize;
+            let mut body_text = String::new();
+            let mut elements = 0usize;
             loop {
                 match reader2.read_event_into(&mut buf2) {
-                    Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                    Ok(ev @ (Event::Start(_) | Event::Empty(_))) => {
+                        let (Event::Start(e) | Event::Empty(e)) = &ev else {
+                            unreachable!()
+                        };
                         let name_owned = e.name().as_ref().to_owned();
                         let lname = local_name(&name_owned);
+                        if lname == b"teiHeader" || header_depth > 0 {
+                            if matches!(ev, Event::Start(_)) {
+                                header_depth += 1;
+                            }
+                        } else if matches!(lname, b"div" | b"head" | b"lg" | b"l" | b"p" | b"note")
+                        {
+                            elements += 1;
+                        }
                         if lname == b"keywords" {
                             in_keywords = true;
                         }
@@ -842,7 +1008,7 @@ pub fn build_gretil_index(root: &Path) -> Vec<IndexEntry> {
                             in_classcode = true;
                         }
                         if lname == b"catRef" {
-                            if let Some(t) = attr_val(&e, b"target") {
+                            if let Some(t) = attr_val(e, b"target") {
                                 let v = t.trim().to_string();
                                 if !v.is_empty() {
                                     cat_refs.push(v);
@@ -853,6 +1019,7 @@ pub fn build_gretil_index(root: &Path) -> Vec<IndexEntry> {
                     Ok(Event::End(e)) => {
                         let name_owned = e.name().as_ref().to_owned();
                         let lname = local_name(&name_owned);
+                        header_depth = header_depth.saturating_sub(1);
                         if lname == b"keywords" {
                             in_keywords = false;
                         }
@@ -865,6 +1032,10 @@ pub fn build_gretil_index(root: &Path) -> Vec<IndexEntry> {
                     }
                     Ok(Event::Text(t)) => {
                         let s = t.decode().unwrap_or_default();
+                        if header_depth == 0 {
+                            body_text.push_str(&s);
+                            body_text.push(' ');
+                        }
                         if in_term {
                             let v = s.trim();
                             if !v.is_empty() {
@@ -896,19 +1067,49 @@ pub fn build_gretil_index(root: &Path) -> Vec<IndexEntry> {
             if !resp_entries.is_empty() {
                 meta_map.insert("respAll".to_string(), resp_entries.join(" | "));
             }
+            // genre: the header's classification, else its first keyword
+            let genre = cat_refs
+                .first()
+                .map(|t| t.trim_start_matches('#').to_string())
+                .or_else(|| class_codes.first().cloned())
+                .or_else(|| terms.first().cloned());
+            if let Some(v) = genre {
+                meta_map.insert("genre".to_string(), v);
+            }
 
-            Some(IndexEntry {
+            let text_chars = std::fs::read_to_string(p)
+                .map(|x| extract_text(&x).chars().count())
+                .unwrap_or(0);
+            sizes::record(&mut meta_map, p, text_chars, &[]);
+            meta_map.insert("indexVersion".to_string(), "gretil_index_v4".to_string());
+
+            let entry = IndexEntry {
                 id,
                 title,
                 path: abs.to_string_lossy().to_string(),
-                meta: if meta_map.is_empty() {
-                    None
-                } else {
-                    Some(meta_map)
-                },
-            })
+                meta: Some(meta_map),
+            };
+            Some((entry, dedup::content_signature(&body_text, elements)))
+        })
+        .unzip();
+    dedup::annotate_duplicates(&mut entries, &sigs);
+    entries
+}
+
+/// Language code implied by a GRETIL language folder above the file (`1_sanskr` → `sa`).
+fn gretil_folder_language(p: &Path) -> Option<String> {
+    p.ancestors()
+        .filter_map(|a| a.file_name()?.to_str()?.split_once('_'))
+        .find_map(|(n, name)| {
+            if n.is_empty() || !n.chars().all(|ch| ch.is_ascii_digit()) {
+                return None;
+            }
+            match name {
+                "sanskr" => Some("sa".to_string()),
+                "pali" => Some("pi".to_string()),
+                _ => None,
+            }
         })
-        .collect()
 }
 
 #[derive(Clone, Debug)]
@@ -975,13 +1176,51 @@ fn pick_best_cbeta_header_title(cands: &[HeaderTitleCandidate]) -> Option<String
     best.map(|(c, _)| c.text.clone())
 }
 
+/// English and romanized titles among the header candidates. CBETA's Latin-script titles
+/// name the collection ("Taishō Tripiṭaka, Electronic version, No. 251 Bore boluomiduo xin
+/// jing"); the words after the number are the romanized title.
+fn cbeta_alternate_titles(cands: &[HeaderTitleCandidate]) -> (Option<String>, Option<String>) {
+    let mut english = None;
+    let mut romanized = None;
+    for c in cands {
+        if titles::script_of(&c.text) != "Latn" {
+            continue;
+        }
+        let lang = c.lang.as_deref().unwrap_or("").to_lowercase();
+        if lang.starts_with("zh") || lang.starts_with("ja") {
+            continue;
+        }
+        if cbeta_title_contains_collection_keywords(&c.text) {
+            let tail = c.text.split_once("No. ").map(|(_, rest)| {
+                rest.trim_start_matches(|ch: char| ch.is_ascii_alphanumeric())
+                    .trim()
+            });
+            if let Some(t) = tail.filter(|t| !t.is_empty()) {
+                romanized.get_or_insert_with(|| t.to_string());
+            }
+        } else if lang.starts_with("en") {
+            english.get_or_insert_with(|| c.text.clone());
+        } else if !lang.is_empty() {
+            romanized.get_or_insert_with(|| c.text.clone());
+        }
+    }
+    (english, romanized)
+}
+
 // CBETA 用: TEI ヘッダや本文の構造からメタ情報を抽出してインデックスを高精度化
 pub fn build_cbeta_index(root: &Path) -> Vec<IndexEntry> {
     let paths = collect_xml_paths(root, |_, name| name.ends_with(".xml"));
+    index_cbeta_files(root, &paths)
+}
 
-    paths
+/// CBETA index entries for `paths` under `root`, the per-file part of [`build_cbeta_index`].
+fn index_cbeta_files(root: &Path, paths: &[PathBuf]) -> Vec<IndexEntry> {
+    let counter = progress::Counter::new("index cbeta", "files", paths.len());
+
+    let mut entries: Vec<IndexEntry> = paths
         .par_iter()
         .filter_map(|p| {
+            counter.tick();
             let f = File::open(p).ok()?;
             let mut reader = Reader::from_reader(BufReader::new(f));
             reader.config_mut().trim_text_start = true;
@@ -1016,13 +1255,21 @@ pub fn build_cbeta_index(root: &Path) -> Vec<IndexEntry> {
             let mut in_idno = false;
             let mut in_head = false;
             let mut head_buf = String::new();
+            let mut mulu: Vec<String> = Vec::new();
+            let mut mulu_count: usize = 0;
+            let mut in_mulu = false;
+            let mut mulu_buf = String::new();
 
             let mut events = 0usize;
             let max_events = 50_000usize;
 
             loop {
                 match reader.read_event_into(&mut buf) {
-                    Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                    Ok(ev @ (Event::Start(_) | Event::Empty(_))) => {
+                        let is_start = matches!(ev, Event::Start(_));
+                        let (Event::Start(e) | Event::Empty(e)) = ev else {
+                            unreachable!()
+                        };
                         let name_owned = e.name().as_ref().to_owned();
                         let lname = local_name(&name_owned).to_vec();
                         if id.is_none() {
@@ -1032,14 +1279,13 @@ pub fn build_cbeta_index(root: &Path) -> Vec<IndexEntry> {
                         }
                         path_stack.push(lname.clone());
 
-                        if lname.as_slice() == b"title" {
-                            if path_stack.iter().any(|n| n.as_slice() == b"titleStmt")
-                                && path_stack.iter().any(|n| n.as_slice() == b"teiHeader")
-                            {
-                                in_title_header = true;
-                                title_buf.clear();
-                                title_lang = attr_val(&e, b"xml:lang").map(|v| v.to_string());
-                            }
+                        if lname.as_slice() == b"title"
+                            && path_stack.iter().any(|n| n.as_slice() == b"titleStmt")
+                            && path_stack.iter().any(|n| n.as_slice() == b"teiHeader")
+                        {
+                            in_title_header = true;
+                            title_buf.clear();
+                            title_lang = attr_val(&e, b"xml:lang").map(|v| v.to_string());
                         }
                         if lname.as_slice() == b"author"
                             && path_stack.iter().any(|n| n.as_slice() == b"titleStmt")
@@ -1083,6 +1329,10 @@ pub fn build_cbeta_index(root: &Path) -> Vec<IndexEntry> {
                                 juan_count += 1;
                             }
                         }
+                        if lname.as_slice() == b"mulu" && is_start {
+                            in_mulu = true;
+                            mulu_buf.clear();
+                        }
                     }
                     Ok(Event::End(e)) => {
                         let name_owned = e.name().as_ref().to_owned();
@@ -1143,6 +1393,17 @@ pub fn build_cbeta_index(root: &Path) -> Vec<IndexEntry> {
                             in_head = false;
                             head_buf.clear();
                         }
+                        if lname == b"mulu" && in_mulu {
+                            let t = mulu_buf.split_whitespace().collect::<Vec<_>>().join(" ");
+                            if !t.is_empty() {
+                                mulu_count += 1;
+                                if mulu.len() < 10 {
+                                    mulu.push(t);
+                                }
+                            }
+                            in_mulu = false;
+                            mulu_buf.clear();
+                        }
                         path_stack.pop();
                     }
                     Ok(Event::Text(t)) => {
@@ -1175,6 +1436,9 @@ pub fn build_cbeta_index(root: &Path) -> Vec<IndexEntry> {
                         if in_head {
                             head_buf.push_str(&s);
                         }
+                        if in_mulu {
+                            mulu_buf.push_str(&s);
+                        }
                     }
                     Ok(Event::Eof) => break,
                     Err(_) => break,
@@ -1189,7 +1453,7 @@ pub fn build_cbeta_index(root: &Path) -> Vec<IndexEntry> {
 
             let id = id.unwrap_or_else(|| stem_from(p));
             let title = pick_best_cbeta_header_title(&titles)
-                .or_else(|| heads.get(0).cloned())
+                .or_else(|| heads.first().cloned())
                 .unwrap_or_else(|| stem_from(p));
             let abs = std::fs::canonicalize(p).unwrap_or_else(|_| p.clone());
 
@@ -1217,7 +1481,18 @@ pub fn build_cbeta_index(root: &Path) -> Vec<IndexEntry> {
             }
 
             let mut meta = BTreeMap::new();
-            meta.insert("indexVersion".to_string(), "cbeta_index_v2".to_string());
+            meta.insert("indexVersion".to_string(), "cbeta_index_v7".to_string());
+            let (english, romanized) = cbeta_alternate_titles(&titles);
+            titles::record(&mut meta, &title, english, romanized);
+            if let Ok(xml) = std::fs::read_to_string(p) {
+                let parts = sizes::cbeta_juan_chars(&xml);
+                let text_chars = if parts.is_empty() {
+                    extract_cbeta_plain_from_xml(&xml, false).chars().count()
+                } else {
+                    parts.iter().map(|j| j.chars).sum()
+                };
+                sizes::record(&mut meta, p, text_chars, &parts);
+            }
             if !canon.is_empty() {
                 meta.insert("canon".to_string(), canon);
             }
@@ -1279,6 +1554,11 @@ pub fn build_cbeta_index(root: &Path) -> Vec<IndexEntry> {
                 );
             }
 
+            if mulu_count > 0 {
+                meta.insert("muluCount".to_string(), mulu_count.to_string());
+                meta.insert("muluPreview".to_string(), mulu.join(" | "));
+            }
+
             Some(IndexEntry {
                 id,
                 title,
@@ -1286,24 +1566,40 @@ pub fn build_cbeta_index(root: &Path) -> Vec<IndexEntry> {
                 meta: if meta.is_empty() { None } else { Some(meta) },
             })
         })
-        .collect()
+        .collect();
+    aliases::merge_work_aliases("cbeta", &mut entries);
+    for e in entries.iter_mut() {
+        cjk_readings::record(e.meta.get_or_insert_with(BTreeMap::new), &e.title);
+    }
+    entries
 }
 
 // Tipitaka 用: teiHeader が空な場合が多いため、<p rend="..."> 系から書誌情報を抽出してタイトルを構築
 pub fn build_tipitaka_index(root: &Path) -> Vec<IndexEntry> {
     // 走査: root 配下の .xml で .toc.xml は除外 (rootは既にromnディレクトリを指している)
     let paths = collect_xml_paths_cached(&TIPITAKA_XML_PATHS_CACHE, root, |_, name| {
-        name.ends_with(".xml")
-            && !name.contains("toc")
-            && !name.contains("sitemap")
-            && !name.contains("tree")
-            && !name.ends_with(".xsl")
-            && !name.ends_with(".css")
+        is_tipitaka_index_file(name)
     });
+    index_tipitaka_files(&paths)
+}
 
-    paths
+fn is_tipitaka_index_file(name: &str) -> bool {
+    name.ends_with(".xml")
+        && !name.contains("toc")
+        && !name.contains("sitemap")
+        && !name.contains("tree")
+        && !name.ends_with(".xsl")
+        && !name.ends_with(".css")
+}
+
+/// Tipitaka index entries for `paths`, the per-file part of [`build_tipitaka_index`].
+fn index_tipitaka_files(paths: &[PathBuf]) -> Vec<IndexEntry> {
+    let counter = progress::Counter::new("index tipitaka", "files", paths.len());
+
+    let mut entries: Vec<IndexEntry> = paths
         .par_iter()
         .filter_map(|p| {
+            counter.tick();
             // UTF-16 TipitakaファイルをUTF-8で読み込み
             let content = match std::fs::read(p) {
                 Ok(bytes) => {
@@ -1393,7 +1689,7 @@ pub fn build_tipitaka_index(root: &Path) -> Vec<IndexEntry> {
                                 let key = rend.trim().to_string();
                                 let val = current_buf.trim().to_string();
                                 if !val.is_empty() && wanted_p.contains(&key.as_str()) {
-                                    let values = fields.entry(key).or_insert_with(Vec::new);
+                                    let values = fields.entry(key).or_default();
                                     // 重複チェック: 同じ文字列が既に存在しない場合のみ追加
                                     if values.len() < MAX_VALUES_PER_KEY && !values.contains(&val) {
                                         values.push(val);
@@ -1410,7 +1706,7 @@ pub fn build_tipitaka_index(root: &Path) -> Vec<IndexEntry> {
                                 let val = head_buf.trim().to_string();
                                 if !val.is_empty() {
                                     if wanted_head.contains(&key.as_str()) {
-                                        let values = fields.entry(key).or_insert_with(Vec::new);
+                                        let values = fields.entry(key).or_default();
                                         // 重複チェック: 同じ文字列が既に存在しない場合のみ追加
                                         if values.len() < MAX_VALUES_PER_KEY
                                             && !values.contains(&val)
@@ -1496,7 +1792,13 @@ pub fn build_tipitaka_index(root: &Path) -> Vec<IndexEntry> {
             }
 
             // index versioning (invalidate old heavy caches)
-            meta_map.insert("indexVersion".to_string(), "tipitaka_index_v2".to_string());
+            meta_map.insert("indexVersion".to_string(), "tipitaka_index_v4".to_string());
+            sizes::record(
+                &mut meta_map,
+                p,
+                extract_text(&content).chars().count(),
+                &[],
+            );
 
             // headsPreview は常にキーを持たせ、MCP側のキャッシュ妥当性チェックを安定させる
             meta_map.insert(
@@ -1650,7 +1952,56 @@ pub fn build_tipitaka_index(root: &Path) -> Vec<IndexEntry> {
                 meta,
             })
         })
-        .collect()
+        .collect();
+    aliases::merge_work_aliases("tipitaka", &mut entries);
+    entries
+}
+
+/// Bring an index of `corpus` (`cbeta`, `tipitaka` or `sarit`) up to date after the files in
+/// `changed` were added, modified or deleted under `root`: their entries are dropped and the
+/// files that still exist are indexed again; the rest of the index is kept as it was.
+/// Returns (entries removed, entries added); None for a corpus without a per-file index.
+pub fn update_index(
+    corpus: &str,
+    root: &Path,
+    entries: &mut Vec<IndexEntry>,
+    changed: &[PathBuf],
+) -> Option<(usize, usize)> {
+    // Entries hold canonical paths; deleted files can no longer be canonicalized.
+    let canon_root = std::fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let mut stale: HashSet<String> = HashSet::new();
+    for p in changed {
+        stale.insert(p.to_string_lossy().to_string());
+        if let Ok(rel) = p.strip_prefix(root) {
+            stale.insert(canon_root.join(rel).to_string_lossy().to_string());
+        }
+    }
+    let present: Vec<PathBuf> = changed
+        .iter()
+        .filter(|p| p.is_file())
+        .filter(|p| {
+            let name = p.file_name().and_then(|s| s.to_str()).unwrap_or("");
+            match corpus {
+                "cbeta" => name.ends_with(".xml"),
+                "tipitaka" => is_tipitaka_index_file(name),
+                _ => is_sarit_xml(p, name),
+            }
+        })
+        .cloned()
+        .collect();
+    let fresh = match corpus {
+        "cbeta" => index_cbeta_files(root, &present),
+        "tipitaka" => index_tipitaka_files(&present),
+        "sarit" => index_sarit_files(&present),
+        _ => return None,
+    };
+    let before = entries.len();
+    entries.retain(|e| !stale.contains(&e.path));
+    let removed = before - entries.len();
+    let added = fresh.len();
+    entries.extend(fresh);
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Some((removed, added))
 }
 
 fn fold_ascii(s: &str) -> String {
@@ -1842,15 +2193,13 @@ fn parse_gaiji_map(xml: &str) -> HashMap<String, String> {
                     if !text.trim().is_empty() {
                         current_val = Some(text);
                     }
-                } else if in_char && in_mapping && current_mapping_type.as_deref() == Some("normal")
+                } else if in_char
+                    && ((in_mapping && current_mapping_type.as_deref() == Some("normal"))
+                        || in_charname)
+                    && current_val.is_none()
+                    && !text.trim().is_empty()
                 {
-                    if current_val.is_none() && !text.trim().is_empty() {
-                        current_val = Some(text);
-                    }
-                } else if in_char && in_charname {
-                    if current_val.is_none() && !text.trim().is_empty() {
-                        current_val = Some(text);
-                    }
+                    current_val = Some(text);
                 }
             }
             Ok(Event::Eof) => break,
@@ -1941,15 +2290,13 @@ pub fn cbeta_gaiji_map_fast(xml: &str) -> HashMap<String, String> {
                     if !text.trim().is_empty() {
                         current_val = Some(text);
                     }
-                } else if in_char && in_mapping && current_mapping_type.as_deref() == Some("normal")
+                } else if in_char
+                    && ((in_mapping && current_mapping_type.as_deref() == Some("normal"))
+                        || in_charname)
+                    && current_val.is_none()
+                    && !text.trim().is_empty()
                 {
-                    if current_val.is_none() && !text.trim().is_empty() {
-                        current_val = Some(text);
-                    }
-                } else if in_char && in_charname {
-                    if current_val.is_none() && !text.trim().is_empty() {
-                        current_val = Some(text);
-                    }
+                    current_val = Some(text);
                 }
             }
             Ok(Event::Eof) => break,
@@ -2016,6 +2363,8 @@ fn extract_cbeta_plain_impl(
     let mut reader = Reader::from_str(xml);
     reader.config_mut().trim_text_start = true;
     reader.config_mut().trim_text_end = true;
+    // Snippets (sections, juans) may close elements opened outside the slice.
+    reader.config_mut().check_end_names = false;
     let mut buf = Vec::new();
     let mut out = String::new();
 
@@ -2191,6 +2540,7 @@ fn extract_cbeta_plain_impl(
 /// - excludes `<teiHeader>`
 /// - preserves line breaks (`lb` -> `\n`, `pb` -> blank line)
 pub fn extract_cbeta_plain_from_xml(xml: &str, include_notes: bool) -> String {
+    let _t = Phase::Extract.start();
     let gaiji = cbeta_gaiji_map_fast(xml);
     extract_cbeta_plain_impl(xml, Some(&gaiji), include_notes, true)
 }
@@ -2202,14 +2552,18 @@ pub fn extract_cbeta_plain_from_snippet(
     gaiji: &HashMap<String, String>,
     include_notes: bool,
 ) -> String {
+    let _t = Phase::Extract.start();
     extract_cbeta_plain_impl(snippet_xml, Some(gaiji), include_notes, true)
 }
 
 pub fn extract_text_opts(xml: &str, include_notes: bool) -> String {
+    let _t = Phase::Extract.start();
     let gaiji = parse_gaiji_map(xml);
     let mut reader = Reader::from_str(xml);
     reader.config_mut().trim_text_start = true;
     reader.config_mut().trim_text_end = true;
+    // Fragments (sections, pages of units) may close elements opened outside them.
+    reader.config_mut().check_end_names = false;
     let mut buf = Vec::new();
     let mut out = String::new();
     let mut skip_depth: usize = 0; // for excluding notes
@@ -2238,13 +2592,11 @@ pub fn extract_text_opts(xml: &str, include_notes: bool) -> String {
                         out.push('\n');
                         out.push('\n');
                     }
-                } else if name == b"g" {
-                    if skip_depth == 0 {
-                        if let Some(r) = attr_val(&e, b"ref") {
-                            let key = r.trim_start_matches('#').to_string();
-                            if let Some(v) = gaiji.get(&key) {
-                                out.push_str(v);
-                            }
+                } else if name == b"g" && skip_depth == 0 {
+                    if let Some(r) = attr_val(&e, b"ref") {
+                        let key = r.trim_start_matches('#').to_string();
+                        if let Some(v) = gaiji.get(&key) {
+                            out.push_str(v);
                         }
                     }
                 }
@@ -2281,11 +2633,7 @@ pub fn extract_text_opts(xml: &str, include_notes: bool) -> String {
                 let name_owned = e.name().as_ref().to_owned();
                 let name = local_name(&name_owned);
                 if name == b"note" {
-                    if skip_depth > 0 {
-                        if skip_depth > 0 {
-                            skip_depth -= 1;
-                        }
-                    }
+                    skip_depth = skip_depth.saturating_sub(1);
                     if collect_note {
                         note_depth = note_depth.saturating_sub(1);
                         if note_depth == 0 {
@@ -2300,11 +2648,9 @@ pub fn extract_text_opts(xml: &str, include_notes: bool) -> String {
                         }
                     }
                 } else if collect_note {
-                    if note_depth > 0 {
-                        note_depth -= 1;
-                    }
-                } else if skip_depth > 0 {
-                    skip_depth -= 1;
+                    note_depth = note_depth.saturating_sub(1);
+                } else {
+                    skip_depth = skip_depth.saturating_sub(1);
                 }
             }
             Ok(Event::Text(t)) => {
@@ -2333,60 +2679,45 @@ pub fn extract_text_opts(xml: &str, include_notes: bool) -> String {
 }
 
 pub fn extract_cbeta_juan(xml: &str, part: &str) -> Option<String> {
+    extract_cbeta_juan_with_map(xml, &JuanMap::build(xml), part)
+}
+
+/// Like [`extract_cbeta_juan`], with a juan map built (or cached) by the caller.
+pub fn extract_cbeta_juan_with_map(xml: &str, map: &JuanMap, part: &str) -> Option<String> {
+    let _t = Phase::Extract.start();
+    let span = map.get(part)?;
     let gaiji = parse_gaiji_map(xml);
-    let target_n1 = part.to_string();
-    let target_n2 = format!("{:0>3}", part);
-    let mut reader = Reader::from_str(xml);
+    let mut reader = Reader::from_str(&xml[span.start..span.end]);
     reader.config_mut().trim_text_start = true;
     reader.config_mut().trim_text_end = true;
+    // The slice cuts through enclosing elements, so their end tags are unmatched.
+    reader.config_mut().check_end_names = false;
     let mut buf = Vec::new();
-    let mut capturing = false;
     let mut out = String::new();
     loop {
         match reader.read_event_into(&mut buf) {
             Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
                 let name_owned = e.name().as_ref().to_owned();
                 let name = local_name(&name_owned);
-                if name == b"juan" {
-                    let fun = attr_val(&e, b"fun").map(|v| v.to_ascii_lowercase());
-                    let n = attr_val(&e, b"n").unwrap_or(Cow::Borrowed(""));
-                    if !capturing {
-                        if (n == target_n1 || n == target_n2)
-                            && (fun.as_deref() == Some("open") || fun.is_none())
-                        {
-                            capturing = true;
-                        }
-                    } else {
-                        if fun.as_deref() == Some("close") {
-                            break;
-                        }
-                    }
-                } else if capturing {
-                    if name == b"lb" {
-                        out.push('\n');
-                    } else if name == b"pb" {
-                        out.push('\n');
-                        out.push('\n');
-                    } else if name == b"g" {
-                        if let Some(r) = attr_val(&e, b"ref") {
-                            let key = r.trim_start_matches('#').to_string();
-                            if let Some(v) = gaiji.get(&key) {
-                                out.push_str(v);
-                            }
+                if name == b"lb" {
+                    out.push('\n');
+                } else if name == b"pb" {
+                    out.push('\n');
+                    out.push('\n');
+                } else if name == b"g" {
+                    if let Some(r) = attr_val(&e, b"ref") {
+                        let key = r.trim_start_matches('#').to_string();
+                        if let Some(v) = gaiji.get(&key) {
+                            out.push_str(v);
                         }
                     }
                 }
             }
-            Ok(Event::End(_)) => {}
             Ok(Event::Text(t)) => {
-                if capturing {
-                    out.push_str(&t.decode().unwrap_or_default());
-                }
+                out.push_str(&t.decode().unwrap_or_default());
             }
             Ok(Event::CData(t)) => {
-                if capturing {
-                    out.push_str(&String::from_utf8_lossy(&t));
-                }
+                out.push_str(&String::from_utf8_lossy(&t));
             }
             Ok(Event::Eof) => break,
             Err(_) => break,
@@ -2394,7 +2725,7 @@ pub fn extract_cbeta_juan(xml: &str, part: &str) -> Option<String> {
         }
         buf.clear();
     }
-    if out.is_empty() {
+    if out.trim().is_empty() {
         None
     } else {
         Some(out.split_whitespace().collect::<Vec<_>>().join(" "))
@@ -2405,155 +2736,24 @@ pub fn extract_cbeta_juan(xml: &str, part: &str) -> Option<String> {
 /// This respects `include_notes` and resolves gaiji using `<charDecl>` from the full document.
 pub fn extract_cbeta_juan_plain(xml: &str, part: &str, include_notes: bool) -> Option<String> {
     let gaiji = cbeta_gaiji_map_fast(xml);
-    let target_n1 = part.to_string();
-    let target_n2 = format!("{:0>3}", part);
-    let mut reader = Reader::from_str(xml);
-    reader.config_mut().trim_text_start = true;
-    reader.config_mut().trim_text_end = true;
-    let mut buf = Vec::new();
-    let mut capturing = false;
-    let mut out = String::new();
-
-    let mut skip_note_depth: usize = 0;
-    let mut collect_note: bool = false;
-    let mut note_depth: usize = 0;
-    let mut note_buf = String::new();
+    extract_cbeta_juan_plain_with_map(xml, &JuanMap::build(xml), &gaiji, part, include_notes)
+}
 
-    loop {
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(e)) => {
-                let name_owned = e.name().as_ref().to_owned();
-                let name = local_name(&name_owned);
-                if name == b"juan" {
-                    let fun = attr_val(&e, b"fun").map(|v| v.to_ascii_lowercase());
-                    let n = attr_val(&e, b"n").unwrap_or(Cow::Borrowed(""));
-                    if !capturing {
-                        if (n == target_n1 || n == target_n2)
-                            && (fun.as_deref() == Some("open") || fun.is_none())
-                        {
-                            capturing = true;
-                        }
-                    } else if fun.as_deref() == Some("close") {
-                        break;
-                    }
-                } else if capturing {
-                    if collect_note {
-                        note_depth += 1;
-                        if name == b"g" {
-                            if let Some(r) = attr_val(&e, b"ref") {
-                                let key = r.trim_start_matches('#').to_string();
-                                if let Some(v) = gaiji.get(&key) {
-                                    note_buf.push_str(v);
-                                }
-                            }
-                        }
-                    } else if skip_note_depth > 0 {
-                        skip_note_depth += 1;
-                    } else if name == b"note" {
-                        if include_notes {
-                            collect_note = true;
-                            note_depth = 1;
-                            note_buf.clear();
-                        } else {
-                            skip_note_depth = 1;
-                        }
-                    } else if name == b"lb" {
-                        out.push('\n');
-                    } else if name == b"pb" {
-                        out.push('\n');
-                        out.push('\n');
-                    } else if name == b"g" {
-                        if let Some(r) = attr_val(&e, b"ref") {
-                            let key = r.trim_start_matches('#').to_string();
-                            if let Some(v) = gaiji.get(&key) {
-                                out.push_str(v);
-                            }
-                        }
-                    }
-                }
-            }
-            Ok(Event::Empty(e)) => {
-                if !capturing {
-                    // ignore
-                } else {
-                    let name_owned = e.name().as_ref().to_owned();
-                    let name = local_name(&name_owned);
-                    if collect_note {
-                        if name == b"g" {
-                            if let Some(r) = attr_val(&e, b"ref") {
-                                let key = r.trim_start_matches('#').to_string();
-                                if let Some(v) = gaiji.get(&key) {
-                                    note_buf.push_str(v);
-                                }
-                            }
-                        }
-                    } else if skip_note_depth == 0 {
-                        if name == b"lb" {
-                            out.push('\n');
-                        } else if name == b"pb" {
-                            out.push('\n');
-                            out.push('\n');
-                        } else if name == b"g" {
-                            if let Some(r) = attr_val(&e, b"ref") {
-                                let key = r.trim_start_matches('#').to_string();
-                                if let Some(v) = gaiji.get(&key) {
-                                    out.push_str(v);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            Ok(Event::End(e)) => {
-                if capturing {
-                    let name_owned = e.name().as_ref().to_owned();
-                    let name = local_name(&name_owned);
-                    if collect_note {
-                        note_depth = note_depth.saturating_sub(1);
-                        if name == b"note" && note_depth == 0 {
-                            let t = note_buf.split_whitespace().collect::<Vec<_>>().join(" ");
-                            if !t.is_empty() {
-                                out.push_str(" [注] ");
-                                out.push_str(&t);
-                            }
-                            collect_note = false;
-                            note_buf.clear();
-                        }
-                    } else if skip_note_depth > 0 {
-                        skip_note_depth = skip_note_depth.saturating_sub(1);
-                    }
-                }
-            }
-            Ok(Event::Text(t)) => {
-                if capturing {
-                    let text = t.decode().unwrap_or_default().into_owned();
-                    if collect_note {
-                        note_buf.push_str(&text);
-                    } else if skip_note_depth == 0 {
-                        out.push_str(&text);
-                    }
-                }
-            }
-            Ok(Event::CData(t)) => {
-                if capturing {
-                    let text = String::from_utf8_lossy(&t).into_owned();
-                    if collect_note {
-                        note_buf.push_str(&text);
-                    } else if skip_note_depth == 0 {
-                        out.push_str(&text);
-                    }
-                }
-            }
-            Ok(Event::Eof) => break,
-            Err(_) => break,
-            _ => {}
-        }
-        buf.clear();
-    }
-    if out.trim().is_empty() {
+/// Like [`extract_cbeta_juan_plain`], with the juan map and gaiji map supplied by the caller.
+pub fn extract_cbeta_juan_plain_with_map(
+    xml: &str,
+    map: &JuanMap,
+    gaiji: &HashMap<String, String>,
+    part: &str,
+    include_notes: bool,
+) -> Option<String> {
+    let _t = Phase::Extract.start();
+    let span = map.get(part)?;
+    let t = extract_cbeta_plain_impl(&xml[span.start..span.end], Some(gaiji), include_notes, true);
+    if t.trim().is_empty() {
         None
     } else {
-        Some(normalize_plain_lines(&out))
+        Some(t)
     }
 }
 
@@ -2578,12 +2778,12 @@ pub fn list_heads_cbeta(xml: &str) -> Vec<String> {
                     in_head = true;
                     head_buf.clear();
                 }
-                if lname.as_slice() == b"title" {
-                    if path_stack.iter().any(|n| n.as_slice() == b"jhead") {
-                        in_jhead_title = true;
-                        jhead_buf.clear();
-                    }
-                }
+                if lname.as_slice() == b"title"
+                    && path_stack.iter().any(|n| n.as_slice() == b"jhead")
+                {
+                    in_jhead_title = true;
+                    jhead_buf.clear();
+                }
             }
             Ok(Event::End(e)) => {
                 let name_owned = e.name().as_ref().to_owned();
@@ -2624,6 +2824,108 @@ pub fn list_heads_cbeta(xml: &str) -> Vec<String> {
     heads
 }
 
+/// One entry of the CBETA table of contents (`<cb:mulu>`).
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MuluEntry {
+    /// Nesting level (1 = top).
+    pub level: u32,
+    /// `n` attribute (section number), when present.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<String>,
+    /// `type` attribute (品, 分, 卷, 序 …).
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    pub title: String,
+    /// Last `<lb n>` seen before the entry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lb: Option<String>,
+    /// Juan (3-digit) the entry falls in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub juan: Option<String>,
+    /// Byte range of the section: from the end of the `<cb:mulu>` element to the next
+    /// entry of the same or a higher level (or the end of the document).
+    #[serde(skip)]
+    pub start: usize,
+    #[serde(skip)]
+    pub end: usize,
+}
+
+/// Parse the `<cb:mulu>` table of contents. Entries without a title (e.g. bare
+/// `<cb:mulu type="卷" n="1"/>` juan markers) are skipped.
+pub fn list_mulu_cbeta(xml: &str) -> Vec<MuluEntry> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut out: Vec<MuluEntry> = Vec::new();
+    let mut tag_starts: Vec<usize> = Vec::new();
+    let mut cur: Option<(usize, MuluEntry)> = None;
+    let mut last_lb: Option<String> = None;
+    loop {
+        let pos = reader.buffer_position() as usize;
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                let name_owned = e.name().as_ref().to_owned();
+                match local_name(&name_owned) {
+                    b"mulu" => {
+                        let entry = MuluEntry {
+                            level: attr_val(&e, b"level")
+                                .and_then(|v| v.trim().parse().ok())
+                                .unwrap_or(1),
+                            n: attr_val(&e, b"n").map(|v| v.into_owned()),
+                            kind: attr_val(&e, b"type").map(|v| v.into_owned()),
+                            title: String::new(),
+                            lb: last_lb.clone(),
+                            juan: None,
+                            start: pos,
+                            end: xml.len(),
+                        };
+                        cur = Some((pos, entry));
+                    }
+                    b"lb" => last_lb = attr_val(&e, b"n").map(|v| v.into_owned()),
+                    _ => {}
+                }
+            }
+            Ok(Event::Empty(e)) => {
+                let name_owned = e.name().as_ref().to_owned();
+                if local_name(&name_owned) == b"lb" {
+                    last_lb = attr_val(&e, b"n").map(|v| v.into_owned());
+                }
+            }
+            Ok(Event::Text(t)) => {
+                if let Some((_, m)) = cur.as_mut() {
+                    m.title.push_str(&t.decode().unwrap_or_default());
+                }
+            }
+            Ok(Event::End(e)) => {
+                let name_owned = e.name().as_ref().to_owned();
+                if local_name(&name_owned) == b"mulu" {
+                    if let Some((tag_start, mut m)) = cur.take() {
+                        m.title = m.title.split_whitespace().collect::<Vec<_>>().join(" ");
+                        if !m.title.is_empty() {
+                            m.start = reader.buffer_position() as usize;
+                            tag_starts.push(tag_start);
+                            out.push(m);
+                        }
+                    }
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    let map = JuanMap::build(xml);
+    for i in 0..out.len() {
+        let level = out[i].level;
+        if let Some(k) = (i + 1..out.len()).find(|&k| out[k].level <= level) {
+            out[i].end = tag_starts[k];
+        }
+        out[i].juan = map.juan_at_offset(out[i].start).map(|j| j.n.clone());
+    }
+    out
+}
+
 pub fn list_heads_generic(xml: &str) -> Vec<String> {
     let mut reader = Reader::from_str(xml);
     reader.config_mut().trim_text_start = true;
@@ -2652,10 +2954,8 @@ pub fn list_heads_generic(xml: &str) -> Vec<String> {
                     head_buf.clear();
                 }
             }
-            Ok(Event::Text(t)) => {
-                if in_head {
-                    head_buf.push_str(&t.decode().unwrap_or_default());
-                }
+            Ok(Event::Text(t)) if in_head => {
+                head_buf.push_str(&t.decode().unwrap_or_default());
             }
             Ok(Event::Eof) => break,
             Err(_) => break,
@@ -2671,7 +2971,7 @@ pub fn strip_tags(s: &str) -> String {
     s.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct GrepResult {
     pub file_path: String,
     pub file_id: String,
@@ -2679,22 +2979,59 @@ pub struct GrepResult {
     pub matches: Vec<GrepMatch>,
     pub total_matches: usize,
     pub fetch_hints: FetchHints,
+    /// Relevance score set by [`relevance::rank`] (higher is better).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    /// The title in its other forms, from the corpus index ([`titles::apply_index_titles`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub titles: Option<titles::Titles>,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct GrepMatch {
     pub context: String,
     pub highlight: String,
     pub juan_number: Option<String>, // CBETA用
     pub section: Option<String>,     // 構造情報
     pub line_number: Option<usize>,  // マッチした行番号
+    /// Indexes of the patterns the line matched, in a multi-pattern search ([`multi`]).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patterns: Vec<usize>,
+    /// Char ranges of every occurrence in `context`, when it holds more than one
+    /// ([`snippets::merge_matches`]).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ranges: Vec<[usize; 2]>,
+    /// Last line of a snippet merged from adjacent lines.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+    /// Other lines whose text is identical to `context`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub also_at: Vec<usize>,
+    /// Stable id of the match ([`match_ids::assign`]), accepted as `matchId` by fetch tools.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_id: Option<String>,
+    /// CBETA `lb` of the line, for matches found in a plain shadow ([`plain`]), whose
+    /// `context` no longer holds the `<lb/>` element.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lb: Option<String>,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct FetchHints {
     pub recommended_parts: Vec<String>,
+    /// Human-readable size of a full fetch; plain-text characters once the index is applied
+    /// (see [`sizes::apply_index_sizes`]), else the XML size.
     pub total_content_size: Option<String>,
     pub structure_info: Vec<String>,
+    /// Bytes on disk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_size: Option<u64>,
+    /// Plain-text characters of the whole file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_chars: Option<usize>,
+    /// Plain-text characters of each juan (CBETA).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub part_chars: Vec<sizes::PartSize>,
 }
 
 pub fn cbeta_grep(
@@ -2703,14 +3040,10 @@ pub fn cbeta_grep(
     max_results: usize,
     max_matches_per_file: usize,
 ) -> Vec<GrepResult> {
+    let cancel = CancelToken::current();
     // Build ripgrep matcher once (case-insensitive)
-    let matcher = match RegexMatcherBuilder::new()
-        .case_insensitive(true)
-        .multi_line(true)
-        .build(query)
-    {
-        Ok(m) => m,
-        Err(_) => return Vec::new(),
+    let Some(matcher) = QueryMatcher::new(query) else {
+        return Vec::new();
     };
 
     // 1. まずTフォルダから優先的に検索
@@ -2718,37 +3051,262 @@ pub fn cbeta_grep(
     let mut all_results = Vec::new();
 
     if t_folder.exists() {
-        let t_results = cbeta_grep_internal(&t_folder, &matcher, max_results, max_matches_per_file);
+        let t_results = cbeta_grep_internal(
+            &t_folder,
+            &matcher,
+            max_results,
+            max_matches_per_file,
+            &cancel,
+        );
         all_results.extend(t_results);
     }
 
     // 2. まだ結果が不足している場合は、他のフォルダも検索
-    if all_results.len() < max_results {
+    if all_results.len() < max_results && !cancel.is_cancelled() {
         let remaining_limit = max_results - all_results.len();
-        let other_results =
-            cbeta_grep_internal_exclude_t(root, &matcher, remaining_limit, max_matches_per_file);
+        let other_results = cbeta_grep_internal_exclude_t(
+            root,
+            &matcher,
+            remaining_limit,
+            max_matches_per_file,
+            &cancel,
+        );
         all_results.extend(other_results);
     }
 
     // NOTE: Avoid building full CBETA index here; it is extremely expensive and
     // dominated cbeta_search latency. Keep ordering deterministic and cheap.
-    all_results.sort_by(|a, b| {
-        let a_is_t = a.file_id.starts_with('T');
-        let b_is_t = b.file_id.starts_with('T');
-        match (a_is_t, b_is_t) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => match b.total_matches.cmp(&a.total_matches) {
-                std::cmp::Ordering::Equal => a.file_id.cmp(&b.file_id),
-                other => other,
-            },
-        }
-    });
-
+    sort_taisho_first(&mut all_results);
     all_results.truncate(max_results);
     all_results
 }
 
+/// Taishō first, then by match count, then by id.
+fn sort_taisho_first(results: &mut [GrepResult]) {
+    results.sort_by(|a, b| {
+        b.file_id
+            .starts_with('T')
+            .cmp(&a.file_id.starts_with('T'))
+            .then(b.total_matches.cmp(&a.total_matches))
+            .then(a.file_id.cmp(&b.file_id))
+    });
+}
+
+/// [`cbeta_grep`] over the given files only (`scope` of the search tools), so a search inside
+/// works already found does not walk the whole corpus.
+pub fn cbeta_grep_scoped(
+    paths: &[PathBuf],
+    query: &str,
+    max_results: usize,
+    max_matches_per_file: usize,
+) -> Vec<GrepResult> {
+    let cancel = CancelToken::current();
+    let Some(matcher) = QueryMatcher::new(query) else {
+        return Vec::new();
+    };
+    let mut results: Vec<GrepResult> = scan::run(|| {
+        paths
+            .par_iter()
+            .filter_map(|p| {
+                if cancel.is_cancelled() {
+                    return None;
+                }
+                grep_file(p, &matcher, max_matches_per_file, &[])
+            })
+            .collect()
+    });
+    sort_taisho_first(&mut results);
+    results.truncate(max_results);
+    results
+}
+
+/// GRETIL / SARIT / MUKTABODHA search over the given files only (see [`cbeta_grep_scoped`]).
+pub fn grep_scoped(
+    paths: &[PathBuf],
+    query: &str,
+    max_results: usize,
+    max_matches_per_file: usize,
+) -> Vec<GrepResult> {
+    let cancel = CancelToken::current();
+    let Some(matcher) = QueryMatcher::new(query) else {
+        return Vec::new();
+    };
+    let mut results: Vec<GrepResult> = scan::run(|| {
+        paths
+            .par_iter()
+            .filter_map(|p| {
+                if cancel.is_cancelled() {
+                    return None;
+                }
+                grep_file(p, &matcher, max_matches_per_file, &["full"])
+            })
+            .collect()
+    });
+    grep_sort_best_first(&mut results, max_results);
+    results
+}
+
+/// A `<note>` element of a CBETA file (editorial comment, variant note, inline note).
+#[derive(Debug, Clone)]
+pub struct CbetaNote {
+    /// `type` attribute (orig, mod, add …), when present.
+    pub kind: Option<String>,
+    pub n: Option<String>,
+    pub text: String,
+    /// 1-based XML line of the opening tag.
+    pub line: usize,
+}
+
+/// Collect the outermost `<note>` elements of a CBETA file with their whitespace-normalized
+/// text (nested markup flattened). Notes repeated with the same type, number and text are
+/// reported once.
+pub fn list_cbeta_notes(xml: &str) -> Vec<CbetaNote> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut out: Vec<CbetaNote> = Vec::new();
+    let mut depth = 0usize;
+    let mut cur: Option<CbetaNote> = None;
+    let mut line = 1usize;
+    let mut line_pos = 0usize;
+    loop {
+        let pos = reader.buffer_position() as usize;
+        line += xml.as_bytes()[line_pos..pos.min(xml.len())]
+            .iter()
+            .filter(|b| **b == b'\n')
+            .count();
+        line_pos = pos.min(xml.len());
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                let name_owned = e.name().as_ref().to_owned();
+                if depth > 0 {
+                    depth += 1;
+                } else if local_name(&name_owned) == b"note" {
+                    depth = 1;
+                    cur = Some(CbetaNote {
+                        kind: attr_val(&e, b"type").map(|v| v.into_owned()),
+                        n: attr_val(&e, b"n").map(|v| v.into_owned()),
+                        text: String::new(),
+                        line,
+                    });
+                }
+            }
+            Ok(Event::Text(t)) if depth > 0 => {
+                if let Some(n) = cur.as_mut() {
+                    n.text.push_str(&t.decode().unwrap_or_default());
+                }
+            }
+            Ok(Event::End(_)) if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    if let Some(mut n) = cur.take() {
+                        n.text = n.text.split_whitespace().collect::<Vec<_>>().join(" ");
+                        let dup = out
+                            .iter()
+                            .any(|o| o.kind == n.kind && o.n == n.n && o.text == n.text);
+                        if !n.text.is_empty() && !dup {
+                            out.push(n);
+                        }
+                    }
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    out
+}
+
+/// Search only inside `<note>` content (editorial and variant notes) of CBETA files.
+/// Files are prefiltered with ripgrep, then each note's text is matched on its own, so a
+/// hit in the main text next to a note does not count. Matches carry the note text as
+/// context and `note:<type>` as section.
+pub fn cbeta_grep_notes(
+    root: &Path,
+    query: &str,
+    max_results: usize,
+    max_matches_per_file: usize,
+) -> Vec<GrepResult> {
+    let paths =
+        collect_xml_paths_cached(&XML_PATHS_ALL_CACHE, root, |_, name| name.ends_with(".xml"));
+    cbeta_grep_notes_scoped(&paths, query, max_results, max_matches_per_file)
+}
+
+/// [`cbeta_grep_notes`] over the given files only.
+pub fn cbeta_grep_notes_scoped(
+    paths: &[PathBuf],
+    query: &str,
+    max_results: usize,
+    max_matches_per_file: usize,
+) -> Vec<GrepResult> {
+    let cancel = CancelToken::current();
+    let (Some(matcher), Ok(re)) = (
+        QueryMatcher::new(query),
+        regex::RegexBuilder::new(query)
+            .case_insensitive(true)
+            .build(),
+    ) else {
+        return Vec::new();
+    };
+    let mut results: Vec<GrepResult> = scan::run(|| {
+        paths
+            .par_iter()
+            .filter_map(|p| {
+                if cancel.is_cancelled() {
+                    return None;
+                }
+                // Note text may span lines, so a file without any line match can still hit;
+                // those are rare enough to trade for the cheap prefilter.
+                ripgrep_search_file(p, &matcher, 1)?;
+                let xml = std::fs::read_to_string(p).ok()?;
+                let notes = list_cbeta_notes(&xml);
+                let mut total = 0usize;
+                let mut matches: Vec<GrepMatch> = Vec::new();
+                for n in notes.iter() {
+                    let Some(m) = re.find(&n.text) else {
+                        continue;
+                    };
+                    total += 1;
+                    if matches.len() < max_matches_per_file {
+                        matches.push(GrepMatch {
+                            context: n.text.clone(),
+                            highlight: m.as_str().to_string(),
+                            juan_number: None,
+                            section: Some(format!("note:{}", n.kind.as_deref().unwrap_or("note"))),
+                            line_number: Some(n.line),
+                            ..Default::default()
+                        });
+                    }
+                }
+                if matches.is_empty() {
+                    return None;
+                }
+                let file_id = stem_from(p);
+                Some(GrepResult {
+                    file_path: p.to_string_lossy().to_string(),
+                    title: file_id.clone(),
+                    file_id,
+                    matches,
+                    total_matches: total,
+                    fetch_hints: FetchHints {
+                        recommended_parts: vec![],
+                        total_content_size: Some(format!("{}KB XML", xml.len() / 1024)),
+                        structure_info: vec![],
+                        file_size: Some(xml.len() as u64),
+                        ..Default::default()
+                    },
+                    score: None,
+                    titles: None,
+                })
+            })
+            .collect()
+    });
+    sort_taisho_first(&mut results);
+    results.truncate(max_results);
+    results
+}
+
 fn grep_sort_best_first(results: &mut Vec<GrepResult>, max_results: usize) {
     // Prefer more matches, then stable file_id ordering.
     let cmp = |a: &GrepResult, b: &GrepResult| match b.total_matches.cmp(&a.total_matches) {
@@ -2779,10 +3337,11 @@ struct RgMatch {
 /// Generic ripgrep-based search function that returns matches per file
 fn ripgrep_search_file(
     path: &Path,
-    matcher: &grep_regex::RegexMatcher,
+    matcher: &QueryMatcher,
     max_matches: usize,
 ) -> Option<Vec<RgMatch>> {
     let mut matches: Vec<RgMatch> = Vec::new();
+    scan::pace(path);
 
     // Use memory-mapped I/O for faster file access
     let mut searcher = SearcherBuilder::new()
@@ -2814,72 +3373,106 @@ fn ripgrep_search_file(
     }
 }
 
+/// Grep one file and turn its ripgrep matches into a [`GrepResult`]. Matches carry no juan or
+/// section; reading structure is left to the callers that need it.
+fn grep_file(
+    p: &Path,
+    matcher: &QueryMatcher,
+    max_matches_per_file: usize,
+    recommended_parts: &[&str],
+) -> Option<GrepResult> {
+    let shadow = plain::fresh_shadow(p);
+    let rg_matches = ripgrep_search_file(
+        shadow.as_deref().unwrap_or(p),
+        matcher,
+        max_matches_per_file,
+    )?;
+    let xml = shadow
+        .is_some()
+        .then(|| read_file_with_encoding(p))
+        .flatten();
+    let xml_lines: Vec<&str> = xml
+        .as_deref()
+        .map(|x| x.lines().collect())
+        .unwrap_or_default();
+
+    // Get file size without reading entire content (faster)
+    let file_size = std::fs::metadata(p).ok().map(|m| m.len()).unwrap_or(0);
+
+    let grep_matches: Vec<GrepMatch> = rg_matches
+        .iter()
+        .map(|m| {
+            // Find the actual matched text in the line
+            let highlight = matcher
+                .find(m.line_content.as_bytes())
+                .ok()
+                .flatten()
+                .map(|mat| {
+                    m.line_content
+                        .get(mat.start()..mat.end())
+                        .unwrap_or("")
+                        .to_string()
+                })
+                .unwrap_or_default();
+
+            GrepMatch {
+                context: m.line_content.clone(),
+                highlight,
+                juan_number: None, // Skip expensive XML parsing for search
+                section: None,
+                line_number: Some(m.line_number as usize),
+                lb: xml_lines
+                    .get(m.line_number as usize - 1)
+                    .and_then(|l| plain::line_lb(l))
+                    .map(str::to_string),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    let file_id = stem_from(p);
+    let total_matches = grep_matches.len();
+    Some(GrepResult {
+        file_path: p.to_string_lossy().to_string(),
+        title: file_id.clone(),
+        file_id,
+        matches: grep_matches,
+        total_matches,
+        fetch_hints: FetchHints {
+            recommended_parts: recommended_parts.iter().map(|s| s.to_string()).collect(),
+            total_content_size: Some(format!("{}KB XML", file_size / 1024)),
+            structure_info: Vec::new(),
+            file_size: Some(file_size),
+            ..Default::default()
+        },
+        score: None,
+        titles: None,
+    })
+}
+
 fn cbeta_grep_internal(
     root: &Path,
-    matcher: &grep_regex::RegexMatcher,
+    matcher: &QueryMatcher,
     max_results: usize,
     max_matches_per_file: usize,
+    cancel: &CancelToken,
 ) -> Vec<GrepResult> {
     // Collect XML file paths using ignore crate
     let paths =
         collect_xml_paths_cached(&XML_PATHS_ALL_CACHE, root, |_, name| name.ends_with(".xml"));
 
     // Search files in parallel using ripgrep
-    let mut results: Vec<GrepResult> = paths
-        .par_iter()
-        .filter_map(|p| {
-            let rg_matches = ripgrep_search_file(p, &matcher, max_matches_per_file)?;
-
-            // Get file size without reading entire content (faster)
-            let file_size = std::fs::metadata(p).ok().map(|m| m.len()).unwrap_or(0);
-
-            // Convert ripgrep matches to GrepMatch
-            let grep_matches: Vec<GrepMatch> = rg_matches
-                .iter()
-                .map(|m| {
-                    // Find the actual matched text in the line
-                    let highlight = matcher
-                        .find(m.line_content.as_bytes())
-                        .ok()
-                        .flatten()
-                        .map(|mat| {
-                            m.line_content
-                                .get(mat.start()..mat.end())
-                                .unwrap_or("")
-                                .to_string()
-                        })
-                        .unwrap_or_default();
-
-                    GrepMatch {
-                        context: m.line_content.clone(),
-                        highlight,
-                        juan_number: None, // Skip expensive XML parsing for search
-                        section: None,
-                        line_number: Some(m.line_number as usize),
-                    }
-                })
-                .collect();
-
-            let file_id = stem_from(p);
-            let title = file_id.clone();
-            let total_matches = grep_matches.len();
-
-            let fetch_hints = FetchHints {
-                recommended_parts: vec![], // Populated on fetch, not search
-                total_content_size: Some(format!("{}KB", file_size / 1024)),
-                structure_info: vec![],
-            };
-
-            Some(GrepResult {
-                file_path: p.to_string_lossy().to_string(),
-                file_id,
-                title,
-                matches: grep_matches,
-                total_matches,
-                fetch_hints,
+    let mut results: Vec<GrepResult> = scan::run(|| {
+        paths
+            .par_iter()
+            .filter_map(|p| {
+                if cancel.is_cancelled() {
+                    return None;
+                }
+                grep_file(p, matcher, max_matches_per_file, &[])
             })
-        })
-        .collect();
+            .collect()
+    });
 
     // Make selection deterministic; otherwise parallel walk + take(N) yields unstable sets.
     grep_sort_best_first(&mut results, max_results);
@@ -2888,9 +3481,10 @@ fn cbeta_grep_internal(
 
 fn cbeta_grep_internal_exclude_t(
     root: &Path,
-    matcher: &grep_regex::RegexMatcher,
+    matcher: &QueryMatcher,
     max_results: usize,
     max_matches_per_file: usize,
+    cancel: &CancelToken,
 ) -> Vec<GrepResult> {
     // Collect XML file paths excluding /T/ folder using ignore crate
     let paths = collect_xml_paths_cached(&CBETA_XML_PATHS_EXCLUDE_T_CACHE, root, |path, name| {
@@ -2898,67 +3492,24 @@ fn cbeta_grep_internal_exclude_t(
     });
 
     // Search files in parallel using ripgrep
-    let mut results: Vec<GrepResult> = paths
-        .par_iter()
-        .filter_map(|p| {
-            let rg_matches = ripgrep_search_file(p, &matcher, max_matches_per_file)?;
-
-            // Get file size without reading entire content (faster)
-            let file_size = std::fs::metadata(p).ok().map(|m| m.len()).unwrap_or(0);
-
-            // Convert ripgrep matches to GrepMatch
-            let grep_matches: Vec<GrepMatch> = rg_matches
-                .iter()
-                .map(|m| {
-                    let highlight = matcher
-                        .find(m.line_content.as_bytes())
-                        .ok()
-                        .flatten()
-                        .map(|mat| {
-                            m.line_content
-                                .get(mat.start()..mat.end())
-                                .unwrap_or("")
-                                .to_string()
-                        })
-                        .unwrap_or_default();
-
-                    GrepMatch {
-                        context: m.line_content.clone(),
-                        highlight,
-                        juan_number: None, // Skip expensive XML parsing for search
-                        section: None,
-                        line_number: Some(m.line_number as usize),
-                    }
-                })
-                .collect();
-
-            let file_id = stem_from(p);
-            let title = file_id.clone();
-            let total_matches = grep_matches.len();
-
-            let fetch_hints = FetchHints {
-                recommended_parts: vec![], // Populated on fetch, not search
-                total_content_size: Some(format!("{}KB", file_size / 1024)),
-                structure_info: vec![],
-            };
-
-            Some(GrepResult {
-                file_path: p.to_string_lossy().to_string(),
-                file_id,
-                title,
-                matches: grep_matches,
-                total_matches,
-                fetch_hints,
+    let mut results: Vec<GrepResult> = scan::run(|| {
+        paths
+            .par_iter()
+            .filter_map(|p| {
+                if cancel.is_cancelled() {
+                    return None;
+                }
+                grep_file(p, matcher, max_matches_per_file, &[])
             })
-        })
-        .collect();
+            .collect()
+    });
 
     grep_sort_best_first(&mut results, max_results);
     results
 }
 
 /// Helper to read file with UTF-16 support (for Tipitaka)
-fn read_file_with_encoding(path: &Path) -> Option<String> {
+pub(crate) fn read_file_with_encoding(path: &Path) -> Option<String> {
     let bytes = std::fs::read(path).ok()?;
     if bytes.starts_with(&[0xFF, 0xFE]) {
         match encoding_rs::UTF_16LE.decode(&bytes) {
@@ -2978,14 +3529,12 @@ fn read_file_with_encoding(path: &Path) -> Option<String> {
 /// Ripgrep search on a string content (for UTF-16 files that need pre-processing)
 fn ripgrep_search_content(
     content: &str,
-    matcher: &grep_regex::RegexMatcher,
+    matcher: &QueryMatcher,
     max_matches: usize,
 ) -> Vec<RgMatch> {
     let mut results = Vec::new();
-    let mut line_number = 0u64;
 
-    for line in content.lines() {
-        line_number += 1;
+    for (line_number, line) in (1u64..).zip(content.lines()) {
         if results.len() >= max_matches {
             break;
         }
@@ -3005,150 +3554,244 @@ pub fn tipitaka_grep(
     max_results: usize,
     max_matches_per_file: usize,
 ) -> Vec<GrepResult> {
-    // Build ripgrep matcher (case-insensitive)
-    let matcher = match RegexMatcherBuilder::new()
-        .case_insensitive(true)
-        .multi_line(true)
-        .build(query)
-    {
-        Ok(m) => m,
-        Err(_) => return Vec::new(),
-    };
+    let paths = tipitaka_grep_paths(root);
+    tipitaka_grep_impl(&paths, query, max_results, max_matches_per_file, false)
+}
 
-    // Collect XML file paths using ignore crate
-    let paths = collect_xml_paths_cached(&TIPITAKA_XML_PATHS_CACHE, root, |_, name| {
-        name.ends_with(".xml") && !name.contains("toc") && !name.contains("sitemap")
-    });
+/// Diacritic-insensitive variant of [`tipitaka_grep`]: the query and a cached folded
+/// shadow of each file are compared (`panna` finds `paññā`), and matches are reported
+/// with the original line and matched text.
+pub fn tipitaka_grep_folded(
+    root: &Path,
+    query: &str,
+    max_results: usize,
+    max_matches_per_file: usize,
+) -> Vec<GrepResult> {
+    let q = fold::fold_diacritics(query);
+    let paths = tipitaka_grep_paths(root);
+    tipitaka_grep_impl(&paths, &q, max_results, max_matches_per_file, true)
+}
 
-    // Search files in parallel using ripgrep
-    paths
-        .par_iter()
-        .filter_map(|p| {
-            // UTF-16対応の読み込み
-            let content = read_file_with_encoding(p)?;
+/// [`tipitaka_grep`] (or [`tipitaka_grep_folded`]) over the given files only.
+pub fn tipitaka_grep_scoped(
+    paths: &[PathBuf],
+    query: &str,
+    max_results: usize,
+    max_matches_per_file: usize,
+    fold_diacritics: bool,
+) -> Vec<GrepResult> {
+    if fold_diacritics {
+        let q = fold::fold_diacritics(query);
+        tipitaka_grep_impl(paths, &q, max_results, max_matches_per_file, true)
+    } else {
+        tipitaka_grep_impl(paths, query, max_results, max_matches_per_file, false)
+    }
+}
 
-            // Search using ripgrep matcher on content
-            let rg_matches = ripgrep_search_content(&content, &matcher, max_matches_per_file);
-            if rg_matches.is_empty() {
-                return None;
-            }
+/// The files a whole-corpus search of `corpus` under `root` reads; empty for an unknown corpus.
+pub fn grep_paths(corpus: &str, root: &Path) -> Arc<Vec<PathBuf>> {
+    match corpus {
+        "cbeta" | "gretil" => {
+            collect_xml_paths_cached(&XML_PATHS_ALL_CACHE, root, |_, name| name.ends_with(".xml"))
+        }
+        "tipitaka" => tipitaka_grep_paths(root),
+        "sarit" => collect_xml_paths_cached(&SARIT_XML_PATHS_CACHE, root, |path, name| {
+            is_sarit_xml(path, name)
+        }),
+        "muktabodha" => collect_xml_paths_cached(&MUKTABODHA_PATHS_CACHE, root, |path, name| {
+            is_muktabodha_file(path, name)
+        }),
+        _ => Arc::new(Vec::new()),
+    }
+}
 
-            let mut structure_info = Vec::new();
+fn tipitaka_grep_paths(root: &Path) -> Arc<Vec<PathBuf>> {
+    collect_xml_paths_cached(&TIPITAKA_XML_PATHS_CACHE, root, |_, name| {
+        name.ends_with(".xml") && !name.contains("toc") && !name.contains("sitemap")
+    })
+}
 
-            // 構造情報の高速抽出
-            let mut reader = Reader::from_str(&content);
-            reader.config_mut().trim_text_start = true;
-            reader.config_mut().trim_text_end = true;
-            let mut buf = Vec::new();
-            let mut events = 0;
-            let mut nikaya = None;
-            let mut book = None;
+fn tipitaka_grep_impl(
+    paths: &[PathBuf],
+    query: &str,
+    max_results: usize,
+    max_matches_per_file: usize,
+    fold_diacritics: bool,
+) -> Vec<GrepResult> {
+    let cancel = CancelToken::current();
+    // Build ripgrep matcher (case-insensitive)
+    let Some(matcher) = QueryMatcher::new(query) else {
+        return Vec::new();
+    };
 
-            loop {
-                if events > 5000 {
-                    break;
+    // Search files in parallel using ripgrep
+    scan::run(|| {
+        paths
+            .par_iter()
+            .filter_map(|p| {
+                if cancel.is_cancelled() {
+                    return None;
                 }
-                match reader.read_event_into(&mut buf) {
-                    Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
-                        let name_owned = e.name().as_ref().to_owned();
-                        let name = local_name(&name_owned);
-                        if name == b"p" {
-                            if let Some(rend) = attr_val(&e, b"rend") {
-                                let rend_str = rend.to_ascii_lowercase();
-                                if rend_str == "nikaya" && nikaya.is_none() {
-                                    // 次のテキストを取得
+                // UTF-16対応の読み込み
+                scan::pace(p);
+                let content = read_file_with_encoding(p)?;
+
+                // Search the plain-text shadow when there is one, else the XML; folded
+                // searches fold whichever it is.
+                let plain = plain::fresh_shadow(p).and_then(|sp| std::fs::read_to_string(sp).ok());
+                let text = plain.as_deref().unwrap_or(&content);
+                let folded = fold_diacritics.then(|| match plain {
+                    Some(_) => fold::fold_diacritics(text),
+                    None => fold::folded_shadow(p, &content),
+                });
+                let rg_matches = ripgrep_search_content(
+                    folded.as_deref().unwrap_or(text),
+                    &matcher,
+                    max_matches_per_file,
+                );
+                if rg_matches.is_empty() {
+                    return None;
+                }
+                let orig_lines: Vec<&str> = if fold_diacritics {
+                    text.lines().collect()
+                } else {
+                    Vec::new()
+                };
+
+                let mut structure_info = Vec::new();
+
+                // 構造情報の高速抽出
+                let mut reader = Reader::from_str(&content);
+                reader.config_mut().trim_text_start = true;
+                reader.config_mut().trim_text_end = true;
+                let mut buf = Vec::new();
+                let mut events = 0;
+                let mut nikaya = None;
+                let mut book = None;
+
+                loop {
+                    if events > 5000 {
+                        break;
+                    }
+                    match reader.read_event_into(&mut buf) {
+                        Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                            let name_owned = e.name().as_ref().to_owned();
+                            let name = local_name(&name_owned);
+                            if name == b"p" {
+                                if let Some(rend) = attr_val(&e, b"rend") {
+                                    let rend_str = rend.to_ascii_lowercase();
+                                    if rend_str == "nikaya" && nikaya.is_none() {
+                                        // 次のテキストを取得
+                                    }
                                 }
-                            }
-                        } else if name == b"head" {
-                            if let Some(rend) = attr_val(&e, b"rend") {
-                                let rend_str = rend.to_ascii_lowercase();
-                                if rend_str == "book" && book.is_none() {
-                                    // 次のテキストを取得
+                            } else if name == b"head" {
+                                if let Some(rend) = attr_val(&e, b"rend") {
+                                    let rend_str = rend.to_ascii_lowercase();
+                                    if rend_str == "book" && book.is_none() {
+                                        // 次のテキストを取得
+                                    }
+                                }
+                            } else if name == b"div" {
+                                if let (Some(n), Some(type_val)) =
+                                    (attr_val(&e, b"n"), attr_val(&e, b"type"))
+                                {
+                                    structure_info.push(format!("{}({})", n, type_val));
                                 }
-                            }
-                        } else if name == b"div" {
-                            if let (Some(n), Some(type_val)) =
-                                (attr_val(&e, b"n"), attr_val(&e, b"type"))
-                            {
-                                structure_info.push(format!("{}({})", n, type_val));
                             }
                         }
-                    }
-                    Ok(Event::Text(t)) => {
-                        let text = t.decode().unwrap_or_default().into_owned();
-                        if nikaya.is_none() && text.trim().len() > 5 {
-                            nikaya = Some(text.trim().to_string());
-                        } else if book.is_none() && text.trim().len() > 3 {
-                            book = Some(text.trim().to_string());
+                        Ok(Event::Text(t)) => {
+                            let text = t.decode().unwrap_or_default().into_owned();
+                            if nikaya.is_none() && text.trim().len() > 5 {
+                                nikaya = Some(text.trim().to_string());
+                            } else if book.is_none() && text.trim().len() > 3 {
+                                book = Some(text.trim().to_string());
+                            }
                         }
+                        Ok(Event::Eof) => break,
+                        Err(_) => break,
+                        _ => {}
                     }
-                    Ok(Event::Eof) => break,
-                    Err(_) => break,
-                    _ => {}
+                    buf.clear();
+                    events += 1;
                 }
-                buf.clear();
-                events += 1;
-            }
 
-            // Convert ripgrep matches to GrepMatch
-            let grep_matches: Vec<GrepMatch> = rg_matches
-                .iter()
-                .map(|m| {
-                    let highlight = matcher
-                        .find(m.line_content.as_bytes())
-                        .ok()
-                        .flatten()
-                        .map(|mat| {
-                            m.line_content
-                                .get(mat.start()..mat.end())
-                                .unwrap_or("")
-                                .to_string()
-                        })
-                        .unwrap_or_default();
-
-                    GrepMatch {
-                        context: m.line_content.clone(),
-                        highlight,
-                        juan_number: None,
-                        section: structure_info.first().cloned(),
-                        line_number: Some(m.line_number as usize),
-                    }
-                })
-                .collect();
-
-            let file_id = stem_from(p);
-            let title = [nikaya.as_deref(), book.as_deref()]
-                .iter()
-                .filter_map(|&s| s)
-                .collect::<Vec<_>>()
-                .join(" · ");
-            let title = if title.is_empty() {
-                file_id.clone()
-            } else {
-                title
-            };
-            let total_matches = grep_matches.len();
-
-            // Fetch用ヒント
-            let fetch_hints = FetchHints {
-                recommended_parts: vec!["full".to_string()], // Tipitakaは通常全体を取得
-                total_content_size: Some(format!("{}KB", content.len() / 1024)),
-                structure_info,
-            };
+                // Convert ripgrep matches to GrepMatch
+                let grep_matches: Vec<GrepMatch> = rg_matches
+                    .iter()
+                    .map(|m| {
+                        let span = matcher
+                            .find(m.line_content.as_bytes())
+                            .ok()
+                            .flatten()
+                            .map(|mat| (mat.start(), mat.end()));
+                        // Folded matches are reported against the original line.
+                        let line = if fold_diacritics {
+                            orig_lines
+                                .get(m.line_number as usize - 1)
+                                .copied()
+                                .unwrap_or(&m.line_content)
+                        } else {
+                            &m.line_content
+                        };
+                        let highlight = span
+                            .map(|(a, b)| {
+                                let (a, b) = if fold_diacritics {
+                                    fold::original_span(line, a, b)
+                                } else {
+                                    (a, b)
+                                };
+                                line.get(a..b).unwrap_or("").to_string()
+                            })
+                            .unwrap_or_default();
+
+                        GrepMatch {
+                            context: line.to_string(),
+                            highlight,
+                            juan_number: None,
+                            section: structure_info.first().cloned(),
+                            line_number: Some(m.line_number as usize),
+                            ..Default::default()
+                        }
+                    })
+                    .collect();
 
-            Some(GrepResult {
-                file_path: p.to_string_lossy().to_string(),
-                file_id,
-                title,
-                matches: grep_matches,
-                total_matches,
-                fetch_hints,
+                let file_id = stem_from(p);
+                let title = [nikaya.as_deref(), book.as_deref()]
+                    .iter()
+                    .filter_map(|&s| s)
+                    .collect::<Vec<_>>()
+                    .join(" · ");
+                let title = if title.is_empty() {
+                    file_id.clone()
+                } else {
+                    title
+                };
+                let total_matches = grep_matches.len();
+
+                // Fetch用ヒント
+                let fetch_hints = FetchHints {
+                    recommended_parts: vec!["full".to_string()], // Tipitakaは通常全体を取得
+                    total_content_size: Some(format!("{}KB XML", content.len() / 1024)),
+                    structure_info,
+                    ..Default::default()
+                };
+
+                Some(GrepResult {
+                    file_path: p.to_string_lossy().to_string(),
+                    file_id,
+                    title,
+                    matches: grep_matches,
+                    total_matches,
+                    fetch_hints,
+                    score: None,
+                    titles: None,
+                })
             })
-        })
-        .collect::<Vec<_>>()
-        .into_iter()
-        .take(max_results)
-        .collect()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .take(max_results)
+            .collect()
+    })
 }
 
 pub fn gretil_grep(
@@ -3157,14 +3800,10 @@ pub fn gretil_grep(
     max_results: usize,
     max_matches_per_file: usize,
 ) -> Vec<GrepResult> {
+    let cancel = CancelToken::current();
     // Build ripgrep matcher (case-insensitive)
-    let matcher = match RegexMatcherBuilder::new()
-        .case_insensitive(true)
-        .multi_line(true)
-        .build(query)
-    {
-        Ok(m) => m,
-        Err(_) => return Vec::new(),
+    let Some(matcher) = QueryMatcher::new(query) else {
+        return Vec::new();
     };
 
     // Collect XML file paths using ignore crate
@@ -3172,63 +3811,20 @@ pub fn gretil_grep(
         collect_xml_paths_cached(&XML_PATHS_ALL_CACHE, root, |_, name| name.ends_with(".xml"));
 
     // Search files in parallel using ripgrep
-    paths
-        .par_iter()
-        .filter_map(|p| {
-            let rg_matches = ripgrep_search_file(p, &matcher, max_matches_per_file)?;
-
-            // Get file size without reading entire content (faster)
-            let file_size = std::fs::metadata(p).ok().map(|m| m.len()).unwrap_or(0);
-
-            // Convert ripgrep matches to GrepMatch
-            let grep_matches: Vec<GrepMatch> = rg_matches
-                .iter()
-                .map(|m| {
-                    let highlight = matcher
-                        .find(m.line_content.as_bytes())
-                        .ok()
-                        .flatten()
-                        .map(|mat| {
-                            m.line_content
-                                .get(mat.start()..mat.end())
-                                .unwrap_or("")
-                                .to_string()
-                        })
-                        .unwrap_or_default();
-
-                    GrepMatch {
-                        context: m.line_content.clone(),
-                        highlight,
-                        juan_number: None,
-                        section: None,
-                        line_number: Some(m.line_number as usize),
-                    }
-                })
-                .collect();
-
-            let file_id = stem_from(p);
-            let title = file_id.clone();
-            let total_matches = grep_matches.len();
-
-            let fetch_hints = FetchHints {
-                recommended_parts: vec!["full".to_string()],
-                total_content_size: Some(format!("{}KB", file_size / 1024)),
-                structure_info: Vec::new(),
-            };
-
-            Some(GrepResult {
-                file_path: p.to_string_lossy().to_string(),
-                file_id,
-                title,
-                matches: grep_matches,
-                total_matches,
-                fetch_hints,
+    scan::run(|| {
+        paths
+            .par_iter()
+            .filter_map(|p| {
+                if cancel.is_cancelled() {
+                    return None;
+                }
+                grep_file(p, &matcher, max_matches_per_file, &["full"])
             })
-        })
-        .collect::<Vec<_>>()
-        .into_iter()
-        .take(max_results)
-        .collect()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .take(max_results)
+            .collect()
+    })
 }
 
 pub fn sarit_grep(
@@ -3237,68 +3833,26 @@ pub fn sarit_grep(
     max_results: usize,
     max_matches_per_file: usize,
 ) -> Vec<GrepResult> {
-    let matcher = match RegexMatcherBuilder::new()
-        .case_insensitive(true)
-        .multi_line(true)
-        .build(query)
-    {
-        Ok(m) => m,
-        Err(_) => return Vec::new(),
+    let cancel = CancelToken::current();
+    let Some(matcher) = QueryMatcher::new(query) else {
+        return Vec::new();
     };
 
     let paths = collect_xml_paths_cached(&SARIT_XML_PATHS_CACHE, root, |path, name| {
         is_sarit_xml(path, name)
     });
 
-    let mut results: Vec<GrepResult> = paths
-        .par_iter()
-        .filter_map(|p| {
-            let rg_matches = ripgrep_search_file(p, &matcher, max_matches_per_file)?;
-            let file_size = std::fs::metadata(p).ok().map(|m| m.len()).unwrap_or(0);
-
-            let grep_matches: Vec<GrepMatch> = rg_matches
-                .iter()
-                .map(|m| {
-                    let highlight = matcher
-                        .find(m.line_content.as_bytes())
-                        .ok()
-                        .flatten()
-                        .map(|mat| {
-                            m.line_content
-                                .get(mat.start()..mat.end())
-                                .unwrap_or("")
-                                .to_string()
-                        })
-                        .unwrap_or_default();
-
-                    GrepMatch {
-                        context: m.line_content.clone(),
-                        highlight,
-                        juan_number: None,
-                        section: None,
-                        line_number: Some(m.line_number as usize),
-                    }
-                })
-                .collect();
-
-            let file_id = stem_from(p);
-            let title = file_id.clone();
-            let total_matches = grep_matches.len();
-            let fetch_hints = FetchHints {
-                recommended_parts: vec!["full".to_string()],
-                total_content_size: Some(format!("{}KB", file_size / 1024)),
-                structure_info: Vec::new(),
-            };
-            Some(GrepResult {
-                file_path: p.to_string_lossy().to_string(),
-                file_id,
-                title,
-                matches: grep_matches,
-                total_matches,
-                fetch_hints,
+    let mut results: Vec<GrepResult> = scan::run(|| {
+        paths
+            .par_iter()
+            .filter_map(|p| {
+                if cancel.is_cancelled() {
+                    return None;
+                }
+                grep_file(p, &matcher, max_matches_per_file, &["full"])
             })
-        })
-        .collect::<Vec<_>>();
+            .collect::<Vec<_>>()
+    });
 
     grep_sort_best_first(&mut results, max_results);
     results
@@ -3310,68 +3864,26 @@ pub fn muktabodha_grep(
     max_results: usize,
     max_matches_per_file: usize,
 ) -> Vec<GrepResult> {
-    let matcher = match RegexMatcherBuilder::new()
-        .case_insensitive(true)
-        .multi_line(true)
-        .build(query)
-    {
-        Ok(m) => m,
-        Err(_) => return Vec::new(),
+    let cancel = CancelToken::current();
+    let Some(matcher) = QueryMatcher::new(query) else {
+        return Vec::new();
     };
 
     let paths = collect_xml_paths_cached(&MUKTABODHA_PATHS_CACHE, root, |path, name| {
         is_muktabodha_file(path, name)
     });
 
-    let mut results: Vec<GrepResult> = paths
-        .par_iter()
-        .filter_map(|p| {
-            let rg_matches = ripgrep_search_file(p, &matcher, max_matches_per_file)?;
-            let file_size = std::fs::metadata(p).ok().map(|m| m.len()).unwrap_or(0);
-
-            let grep_matches: Vec<GrepMatch> = rg_matches
-                .iter()
-                .map(|m| {
-                    let highlight = matcher
-                        .find(m.line_content.as_bytes())
-                        .ok()
-                        .flatten()
-                        .map(|mat| {
-                            m.line_content
-                                .get(mat.start()..mat.end())
-                                .unwrap_or("")
-                                .to_string()
-                        })
-                        .unwrap_or_default();
-
-                    GrepMatch {
-                        context: m.line_content.clone(),
-                        highlight,
-                        juan_number: None,
-                        section: None,
-                        line_number: Some(m.line_number as usize),
-                    }
-                })
-                .collect();
-
-            let file_id = stem_from(p);
-            let title = file_id.clone();
-            let total_matches = grep_matches.len();
-            let fetch_hints = FetchHints {
-                recommended_parts: vec!["full".to_string()],
-                total_content_size: Some(format!("{}KB", file_size / 1024)),
-                structure_info: Vec::new(),
-            };
-            Some(GrepResult {
-                file_path: p.to_string_lossy().to_string(),
-                file_id,
-                title,
-                matches: grep_matches,
-                total_matches,
-                fetch_hints,
+    let mut results: Vec<GrepResult> = scan::run(|| {
+        paths
+            .par_iter()
+            .filter_map(|p| {
+                if cancel.is_cancelled() {
+                    return None;
+                }
+                grep_file(p, &matcher, max_matches_per_file, &["full"])
             })
-        })
-        .collect();
+            .collect()
+    });
 
     grep_sort_best_first(&mut results, max_results);
     results
@@ -3392,7 +3904,38 @@ mod tests_gretil {
         fs::write(&p, xml).unwrap();
         let results = gretil_grep(dir.path(), "kṛṣṇa", 10, 3);
         assert_eq!(results.len(), 1);
-        assert!(results[0].matches.len() >= 1);
+        assert!(!results[0].matches.is_empty());
+    }
+
+    #[test]
+    fn cancelled_grep_stops_early() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.xml"), "<p>kṛṣṇa</p>").unwrap();
+        let token = CancelToken::new();
+        token.cancel();
+        assert!(token
+            .scope(|| gretil_grep(dir.path(), "kṛṣṇa", 10, 3))
+            .is_empty());
+        assert_eq!(gretil_grep(dir.path(), "kṛṣṇa", 10, 3).len(), 1);
+    }
+
+    #[test]
+    fn scoped_grep_only_reads_the_listed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for id in ["T09n0262", "T08n0251", "X01n0001"] {
+            fs::write(dir.path().join(format!("{}.xml", id)), "<p>如是我聞</p>").unwrap();
+        }
+        let scope = [
+            dir.path().join("X01n0001.xml"),
+            dir.path().join("T09n0262.xml"),
+        ];
+        let ids: Vec<String> = cbeta_grep_scoped(&scope, "如是", 10, 3)
+            .into_iter()
+            .map(|r| r.file_id)
+            .collect();
+        assert_eq!(ids, ["T09n0262", "X01n0001"]);
+        assert_eq!(grep_scoped(&scope[..1], "如是", 10, 3).len(), 1);
+        assert!(cbeta_grep_scoped(&[], "如是", 10, 3).is_empty());
     }
 }
 
@@ -3423,12 +3966,120 @@ mod tests_cbeta_index_and_plain {
         assert_eq!(idx.len(), 1);
         assert_eq!(idx[0].id, "T0001");
         assert_eq!(idx[0].title, "妙法蓮華經");
+    }
+
+    #[test]
+    fn build_cbeta_index_stamps_the_index_version() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("T0001.xml"),
+            "<TEI><text><body><p>如是</p></body></text></TEI>",
+        )
+        .unwrap();
+        let idx = build_cbeta_index(dir.path());
         let ver = idx[0]
             .meta
             .as_ref()
             .and_then(|m| m.get("indexVersion"))
             .map(|s| s.as_str());
-        assert_eq!(ver, Some("cbeta_index_v2"));
+        assert_eq!(ver, Some("cbeta_index_v7"));
+    }
+
+    #[test]
+    fn build_cbeta_index_records_file_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let xml = "<TEI><text><body><p>如是我聞</p></body></text></TEI>\n";
+        fs::write(dir.path().join("T0001.xml"), xml).unwrap();
+        let idx = build_cbeta_index(dir.path());
+        assert_eq!(
+            idx[0].meta.as_ref().unwrap()["fileSize"],
+            xml.len().to_string()
+        );
+    }
+
+    #[test]
+    fn build_cbeta_index_records_text_length() {
+        let dir = tempfile::tempdir().unwrap();
+        let xml = "<TEI><teiHeader><title>經</title></teiHeader><text><body><p>須彌山</p><note>註</note></body></text></TEI>";
+        fs::write(dir.path().join("T0001.xml"), xml).unwrap();
+        let idx = build_cbeta_index(dir.path());
+        assert_eq!(idx[0].meta.as_ref().unwrap()["textChars"], "3");
+    }
+
+    #[test]
+    fn build_cbeta_index_keeps_romanized_title_from_collection_title() {
+        let dir = tempfile::tempdir().unwrap();
+        let xml = r#"<TEI xml:id="T0262"><teiHeader><fileDesc><titleStmt>
+<title xml:lang="en">Taishō Tripiṭaka, Electronic version, No. 0262 Miaofa lianhua jing</title>
+<title xml:lang="zh">妙法蓮華經</title>
+</titleStmt></fileDesc></teiHeader><text><body><p>如是</p></body></text></TEI>"#;
+        fs::write(dir.path().join("T0262.xml"), xml).unwrap();
+        let idx = build_cbeta_index(dir.path());
+        assert_eq!(idx[0].title, "妙法蓮華經");
+        let meta = idx[0].meta.as_ref().unwrap();
+        assert_eq!(meta["titleLatn"], "Miaofa lianhua jing");
+        assert!(!meta.contains_key("titleEn"));
+    }
+
+    #[test]
+    fn list_mulu_cbeta_reads_toc_levels_and_section_bounds() {
+        let xml = r#"<TEI><text><body>
+<cb:juan n="001" fun="open"><cb:jhead>卷第一</cb:jhead></cb:juan>
+<cb:mulu type="卷" n="1"/>
+<lb n="0001a01"/><cb:mulu type="品" n="1" level="1">序品</cb:mulu><p>如是我聞</p>
+<cb:mulu type="分" level="2">別序</cb:mulu><p>一時佛住</p>
+<lb n="0002a01"/><cb:mulu type="品" n="2" level="1">方便品</cb:mulu><p>爾時世尊</p>
+</body></text></TEI>"#;
+        let toc = list_mulu_cbeta(xml);
+        let titles: Vec<&str> = toc.iter().map(|m| m.title.as_str()).collect();
+        assert_eq!(titles, vec!["序品", "別序", "方便品"]);
+        assert_eq!(toc[0].level, 1);
+        assert_eq!(toc[0].n.as_deref(), Some("1"));
+        assert_eq!(toc[0].kind.as_deref(), Some("品"));
+        assert_eq!(toc[0].lb.as_deref(), Some("0001a01"));
+        assert_eq!(toc[0].juan.as_deref(), Some("001"));
+        assert_eq!(toc[1].level, 2);
+        // a level-1 section spans its sub-sections up to the next level-1 entry
+        let first = &xml[toc[0].start..toc[0].end];
+        assert!(first.contains("如是我聞") && first.contains("一時佛住"));
+        assert!(!first.contains("爾時世尊"));
+        assert!(!xml[toc[1].start..toc[1].end].contains("方便品"));
+        assert!(xml[toc[2].start..toc[2].end].contains("爾時世尊"));
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("T0262.xml"), xml).unwrap();
+        let idx = build_cbeta_index(dir.path());
+        let meta = idx[0].meta.as_ref().unwrap();
+        assert_eq!(meta.get("muluCount").map(|s| s.as_str()), Some("3"));
+        assert_eq!(
+            meta.get("muluPreview").map(|s| s.as_str()),
+            Some("序品 | 別序 | 方便品")
+        );
+    }
+
+    #[test]
+    fn cbeta_grep_notes_matches_note_text_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let xml = r#"<TEI><text><body>
+<lb n="0001a01"/>如是我聞<note n="0001001" type="orig">聞＝問【宋】</note>
+<lb n="0001a02"/>宋本云何
+<note place="inline">按宋本作問
+字</note>
+</body><back><note n="0001001" type="orig">聞＝問【宋】</note></back></text></TEI>"#;
+        fs::write(dir.path().join("T0001.xml"), xml).unwrap();
+        let notes = list_cbeta_notes(xml);
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0].line, 2);
+        assert_eq!(notes[1].text, "按宋本作問 字");
+
+        let r = cbeta_grep_notes(dir.path(), "宋", 10, 10);
+        assert_eq!(r.len(), 1);
+        assert_eq!(r[0].total_matches, 2);
+        assert_eq!(r[0].matches[0].section.as_deref(), Some("note:orig"));
+        assert_eq!(r[0].matches[0].line_number, Some(2));
+        assert_eq!(r[0].matches[1].section.as_deref(), Some("note:note"));
+        // 宋本云何 is main text, and 如是 appears only outside notes
+        assert!(cbeta_grep_notes(dir.path(), "如是", 10, 10).is_empty());
     }
 
     #[test]
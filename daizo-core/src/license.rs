@@ -0,0 +1,248 @@
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::Serialize;
+use std::io::Read;
+use std::path::Path;
+
+/// Licensing and attribution terms of a corpus, attached to fetched and exported text so
+/// that downstream use can honour them.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorpusLicense {
+    pub corpus: String,
+    /// Publisher / collection name.
+    pub name: &'static str,
+    /// License or terms-of-use label.
+    pub license: &'static str,
+    pub url: &'static str,
+    /// Credit line to reproduce alongside quoted text.
+    pub attribution: &'static str,
+    /// Whether commercial reuse is permitted; None when it depends on the file.
+    pub commercial_use: Option<bool>,
+    /// `<availability>` / `<licence>` statement from the file's TEI header.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub availability: Option<String>,
+    /// `<sourceDesc>` statement (print edition, input by) from the file's TEI header.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_statement: Option<String>,
+}
+
+/// (corpus, name, license, url, attribution, commercial use)
+type Terms = (
+    &'static str,
+    &'static str,
+    &'static str,
+    &'static str,
+    &'static str,
+    Option<bool>,
+);
+
+static TERMS: &[Terms] = &[
+    (
+        "cbeta",
+        "CBETA Chinese Electronic Tripitaka Collection (Chinese Buddhist Electronic Text Association)",
+        "CC BY-NC-SA 3.0 TW",
+        "https://www.cbeta.org/copyright",
+        "Text from the CBETA Chinese Electronic Tripitaka Collection, Chinese Buddhist Electronic Text Association (CBETA).",
+        Some(false),
+    ),
+    (
+        "tipitaka",
+        "Chaṭṭha Saṅgāyana Tipiṭaka (Vipassana Research Institute)",
+        "VRI terms: free distribution only, no sale or modification",
+        "https://tipitaka.org/",
+        "Chaṭṭha Saṅgāyana Tipiṭaka, © Vipassana Research Institute.",
+        Some(false),
+    ),
+    (
+        "gretil",
+        "GRETIL – Göttingen Register of Electronic Texts in Indian Languages",
+        "per file (see availability / sourceStatement); research and teaching use",
+        "https://gretil.sub.uni-goettingen.de/",
+        "E-text made available by GRETIL (SUB Göttingen); credit the input source named in the file.",
+        None,
+    ),
+    (
+        "sarit",
+        "SARIT – Search and Retrieval of Indic Texts",
+        "per file (see availability)",
+        "https://sarit.indology.info/",
+        "E-text from the SARIT corpus; credit the editors named in the file.",
+        None,
+    ),
+    (
+        "muktabodha",
+        "Muktabodha Digital Library (Muktabodha Indological Research Institute)",
+        "Muktabodha terms of use: personal study and research",
+        "https://www.muktabodha.org/",
+        "E-text from the Muktabodha Digital Library, Muktabodha Indological Research Institute.",
+        Some(false),
+    ),
+    (
+        "sat",
+        "SAT Daizōkyō Text Database (University of Tokyo)",
+        "SAT terms of use: non-commercial, cite SAT",
+        "https://21dzk.l.u-tokyo.ac.jp/SAT/",
+        "SAT Daizōkyō Text Database, The University of Tokyo.",
+        Some(false),
+    ),
+    (
+        "jozen",
+        "浄土宗全書テキストデータベース (Jōdo Shū Research Institute)",
+        "Jōdo Shū Research Institute terms of use",
+        "https://jodoshuzensho.jp/",
+        "浄土宗全書テキストデータベース, 浄土宗総合研究所.",
+        Some(false),
+    ),
+];
+
+/// Corpus-level terms for a corpus key (`cbeta`, `tipitaka`, `gretil`, `sarit`,
+/// `muktabodha`, `sat`, `jozen`).
+pub fn corpus_license(corpus: &str) -> Option<CorpusLicense> {
+    let (corpus, name, license, url, attribution, commercial_use) =
+        TERMS.iter().find(|t| t.0 == corpus)?;
+    Some(CorpusLicense {
+        corpus: corpus.to_string(),
+        name,
+        license,
+        url,
+        attribution,
+        commercial_use: *commercial_use,
+        availability: None,
+        source_statement: None,
+    })
+}
+
+/// Corpus terms plus the availability and source statements of one TEI file.
+pub fn file_license(corpus: &str, xml: &str) -> Option<CorpusLicense> {
+    let mut lic = corpus_license(corpus)?;
+    let (availability, source) = tei_header_statements(xml);
+    lic.availability = availability;
+    lic.source_statement = source;
+    Some(lic)
+}
+
+/// Bytes read from the start of a file when looking for its TEI header.
+const HEADER_READ_LIMIT: u64 = 64 * 1024;
+
+/// `file_license` for the file at `path`, reading only its head (TEI headers come first).
+pub fn license_for_path(corpus: &str, path: &Path) -> Option<CorpusLicense> {
+    let mut buf = Vec::new();
+    if let Ok(f) = std::fs::File::open(path) {
+        let _ = f.take(HEADER_READ_LIMIT).read_to_end(&mut buf);
+    }
+    file_license(corpus, &String::from_utf8_lossy(&buf))
+}
+
+/// Maximum length (chars) kept of each header statement.
+const STATEMENT_MAX: usize = 600;
+
+fn tidy(s: &str) -> Option<String> {
+    let t = s.split_whitespace().collect::<Vec<_>>().join(" ");
+    if t.is_empty() {
+        return None;
+    }
+    Some(match t.char_indices().nth(STATEMENT_MAX) {
+        Some((i, _)) => format!("{}…", &t[..i]),
+        None => t,
+    })
+}
+
+/// Text of the `<availability>` and `<sourceDesc>` elements of a TEI header.
+/// Reading stops at `</teiHeader>`, so only the header is scanned.
+pub fn tei_header_statements(xml: &str) -> (Option<String>, Option<String>) {
+    let mut reader = Reader::from_str(xml);
+    let (mut avail, mut source) = (String::new(), String::new());
+    let (mut in_avail, mut in_source) = (0usize, 0usize);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = e.name();
+                let local = name.local_name();
+                match local.as_ref() {
+                    b"availability" => in_avail += 1,
+                    b"sourceDesc" => in_source += 1,
+                    _ => {}
+                }
+                // Separate block children so their texts do not run together.
+                if matches!(
+                    local.as_ref(),
+                    b"p" | b"ab" | b"bibl" | b"licence" | b"note"
+                ) {
+                    if in_avail > 0 {
+                        avail.push(' ');
+                    }
+                    if in_source > 0 {
+                        source.push(' ');
+                    }
+                }
+                if in_avail > 0 && local.as_ref() == b"licence" {
+                    if let Some(t) = crate::attr_val(&e, b"target") {
+                        avail.push_str(&t);
+                        avail.push(' ');
+                    }
+                }
+            }
+            Ok(Event::End(e)) => {
+                let name = e.name();
+                match name.local_name().as_ref() {
+                    b"availability" => in_avail = in_avail.saturating_sub(1),
+                    b"sourceDesc" => in_source = in_source.saturating_sub(1),
+                    b"teiHeader" => break,
+                    _ => {}
+                }
+            }
+            // <licence target="..."/> carries the licence URL only as an attribute.
+            Ok(Event::Empty(e)) if in_avail > 0 && e.name().local_name().as_ref() == b"licence" => {
+                if let Some(t) = crate::attr_val(&e, b"target") {
+                    avail.push(' ');
+                    avail.push_str(&t);
+                }
+            }
+            Ok(Event::Text(t)) => {
+                let s = t.decode().map(|c| c.into_owned()).unwrap_or_default();
+                if in_avail > 0 {
+                    avail.push_str(&s);
+                }
+                if in_source > 0 {
+                    source.push_str(&s);
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    (tidy(&avail), tidy(&source))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_statements_are_attached() {
+        let xml = r#"<TEI><teiHeader><fileDesc>
+            <publicationStmt><availability status="restricted">
+              <licence target="https://creativecommons.org/licenses/by-nc-sa/4.0/"/>
+              <p>Licensed for   non-commercial use.</p>
+            </availability></publicationStmt>
+            <sourceDesc><bibl>Ed. by <editor>P. L. Vaidya</editor>, Darbhanga 1961</bibl></sourceDesc>
+          </fileDesc></teiHeader>
+          <text><body><availability>not header</availability></body></text></TEI>"#;
+        let lic = file_license("gretil", xml).unwrap();
+        assert_eq!(
+            lic.availability.as_deref(),
+            Some("https://creativecommons.org/licenses/by-nc-sa/4.0/ Licensed for non-commercial use.")
+        );
+        assert_eq!(
+            lic.source_statement.as_deref(),
+            Some("Ed. by P. L. Vaidya, Darbhanga 1961")
+        );
+        assert_eq!(lic.commercial_use, None);
+        assert_eq!(
+            corpus_license("cbeta").unwrap().license,
+            "CC BY-NC-SA 3.0 TW"
+        );
+        assert!(corpus_license("unknown").is_none());
+    }
+}
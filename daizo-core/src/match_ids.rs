@@ -0,0 +1,220 @@
+//! Stable identifiers for search matches.
+//!
+//! [`assign`] gives every match an id derived from its file and the byte offset of the
+//! matched text, and returns a [`MatchRef`] for each. A server keeps these in a [`Registry`]
+//! so that a follow-up fetch can name the match instead of repeating `id` and `lineNumber`.
+//! [`locate`] reads the file again at that point and finds the matched text by offset, so a
+//! line renumbered by an update of the corpus is followed rather than trusted.
+
+use crate::bundle::hash_text;
+use crate::{read_file_with_encoding, GrepResult};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Default number of ids a [`Registry`] remembers.
+pub const DEFAULT_CAPACITY: usize = 20_000;
+
+/// Where a match was found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchRef {
+    pub corpus: String,
+    pub file_id: String,
+    pub path: PathBuf,
+    /// Byte offset of the matched text in the (decoded) file.
+    pub offset: usize,
+    pub highlight: String,
+}
+
+/// `m` followed by 12 hex digits of SHA-1 over the file id and offset.
+pub fn match_id(file_id: &str, offset: usize) -> String {
+    format!(
+        "m{}",
+        &hash_text(&format!("{}\u{0}{}", file_id, offset))[..12]
+    )
+}
+
+/// Byte offset at which each line starts (index 0 is line 1).
+fn line_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+/// Set `match_id` on every match of a `corpus` search that has a line number and return
+/// their references. Each result's file is read once.
+pub fn assign(corpus: &str, results: &mut [GrepResult]) -> Vec<(String, MatchRef)> {
+    let mut out = Vec::new();
+    for r in results.iter_mut() {
+        let path = PathBuf::from(&r.file_path);
+        let Some(text) = read_file_with_encoding(&path) else {
+            continue;
+        };
+        let starts = line_starts(&text);
+        for m in r.matches.iter_mut() {
+            let Some(start) = m.line_number.and_then(|n| starts.get(n.checked_sub(1)?)) else {
+                continue;
+            };
+            // Notes may report their first line while the text sits further on. Text found in
+            // a plain shadow may not occur in the XML as such (a resolved gaiji); the match is
+            // then kept by its line alone.
+            let found = (!m.highlight.is_empty())
+                .then(|| text[*start..].find(m.highlight.as_str()))
+                .flatten();
+            let (offset, highlight) = match found {
+                Some(i) => (start + i, m.highlight.clone()),
+                None => (*start, String::new()),
+            };
+            let id = match_id(&r.file_id, offset);
+            m.match_id = Some(id.clone());
+            out.push((
+                id,
+                MatchRef {
+                    corpus: corpus.to_string(),
+                    file_id: r.file_id.clone(),
+                    path: path.clone(),
+                    offset,
+                    highlight,
+                },
+            ));
+        }
+    }
+    out
+}
+
+/// Where a registered match is now.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Located {
+    pub line_number: usize,
+    pub offset: usize,
+    /// The text was no longer at the recorded offset and the nearest occurrence was taken.
+    pub moved: bool,
+}
+
+/// Find `r` in the current file: at its offset, else the occurrence of its text nearest to
+/// the offset. None when the file or the text is gone.
+pub fn locate(r: &MatchRef) -> Option<Located> {
+    let text = read_file_with_encoding(&r.path)?;
+    locate_in(&text, r)
+}
+
+fn locate_in(text: &str, r: &MatchRef) -> Option<Located> {
+    let at = |o: usize| text.get(o..).is_some_and(|t| t.starts_with(&r.highlight));
+    let (offset, moved) = if at(r.offset) {
+        (r.offset, false)
+    } else if r.highlight.is_empty() {
+        return None;
+    } else {
+        let nearest = text
+            .match_indices(r.highlight.as_str())
+            .map(|(i, _)| i)
+            .min_by_key(|i| i.abs_diff(r.offset))?;
+        (nearest, true)
+    };
+    let line_number = line_starts(text).partition_point(|s| *s <= offset);
+    Some(Located {
+        line_number,
+        offset,
+        moved,
+    })
+}
+
+/// Ids handed out in this process, oldest forgotten first.
+pub struct Registry {
+    capacity: usize,
+    inner: Mutex<(HashMap<String, MatchRef>, VecDeque<String>)>,
+}
+
+impl Registry {
+    pub fn new(capacity: usize) -> Self {
+        Registry {
+            capacity,
+            inner: Mutex::new((HashMap::new(), VecDeque::new())),
+        }
+    }
+
+    pub fn insert(&self, refs: Vec<(String, MatchRef)>) {
+        let mut g = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let (map, order) = &mut *g;
+        for (id, r) in refs {
+            if map.insert(id.clone(), r).is_none() {
+                order.push_back(id);
+            }
+        }
+        while order.len() > self.capacity {
+            if let Some(old) = order.pop_front() {
+                map.remove(&old);
+            }
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<MatchRef> {
+        let g = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        g.0.get(id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GrepMatch;
+
+    #[test]
+    fn ids_follow_the_text_when_lines_move() {
+        let dir = tempfile::tempdir().unwrap();
+        let p = dir.path().join("T08n0251.xml");
+        std::fs::write(
+            &p,
+            "<lb n=\"0848c06\"/>觀自在菩薩\n<lb n=\"0848c07\"/>舍利子色不異空\n",
+        )
+        .unwrap();
+        let mut results = vec![GrepResult {
+            file_path: p.to_string_lossy().into(),
+            file_id: "T08n0251".into(),
+            matches: vec![GrepMatch {
+                context: "<lb n=\"0848c07\"/>舍利子色不異空".into(),
+                highlight: "舍利子".into(),
+                line_number: Some(2),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }];
+        let refs = assign("cbeta", &mut results);
+        assert_eq!(refs.len(), 1);
+        let (id, r) = &refs[0];
+        assert_eq!(results[0].matches[0].match_id.as_deref(), Some(id.as_str()));
+        assert_eq!(*id, match_id("T08n0251", r.offset));
+        assert_eq!(
+            r.offset,
+            "<lb n=\"0848c06\"/>觀自在菩薩\n<lb n=\"0848c07\"/>".len()
+        );
+
+        let reg = Registry::new(1);
+        reg.insert(refs.clone());
+        assert_eq!(reg.get(id).as_ref(), Some(r));
+        assert_eq!(
+            locate(r),
+            Some(Located {
+                line_number: 2,
+                offset: r.offset,
+                moved: false
+            })
+        );
+
+        // A line inserted above: the text is found again one line down.
+        std::fs::write(
+            &p,
+            "<pb n=\"0848c\"/>\n<lb n=\"0848c06\"/>觀自在菩薩\n<lb n=\"0848c07\"/>舍利子色不異空\n",
+        )
+        .unwrap();
+        let l = locate(r).unwrap();
+        assert_eq!((l.line_number, l.moved), (3, true));
+
+        std::fs::write(&p, "gone\n").unwrap();
+        assert_eq!(locate(r), None);
+
+        // Capacity 1: a second id pushes the first one out.
+        reg.insert(vec![("m2".into(), r.clone())]);
+        assert!(reg.get(id).is_none());
+    }
+}
@@ -0,0 +1,153 @@
+//! The matcher the `*_grep` functions search with.
+//!
+//! Most queries are literal phrases, or close to it: a CBETA query becomes a sequence of
+//! characters and small variant classes (`[經経经]`), and case-insensitivity turns a Pāli word
+//! into a handful of spellings. Such a pattern matches exactly a finite set of strings, and
+//! [`QueryMatcher::new`] searches for that set with Aho-Corasick instead of the regex engine.
+//! The literal matcher also tells the searcher that it never matches across a line break, which
+//! lets it scan a whole file at once rather than line by line. Anything else (repetition,
+//! `\s*`, anchors, large classes) keeps the case-insensitive regex matcher.
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use grep_matcher::{LineTerminator, Match, Matcher, NoCaptures, NoError};
+use grep_regex::{RegexMatcher, RegexMatcherBuilder};
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+
+/// Most strings a pattern may stand for and still be searched as literals.
+const MAX_LITERALS: usize = 256;
+
+/// Aho-Corasick over the strings a literal pattern matches.
+#[derive(Clone, Debug)]
+pub struct LiteralMatcher {
+    ac: AhoCorasick,
+    multi_line: bool,
+}
+
+impl LiteralMatcher {
+    /// The literal matcher equivalent to `pattern` compiled case-insensitively, when it
+    /// matches a finite set of non-empty strings and has no anchors or word boundaries.
+    pub fn new(pattern: &str) -> Option<Self> {
+        let hir = regex_syntax::ParserBuilder::new()
+            .case_insensitive(true)
+            .multi_line(true)
+            .build()
+            .parse(pattern)
+            .ok()?;
+        // Assertions come out of the extractor as empty literals, which would match anywhere.
+        if !hir.properties().look_set().is_empty() {
+            return None;
+        }
+        let seq = Extractor::new()
+            .kind(ExtractKind::Prefix)
+            .limit_total(MAX_LITERALS)
+            .extract(&hir);
+        let lits = seq.literals()?;
+        if lits.is_empty() || !seq.is_exact() || lits.iter().any(|l| l.as_bytes().is_empty()) {
+            return None;
+        }
+        let multi_line = lits.iter().any(|l| l.as_bytes().contains(&b'\n'));
+        // Leftmost-first over the literals in preference order is what the regex reports.
+        let ac = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostFirst)
+            .build(lits.iter().map(|l| l.as_bytes()))
+            .ok()?;
+        Some(LiteralMatcher { ac, multi_line })
+    }
+}
+
+/// A grep query compiled for the searcher.
+#[derive(Clone, Debug)]
+pub enum QueryMatcher {
+    Literal(LiteralMatcher),
+    Regex(RegexMatcher),
+}
+
+impl QueryMatcher {
+    /// `query` as the grep functions search it: case-insensitive, `^`/`$` at line ends. None
+    /// when it does not compile.
+    pub fn new(query: &str) -> Option<Self> {
+        if let Some(l) = LiteralMatcher::new(query) {
+            return Some(QueryMatcher::Literal(l));
+        }
+        RegexMatcherBuilder::new()
+            .case_insensitive(true)
+            .multi_line(true)
+            .build(query)
+            .ok()
+            .map(QueryMatcher::Regex)
+    }
+
+    /// `literal` or `regex`.
+    pub fn engine(&self) -> &'static str {
+        match self {
+            QueryMatcher::Literal(_) => "literal",
+            QueryMatcher::Regex(_) => "regex",
+        }
+    }
+}
+
+impl Matcher for QueryMatcher {
+    type Captures = NoCaptures;
+    type Error = NoError;
+
+    fn find_at(&self, haystack: &[u8], at: usize) -> Result<Option<Match>, NoError> {
+        match self {
+            QueryMatcher::Literal(l) => Ok(l
+                .ac
+                .find(aho_corasick::Input::new(haystack).span(at..haystack.len()))
+                .map(|m| Match::new(m.start(), m.end()))),
+            QueryMatcher::Regex(r) => r.find_at(haystack, at),
+        }
+    }
+
+    fn new_captures(&self) -> Result<NoCaptures, NoError> {
+        Ok(NoCaptures::new())
+    }
+
+    fn line_terminator(&self) -> Option<LineTerminator> {
+        match self {
+            QueryMatcher::Literal(l) if !l.multi_line => Some(LineTerminator::byte(b'\n')),
+            QueryMatcher::Literal(_) => None,
+            QueryMatcher::Regex(r) => r.line_terminator(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found<'a>(m: &QueryMatcher, hay: &'a str) -> Option<&'a str> {
+        m.find(hay.as_bytes())
+            .unwrap()
+            .map(|x| &hay[x.start()..x.end()])
+    }
+
+    #[test]
+    fn literal_patterns_skip_the_regex_engine() {
+        let cjk = QueryMatcher::new("般若").unwrap();
+        assert_eq!(cjk.engine(), "literal");
+        assert_eq!(found(&cjk, "摩訶般若波羅蜜"), Some("般若"));
+
+        let variants = QueryMatcher::new("[經経经]典").unwrap();
+        assert_eq!(variants.engine(), "literal");
+        assert_eq!(found(&variants, "大乘経典"), Some("経典"));
+
+        // Case-insensitive like the regex, Unicode letters included.
+        let pali = QueryMatcher::new("evaṃ").unwrap();
+        assert_eq!(pali.engine(), "literal");
+        assert_eq!(found(&pali, "EVAṂ me sutaṃ"), Some("EVAṂ"));
+        assert_eq!(found(&pali, "Evaṃ"), Some("Evaṃ"));
+
+        // Escaped metacharacters are literal too; the first alternative wins as in the regex.
+        let alt = QueryMatcher::new(r"a\.b|a").unwrap();
+        assert_eq!(alt.engine(), "literal");
+        assert_eq!(found(&alt, "xa.b"), Some("a.b"));
+
+        for re in [r"色\s*即", "^空", r"\bsutta", "a+", "."] {
+            assert_eq!(QueryMatcher::new(re).unwrap().engine(), "regex", "{re}");
+        }
+        assert!(QueryMatcher::new("(").is_none());
+        assert!(cjk.line_terminator().is_some());
+    }
+}
@@ -0,0 +1,365 @@
+//! Opt-in per-tool metrics.
+//!
+//! With `DAIZO_METRICS=1` (or `[metrics] enabled = true`) the MCP server appends one
+//! [`CallRecord`] per tool call to `~/.daizo/metrics/requests.jsonl`: the tool, its latency,
+//! the size of the text it returned, whether it failed, and the hits and misses of the caches
+//! it consulted (index, search results, prefetched juans, remote pages). [`summarize`] folds
+//! records into per-tool call counts, latency percentiles, output sizes and cache hit rates,
+//! which `daizo_metrics` and `daizo-cli metrics` report to help tune `DAIZO_MCP_MAX_CHARS`
+//! and the caches.
+//!
+//! Cache lookups are counted on the thread running the call, the way [`crate::timing`]
+//! counts phases; outside [`record`] [`cache_hit`] and [`cache_miss`] cost nothing.
+
+use crate::path_resolver::daizo_home;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// The log is moved to `requests.1.jsonl` (replacing the previous one) beyond this size.
+pub const MAX_LOG_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Hits and misses of one cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheCount {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// One tool call.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallRecord {
+    /// Unix time the call finished, in seconds.
+    pub ts: u64,
+    pub tool: String,
+    pub ms: f64,
+    /// UTF-8 bytes and characters of the text content returned.
+    pub bytes: usize,
+    pub chars: usize,
+    #[serde(default)]
+    pub error: bool,
+    /// Cache lookups by cache (`index`, `grep`, `prefetch`, `remote`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub cache: BTreeMap<String, CacheCount>,
+}
+
+thread_local! {
+    static CACHE: RefCell<Option<BTreeMap<&'static str, CacheCount>>> = const { RefCell::new(None) };
+}
+
+fn count(kind: &'static str, hit: bool) {
+    CACHE.with(|c| {
+        if let Some(m) = c.borrow_mut().as_mut() {
+            let e = m.entry(kind).or_default();
+            if hit {
+                e.hits += 1;
+            } else {
+                e.misses += 1;
+            }
+        }
+    });
+}
+
+/// Count a lookup of `kind` that was answered from the cache.
+pub fn cache_hit(kind: &'static str) {
+    count(kind, true)
+}
+
+/// Count a lookup of `kind` that had to do the work.
+pub fn cache_miss(kind: &'static str) {
+    count(kind, false)
+}
+
+/// Run `f`, collecting the cache lookups made on this thread meanwhile.
+pub fn record<R>(f: impl FnOnce() -> R) -> (R, BTreeMap<String, CacheCount>) {
+    let prev = CACHE.with(|c| c.borrow_mut().replace(BTreeMap::new()));
+    let r = f();
+    let counts = CACHE.with(|c| std::mem::replace(&mut *c.borrow_mut(), prev));
+    let counts = counts
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    (r, counts)
+}
+
+/// Where the request log lives by default: `~/.daizo/metrics`.
+pub fn default_dir() -> PathBuf {
+    daizo_home().join("metrics")
+}
+
+fn log_path(dir: &Path) -> PathBuf {
+    dir.join("requests.jsonl")
+}
+
+fn rotated_path(dir: &Path) -> PathBuf {
+    dir.join("requests.1.jsonl")
+}
+
+/// Append `rec` to the request log under `dir`, rotating it once it grows past
+/// [`MAX_LOG_BYTES`]. Lines are written whole, so several servers can share the log.
+pub fn append(dir: &Path, rec: &CallRecord) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let path = log_path(dir);
+    if std::fs::metadata(&path).is_ok_and(|m| m.len() > MAX_LOG_BYTES) {
+        std::fs::rename(&path, rotated_path(dir))?;
+    }
+    let mut line = serde_json::to_vec(rec)?;
+    line.push(b'\n');
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?
+        .write_all(&line)
+}
+
+/// Records of the request log under `dir`, oldest first (the rotated file included);
+/// unreadable lines are skipped.
+pub fn read_log(dir: &Path) -> Vec<CallRecord> {
+    let mut out = Vec::new();
+    for path in [rotated_path(dir), log_path(dir)] {
+        let Ok(f) = std::fs::File::open(&path) else {
+            continue;
+        };
+        out.extend(
+            std::io::BufReader::new(f)
+                .lines()
+                .map_while(Result::ok)
+                .filter_map(|l| serde_json::from_str::<CallRecord>(&l).ok()),
+        );
+    }
+    out
+}
+
+/// Remove the request log.
+pub fn clear(dir: &Path) -> std::io::Result<()> {
+    for path in [rotated_path(dir), log_path(dir)] {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Hit rate of one cache over a set of calls.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheSummary {
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+}
+
+/// Aggregate of one tool's calls.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolSummary {
+    pub tool: String,
+    pub calls: usize,
+    pub errors: usize,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+    pub total_bytes: usize,
+    pub mean_chars: usize,
+    pub max_chars: usize,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub cache: BTreeMap<String, CacheSummary>,
+}
+
+/// Aggregate of a set of calls: per tool (most called first) and per cache.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Summary {
+    pub calls: usize,
+    /// Unix times of the first and last call.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<u64>,
+    pub tools: Vec<ToolSummary>,
+    pub cache: BTreeMap<String, CacheSummary>,
+}
+
+fn cache_summary(counts: &BTreeMap<String, CacheCount>) -> BTreeMap<String, CacheSummary> {
+    counts
+        .iter()
+        .map(|(k, c)| {
+            let n = c.hits + c.misses;
+            let rate = if n == 0 {
+                0.0
+            } else {
+                round3(c.hits as f64 / n as f64)
+            };
+            (
+                k.clone(),
+                CacheSummary {
+                    hits: c.hits,
+                    misses: c.misses,
+                    hit_rate: rate,
+                },
+            )
+        })
+        .collect()
+}
+
+fn round3(x: f64) -> f64 {
+    (x * 1000.0).round() / 1000.0
+}
+
+/// Nearest-rank percentile of sorted values.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn add_counts(into: &mut BTreeMap<String, CacheCount>, from: &BTreeMap<String, CacheCount>) {
+    for (k, c) in from {
+        let e = into.entry(k.clone()).or_default();
+        e.hits += c.hits;
+        e.misses += c.misses;
+    }
+}
+
+/// Fold `records` into per-tool and per-cache figures.
+pub fn summarize(records: &[CallRecord]) -> Summary {
+    let mut by_tool: BTreeMap<&str, Vec<&CallRecord>> = BTreeMap::new();
+    for r in records {
+        by_tool.entry(r.tool.as_str()).or_default().push(r);
+    }
+    let mut all_cache = BTreeMap::new();
+    let mut tools: Vec<ToolSummary> = by_tool
+        .into_iter()
+        .map(|(tool, recs)| {
+            let mut ms: Vec<f64> = recs.iter().map(|r| r.ms).collect();
+            ms.sort_by(f64::total_cmp);
+            let mut cache = BTreeMap::new();
+            for r in &recs {
+                add_counts(&mut cache, &r.cache);
+            }
+            add_counts(&mut all_cache, &cache);
+            let n = recs.len();
+            ToolSummary {
+                tool: tool.to_string(),
+                calls: n,
+                errors: recs.iter().filter(|r| r.error).count(),
+                mean_ms: round3(ms.iter().sum::<f64>() / n as f64),
+                p50_ms: percentile(&ms, 50.0),
+                p95_ms: percentile(&ms, 95.0),
+                max_ms: ms.last().copied().unwrap_or_default(),
+                total_bytes: recs.iter().map(|r| r.bytes).sum(),
+                mean_chars: recs.iter().map(|r| r.chars).sum::<usize>() / n,
+                max_chars: recs.iter().map(|r| r.chars).max().unwrap_or_default(),
+                cache: cache_summary(&cache),
+            }
+        })
+        .collect();
+    tools.sort_by(|a, b| b.calls.cmp(&a.calls).then_with(|| a.tool.cmp(&b.tool)));
+    Summary {
+        calls: records.len(),
+        since: records.iter().map(|r| r.ts).min(),
+        until: records.iter().map(|r| r.ts).max(),
+        tools,
+        cache: cache_summary(&all_cache),
+    }
+}
+
+/// Plain-text table of a summary, one line per tool, for `daizo_metrics` and the CLI.
+pub fn render(s: &Summary) -> String {
+    if s.calls == 0 {
+        return "no calls recorded\n".to_string();
+    }
+    let mut out = format!(
+        "{:<28} {:>6} {:>4} {:>9} {:>9} {:>9} {:>10} {:>10}\n",
+        "tool", "calls", "err", "p50 ms", "p95 ms", "max ms", "mean chars", "max chars"
+    );
+    for t in &s.tools {
+        out.push_str(&format!(
+            "{:<28} {:>6} {:>4} {:>9.1} {:>9.1} {:>9.1} {:>10} {:>10}\n",
+            t.tool, t.calls, t.errors, t.p50_ms, t.p95_ms, t.max_ms, t.mean_chars, t.max_chars
+        ));
+    }
+    let caches: Vec<String> = s
+        .cache
+        .iter()
+        .map(|(k, c)| {
+            format!(
+                "{} {:.0}% ({}/{})",
+                k,
+                c.hit_rate * 100.0,
+                c.hits,
+                c.hits + c.misses
+            )
+        })
+        .collect();
+    if !caches.is_empty() {
+        out.push_str(&format!("cache hits: {}\n", caches.join(", ")));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_fold_into_tool_and_cache_figures() {
+        let ((), counts) = record(|| {
+            cache_hit("index");
+            cache_miss("grep");
+            cache_hit("index");
+        });
+        assert_eq!(counts["index"], CacheCount { hits: 2, misses: 0 });
+        assert_eq!(counts["grep"], CacheCount { hits: 0, misses: 1 });
+        // Outside a recording nothing is kept.
+        cache_hit("index");
+        assert!(record(|| ()).1.is_empty());
+
+        let dir = tempfile::tempdir().unwrap();
+        let call = |tool: &str, ms: f64, chars: usize, error: bool| CallRecord {
+            ts: 100 + ms as u64,
+            tool: tool.to_string(),
+            ms,
+            bytes: chars * 3,
+            chars,
+            error,
+            cache: counts.clone(),
+        };
+        for rec in [
+            call("cbeta_fetch", 10.0, 8000, false),
+            call("cbeta_fetch", 30.0, 4000, false),
+            call("cbeta_fetch", 20.0, 6000, false),
+            call("cbeta_search", 5.0, 100, true),
+        ] {
+            append(dir.path(), &rec).unwrap();
+        }
+        let recs = read_log(dir.path());
+        assert_eq!(recs.len(), 4);
+        let s = summarize(&recs);
+        assert_eq!((s.calls, s.since, s.until), (4, Some(105), Some(130)));
+        let fetch = &s.tools[0];
+        assert_eq!(fetch.tool, "cbeta_fetch");
+        assert_eq!((fetch.calls, fetch.errors), (3, 0));
+        assert_eq!(
+            (fetch.p50_ms, fetch.p95_ms, fetch.max_ms),
+            (20.0, 30.0, 30.0)
+        );
+        assert_eq!((fetch.mean_chars, fetch.max_chars), (6000, 8000));
+        assert_eq!(fetch.cache["index"].hit_rate, 1.0);
+        assert_eq!(s.tools[1].errors, 1);
+        assert_eq!(s.cache["grep"].misses, 4);
+        assert_eq!(s.cache["index"].hits, 8);
+        assert!(render(&s).contains("index 100% (8/8)"));
+
+        clear(dir.path()).unwrap();
+        assert!(read_log(dir.path()).is_empty());
+    }
+}
@@ -0,0 +1,107 @@
+//! Several search patterns in one corpus walk.
+//!
+//! The grep functions take one pattern, so [`combined`] joins the patterns into an alternation
+//! that is walked once; [`PatternSet::tag`] then runs a `RegexSet` over each matched line to
+//! record which of the patterns it holds.
+
+use crate::pattern::{validate, PatternError};
+use crate::GrepResult;
+use regex::{RegexSet, RegexSetBuilder};
+
+/// One alternation matching any of `patterns`.
+pub fn combined(patterns: &[String]) -> String {
+    patterns
+        .iter()
+        .map(|p| format!("(?:{})", p))
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// The patterns of a multi-pattern search, compiled the way the grep functions compile one
+/// (case-insensitive, multi-line).
+pub struct PatternSet {
+    set: RegexSet,
+}
+
+impl PatternSet {
+    /// Compile `patterns`; the error names the first one that does not compile.
+    pub fn new(patterns: &[String]) -> Result<Self, (usize, PatternError)> {
+        for (i, p) in patterns.iter().enumerate() {
+            validate(p).map_err(|e| (i, e))?;
+        }
+        RegexSetBuilder::new(patterns)
+            .case_insensitive(true)
+            .multi_line(true)
+            .build()
+            .map(|set| PatternSet { set })
+            .map_err(|e| {
+                (
+                    0,
+                    PatternError {
+                        message: e.to_string(),
+                        position: None,
+                        suggestion: None,
+                    },
+                )
+            })
+    }
+
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.len() == 0
+    }
+
+    /// Set `patterns` on every match to the indexes of the patterns its line matches.
+    pub fn tag(&self, results: &mut [GrepResult]) {
+        for r in results.iter_mut() {
+            for m in r.matches.iter_mut() {
+                m.patterns = self.set.matches(&m.context).into_iter().collect();
+            }
+        }
+    }
+
+    /// Matches of each pattern among the tagged matches of `r`.
+    pub fn counts(&self, r: &GrepResult) -> Vec<usize> {
+        let mut c = vec![0; self.len()];
+        for i in r.matches.iter().flat_map(|m| &m.patterns) {
+            if let Some(n) = c.get_mut(*i) {
+                *n += 1;
+            }
+        }
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_walk_reports_the_patterns_of_each_hit() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("T08n0235.xml"),
+            "<p>般若波羅蜜</p>\n<p>色即是空</p>\n<p>般若皆空</p>\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("T09n0262.xml"), "<p>妙法蓮華</p>\n").unwrap();
+        let patterns = vec!["般若".to_string(), "空".to_string(), "蓮華".to_string()];
+        let set = PatternSet::new(&patterns).unwrap();
+        let mut rs = crate::cbeta_grep(dir.path(), &combined(&patterns), 10, 10);
+        set.tag(&mut rs);
+        rs.sort_by(|a, b| a.file_id.cmp(&b.file_id));
+        assert_eq!(rs.len(), 2);
+        let tags: Vec<&[usize]> = rs[0].matches.iter().map(|m| &m.patterns[..]).collect();
+        assert_eq!(tags, [&[0][..], &[1], &[0, 1]]);
+        assert_eq!(set.counts(&rs[0]), [2, 2, 0]);
+        assert_eq!(set.counts(&rs[1]), [0, 0, 1]);
+
+        let (i, e) = PatternSet::new(&["空".to_string(), "如(是".to_string()])
+            .err()
+            .unwrap();
+        assert_eq!((i, e.position), (1, Some(1)));
+    }
+}
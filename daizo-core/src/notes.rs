@@ -0,0 +1,202 @@
+//! `<note>` elements of a TEI file, listed apart from the text they annotate.
+//!
+//! The extractors drop notes (or inline them with `includeNotes`); [`list_notes`] returns them
+//! with where they apply instead. A note applies either where it stands or, when its `target`
+//! names an `<anchor xml:id>`, at that anchor — CBETA keeps the Taishō footnotes in the back
+//! matter that way. Each note is sorted into a [`NoteCategory`] from its `place` and `type`.
+
+use crate::{attr_val, local_name};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// What a note is: inline (a gloss or small-print comment in the running text), apparatus
+/// (a textual footnote: variant readings, emendations) or editorial (everything else).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteCategory {
+    Inline,
+    Apparatus,
+    Editorial,
+}
+
+impl NoteCategory {
+    pub const NAMES: &'static [&'static str] = &["inline", "apparatus", "editorial"];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "inline" => Some(NoteCategory::Inline),
+            "apparatus" => Some(NoteCategory::Apparatus),
+            "editorial" => Some(NoteCategory::Editorial),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NoteCategory::Inline => "inline",
+            NoteCategory::Apparatus => "apparatus",
+            NoteCategory::Editorial => "editorial",
+        }
+    }
+
+    /// The category of a note with `type` `kind` and `place`. CBETA's `orig` (Taishō) and
+    /// `mod` (CBETA's revision of it) notes are the apparatus, as are footnotes in general.
+    fn of(kind: Option<&str>, place: Option<&str>) -> Self {
+        let place = place.unwrap_or_default().to_ascii_lowercase();
+        let kind = kind.unwrap_or_default().to_ascii_lowercase();
+        if place.contains("inline") || place.contains("interlinear") {
+            NoteCategory::Inline
+        } else if matches!(
+            kind.as_str(),
+            "orig" | "mod" | "var" | "variant" | "app" | "textual" | "critical"
+        ) || place.contains("foot")
+        {
+            NoteCategory::Apparatus
+        } else {
+            NoteCategory::Editorial
+        }
+    }
+}
+
+/// A `<note>` and the place it annotates.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Note {
+    pub n: Option<String>,
+    /// `type` attribute (CBETA: orig, mod, add …).
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub place: Option<String>,
+    pub category: NoteCategory,
+    /// Whitespace-normalized text, nested markup flattened.
+    pub text: String,
+    /// 1-based XML line of the note itself.
+    pub line: usize,
+    /// 1-based XML line of the annotated place: the `target` anchor, else the note.
+    pub anchor_line: usize,
+    /// Byte offset of the annotated place in the XML.
+    pub offset: usize,
+    /// The last `<lb n>` before the annotated place.
+    pub lb: Option<String>,
+}
+
+/// The outermost `<note>` elements of `xml` in the order of the places they annotate, empty
+/// ones left out.
+pub fn list_notes(xml: &str) -> Vec<Note> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().check_end_names = false;
+    let mut buf = Vec::new();
+    let mut out: Vec<(Note, Option<String>)> = Vec::new();
+    // xml:id of each anchor → its offset, line and lb.
+    let mut anchors: HashMap<String, (usize, usize, Option<String>)> = HashMap::new();
+    let mut lb: Option<String> = None;
+    let mut depth = 0usize;
+    let mut line = 1usize;
+    let mut line_pos = 0usize;
+    loop {
+        let pos = reader.buffer_position() as usize;
+        line += xml.as_bytes()[line_pos..pos.min(xml.len())]
+            .iter()
+            .filter(|b| **b == b'\n')
+            .count();
+        line_pos = pos.min(xml.len());
+        let ev = reader.read_event_into(&mut buf);
+        match &ev {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                let is_start = matches!(ev, Ok(Event::Start(_)));
+                let name_owned = e.name().as_ref().to_owned();
+                let name = local_name(&name_owned);
+                if depth > 0 {
+                    depth += usize::from(is_start);
+                } else if name == b"note" && is_start {
+                    depth = 1;
+                    let kind = attr_val(e, b"type").map(|v| v.into_owned());
+                    let place = attr_val(e, b"place").map(|v| v.into_owned());
+                    let target = attr_val(e, b"target")
+                        .map(|v| v.trim().trim_start_matches('#').to_string());
+                    out.push((
+                        Note {
+                            n: attr_val(e, b"n").map(|v| v.into_owned()),
+                            category: NoteCategory::of(kind.as_deref(), place.as_deref()),
+                            kind,
+                            place,
+                            text: String::new(),
+                            line,
+                            anchor_line: line,
+                            offset: pos,
+                            lb: lb.clone(),
+                        },
+                        target,
+                    ));
+                } else if name == b"lb" {
+                    if let Some(n) = attr_val(e, b"n") {
+                        lb = Some(n.into_owned());
+                    }
+                } else if name == b"anchor" {
+                    if let Some(id) = attr_val(e, b"xml:id") {
+                        anchors.insert(id.into_owned(), (pos, line, lb.clone()));
+                    }
+                }
+            }
+            Ok(Event::Text(t)) if depth > 0 => {
+                if let Some((n, _)) = out.last_mut() {
+                    n.text.push_str(&t.decode().unwrap_or_default());
+                }
+            }
+            Ok(Event::End(_)) if depth > 0 => depth -= 1,
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    let mut notes: Vec<Note> = out
+        .into_iter()
+        .filter_map(|(mut n, target)| {
+            n.text = n.text.split_whitespace().collect::<Vec<_>>().join(" ");
+            if let Some((offset, line, lb)) = target.and_then(|t| anchors.get(&t)) {
+                (n.offset, n.anchor_line, n.lb) = (*offset, *line, lb.clone());
+            }
+            (!n.text.is_empty()).then_some(n)
+        })
+        .collect();
+    notes.sort_by_key(|n| n.offset);
+    notes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_get_their_anchor_and_category() {
+        let xml = r##"<TEI><text><body>
+<p><lb n="0001a01"/>如是我聞<anchor xml:id="nkr_note_orig_0001001" n="0001001"/>一時
+<note place="inline">秦言<hi>善</hi>哉</note>
+<lb n="0001a02"/>佛在<note type="add" n="0001a0201">CBETA 按</note>舍衛國</p>
+</body><back>
+<note n="0001001" resp="Taisho" place="foot text" type="orig" target="#nkr_note_orig_0001001">一時＝一時佛【宋】</note>
+<note type="orig"> </note>
+</back></text></TEI>"##;
+        let notes = list_notes(xml);
+        let got: Vec<(&str, usize, usize, Option<&str>)> = notes
+            .iter()
+            .map(|n| (n.category.as_str(), n.line, n.anchor_line, n.lb.as_deref()))
+            .collect();
+        assert_eq!(
+            got,
+            [
+                ("apparatus", 6, 2, Some("0001a01")),
+                ("inline", 3, 3, Some("0001a01")),
+                ("editorial", 4, 4, Some("0001a02")),
+            ]
+        );
+        assert_eq!(notes[1].text, "秦言善哉");
+        assert!(xml[notes[0].offset..].starts_with("<anchor"));
+        assert_eq!(
+            NoteCategory::parse("Apparatus"),
+            Some(NoteCategory::Apparatus)
+        );
+    }
+}
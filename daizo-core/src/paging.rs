@@ -0,0 +1,206 @@
+//! Paging by structural unit (`pageBy`).
+//!
+//! Character pages cut sentences and verses at arbitrary points. These are the byte ranges of
+//! a TEI/CBETA file's headed sections, juans or paragraphs instead; together they cover the
+//! body without gaps, so a page of units never starts or ends inside one.
+
+use crate::juan_map::JuanMap;
+use regex::Regex;
+use serde::Serialize;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageBy {
+    Head,
+    Juan,
+    Paragraph,
+}
+
+impl PageBy {
+    pub const NAMES: &'static [&'static str] = &["head", "juan", "paragraph"];
+
+    pub fn parse(s: &str) -> Option<PageBy> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "head" => Some(PageBy::Head),
+            "juan" => Some(PageBy::Juan),
+            "paragraph" => Some(PageBy::Paragraph),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PageBy::Head => "head",
+            PageBy::Juan => "juan",
+            PageBy::Paragraph => "paragraph",
+        }
+    }
+}
+
+/// One unit: a byte range of the XML and, for sections and juans, its title.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Unit {
+    pub start: usize,
+    pub end: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+fn head_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?is)<head\b[^>]*>(.*?)</head>").unwrap())
+}
+
+fn para_end_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)</(?:p|lg)\s*>").unwrap())
+}
+
+fn tag_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"<[^>]*>").unwrap())
+}
+
+/// `<body>`…`</body>`, or the whole text when there is no body element.
+fn body(xml: &str) -> (usize, usize) {
+    let start = xml.find("<body").unwrap_or(0);
+    let end = xml
+        .rfind("</body>")
+        .map(|i| i + "</body>".len())
+        .unwrap_or(xml.len());
+    (start, end.max(start))
+}
+
+/// Cut `start..end` at `cuts` (ascending, inside the range) into contiguous units.
+fn cut(start: usize, end: usize, cuts: Vec<(usize, Option<String>)>) -> Vec<Unit> {
+    let mut out: Vec<Unit> = Vec::new();
+    let mut from = (start, None);
+    for (at, title) in cuts {
+        if at > from.0 {
+            out.push(Unit {
+                start: from.0,
+                end: at,
+                title: from.1,
+            });
+        }
+        from = (at, title);
+    }
+    if end > from.0 {
+        out.push(Unit {
+            start: from.0,
+            end,
+            title: from.1,
+        });
+    }
+    out
+}
+
+/// The units of `xml`, in document order. Empty without any `<head>` or paragraph; a text
+/// without juan markers is one juan, as in [`JuanMap`].
+pub fn units(xml: &str, by: PageBy) -> Vec<Unit> {
+    let (start, end) = body(xml);
+    match by {
+        PageBy::Head => {
+            let heads: Vec<(usize, Option<String>)> = head_re()
+                .captures_iter(&xml[start..end])
+                .map(|c| {
+                    let title = tag_re().replace_all(&c[1], "");
+                    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+                    (start + c.get(0).unwrap().start(), Some(title))
+                })
+                .collect();
+            if heads.is_empty() {
+                return Vec::new();
+            }
+            let mut out = cut(start, end, heads);
+            // Front matter before the first heading only counts when it has text.
+            if out.first().is_some_and(|u| {
+                u.title.is_none()
+                    && tag_re()
+                        .replace_all(&xml[u.start..u.end], "")
+                        .trim()
+                        .is_empty()
+            }) && out.len() > 1
+            {
+                let front = out.remove(0);
+                out[0].start = front.start;
+            }
+            out
+        }
+        PageBy::Juan => {
+            let map = JuanMap::build(xml);
+            let cuts: Vec<(usize, Option<String>)> = map
+                .juans
+                .iter()
+                .map(|j| {
+                    let at = j.milestone.unwrap_or(j.start).min(j.start);
+                    let title = j.title.clone().unwrap_or_else(|| format!("卷{}", j.n));
+                    (at, Some(title))
+                })
+                .collect();
+            if cuts.is_empty() {
+                return Vec::new();
+            }
+            // The text before the first juan marker belongs to the first juan.
+            let mut out = cut(start, end, cuts);
+            if out.len() > 1 && out[0].title.is_none() {
+                let front = out.remove(0);
+                out[0].start = front.start;
+            }
+            out
+        }
+        PageBy::Paragraph => {
+            let mut ends: Vec<usize> = para_end_re()
+                .find_iter(&xml[start..end])
+                .map(|m| start + m.end())
+                .collect();
+            if ends.is_empty() {
+                return Vec::new();
+            }
+            // What follows the last paragraph (closing tags, a trailer) stays with it.
+            ends.pop();
+            cut(start, end, ends.into_iter().map(|e| (e, None)).collect())
+        }
+    }
+}
+
+/// Byte range of page `page` (0-based) of `page_size` units; None past the last page.
+pub fn page_range(units: &[Unit], page: usize, page_size: usize) -> Option<(usize, usize)> {
+    let first = page.checked_mul(page_size.max(1))?;
+    let last = (first + page_size.max(1)).min(units.len());
+    (first < last).then(|| (units[first].start, units[last - 1].end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = "<TEI><teiHeader><title>T</title></teiHeader><text><body>\
+<div><head>First <hi>one</hi></head><p>a.</p><lg><l>b</l></lg></div>\
+<div><head>Second</head><p>c.</p><p>d.</p></div></body></text></TEI>";
+
+    #[test]
+    fn units_cover_the_body_without_gaps() {
+        let heads = units(XML, PageBy::Head);
+        let titles: Vec<_> = heads.iter().map(|u| u.title.as_deref()).collect();
+        assert_eq!(titles, vec![Some("First one"), Some("Second")]);
+        assert_eq!(heads[0].start, XML.find("<body").unwrap());
+        assert_eq!(heads[0].end, heads[1].start);
+        assert!(XML[heads[1].start..].starts_with("<head>Second"));
+        assert!(XML[..heads[1].end].ends_with("</body>"));
+
+        let paras = units(XML, PageBy::Paragraph);
+        assert_eq!(paras.len(), 4);
+        assert!(XML[paras[0].start..paras[0].end].ends_with("<p>a.</p>"));
+        assert!(XML[paras[1].start..paras[1].end].ends_with("</lg>"));
+        assert!(XML[..paras[3].end].ends_with("</body>"));
+        assert_eq!(
+            page_range(&paras, 1, 2),
+            Some((paras[2].start, paras[3].end))
+        );
+        assert_eq!(page_range(&paras, 2, 2), None);
+
+        assert_eq!(units(XML, PageBy::Juan).len(), 1);
+        assert_eq!(PageBy::parse("Head"), Some(PageBy::Head));
+    }
+}
@@ -0,0 +1,421 @@
+//! Canonical Pāli citations (`SN 56.11`, `MN 10`, `Dhp 183`, `Sn 1.8`) resolved to a VRI
+//! file and the lines of the sutta or verse they name.
+//!
+//! [`parse`] reads a citation into a [`PaliRef`], whose [`PaliRef::file_stem`] picks the VRI
+//! volume by number. [`locate`] then finds the section in that file from its numbered
+//! headings (`1. Brahmajālasuttaṃ`, `12. Saccasaṃyuttaṃ`) or, for verses, the paragraph
+//! numbers (`<p rend="gatha1" n="183">`). Volumes that restart their numbering (the
+//! saṃyuttas of each SN vagga) are tried both ways.
+
+use crate::fold::fold_diacritics;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// How the numbers of a citation address a book.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Numbering {
+    /// `MN 10`: one number, the sutta.
+    Sutta,
+    /// `SN 56.11`: a chapter (a heading containing the marker), then a sutta in it.
+    Chapters(&'static str),
+    /// `AN 4.10`: the first number picks the volume, the second the sutta in it.
+    Volumes,
+    /// `Dhp 183`: one number is a verse; `Sn 1.8` is a chapter and a sutta.
+    Verses(&'static str),
+    /// `KN`: a collection whose books are cited by their own abbreviations.
+    Collection,
+}
+
+/// A book that can be cited: its canonical abbreviation, other names, how its citations
+/// number it and its VRI volumes (first number of each).
+struct Book {
+    code: &'static str,
+    names: &'static [&'static str],
+    numbering: Numbering,
+    files: &'static [(u32, &'static str)],
+}
+
+impl PartialEq for Book {
+    fn eq(&self, other: &Self) -> bool {
+        self.code == other.code
+    }
+}
+
+impl Eq for Book {}
+
+impl std::fmt::Debug for Book {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.code)
+    }
+}
+
+const BOOKS: &[Book] = &[
+    Book {
+        code: "DN",
+        names: &["dn", "digha", "dighanikaya"],
+        numbering: Numbering::Sutta,
+        files: &[(1, "s0101m.mul"), (14, "s0102m.mul"), (24, "s0103m.mul")],
+    },
+    Book {
+        code: "MN",
+        names: &["mn", "majjhima", "majjhimanikaya"],
+        numbering: Numbering::Sutta,
+        files: &[(1, "s0201m.mul"), (51, "s0202m.mul"), (101, "s0203m.mul")],
+    },
+    Book {
+        code: "SN",
+        names: &["sn", "samyutta", "samyuttanikaya"],
+        numbering: Numbering::Chapters("samyutt"),
+        files: &[
+            (1, "s0301m.mul"),
+            (12, "s0302m.mul"),
+            (22, "s0303m.mul"),
+            (35, "s0304m.mul"),
+            (45, "s0305m.mul"),
+        ],
+    },
+    Book {
+        code: "AN",
+        names: &["an", "anguttara", "anguttaranikaya"],
+        numbering: Numbering::Volumes,
+        files: &[
+            (1, "s0401m.mul"),
+            (2, "s0402m1.mul"),
+            (3, "s0402m2.mul"),
+            (4, "s0402m3.mul"),
+            (5, "s0403m1.mul"),
+            (6, "s0403m2.mul"),
+            (7, "s0403m3.mul"),
+            (8, "s0404m1.mul"),
+            (9, "s0404m2.mul"),
+            (10, "s0404m3.mul"),
+            (11, "s0404m4.mul"),
+        ],
+    },
+    Book {
+        code: "KN",
+        names: &["kn", "khuddaka", "khuddakanikaya"],
+        numbering: Numbering::Collection,
+        files: &[(1, "s0501m.mul")],
+    },
+    Book {
+        code: "Khp",
+        names: &["khp", "kp", "khuddakapatha"],
+        numbering: Numbering::Sutta,
+        files: &[(1, "s0501m.mul")],
+    },
+    Book {
+        code: "Dhp",
+        names: &["dhp", "dhammapada"],
+        numbering: Numbering::Verses("vagg"),
+        files: &[(1, "s0502m.mul")],
+    },
+    Book {
+        code: "Ud",
+        names: &["ud", "udana"],
+        numbering: Numbering::Chapters("vagg"),
+        files: &[(1, "s0503m.mul")],
+    },
+    Book {
+        code: "Iti",
+        names: &["iti", "it", "itivuttaka"],
+        numbering: Numbering::Sutta,
+        files: &[(1, "s0504m.mul")],
+    },
+    Book {
+        code: "Snp",
+        names: &["snp", "suttanipata"],
+        numbering: Numbering::Verses("vagg"),
+        files: &[(1, "s0505m.mul")],
+    },
+    Book {
+        code: "Thag",
+        names: &["thag", "theragatha"],
+        numbering: Numbering::Verses("nipat"),
+        files: &[(1, "s0508m.mul")],
+    },
+    Book {
+        code: "Thig",
+        names: &["thig", "therigatha"],
+        numbering: Numbering::Verses("nipat"),
+        files: &[(1, "s0509m.mul")],
+    },
+];
+
+/// A parsed citation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaliRef {
+    book: &'static Book,
+    /// Sutta or verse (`MN 10`), or chapter and sutta (`SN 56.11`); empty for the book.
+    pub numbers: Vec<u32>,
+}
+
+impl PaliRef {
+    /// Canonical abbreviation of the book (`SN`, `Dhp`, `Snp`).
+    pub fn book(&self) -> &'static str {
+        self.book.code
+    }
+
+    /// The citation written the usual way: `SN 56.11`.
+    pub fn canonical(&self) -> String {
+        let nums: Vec<String> = self.numbers.iter().map(u32::to_string).collect();
+        if nums.is_empty() {
+            self.book.code.to_string()
+        } else {
+            format!("{} {}", self.book.code, nums.join("."))
+        }
+    }
+
+    /// Whether the citation names a verse (`Dhp 183`) rather than a sutta.
+    pub fn is_verse(&self) -> bool {
+        matches!(self.book.numbering, Numbering::Verses(_)) && self.numbers.len() == 1
+    }
+
+    /// VRI file stem of the volume the citation falls in (`s0305m.mul` for SN 56.11).
+    pub fn file_stem(&self) -> &'static str {
+        let key = match self.book.numbering {
+            Numbering::Sutta | Numbering::Chapters(_) | Numbering::Volumes => {
+                self.numbers.first().copied().unwrap_or(1)
+            }
+            Numbering::Verses(_) | Numbering::Collection => 1,
+        };
+        self.volume(key).1
+    }
+
+    /// First number and stem of the volume holding `key`.
+    fn volume(&self, key: u32) -> (u32, &'static str) {
+        self.book
+            .files
+            .iter()
+            .rev()
+            .find(|(first, _)| *first <= key)
+            .or(self.book.files.first())
+            .copied()
+            .unwrap_or((1, ""))
+    }
+}
+
+/// Canonical abbreviation of the book a VRI volume belongs to (`s0201m.mul` → `MN`).
+pub fn book_for_stem(stem: &str) -> Option<&'static str> {
+    BOOKS
+        .iter()
+        .filter(|b| b.numbering != Numbering::Collection)
+        .find(|b| b.files.iter().any(|(_, f)| *f == stem))
+        .map(|b| b.code)
+}
+
+/// Read a citation: a book abbreviation or name (`SN`, `Saṃyutta`, `Dhp`), then up to two
+/// numbers separated by `.` or `:`. Case does not matter, except that `Sn` is the Sutta
+/// Nipāta and `SN`/`sn` the Saṃyutta Nikāya, as in most citations.
+pub fn parse(s: &str) -> Option<PaliRef> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"^\s*([A-Za-z]+)\.?\s*(?:(\d+)(?:\s*[.:]\s*(\d+))?)?\s*$").unwrap()
+    });
+    let folded = fold_diacritics(s).replace('\u{a0}', " ");
+    let caps = re.captures(folded.trim())?;
+    let name = &caps[1];
+    let lower = name.to_ascii_lowercase();
+    let book = if name == "Sn" {
+        BOOKS.iter().find(|b| b.code == "Snp")?
+    } else {
+        BOOKS.iter().find(|b| b.names.contains(&lower.as_str()))?
+    };
+    let numbers: Vec<u32> = [caps.get(2), caps.get(3)]
+        .into_iter()
+        .flatten()
+        .filter_map(|m| m.as_str().parse().ok())
+        .collect();
+    if numbers.contains(&0) {
+        return None;
+    }
+    Some(PaliRef { book, numbers })
+}
+
+/// The lines of a file that a citation names.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Locus {
+    /// First line (1-based): the sutta's heading or the verse's first line.
+    pub line: usize,
+    /// Number of lines up to the next section or verse.
+    pub lines: usize,
+    /// The heading of the section, or of the verse's chapter.
+    pub head: Option<String>,
+}
+
+/// A heading or numbered paragraph on a line of the file.
+#[derive(Debug)]
+enum Mark {
+    Head(String),
+    Para(u32),
+}
+
+fn marks(xml: &str) -> Vec<(usize, Mark)> {
+    static HEAD: OnceLock<Regex> = OnceLock::new();
+    static PARA: OnceLock<Regex> = OnceLock::new();
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let head = HEAD.get_or_init(|| Regex::new(r"(?s)<head\b[^>]*>(.*?)</head>").unwrap());
+    let para = PARA.get_or_init(|| Regex::new(r#"<p\b[^>]*\bn="(\d+)""#).unwrap());
+    let tag = TAG.get_or_init(|| Regex::new(r"<[^>]+>").unwrap());
+    let mut out = Vec::new();
+    for (i, l) in xml.lines().enumerate() {
+        for c in head.captures_iter(l) {
+            let text = tag.replace_all(&c[1], "");
+            out.push((
+                i + 1,
+                Mark::Head(text.split_whitespace().collect::<Vec<_>>().join(" ")),
+            ));
+        }
+        if let Some(n) = para.captures(l).and_then(|c| c[1].parse().ok()) {
+            out.push((i + 1, Mark::Para(n)));
+        }
+    }
+    out
+}
+
+/// Number range a heading starts with: `12. Saccasaṃyuttaṃ` → 12..=12, `7-10. …` → 7..=10.
+fn head_number(text: &str) -> Option<(u32, u32)> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"^\s*(\d+)(?:\s*[-–]\s*(\d+))?\s*\.").unwrap());
+    let c = re.captures(text)?;
+    let a: u32 = c[1].parse().ok()?;
+    let b = c.get(2).and_then(|m| m.as_str().parse().ok()).unwrap_or(a);
+    Some((a, b.max(a)))
+}
+
+/// Whether a heading groups suttas (a vagga, saṃyutta, nipāta, paṇṇāsa) instead of being one.
+fn is_group(text: &str, marker: Option<&str>) -> bool {
+    let f = fold_diacritics(text).to_lowercase();
+    if f.contains("sutta") {
+        return false;
+    }
+    match marker {
+        Some(m) => f.contains(m),
+        None => ["vagg", "samyutt", "nipat", "pannas"]
+            .iter()
+            .any(|m| f.contains(m)),
+    }
+}
+
+/// The numbered heading for `n` among `marks[range]` that passes `want`, and where its
+/// section ends: at the next numbered heading or the end of the range.
+fn find_head(
+    marks: &[(usize, Mark)],
+    range: std::ops::Range<usize>,
+    n: u32,
+    want: impl Fn(&str) -> bool,
+) -> Option<(usize, usize)> {
+    let start = range.clone().find(|&i| match &marks[i].1 {
+        Mark::Head(t) => head_number(t).is_some_and(|(a, b)| a <= n && n <= b) && want(t),
+        Mark::Para(_) => false,
+    })?;
+    let end = (start + 1..range.end)
+        .find(|&i| matches!(&marks[i].1, Mark::Head(t) if head_number(t).is_some()))
+        .unwrap_or(range.end);
+    Some((start, end))
+}
+
+/// Find the section or verse `r` names in `xml`, the text of its volume.
+pub fn locate(xml: &str, r: &PaliRef) -> Option<Locus> {
+    let marks = marks(xml);
+    let total_lines = xml.lines().count();
+    let line_of = |i: usize| marks.get(i).map(|m| m.0).unwrap_or(total_lines + 1);
+    let locus = |start: usize, end: usize| {
+        let line = marks[start].0;
+        let head = match &marks[start].1 {
+            Mark::Head(t) => Some(t.clone()),
+            Mark::Para(_) => (0..start).rev().find_map(|i| match &marks[i].1 {
+                Mark::Head(t) => Some(t.clone()),
+                Mark::Para(_) => None,
+            }),
+        };
+        Some(Locus {
+            line,
+            lines: line_of(end).saturating_sub(line).max(1),
+            head,
+        })
+    };
+    let all = 0..marks.len();
+    // A number as the volume numbers it, first as the book does, then from 1 again.
+    let local = |n: u32| {
+        let (first, _) = r.volume(n);
+        [Some(n), (first > 1).then(|| n - first + 1)]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<u32>>()
+    };
+    let sutta_in = |range: std::ops::Range<usize>, ns: &[u32]| {
+        ns.iter()
+            .find_map(|&n| find_head(&marks, range.clone(), n, |t| !is_group(t, None)))
+    };
+    let (start, end) = match (r.book.numbering, r.numbers.as_slice()) {
+        (_, []) => return None,
+        (Numbering::Verses(_), [v]) => {
+            let start = all
+                .clone()
+                .find(|&i| matches!(marks[i].1, Mark::Para(n) if n == *v))?;
+            let end = (start + 1..marks.len())
+                .find(|&i| matches!(&marks[i].1, Mark::Para(_) | Mark::Head(_)))
+                .unwrap_or(marks.len());
+            (start, end)
+        }
+        (Numbering::Sutta, [n]) => sutta_in(all, &local(*n))?,
+        (Numbering::Volumes, [_, b]) => sutta_in(all, &[*b])?,
+        (Numbering::Chapters(m) | Numbering::Verses(m), [a, rest @ ..]) => {
+            let (cs, ce) = local(*a).into_iter().find_map(|n| {
+                let (s, _) = find_head(&marks, all.clone(), n, |t| is_group(t, Some(m)))?;
+                // The chapter runs to the next heading of its kind.
+                let e = (s + 1..marks.len())
+                    .find(|&i| matches!(&marks[i].1, Mark::Head(t) if is_group(t, Some(m)) && head_number(t).is_some()))
+                    .unwrap_or(marks.len());
+                Some((s, e))
+            })?;
+            match rest {
+                [b] => sutta_in(cs + 1..ce, &[*b])?,
+                _ => (cs, ce),
+            }
+        }
+        _ => return None,
+    };
+    locus(start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn citations_resolve_to_files_and_lines() {
+        let r = parse("SN 56.11").unwrap();
+        assert_eq!((r.book(), r.file_stem()), ("SN", "s0305m.mul"));
+        assert_eq!(parse("sn56:11").unwrap().canonical(), "SN 56.11");
+        assert_eq!(parse("AN 4.10").unwrap().file_stem(), "s0402m3.mul");
+        assert_eq!(parse("MN 60").unwrap().file_stem(), "s0202m.mul");
+        assert_eq!(parse("Sn 1.8").unwrap().book(), "Snp");
+        assert_eq!(parse("Dīgha 2").unwrap().canonical(), "DN 2");
+        assert!(parse("Dhp 183").unwrap().is_verse());
+        assert!(parse("XY 1").is_none() && parse("MN 0").is_none());
+
+        // SN volumes number their saṃyuttas from 1 again.
+        let sn = "<head rend=\"book\">Mahāvaggapāḷi</head>\n\
+                  <head rend=\"chapter\">12. Saccasaṃyuttaṃ</head>\n\
+                  <head rend=\"title\">2. Dhammacakkappavattanavaggo</head>\n\
+                  <head rend=\"subhead\">1. Dhammacakkappavattanasuttaṃ</head>\n\
+                  <p rend=\"bodytext\" n=\"1081\">Evaṃ me sutaṃ.</p>\n\
+                  <p rend=\"bodytext\" n=\"1082\">Dve me, bhikkhave, antā.</p>\n\
+                  <head rend=\"subhead\">2. Tathāgatenavuttasuttaṃ</head>\n\
+                  <p rend=\"bodytext\" n=\"1083\">…</p>";
+        let l = locate(sn, &parse("SN 56.1").unwrap()).unwrap();
+        assert_eq!((l.line, l.lines), (4, 3));
+        assert_eq!(l.head.as_deref(), Some("1. Dhammacakkappavattanasuttaṃ"));
+        assert!(locate(sn, &parse("SN 56.3").unwrap()).is_none());
+
+        let dhp = "<head rend=\"chapter\">14. Buddhavaggo</head>\n\
+                   <p rend=\"gatha1\" n=\"183\">Sabbapāpassa akaraṇaṃ,</p>\n\
+                   <p rend=\"gathalast\">etaṃ buddhāna sāsanaṃ.</p>\n\
+                   <p rend=\"gatha1\" n=\"184\">Khantī paramaṃ tapo titikkhā,</p>";
+        let l = locate(dhp, &parse("Dhp 183").unwrap()).unwrap();
+        assert_eq!((l.line, l.lines), (2, 2));
+        assert_eq!(l.head.as_deref(), Some("14. Buddhavaggo"));
+    }
+}
@@ -5,35 +5,96 @@ use regex::Regex;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
+/// Data directory shared by daizo-cli and daizo-mcp (corpora, cache, bundles, …):
+/// 1. `DAIZO_DIR` (a leading `~` is expanded, a relative path is taken from the working
+///    directory);
+/// 2. `~/.daizo` when it exists, so installs made before XDG support keep working;
+/// 3. `$XDG_DATA_HOME/daizo` when XDG_DATA_HOME is an absolute path;
+/// 4. `~/.daizo`.
+///
+/// The home directory is HOME, else USERPROFILE (Windows). Empty variables count as unset.
 pub fn daizo_home() -> PathBuf {
-    if let Ok(p) = std::env::var("DAIZO_DIR") {
-        return PathBuf::from(p);
+    resolve_daizo_home(
+        |k| std::env::var_os(k).filter(|v| !v.is_empty()),
+        Path::is_dir,
+    )
+}
+
+/// [`daizo_home`] for the environment `var` reads and the directories `exists` reports.
+pub fn resolve_daizo_home(
+    var: impl Fn(&str) -> Option<std::ffi::OsString>,
+    exists: impl Fn(&Path) -> bool,
+) -> PathBuf {
+    let home = var("HOME")
+        .or_else(|| var("USERPROFILE"))
+        .map(PathBuf::from);
+    if let Some(dir) = var("DAIZO_DIR") {
+        let dir = PathBuf::from(dir);
+        let dir = match (dir.strip_prefix("~"), &home) {
+            (Ok(rest), Some(h)) => h.join(rest),
+            _ => dir,
+        };
+        return std::path::absolute(&dir).unwrap_or(dir);
     }
-    std::env::var_os("HOME")
-        .map(PathBuf::from)
+    let legacy = home
+        .clone()
         .unwrap_or_else(|| PathBuf::from("."))
-        .join(".daizo")
+        .join(".daizo");
+    if exists(&legacy) {
+        return legacy;
+    }
+    match var("XDG_DATA_HOME").map(PathBuf::from) {
+        Some(xdg) if xdg.is_absolute() => xdg.join("daizo"),
+        _ => legacy,
+    }
+}
+
+/// Root of `corpus` from the config's corpus registry, if overridden there.
+fn configured_root(corpus: &str) -> Option<PathBuf> {
+    crate::config::current().corpus_root(corpus)
 }
 
 pub fn cbeta_root() -> PathBuf {
-    daizo_home().join("xml-p5")
+    configured_root("cbeta").unwrap_or_else(|| daizo_home().join("xml-p5"))
 }
 pub fn tipitaka_root() -> PathBuf {
-    daizo_home().join("tipitaka-xml").join("romn")
+    configured_root("tipitaka").unwrap_or_else(|| daizo_home().join("tipitaka-xml").join("romn"))
 }
 pub fn gretil_root() -> PathBuf {
-    daizo_home().join("GRETIL").join("1_sanskr").join("tei")
+    configured_root("gretil")
+        .unwrap_or_else(|| daizo_home().join("GRETIL").join("1_sanskr").join("tei"))
 }
 pub fn sarit_root() -> PathBuf {
-    daizo_home().join("SARIT-corpus")
+    configured_root("sarit").unwrap_or_else(|| daizo_home().join("SARIT-corpus"))
 }
 pub fn muktabodha_root() -> PathBuf {
-    daizo_home().join("MUKTABODHA")
+    configured_root("muktabodha").unwrap_or_else(|| daizo_home().join("MUKTABODHA"))
 }
 pub fn cache_dir() -> PathBuf {
     daizo_home().join("cache")
 }
 
+/// Whether `id` can name a file under a corpus root: a single path component with no
+/// separators, no `.`/`..`, no drive prefix, no control characters and no glob syntax.
+/// Ids come from MCP clients and are joined onto roots or spliced into glob patterns.
+pub fn safe_id(id: &str) -> bool {
+    !id.is_empty()
+        && id != "."
+        && id != ".."
+        && !id.contains("..")
+        && !id.contains(['/', '\\', ':', '*', '?', '[', ']', '{', '}'])
+        && !id.chars().any(char::is_control)
+}
+
+/// Whether `path` lies under `root` once both are canonicalized, so a symlink inside a
+/// checkout cannot lead out of it. False when either does not exist.
+pub fn within_root(path: &Path, root: &Path) -> bool {
+    match (path.canonicalize(), root.canonicalize()) {
+        (Ok(p), Ok(r)) => p.starts_with(r),
+        _ => false,
+    }
+}
+
 pub fn find_in_dir(root: &Path, stem_hint: &str) -> Option<PathBuf> {
     let hint = stem_hint.to_lowercase();
     let result: Mutex<Option<PathBuf>> = Mutex::new(None);
@@ -107,6 +168,9 @@ pub fn find_exact_file_by_name(root: &Path, filename: &str) -> Option<PathBuf> {
 /// Fast direct path resolution for CBETA using glob patterns.
 /// This avoids expensive directory traversal when the ID format is known.
 pub fn resolve_cbeta_path_direct(id: &str) -> Option<PathBuf> {
+    if !safe_id(id) {
+        return None;
+    }
     let re = Regex::new(r"^([A-Za-z]+)(\d+)$").ok()?;
     let root = cbeta_root();
 
@@ -126,7 +190,7 @@ pub fn resolve_cbeta_path_direct(id: &str) -> Option<PathBuf> {
         );
 
         if let Ok(paths) = glob(&pattern) {
-            for entry in paths.filter_map(|e| e.ok()) {
+            if let Some(entry) = paths.filter_map(|e| e.ok()).next() {
                 return Some(entry);
             }
         }
@@ -141,7 +205,7 @@ pub fn resolve_cbeta_path_direct(id: &str) -> Option<PathBuf> {
             num
         );
         if let Ok(paths) = glob(&pattern_lower) {
-            for entry in paths.filter_map(|e| e.ok()) {
+            if let Some(entry) = paths.filter_map(|e| e.ok()).next() {
                 return Some(entry);
             }
         }
@@ -152,6 +216,9 @@ pub fn resolve_cbeta_path_direct(id: &str) -> Option<PathBuf> {
 
 /// Resolve CBETA path by canonical id, trying fast glob first, then fallback scan.
 pub fn resolve_cbeta_path_by_id(id: &str) -> Option<PathBuf> {
+    if !safe_id(id) {
+        return None;
+    }
     // Try fast direct resolution first
     if let Some(path) = resolve_cbeta_path_direct(id) {
         return Some(path);
@@ -226,21 +293,15 @@ pub fn resolve_cbeta_path_by_id(id: &str) -> Option<PathBuf> {
     result.into_inner().unwrap()
 }
 
-/// Fast direct path resolution for Tipitaka using Nikāya codes or file stems.
-/// Supports: DN, MN, SN, AN, KN (e.g., "DN1", "MN1", "SN1.1") or direct file stems (e.g., "s0101m.mul")
+/// Fast direct path resolution for Tipitaka using citations or file stems.
+/// Supports citations read by [`crate::pali_ref::parse`] (e.g., "DN1", "MN 10", "SN56.11",
+/// "Dhp 183") or direct file stems (e.g., "s0101m.mul")
 pub fn resolve_tipitaka_path_direct(id: &str) -> Option<PathBuf> {
+    if !safe_id(id) {
+        return None;
+    }
     let root = tipitaka_root();
     let id_lower = id.to_lowercase();
-    let id_upper = id.to_uppercase();
-
-    // Nikāya code mapping: DN->s01, MN->s02, SN->s03, AN->s04, KN->s05
-    let nikaya_map: &[(&str, &str)] = &[
-        ("DN", "s01"),
-        ("MN", "s02"),
-        ("SN", "s03"),
-        ("AN", "s04"),
-        ("KN", "s05"),
-    ];
 
     // Try direct file stem match first (e.g., "s0101m.mul" -> "s0101m.mul.xml")
     let direct_path = root.join(format!("{}.xml", id));
@@ -256,83 +317,28 @@ pub fn resolve_tipitaka_path_direct(id: &str) -> Option<PathBuf> {
         }
     }
 
-    // Parse Nikāya code (e.g., "DN1", "MN 1", "SN1.1", "AN1", or just "DN")
-    let re_nikaya = Regex::new(r"^(DN|MN|SN|AN|KN)\s*(\d+)?(?:\.(\d+))?$").ok()?;
-    if let Some(caps) = re_nikaya.captures(&id_upper) {
-        let nikaya = &caps[1];
-        let num1 = caps.get(2).map(|m| m.as_str());
-        let _num2 = caps.get(3).map(|m| m.as_str());
-
-        // Find the file prefix for this Nikāya
-        let file_prefix = nikaya_map
-            .iter()
-            .find(|(n, _)| *n == nikaya)
-            .map(|(_, p)| *p)?;
-
-        // FAST PATH: Try direct path construction first (no glob needed)
-        // DN -> s0101m.mul.xml (first volume)
-        // DN1 -> s0101m.mul.xml (sutta 1 is in volume 1)
-        // Most suttas are in the first volume of each Nikāya
-        let vol_num = if let Some(n) = num1 {
-            // For simplicity, map sutta number to volume 01 (most common case)
-            // DN1-DN13 are in volume 1 (Sīlakkhandhavagga)
-            // More sophisticated mapping would require a lookup table
-            let sutta_num: u32 = n.parse().unwrap_or(1);
-            // Simple heuristic: suttas 1-13 -> vol 01, 14-23 -> vol 02, 24+ -> vol 03
-            match nikaya {
-                "DN" => {
-                    if sutta_num <= 13 {
-                        "01"
-                    } else if sutta_num <= 23 {
-                        "02"
-                    } else {
-                        "03"
-                    }
-                }
-                "MN" => {
-                    // MN has 3 volumes: Mūlapaṇṇāsa (1-50), Majjhimapaṇṇāsa (51-100), Uparipaṇṇāsa (101-152)
-                    if sutta_num <= 50 {
-                        "01"
-                    } else if sutta_num <= 100 {
-                        "02"
-                    } else {
-                        "03"
-                    }
-                }
-                _ => "01", // Default to first volume for SN, AN, KN
-            }
-        } else {
-            "01" // Default to first volume when no number specified
-        };
-
-        // Try direct path first
-        let direct_vol_path = root.join(format!("{}{}m.mul.xml", file_prefix, vol_num));
-        if direct_vol_path.exists() {
-            return Some(direct_vol_path);
-        }
-
-        // Fallback: try glob pattern (slower but more comprehensive)
-        let pattern = if num1.is_some() {
-            format!("{}/{}{}*m.mul.xml", root.display(), file_prefix, vol_num)
-        } else {
-            format!("{}/{}*m.mul.xml", root.display(), file_prefix)
-        };
-
-        if let Ok(paths) = glob(&pattern) {
-            let mut found: Vec<PathBuf> = paths.filter_map(|e| e.ok()).collect();
-            found.sort();
-            if let Some(first) = found.first() {
-                return Some(first.clone());
-            }
+    // Citation (e.g., "DN1", "MN 1", "SN56.11", or just "DN"): the volume it falls in
+    if let Some(r) = crate::pali_ref::parse(id) {
+        let stem = r.file_stem();
+        let path = root.join(format!("{}.xml", stem));
+        if path.exists() {
+            return Some(path);
         }
-
-        // Final fallback: any file with matching prefix
-        let fallback_pattern = format!("{}/{}*.xml", root.display(), file_prefix);
-        if let Ok(paths) = glob(&fallback_pattern) {
-            let mut found: Vec<PathBuf> = paths.filter_map(|e| e.ok()).collect();
-            found.sort();
-            if let Some(first) = found.first() {
-                return Some(first.clone());
+        // Volumes split over numbered files (s0404m1.mul0.xml, …), then any file of the book
+        let book_prefix = &stem[..stem.len().min(3)];
+        for pattern in [
+            format!("{}/{}*.xml", root.display(), stem),
+            format!("{}/{}*m.mul.xml", root.display(), book_prefix),
+        ] {
+            if let Ok(paths) = glob(&pattern) {
+                let mut found: Vec<PathBuf> = paths
+                    .filter_map(|e| e.ok())
+                    .filter(|p| !p.to_string_lossy().ends_with(".toc.xml"))
+                    .collect();
+                found.sort();
+                if let Some(first) = found.first() {
+                    return Some(first.clone());
+                }
             }
         }
     }
@@ -424,8 +430,58 @@ pub fn find_tipitaka_content_for_base(base: &str) -> Option<PathBuf> {
     best.into_inner().unwrap().map(|(_, p)| p)
 }
 
+/// Split a numbered Tipitaka part stem into its work base and part number
+/// (`s0404m1.mul2` -> `("s0404m1.mul", 2)`). Stems without a numbered suffix give None.
+pub fn tipitaka_part_of(stem: &str) -> Option<(&str, u32)> {
+    let (_, ext) = stem.rsplit_once('.')?;
+    let digits = ext.len() - ext.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 || digits == ext.len() {
+        return None;
+    }
+    let cut = stem.len() - digits;
+    Some((&stem[..cut], stem[cut..].parse().ok()?))
+}
+
+/// All files of the work `path` belongs to, in part order, when the work is split over numbered
+/// files (`s0404m1.mul0.xml`, `s0404m1.mul1.xml`, …) in the same directory. Otherwise just `path`.
+pub fn tipitaka_work_parts(path: &Path) -> Vec<PathBuf> {
+    let base = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .and_then(tipitaka_part_of)
+        .map(|(b, _)| b.to_string());
+    let (Some(base), Some(dir)) = (base, path.parent()) else {
+        return vec![path.to_path_buf()];
+    };
+    let mut parts: Vec<(u32, PathBuf)> = std::fs::read_dir(dir)
+        .map(|rd| {
+            rd.flatten()
+                .filter_map(|e| {
+                    let p = e.path();
+                    if p.extension().and_then(|s| s.to_str()) != Some("xml") {
+                        return None;
+                    }
+                    let stem = p.file_stem()?.to_str()?;
+                    match tipitaka_part_of(stem) {
+                        Some((b, n)) if b == base => Some((n, p.clone())),
+                        _ => None,
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if parts.is_empty() {
+        return vec![path.to_path_buf()];
+    }
+    parts.sort();
+    parts.into_iter().map(|(_, p)| p).collect()
+}
+
 /// Resolve a Tipitaka XML path by id (stem) using fast direct resolution first, then index fallbacks.
 pub fn resolve_tipitaka_by_id(index: &[IndexEntry], id: &str) -> Option<PathBuf> {
+    if !safe_id(id) {
+        return None;
+    }
     // Try fast direct resolution first (no index needed)
     if let Some(path) = resolve_tipitaka_path_direct(id) {
         return Some(path);
@@ -478,6 +534,9 @@ pub fn resolve_tipitaka_by_id(index: &[IndexEntry], id: &str) -> Option<PathBuf>
 /// Fast direct path resolution for GRETIL using direct file access or glob patterns.
 /// Supports: full file stem (e.g., "sa_saddharmapuNDarIka") or partial name (e.g., "saddharmapuNDarIka")
 pub fn resolve_gretil_path_direct(id: &str) -> Option<PathBuf> {
+    if !safe_id(id) {
+        return None;
+    }
     let root = gretil_root();
 
     // Try exact file stem match first (fastest path)
@@ -558,8 +617,8 @@ pub fn resolve_gretil_path_direct(id: &str) -> Option<PathBuf> {
 /// Supports: file stem (e.g., "asvaghosa-buddhacarita") and tries both repository root
 /// and "transliterated/" subdir.
 pub fn resolve_sarit_path_direct(id: &str) -> Option<PathBuf> {
-    // Avoid accidental path traversal; SARIT IDs should be file stems.
-    if id.contains('/') || id.contains('\\') {
+    // SARIT IDs are file stems; anything path-like is refused.
+    if !safe_id(id) {
         return None;
     }
     let root = sarit_root();
@@ -583,6 +642,9 @@ pub fn resolve_sarit_path_direct(id: &str) -> Option<PathBuf> {
 
 /// Resolve a SARIT TEI path by id (file stem) using fast direct resolution first, then index fallbacks.
 pub fn resolve_sarit_by_id(index: &[IndexEntry], id: &str) -> Option<PathBuf> {
+    if !safe_id(id) {
+        return None;
+    }
     if let Some(path) = resolve_sarit_path_direct(id) {
         return Some(path);
     }
@@ -609,7 +671,7 @@ pub fn resolve_sarit_by_id(index: &[IndexEntry], id: &str) -> Option<PathBuf> {
 /// Fast direct path resolution for MUKTABODHA.
 /// Supports: file stem (id) and tries both .xml and .txt under DAIZO_DIR/MUKTABODHA.
 pub fn resolve_muktabodha_path_direct(id: &str) -> Option<PathBuf> {
-    if id.contains('/') || id.contains('\\') {
+    if !safe_id(id) {
         return None;
     }
     let root = muktabodha_root();
@@ -632,6 +694,9 @@ pub fn resolve_muktabodha_path_direct(id: &str) -> Option<PathBuf> {
 
 /// Resolve a MUKTABODHA path by id (file stem) using fast direct resolution first, then index fallbacks.
 pub fn resolve_muktabodha_by_id(index: &[IndexEntry], id: &str) -> Option<PathBuf> {
+    if !safe_id(id) {
+        return None;
+    }
     if let Some(path) = resolve_muktabodha_path_direct(id) {
         return Some(path);
     }
@@ -656,7 +721,7 @@ pub fn resolve_muktabodha_by_id(index: &[IndexEntry], id: &str) -> Option<PathBu
     // Last resort: scan the directory for a matching stem across xml/txt.
     let id_lower = id.to_lowercase();
     let result: Mutex<Option<PathBuf>> = Mutex::new(None);
-    WalkBuilder::new(&muktabodha_root())
+    WalkBuilder::new(muktabodha_root())
         .hidden(false)
         .git_ignore(false)
         .git_global(false)
@@ -692,6 +757,9 @@ pub fn resolve_muktabodha_by_id(index: &[IndexEntry], id: &str) -> Option<PathBu
 
 /// Resolve a GRETIL TEI path by id (file stem) using fast direct resolution first, then index fallbacks.
 pub fn resolve_gretil_by_id(index: &[IndexEntry], id: &str) -> Option<PathBuf> {
+    if !safe_id(id) {
+        return None;
+    }
     // Try fast direct resolution first (no index needed)
     if let Some(path) = resolve_gretil_path_direct(id) {
         return Some(path);
@@ -733,6 +801,105 @@ mod tests {
     use std::collections::BTreeMap;
     use std::fs;
 
+    #[test]
+    fn daizo_home_from_the_environment() {
+        fn env<'a>(
+            pairs: &'a [(&'a str, &'a str)],
+        ) -> impl Fn(&str) -> Option<std::ffi::OsString> + 'a {
+            move |k| {
+                pairs
+                    .iter()
+                    .find(|(n, _)| *n == k)
+                    .map(|(_, v)| std::ffi::OsString::from(v))
+            }
+        }
+        let none = |_: &Path| false;
+        let home = [("HOME", "/home/u")];
+        assert_eq!(
+            resolve_daizo_home(env(&home), none),
+            PathBuf::from("/home/u/.daizo")
+        );
+        assert_eq!(
+            resolve_daizo_home(env(&[("HOME", "/home/u"), ("DAIZO_DIR", "/data/d")]), none),
+            PathBuf::from("/data/d")
+        );
+        assert_eq!(
+            resolve_daizo_home(env(&[("HOME", "/home/u"), ("DAIZO_DIR", "~/d")]), none),
+            PathBuf::from("/home/u/d")
+        );
+        let rel = resolve_daizo_home(env(&[("DAIZO_DIR", "d")]), none);
+        assert_eq!(rel, std::env::current_dir().unwrap().join("d"));
+        assert_eq!(
+            resolve_daizo_home(env(&[("USERPROFILE", "C:/Users/u")]), none),
+            PathBuf::from("C:/Users/u/.daizo")
+        );
+
+        let xdg = [
+            ("HOME", "/home/u"),
+            ("XDG_DATA_HOME", "/home/u/.local/share"),
+        ];
+        assert_eq!(
+            resolve_daizo_home(env(&xdg), none),
+            PathBuf::from("/home/u/.local/share/daizo")
+        );
+        // An existing ~/.daizo wins over XDG; a relative XDG_DATA_HOME is ignored.
+        assert_eq!(
+            resolve_daizo_home(env(&xdg), |p| p == Path::new("/home/u/.daizo")),
+            PathBuf::from("/home/u/.daizo")
+        );
+        assert_eq!(
+            resolve_daizo_home(
+                env(&[("HOME", "/home/u"), ("XDG_DATA_HOME", "share")]),
+                none
+            ),
+            PathBuf::from("/home/u/.daizo")
+        );
+    }
+
+    #[test]
+    fn path_like_ids_are_refused() {
+        for ok in [
+            "T0251",
+            "T08n0251",
+            "s0101m.mul",
+            "sa_saddharmapuNDarIka",
+            "a-b_c",
+        ] {
+            assert!(safe_id(ok), "{ok}");
+        }
+        for bad in [
+            "",
+            ".",
+            "..",
+            "../etc/passwd",
+            "/etc/passwd",
+            "a/b",
+            "a\\b",
+            "C:x",
+            "x*",
+            "a[b]",
+            "a\0b",
+            "s01..xml",
+        ] {
+            assert!(!safe_id(bad), "{bad:?}");
+        }
+        assert_eq!(resolve_sarit_path_direct("../../etc/passwd"), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("in.xml"), "<x/>").unwrap();
+        fs::write(dir.path().join("out.xml"), "<x/>").unwrap();
+        assert!(within_root(&root.join("in.xml"), &root));
+        assert!(!within_root(&root.join("../out.xml"), &root));
+        assert!(!within_root(&root.join("missing.xml"), &root));
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path().join("out.xml"), root.join("link.xml")).unwrap();
+            assert!(!within_root(&root.join("link.xml"), &root));
+        }
+    }
+
     #[test]
     fn find_exact_file() {
         let dir = tempfile::tempdir().unwrap();
@@ -856,4 +1023,31 @@ mod tests {
         let p = resolve_tipitaka_by_id(&idx, "base").unwrap();
         assert_eq!(p.file_name().unwrap(), "base0.xml");
     }
+
+    #[test]
+    fn tipitaka_work_parts_follow_the_part_number() {
+        assert_eq!(tipitaka_part_of("s0404m1.mul10"), Some(("s0404m1.mul", 10)));
+        assert_eq!(tipitaka_part_of("s0101m.mul"), None);
+        assert_eq!(tipitaka_part_of("s0404m1.mul.toc"), None);
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "s0404m1.mul10.xml",
+            "s0404m1.mul2.xml",
+            "s0404m1.mul0.xml",
+            "s0404m1.mul.toc.xml",
+            "s0404m2.mul0.xml",
+        ] {
+            fs::write(dir.path().join(name), "<xml/>").unwrap();
+        }
+        let names: Vec<String> = tipitaka_work_parts(&dir.path().join("s0404m1.mul2.xml"))
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            ["s0404m1.mul0.xml", "s0404m1.mul2.xml", "s0404m1.mul10.xml"]
+        );
+        let single = dir.path().join("s0404m1.mul.toc.xml");
+        assert_eq!(tipitaka_work_parts(&single), [single]);
+    }
 }
@@ -0,0 +1,127 @@
+use grep_regex::RegexMatcherBuilder;
+use regex_syntax::ast::ErrorKind as AstKind;
+use regex_syntax::hir::ErrorKind as HirKind;
+use serde::Serialize;
+
+/// Why a search pattern does not compile.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PatternError {
+    pub message: String,
+    /// Character (not byte) offset of the offending part of the pattern.
+    pub position: Option<usize>,
+    pub suggestion: Option<String>,
+}
+
+impl std::fmt::Display for PatternError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(p) = self.position {
+            write!(f, " at character {}", p)?;
+        }
+        if let Some(s) = &self.suggestion {
+            write!(f, "; {}", s)?;
+        }
+        Ok(())
+    }
+}
+
+/// Metacharacters that make the search tools treat a query as a regex.
+pub const REGEX_META: &str = ".+*?[](){}|\\";
+
+pub fn looks_like_regex(q: &str) -> bool {
+    q.chars().any(|c| REGEX_META.contains(c))
+}
+
+/// Check `pattern` the way the `*_grep` functions compile it (case-insensitive, multi-line).
+/// The grep functions answer a pattern that does not compile with no results, so callers that
+/// want to report it check first.
+pub fn validate(pattern: &str) -> Result<(), PatternError> {
+    let char_pos = |byte: usize| pattern[..byte.min(pattern.len())].chars().count();
+    let (message, position, suggestion) = match regex_syntax::ParserBuilder::new()
+        .case_insensitive(true)
+        .multi_line(true)
+        .build()
+        .parse(pattern)
+    {
+        Ok(_) => {
+            return RegexMatcherBuilder::new()
+                .case_insensitive(true)
+                .multi_line(true)
+                .build(pattern)
+                .map(|_| ())
+                .map_err(|e| PatternError {
+                    message: e.to_string(),
+                    position: None,
+                    suggestion: None,
+                })
+        }
+        Err(regex_syntax::Error::Parse(e)) => (
+            e.kind().to_string(),
+            Some(char_pos(e.span().start.offset)),
+            ast_suggestion(e.kind()),
+        ),
+        Err(regex_syntax::Error::Translate(e)) => (
+            e.kind().to_string(),
+            Some(char_pos(e.span().start.offset)),
+            match e.kind() {
+                HirKind::UnicodePropertyNotFound | HirKind::UnicodePropertyValueNotFound => {
+                    Some("use a Unicode script or category such as \\p{Han} or \\p{L}".to_string())
+                }
+                _ => None,
+            },
+        ),
+        Err(e) => (e.to_string(), None, None),
+    };
+    Err(PatternError {
+        message,
+        position,
+        suggestion: suggestion.or_else(|| {
+            Some("escape regex metacharacters with \\ to search for them literally".to_string())
+        }),
+    })
+}
+
+fn ast_suggestion(kind: &AstKind) -> Option<String> {
+    let s = match kind {
+        AstKind::GroupUnclosed => "close the group with ')' or write \\( for a literal parenthesis",
+        AstKind::GroupUnopened => "remove the ')' or write \\) for a literal parenthesis",
+        AstKind::ClassUnclosed => "close the class with ']' or write \\[ for a literal bracket",
+        AstKind::RepetitionMissing => {
+            "a quantifier (*, +, ?) needs something before it; write \\* \\+ \\? to match the character"
+        }
+        AstKind::RepetitionCountUnclosed
+        | AstKind::RepetitionCountDecimalEmpty
+        | AstKind::RepetitionCountInvalid => {
+            "'{' starts a count such as {2} or {1,3}; write \\{ for a literal brace"
+        }
+        AstKind::EscapeUnrecognized | AstKind::EscapeUnexpectedEof => {
+            "drop the backslash, or double it (\\\\) to match a backslash"
+        }
+        AstKind::ClassRangeInvalid => "put the smaller character first in a range, e.g. [a-z]",
+        _ => return None,
+    };
+    Some(s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_point_at_the_problem_in_characters() {
+        assert!(validate("如是(我|聞)").is_ok());
+        let e = validate("如是(我聞").unwrap_err();
+        assert_eq!(e.message, "unclosed group");
+        assert_eq!(e.position, Some(2));
+        assert!(e.suggestion.unwrap().contains("\\("));
+        let e = validate("*空").unwrap_err();
+        assert_eq!(e.position, Some(0));
+        assert!(e.suggestion.unwrap().contains("quantifier"));
+        let e = validate(r"\p{Nope}").unwrap_err();
+        assert!(e.suggestion.unwrap().contains("\\p{Han}"));
+        // The grep functions swallow the error and find nothing.
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.xml"), "<p>如是(我聞</p>").unwrap();
+        assert!(crate::gretil_grep(dir.path(), "如是(我聞", 5, 1).is_empty());
+    }
+}
@@ -0,0 +1,421 @@
+//! Plain-text shadow corpus.
+//!
+//! Searching the XML directly scans every tag and attribute, and a query can hit
+//! `rend="bodytext"` or a `teiHeader` field as readily as the text. [`build`] writes each file
+//! of a corpus as plain text under `~/.daizo/cache/plain/`, and the `*_grep` functions search
+//! that copy instead while it is newer than the XML ([`fresh_shadow`]).
+//!
+//! The copy keeps the lines of the XML: line N of the shadow holds the text of line N of the
+//! source, with markup removed, entities decoded and CBETA gaiji resolved. Line numbers,
+//! match ids and fetch hints found in the shadow therefore point into the XML unchanged.
+//! Header, variant readings (`rdg`) and TOC labels (`cb:mulu`) are left out; block elements
+//! leave a space so that words of adjacent paragraphs do not run together.
+//!
+//! Without a shadow, [`retain_text_matches`] filters XML search results after the fact
+//! (`textOnly`), dropping lines where the query only hit markup.
+
+use crate::cancel::CancelToken;
+use crate::fetch_cache::write_atomic;
+use crate::fold::shadow_path;
+use crate::path_resolver::cache_dir;
+use crate::progress::Counter;
+use crate::{cbeta_gaiji_map_fast, read_file_with_encoding, scan, GrepResult};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Elements whose content is not text of the work.
+const SKIP: &[&str] = &["teiHeader", "rdg", "mulu"];
+
+/// Elements that separate what they contain from the text around them.
+const BLOCK: &[&str] = &[
+    "p",
+    "head",
+    "l",
+    "lg",
+    "div",
+    "item",
+    "list",
+    "trailer",
+    "byline",
+    "docNumber",
+    "juan",
+    "table",
+    "row",
+    "cell",
+];
+
+/// Where shadows are written: `~/.daizo/cache/plain`.
+pub fn default_dir() -> PathBuf {
+    cache_dir().join("plain")
+}
+
+/// Whether searches read shadows; `DAIZO_PLAIN=off` makes them search the XML again.
+pub fn enabled() -> bool {
+    !matches!(
+        std::env::var("DAIZO_PLAIN")
+            .map(|v| v.trim().to_ascii_lowercase())
+            .as_deref(),
+        Ok("0" | "false" | "off" | "no")
+    )
+}
+
+/// The shadow of the XML file `src`, when one exists and is newer than `src`.
+pub fn fresh_shadow(src: &Path) -> Option<PathBuf> {
+    if !enabled() || src.extension().and_then(|e| e.to_str()) != Some("xml") {
+        return None;
+    }
+    let sp = shadow_path(&default_dir(), src);
+    let shadow = std::fs::metadata(&sp).ok()?.modified().ok()?;
+    let source = std::fs::metadata(src).ok()?.modified().ok()?;
+    (shadow >= source).then_some(sp)
+}
+
+fn local(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Value of attribute `key` in the inside of a start tag.
+fn attr<'a>(tag: &'a str, key: &str) -> Option<&'a str> {
+    let pat = format!("{}=", key);
+    let mut from = 0;
+    while let Some(i) = tag[from..].find(&pat) {
+        let at = from + i;
+        let before_ok = at == 0 || tag.as_bytes()[at - 1].is_ascii_whitespace();
+        let rest = &tag[at + pat.len()..];
+        let q = rest.chars().next()?;
+        if before_ok && (q == '"' || q == '\'') {
+            let end = rest[1..].find(q)?;
+            return Some(&rest[1..1 + end]);
+        }
+        from = at + pat.len();
+    }
+    None
+}
+
+fn push_entity(ent: &str, out: &mut String) -> bool {
+    let c = match ent {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        _ => {
+            let n = match ent.strip_prefix("#x").or_else(|| ent.strip_prefix("#X")) {
+                Some(h) => u32::from_str_radix(h, 16).ok(),
+                None => ent.strip_prefix('#').and_then(|d| d.parse().ok()),
+            };
+            match n.and_then(char::from_u32) {
+                Some(c) => c,
+                None => return false,
+            }
+        }
+    };
+    out.push(c);
+    true
+}
+
+/// Push the newlines in `s`, so that skipped markup keeps the line count.
+fn push_newlines(s: &str, out: &mut String) {
+    out.extend(s.chars().filter(|c| *c == '\n'));
+}
+
+/// End of the tag starting at `s[0] == '<'`: the `>` outside attribute quotes.
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, b) in s.bytes().enumerate() {
+        match (quote, b) {
+            (Some(q), _) if b == q => quote = None,
+            (Some(_), _) => {}
+            (None, b'"' | b'\'') => quote = Some(b),
+            (None, b'>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `n` of the first `<lb/>` on an XML line.
+pub fn line_lb(line: &str) -> Option<&str> {
+    let at = line.find("<lb")?;
+    let tag = &line[at + 1..at + tag_end(&line[at..])?];
+    attr(tag, "n")
+}
+
+/// The text of `xml` line for line, as described in the module docs.
+pub fn plain_lines(xml: &str) -> String {
+    let gaiji: HashMap<String, String> = cbeta_gaiji_map_fast(xml);
+    let mut out = String::with_capacity(xml.len() / 2);
+    let mut skip = 0usize;
+    let mut rest = xml;
+    let separate = |out: &mut String| {
+        if out.chars().next_back().is_some_and(|c| !c.is_whitespace()) {
+            out.push(' ');
+        }
+    };
+    while !rest.is_empty() {
+        let Some(i) = rest.find(['<', '&']) else {
+            if skip == 0 {
+                out.push_str(rest);
+            } else {
+                push_newlines(rest, &mut out);
+            }
+            break;
+        };
+        let (text, at) = rest.split_at(i);
+        if skip == 0 {
+            out.push_str(text);
+        } else {
+            push_newlines(text, &mut out);
+        }
+        if let Some(ent) = at.strip_prefix('&') {
+            let decoded = ent
+                .find(';')
+                .filter(|e| *e <= 10)
+                .filter(|e| skip > 0 || push_entity(&ent[..*e], &mut out));
+            match decoded {
+                Some(e) => rest = &ent[e + 1..],
+                None => {
+                    if skip == 0 {
+                        out.push('&');
+                    }
+                    rest = ent;
+                }
+            }
+            continue;
+        }
+        if let Some(body) = at.strip_prefix("<!--") {
+            let end = body.find("-->").unwrap_or(body.len());
+            push_newlines(&body[..end], &mut out);
+            rest = body.get(end + 3..).unwrap_or("");
+            continue;
+        }
+        if let Some(body) = at.strip_prefix("<![CDATA[") {
+            let end = body.find("]]>").unwrap_or(body.len());
+            if skip == 0 {
+                out.push_str(&body[..end]);
+            } else {
+                push_newlines(&body[..end], &mut out);
+            }
+            rest = body.get(end + 3..).unwrap_or("");
+            continue;
+        }
+        let Some(end) = tag_end(at) else {
+            push_newlines(at, &mut out);
+            break;
+        };
+        let tag = &at[1..end];
+        push_newlines(tag, &mut out);
+        rest = &at[end + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        let closing = tag.starts_with('/');
+        let empty = tag.ends_with('/');
+        let inner = tag.trim_start_matches('/').trim_end_matches('/');
+        let name = local(
+            inner
+                .split(|c: char| c.is_whitespace())
+                .next()
+                .unwrap_or(""),
+        );
+        if SKIP.contains(&name) {
+            match (closing, empty) {
+                (true, _) => skip = skip.saturating_sub(1),
+                (false, false) => skip += 1,
+                (false, true) => {}
+            }
+            continue;
+        }
+        if skip > 0 {
+            continue;
+        }
+        if BLOCK.contains(&name) {
+            separate(&mut out);
+        } else if name == "g" && empty {
+            let key = attr(inner, "ref").map(|r| r.trim_start_matches('#'));
+            if let Some(v) = key.and_then(|k| gaiji.get(k)) {
+                out.push_str(v);
+            }
+        }
+    }
+    out
+}
+
+/// Outcome of a [`build`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BuildReport {
+    pub files: usize,
+    pub written: usize,
+    /// Shadows already newer than their file, left as they were.
+    pub fresh: usize,
+    pub failed: usize,
+}
+
+/// Write the shadow of every XML file in `paths` (other files are skipped), or only of
+/// those without a fresh one unless `force`. Reports progress as `task`.
+pub fn build(task: &str, paths: &[PathBuf], force: bool) -> BuildReport {
+    let dir = default_dir();
+    let xml: Vec<&PathBuf> = paths
+        .iter()
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("xml"))
+        .collect();
+    let cancel = CancelToken::current();
+    let counter = Counter::new(task, "files", xml.len());
+    let outcomes: Vec<Option<bool>> = scan::run(|| {
+        xml.par_iter()
+            .filter_map(|p| {
+                if cancel.is_cancelled() {
+                    return None;
+                }
+                let out = if !force && fresh_shadow(p).is_some() {
+                    Some(false)
+                } else {
+                    read_file_with_encoding(p)
+                        .and_then(|x| {
+                            write_atomic(&shadow_path(&dir, p), plain_lines(&x).as_bytes()).ok()
+                        })
+                        .map(|_| true)
+                };
+                counter.tick();
+                Some(out)
+            })
+            .collect()
+    });
+    BuildReport {
+        files: xml.len(),
+        written: outcomes.iter().filter(|o| **o == Some(true)).count(),
+        fresh: outcomes.iter().filter(|o| **o == Some(false)).count(),
+        failed: outcomes.iter().filter(|o| o.is_none()).count(),
+    }
+}
+
+/// Byte ranges of markup on an XML line: tags, including a tag left open by the previous
+/// line, comments and entity references.
+pub fn markup_spans(line: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    // A `>` before any `<` closes a tag that began on an earlier line.
+    let mut i = match (line.find('>'), line.find('<')) {
+        (Some(gt), lt) if lt.is_none_or(|lt| gt < lt) => {
+            spans.push((0, gt + 1));
+            gt + 1
+        }
+        _ => 0,
+    };
+    while let Some(off) = line[i..].find(['<', '&']) {
+        let at = i + off;
+        let rest = &line[at..];
+        let end = if rest.starts_with('&') {
+            match rest.find(';').filter(|e| *e <= 10) {
+                Some(e) => at + e + 1,
+                None => at + 1,
+            }
+        } else if let Some(body) = rest.strip_prefix("<!--") {
+            body.find("-->").map_or(line.len(), |e| at + 4 + e + 3)
+        } else {
+            tag_end(rest).map_or(line.len(), |e| at + e + 1)
+        };
+        if end > at + 1 || rest.starts_with('<') {
+            spans.push((at, end));
+        }
+        i = end;
+    }
+    spans
+}
+
+/// Keep the matches of an XML search whose line holds an occurrence of `re` outside
+/// [`markup_spans`], highlighting the first such occurrence; files left without matches are
+/// dropped. Lines where `re` is not found again (a folded search) are kept as they are.
+pub fn retain_text_matches(results: &mut Vec<GrepResult>, re: &regex::Regex) {
+    for r in results.iter_mut() {
+        r.matches.retain_mut(|m| {
+            let mut found = re.find_iter(&m.context).peekable();
+            if found.peek().is_none() {
+                return true;
+            }
+            let spans = markup_spans(&m.context);
+            let text = found.find(|f| !spans.iter().any(|(a, b)| f.start() < *b && *a < f.end()));
+            match text {
+                Some(f) => {
+                    m.highlight = f.as_str().to_string();
+                    true
+                }
+                None => false,
+            }
+        });
+        r.total_matches = r.matches.len();
+    }
+    results.retain(|r| !r.matches.is_empty());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markup_goes_and_lines_stay() {
+        let xml = "<?xml version=\"1.0\"?>\n<TEI><teiHeader>\n<title>般若心經</title>\n\
+            <charDecl><char xml:id=\"CB00178\"><mapping type=\"unicode\">㒰</mapping></char></charDecl>\n\
+            </teiHeader><text><body>\n\
+            <lb n=\"0848c07\"/><cb:mulu type=\"品\">1 序</cb:mulu>觀自在<g ref=\"#CB00178\"/>菩薩<!-- x\n-->\n\
+            <lb n=\"0848c08\"/>照見<app><lem>五</lem><rdg wit=\"【宋】\">三</rdg></app>蘊 &amp; &#x7A7A;\n\
+            <p rend=\"bodytext\">Evaṃ</p><p>me</p>\n</body></text></TEI>\n";
+        let plain = plain_lines(xml);
+        let lines: Vec<&str> = plain.lines().collect();
+        assert_eq!(plain.lines().count(), xml.lines().count());
+        assert!(!plain.contains("般若心經") && !plain.contains("lb") && !plain.contains('<'));
+        assert_eq!(lines[5], "觀自在㒰菩薩");
+        assert_eq!(lines[7], "照見五蘊 & 空");
+        assert_eq!(lines[8].trim(), "Evaṃ me");
+        assert_eq!(line_lb(r#"<lb ed="T" n="0848c07"/>觀"#), Some("0848c07"));
+    }
+
+    #[test]
+    fn text_only_drops_markup_hits() {
+        let line = r#"<p rend="bodytext" n="3">Atha kho rendati &amp; <hi>rend</hi></p>"#;
+        let spans = markup_spans(line);
+        assert_eq!(
+            &line[spans[0].0..spans[0].1],
+            r#"<p rend="bodytext" n="3">"#
+        );
+        assert!(markup_spans(r#"n="1">text"#)[0] == (0, 6));
+
+        let m = |ctx: &str| crate::GrepMatch {
+            context: ctx.to_string(),
+            line_number: Some(1),
+            ..Default::default()
+        };
+        let mut results = vec![
+            GrepResult {
+                file_id: "a".into(),
+                matches: vec![m(line), m(r#"<p rend="centre">Namo</p>"#)],
+                total_matches: 2,
+                ..Default::default()
+            },
+            GrepResult {
+                file_id: "b".into(),
+                matches: vec![m(r#"<head rend="chapter">x</head>"#)],
+                total_matches: 1,
+                ..Default::default()
+            },
+        ];
+        let re = regex::RegexBuilder::new("rend")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        retain_text_matches(&mut results, &re);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].total_matches, 1);
+        assert_eq!(results[0].matches[0].highlight, "rend");
+        assert!(results[0].matches[0].context.contains("rendati"));
+
+        // "amp" only occurs in the entity.
+        let mut results = vec![GrepResult {
+            matches: vec![m(line)],
+            ..Default::default()
+        }];
+        retain_text_matches(&mut results, &regex::Regex::new("amp").unwrap());
+        assert!(results.is_empty());
+    }
+}
@@ -0,0 +1,444 @@
+//! Politeness toward remote sources: robots.txt, per-host spacing, a requests-per-minute cap
+//! and the User-Agent sent.
+//!
+//! The module does no I/O. An HTTP layer reads the [`Policy`] in effect and, for each request,
+//! asks its [`Gate`] for the robots.txt to read first ([`Gate::robots_due`]), hands the gate
+//! what it fetched, and waits for the slot [`Gate::admit`] gives the request (or drops it when
+//! robots.txt disallows it). The server's async client, the CLI's blocking client and the
+//! http embedder all go through these two calls on the process-wide [`shared`] gate.
+
+use crate::config;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_USER_AGENT: &str = concat!(
+    "daizo-mcp/",
+    env!("CARGO_PKG_VERSION"),
+    " (+https://github.com/sinryo/daizo-mcp)"
+);
+
+/// How long a host's robots.txt is trusted before it is fetched again.
+pub const ROBOTS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Crawl-delay values above this are capped, so one tool call cannot stall for minutes.
+pub const MAX_CRAWL_DELAY: Duration = Duration::from_secs(60);
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Policy {
+    pub user_agent: String,
+    /// Least time between two requests to the same host (raised by a robots.txt Crawl-delay).
+    pub host_interval: Duration,
+    /// Requests per host in any 60 seconds; None for no cap.
+    pub max_per_minute: Option<u32>,
+    /// Whether robots.txt is fetched and obeyed.
+    pub robots: bool,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            host_interval: Duration::from_millis(500),
+            max_per_minute: Some(60),
+            robots: true,
+        }
+    }
+}
+
+impl Policy {
+    /// Settings in effect: `DAIZO_HTTP_USER_AGENT`, `DAIZO_HTTP_INTERVAL_MS`,
+    /// `DAIZO_HTTP_MAX_PER_MINUTE` (0 for no cap) and `DAIZO_HTTP_ROBOTS` (0 to ignore
+    /// robots.txt), else `[network]` in config.toml, else the defaults.
+    pub fn current() -> Policy {
+        let net = config::current().network.clone();
+        let env = |k: &str| std::env::var(k).ok().filter(|v| !v.trim().is_empty());
+        let d = Policy::default();
+        let user_agent = env("DAIZO_HTTP_USER_AGENT")
+            .or(net.user_agent)
+            .unwrap_or(d.user_agent);
+        let host_interval = env("DAIZO_HTTP_INTERVAL_MS")
+            .and_then(|v| v.trim().parse().ok())
+            .or(net.host_interval_ms)
+            .map_or(d.host_interval, Duration::from_millis);
+        let max_per_minute = env("DAIZO_HTTP_MAX_PER_MINUTE")
+            .and_then(|v| v.trim().parse().ok())
+            .or(net.max_per_minute)
+            .map_or(d.max_per_minute, |n: u32| (n > 0).then_some(n));
+        let robots = env("DAIZO_HTTP_ROBOTS")
+            .map(|v| !matches!(v.trim(), "0" | "false" | "no" | "off"))
+            .or(net.robots)
+            .unwrap_or(d.robots);
+        Policy {
+            user_agent,
+            host_interval,
+            max_per_minute,
+            robots,
+        }
+    }
+}
+
+/// The rules of one robots.txt that apply to one user agent.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Robots {
+    /// `(allow, pattern)` in file order.
+    rules: Vec<(bool, String)>,
+    crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    /// Parse `body` for `user_agent`. The group naming the agent's product token (the part
+    /// before `/`, e.g. `daizo-mcp`, compared case-insensitively as RFC 9309 asks) wins over
+    /// `*`; groups naming the same agents are merged.
+    pub fn parse(body: &str, user_agent: &str) -> Robots {
+        let product = user_agent
+            .split(['/', ' '])
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+        // (agents, rules) per group, in file order.
+        let mut groups: Vec<(Vec<String>, Robots)> = Vec::new();
+        let mut in_agents = false;
+        for line in body.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_ascii_lowercase();
+            let value = value.trim();
+            if key == "user-agent" {
+                if !in_agents {
+                    groups.push((Vec::new(), Robots::default()));
+                    in_agents = true;
+                }
+                if let Some(g) = groups.last_mut() {
+                    g.0.push(value.to_lowercase());
+                }
+                continue;
+            }
+            in_agents = false;
+            let Some((_, r)) = groups.last_mut() else {
+                continue;
+            };
+            match key.as_str() {
+                "allow" | "disallow" if !value.is_empty() => {
+                    r.rules.push((key == "allow", value.to_string()));
+                }
+                "crawl-delay" => {
+                    if let Ok(s) = value.parse::<f64>() {
+                        if s.is_finite() && s >= 0.0 {
+                            r.crawl_delay = Some(Duration::from_secs_f64(s).min(MAX_CRAWL_DELAY));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        let named =
+            !product.is_empty() && groups.iter().any(|(agents, _)| agents.contains(&product));
+        let best = if named { product } else { "*".to_string() };
+        let mut out = Robots::default();
+        for (agents, r) in groups {
+            if agents.contains(&best) {
+                out.rules.extend(r.rules);
+                out.crawl_delay = out.crawl_delay.max(r.crawl_delay);
+            }
+        }
+        out
+    }
+
+    /// Whether `path` (with its query) may be fetched: the longest matching rule decides,
+    /// and Allow wins a tie. No matching rule allows.
+    pub fn allows(&self, path: &str) -> bool {
+        let mut best: Option<(usize, bool)> = None;
+        for (allow, pat) in &self.rules {
+            if pattern_matches(pat, path) {
+                let len = pat.len();
+                if best.is_none_or(|(l, a)| len > l || (len == l && *allow && !a)) {
+                    best = Some((len, *allow));
+                }
+            }
+        }
+        best.is_none_or(|(_, a)| a)
+    }
+
+    pub fn crawl_delay(&self) -> Option<Duration> {
+        self.crawl_delay
+    }
+}
+
+/// robots.txt path patterns: a prefix, where `*` matches any run of characters and a final
+/// `$` anchors the end.
+fn pattern_matches(pat: &str, path: &str) -> bool {
+    let (pat, anchored) = match pat.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pat, false),
+    };
+    let parts: Vec<&str> = pat.split('*').collect();
+    let Some(rest) = path.strip_prefix(parts[0]) else {
+        return false;
+    };
+    if parts.len() == 1 {
+        return !anchored || rest.is_empty();
+    }
+    let mut rest = rest;
+    for (i, part) in parts.iter().enumerate().skip(1) {
+        let last = i == parts.len() - 1;
+        if last && anchored {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// `https://host:port/path?q` → (`https://host:port`, `host`, `/path?q`).
+pub fn split_url(url: &str) -> Option<(String, String, String)> {
+    let (scheme, rest) = url.split_once("://")?;
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let authority = &rest[..end];
+    let host = authority
+        .rsplit('@')
+        .next()
+        .unwrap_or("")
+        .split(':')
+        .next()
+        .unwrap_or("")
+        .to_lowercase();
+    if host.is_empty() {
+        return None;
+    }
+    let mut path = rest[end..].split('#').next().unwrap_or("").to_string();
+    if !path.starts_with('/') {
+        path.insert(0, '/');
+    }
+    Some((format!("{}://{}", scheme, authority), host, path))
+}
+
+/// A robots.txt to read before a request, from [`Gate::robots_due`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct RobotsFetch {
+    pub host: String,
+    pub url: String,
+}
+
+#[derive(Default)]
+struct Host {
+    /// When the host may next be asked.
+    next: Option<Instant>,
+    /// Start times of the requests of the last minute, oldest first.
+    sent: VecDeque<Instant>,
+    /// The host's rules and when they were read.
+    robots: Option<(Robots, Instant)>,
+}
+
+/// Per-host request schedule and robots.txt cache.
+#[derive(Default)]
+pub struct Gate(Mutex<HashMap<String, Host>>);
+
+/// The gate of this process, so every HTTP layer in it spaces its requests to a host together.
+pub fn shared() -> &'static Gate {
+    static GATE: OnceLock<Gate> = OnceLock::new();
+    GATE.get_or_init(Gate::default)
+}
+
+impl Gate {
+    fn with<T>(&self, host: &str, f: impl FnOnce(&mut Host) -> T) -> T {
+        let mut hosts = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        f(hosts.entry(host.to_string()).or_default())
+    }
+
+    /// Whether `host`'s robots.txt has to be fetched (again) before [`Gate::allows`].
+    pub fn wants_robots(&self, host: &str, now: Instant) -> bool {
+        self.with(host, |h| {
+            h.robots
+                .as_ref()
+                .is_none_or(|(_, at)| now.duration_since(*at) >= ROBOTS_TTL)
+        })
+    }
+
+    /// Record `host`'s robots.txt; None when it has none or it could not be read, which
+    /// allows everything until the next check.
+    pub fn set_robots(&self, host: &str, body: Option<&str>, user_agent: &str, now: Instant) {
+        let r = body.map_or_else(Robots::default, |b| Robots::parse(b, user_agent));
+        self.with(host, |h| h.robots = Some((r, now)));
+    }
+
+    /// Whether the rules recorded for `host` allow `path`.
+    pub fn allows(&self, host: &str, path: &str) -> bool {
+        self.with(host, |h| {
+            h.robots.as_ref().is_none_or(|(r, _)| r.allows(path))
+        })
+    }
+
+    /// The robots.txt to read before fetching `url`: due when `policy` obeys robots.txt and no
+    /// recent copy of the host's is known. Its request takes a slot of the host like any other
+    /// ([`Gate::reserve`]), and its body goes to [`Gate::set_robots`].
+    pub fn robots_due(
+        &self,
+        url: &str,
+        policy: &Policy,
+        now: Instant,
+    ) -> Result<Option<RobotsFetch>, String> {
+        let (origin, host, _) = split_url(url).ok_or_else(|| format!("invalid URL {}", url))?;
+        if !policy.robots || !self.wants_robots(&host, now) {
+            return Ok(None);
+        }
+        Ok(Some(RobotsFetch {
+            url: format!("{}/robots.txt", origin),
+            host,
+        }))
+    }
+
+    /// Admit a request to `url`: an error when the host's robots.txt disallows it (and
+    /// `policy` obeys robots.txt), else the start of the slot reserved for it.
+    pub fn admit(&self, url: &str, policy: &Policy, now: Instant) -> Result<Instant, String> {
+        let (_, host, path) = split_url(url).ok_or_else(|| format!("invalid URL {}", url))?;
+        if policy.robots && !self.allows(&host, &path) {
+            return Err(format!("disallowed by robots.txt of {}", host));
+        }
+        Ok(self.reserve(&host, policy, now))
+    }
+
+    /// Take the next free slot of `host` and return when it starts: at least the policy's
+    /// interval (or the host's Crawl-delay) after the previous request, and no more than
+    /// `max_per_minute` requests in any 60 seconds.
+    pub fn reserve(&self, host: &str, policy: &Policy, now: Instant) -> Instant {
+        self.with(host, |h| {
+            let mut at = h.next.map_or(now, |t| t.max(now));
+            if let Some(max) = policy.max_per_minute.map(|m| m.max(1) as usize) {
+                while h.sent.front().is_some_and(|t| *t + MINUTE <= at) {
+                    h.sent.pop_front();
+                }
+                if h.sent.len() >= max {
+                    at = at.max(h.sent[h.sent.len() - max] + MINUTE);
+                }
+                h.sent.push_back(at);
+            }
+            let crawl = h.robots.as_ref().and_then(|(r, _)| r.crawl_delay());
+            h.next = Some(at + crawl.map_or(policy.host_interval, |c| c.max(policy.host_interval)));
+            at
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "\
+User-agent: *
+Disallow: /private/
+Disallow: /*.php$
+Allow: /SAT2018/sat/satdb2018.php
+Crawl-delay: 2
+
+User-agent: Daizo-MCP
+User-agent: other
+Disallow: /SAT2018/master30.php  # detail pages
+Crawl-delay: 5
+
+User-agent: badbot
+Disallow: /
+";
+
+    #[test]
+    fn robots_picks_the_agent_group_and_longest_rule() {
+        let star = Robots::parse(ROBOTS, "curl/8.0");
+        assert!(!star.allows("/private/x"));
+        assert!(!star.allows("/index.php"));
+        assert!(star.allows("/index.php?x=1"));
+        assert!(star.allows("/SAT2018/sat/satdb2018.php"));
+        assert_eq!(star.crawl_delay(), Some(Duration::from_secs(2)));
+
+        let ours = Robots::parse(ROBOTS, DEFAULT_USER_AGENT);
+        assert!(ours.allows("/private/x"));
+        assert!(!ours.allows("/SAT2018/master30.php?lp=T0262_,09,0001c14"));
+        assert_eq!(ours.crawl_delay(), Some(Duration::from_secs(5)));
+
+        assert!(Robots::parse("", "daizo").allows("/anything"));
+        assert!(!Robots::parse(ROBOTS, "BadBot/1.0").allows("/"));
+        // The product token must equal the group's agent, not merely contain it.
+        assert!(Robots::parse("User-agent: daizo\nDisallow: /\n", DEFAULT_USER_AGENT).allows("/"));
+        assert_eq!(
+            split_url("https://21dzk.l.u-tokyo.ac.jp:443/SAT2018/x.php?a=1#top"),
+            Some((
+                "https://21dzk.l.u-tokyo.ac.jp:443".into(),
+                "21dzk.l.u-tokyo.ac.jp".into(),
+                "/SAT2018/x.php?a=1".into()
+            ))
+        );
+        assert_eq!(
+            split_url("http://example.org?q").map(|u| u.2),
+            Some("/?q".into())
+        );
+    }
+
+    #[test]
+    fn gate_spaces_requests_and_caps_each_minute() {
+        let gate = Gate::default();
+        let t0 = Instant::now();
+        let gap = Duration::from_millis(500);
+        let policy = Policy {
+            host_interval: gap,
+            max_per_minute: Some(3),
+            ..Policy::default()
+        };
+        assert_eq!(gate.reserve("sat", &policy, t0), t0);
+        assert_eq!(gate.reserve("sat", &policy, t0), t0 + gap);
+        assert_eq!(gate.reserve("sat", &policy, t0), t0 + gap * 2);
+        // The fourth request waits until the first is a minute old.
+        assert_eq!(gate.reserve("sat", &policy, t0), t0 + MINUTE);
+        // Another host is not held up.
+        assert_eq!(gate.reserve("jozen", &policy, t0), t0);
+
+        // A Crawl-delay above the interval spaces the host further.
+        let t1 = t0 + MINUTE * 10;
+        assert!(gate.wants_robots("jozen", t1));
+        gate.set_robots("jozen", Some(ROBOTS), DEFAULT_USER_AGENT, t1);
+        assert!(!gate.wants_robots("jozen", t1 + MINUTE));
+        assert!(gate.wants_robots("jozen", t1 + ROBOTS_TTL));
+        assert!(!gate.allows("jozen", "/SAT2018/master30.php"));
+        assert!(gate.allows("sat", "/SAT2018/master30.php"));
+        assert_eq!(gate.reserve("jozen", &policy, t1), t1);
+        assert_eq!(
+            gate.reserve("jozen", &policy, t1),
+            t1 + Duration::from_secs(5)
+        );
+    }
+
+    #[test]
+    fn requests_read_robots_once_and_are_admitted_in_turn() {
+        let gate = Gate::default();
+        let t0 = Instant::now();
+        let policy = Policy::default();
+        let url = "https://21dzk.l.u-tokyo.ac.jp/SAT2018/master30.php?lp=T0262";
+        let due = gate.robots_due(url, &policy, t0).unwrap().unwrap();
+        assert_eq!(due.url, "https://21dzk.l.u-tokyo.ac.jp/robots.txt");
+        gate.set_robots(&due.host, Some(ROBOTS), DEFAULT_USER_AGENT, t0);
+        assert_eq!(gate.robots_due(url, &policy, t0), Ok(None));
+        assert_eq!(
+            gate.admit(url, &policy, t0),
+            Err("disallowed by robots.txt of 21dzk.l.u-tokyo.ac.jp".to_string())
+        );
+        let search = "https://21dzk.l.u-tokyo.ac.jp/SAT2018/search.php";
+        assert_eq!(gate.admit(search, &policy, t0), Ok(t0));
+        assert_eq!(
+            gate.admit(search, &policy, t0),
+            Ok(t0 + Duration::from_secs(5))
+        );
+        // A policy that ignores robots.txt neither reads nor obeys it.
+        let rude = Policy {
+            robots: false,
+            ..Policy::default()
+        };
+        assert_eq!(gate.robots_due("http://x.org/", &rude, t0), Ok(None));
+        assert!(gate.admit(url, &rude, t0).is_ok());
+        assert!(gate.admit("not a url", &policy, t0).is_err());
+    }
+}
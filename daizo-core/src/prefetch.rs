@@ -0,0 +1,205 @@
+//! Speculative cache for the section a client is likely to read next.
+//!
+//! When a fetch returns one juan (or one part of a work split over several files), the next
+//! one is usually requested shortly after. [`Prefetcher::schedule`] extracts it on a
+//! background thread and keeps the text in a byte-capped LRU, so the following fetch is
+//! served by [`Prefetcher::get`] without touching the XML. One extraction runs at a time and
+//! a new one starts only after `min_interval`, so paging quickly cannot pile up work.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrefetchSettings {
+    pub enabled: bool,
+    /// Total size of the cached texts, in bytes.
+    pub max_bytes: usize,
+    /// Least time between the starts of two extractions.
+    pub min_interval: Duration,
+}
+
+impl Default for PrefetchSettings {
+    fn default() -> Self {
+        PrefetchSettings {
+            enabled: true,
+            max_bytes: 64 << 20,
+            min_interval: Duration::from_millis(200),
+        }
+    }
+}
+
+/// Counters for status reporting.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PrefetchStats {
+    pub entries: usize,
+    pub bytes: usize,
+    pub hits: u64,
+    pub scheduled: u64,
+    /// Requests dropped because another extraction was running or the interval had not passed.
+    pub throttled: u64,
+}
+
+#[derive(Default)]
+struct State {
+    /// Most recently inserted first.
+    entries: VecDeque<(String, Arc<String>)>,
+    bytes: usize,
+    inflight: HashSet<String>,
+    last_start: Option<Instant>,
+    stats: PrefetchStats,
+}
+
+#[derive(Default)]
+pub struct Prefetcher {
+    state: Mutex<State>,
+}
+
+impl Prefetcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A prefetched text, marked as recently used; cursor pages of the same section keep
+    /// hitting it until it is evicted.
+    pub fn get(&self, key: &str) -> Option<Arc<String>> {
+        let mut st = self.lock();
+        let Some(i) = st.entries.iter().position(|(k, _)| k == key) else {
+            crate::metrics::cache_miss("prefetch");
+            return None;
+        };
+        crate::metrics::cache_hit("prefetch");
+        let e = st.entries.remove(i)?;
+        let v = e.1.clone();
+        st.entries.push_front(e);
+        st.stats.hits += 1;
+        Some(v)
+    }
+
+    /// Store `text` under `key`, evicting the oldest entries beyond `max_bytes`. A text larger
+    /// than the cap is not kept.
+    pub fn insert(&self, key: &str, text: String, max_bytes: usize) {
+        if text.len() > max_bytes {
+            return;
+        }
+        let mut st = self.lock();
+        if let Some(i) = st.entries.iter().position(|(k, _)| k == key) {
+            if let Some((_, old)) = st.entries.remove(i) {
+                st.bytes -= old.len();
+            }
+        }
+        st.bytes += text.len();
+        st.entries.push_front((key.to_string(), Arc::new(text)));
+        while st.bytes > max_bytes {
+            match st.entries.pop_back() {
+                Some((_, v)) => st.bytes -= v.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Run `work` on a background thread and cache its text under `key`, unless prefetching
+    /// is off, the key is cached or being extracted, or the rate limit drops the request.
+    /// Returns whether a thread was started.
+    pub fn schedule<F>(&'static self, key: String, settings: PrefetchSettings, work: F) -> bool
+    where
+        F: FnOnce() -> Option<String> + Send + 'static,
+    {
+        if !settings.enabled || settings.max_bytes == 0 {
+            return false;
+        }
+        {
+            let mut st = self.lock();
+            if st.inflight.contains(&key) || st.entries.iter().any(|(k, _)| *k == key) {
+                return false;
+            }
+            let now = Instant::now();
+            let too_soon = st
+                .last_start
+                .is_some_and(|t| now.duration_since(t) < settings.min_interval);
+            if !st.inflight.is_empty() || too_soon {
+                st.stats.throttled += 1;
+                return false;
+            }
+            st.inflight.insert(key.clone());
+            st.last_start = Some(now);
+            st.stats.scheduled += 1;
+        }
+        std::thread::spawn(move || {
+            let text = work();
+            if let Some(t) = text {
+                self.insert(&key, t, settings.max_bytes);
+            }
+            self.lock().inflight.remove(&key);
+        });
+        true
+    }
+
+    /// Whether an extraction is running.
+    pub fn busy(&self) -> bool {
+        !self.lock().inflight.is_empty()
+    }
+
+    pub fn stats(&self) -> PrefetchStats {
+        let st = self.lock();
+        PrefetchStats {
+            entries: st.entries.len(),
+            bytes: st.bytes,
+            ..st.stats
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut st = self.lock();
+        st.entries.clear();
+        st.bytes = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caches_the_next_section_within_the_cap_and_rate_limit() {
+        let p: &'static Prefetcher = Box::leak(Box::new(Prefetcher::new()));
+        let s = PrefetchSettings {
+            enabled: true,
+            max_bytes: 10,
+            min_interval: Duration::from_secs(60),
+        };
+        assert!(p.schedule("T0262/002".into(), s, || Some("卷第二".into())));
+        // Still within the interval: dropped.
+        assert!(!p.schedule("T0262/003".into(), s, || Some("x".into())));
+        while p.busy() {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(
+            p.get("T0262/002").as_deref().map(|s| s.as_str()),
+            Some("卷第二")
+        );
+        assert_eq!(p.stats().throttled, 1);
+        p.clear();
+
+        p.insert("a", "1234".into(), 10);
+        p.insert("b", "12345".into(), 10);
+        // Using "a" makes "b" the oldest entry.
+        assert!(p.get("a").is_some());
+        p.insert("c", "123".into(), 10);
+        assert!(p.get("b").is_none());
+        assert!(p.get("a").is_some() && p.get("c").is_some());
+        p.insert("big", "x".repeat(11), 10);
+        assert!(p.get("big").is_none());
+        assert_eq!(p.stats().bytes, 7);
+
+        let off = PrefetchSettings {
+            enabled: false,
+            ..PrefetchSettings::default()
+        };
+        assert!(!p.schedule("k".into(), off, || None));
+    }
+}
@@ -0,0 +1,177 @@
+use serde::Serialize;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A progress update from a long-running step (corpus clone, index build).
+#[derive(Clone, Debug, Serialize)]
+pub struct Progress {
+    /// What is running, e.g. `index cbeta` or `clone xml-p5`.
+    pub task: String,
+    pub done: u64,
+    pub total: Option<u64>,
+    /// Bytes received so far (downloads only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    pub message: String,
+}
+
+type Sink = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+/// Receiver of progress updates, bound to a thread with [`Reporter::scope`]. Index builders
+/// and `repo::ensure_*` pick up the reporter of the calling thread; without one they stay quiet.
+#[derive(Clone)]
+pub struct Reporter(Sink);
+
+thread_local! {
+    static CURRENT: RefCell<Option<Reporter>> = const { RefCell::new(None) };
+}
+
+impl Reporter {
+    pub fn new(f: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        Reporter(Arc::new(f))
+    }
+
+    pub fn report(&self, p: &Progress) {
+        (self.0)(p)
+    }
+
+    /// Run `f` with this reporter bound to the current thread.
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        let prev = CURRENT.with(|c| c.replace(Some(self.clone())));
+        let out = f();
+        CURRENT.with(|c| *c.borrow_mut() = prev);
+        out
+    }
+
+    pub fn current() -> Option<Self> {
+        CURRENT.with(|c| c.borrow().clone())
+    }
+}
+
+/// Minimum time between two updates of a counter (the final one is always sent).
+const MIN_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Thread-safe item counter for parallel loops; reports through the reporter bound when it
+/// was created, at most every [`MIN_INTERVAL`] and once more at the end.
+pub struct Counter {
+    reporter: Option<Reporter>,
+    task: String,
+    unit: &'static str,
+    total: u64,
+    done: AtomicU64,
+    last: Mutex<Instant>,
+}
+
+impl Counter {
+    pub fn new(task: &str, unit: &'static str, total: usize) -> Self {
+        let reporter = Reporter::current();
+        let c = Counter {
+            reporter,
+            task: task.to_string(),
+            unit,
+            total: total as u64,
+            done: AtomicU64::new(0),
+            last: Mutex::new(Instant::now()),
+        };
+        c.send(0);
+        c
+    }
+
+    pub fn tick(&self) {
+        if self.reporter.is_none() {
+            return;
+        }
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if done < self.total {
+            let Ok(mut last) = self.last.try_lock() else {
+                return;
+            };
+            if last.elapsed() < MIN_INTERVAL {
+                return;
+            }
+            *last = Instant::now();
+        }
+        self.send(done);
+    }
+
+    fn send(&self, done: u64) {
+        if let Some(r) = &self.reporter {
+            r.report(&Progress {
+                task: self.task.clone(),
+                done,
+                total: Some(self.total),
+                bytes: None,
+                message: format!("{}: {}/{} {}", self.task, done, self.total, self.unit),
+            });
+        }
+    }
+}
+
+/// Parse a git `--progress` line such as
+/// `Receiving objects:  45% (1234/2742), 12.34 MiB | 2.00 MiB/s` into (phase, done, total, bytes).
+pub fn parse_git_progress(line: &str) -> Option<(String, u64, u64, Option<u64>)> {
+    let line = line.trim().trim_start_matches("remote: ");
+    let (phase, rest) = line.split_once(':')?;
+    let open = rest.find('(')?;
+    let close = open + rest[open..].find(')')?;
+    let (done, total) = rest[open + 1..close].split_once('/')?;
+    let bytes = rest[close + 1..]
+        .trim_start_matches(',')
+        .split('|')
+        .next()
+        .and_then(parse_size);
+    Some((
+        phase.trim().to_string(),
+        done.trim().parse().ok()?,
+        total.trim().parse().ok()?,
+        bytes,
+    ))
+}
+
+fn parse_size(s: &str) -> Option<u64> {
+    let mut it = s.split_whitespace();
+    let n: f64 = it.next()?.parse().ok()?;
+    let mul = match it.next()? {
+        "bytes" | "B" => 1.0,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((n * mul) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn git_progress_lines() {
+        assert_eq!(
+            parse_git_progress("Receiving objects:  45% (1234/2742), 12.50 MiB | 2.00 MiB/s"),
+            Some(("Receiving objects".into(), 1234, 2742, Some(13_107_200)))
+        );
+        assert_eq!(
+            parse_git_progress("remote: Counting objects: 100% (10/10), done."),
+            Some(("Counting objects".into(), 10, 10, None))
+        );
+        assert_eq!(parse_git_progress("Cloning into 'xml-p5'..."), None);
+    }
+
+    #[test]
+    fn counter_reports_start_and_end() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let s = seen.clone();
+        let r = Reporter::new(move |p| s.lock().unwrap().push(p.done));
+        r.scope(|| {
+            let c = Counter::new("index test", "files", 3);
+            (0..3).for_each(|_| c.tick());
+        });
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.first(), Some(&0));
+        assert_eq!(seen.last(), Some(&3));
+        assert!(Counter::new("quiet", "files", 1).reporter.is_none());
+    }
+}
@@ -0,0 +1,167 @@
+use crate::path_resolver::daizo_home;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Where a quoted text came from: enough to reproduce the retrieval later.
+#[derive(Clone, Debug, Serialize)]
+pub struct Provenance {
+    pub corpus: String,
+    /// Text id, file stem or URL within the corpus.
+    pub source: Option<String>,
+    /// Commit of the local corpus checkout (None for online sources and plain downloads).
+    pub snapshot: Option<String>,
+    /// Retrieval date, UTC (YYYY-MM-DD).
+    pub retrieved: String,
+    /// Tool name and version, e.g. `daizo-mcp 0.6.1`.
+    pub tool: String,
+}
+
+impl Provenance {
+    /// `root` is the local corpus directory, searched upwards (within the daizo home) for a git checkout.
+    pub fn new(corpus: &str, root: Option<&Path>, source: Option<String>, tool: &str) -> Self {
+        Provenance {
+            corpus: corpus.to_string(),
+            source,
+            snapshot: root.and_then(git_snapshot),
+            retrieved: today_utc(),
+            tool: tool.to_string(),
+        }
+    }
+
+    /// One-line citation footer, e.g.
+    /// `Source: cbeta T0262 | snapshot 1a2b3c4d5e6f | retrieved 2026-10-17 | daizo-mcp 0.6.1`.
+    pub fn footer(&self) -> String {
+        let mut parts = vec![match &self.source {
+            Some(s) => format!("Source: {} {}", self.corpus, s),
+            None => format!("Source: {}", self.corpus),
+        }];
+        if let Some(c) = &self.snapshot {
+            parts.push(format!("snapshot {}", &c[..c.len().min(12)]));
+        }
+        parts.push(format!("retrieved {}", self.retrieved));
+        parts.push(self.tool.clone());
+        parts.join(" | ")
+    }
+}
+
+/// HEAD commit of the git checkout containing `dir`, read from `.git` without running git.
+pub fn git_snapshot(dir: &Path) -> Option<String> {
+    read_head(&git_dir(dir)?)
+}
+
+/// `.git` directory of the checkout containing `dir`, searched upwards within the daizo home.
+pub fn git_dir(dir: &Path) -> Option<PathBuf> {
+    let home = daizo_home();
+    let mut cur = Some(dir);
+    while let Some(d) = cur {
+        let git = d.join(".git");
+        if git.is_dir() {
+            return Some(git);
+        }
+        if d == home {
+            break;
+        }
+        cur = d.parent().filter(|p| p.starts_with(&home));
+    }
+    None
+}
+
+pub(crate) fn read_head(git: &Path) -> Option<String> {
+    let head = std::fs::read_to_string(git.join("HEAD")).ok()?;
+    let head = head.trim();
+    let Some(r) = head.strip_prefix("ref: ") else {
+        return Some(head.to_string());
+    };
+    if let Ok(s) = std::fs::read_to_string(git.join(r)) {
+        return Some(s.trim().to_string());
+    }
+    // Refs may only exist in packed-refs (after gc or a fresh clone).
+    let packed = std::fs::read_to_string(git.join("packed-refs")).ok()?;
+    packed.lines().find_map(|l| {
+        let (sha, name) = l.split_once(' ')?;
+        (name == r).then(|| sha.to_string())
+    })
+}
+
+/// Today's date in UTC as YYYY-MM-DD.
+pub fn today_utc() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    date_utc(secs)
+}
+
+/// UTC date (YYYY-MM-DD) of a Unix timestamp.
+pub fn date_utc(secs: u64) -> String {
+    let (y, m, d) = civil_from_days((secs / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// UTC timestamp (YYYY-MM-DDTHH:MM:SSZ) of a Unix timestamp.
+pub fn datetime_utc(secs: u64) -> String {
+    let t = secs % 86_400;
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        date_utc(secs),
+        t / 3600,
+        t / 60 % 60,
+        t % 60
+    )
+}
+
+/// Proleptic Gregorian date of a day count since 1970-01-01 (H. Hinnant's algorithm).
+fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn footer_and_snapshot() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(20_743), (2026, 10, 17));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(
+            datetime_utc(20_743 * 86_400 + 3_723),
+            "2026-10-17T01:02:03Z"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let git = dir.path().join(".git");
+        std::fs::create_dir_all(&git).unwrap();
+        std::fs::write(git.join("HEAD"), "ref: refs/heads/master\n").unwrap();
+        std::fs::write(
+            git.join("packed-refs"),
+            "# pack-refs\n0123456789abcdef0123456789abcdef01234567 refs/heads/master\n",
+        )
+        .unwrap();
+        assert_eq!(
+            read_head(&git).as_deref(),
+            Some("0123456789abcdef0123456789abcdef01234567")
+        );
+
+        let p = Provenance {
+            corpus: "cbeta".into(),
+            source: Some("T0262".into()),
+            snapshot: read_head(&git),
+            retrieved: "2026-10-17".into(),
+            tool: "daizo-mcp 0.6.1".into(),
+        };
+        assert_eq!(
+            p.footer(),
+            "Source: cbeta T0262 | snapshot 0123456789ab | retrieved 2026-10-17 | daizo-mcp 0.6.1"
+        );
+    }
+}
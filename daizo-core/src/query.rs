@@ -0,0 +1,378 @@
+use crate::analysis::plain_text_for_source;
+use crate::GrepResult;
+use rayon::prelude::*;
+use regex::Regex;
+use std::path::Path;
+
+/// Default distance for a bare `NEAR` without `/n`.
+pub const DEFAULT_NEAR: usize = 10;
+
+/// Parsed boolean query. Terms are indices into `BoolQuery::terms`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BoolExpr {
+    Term(usize),
+    And(Box<BoolExpr>, Box<BoolExpr>),
+    Or(Box<BoolExpr>, Box<BoolExpr>),
+    Not(Box<BoolExpr>),
+    /// Both terms occur within `n` characters of each other (whitespace not counted).
+    Near(usize, usize, usize),
+}
+
+/// Small query language for content search:
+/// `AND` / `OR` / `NOT` (upper case), `( … )`, `"quoted phrases"`, `a NEAR/20 b`.
+/// Adjacent terms without an operator are ANDed.
+#[derive(Clone, Debug)]
+pub struct BoolQuery {
+    pub expr: BoolExpr,
+    pub terms: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Tok {
+    Word(String),
+    And,
+    Or,
+    Not,
+    Near(usize),
+    LParen,
+    RParen,
+}
+
+fn lex(s: &str) -> Result<Vec<Tok>, String> {
+    let mut out = Vec::new();
+    let mut it = s.chars().peekable();
+    while let Some(&c) = it.peek() {
+        if c.is_whitespace() {
+            it.next();
+        } else if c == '(' {
+            it.next();
+            out.push(Tok::LParen);
+        } else if c == ')' {
+            it.next();
+            out.push(Tok::RParen);
+        } else if c == '"' || c == '“' {
+            it.next();
+            let mut w = String::new();
+            let mut closed = false;
+            for ch in it.by_ref() {
+                if ch == '"' || ch == '”' {
+                    closed = true;
+                    break;
+                }
+                w.push(ch);
+            }
+            if !closed {
+                return Err("unterminated quoted phrase".to_string());
+            }
+            if !w.trim().is_empty() {
+                out.push(Tok::Word(w.trim().to_string()));
+            }
+        } else {
+            let mut w = String::new();
+            while let Some(&ch) = it.peek() {
+                if ch.is_whitespace() || ch == '(' || ch == ')' || ch == '"' {
+                    break;
+                }
+                w.push(ch);
+                it.next();
+            }
+            let tok = match w.as_str() {
+                "AND" => Tok::And,
+                "OR" => Tok::Or,
+                "NOT" => Tok::Not,
+                "NEAR" => Tok::Near(DEFAULT_NEAR),
+                _ => match w.strip_prefix("NEAR/") {
+                    Some(n) => Tok::Near(
+                        n.parse()
+                            .map_err(|_| format!("invalid NEAR distance: {}", w))?,
+                    ),
+                    None => Tok::Word(w),
+                },
+            };
+            out.push(tok);
+        }
+    }
+    Ok(out)
+}
+
+struct Parser {
+    toks: Vec<Tok>,
+    pos: usize,
+    terms: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos)
+    }
+
+    fn term_index(&mut self, w: String) -> usize {
+        if let Some(i) = self.terms.iter().position(|t| *t == w) {
+            return i;
+        }
+        self.terms.push(w);
+        self.terms.len() - 1
+    }
+
+    fn parse_or(&mut self) -> Result<BoolExpr, String> {
+        let mut lhs = self.parse_and()?;
+        while self.peek() == Some(&Tok::Or) {
+            self.pos += 1;
+            let rhs = self.parse_and()?;
+            lhs = BoolExpr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<BoolExpr, String> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Tok::And) => self.pos += 1,
+                Some(Tok::Word(_)) | Some(Tok::Not) | Some(Tok::LParen) => {}
+                _ => break,
+            }
+            let rhs = self.parse_unary()?;
+            lhs = BoolExpr::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<BoolExpr, String> {
+        if self.peek() == Some(&Tok::Not) {
+            self.pos += 1;
+            return Ok(BoolExpr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_near()
+    }
+
+    fn parse_near(&mut self) -> Result<BoolExpr, String> {
+        let first = self.parse_atom()?;
+        let Some(Tok::Near(_)) = self.peek() else {
+            return Ok(first);
+        };
+        let BoolExpr::Term(mut prev) = first else {
+            return Err("NEAR operands must be terms or phrases".to_string());
+        };
+        let mut acc: Option<BoolExpr> = None;
+        while let Some(Tok::Near(n)) = self.peek().cloned() {
+            self.pos += 1;
+            let BoolExpr::Term(next) = self.parse_atom()? else {
+                return Err("NEAR operands must be terms or phrases".to_string());
+            };
+            let near = BoolExpr::Near(prev, next, n);
+            acc = Some(match acc {
+                Some(a) => BoolExpr::And(Box::new(a), Box::new(near)),
+                None => near,
+            });
+            prev = next;
+        }
+        Ok(acc.expect("at least one NEAR"))
+    }
+
+    fn parse_atom(&mut self) -> Result<BoolExpr, String> {
+        match self.toks.get(self.pos).cloned() {
+            Some(Tok::Word(w)) => {
+                self.pos += 1;
+                Ok(BoolExpr::Term(self.term_index(w)))
+            }
+            Some(Tok::LParen) => {
+                self.pos += 1;
+                let e = self.parse_or()?;
+                if self.peek() != Some(&Tok::RParen) {
+                    return Err("missing ')'".to_string());
+                }
+                self.pos += 1;
+                Ok(e)
+            }
+            Some(t) => Err(format!("unexpected {:?}", t)),
+            None => Err("unexpected end of query".to_string()),
+        }
+    }
+}
+
+impl BoolQuery {
+    pub fn parse(s: &str) -> Result<BoolQuery, String> {
+        let mut p = Parser {
+            toks: lex(s)?,
+            pos: 0,
+            terms: Vec::new(),
+        };
+        if p.toks.is_empty() {
+            return Err("empty query".to_string());
+        }
+        let expr = p.parse_or()?;
+        if p.pos < p.toks.len() {
+            return Err(format!("unexpected {:?}", p.toks[p.pos]));
+        }
+        let q = BoolQuery {
+            expr,
+            terms: p.terms,
+        };
+        if q.positive_terms().is_empty() {
+            return Err("query needs at least one term outside NOT".to_string());
+        }
+        Ok(q)
+    }
+
+    /// Indices of terms that are not under a NOT; a matching file must contain at least one.
+    pub fn positive_terms(&self) -> Vec<usize> {
+        fn walk(e: &BoolExpr, neg: bool, out: &mut Vec<usize>) {
+            match e {
+                BoolExpr::Term(i) => {
+                    if !neg && !out.contains(i) {
+                        out.push(*i)
+                    }
+                }
+                BoolExpr::And(a, b) | BoolExpr::Or(a, b) => {
+                    walk(a, neg, out);
+                    walk(b, neg, out);
+                }
+                BoolExpr::Not(a) => walk(a, !neg, out),
+                BoolExpr::Near(a, b, _) => {
+                    walk(&BoolExpr::Term(*a), neg, out);
+                    walk(&BoolExpr::Term(*b), neg, out);
+                }
+            }
+        }
+        let mut out = Vec::new();
+        walk(&self.expr, false, &mut out);
+        out
+    }
+
+    /// Alternation of the positive terms, used as the grep prefilter for candidate files.
+    pub fn prefilter_regex(&self, literal: impl Fn(&str) -> String) -> String {
+        let parts: Vec<String> = self
+            .positive_terms()
+            .into_iter()
+            .map(|i| literal(&self.terms[i]))
+            .collect();
+        format!("(?:{})", parts.join("|"))
+    }
+
+    /// Compile each term with `literal` (term → regex source); matching is case-insensitive.
+    pub fn compile(&self, literal: impl Fn(&str) -> String) -> Result<CompiledBoolQuery, String> {
+        let regexes = self
+            .terms
+            .iter()
+            .map(|t| Regex::new(&format!("(?i){}", literal(t))).map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(CompiledBoolQuery {
+            expr: self.expr.clone(),
+            regexes,
+        })
+    }
+}
+
+pub struct CompiledBoolQuery {
+    expr: BoolExpr,
+    regexes: Vec<Regex>,
+}
+
+fn gap_chars(text: &str, a: (usize, usize), b: (usize, usize)) -> usize {
+    let (from, to) = if a.1 <= b.0 {
+        (a.1, b.0)
+    } else if b.1 <= a.0 {
+        (b.1, a.0)
+    } else {
+        return 0;
+    };
+    text[from..to]
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .count()
+}
+
+impl CompiledBoolQuery {
+    pub fn matches(&self, text: &str) -> bool {
+        self.eval(&self.expr, text)
+    }
+
+    fn eval(&self, e: &BoolExpr, text: &str) -> bool {
+        match e {
+            BoolExpr::Term(i) => self.regexes[*i].is_match(text),
+            BoolExpr::And(a, b) => self.eval(a, text) && self.eval(b, text),
+            BoolExpr::Or(a, b) => self.eval(a, text) || self.eval(b, text),
+            BoolExpr::Not(a) => !self.eval(a, text),
+            BoolExpr::Near(a, b, n) => {
+                let spans = |i: usize| -> Vec<(usize, usize)> {
+                    self.regexes[i]
+                        .find_iter(text)
+                        .map(|m| (m.start(), m.end()))
+                        .collect()
+                };
+                let (sa, sb) = (spans(*a), spans(*b));
+                if sa.is_empty() || sb.is_empty() {
+                    return false;
+                }
+                // Both lists are in text order; for each `a` only nearby `b` spans need checking.
+                let mut j0 = 0usize;
+                for &x in &sa {
+                    while j0 < sb.len() && sb[j0].1 < x.0 && gap_chars(text, sb[j0], x) > *n {
+                        j0 += 1;
+                    }
+                    for &y in &sb[j0..] {
+                        if gap_chars(text, x, y) <= *n {
+                            return true;
+                        }
+                        if y.0 >= x.1 {
+                            break;
+                        }
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    /// Keep grep hits whose plain text satisfies the query (prefilter results from `*_grep`).
+    pub fn filter_results(&self, source: &str, results: Vec<GrepResult>) -> Vec<GrepResult> {
+        let cancel = crate::cancel::CancelToken::current();
+        crate::scan::run(|| {
+            results
+                .into_par_iter()
+                .filter(|r| {
+                    !cancel.is_cancelled()
+                        && plain_text_for_source(source, Path::new(&r.file_path), None, false)
+                            .map(|t| self.matches(&t))
+                            .unwrap_or(false)
+                })
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(q: &str) -> CompiledBoolQuery {
+        BoolQuery::parse(q).unwrap().compile(regex::escape).unwrap()
+    }
+
+    #[test]
+    fn parses_operators_and_phrases() {
+        let q = BoolQuery::parse(r#"("色即是空" OR 般若) 菩薩 NOT 涅槃"#).unwrap();
+        assert_eq!(q.terms, vec!["色即是空", "般若", "菩薩", "涅槃"]);
+        assert_eq!(q.positive_terms(), vec![0, 1, 2]);
+        assert_eq!(
+            q.prefilter_regex(|t| t.to_string()),
+            "(?:色即是空|般若|菩薩)"
+        );
+        assert!(BoolQuery::parse("NOT 空").is_err());
+        assert!(BoolQuery::parse("(空 OR 色").is_err());
+        assert!(BoolQuery::parse("(a OR b) NEAR/3 c").is_err());
+    }
+
+    #[test]
+    fn evaluates_boolean_and_near() {
+        let text = "觀自在菩薩行深般若波羅蜜多時，照見五蘊皆空";
+        assert!(compile("菩薩 AND 般若").matches(text));
+        assert!(!compile("菩薩 NOT 般若").matches(text));
+        assert!(compile("涅槃 OR 五蘊").matches(text));
+        assert!(compile("菩薩 NEAR/3 般若").matches(text));
+        assert!(!compile("菩薩 NEAR/3 五蘊").matches(text));
+        assert!(compile("五蘊 NEAR/20 菩薩").matches(text));
+        assert!(compile(r#""evam me" NEAR/2 sutam"#).matches("Evam me sutam"));
+    }
+}
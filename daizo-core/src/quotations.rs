@@ -0,0 +1,444 @@
+//! Quotation finder: where else in CBETA a passage occurs, whole or nearly so.
+//!
+//! Each file's body text is reduced to its Han characters (variants folded to one form,
+//! punctuation and notes dropped) and cut into overlapping shingles of [`SHINGLE`] characters.
+//! Winnowing keeps the smallest shingle hash of every [`WINDOW`] consecutive ones, so any
+//! shared run of `WINDOW + SHINGLE - 1` characters or more is guaranteed to share a
+//! fingerprint. The fingerprints, each with its file and line, are sorted by hash into
+//! `~/.daizo/cache/cbeta-quotes.bin` and rebuilt when the index changes.
+//!
+//! A query looks up every shingle of the passage, groups the postings by file and nearby
+//! lines, and rereads the best regions to score them by containment: the share of the
+//! passage's shingles found in the region (1.0 for a verbatim quotation), trimmed to the lines
+//! those shingles fall on.
+
+use crate::fetch_cache::write_atomic;
+use crate::variants::variant_table;
+use crate::{progress, IndexEntry};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Shingle width in Han characters.
+pub const SHINGLE: usize = 5;
+/// Winnowing window in shingles.
+pub const WINDOW: usize = 16;
+/// Shortest passage (in Han characters) that is always found where it occurs verbatim.
+pub const GUARANTEED: usize = WINDOW + SHINGLE - 1;
+/// Shingles occurring in more places than this (formulae such as 如是我聞一) are not
+/// looked up.
+const MAX_POSTINGS: usize = 4000;
+/// Matched lines at most this far apart belong to one region.
+const GAP: u32 = 3;
+const LINE_BITS: u32 = 18;
+
+fn is_han(c: char) -> bool {
+    matches!(c as u32, 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x3FFFF)
+}
+
+/// Han characters of `text` in canonical variant form.
+pub fn fold(text: &str) -> Vec<char> {
+    let v = variant_table();
+    text.chars()
+        .filter(|c| is_han(*c))
+        .map(|c| v.canonical(c))
+        .collect()
+}
+
+fn hash(chars: &[char]) -> u32 {
+    let mut h: u32 = 0x811c_9dc5;
+    for c in chars {
+        for b in (*c as u32).to_le_bytes() {
+            h ^= b as u32;
+            h = h.wrapping_mul(0x0100_0193);
+        }
+    }
+    h
+}
+
+fn shingles(chars: &[char]) -> Vec<u32> {
+    chars.windows(SHINGLE).map(hash).collect()
+}
+
+/// Positions of the fingerprints winnowing keeps: the rightmost minimum of each window.
+fn winnow(hashes: &[u32]) -> Vec<usize> {
+    let mut out: Vec<usize> = Vec::new();
+    if hashes.len() <= WINDOW {
+        if let Some(i) = (0..hashes.len()).rev().min_by_key(|&i| hashes[i]) {
+            out.push(i);
+        }
+        return out;
+    }
+    for start in 0..=hashes.len() - WINDOW {
+        let m = (start..start + WINDOW)
+            .rev()
+            .min_by_key(|&i| hashes[i])
+            .unwrap_or(start);
+        if out.last() != Some(&m) {
+            out.push(m);
+        }
+    }
+    out
+}
+
+/// Plain text of each line of a CBETA file, from its body on.
+fn line_texts(xml: &str) -> Vec<(usize, String)> {
+    let gaiji = crate::cbeta_gaiji_map_fast(xml);
+    let lines: Vec<&str> = xml.lines().collect();
+    let start = lines
+        .iter()
+        .position(|l| l.contains("</teiHeader>"))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    lines
+        .iter()
+        .enumerate()
+        .skip(start)
+        .map(|(i, l)| {
+            (
+                i + 1,
+                crate::extract_cbeta_plain_from_snippet(l, &gaiji, false),
+            )
+        })
+        .collect()
+}
+
+/// Winnowed fingerprints of a file as `(hash, line)`.
+pub fn fingerprints(xml: &str) -> Vec<(u32, u32)> {
+    let mut chars = Vec::new();
+    let mut line_of = Vec::new();
+    for (line, text) in line_texts(xml) {
+        for c in fold(&text) {
+            chars.push(c);
+            line_of.push(line as u32);
+        }
+    }
+    let hashes = shingles(&chars);
+    winnow(&hashes)
+        .into_iter()
+        .map(|i| (hashes[i], line_of[i]))
+        .collect()
+}
+
+/// How many of the shingles `query` holds occur in `lines`, and the first and last line
+/// that those shingles touch.
+fn matched_span(
+    query: &HashSet<u32>,
+    lines: &[&(usize, String)],
+) -> (usize, Option<(usize, usize)>) {
+    let mut chars = Vec::new();
+    let mut line_of = Vec::new();
+    for (n, t) in lines {
+        for ch in fold(t) {
+            chars.push(ch);
+            line_of.push(*n);
+        }
+    }
+    let mut found = HashSet::new();
+    let mut span: Option<(usize, usize)> = None;
+    for (i, h) in shingles(&chars).into_iter().enumerate() {
+        if query.contains(&h) {
+            found.insert(h);
+            let (a, b) = (line_of[i], line_of[i + SHINGLE - 1]);
+            span = Some(span.map_or((a, b), |(s, e)| (s.min(a), e.max(b))));
+        }
+    }
+    (found.len(), span)
+}
+
+/// A region of a file that contains much of the passage.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Quotation {
+    pub path: String,
+    /// First and last line of the region (1-based).
+    pub start_line: usize,
+    pub end_line: usize,
+    /// Containment of the passage in the region.
+    pub score: f32,
+    /// Distinct fingerprints of the passage found in the region.
+    pub hits: usize,
+    /// Plain text of the region.
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct QuoteFile {
+    index_modified: u64,
+    index_bytes: u64,
+    paths: Vec<String>,
+}
+
+/// Fingerprints of a corpus sorted by hash; each record is a little-endian `u32` hash and a
+/// `u32` holding the file number above [`LINE_BITS`] bits of line.
+pub struct QuoteIndex {
+    paths: Vec<String>,
+    records: Records,
+}
+
+enum Records {
+    Mapped(memmap2::Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Records {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Records::Mapped(m) => m,
+            Records::Owned(v) => v,
+        }
+    }
+}
+
+fn index_stamp(index_file: &Path) -> Option<(u64, u64)> {
+    let m = std::fs::metadata(index_file).ok()?;
+    let secs = m
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some((secs, m.len()))
+}
+
+impl QuoteIndex {
+    /// Fingerprint every file of `entries`.
+    pub fn build(entries: &[IndexEntry]) -> Self {
+        let counter = progress::Counter::new("fingerprint cbeta", "files", entries.len());
+        let max_line = (1u32 << LINE_BITS) - 1;
+        let mut all: Vec<(u32, u32)> = crate::scan::run(|| {
+            entries
+                .par_iter()
+                .enumerate()
+                .flat_map_iter(|(f, e)| {
+                    counter.tick();
+                    let xml =
+                        crate::analysis::read_text_file(Path::new(&e.path)).unwrap_or_default();
+                    fingerprints(&xml)
+                        .into_iter()
+                        .map(move |(h, line)| (h, (f as u32) << LINE_BITS | line.min(max_line)))
+                })
+                .collect()
+        });
+        all.par_sort_unstable();
+        let records = all
+            .iter()
+            .flat_map(|(h, loc)| h.to_le_bytes().into_iter().chain(loc.to_le_bytes()))
+            .collect();
+        QuoteIndex {
+            paths: entries.iter().map(|e| e.path.clone()).collect(),
+            records: Records::Owned(records),
+        }
+    }
+
+    /// The index stored in `file` (`.bin`, with a `.json` list of files beside it) when it was
+    /// built from `index_file` as it is now; otherwise built from `entries` and stored.
+    pub fn load_or_build(entries: &[IndexEntry], file: &Path, index_file: &Path) -> Self {
+        let stamp = index_stamp(index_file);
+        let side = file.with_extension("json");
+        let stored: Option<QuoteFile> = std::fs::read(&side)
+            .ok()
+            .and_then(|b| serde_json::from_slice(&b).ok());
+        if let (Some(s), Some((m, b))) = (stored, stamp) {
+            if s.index_modified == m && s.index_bytes == b && s.paths.len() == entries.len() {
+                // SAFETY: the file is only replaced by rename (write_atomic), never rewritten
+                // in place, so the mapping stays valid while it is open.
+                let map = std::fs::File::open(file)
+                    .and_then(|f| unsafe { memmap2::Mmap::map(&f) })
+                    .ok();
+                if let Some(map) = map.filter(|m| m.len() % 8 == 0) {
+                    return QuoteIndex {
+                        paths: s.paths,
+                        records: Records::Mapped(map),
+                    };
+                }
+            }
+        }
+        let q = QuoteIndex::build(entries);
+        if let Some((index_modified, index_bytes)) = stamp {
+            let side_file = QuoteFile {
+                index_modified,
+                index_bytes,
+                paths: q.paths.clone(),
+            };
+            if write_atomic(file, q.records.bytes()).is_ok() {
+                if let Ok(b) = serde_json::to_vec(&side_file) {
+                    let _ = write_atomic(&side, &b);
+                }
+            }
+        }
+        q
+    }
+
+    /// Number of fingerprints.
+    pub fn len(&self) -> usize {
+        self.records.bytes().len() / 8
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn record(&self, i: usize) -> (u32, u32) {
+        let b = &self.records.bytes()[i * 8..i * 8 + 8];
+        (
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            u32::from_le_bytes([b[4], b[5], b[6], b[7]]),
+        )
+    }
+
+    /// Locations (`file`, `line`) of fingerprint `h`.
+    fn postings(&self, h: u32) -> Vec<(usize, u32)> {
+        let n = self.len();
+        let (mut lo, mut hi) = (0, n);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.record(mid).0 < h {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        let mut out = Vec::new();
+        let mut i = lo;
+        while i < n {
+            let (rh, loc) = self.record(i);
+            if rh != h {
+                break;
+            }
+            out.push(((loc >> LINE_BITS) as usize, loc & ((1 << LINE_BITS) - 1)));
+            i += 1;
+        }
+        out
+    }
+
+    /// Regions that quote `passage`, best first, with a containment of at least `min_score`.
+    pub fn find(&self, passage: &str, limit: usize, min_score: f32) -> Vec<Quotation> {
+        let query = fold(passage);
+        let query_set: HashSet<u32> = shingles(&query).into_iter().collect();
+        let mut seen = HashSet::new();
+        // file → (line, hash) of each fingerprint of the passage found there
+        let mut by_file: HashMap<usize, Vec<(u32, u32)>> = HashMap::new();
+        for h in shingles(&query) {
+            if !seen.insert(h) {
+                continue;
+            }
+            let p = self.postings(h);
+            if p.len() > MAX_POSTINGS {
+                continue;
+            }
+            for (f, line) in p {
+                by_file.entry(f).or_default().push((line, h));
+            }
+        }
+        let mut regions: Vec<(usize, u32, u32, usize)> = Vec::new();
+        for (f, mut hits) in by_file {
+            hits.sort_unstable();
+            let mut start = 0;
+            for i in 1..=hits.len() {
+                if i == hits.len() || hits[i].0 > hits[i - 1].0 + GAP {
+                    let distinct: HashSet<u32> = hits[start..i].iter().map(|x| x.1).collect();
+                    regions.push((f, hits[start].0, hits[i - 1].0, distinct.len()));
+                    start = i;
+                }
+            }
+        }
+        regions.sort_by(|a, b| b.3.cmp(&a.3).then_with(|| (a.0, a.1).cmp(&(b.0, b.1))));
+        regions.truncate(limit.saturating_mul(3).max(10));
+        // Reread each region with a line of margin (the passage may start before the first
+        // fingerprint) and score it exactly.
+        let mut texts: HashMap<usize, Vec<(usize, String)>> = HashMap::new();
+        let mut out: Vec<Quotation> = regions
+            .into_iter()
+            .filter_map(|(f, first, last, hits)| {
+                let lines = texts.entry(f).or_insert_with(|| {
+                    crate::analysis::read_text_file(Path::new(&self.paths[f]))
+                        .map(|x| line_texts(&x))
+                        .unwrap_or_default()
+                });
+                let (from, to) = (first.saturating_sub(1) as usize, last as usize + 1);
+                let region: Vec<&(usize, String)> = lines
+                    .iter()
+                    .filter(|(n, t)| *n >= from && *n <= to && !t.trim().is_empty())
+                    .collect();
+                let (shared, span) = matched_span(&query_set, &region);
+                let (start, end) = span?;
+                let score = shared as f32 / query_set.len() as f32;
+                (score >= min_score).then(|| Quotation {
+                    path: self.paths[f].clone(),
+                    start_line: start,
+                    end_line: end,
+                    score,
+                    hits,
+                    text: region
+                        .iter()
+                        .filter(|(n, _)| *n >= start && *n <= end)
+                        .map(|(_, t)| t.trim())
+                        .collect(),
+                })
+            })
+            .collect();
+        out.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| b.hits.cmp(&a.hits))
+                .then_with(|| (&a.path, a.start_line).cmp(&(&b.path, b.start_line)))
+        });
+        out.truncate(limit);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_passages_are_found_with_their_lines() {
+        let sutra = "<TEI><teiHeader/>\n<text><body>\n\
+<p><lb n=\"0001a01\"/>舍利子色不異空空不異色色即是空空即是色受想行識亦復如是</p>\n\
+<p><lb n=\"0001a02\"/>舍利子是諸法空相不生不滅不垢不淨不增不減</p>\n</body></text></TEI>";
+        let commentary = "<TEI><teiHeader/>\n<text><body>\n\
+<p><lb n=\"0002a01\"/>釋曰此明五蘊皆空之義</p>\n\
+<p><lb n=\"0002a02\"/>經云。色不異空。空不異色。<note>校</note>色即是空。空即是色。</p>\n\
+<p><lb n=\"0002a03\"/>受想行識亦復如是。此顯真空</p>\n\
+<p><lb n=\"0002a04\"/>一切眾生皆有佛性</p>\n</body></text></TEI>";
+        let dir = tempfile::tempdir().unwrap();
+        let mut entries = Vec::new();
+        for (id, xml) in [("T0251", sutra), ("T1710", commentary)] {
+            let p = dir.path().join(format!("{}.xml", id));
+            std::fs::write(&p, xml).unwrap();
+            entries.push(IndexEntry {
+                id: id.into(),
+                title: id.into(),
+                path: p.to_string_lossy().into(),
+                meta: None,
+            });
+        }
+        assert!(fingerprints(sutra).iter().all(|(_, l)| *l == 3 || *l == 4));
+
+        let index = dir.path().join("cbeta-index.json");
+        std::fs::write(&index, "[]").unwrap();
+        let file = dir.path().join("cbeta-quotes.bin");
+        let built = QuoteIndex::load_or_build(&entries, &file, &index);
+        assert!(!built.is_empty() && file.exists());
+        let q = QuoteIndex::load_or_build(&entries, &file, &index);
+        assert!(matches!(q.records, Records::Mapped(_)) && q.len() == built.len());
+
+        // Punctuation and the note do not hide the quotation.
+        let hits = q.find(
+            "色不異空，空不異色；色即是空，空即是色。受想行識，亦復如是。",
+            5,
+            0.5,
+        );
+        assert_eq!(hits.len(), 2, "{:?}", hits);
+        assert!(hits.iter().all(|h| h.score > 0.99));
+        let c = hits.iter().find(|h| h.path.ends_with("T1710.xml")).unwrap();
+        assert_eq!((c.start_line, c.end_line), (4, 5));
+        assert!(c.text.starts_with("經云") && !c.text.contains('校'));
+        // Nor does a variant form (浄 for 淨).
+        let v = q.find("舍利子是諸法空相不生不滅不垢不浄不增不減", 5, 0.5);
+        assert_eq!((v.len(), v[0].start_line, v[0].score), (1, 4, 1.0));
+        assert!(q.find("南無阿彌陀佛", 5, 0.5).is_empty());
+    }
+}
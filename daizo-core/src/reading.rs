@@ -0,0 +1,304 @@
+//! Reading lists: the works of a collection in canonical order.
+//!
+//! A collection is named the way readers name it: a Taishō division (`阿含部`, `Āgama`), a
+//! CBETA canon or volume (`T`, `T08`), a Pāli nikāya (`MN`, `Majjhima Nikāya`) or, in any
+//! corpus, a file-stem prefix. [`members`] returns its works in canon order (Taishō number,
+//! VRI file order) so that an agent can read through it one work at a time, resuming after
+//! the last id it read.
+
+use crate::text_utils::normalized;
+use crate::{stem_from, IndexEntry};
+use std::path::Path;
+
+/// A division of the Taishō canon by text number.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Division {
+    pub zh: &'static str,
+    pub en: &'static str,
+    pub first: u32,
+    pub last: u32,
+}
+
+const fn div(zh: &'static str, en: &'static str, first: u32, last: u32) -> Division {
+    Division {
+        zh,
+        en,
+        first,
+        last,
+    }
+}
+
+pub const TAISHO_DIVISIONS: &[Division] = &[
+    div("阿含部", "Āgama", 1, 151),
+    div("本緣部", "Jātaka and Avadāna", 152, 219),
+    div("般若部", "Prajñāpāramitā", 220, 261),
+    div("法華部", "Lotus", 262, 277),
+    div("華嚴部", "Avataṃsaka", 278, 309),
+    div("寶積部", "Ratnakūṭa", 310, 373),
+    div("涅槃部", "Nirvāṇa", 374, 396),
+    div("大集部", "Mahāsaṃnipāta", 397, 424),
+    div("經集部", "Sūtra Collection", 425, 847),
+    div("密教部", "Esoteric", 848, 1420),
+    div("律部", "Vinaya", 1421, 1504),
+    div("釋經論部", "Sūtra Treatises", 1505, 1535),
+    div("毘曇部", "Abhidharma", 1536, 1563),
+    div("中觀部", "Madhyamaka", 1564, 1578),
+    div("瑜伽部", "Yogācāra", 1579, 1627),
+    div("論集部", "Treatise Collection", 1628, 1692),
+    div("經疏部", "Sūtra Commentaries", 1693, 1803),
+    div("律疏部", "Vinaya Commentaries", 1804, 1815),
+    div("論疏部", "Treatise Commentaries", 1816, 1850),
+    div("諸宗部", "Schools", 1851, 2025),
+    div("史傳部", "History and Biography", 2026, 2120),
+    div("事彙部", "Encyclopedias", 2121, 2136),
+    div("外教部", "Non-Buddhist Teachings", 2137, 2144),
+    div("目錄部", "Catalogues", 2145, 2184),
+];
+
+/// Pāli collections by VRI file-stem prefix: code, name, prefix.
+const PALI_COLLECTIONS: &[(&str, &str, &str)] = &[
+    ("Vin", "Vinaya Piṭaka", "vin"),
+    ("DN", "Dīgha Nikāya", "s01"),
+    ("MN", "Majjhima Nikāya", "s02"),
+    ("SN", "Saṃyutta Nikāya", "s03"),
+    ("AN", "Aṅguttara Nikāya", "s04"),
+    ("KN", "Khuddaka Nikāya", "s05"),
+    ("Abh", "Abhidhamma Piṭaka", "abh"),
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    All,
+    Taisho(u32, u32),
+    Canon(String),
+    Volume(String),
+    /// Root texts (`.mul`) under a VRI prefix.
+    Pali(&'static str),
+    Prefix(String),
+}
+
+/// A resolved collection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Collection {
+    /// How the collection is reported, e.g. `阿含部 (Āgama, T1–151)`.
+    pub label: String,
+    kind: Kind,
+}
+
+fn cbeta_canon(e: &IndexEntry) -> String {
+    e.meta
+        .as_ref()
+        .and_then(|m| m.get("canon").cloned())
+        .unwrap_or_else(|| {
+            e.id.chars()
+                .take_while(|c| c.is_ascii_alphabetic())
+                .collect()
+        })
+}
+
+fn cbeta_number(e: &IndexEntry) -> u32 {
+    e.meta
+        .as_ref()
+        .and_then(|m| m.get("nnum"))
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(u32::MAX)
+}
+
+/// The collection `name` stands for in `corpus`; empty or `all` is the whole corpus. None
+/// when the name is not recognized.
+pub fn resolve(corpus: &str, name: &str) -> Option<Collection> {
+    let name = name.trim();
+    if name.is_empty() || name.eq_ignore_ascii_case("all") {
+        return Some(Collection {
+            label: format!("all of {}", corpus),
+            kind: Kind::All,
+        });
+    }
+    let folded = normalized(name);
+    match corpus {
+        "cbeta" => {
+            let short = name.trim_end_matches('部');
+            if let Some(d) = TAISHO_DIVISIONS.iter().find(|d| {
+                d.zh == name || d.zh.trim_end_matches('部') == short || normalized(d.en) == folded
+            }) {
+                return Some(Collection {
+                    label: format!("{} ({}, T{}–{})", d.zh, d.en, d.first, d.last),
+                    kind: Kind::Taisho(d.first, d.last),
+                });
+            }
+            let letters: String = name
+                .chars()
+                .take_while(|c| c.is_ascii_alphabetic())
+                .collect();
+            let rest = &name[letters.len()..];
+            // CBETA canon codes are one or two letters (T, X, GA, ZW, …).
+            if !(1..=2).contains(&letters.len()) || !rest.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            let canon = letters.to_ascii_uppercase();
+            Some(if rest.is_empty() {
+                Collection {
+                    label: format!("canon {}", canon),
+                    kind: Kind::Canon(canon),
+                }
+            } else {
+                let vol = format!("{}{}", canon, rest);
+                Collection {
+                    label: format!("volume {}", vol),
+                    kind: Kind::Volume(vol),
+                }
+            })
+        }
+        "tipitaka" => {
+            if let Some((code, full, prefix)) = PALI_COLLECTIONS.iter().find(|(code, full, _)| {
+                code.eq_ignore_ascii_case(name)
+                    || normalized(full) == folded
+                    || normalized(full.split(' ').next().unwrap_or("")) == folded
+            }) {
+                return Some(Collection {
+                    label: format!("{} ({})", full, code),
+                    kind: Kind::Pali(prefix),
+                });
+            }
+            Some(Collection {
+                label: format!("files {}*", name.to_lowercase()),
+                kind: Kind::Prefix(name.to_lowercase()),
+            })
+        }
+        _ => Some(Collection {
+            label: format!("files {}*", name.to_lowercase()),
+            kind: Kind::Prefix(name.to_lowercase()),
+        }),
+    }
+}
+
+impl Collection {
+    fn contains(&self, corpus: &str, e: &IndexEntry) -> bool {
+        let stem = || stem_from(Path::new(&e.path)).to_lowercase();
+        match &self.kind {
+            Kind::All => true,
+            Kind::Taisho(first, last) => {
+                cbeta_canon(e) == "T" && (*first..=*last).contains(&cbeta_number(e))
+            }
+            Kind::Canon(c) => cbeta_canon(e) == *c,
+            Kind::Volume(v) => e.id.split('n').next() == Some(v.as_str()),
+            Kind::Pali(p) => {
+                let s = stem();
+                s.starts_with(p) && s.ends_with(".mul")
+            }
+            Kind::Prefix(p) => corpus != "cbeta" && stem().starts_with(p.as_str()),
+        }
+    }
+}
+
+/// Sort key of a work in canon order: CBETA by canon and text number, others by file stem.
+fn order_key(corpus: &str, e: &IndexEntry) -> (String, u32, String) {
+    if corpus == "cbeta" {
+        (cbeta_canon(e), cbeta_number(e), e.id.clone())
+    } else {
+        (String::new(), 0, stem_from(Path::new(&e.path)))
+    }
+}
+
+/// The works of `c` in canon order.
+pub fn members<'a>(corpus: &str, c: &Collection, idx: &'a [IndexEntry]) -> Vec<&'a IndexEntry> {
+    let mut v: Vec<&IndexEntry> = idx.iter().filter(|e| c.contains(corpus, e)).collect();
+    v.sort_by_cached_key(|e| order_key(corpus, e));
+    v
+}
+
+/// Whether `id` names `e`: its index id, its file stem or, for CBETA, the number without
+/// the volume (`T0262` for `T09n0262`).
+pub fn names(corpus: &str, e: &IndexEntry, id: &str) -> bool {
+    let id = id.trim();
+    if e.id.eq_ignore_ascii_case(id) || stem_from(Path::new(&e.path)).eq_ignore_ascii_case(id) {
+        return true;
+    }
+    corpus == "cbeta"
+        && e.id.split_once('n').is_some_and(|(vol, num)| {
+            let canon: String = vol
+                .chars()
+                .take_while(|c| c.is_ascii_alphabetic())
+                .collect();
+            format!("{}{}", canon, num).eq_ignore_ascii_case(id)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cbeta(id: &str, nnum: &str) -> IndexEntry {
+        let canon: String = id.chars().take_while(|c| c.is_ascii_alphabetic()).collect();
+        IndexEntry {
+            id: id.into(),
+            title: id.into(),
+            path: format!("/c/{}/{}.xml", canon, id),
+            meta: Some(
+                [("canon", canon.as_str()), ("nnum", nnum)]
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn pali(stem: &str) -> IndexEntry {
+        IndexEntry {
+            id: stem.into(),
+            title: stem.into(),
+            path: format!("/p/{}.xml", stem),
+            meta: None,
+        }
+    }
+
+    #[test]
+    fn collections_list_works_in_canon_order() {
+        let idx = vec![
+            cbeta("T02n0099", "0099"),
+            cbeta("T01n0026", "0026"),
+            cbeta("T01n0001", "0001"),
+            cbeta("T08n0251", "0251"),
+            cbeta("X01n0001", "0001"),
+        ];
+        for name in ["阿含部", "阿含", "agama", "Āgama"] {
+            let c = resolve("cbeta", name).unwrap();
+            let ids: Vec<&str> = members("cbeta", &c, &idx)
+                .iter()
+                .map(|e| e.id.as_str())
+                .collect();
+            assert_eq!(ids, ["T01n0001", "T01n0026", "T02n0099"], "{name}");
+        }
+        let t01 = resolve("cbeta", "t01").unwrap();
+        assert_eq!(t01.label, "volume T01");
+        assert_eq!(members("cbeta", &t01, &idx).len(), 2);
+        assert_eq!(
+            members("cbeta", &resolve("cbeta", "T").unwrap(), &idx).len(),
+            4
+        );
+        assert_eq!(
+            members("cbeta", &resolve("cbeta", "").unwrap(), &idx)[4].id,
+            "X01n0001"
+        );
+        assert!(resolve("cbeta", "nowhere").is_none());
+        assert!(names("cbeta", &idx[0], "T0099") && names("cbeta", &idx[0], "t02n0099"));
+        assert!(!names("cbeta", &idx[0], "T0026"));
+
+        let p = vec![
+            pali("s0202m.mul"),
+            pali("s0201a.att"),
+            pali("s0201m.mul"),
+            pali("s0101m.mul"),
+        ];
+        for name in ["MN", "Majjhima Nikāya", "majjhima"] {
+            let c = resolve("tipitaka", name).unwrap();
+            let ids: Vec<&str> = members("tipitaka", &c, &p)
+                .iter()
+                .map(|e| e.id.as_str())
+                .collect();
+            assert_eq!(ids, ["s0201m.mul", "s0202m.mul"], "{name}");
+        }
+        let pre = resolve("tipitaka", "s0201").unwrap();
+        assert_eq!(members("tipitaka", &pre, &p).len(), 2);
+    }
+}
@@ -0,0 +1,189 @@
+//! Relevance ranking of full-text search results.
+//!
+//! The grep functions only know how often a file matched. [`rank`] scores each file from
+//! signals that are cheap to get at that point: match density against the text size, whether
+//! the match also appears in the title, matches in headings, and how early in the work (juan)
+//! the matches fall.
+
+use crate::text_utils::normalized;
+use crate::{config, GrepResult};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Order of search results (`sortBy`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortBy {
+    /// Highest relevance score first.
+    #[default]
+    Relevance,
+    /// Most matches first (CBETA keeps Taishō works ahead), the order before scoring existed.
+    Matches,
+    /// File id, ascending.
+    Id,
+}
+
+impl SortBy {
+    /// Parse a `sortBy` value; unknown values give the default.
+    pub fn parse(s: Option<&str>) -> SortBy {
+        match s.map(|s| s.trim().to_ascii_lowercase()).as_deref() {
+            Some("matches") | Some("count") => SortBy::Matches,
+            Some("id") => SortBy::Id,
+            _ => SortBy::Relevance,
+        }
+    }
+}
+
+const W_DENSITY: f64 = 0.35;
+const W_COUNT: f64 = 0.25;
+const W_TITLE: f64 = 0.2;
+const W_HEAD: f64 = 0.1;
+const W_JUAN: f64 = 0.1;
+
+/// Matches per thousand characters that counts as "dense".
+const DENSITY_SCALE: f64 = 2.0;
+
+/// CBETA Taishō file id such as `T08n0251` (other corpora never match).
+fn is_taisho(id: &str) -> bool {
+    let Some(rest) = id.strip_prefix('T') else {
+        return false;
+    };
+    let vol = rest.bytes().take_while(|b| b.is_ascii_digit()).count();
+    vol > 0 && rest[vol..].starts_with('n')
+}
+
+fn in_heading(context: &str) -> bool {
+    [
+        "<head",
+        "<cb:jhead",
+        "<jhead",
+        "<cb:mulu",
+        "<title",
+        "<trailer",
+    ]
+    .iter()
+    .any(|t| context.contains(t))
+}
+
+/// Score one result in `[0, 1]` (plus the Taishō bias for CBETA). `title` is the work title
+/// from the index when known.
+pub fn score(r: &GrepResult, title: Option<&str>) -> f64 {
+    let m = r.total_matches as f64;
+    // Text size: index figure, else the XML size (about three bytes per CJK character).
+    let chars = r
+        .fetch_hints
+        .text_chars
+        .map(|c| c as f64)
+        .or_else(|| r.fetch_hints.file_size.map(|b| b as f64 / 3.0))
+        .unwrap_or(0.0);
+    let density = if chars > 0.0 {
+        1.0 - (-(m * 1000.0 / chars) / DENSITY_SCALE).exp()
+    } else {
+        0.0
+    };
+    let count = 1.0 - (-m / 5.0).exp();
+
+    let title = normalized(title.unwrap_or(&r.title));
+    let title_hit = r.matches.iter().any(|x| {
+        let h = normalized(&x.highlight);
+        !h.is_empty() && title.contains(&h)
+    });
+
+    let shown = r.matches.len().max(1) as f64;
+    let head = r.matches.iter().filter(|x| in_heading(&x.context)).count() as f64 / shown;
+
+    let juan = r
+        .matches
+        .iter()
+        .filter_map(|x| x.juan_number.as_deref()?.trim().parse::<u32>().ok())
+        .min()
+        .map(|n| 1.0 / n.max(1) as f64)
+        .unwrap_or(0.0);
+
+    let mut s = W_DENSITY * density
+        + W_COUNT * count
+        + if title_hit { W_TITLE } else { 0.0 }
+        + W_HEAD * head
+        + W_JUAN * juan;
+    if is_taisho(&r.file_id) {
+        s += config::current().scoring.taisho_bias as f64;
+    }
+    s
+}
+
+fn by_matches(a: &GrepResult, b: &GrepResult) -> Ordering {
+    is_taisho(&b.file_id)
+        .cmp(&is_taisho(&a.file_id))
+        .then(b.total_matches.cmp(&a.total_matches))
+        .then(a.file_id.cmp(&b.file_id))
+}
+
+/// Set `score` on every result and order them by `sort`. `titles` maps file ids to index
+/// titles (may be empty).
+pub fn rank(results: &mut [GrepResult], titles: &HashMap<String, String>, sort: SortBy) {
+    for r in results.iter_mut() {
+        let s = score(r, titles.get(&r.file_id).map(|t| t.as_str()));
+        r.score = Some((s * 1000.0).round() / 1000.0);
+    }
+    match sort {
+        SortBy::Relevance => results.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| by_matches(a, b))
+        }),
+        SortBy::Matches => results.sort_by(by_matches),
+        SortBy::Id => results.sort_by(|a, b| a.file_id.cmp(&b.file_id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FetchHints, GrepMatch};
+
+    fn hit(id: &str, n: usize, chars: usize, ctx: &str, juan: Option<&str>) -> GrepResult {
+        GrepResult {
+            file_path: String::new(),
+            file_id: id.into(),
+            title: id.into(),
+            matches: vec![GrepMatch {
+                context: ctx.into(),
+                highlight: "般若".into(),
+                juan_number: juan.map(|s| s.to_string()),
+                section: None,
+                line_number: Some(1),
+                ..Default::default()
+            }],
+            total_matches: n,
+            fetch_hints: FetchHints {
+                text_chars: Some(chars),
+                ..Default::default()
+            },
+            score: None,
+            titles: None,
+        }
+    }
+
+    #[test]
+    fn dense_titled_and_early_matches_rank_first() {
+        let mut rs = vec![
+            // Many matches spread over a huge text, late juan.
+            hit("T05n0220a", 6, 2_000_000, "<p>般若</p>", Some("200")),
+            // Few matches in a short text whose title names the term, in a heading.
+            hit("T08n0251", 3, 300, "<head>般若</head>", Some("001")),
+        ];
+        let titles = HashMap::from([("T08n0251".to_string(), "般若波羅蜜多心經".to_string())]);
+        rank(&mut rs, &titles, SortBy::Relevance);
+        assert_eq!(rs[0].file_id, "T08n0251");
+        assert!(rs[0].score > rs[1].score);
+
+        rank(&mut rs, &titles, SortBy::Matches);
+        assert_eq!(rs[0].file_id, "T05n0220a");
+        assert!(rs.iter().all(|r| r.score.is_some()));
+
+        rank(&mut rs, &titles, SortBy::Id);
+        assert_eq!(rs[0].file_id, "T05n0220a");
+        assert_eq!(SortBy::parse(Some("ID")), SortBy::Id);
+        assert_eq!(SortBy::parse(Some("bogus")), SortBy::Relevance);
+    }
+}
@@ -0,0 +1,425 @@
+//! Remote sources: web databases searched and read over HTTP.
+//!
+//! A [`RemoteSource`] describes one site — how to build its search and detail URLs, how to
+//! read a search response and a detail page, and how long its responses may be cached — and
+//! does no I/O itself. The server sends the requests (through its polite HTTP client, see
+//! [`crate::politeness`]), keeps the cache and serves the tools: a source listed in
+//! [`sources`] gets `<name>_search` and `<name>_fetch` tools without changes to the tool
+//! dispatcher, unless it has hand-written tools of its own, as SAT does.
+
+use scraper::{Html, Selector};
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
+
+/// A search request; fields a source has no use for are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct SearchQuery {
+    pub query: String,
+    pub rows: usize,
+    pub offset: usize,
+    /// Fields to return, comma-separated (SAT `fl`).
+    pub fields: Option<String>,
+    /// Filter expressions (SAT `fq`).
+    pub filters: Vec<String>,
+}
+
+/// One page of search results as the source returned them.
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct SearchPage {
+    /// Hits in all pages.
+    pub total: u64,
+    pub docs: Vec<Value>,
+}
+
+/// Where a source's responses are cached (a directory under the daizo cache) and for how
+/// long; `ttl: None` keeps them until the cache is cleared.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CachePolicy {
+    pub dir: &'static str,
+    pub ttl: Option<Duration>,
+}
+
+pub trait RemoteSource: Send + Sync {
+    /// Short name: the tool prefix, corpus group and cache directory (`sat`).
+    fn name(&self) -> &'static str;
+    /// Name shown in tool descriptions.
+    fn label(&self) -> &'static str;
+    fn search_url(&self, q: &SearchQuery) -> String;
+    /// Read a search response; None when it is not one.
+    fn parse_search(&self, body: &str) -> Option<SearchPage>;
+    /// Id of a search hit, as [`RemoteSource::detail_url`] takes it.
+    fn doc_id(&self, doc: &Value) -> Option<String>;
+    fn doc_title(&self, doc: &Value) -> String;
+    fn detail_url(&self, id: &str) -> String;
+    /// Plain text of a detail page.
+    fn extract_text(&self, html: &str) -> String;
+    fn cache_policy(&self) -> CachePolicy {
+        CachePolicy {
+            dir: self.name(),
+            ttl: None,
+        }
+    }
+    /// Whether the server exposes the generic `<name>_search` / `<name>_fetch` tools; false
+    /// for sources with tools of their own.
+    fn generic_tools(&self) -> bool {
+        true
+    }
+}
+
+static SOURCES: &[&dyn RemoteSource] = &[&SAT, &CBETA_ONLINE];
+
+/// Every registered source.
+pub fn sources() -> &'static [&'static dyn RemoteSource] {
+    SOURCES
+}
+
+pub fn source(name: &str) -> Option<&'static dyn RemoteSource> {
+    sources().iter().copied().find(|s| s.name() == name)
+}
+
+fn str_field(doc: &Value, key: &str) -> Option<String> {
+    doc.get(key).and_then(|v| v.as_str()).map(str::to_string)
+}
+
+/// Trim each line and collapse runs of blank lines to one.
+pub fn normalize_ws(s: &str) -> String {
+    let mut t = s
+        .replace('\r', "")
+        .split('\n')
+        .map(|l| l.trim())
+        .collect::<Vec<_>>()
+        .join("\n");
+    while t.contains("\n\n\n") {
+        t = t.replace("\n\n\n", "\n\n");
+    }
+    t
+}
+
+// ============ SAT ============
+
+/// SAT Daizōkyō Text Database: the wrap7 search API (Solr JSON) and the detail pages of
+/// `satdb2018pre.php`, keyed by line id (`startid`, e.g. `0262_,09,0001c14`).
+pub struct Sat;
+
+pub static SAT: Sat = Sat;
+
+const SAT_BASE: &str = "https://21dzk.l.u-tokyo.ac.jp/SAT2018";
+
+impl RemoteSource for Sat {
+    fn name(&self) -> &'static str {
+        "sat"
+    }
+
+    fn label(&self) -> &'static str {
+        "SAT Daizōkyō Text Database"
+    }
+
+    fn search_url(&self, q: &SearchQuery) -> String {
+        let mut url = url::Url::parse(&format!("{}/wrap7.php", SAT_BASE)).unwrap();
+        {
+            let mut p = url.query_pairs_mut();
+            p.append_pair("regex", "off");
+            // Sent as-is (callers quote phrases themselves).
+            p.append_pair("q", &q.query);
+            p.append_pair("rows", &q.rows.to_string());
+            p.append_pair("offs", &q.offset.to_string());
+            p.append_pair("schop", "AND");
+            if let Some(fl) = q.fields.as_deref().filter(|f| !f.trim().is_empty()) {
+                p.append_pair("fl", fl);
+            }
+            for f in q.filters.iter().filter(|f| !f.trim().is_empty()) {
+                p.append_pair("fq", f);
+            }
+        }
+        url.to_string()
+    }
+
+    fn parse_search(&self, body: &str) -> Option<SearchPage> {
+        let v: Value = serde_json::from_str(body).ok()?;
+        let resp = v.get("response")?;
+        Some(SearchPage {
+            total: resp.get("numFound").and_then(|n| n.as_u64()).unwrap_or(0),
+            docs: resp
+                .get("docs")
+                .and_then(|d| d.as_array())
+                .cloned()
+                .unwrap_or_default(),
+        })
+    }
+
+    fn doc_id(&self, doc: &Value) -> Option<String> {
+        str_field(doc, "startid").filter(|s| !s.is_empty())
+    }
+
+    fn doc_title(&self, doc: &Value) -> String {
+        str_field(doc, "fascnm").unwrap_or_default()
+    }
+
+    fn detail_url(&self, id: &str) -> String {
+        format!(
+            "{}/satdb2018pre.php?mode=detail&ob=1&mode2=2&useid={}",
+            SAT_BASE,
+            urlencoding::encode(id)
+        )
+    }
+
+    fn extract_text(&self, html: &str) -> String {
+        let doc = Html::parse_document(html);
+        // Detail pages put each line in span.tx, after its number (span.ln) and anchors.
+        if let Ok(sel) = Selector::parse("span.tx") {
+            let lines: Vec<String> = doc
+                .select(&sel)
+                .map(|n| n.text().collect::<String>().trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect();
+            let joined = normalize_ws(&lines.join("\n"));
+            if joined.len() > 50 {
+                return joined;
+            }
+        }
+        for sel in [
+            "#text", "#viewer", "#content", "main", ".content", ".article", "#main", "#result",
+            "#detail", "#sattext", "pre#text", "pre", ".text", "body",
+        ] {
+            if let Ok(selector) = Selector::parse(sel) {
+                if let Some(node) = doc.select(&selector).next() {
+                    let t = normalize_ws(&node.text().collect::<Vec<_>>().join("\n"));
+                    if t.len() > 50 {
+                        return t;
+                    }
+                }
+            }
+        }
+        String::new()
+    }
+
+    fn generic_tools(&self) -> bool {
+        false
+    }
+}
+
+// ============ CBETA Online ============
+
+/// The CBETA Online REST API (`api.cbetaonline.cn`): full-text search by work and juan, and
+/// each juan as HTML. The server reads it in place of a missing local CBETA checkout when
+/// `DAIZO_ALLOW_REMOTE=1`; ids are `T0262` (juan 1) or `T0262_002`.
+pub struct CbetaOnline;
+
+pub static CBETA_ONLINE: CbetaOnline = CbetaOnline;
+
+const CBETA_API: &str = "https://api.cbetaonline.cn";
+
+impl CbetaOnline {
+    /// `T0262_002` → (`T0262`, 2); a bare work id is juan 1.
+    pub fn split_id(id: &str) -> (String, u32) {
+        match id.rsplit_once('_') {
+            Some((work, juan)) if juan.chars().all(|c| c.is_ascii_digit()) && !juan.is_empty() => {
+                (work.to_string(), juan.parse().unwrap_or(1).max(1))
+            }
+            _ => (id.to_string(), 1),
+        }
+    }
+
+    pub fn juan_id(work: &str, juan: u32) -> String {
+        format!("{}_{:03}", work, juan)
+    }
+}
+
+/// Elements left out of the text: line-number labels, note anchors and the apparatus and
+/// copyright notice at the end of a juan.
+fn skipped(el: &scraper::node::Element) -> bool {
+    let class = el.attr("class").unwrap_or("");
+    matches!(el.attr("id"), Some("back" | "cbeta-copyright"))
+        || class
+            .split_whitespace()
+            .any(|c| matches!(c, "lineInfo" | "noteAnchor" | "linehead"))
+}
+
+fn collect_text(node: scraper::ElementRef, out: &mut String) {
+    for child in node.children() {
+        if let Some(t) = child.value().as_text() {
+            out.push_str(t);
+        } else if let Some(el) = scraper::ElementRef::wrap(child) {
+            let v = el.value();
+            if skipped(v) {
+                continue;
+            }
+            let breaks = matches!(v.name(), "p" | "div" | "br")
+                || v.attr("class")
+                    .is_some_and(|c| c.split_whitespace().any(|c| c == "lb"));
+            if breaks && !out.ends_with('\n') {
+                out.push('\n');
+            }
+            collect_text(el, out);
+        }
+    }
+}
+
+impl RemoteSource for CbetaOnline {
+    fn name(&self) -> &'static str {
+        "cbeta-online"
+    }
+
+    fn label(&self) -> &'static str {
+        "CBETA Online"
+    }
+
+    fn search_url(&self, q: &SearchQuery) -> String {
+        let mut url = url::Url::parse(&format!("{}/search", CBETA_API)).unwrap();
+        {
+            let mut p = url.query_pairs_mut();
+            p.append_pair("q", &q.query);
+            p.append_pair("rows", &q.rows.to_string());
+            p.append_pair("start", &q.offset.to_string());
+            // `canon:T`, `work:T0262`, `category:般若部類` become API parameters.
+            for (k, v) in q.filters.iter().filter_map(|f| f.split_once(':')) {
+                p.append_pair(k.trim(), v.trim());
+            }
+        }
+        url.to_string()
+    }
+
+    fn parse_search(&self, body: &str) -> Option<SearchPage> {
+        let v: Value = serde_json::from_str(body).ok()?;
+        Some(SearchPage {
+            total: v.get("num_found").and_then(|n| n.as_u64()).unwrap_or(0),
+            docs: v.get("results")?.as_array()?.clone(),
+        })
+    }
+
+    fn doc_id(&self, doc: &Value) -> Option<String> {
+        let work = str_field(doc, "work").filter(|w| !w.is_empty())?;
+        let juan = doc.get("juan").and_then(|j| j.as_u64()).unwrap_or(1);
+        Some(CbetaOnline::juan_id(&work, juan as u32))
+    }
+
+    fn doc_title(&self, doc: &Value) -> String {
+        str_field(doc, "title").unwrap_or_default()
+    }
+
+    fn detail_url(&self, id: &str) -> String {
+        let (work, juan) = CbetaOnline::split_id(id);
+        format!(
+            "{}/juans?work={}&juan={}",
+            CBETA_API,
+            urlencoding::encode(&work),
+            juan
+        )
+    }
+
+    /// The juan endpoint answers JSON with the juan's HTML in `results`.
+    fn extract_text(&self, body: &str) -> String {
+        let html = match serde_json::from_str::<Value>(body) {
+            Ok(v) => v
+                .get("results")
+                .and_then(|r| r.as_array())
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|x| x.as_str())
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .unwrap_or_default(),
+            Err(_) => body.to_string(),
+        };
+        let doc = Html::parse_fragment(&html);
+        let mut out = String::new();
+        collect_text(doc.root_element(), &mut out);
+        normalize_ws(&out).trim().to_string()
+    }
+
+    fn cache_policy(&self) -> CachePolicy {
+        CachePolicy {
+            dir: "cbeta-online",
+            ttl: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+        }
+    }
+
+    fn generic_tools(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sat_builds_urls_and_reads_responses() {
+        let sat = source("sat").unwrap();
+        assert_eq!(sat.cache_policy().dir, "sat");
+        assert!(!sat.generic_tools());
+        let url = sat.search_url(&SearchQuery {
+            query: "\"如是我聞\"".into(),
+            rows: 5,
+            offset: 10,
+            fields: Some("id,fascnm,startid".into()),
+            filters: vec!["".into(), "vol:9".into()],
+        });
+        assert!(url.starts_with("https://21dzk.l.u-tokyo.ac.jp/SAT2018/wrap7.php?regex=off&q="));
+        assert!(url.ends_with("&rows=5&offs=10&schop=AND&fl=id%2Cfascnm%2Cstartid&fq=vol%3A9"));
+        assert_eq!(
+            sat.detail_url("0262_,09,0001c14"),
+            "https://21dzk.l.u-tokyo.ac.jp/SAT2018/satdb2018pre.php?mode=detail&ob=1&mode2=2&useid=0262_%2C09%2C0001c14"
+        );
+
+        let page = sat
+            .parse_search(r#"{"response":{"numFound":42,"docs":[{"fascnm":"妙法蓮華經","startid":"0262_,09,0001c14"}]}}"#)
+            .unwrap();
+        assert_eq!(page.total, 42);
+        assert_eq!(sat.doc_title(&page.docs[0]), "妙法蓮華經");
+        assert_eq!(
+            sat.doc_id(&page.docs[0]).as_deref(),
+            Some("0262_,09,0001c14")
+        );
+        assert!(sat.parse_search("<html>busy</html>").is_none());
+
+        let html = "<div><span class=\"ln\">0001c14</span><span class=\"tx\">如是我聞。一時佛住王舍城耆闍崛山中，與大比丘眾萬二千人俱。</span></div>\
+                    <div><span class=\"ln\">0001c15</span><span class=\"tx\"> 皆是阿羅漢，諸漏已盡，無復煩惱，逮得己利。 </span></div>";
+        assert_eq!(
+            sat.extract_text(html),
+            "如是我聞。一時佛住王舍城耆闍崛山中，與大比丘眾萬二千人俱。\n皆是阿羅漢，諸漏已盡，無復煩惱，逮得己利。"
+        );
+    }
+
+    #[test]
+    fn cbeta_online_reads_search_and_juans() {
+        let c = source("cbeta-online").unwrap();
+        assert_eq!(c.cache_policy().dir, "cbeta-online");
+        assert_eq!(
+            c.search_url(&SearchQuery {
+                query: "法華".into(),
+                rows: 20,
+                offset: 0,
+                filters: vec!["canon:T".into()],
+                ..Default::default()
+            }),
+            "https://api.cbetaonline.cn/search?q=%E6%B3%95%E8%8F%AF&rows=20&start=0&canon=T"
+        );
+        let page = c
+            .parse_search(
+                r#"{"num_found":2,"results":[{"work":"T0262","juan":2,"title":"妙法蓮華經"}]}"#,
+            )
+            .unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(c.doc_id(&page.docs[0]).as_deref(), Some("T0262_002"));
+        assert_eq!(
+            c.detail_url("T0262_002"),
+            "https://api.cbetaonline.cn/juans?work=T0262&juan=2"
+        );
+        assert_eq!(CbetaOnline::split_id("T0128a"), ("T0128a".into(), 1));
+
+        let body = serde_json::json!({"num_found": 1, "results": [
+            "<div id='body'><p class='juan'>妙法蓮華經卷第一</p>\
+             <span class='lb' id='T09n0262_p0001c14'></span><span class='lineInfo'>0001c14</span>如是我聞<a class='noteAnchor' href='#n1'>[1]</a>：\
+             <span class='lb' id='T09n0262_p0001c15'></span>一時佛住王舍城</div>\
+             <div id='back'>[1] 聞＝問【宋】</div>"
+        ]})
+        .to_string();
+        assert_eq!(
+            c.extract_text(&body),
+            "妙法蓮華經卷第一\n如是我聞：\n一時佛住王舍城"
+        );
+    }
+}
@@ -1,5 +1,11 @@
-use std::path::Path;
-use std::process::Command;
+use crate::progress::{self, Progress, Reporter};
+use crate::provenance::{date_utc, git_dir, read_head};
+use crate::timing::Phase;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::sync::{Mutex, OnceLock};
 use std::time::{Duration, Instant};
 
@@ -7,21 +13,13 @@ fn log(msg: &str) {
     eprintln!("[daizo-repo] {}", msg);
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct RepoPolicy {
     pub min_delay_ms: u64,
     pub robots_txt: bool,
     pub user_agent: Option<String>,
-}
-
-impl Default for RepoPolicy {
-    fn default() -> Self {
-        Self {
-            min_delay_ms: 0,
-            robots_txt: false,
-            user_agent: None,
-        }
-    }
+    /// Extra attempts for clones and fetches that fail part way (flaky networks).
+    pub retries: u32,
 }
 
 static POLICY: OnceLock<RepoPolicy> = OnceLock::new();
@@ -39,7 +37,10 @@ pub fn init_policy_from_env() {
     if POLICY.get().is_some() {
         return;
     }
-    let mut p = RepoPolicy::default();
+    let mut p = RepoPolicy {
+        retries: 2,
+        ..RepoPolicy::default()
+    };
     if let Ok(ms) = std::env::var("DAIZO_REPO_MIN_DELAY_MS") {
         if let Ok(v) = ms.parse::<u64>() {
             p.min_delay_ms = v;
@@ -53,6 +54,11 @@ pub fn init_policy_from_env() {
     if let Ok(v) = std::env::var("DAIZO_REPO_RESPECT_ROBOTS") {
         p.robots_txt = matches!(v.as_str(), "1" | "true" | "yes");
     }
+    if let Ok(v) = std::env::var("DAIZO_REPO_RETRIES") {
+        if let Ok(n) = v.parse::<u32>() {
+            p.retries = n;
+        }
+    }
     set_repo_policy(p);
 }
 
@@ -74,60 +80,512 @@ fn maybe_throttle() {
 }
 
 pub fn run(cmd: &str, args: &[&str], cwd: Option<&Path>) -> bool {
+    // Clones and fetches wait on the remote; other git commands are local.
+    let _t = (cmd == "git"
+        && args
+            .iter()
+            .any(|a| matches!(*a, "clone" | "fetch" | "pull")))
+    .then(|| Phase::Network.start());
     maybe_throttle();
     log(&format!("{} {}", cmd, args.join(" ")));
+    if cmd == "git" {
+        if let Some(reporter) = Reporter::current() {
+            return run_git_with_progress(args, cwd, &reporter);
+        }
+    }
     let mut c = Command::new(cmd);
     c.args(args);
     if let Some(d) = cwd {
         c.current_dir(d);
     }
     if cmd == "git" {
-        c.stdout(std::process::Stdio::inherit());
-        c.stderr(std::process::Stdio::inherit());
+        c.stdout(Stdio::inherit());
+        c.stderr(Stdio::inherit());
     }
     c.status().map(|s| s.success()).unwrap_or(false)
 }
 
-pub fn ensure_cbeta_data_at(root: &Path) -> bool {
-    if root.exists() {
-        return true;
+/// Run git with `--progress`, echoing its stderr and forwarding the progress lines
+/// (objects received, bytes downloaded, files checked out) to `reporter`.
+fn run_git_with_progress(args: &[&str], cwd: Option<&Path>, reporter: &Reporter) -> bool {
+    // Subcommand: first non-option argument, skipping the value of `-C <dir>`.
+    let sub = args
+        .iter()
+        .enumerate()
+        .find(|(i, a)| !a.starts_with('-') && (*i == 0 || args[i - 1] != "-C"))
+        .map(|(i, a)| (i, *a));
+    let mut c = Command::new("git");
+    for (i, a) in args.iter().enumerate() {
+        c.arg(a);
+        // git only reports progress to a non-terminal when asked to.
+        if matches!(sub, Some((j, "clone" | "fetch" | "checkout")) if j == i) {
+            c.arg("--progress");
+        }
     }
-    if let Some(parent) = root.parent() {
-        let _ = std::fs::create_dir_all(parent);
+    if let Some(d) = cwd {
+        c.current_dir(d);
     }
-    log(&format!("cloning CBETA xml-p5 -> {}", root.display()));
+    // stdout may be a protocol channel (MCP over stdio); keep git off it.
+    c.stdout(Stdio::null());
+    c.stderr(Stdio::piped());
+    let Ok(mut child) = c.spawn() else {
+        return false;
+    };
+    let task = format!("git {}", sub.map(|(_, a)| a).unwrap_or_default());
+    if let Some(mut err) = child.stderr.take() {
+        let mut buf = [0u8; 4096];
+        let mut line = Vec::new();
+        let mut last = String::new();
+        while let Ok(n) = err.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            for &b in &buf[..n] {
+                if b != b'\r' && b != b'\n' {
+                    line.push(b);
+                    continue;
+                }
+                let text = String::from_utf8_lossy(&line).trim().to_string();
+                line.clear();
+                if text.is_empty() {
+                    continue;
+                }
+                match progress::parse_git_progress(&text) {
+                    Some((phase, done, total, bytes)) => {
+                        // Log each phase once rather than every percentage step.
+                        if phase != last {
+                            log(&text);
+                        }
+                        reporter.report(&Progress {
+                            task: format!("{} ({})", task, phase.to_lowercase()),
+                            done,
+                            total: Some(total),
+                            bytes,
+                            message: text,
+                        });
+                        last = phase;
+                    }
+                    None => log(&text),
+                }
+            }
+        }
+    }
+    child.wait().map(|s| s.success()).unwrap_or(false)
+}
+
+/// Run a git command that talks to the remote, retrying with a growing pause (2s, 4s, …)
+/// up to the policy's `retries` when it fails, e.g. on a dropped connection.
+fn run_net(args: &[&str], cwd: Option<&Path>) -> bool {
+    let retries = policy().retries;
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            log(&format!("retrying ({}/{})", attempt, retries));
+            std::thread::sleep(Duration::from_secs(1 << attempt.min(5)));
+        }
+        if run("git", args, cwd) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Output of a git command run in `root`; None when it fails.
+fn git_output(root: &Path, args: &[&str]) -> Option<Vec<u8>> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    out.status.success().then_some(out.stdout)
+}
+
+/// Whether `root` holds a clone that stopped before its first commit arrived (`.git`
+/// exists, HEAD does not resolve).
+pub fn is_incomplete_clone(root: &Path) -> bool {
+    let git = root.join(".git");
+    git.is_dir() && read_head(&git).is_none()
+}
+
+/// Finish an interrupted clone in place: fetch the remote's default branch again (objects
+/// already on disk are kept) and point HEAD at it. With `checkout` the work tree is
+/// populated too; otherwise it is left as after `git clone --no-checkout`.
+pub fn resume_clone(root: &Path, checkout: bool) -> bool {
+    log(&format!("resuming interrupted clone in {}", root.display()));
+    let r = root.to_string_lossy();
+    if !run_net(&["-C", &r, "fetch", "--depth", "1", "origin"], None)
+        || !run_net(&["-C", &r, "remote", "set-head", "origin", "--auto"], None)
+    {
+        return false;
+    }
+    let head =
+        std::fs::read_to_string(root.join(".git/refs/remotes/origin/HEAD")).unwrap_or_default();
+    let Some(branch) = head.trim().strip_prefix("ref: refs/remotes/origin/") else {
+        return false;
+    };
+    let local = format!("refs/heads/{}", branch);
     run(
         "git",
         &[
-            "clone",
-            "--depth",
-            "1",
-            "https://github.com/cbeta-org/xml-p5",
-            &root.to_string_lossy(),
+            "-C",
+            &r,
+            "update-ref",
+            &local,
+            &format!("refs/remotes/origin/{}", branch),
         ],
         None,
-    )
+    ) && run("git", &["-C", &r, "symbolic-ref", "HEAD", &local], None)
+        && (!checkout || run_net(&["-C", &r, "checkout", "-f"], None))
 }
 
-pub fn ensure_sarit_data_at(root: &Path) -> bool {
-    if root.exists() {
+/// Clone `url` into `root` (`extra` goes before the URL, e.g. `--no-checkout`), retrying
+/// on failure; a clone left half-done by an earlier run is resumed rather than restarted.
+fn clone_or_resume(url: &str, root: &Path, extra: &[&str]) -> bool {
+    if let Some(parent) = root.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let checkout = !extra.contains(&"--no-checkout");
+    if is_incomplete_clone(root) {
+        return resume_clone(root, checkout);
+    }
+    let root_str = root.to_string_lossy();
+    let mut args = vec!["clone", "--depth", "1"];
+    args.extend_from_slice(extra);
+    args.extend([url, root_str.as_ref()]);
+    let retries = policy().retries;
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            log(&format!("retrying ({}/{})", attempt, retries));
+            std::thread::sleep(Duration::from_secs(1 << attempt.min(5)));
+        }
+        // A clone killed mid-transfer can leave `.git` behind; pick up from there.
+        let ok = if is_incomplete_clone(root) {
+            resume_clone(root, checkout)
+        } else {
+            run("git", &args, None)
+        };
+        if ok {
+            return true;
+        }
+    }
+    false
+}
+
+/// Integrity of one top-level directory of a checkout (a CBETA canon, `romn`, …) against
+/// HEAD: how many files are on disk, and whether a sample of them has the committed content.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CanonCheck {
+    /// Directory name; `.` for files at the top of the repository.
+    pub canon: String,
+    pub expected: usize,
+    pub present: usize,
+    pub sampled: usize,
+    /// Sampled files whose content differs from HEAD (truncated or partly written).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mismatched: Vec<String>,
+}
+
+impl CanonCheck {
+    pub fn ok(&self) -> bool {
+        self.present == self.expected && self.mismatched.is_empty()
+    }
+}
+
+/// Git blob id of `data`: SHA-1 over `blob <len>\0<data>`.
+fn blob_id(data: &[u8]) -> String {
+    let mut h = Sha1::new();
+    h.update(format!("blob {}\0", data.len()).as_bytes());
+    h.update(data);
+    h.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Check the files checked out under `root` against HEAD, per top-level directory: the file
+/// count must match the tree, and `samples` files spread over each directory are hashed and
+/// compared with their blob ids. Directories outside a sparse checkout are skipped. None
+/// when `root` is not a checkout with a commit.
+pub fn verify_checkout(root: &Path, samples: usize) -> Option<Vec<CanonCheck>> {
+    if is_incomplete_clone(root) || !root.join(".git").is_dir() {
+        return None;
+    }
+    let tree = git_output(root, &["ls-tree", "-r", "-z", "--full-tree", "HEAD"])?;
+    let sparse = sparse_dirs(root);
+    let mut by_canon: std::collections::BTreeMap<String, Vec<(String, String)>> =
+        Default::default();
+    for entry in tree.split(|&b| b == 0).filter(|e| !e.is_empty()) {
+        let entry = String::from_utf8_lossy(entry);
+        let Some((meta, path)) = entry.split_once('\t') else {
+            continue;
+        };
+        let mut f = meta.split(' ');
+        let (mode, kind, id) = (f.next(), f.next(), f.next());
+        // Symlinks hash their target and submodules are not files; neither is sampled.
+        if kind != Some("blob") || mode == Some("120000") {
+            continue;
+        }
+        let canon = match path.split_once('/') {
+            Some((dir, _)) => dir,
+            None => ".",
+        };
+        if let Some(dirs) = &sparse {
+            if !dirs.iter().any(|d| d == canon) {
+                continue;
+            }
+        }
+        by_canon
+            .entry(canon.to_string())
+            .or_default()
+            .push((path.to_string(), id.unwrap_or_default().to_string()));
+    }
+    let checks = by_canon
+        .into_iter()
+        .map(|(canon, files)| {
+            let present: Vec<&(String, String)> = files
+                .iter()
+                .filter(|(p, _)| root.join(p).is_file())
+                .collect();
+            let n = samples.min(present.len());
+            let mut check = CanonCheck {
+                canon,
+                expected: files.len(),
+                present: present.len(),
+                sampled: n,
+                mismatched: Vec::new(),
+            };
+            for i in 0..n {
+                let (path, id) = present[i * present.len() / n];
+                match std::fs::read(root.join(path)) {
+                    Ok(data) if blob_id(&data) == *id => {}
+                    _ => check.mismatched.push(path.clone()),
+                }
+            }
+            check
+        })
+        .collect();
+    Some(checks)
+}
+
+/// Restore missing or damaged files of a checkout from HEAD (`git checkout -f`, which also
+/// fetches the blobs a partial clone is missing), or finish an interrupted clone.
+pub fn repair_checkout(root: &Path) -> bool {
+    if is_incomplete_clone(root) {
+        return resume_clone(root, true);
+    }
+    run_net(&["-C", &root.to_string_lossy(), "checkout", "-f"], None)
+}
+
+/// A file a pull added (`A`), modified (`M`) or deleted (`D`), relative to the checkout.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FileChange {
+    pub status: char,
+    pub path: String,
+}
+
+/// What `git pull` changed in a corpus checkout.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PullReport {
+    /// Top of the checkout; `changed` paths are relative to it.
+    pub checkout: PathBuf,
+    /// HEAD before and after the pull.
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Files that differ between the two commits, limited to the directories of a sparse
+    /// checkout; empty when the checkout was already current.
+    pub changed: Vec<FileChange>,
+}
+
+/// Pull the checkout under `root` (fast-forward only; the data repos are read-only copies)
+/// and list the files that changed. None when the pull fails.
+pub fn pull(root: &Path) -> Option<PullReport> {
+    // The corpus root may lie inside the checkout (Tipitaka's `romn`).
+    let git = git_dir(root)?;
+    let top = git.parent()?.to_path_buf();
+    let from = read_head(&git);
+    let r = top.to_string_lossy();
+    if !run_net(&["-C", &r, "pull", "--ff-only"], None) {
+        return None;
+    }
+    let to = read_head(&git);
+    let mut report = PullReport {
+        checkout: top.clone(),
+        from: from.clone(),
+        to: to.clone(),
+        changed: Vec::new(),
+    };
+    let (Some(from), Some(to)) = (from, to) else {
+        return Some(report);
+    };
+    if from == to {
+        return Some(report);
+    }
+    // Names only: no rename detection, so a partial clone need not fetch old blobs.
+    let out = git_output(
+        &top,
+        &["diff", "--name-status", "--no-renames", "-z", &from, &to],
+    )?;
+    let sparse = sparse_dirs(root);
+    let fields: Vec<String> = out
+        .split(|&b| b == 0)
+        .map(|f| String::from_utf8_lossy(f).into_owned())
+        .collect();
+    for pair in fields.chunks(2) {
+        let [status, path] = pair else {
+            continue;
+        };
+        let status = match status.chars().next() {
+            Some('A') => 'A',
+            Some('D') => 'D',
+            Some(_) => 'M',
+            None => continue,
+        };
+        if let Some(dirs) = &sparse {
+            let top = path.split('/').next().unwrap_or_default();
+            if !dirs.iter().any(|d| d == top) {
+                continue;
+            }
+        }
+        report.changed.push(FileChange {
+            status,
+            path: path.clone(),
+        });
+    }
+    Some(report)
+}
+
+pub fn ensure_cbeta_data_at(root: &Path) -> bool {
+    if root.exists() && !is_incomplete_clone(root) {
         return true;
     }
-    if let Some(parent) = root.parent() {
+    log(&format!("cloning CBETA xml-p5 -> {}", root.display()));
+    clone_or_resume("https://github.com/cbeta-org/xml-p5", root, &[])
+}
+
+/// Whether `c` can be a CBETA canon directory of xml-p5 (`T`, `X`, `GA`, …).
+pub fn valid_canon(c: &str) -> bool {
+    (1..=3).contains(&c.len()) && c.chars().all(|ch| ch.is_ascii_uppercase())
+}
+
+/// Canon of a CBETA work or file id: its leading letters (`T0262` → `T`, `GA0037` → `GA`).
+pub fn canon_of(id: &str) -> Option<&str> {
+    let end = id
+        .find(|c: char| !c.is_ascii_uppercase())
+        .unwrap_or(id.len());
+    let c = &id[..end];
+    valid_canon(c).then_some(c)
+}
+
+fn sparse_file(git: &Path) -> PathBuf {
+    git.join("info").join("sparse-checkout")
+}
+
+/// Directories a sparse checkout is limited to, from `.git/info/sparse-checkout`
+/// (`/T/` → `T`); None for a full checkout.
+pub fn sparse_dirs(root: &Path) -> Option<Vec<String>> {
+    let git = git_dir(root)?;
+    let cfg = std::fs::read_to_string(git.join("config")).unwrap_or_default();
+    let enabled = cfg.lines().any(|l| {
+        let l = l.trim().to_ascii_lowercase().replace(' ', "");
+        l == "sparsecheckout=true"
+    });
+    if !enabled {
+        return None;
+    }
+    let list = std::fs::read_to_string(sparse_file(&git)).ok()?;
+    let mut dirs: Vec<String> = list
+        .lines()
+        .map(|l| l.trim().trim_matches('/'))
+        .filter(|l| !l.is_empty() && !l.starts_with('#') && !l.contains('*'))
+        .map(str::to_string)
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    Some(dirs)
+}
+
+fn write_sparse(root: &Path, dirs: &[String]) -> bool {
+    let file = sparse_file(&root.join(".git"));
+    if let Some(parent) = file.parent() {
         let _ = std::fs::create_dir_all(parent);
     }
-    log(&format!("cloning SARIT corpus -> {}", root.display()));
-    run(
+    let body: String = dirs.iter().map(|d| format!("/{}/\n", d)).collect();
+    std::fs::write(&file, body).is_ok()
+}
+
+/// Clone xml-p5 with only the `canons` directories checked out. The clone is partial
+/// (`--filter=blob:none`), so only the files of those canons are downloaded; more canons
+/// can be added later with [`add_cbeta_canons`].
+pub fn clone_cbeta_sparse(root: &Path, canons: &[String]) -> bool {
+    if canons.is_empty() || !canons.iter().all(|c| valid_canon(c)) {
+        return false;
+    }
+    log(&format!(
+        "cloning CBETA xml-p5 ({} only) -> {}",
+        canons.join(","),
+        root.display()
+    ));
+    if !clone_or_resume(
+        "https://github.com/cbeta-org/xml-p5",
+        root,
+        &["--no-checkout", "--filter=blob:none"],
+    ) {
+        return false;
+    }
+    let root_str = root.to_string_lossy();
+    if !run(
         "git",
-        &[
-            "clone",
-            "--depth",
-            "1",
-            "https://github.com/sarit/SARIT-corpus.git",
-            &root.to_string_lossy(),
-        ],
+        &["-C", &root_str, "config", "core.sparseCheckout", "true"],
         None,
-    )
+    ) {
+        return false;
+    }
+    // Checking out downloads the canons' blobs.
+    write_sparse(root, canons) && run_net(&["-C", &root_str, "checkout"], None)
+}
+
+/// Add `canons` to a sparse xml-p5 checkout and check them out. A full checkout already
+/// has every canon; returns whether all of them are present afterwards.
+pub fn add_cbeta_canons(root: &Path, canons: &[String]) -> bool {
+    let Some(mut dirs) = sparse_dirs(root) else {
+        return root.exists();
+    };
+    let missing: Vec<&String> = canons.iter().filter(|c| !dirs.contains(c)).collect();
+    if missing.is_empty() {
+        return true;
+    }
+    if !missing.iter().all(|c| valid_canon(c)) {
+        return false;
+    }
+    log(&format!(
+        "adding CBETA canons {} -> {}",
+        missing
+            .iter()
+            .map(|c| c.as_str())
+            .collect::<Vec<_>>()
+            .join(","),
+        root.display()
+    ));
+    dirs.extend(missing.into_iter().cloned());
+    dirs.sort();
+    let root_str = root.to_string_lossy();
+    write_sparse(root, &dirs) && run_net(&["-C", &root_str, "read-tree", "-mu", "HEAD"], None)
+}
+
+/// Make sure the canon of a CBETA id is checked out: a no-op for a full checkout, and for a
+/// sparse one (see [`clone_cbeta_sparse`]) the canon is added on first use.
+pub fn ensure_cbeta_canon_at(root: &Path, canon: &str) -> bool {
+    match sparse_dirs(root) {
+        None => true,
+        Some(dirs) if dirs.iter().any(|d| d == canon) => true,
+        Some(_) => add_cbeta_canons(root, &[canon.to_string()]),
+    }
+}
+
+pub fn ensure_sarit_data_at(root: &Path) -> bool {
+    if root.exists() && !is_incomplete_clone(root) {
+        return true;
+    }
+    log(&format!("cloning SARIT corpus -> {}", root.display()));
+    clone_or_resume("https://github.com/sarit/SARIT-corpus.git", root, &[])
 }
 
 pub fn ensure_muktabodha_dir(root: &Path) {
@@ -142,27 +600,10 @@ pub fn clone_tipitaka_sparse(target_dir: &Path) -> bool {
         "cloning Tipitaka (romn only) -> {}",
         target_dir.display()
     ));
-    if let Some(parent) = target_dir.parent() {
-        let _ = std::fs::create_dir_all(parent);
-    }
-    // Clone the repository with no checkout
-    let temp_dir = target_dir.parent().unwrap_or(Path::new("."));
-    let target_name = target_dir
-        .file_name()
-        .unwrap_or_else(|| std::ffi::OsStr::new("tipitaka-xml"))
-        .to_string_lossy()
-        .to_string();
-    if !run(
-        "git",
-        &[
-            "clone",
-            "--no-checkout",
-            "--depth",
-            "1",
-            "https://github.com/VipassanaTech/tipitaka-xml",
-            &target_name,
-        ],
-        Some(temp_dir),
+    if !clone_or_resume(
+        "https://github.com/VipassanaTech/tipitaka-xml",
+        target_dir,
+        &["--no-checkout"],
     ) {
         return false;
     }
@@ -181,10 +622,7 @@ pub fn clone_tipitaka_sparse(target_dir: &Path) -> bool {
     if std::fs::write(&sparse_file, "romn/\n").is_err() {
         return false;
     }
-    if !run("git", &["-C", &target_str, "checkout"], None) {
-        return false;
-    }
-    true
+    run_net(&["-C", &target_str, "checkout"], None)
 }
 
 pub fn ensure_tipitaka_data_at(target_dir: &Path) -> bool {
@@ -197,3 +635,245 @@ pub fn ensure_tipitaka_data_at(target_dir: &Path) -> bool {
 pub fn ensure_dir(p: &Path) {
     let _ = std::fs::create_dir_all(p);
 }
+
+/// Which data a local corpus holds: the checkout's commit and when it was cloned.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorpusRevision {
+    /// HEAD commit; None when the data is not a git checkout (e.g. GRETIL downloads).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Clone date from the first reflog entry, or the directory's date for plain downloads
+    /// (UTC, YYYY-MM-DD).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloned: Option<String>,
+    /// Directories of a sparse checkout (CBETA canons); None for a full one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sparse: Option<Vec<String>>,
+}
+
+/// Revision of the data under `root`; None when the directory does not exist.
+pub fn corpus_revision(root: &Path) -> Option<CorpusRevision> {
+    let meta = std::fs::metadata(root).ok()?;
+    let git = git_dir(root);
+    let secs = git.as_deref().and_then(clone_time).or_else(|| {
+        let t = meta.created().or_else(|_| meta.modified()).ok()?;
+        Some(t.duration_since(std::time::UNIX_EPOCH).ok()?.as_secs())
+    });
+    Some(CorpusRevision {
+        commit: git.as_deref().and_then(read_head),
+        cloned: secs.map(date_utc),
+        sparse: sparse_dirs(root),
+    })
+}
+
+/// Timestamp of the first reflog entry (`<old> <new> <who> <time> <tz>\tclone: from ...`).
+fn clone_time(git: &Path) -> Option<u64> {
+    let log = std::fs::read_to_string(git.join("logs").join("HEAD")).ok()?;
+    let who = log.lines().next()?.split('\t').next()?;
+    who.split_whitespace().rev().nth(1)?.parse().ok()
+}
+
+/// Where the revision an index cache was built from is kept:
+/// `cbeta-index.json` → `cbeta-index.revision.json`.
+pub fn index_revision_path(index: &Path) -> PathBuf {
+    let stem = index.file_stem().unwrap_or_default().to_string_lossy();
+    index.with_file_name(format!("{}.revision.json", stem))
+}
+
+/// Record the revision of `root` next to a freshly written index.
+pub fn record_index_revision(index: &Path, root: &Path) {
+    let path = index_revision_path(index);
+    match corpus_revision(root) {
+        Some(rev) => {
+            if let Ok(b) = serde_json::to_vec(&rev) {
+                let _ = std::fs::write(&path, b);
+            }
+        }
+        None => {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
+
+/// Revision recorded for an index cache.
+pub fn index_revision(index: &Path) -> Option<CorpusRevision> {
+    let b = std::fs::read(index_revision_path(index)).ok()?;
+    serde_json::from_slice(&b).ok()
+}
+
+/// Whether the index was built from the commit (and sparse canons) now checked out under `root`. None when
+/// either commit is unknown (plain downloads, indexes from older versions); callers then
+/// fall back to checking that a few indexed paths still exist.
+pub fn index_is_current(index: &Path, root: &Path) -> Option<bool> {
+    let built = index_revision(index)?;
+    let now = git_dir(root).as_deref().and_then(read_head)?;
+    // Canons added to a sparse checkout leave HEAD as it was.
+    Some(built.commit? == now && built.sparse == sparse_dirs(root))
+}
+
+/// Whether a cached index still describes the data under `root`: by commit when both are
+/// known, else by checking that the first indexed paths exist.
+pub fn index_is_fresh(index: &Path, root: &Path, entries: &[crate::IndexEntry]) -> bool {
+    index_is_current(index, root)
+        .unwrap_or_else(|| entries.iter().take(10).all(|e| Path::new(&e.path).exists()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_revision_follows_the_checkout() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("xml-p5");
+        let git = root.join(".git");
+        std::fs::create_dir_all(git.join("logs")).unwrap();
+        std::fs::write(git.join("HEAD"), "aaaa\n").unwrap();
+        std::fs::write(
+            git.join("logs").join("HEAD"),
+            "0000 aaaa Some One <a@b> 1791072000 +0900\tclone: from https://github.com/cbeta-org/xml-p5\n",
+        )
+        .unwrap();
+        let rev = corpus_revision(&root).unwrap();
+        assert_eq!(rev.commit.as_deref(), Some("aaaa"));
+        assert_eq!(rev.cloned.as_deref(), Some("2026-10-04"));
+
+        let index = dir.path().join("cbeta-index.json");
+        assert_eq!(
+            index_revision_path(&index),
+            dir.path().join("cbeta-index.revision.json")
+        );
+        assert_eq!(index_is_current(&index, &root), None);
+        record_index_revision(&index, &root);
+        assert_eq!(index_is_current(&index, &root), Some(true));
+        std::fs::write(git.join("HEAD"), "bbbb\n").unwrap();
+        assert_eq!(index_is_current(&index, &root), Some(false));
+
+        // Adding a canon to a sparse checkout makes the index stale without moving HEAD.
+        record_index_revision(&index, &root);
+        std::fs::write(git.join("config"), "[core]\n\tsparseCheckout = true\n").unwrap();
+        std::fs::create_dir_all(git.join("info")).unwrap();
+        std::fs::write(git.join("info").join("sparse-checkout"), "/X/\n/T/\n").unwrap();
+        assert_eq!(
+            sparse_dirs(&root),
+            Some(vec!["T".to_string(), "X".to_string()])
+        );
+        assert_eq!(index_is_current(&index, &root), Some(false));
+        record_index_revision(&index, &root);
+        assert_eq!(index_is_current(&index, &root), Some(true));
+        assert!(ensure_cbeta_canon_at(&root, "T"));
+        assert_eq!(canon_of("GA0037"), Some("GA"));
+        assert_eq!(canon_of("T0262"), Some("T"));
+        assert_eq!(canon_of("0262"), None);
+
+        // Not a checkout: dated by the directory, no commit to compare.
+        let plain = dir.path().join("GRETIL");
+        std::fs::create_dir_all(&plain).unwrap();
+        assert!(corpus_revision(&plain).unwrap().commit.is_none());
+        assert!(corpus_revision(&dir.path().join("missing")).is_none());
+    }
+
+    fn git(dir: &Path, args: &[&str]) {
+        let ok = Command::new("git")
+            .args([
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "-c",
+                "init.defaultBranch=main",
+            ])
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+            .success();
+        assert!(ok, "git {:?}", args);
+    }
+
+    #[test]
+    fn interrupted_clone_resumes_and_verifies() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        for (path, body) in [
+            ("T/T01/a.xml", "a"),
+            ("T/T01/b.xml", "bb"),
+            ("X/c.xml", "c"),
+            ("README.md", "r"),
+        ] {
+            let p = src.join(path);
+            std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+            std::fs::write(p, body).unwrap();
+        }
+        git(&src, &["init", "-q"]);
+        git(&src, &["add", "-A"]);
+        git(&src, &["commit", "-qm", "data"]);
+
+        // What a clone killed before the first commit arrived leaves behind.
+        let root = dir.path().join("xml-p5");
+        std::fs::create_dir_all(&root).unwrap();
+        git(&root, &["init", "-q"]);
+        let url = format!("file://{}", src.display());
+        git(&root, &["remote", "add", "origin", &url]);
+        assert!(is_incomplete_clone(&root));
+        assert!(verify_checkout(&root, 2).is_none());
+        assert!(resume_clone(&root, true));
+        assert!(!is_incomplete_clone(&root));
+
+        let checks = verify_checkout(&root, 2).unwrap();
+        let names: Vec<&str> = checks.iter().map(|c| c.canon.as_str()).collect();
+        assert_eq!(names, [".", "T", "X"]);
+        assert!(checks.iter().all(CanonCheck::ok));
+
+        // A truncated file and a missing one.
+        std::fs::write(root.join("T/T01/b.xml"), "b").unwrap();
+        std::fs::remove_file(root.join("X/c.xml")).unwrap();
+        let checks = verify_checkout(&root, 2).unwrap();
+        assert_eq!(checks[1].mismatched, ["T/T01/b.xml"]);
+        assert_eq!((checks[2].expected, checks[2].present), (1, 0));
+        assert!(repair_checkout(&root));
+        assert!(verify_checkout(&root, 2)
+            .unwrap()
+            .iter()
+            .all(CanonCheck::ok));
+    }
+
+    #[test]
+    fn pull_lists_changed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(src.join("T")).unwrap();
+        std::fs::write(src.join("T/a.xml"), "a").unwrap();
+        std::fs::write(src.join("T/b.xml"), "b").unwrap();
+        git(&src, &["init", "-q"]);
+        git(&src, &["add", "-A"]);
+        git(&src, &["commit", "-qm", "one"]);
+        let root = dir.path().join("xml-p5");
+        git(dir.path(), &["clone", "-q", "src", "xml-p5"]);
+
+        let report = pull(&root).unwrap();
+        assert_eq!(report.from, report.to);
+        assert!(report.changed.is_empty());
+
+        std::fs::write(src.join("T/a.xml"), "a2").unwrap();
+        std::fs::remove_file(src.join("T/b.xml")).unwrap();
+        std::fs::write(src.join("T/c.xml"), "c").unwrap();
+        git(&src, &["add", "-A"]);
+        git(&src, &["commit", "-qm", "two"]);
+        let report = pull(&root).unwrap();
+        assert_ne!(report.from, report.to);
+        let changed: Vec<(char, &str)> = report
+            .changed
+            .iter()
+            .map(|c| (c.status, c.path.as_str()))
+            .collect();
+        assert_eq!(
+            changed,
+            [('M', "T/a.xml"), ('D', "T/b.xml"), ('A', "T/c.xml")]
+        );
+        assert_eq!(std::fs::read_to_string(root.join("T/c.xml")).unwrap(), "c");
+    }
+}
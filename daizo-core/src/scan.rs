@@ -0,0 +1,212 @@
+//! Limits on corpus scans.
+//!
+//! A whole-corpus search uses every core of rayon's global pool and reads as fast as the disk
+//! allows, which on a laptop leaves little for anything else. [`run`] executes a scan (a grep,
+//! corpus statistics, similarity profiles) in a pool of its own when a limit is set: `DAIZO_THREADS` (or `[scan] threads`) caps
+//! the workers, `DAIZO_SCAN_NICE` lowers their CPU and IO priority on Linux, and a call can ask
+//! for fewer workers still with [`with_max_parallelism`]. `DAIZO_SCAN_IO_MBPS` paces the files
+//! a search opens ([`pace`]) to a read rate shared by all workers.
+
+use crate::cancel::CancelToken;
+use crate::config;
+use crate::progress::Reporter;
+use crate::timing::Phase;
+use std::cell::Cell;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Effective scan limits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScanSettings {
+    /// Worker threads; 0 uses rayon's global pool.
+    pub threads: usize,
+    /// Read rate in bytes per second; 0 is unlimited.
+    pub io_bytes_per_sec: u64,
+    pub nice: bool,
+}
+
+fn env_parse<T: std::str::FromStr>(key: &str) -> Option<T> {
+    std::env::var(key).ok().and_then(|v| v.trim().parse().ok())
+}
+
+impl ScanSettings {
+    /// `DAIZO_THREADS`, `DAIZO_SCAN_IO_MBPS` and `DAIZO_SCAN_NICE`, else `cfg`.
+    pub fn from_config(cfg: &config::Scan) -> Self {
+        let nice = match std::env::var("DAIZO_SCAN_NICE") {
+            Ok(v) => matches!(
+                v.trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "on" | "yes"
+            ),
+            Err(_) => cfg.nice.unwrap_or(false),
+        };
+        ScanSettings {
+            threads: env_parse("DAIZO_THREADS").or(cfg.threads).unwrap_or(0),
+            io_bytes_per_sec: env_parse::<u64>("DAIZO_SCAN_IO_MBPS")
+                .or(cfg.io_mbps)
+                .unwrap_or(0)
+                << 20,
+            nice,
+        }
+    }
+
+    /// The settings in effect now (environment and the current config.toml).
+    pub fn current() -> Self {
+        ScanSettings::from_config(&config::current().scan)
+    }
+}
+
+thread_local! {
+    static MAX_PARALLELISM: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Run `f` with scans started from this thread limited to `n` workers (at most the
+/// configured number).
+pub fn with_max_parallelism<R>(n: usize, f: impl FnOnce() -> R) -> R {
+    let prev = MAX_PARALLELISM.with(|c| c.replace(Some(n.max(1))));
+    let out = f();
+    MAX_PARALLELISM.with(|c| c.set(prev));
+    out
+}
+
+/// Workers for a scan started now from this thread; 0 for rayon's default.
+fn workers(s: &ScanSettings) -> usize {
+    match (s.threads, MAX_PARALLELISM.with(|c| c.get())) {
+        (0, Some(n)) => n,
+        (t, Some(n)) => t.min(n),
+        (t, None) => t,
+    }
+}
+
+type PoolKey = (usize, bool);
+
+static POOLS: Mutex<Vec<(PoolKey, Arc<rayon::ThreadPool>)>> = Mutex::new(Vec::new());
+
+/// Lower the calling thread's CPU priority and put its IO in the idle class.
+#[cfg(target_os = "linux")]
+fn lower_priority() {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+    // SAFETY: both calls take plain integers; `who = 0` names the calling thread.
+    unsafe {
+        libc::setpriority(libc::PRIO_PROCESS, 0, 10);
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            0,
+            IOPRIO_CLASS_IDLE << 13,
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lower_priority() {}
+
+fn pool(key: PoolKey) -> Option<Arc<rayon::ThreadPool>> {
+    let mut g = POOLS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((_, p)) = g.iter().find(|(k, _)| *k == key) {
+        return Some(p.clone());
+    }
+    let (threads, nice) = key;
+    let mut b = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("daizo-scan-{}", i));
+    if nice {
+        b = b.start_handler(|_| lower_priority());
+    }
+    let p = Arc::new(b.build().ok()?);
+    g.push((key, p.clone()));
+    Some(p)
+}
+
+/// Run the scan `f` (whose parallel iterators then use the scan pool) under the current
+/// limits. Without limits it runs on rayon's global pool. The calling thread's cancel token
+/// and progress reporter stay bound inside `f`.
+pub fn run<R: Send>(f: impl FnOnce() -> R + Send) -> R {
+    let _t = Phase::Scan.start();
+    let s = ScanSettings::current();
+    let threads = workers(&s);
+    if threads == 0 && !s.nice {
+        return f();
+    }
+    let Some(p) = pool((threads, s.nice)) else {
+        return f();
+    };
+    let cancel = CancelToken::current();
+    let reporter = Reporter::current();
+    p.install(|| {
+        cancel.scope(|| match reporter {
+            Some(r) => r.scope(f),
+            None => f(),
+        })
+    })
+}
+
+/// Time the paced reads are booked until.
+static NEXT_READ: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Book `bytes` of reading at `rate` bytes per second; returns how long to wait first.
+fn book(bytes: u64, rate: u64) -> Duration {
+    let now = Instant::now();
+    let mut g = NEXT_READ.lock().unwrap_or_else(|e| e.into_inner());
+    let start = g.filter(|t| *t > now).unwrap_or(now);
+    *g = Some(start + Duration::from_secs_f64(bytes as f64 / rate as f64));
+    start - now
+}
+
+/// Before reading `path` in a scan: wait until the configured read rate allows it.
+pub fn pace(path: &Path) {
+    let rate = ScanSettings::current().io_bytes_per_sec;
+    if rate == 0 {
+        return;
+    }
+    if let Ok(m) = std::fs::metadata(path) {
+        let wait = book(m.len(), rate);
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rayon::prelude::*;
+
+    #[test]
+    fn scans_follow_the_limits() {
+        let s = ScanSettings::from_config(&config::Scan {
+            threads: Some(3),
+            io_mbps: Some(2),
+            nice: None,
+        });
+        if std::env::var_os("DAIZO_THREADS").is_none() {
+            assert_eq!(s.threads, 3);
+        }
+        if std::env::var_os("DAIZO_SCAN_IO_MBPS").is_none() {
+            assert_eq!(s.io_bytes_per_sec, 2 << 20);
+        }
+        let three = ScanSettings {
+            threads: 3,
+            ..Default::default()
+        };
+        assert_eq!(workers(&ScanSettings::default()), 0);
+        assert_eq!(workers(&three), 3);
+        with_max_parallelism(2, || {
+            assert_eq!(workers(&three), 2);
+            assert_eq!(workers(&ScanSettings::default()), 2);
+            with_max_parallelism(8, || assert_eq!(workers(&three), 3));
+            let n = pool((2, false))
+                .unwrap()
+                .install(rayon::current_num_threads);
+            assert_eq!(n, 2);
+        });
+        let sum: u32 = run(|| (1..=4u32).into_par_iter().sum());
+        assert_eq!(sum, 10);
+
+        // Bookings queue up behind each other at the given rate.
+        assert!(book(0, 1000).is_zero());
+        book(100, 1000);
+        assert!(book(100, 1000) >= Duration::from_millis(50));
+    }
+}
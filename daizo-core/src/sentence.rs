@@ -0,0 +1,130 @@
+//! Sentence boundaries for slicing (`snap: "sentence"`).
+//!
+//! A boundary is the position right after sentence-final punctuation — CJK 。？！, Sanskrit
+//! danda/double danda (। ॥), Tibetan shad (།), Burmese section marks (။) and, in romanized
+//! Pāli and Sanskrit, `.`/`?`/`!` followed by whitespace — together with any closing quotes or
+//! brackets and the whitespace that follow it, so the next sentence starts on its first
+//! letter. Offsets are in chars.
+
+/// Closing marks that stay with the sentence they end.
+fn is_closing(c: char) -> bool {
+    matches!(
+        c,
+        '」' | '』' | '）' | ')' | '”' | '’' | '"' | '\'' | '〕' | '】' | '》' | '〉' | ']'
+    )
+}
+
+/// Sentence-final punctuation that needs no following space.
+fn is_final(c: char) -> bool {
+    matches!(c, '。' | '？' | '！' | '｡' | '।' | '॥' | '།' | '༎' | '။')
+}
+
+/// Is `chars[i]` sentence-final? ASCII `.`/`?`/`!` count only before whitespace or the end, so
+/// `1.2` and `ti.ādi` do not.
+fn ends_sentence(chars: &[char], i: usize) -> bool {
+    match chars[i] {
+        c if is_final(c) => true,
+        '.' | '?' | '!' => {
+            let mut j = i + 1;
+            while j < chars.len() && is_closing(chars[j]) {
+                j += 1;
+            }
+            j == chars.len() || chars[j].is_whitespace()
+        }
+        _ => false,
+    }
+}
+
+/// Sentence boundaries of `chars` in `lo..=hi`, ascending. 0 and the end always count.
+fn boundaries(chars: &[char], lo: usize, hi: usize) -> Vec<usize> {
+    let hi = hi.min(chars.len());
+    let mut out = Vec::new();
+    if lo == 0 {
+        out.push(0);
+    }
+    // A boundary at `lo` comes from punctuation just before it.
+    let mut i = lo.saturating_sub(1);
+    while i < hi {
+        if ends_sentence(chars, i) {
+            let mut k = i + 1;
+            while k < chars.len() && (is_closing(chars[k]) || is_final(chars[k])) {
+                k += 1;
+            }
+            while k < chars.len() && chars[k].is_whitespace() {
+                k += 1;
+            }
+            if k >= lo && k <= hi && out.last() != Some(&k) {
+                out.push(k);
+            }
+            i = k;
+        } else {
+            i += 1;
+        }
+    }
+    if hi == chars.len() && out.last() != Some(&hi) {
+        out.push(hi);
+    }
+    out
+}
+
+/// Move `start..end` onto sentence boundaries at most `window` chars away: the start back to
+/// the beginning of its sentence, the end back to the last boundary in the slice (or, when
+/// there is none within `window` before it, forward to the next one). A bound with no boundary
+/// within `window` stays where it is, as do bounds already on a boundary.
+pub fn snap(text: &str, start: usize, end: usize, window: usize) -> (usize, usize) {
+    let chars: Vec<char> = text.chars().collect();
+    let end = end.min(chars.len());
+    let start = start.min(end);
+    let near = |at: usize| boundaries(&chars, at.saturating_sub(window), at + window);
+    let new_start = near(start)
+        .into_iter()
+        .rfind(|&b| b <= start)
+        .unwrap_or(start);
+    let around_end = near(end);
+    let new_end = around_end
+        .iter()
+        .copied()
+        .rfind(|&b| b <= end && b > new_start)
+        .or_else(|| around_end.iter().copied().find(|&b| b > end))
+        .unwrap_or(end);
+    (new_start, new_end)
+}
+
+/// The last sentence boundary after `after` and at or before `at`, at most `window` chars back.
+pub fn boundary_before(text: &str, after: usize, at: usize, window: usize) -> Option<usize> {
+    let chars: Vec<char> = text.chars().collect();
+    let at = at.min(chars.len());
+    boundaries(&chars, at.saturating_sub(window), at)
+        .into_iter()
+        .rfind(|&b| b > after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snaps_to_cjk_danda_and_pali_boundaries() {
+        let zh = "如是我聞。一時佛在舍衛國。「善哉！」佛言。";
+        // The end falls back to the last boundary before it; the start to its sentence.
+        assert_eq!(snap(zh, 0, 9, 10), (0, 5));
+        assert_eq!(snap(zh, 7, 20, 10), (5, 18));
+        // Closing quotes stay with their sentence.
+        assert_eq!(
+            &zh.chars().skip(13).take(5).collect::<String>(),
+            "「善哉！」"
+        );
+        assert_eq!(snap(zh, 13, 17, 10).1, 18);
+        // Already on boundaries: unchanged.
+        assert_eq!(snap(zh, 5, 13, 10), (5, 13));
+        // Nothing within the window: unchanged.
+        assert_eq!(snap(zh, 7, 9, 1), (7, 9));
+
+        let sa = "evaṃ mayā śrutam । ekasmin samaye ॥ bhagavān";
+        // No boundary inside the slice: the end moves forward to the double danda.
+        assert_eq!(snap(sa, 20, 30, 40), (19, 36));
+        let pi = "Evaṃ me sutaṃ. Ekaṃ samayaṃ 1.2 bhagavā";
+        assert_eq!(snap(pi, 0, 30, 30), (0, 15));
+        assert_eq!(snap(pi, 16, 38, 30).0, 15);
+    }
+}
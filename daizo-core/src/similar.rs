@@ -0,0 +1,266 @@
+//! Works that resemble each other in vocabulary.
+//!
+//! Each indexed file gets a profile: the counts of its most frequent terms, character bigrams
+//! for Chinese and words elsewhere. Profiles are kept in `~/.daizo/cache/<corpus>-profiles.json`
+//! next to the index they were built from and are rebuilt when that index is. [`Profiles::similar`]
+//! ranks the other works by cosine similarity of their profiles, with terms weighted by how
+//! rare they are across the corpus so that particles shared by every text count for little.
+//! Alternate translations of one sūtra and commentaries on a text score high.
+
+use crate::analysis::{plain_text_for_source, tokenize_segments};
+use crate::fetch_cache::write_atomic;
+use crate::{progress, IndexEntry};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Terms kept per work.
+pub const PROFILE_TERMS: usize = 300;
+
+/// Term counts of one file.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorkProfile {
+    pub path: String,
+    pub terms: Vec<(String, u32)>,
+}
+
+fn is_han(s: &str) -> bool {
+    let mut cs = s.chars();
+    matches!((cs.next(), cs.next()), (Some(c), None) if crate::titles::script_of(&c.to_string()) == "Hani")
+}
+
+/// The `PROFILE_TERMS` most frequent terms of `text`: bigrams of adjacent Han characters
+/// (single characters say little about a Chinese text) and words of three letters or more.
+pub fn profile_terms(text: &str) -> Vec<(String, u32)> {
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    for seg in tokenize_segments(text) {
+        for (i, t) in seg.iter().enumerate() {
+            if is_han(t) {
+                if let Some(next) = seg.get(i + 1).filter(|n| is_han(n)) {
+                    *counts.entry(format!("{}{}", t, next)).or_default() += 1;
+                }
+            } else if t.chars().count() >= 3 && !t.chars().all(|c| c.is_ascii_digit()) {
+                *counts.entry(t.clone()).or_default() += 1;
+            }
+        }
+    }
+    let mut terms: Vec<(String, u32)> = counts.into_iter().collect();
+    terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    terms.truncate(PROFILE_TERMS);
+    terms
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProfileFile {
+    /// Modification time (Unix seconds) and size of the index file the profiles came from.
+    index_modified: u64,
+    index_bytes: u64,
+    works: Vec<WorkProfile>,
+}
+
+fn index_stamp(index_file: &Path) -> Option<(u64, u64)> {
+    let m = std::fs::metadata(index_file).ok()?;
+    let secs = m
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some((secs, m.len()))
+}
+
+/// One work found similar, with the terms that contributed most.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Similar {
+    pub path: String,
+    pub score: f32,
+    pub shared: Vec<String>,
+}
+
+/// Profiles of a corpus with the weights derived from them.
+pub struct Profiles {
+    works: Vec<WorkProfile>,
+    /// Per work: term → tf-idf weight, and the vector's norm.
+    vectors: Vec<(HashMap<String, f32>, f32)>,
+}
+
+impl Profiles {
+    pub fn new(works: Vec<WorkProfile>) -> Self {
+        let n = works.len() as f32;
+        let mut df: HashMap<&str, u32> = HashMap::new();
+        for w in &works {
+            for (t, _) in &w.terms {
+                *df.entry(t.as_str()).or_default() += 1;
+            }
+        }
+        let vectors = works
+            .iter()
+            .map(|w| {
+                let v: HashMap<String, f32> = w
+                    .terms
+                    .iter()
+                    .map(|(t, c)| {
+                        let idf = (1.0 + n / df[t.as_str()] as f32).ln();
+                        (t.clone(), (1.0 + (*c as f32).ln()) * idf)
+                    })
+                    .collect();
+                let norm = v.values().map(|x| x * x).sum::<f32>().sqrt();
+                (v, norm)
+            })
+            .collect();
+        Profiles { works, vectors }
+    }
+
+    /// Profile a corpus: the plain text of every indexed file, as the fetch tools extract it.
+    pub fn build(corpus: &str, entries: &[IndexEntry]) -> Self {
+        let counter =
+            progress::Counter::new(&format!("profile {}", corpus), "files", entries.len());
+        let works = crate::scan::run(|| {
+            entries
+                .par_iter()
+                .map(|e| {
+                    counter.tick();
+                    let text = plain_text_for_source(corpus, Path::new(&e.path), None, false);
+                    WorkProfile {
+                        path: e.path.clone(),
+                        terms: text.map(|t| profile_terms(&t)).unwrap_or_default(),
+                    }
+                })
+                .collect()
+        });
+        Profiles::new(works)
+    }
+
+    /// The profiles stored in `file` when they were built from `index_file` as it is now;
+    /// otherwise built from `entries` and stored.
+    pub fn load_or_build(
+        corpus: &str,
+        entries: &[IndexEntry],
+        file: &Path,
+        index_file: &Path,
+    ) -> Self {
+        let stamp = index_stamp(index_file);
+        let stored: Option<ProfileFile> = std::fs::read(file)
+            .ok()
+            .and_then(|b| serde_json::from_slice(&b).ok());
+        if let (Some(s), Some((m, b))) = (stored, stamp) {
+            if s.index_modified == m && s.index_bytes == b && s.works.len() == entries.len() {
+                return Profiles::new(s.works);
+            }
+        }
+        let p = Profiles::build(corpus, entries);
+        if let Some((index_modified, index_bytes)) = stamp {
+            let out = ProfileFile {
+                index_modified,
+                index_bytes,
+                works: p.works.clone(),
+            };
+            if let Ok(b) = serde_json::to_vec(&out) {
+                let _ = write_atomic(file, &b);
+            }
+        }
+        p
+    }
+
+    pub fn len(&self) -> usize {
+        self.works.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.works.is_empty()
+    }
+
+    /// The `limit` works most similar to the one at `path`, best first. None when `path` has
+    /// no profile.
+    pub fn similar(&self, path: &str, limit: usize) -> Option<Vec<Similar>> {
+        let i = self.works.iter().position(|w| w.path == path)?;
+        let (qv, qn) = &self.vectors[i];
+        if *qn == 0.0 {
+            return Some(Vec::new());
+        }
+        let mut out: Vec<Similar> = self
+            .vectors
+            .iter()
+            .enumerate()
+            .filter(|(j, (_, n))| *j != i && *n > 0.0)
+            .filter_map(|(j, (v, n))| {
+                let mut parts: Vec<(&str, f32)> = qv
+                    .iter()
+                    .filter_map(|(t, w)| Some((t.as_str(), w * v.get(t)?)))
+                    .collect();
+                if parts.is_empty() {
+                    return None;
+                }
+                let dot: f32 = parts.iter().map(|(_, x)| x).sum();
+                parts.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+                Some(Similar {
+                    path: self.works[j].path.clone(),
+                    score: dot / (qn * n),
+                    shared: parts.iter().take(8).map(|(t, _)| t.to_string()).collect(),
+                })
+            })
+            .collect();
+        out.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        out.truncate(limit);
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn work(path: &str, text: &str) -> WorkProfile {
+        WorkProfile {
+            path: path.into(),
+            terms: profile_terms(text),
+        }
+    }
+
+    #[test]
+    fn translations_of_one_text_rank_first() {
+        let t = profile_terms("觀自在菩薩。觀自在菩薩行深般若。the the sutra 12");
+        assert!(t.contains(&("自在".to_string(), 2)) && t.contains(&("般若".to_string(), 1)));
+        assert!(t.iter().any(|(w, c)| w == "the" && *c == 2));
+        assert!(!t.iter().any(|(w, _)| w == "12" || w == "觀"));
+
+        let p = Profiles::new(vec![
+            work("T0251", "觀自在菩薩行深般若波羅蜜多時照見五蘊皆空"),
+            work("T0250", "觀世音菩薩行深般若波羅蜜時照見五陰空"),
+            work("T0262", "如是我聞一時佛住王舍城耆闍崛山中"),
+            work("T0001", "如是我聞一時佛在舍衛國"),
+        ]);
+        assert_eq!(p.len(), 4);
+        let s = p.similar("T0251", 2).unwrap();
+        assert_eq!(s[0].path, "T0250");
+        assert!(s[0].score > 0.0 && s[0].shared.contains(&"般若".to_string()));
+        // Works sharing no term are not listed.
+        assert_eq!(s.len(), 1);
+        assert_eq!(p.similar("T0262", 5).unwrap()[0].path, "T0001");
+        assert!(p.similar("missing", 5).is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let index = dir.path().join("cbeta-index.json");
+        let file = dir.path().join("cbeta-profiles.json");
+        std::fs::write(&index, "[]").unwrap();
+        let f = dir.path().join("T0251.txt");
+        std::fs::write(&f, "般若波羅蜜多").unwrap();
+        let entries = vec![IndexEntry {
+            id: "T0251".into(),
+            title: "心經".into(),
+            path: f.to_string_lossy().into(),
+            meta: None,
+        }];
+        let built = Profiles::load_or_build("gretil", &entries, &file, &index);
+        assert!(file.exists());
+        let again = Profiles::load_or_build("gretil", &entries, &file, &index);
+        assert_eq!(built.works, again.works);
+        assert!(again.works[0].terms.contains(&("般若".to_string(), 1)));
+    }
+}
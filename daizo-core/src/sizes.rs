@@ -0,0 +1,178 @@
+//! Text sizes recorded in the index, so search hints can tell how much a fetch returns.
+//!
+//! The XML size overstates a fetch several times over (markup, apparatus, header), so the
+//! index keeps `fileSize` (bytes on disk), `textChars` (plain-text characters) and, for CBETA,
+//! `juanChars` (`001:12034,002:9810`), the plain-text characters of each juan.
+
+use crate::juan_map::JuanMap;
+use crate::{cbeta_gaiji_map_fast, extract_cbeta_juan_plain_with_map, FetchHints, GrepResult};
+use crate::{stem_from, IndexEntry};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Plain-text size of one part (CBETA juan).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartSize {
+    pub part: String,
+    pub chars: usize,
+}
+
+/// Plain-text characters of each juan of a CBETA file, in document order.
+pub fn cbeta_juan_chars(xml: &str) -> Vec<PartSize> {
+    let map = JuanMap::build(xml);
+    if map.juans.is_empty() {
+        return Vec::new();
+    }
+    let gaiji = cbeta_gaiji_map_fast(xml);
+    map.juans
+        .iter()
+        .map(|j| PartSize {
+            part: j.n.clone(),
+            chars: extract_cbeta_juan_plain_with_map(xml, &map, &gaiji, &j.n, false)
+                .map(|t| t.chars().count())
+                .unwrap_or(0),
+        })
+        .collect()
+}
+
+/// Record the sizes of `path` in index metadata.
+pub fn record(
+    meta: &mut BTreeMap<String, String>,
+    path: &Path,
+    text_chars: usize,
+    parts: &[PartSize],
+) {
+    if let Ok(m) = std::fs::metadata(path) {
+        meta.insert("fileSize".to_string(), m.len().to_string());
+    }
+    meta.insert("textChars".to_string(), text_chars.to_string());
+    if !parts.is_empty() {
+        let s = parts
+            .iter()
+            .map(|p| format!("{}:{}", p.part, p.chars))
+            .collect::<Vec<_>>()
+            .join(",");
+        meta.insert("juanChars".to_string(), s);
+    }
+}
+
+/// Juan sizes stored by [`record`].
+pub fn parse_parts(s: &str) -> Vec<PartSize> {
+    s.split(',')
+        .filter_map(|kv| {
+            let (part, chars) = kv.split_once(':')?;
+            Some(PartSize {
+                part: part.trim().to_string(),
+                chars: chars.trim().parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Sizes of an index entry: (file bytes, text chars, juan sizes).
+pub fn of_entry(e: &IndexEntry) -> (Option<u64>, Option<usize>, Vec<PartSize>) {
+    let Some(m) = e.meta.as_ref() else {
+        return (None, None, Vec::new());
+    };
+    (
+        m.get("fileSize").and_then(|s| s.parse().ok()),
+        m.get("textChars").and_then(|s| s.parse().ok()),
+        m.get("juanChars")
+            .map(|s| parse_parts(s))
+            .unwrap_or_default(),
+    )
+}
+
+/// One-line size description for summaries, e.g. "48210 chars of text in 3 juan (largest 17002)".
+pub fn describe(text_chars: usize, parts: &[PartSize]) -> String {
+    match parts.iter().map(|p| p.chars).max() {
+        Some(max) if parts.len() > 1 => format!(
+            "{} chars of text in {} juan (largest {})",
+            text_chars,
+            parts.len(),
+            max
+        ),
+        _ => format!("{} chars of text", text_chars),
+    }
+}
+
+/// Replace the XML-size guess in `results` with the sizes recorded in `idx` (matched by file
+/// stem). Results whose file is not in the index keep their estimate.
+pub fn apply_index_sizes(results: &mut [GrepResult], idx: &[IndexEntry]) {
+    let by_stem: HashMap<String, &IndexEntry> = idx
+        .iter()
+        .map(|e| (stem_from(Path::new(&e.path)), e))
+        .collect();
+    for r in results.iter_mut() {
+        let Some(e) = by_stem.get(&r.file_id) else {
+            continue;
+        };
+        let (file_size, text_chars, parts) = of_entry(e);
+        let Some(text_chars) = text_chars else {
+            continue;
+        };
+        r.fetch_hints = FetchHints {
+            total_content_size: Some(describe(text_chars, &parts)),
+            file_size: file_size.or(r.fetch_hints.file_size),
+            text_chars: Some(text_chars),
+            part_chars: parts,
+            ..std::mem::take(&mut r.fetch_hints)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn juan_sizes_round_trip_through_the_index() {
+        let xml = r#"<TEI><text><body>
+<cb:juan n="001" fun="open"><cb:jhead>卷第一</cb:jhead></cb:juan><p>如是我聞</p>
+<cb:juan n="001" fun="close"/>
+<cb:juan n="002" fun="open"><cb:jhead>卷第二</cb:jhead></cb:juan><p>一時佛住王舍城</p>
+<cb:juan n="002" fun="close"/>
+</body></text></TEI>"#;
+        let parts = cbeta_juan_chars(xml);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].part, "001");
+        assert!(parts[1].chars > parts[0].chars);
+
+        let dir = tempfile::tempdir().unwrap();
+        let p = dir.path().join("T01n0001.xml");
+        std::fs::write(&p, xml).unwrap();
+        let mut meta = BTreeMap::new();
+        let total = parts.iter().map(|p| p.chars).sum();
+        record(&mut meta, &p, total, &parts);
+        assert_eq!(meta["fileSize"], xml.len().to_string());
+        let entry = IndexEntry {
+            id: "T01n0001".into(),
+            title: String::new(),
+            path: p.to_string_lossy().into_owned(),
+            meta: Some(meta),
+        };
+        let mut results = vec![GrepResult {
+            file_path: entry.path.clone(),
+            file_id: "T01n0001".into(),
+            title: String::new(),
+            matches: vec![],
+            total_matches: 0,
+            fetch_hints: FetchHints {
+                total_content_size: Some("0KB".into()),
+                ..Default::default()
+            },
+            score: None,
+            titles: None,
+        }];
+        apply_index_sizes(&mut results, &[entry]);
+        let h = &results[0].fetch_hints;
+        assert_eq!(h.text_chars, Some(total));
+        assert_eq!(h.part_chars, parts);
+        assert!(h
+            .total_content_size
+            .as_deref()
+            .unwrap()
+            .contains("in 2 juan"));
+    }
+}
@@ -0,0 +1,140 @@
+//! Merging of search matches that would repeat each other in the output.
+//!
+//! Grep reports one match per line, so a term that occurs on neighbouring lines comes back as
+//! several overlapping contexts, and refrains repeat the same line many times. [`merge_matches`]
+//! joins matches on adjacent lines whose occurrences lie within a few characters of each other
+//! into one snippet with one highlight range per occurrence, and folds identical lines into the
+//! first one.
+
+use crate::{GrepMatch, GrepResult};
+use regex::Regex;
+
+/// Default largest distance, in characters, between occurrences that are merged.
+pub const DEFAULT_WITHIN: usize = 80;
+
+/// Char ranges of the occurrences of `re` in `s`.
+fn occurrences(re: &Regex, s: &str) -> Vec<[usize; 2]> {
+    let mut out = Vec::new();
+    let mut chars = 0usize;
+    let mut last = 0usize;
+    for m in re.find_iter(s) {
+        if m.start() == m.end() {
+            continue;
+        }
+        chars += s[last..m.start()].chars().count();
+        let len = m.as_str().chars().count();
+        out.push([chars, chars + len]);
+        chars += len;
+        last = m.end();
+    }
+    out
+}
+
+/// Characters between the last occurrence in `a` and the first one in `b`, across the line
+/// break. Without an occurrence the whole line counts.
+fn distance(a: &GrepMatch, b: &GrepMatch, re: &Regex) -> usize {
+    let a_len = a.context.chars().count();
+    let after = occurrences(re, &a.context)
+        .last()
+        .map(|r| a_len - r[1])
+        .unwrap_or(a_len);
+    let before = occurrences(re, &b.context)
+        .first()
+        .map(|r| r[0])
+        .unwrap_or_else(|| b.context.chars().count());
+    after + 1 + before
+}
+
+fn adjacent(a: &GrepMatch, b: &GrepMatch) -> bool {
+    let Some(last) = a.end_line.or(a.line_number) else {
+        return false;
+    };
+    b.line_number == Some(last + 1) && a.section == b.section && a.juan_number == b.juan_number
+}
+
+fn add_patterns(into: &mut Vec<usize>, from: &[usize]) {
+    for p in from {
+        if !into.contains(p) {
+            into.push(*p);
+        }
+    }
+    into.sort_unstable();
+}
+
+/// Merge the matches of one file; `re` finds the occurrences of the searched pattern.
+pub fn merge(matches: Vec<GrepMatch>, re: &Regex, within: usize) -> Vec<GrepMatch> {
+    let mut out: Vec<GrepMatch> = Vec::with_capacity(matches.len());
+    for m in matches {
+        let text = m.context.trim();
+        if let Some(first) = out
+            .iter_mut()
+            .find(|p| p.section == m.section && p.context.lines().any(|l| l.trim() == text))
+        {
+            first.also_at.extend(m.line_number);
+            add_patterns(&mut first.patterns, &m.patterns);
+            continue;
+        }
+        if let Some(last) = out.last_mut() {
+            if adjacent(last, &m) && distance(last, &m, re) <= within {
+                last.context.push('\n');
+                last.context.push_str(&m.context);
+                last.end_line = m.line_number;
+                add_patterns(&mut last.patterns, &m.patterns);
+                continue;
+            }
+        }
+        out.push(m);
+    }
+    for m in out.iter_mut() {
+        let r = occurrences(re, &m.context);
+        if r.len() > 1 {
+            m.ranges = r;
+        }
+    }
+    out
+}
+
+/// [`merge`] the matches of every result. `total_matches` is left as counted by grep.
+pub fn merge_matches(results: &mut [GrepResult], re: &Regex, within: usize) {
+    for r in results.iter_mut() {
+        let ms = std::mem::take(&mut r.matches);
+        r.matches = merge(ms, re, within);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(line: usize, context: &str) -> GrepMatch {
+        GrepMatch {
+            context: context.into(),
+            highlight: "空".into(),
+            line_number: Some(line),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn neighbouring_and_repeated_lines_become_one_snippet() {
+        let re = Regex::new("空").unwrap();
+        let ms = vec![
+            at(10, "<lb n=\"0848c10\"/>色不異空空不異色"),
+            at(11, "<lb n=\"0848c11\"/>色即是空"),
+            at(20, "<lb n=\"0848c20\"/>是故空中無色"),
+            at(30, "<lb n=\"0848c10\"/>色不異空空不異色"),
+        ];
+        let out = merge(ms.clone(), &re, 30);
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].end_line, Some(11));
+        assert_eq!(out[0].ranges, [[20, 21], [21, 22], [46, 47]]);
+        assert_eq!(out[0].also_at, [30]);
+        assert!(out[1].ranges.is_empty());
+
+        // Too far apart to merge; the repeated line still folds.
+        let out = merge(ms, &re, 5);
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0].end_line, None);
+        assert_eq!(out[0].also_at, [30]);
+    }
+}
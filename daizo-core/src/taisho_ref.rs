@@ -0,0 +1,276 @@
+//! Citations of CBETA texts by canon, text number, volume, page and column
+//! (`T 2076, 51:196b`, `T51, no. 2076, p. 196b12`, `T51n2076_p0196b12`).
+//!
+//! [`parse`] reads the citation; [`locate`] finds the `<lb n>` line marker it points at in a
+//! file of that text, using the file's juan map to name the juan. A citation without a line
+//! number lands on the first line of its column or page.
+//!
+//! SAT numbers lines of the Taishō the same way; [`parse_sat`] and [`TaishoRef::sat_id`]
+//! convert to and from its line ids (`0262_,09,0001c14`).
+
+use crate::juan_map::JuanMap;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// A parsed citation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaishoRef {
+    /// Canon code: `T`, `X`, `J`, …
+    pub canon: String,
+    /// Text number as CBETA writes it, four digits and an optional part letter (`2076`, `0220a`).
+    pub number: String,
+    pub volume: Option<u32>,
+    pub page: Option<u32>,
+    /// Column letter (`a`, `b`, `c`).
+    pub column: Option<char>,
+    pub line: Option<u32>,
+}
+
+impl TaishoRef {
+    /// Work id in the form `cbeta_fetch` takes: `T2076`.
+    pub fn id(&self) -> String {
+        format!("{}{}", self.canon, self.number)
+    }
+
+    /// Prefix of the `<lb n>` values the citation covers: `0196b12`, `0196b` or `0196`.
+    pub fn lb_prefix(&self) -> Option<String> {
+        let page = self.page?;
+        let mut s = format!("{:04}", page);
+        if let Some(c) = self.column {
+            s.push(c);
+            if let Some(l) = self.line {
+                s.push_str(&format!("{:02}", l));
+            }
+        }
+        Some(s)
+    }
+
+    /// The citation written the usual way: `T 2076, 51:196b12`.
+    pub fn canonical(&self) -> String {
+        let mut s = format!("{} {}", self.canon, self.number);
+        if let Some(v) = self.volume {
+            s.push_str(&format!(", {}", v));
+            if let Some(p) = self.page {
+                s.push_str(&format!(":{}", p));
+                if let Some(c) = self.column {
+                    s.push(c);
+                }
+                if let Some(l) = self.line {
+                    s.push_str(&l.to_string());
+                }
+            }
+        }
+        s
+    }
+
+    /// SAT line id of the citation (`0262_,09,0001c14`); a citation of a page or column
+    /// names its first line. Only Taishō citations with a page have one.
+    pub fn sat_id(&self) -> Option<String> {
+        if self.canon != "T" {
+            return None;
+        }
+        let (volume, page) = (self.volume?, self.page?);
+        let digits: String = self.number.chars().filter(|c| c.is_ascii_digit()).collect();
+        let part = self.number[digits.len()..].chars().next().unwrap_or('_');
+        Some(format!(
+            "{}{},{:02},{:04}{}{:02}",
+            digits,
+            part,
+            volume,
+            page,
+            self.column.unwrap_or('a'),
+            self.line.unwrap_or(1)
+        ))
+    }
+}
+
+/// Read a SAT line id (`0262_,09,0001c14`) as a Taishō citation.
+pub fn parse_sat(s: &str) -> Option<TaishoRef> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"^(\d{4})([A-Za-z_])?[,.](\d{1,3})[,.](\d{4})([a-z])(\d{2})$").unwrap()
+    });
+    let c = re.captures(s.trim())?;
+    let part = c
+        .get(2)
+        .map(|m| m.as_str().to_ascii_lowercase())
+        .filter(|p| p != "_")
+        .unwrap_or_default();
+    Some(TaishoRef {
+        canon: "T".to_string(),
+        number: format!("{}{}", &c[1], part),
+        volume: c[3].parse().ok(),
+        page: c[4].parse().ok(),
+        column: c[5].chars().next(),
+        line: c[6].parse().ok(),
+    })
+}
+
+/// Four-digit text number with its part letter, if any.
+fn text_number(s: &str) -> String {
+    let digits: String = s.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let rest: String = s[digits.len()..].to_ascii_lowercase();
+    format!("{:0>4}{}", digits.trim_start_matches('0'), rest)
+}
+
+/// Read a citation. Accepted forms:
+///
+/// - `T 2076, 51:196b12` (number, then volume:page, column and line)
+/// - `T51, no. 2076, p. 196b12` (volume first, as CBETA cites itself)
+/// - `T51n2076_p0196b12` (a CBETA line head)
+/// - `T 2076` or `T2076` (the text alone)
+///
+/// Column and line are optional; a line range (`196b12-c3`) cites its first line.
+pub fn parse(s: &str) -> Option<TaishoRef> {
+    static FORMS: OnceLock<[Regex; 3]> = OnceLock::new();
+    let forms = FORMS.get_or_init(|| {
+        let loc = r"(?:p\.?\s*)?(\d{1,4})([a-z])?(\d{1,2})?";
+        [
+            Regex::new(r"^([A-Z]{1,2})(\d{1,3})n(\d{1,5}[A-Za-z]?)(?:_p(\d{4})([a-z])?(\d{2})?)?")
+                .unwrap(),
+            Regex::new(&format!(
+                r"^([A-Z]{{1,2}})\s*(\d{{1,3}})\s*,\s*no\.?\s*(\d{{1,5}}[A-Za-z]?)(?:\s*,\s*{loc})?"
+            ))
+            .unwrap(),
+            Regex::new(&format!(
+                r"^([A-Z]{{1,2}})\.?\s*(?:no\.?\s*)?(\d{{1,5}}[A-Za-z]?)(?:\s*[,;]?\s*(?:vol\.?\s*)?(\d{{1,3}})\s*[:：]\s*{loc})?"
+            ))
+            .unwrap(),
+        ]
+    });
+    let s = s.trim();
+    let num = |m: Option<regex::Match>| m.and_then(|m| m.as_str().parse::<u32>().ok());
+    for (i, re) in forms.iter().enumerate() {
+        let Some(c) = re.captures(s) else {
+            continue;
+        };
+        // Anything left over must be a line range or punctuation, not more citation.
+        let rest = s[c.get(0)?.end()..].trim();
+        if !rest.is_empty() && !rest.starts_with(['-', '–', ')', '.', ';', ',']) {
+            continue;
+        }
+        let (volume, number) = if i == 2 {
+            (num(c.get(3)), c.get(2)?.as_str())
+        } else {
+            (num(c.get(2)), c.get(3)?.as_str())
+        };
+        let (page, line) = (num(c.get(4)), num(c.get(6)));
+        let column = c.get(5).and_then(|m| m.as_str().chars().next());
+        return Some(TaishoRef {
+            canon: c[1].to_string(),
+            number: text_number(number),
+            volume,
+            page,
+            column,
+            line: line.filter(|_| column.is_some()),
+        });
+    }
+    None
+}
+
+/// Where a citation lands in a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LbHit {
+    /// `n` of the `<lb>` marker: `0196b12`.
+    pub lb: String,
+    /// 1-based XML line of the marker.
+    pub line: usize,
+    /// Whether the marker is the cited line (or in the cited column or page) rather than
+    /// the next line after a gap.
+    pub exact: bool,
+    /// Juan containing it ("003").
+    pub juan: Option<String>,
+}
+
+/// Find the line marker `r` cites in `xml`, a file of the cited text. `None` when the
+/// citation has no page or the page lies outside the file.
+pub fn locate(xml: &str, r: &TaishoRef) -> Option<LbHit> {
+    static LB: OnceLock<Regex> = OnceLock::new();
+    static ATTR: OnceLock<Regex> = OnceLock::new();
+    let lb_re = LB.get_or_init(|| Regex::new(r"<lb\b([^>]*)/?>").unwrap());
+    let attr = ATTR.get_or_init(|| Regex::new(r#"\b(n|ed)\s*=\s*["']([^"']*)["']"#).unwrap());
+    let target = r.lb_prefix()?;
+    let mut first: Option<String> = None;
+    for m in lb_re.captures_iter(xml) {
+        let (mut n, mut ed) = (None, None);
+        for a in attr.captures_iter(&m[1]) {
+            match &a[1] {
+                "n" => n = Some(a[2].to_string()),
+                _ => ed = Some(a[2].to_string()),
+            }
+        }
+        // Markers of other editions (ed="R…") number other pages.
+        let Some(n) = n.filter(|_| ed.as_deref().is_none_or(|e| e == r.canon)) else {
+            continue;
+        };
+        let first = first.get_or_insert_with(|| n.clone());
+        let exact = n.starts_with(&target);
+        if exact || n.as_str() > target.as_str() {
+            if !exact && n == *first {
+                // The file starts after the cited page.
+                return None;
+            }
+            let start = m.get(0)?.start();
+            let line = xml[..start].matches('\n').count() + 1;
+            let juan = JuanMap::build(xml)
+                .juan_at_offset(start)
+                .map(|j| j.n.clone());
+            return Some(LbHit {
+                lb: n,
+                line,
+                exact,
+                juan,
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn citations_parse_and_land_on_lines() {
+        let r = parse("T 2076, 51:196b").unwrap();
+        assert_eq!(
+            (r.id(), r.volume, r.page, r.column),
+            ("T2076".into(), Some(51), Some(196), Some('b'))
+        );
+        assert_eq!(r.lb_prefix().as_deref(), Some("0196b"));
+        let same = [
+            "T51, no. 2076, p. 196b12",
+            "T51n2076_p0196b12",
+            "T2076, 51:196b12-c3",
+        ];
+        for s in same {
+            let r = parse(s).unwrap();
+            assert_eq!(r.canonical(), "T 2076, 51:196b12", "{}", s);
+        }
+        assert_eq!(parse("T 220a").unwrap().id(), "T0220a");
+        assert_eq!(parse("X1565").unwrap().page, None);
+        assert!(parse("T 2076 foo").is_none());
+
+        let xml = "<body>\n<juan fun=\"open\" n=\"1\"/>\n<lb n=\"0196a29\"/>甲\n\
+                   <lb n=\"0196b01\"/>乙\n<lb ed=\"R\" n=\"0196b02\"/>\n<lb n=\"0196b02\"/>丙\n</body>";
+        let hit = locate(xml, &parse("T 2076, 51:196b").unwrap()).unwrap();
+        assert_eq!((hit.lb.as_str(), hit.line, hit.exact), ("0196b01", 4, true));
+        assert_eq!(hit.juan.as_deref(), Some("001"));
+        let gap = locate(xml, &parse("T 2076, 51:196a30").unwrap()).unwrap();
+        assert_eq!((gap.lb.as_str(), gap.exact), ("0196b01", false));
+        assert!(locate(xml, &parse("T 2076, 51:195a").unwrap()).is_none());
+        assert!(locate(xml, &parse("T 2076, 51:197a").unwrap()).is_none());
+    }
+
+    #[test]
+    fn sat_line_ids_round_trip() {
+        let r = parse_sat("0262_,09,0001c14").unwrap();
+        assert_eq!(r.canonical(), "T 0262, 9:1c14");
+        assert_eq!(r.sat_id().as_deref(), Some("0262_,09,0001c14"));
+        assert_eq!(parse_sat("0220a,05,0001a01").unwrap().id(), "T0220a");
+        let page = parse("T 2076, 51:196b").unwrap();
+        assert_eq!(page.sat_id().as_deref(), Some("2076_,51,0196b01"));
+        assert!(parse("X1565").unwrap().sat_id().is_none());
+        assert!(parse_sat("T0262").is_none());
+    }
+}
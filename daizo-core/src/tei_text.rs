@@ -0,0 +1,735 @@
+//! Streaming TEI → plain text or Markdown conversion, independent of any corpus.
+//!
+//! [`convert`] reads TEI XML from a `BufRead` and writes text while it parses, so
+//! `daizo-cli extract-text` works as a filter in shell pipelines on files of any size. The
+//! `<charDecl>` in the header comes before the body, so gaiji declarations are collected on
+//! the way and resolve the `<g>` references that follow them.
+
+use crate::{attr_val, local_name};
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+/// Where line breaks go in the output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineBreaks {
+    /// One line: blocks and verse lines are separated by a space; `<lb/>` and `<pb/>` add
+    /// one only where the script uses spaces (not between two CJK characters).
+    #[default]
+    None,
+    /// A blank line between paragraphs, heads and other blocks; verse lines and list items
+    /// on lines of their own.
+    Paragraph,
+    /// As `Paragraph`, and every `<lb/>` starts a line and `<pb/>` leaves a blank line.
+    Lb,
+}
+
+impl LineBreaks {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "none" | "join" => Some(LineBreaks::None),
+            "paragraph" | "p" => Some(LineBreaks::Paragraph),
+            "lb" | "line" => Some(LineBreaks::Lb),
+            _ => None,
+        }
+    }
+}
+
+/// How `<g ref="#id">` characters are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GaijiMode {
+    /// The Unicode mapping, else the normalized form, else the composition.
+    #[default]
+    Unicode,
+    /// The normalized (regular) form first.
+    Normal,
+    /// The composition description, e.g. `[王*番]`.
+    Composition,
+    /// The id in brackets, e.g. `[CB00178]`.
+    Ref,
+    /// Nothing.
+    Drop,
+}
+
+impl GaijiMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "unicode" => Some(GaijiMode::Unicode),
+            "normal" | "normalized" => Some(GaijiMode::Normal),
+            "composition" | "ids" => Some(GaijiMode::Composition),
+            "ref" | "id" => Some(GaijiMode::Ref),
+            "drop" | "none" => Some(GaijiMode::Drop),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextFormat {
+    #[default]
+    Text,
+    /// Heads as `#` headings (level from `<div>` nesting), verse as block quotes, list
+    /// items as `- `, `<hi>` as emphasis and notes as footnotes.
+    Markdown,
+}
+
+impl TextFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "text" | "plain" | "txt" => Some(TextFormat::Text),
+            "markdown" | "md" => Some(TextFormat::Markdown),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextOptions {
+    /// Keep `<note>` text: inline after `[注]` in text, as footnotes in Markdown.
+    pub include_notes: bool,
+    /// Markdown always separates blocks; `None` is read as `Paragraph` there.
+    pub line_breaks: LineBreaks,
+    pub gaiji: GaijiMode,
+    pub format: TextFormat,
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x2E80..=0x303F | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F | 0xFF00..=0xFFEF | 0x20000..=0x3FFFF)
+}
+
+/// A space waiting for the next text. Line breaks in the source and `<lb/>` are soft: they
+/// vanish between two CJK characters. Spaces typed in the text and block boundaries are hard.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Space {
+    None,
+    Soft,
+    Hard,
+}
+
+/// Writes text with runs of whitespace collapsed. Breaks are held until the next text, so
+/// consecutive block ends merge and the output neither starts nor ends with blank lines.
+struct Sink<W: Write> {
+    out: W,
+    started: bool,
+    /// Newlines to write before the next text (at most two).
+    newlines: usize,
+    space: Space,
+    /// Set after an opening marker (`**`, `# `), which takes no space after it.
+    glue: bool,
+    last: Option<char>,
+    /// Written at the start of each line (`> ` inside verse in Markdown).
+    prefix: &'static str,
+}
+
+impl<W: Write> Sink<W> {
+    fn new(out: W) -> Self {
+        Sink {
+            out,
+            started: false,
+            newlines: 1,
+            space: Space::None,
+            glue: false,
+            last: None,
+            prefix: "",
+        }
+    }
+
+    fn lead(&mut self, next: Option<char>) -> io::Result<()> {
+        if self.newlines > 0 {
+            if self.started {
+                self.out.write_all(&b"\n\n"[..self.newlines.min(2)])?;
+            }
+            self.out.write_all(self.prefix.as_bytes())?;
+        } else if self.space == Space::Hard
+            || (self.space == Space::Soft
+                && !matches!((self.last, next), (Some(a), Some(b)) if is_cjk(a) && is_cjk(b)))
+        {
+            self.out.write_all(b" ")?;
+        }
+        Ok(())
+    }
+
+    fn wrote(&mut self, last: Option<char>) {
+        self.started = true;
+        self.newlines = 0;
+        self.space = Space::None;
+        self.glue = false;
+        self.last = last;
+    }
+
+    fn text(&mut self, s: &str) -> io::Result<()> {
+        let mut utf8 = [0u8; 4];
+        let mut run = Space::None;
+        for ch in s.chars() {
+            if ch.is_whitespace() {
+                let kind = if matches!(ch, '\n' | '\r' | '\t') {
+                    Space::Soft
+                } else {
+                    Space::Hard
+                };
+                run = run.max(kind);
+                continue;
+            }
+            if run != Space::None {
+                self.add_space(run);
+                run = Space::None;
+            }
+            self.lead(Some(ch))?;
+            self.out.write_all(ch.encode_utf8(&mut utf8).as_bytes())?;
+            self.wrote(Some(ch));
+        }
+        self.add_space(run);
+        Ok(())
+    }
+
+    /// A marker or word written as is, after a space if one is pending.
+    fn word(&mut self, s: &str) -> io::Result<()> {
+        self.lead(s.chars().next())?;
+        self.out.write_all(s.as_bytes())?;
+        self.wrote(s.chars().last());
+        Ok(())
+    }
+
+    fn open(&mut self, s: &str) -> io::Result<()> {
+        self.word(s)?;
+        self.glue = true;
+        Ok(())
+    }
+
+    /// A closing marker sticks to the preceding text; a pending space moves after it.
+    fn close(&mut self, s: &str) -> io::Result<()> {
+        let space = std::mem::replace(&mut self.space, Space::None);
+        self.word(s)?;
+        self.space = space;
+        Ok(())
+    }
+
+    fn add_space(&mut self, kind: Space) {
+        if self.newlines == 0 && !self.glue {
+            self.space = self.space.max(kind);
+        }
+    }
+
+    fn space(&mut self) {
+        self.add_space(Space::Soft);
+    }
+
+    fn separator(&mut self) {
+        self.add_space(Space::Hard);
+    }
+
+    fn newline(&mut self, n: usize) {
+        self.newlines = self.newlines.max(n);
+        self.space = Space::None;
+        self.glue = false;
+    }
+}
+
+#[derive(Default)]
+struct CharDecl {
+    unicode: Option<String>,
+    normal: Option<String>,
+    composition: Option<String>,
+}
+
+/// `U+24D36` → the character; anything else as is.
+fn mapping_value(s: &str) -> String {
+    let s = s.trim();
+    s.strip_prefix("U+")
+        .or_else(|| s.strip_prefix("u+"))
+        .and_then(|h| u32::from_str_radix(h, 16).ok())
+        .and_then(char::from_u32)
+        .map(String::from)
+        .unwrap_or_else(|| s.to_string())
+}
+
+/// `<charDecl>` entries gathered while the header streams past.
+#[derive(Default)]
+struct Gaiji {
+    chars: HashMap<String, CharDecl>,
+    cur: Option<(String, CharDecl)>,
+    /// Element whose text is being read: `mapping:<type>`, `localName` or `value`.
+    field: Option<String>,
+    prop_name: String,
+}
+
+impl Gaiji {
+    fn start(&mut self, name: &[u8], e: &BytesStart) {
+        match name {
+            b"char" | b"glyph" => {
+                self.cur = attr_val(e, b"xml:id").map(|id| (id.into_owned(), CharDecl::default()));
+            }
+            b"mapping" if self.cur.is_some() => {
+                let t = attr_val(e, b"type")
+                    .unwrap_or_default()
+                    .to_ascii_lowercase();
+                self.field = Some(format!("mapping:{}", t));
+            }
+            b"charProp" => self.prop_name.clear(),
+            b"localName" | b"value" if self.cur.is_some() => {
+                self.field = Some(String::from_utf8_lossy(name).into_owned());
+            }
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, t: &str) {
+        let (Some((_, d)), Some(f)) = (self.cur.as_mut(), self.field.as_deref()) else {
+            return;
+        };
+        let t = t.trim();
+        if t.is_empty() {
+            return;
+        }
+        match f {
+            "mapping:unicode" => d.unicode = Some(mapping_value(t)),
+            "mapping:normal" | "mapping:normal_unicode" | "mapping:normalized" => {
+                d.normal.get_or_insert_with(|| mapping_value(t));
+            }
+            "localName" => self.prop_name = t.to_ascii_lowercase(),
+            "value" => match self.prop_name.as_str() {
+                "composition" => d.composition = Some(t.to_string()),
+                "normalized form" | "normal" | "normalized" => {
+                    d.normal.get_or_insert_with(|| t.to_string());
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &[u8]) {
+        match name {
+            b"char" | b"glyph" => {
+                if let Some((id, d)) = self.cur.take() {
+                    self.chars.insert(id, d);
+                }
+            }
+            b"mapping" | b"localName" | b"value" => self.field = None,
+            _ => {}
+        }
+    }
+
+    fn resolve(&self, r: &str, mode: GaijiMode) -> Option<String> {
+        let id = r.trim_start_matches('#');
+        let d = self.chars.get(id);
+        let order = match (mode, d) {
+            (GaijiMode::Drop, _) => return Some(String::new()),
+            (GaijiMode::Ref, _) => return Some(format!("[{}]", id)),
+            // An undeclared reference keeps the element's own content, if it has any.
+            (_, None) => return None,
+            (GaijiMode::Unicode, Some(d)) => [&d.unicode, &d.normal, &d.composition],
+            (GaijiMode::Normal, Some(d)) => [&d.normal, &d.unicode, &d.composition],
+            (GaijiMode::Composition, Some(d)) => [&d.composition, &d.unicode, &d.normal],
+        };
+        Some(
+            order
+                .into_iter()
+                .find_map(|v| v.clone())
+                .unwrap_or_else(|| format!("[{}]", id)),
+        )
+    }
+}
+
+/// What to do when an element closes.
+enum Close {
+    Nothing,
+    Block,
+    Div,
+    Choice,
+    Quote,
+    Mark(&'static str),
+    InlineNote,
+    Footnote,
+}
+
+const BLOCKS: &[&[u8]] = &[
+    b"p",
+    b"ab",
+    b"head",
+    b"lg",
+    b"byline",
+    b"juan",
+    b"list",
+    b"table",
+    b"trailer",
+    b"sp",
+    b"docTitle",
+    b"docAuthor",
+    b"titlePage",
+    b"opener",
+    b"closer",
+    b"dateline",
+    b"signed",
+    b"salute",
+    b"epigraph",
+    b"argument",
+    b"figure",
+    b"colophon",
+];
+
+const LINES: &[&[u8]] = &[b"l", b"item", b"row", b"speaker"];
+
+/// Elements whose content is never text: apparatus readings, page furniture and CBETA's
+/// table-of-contents labels (which repeat the heads).
+const SKIPPED: &[&[u8]] = &[b"rdg", b"fw", b"mulu", b"figDesc", b"back"];
+
+fn text_of(raw: &[u8], decoded: Result<std::borrow::Cow<'_, str>, impl Sized>) -> String {
+    decoded
+        .map(|c| c.into_owned())
+        .unwrap_or_else(|_| String::from_utf8_lossy(raw).into_owned())
+}
+
+/// Convert TEI XML from `input` to text on `output`.
+pub fn convert<R: BufRead, W: Write>(input: R, output: W, opts: &TextOptions) -> io::Result<()> {
+    let md = opts.format == TextFormat::Markdown;
+    let breaks = match opts.line_breaks {
+        LineBreaks::None if md => LineBreaks::Paragraph,
+        b => b,
+    };
+    let mut reader = Reader::from_reader(input);
+    reader.config_mut().check_end_names = false;
+    let mut sink = Sink::new(output);
+    let mut buf = Vec::new();
+    let mut gaiji = Gaiji::default();
+    let mut stack: Vec<Close> = Vec::new();
+    let mut header_depth = 0usize;
+    let mut skip_depth = 0usize;
+    let mut choice_depth = 0usize;
+    let mut div_depth = 0usize;
+    let mut quote_depth = 0usize;
+    // Markdown footnotes: depth inside the note being collected, its text, and the finished ones.
+    let mut note_depth = 0usize;
+    let mut note_buf = String::new();
+    let mut footnotes: Vec<String> = Vec::new();
+
+    macro_rules! block {
+        () => {
+            if breaks == LineBreaks::None {
+                sink.separator();
+            } else {
+                sink.newline(2);
+            }
+        };
+    }
+
+    loop {
+        let ev = reader.read_event_into(&mut buf).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("XML error at byte {}: {}", reader.error_position(), e),
+            )
+        })?;
+        let (start, empty) = match &ev {
+            Event::Start(e) => (Some(e.clone().into_owned()), false),
+            Event::Empty(e) => (Some(e.clone().into_owned()), true),
+            _ => (None, false),
+        };
+        if let Some(e) = start {
+            let name_owned = e.name().as_ref().to_owned();
+            let name = local_name(&name_owned);
+            if header_depth > 0 {
+                if !empty {
+                    header_depth += 1;
+                    gaiji.start(name, &e);
+                }
+                buf.clear();
+                continue;
+            }
+            if skip_depth > 0 {
+                skip_depth += usize::from(!empty);
+                buf.clear();
+                continue;
+            }
+            if note_depth > 0 {
+                // Inside a Markdown footnote only text and gaiji matter.
+                if name == b"g" {
+                    if let Some(v) =
+                        attr_val(&e, b"ref").and_then(|r| gaiji.resolve(&r, opts.gaiji))
+                    {
+                        note_buf.push_str(&v);
+                    }
+                } else if matches!(name, b"lb" | b"pb" | b"l" | b"p") {
+                    note_buf.push(' ');
+                }
+                note_depth += usize::from(!empty);
+                buf.clear();
+                continue;
+            }
+            match name {
+                b"lb" | b"cb" => {
+                    if breaks == LineBreaks::Lb && name == b"lb" {
+                        sink.newline(1);
+                    } else if attr_val(&e, b"break").as_deref() != Some("no") {
+                        sink.space();
+                    }
+                }
+                b"pb" => {
+                    if breaks == LineBreaks::Lb {
+                        sink.newline(2);
+                    } else if attr_val(&e, b"break").as_deref() != Some("no") {
+                        sink.space();
+                    }
+                }
+                b"space" => sink.space(),
+                _ => {}
+            }
+            if empty {
+                if name == b"g" {
+                    if let Some(v) =
+                        attr_val(&e, b"ref").and_then(|r| gaiji.resolve(&r, opts.gaiji))
+                    {
+                        sink.text(&v)?;
+                    }
+                }
+                buf.clear();
+                continue;
+            }
+            let close = if name == b"teiHeader" {
+                header_depth = 1;
+                buf.clear();
+                continue;
+            } else if SKIPPED.contains(&name)
+                || (name == b"note" && !opts.include_notes)
+                || (choice_depth > 0 && matches!(name, b"sic" | b"orig" | b"abbr"))
+            {
+                skip_depth = 1;
+                buf.clear();
+                continue;
+            } else if name == b"g" {
+                match attr_val(&e, b"ref").and_then(|r| gaiji.resolve(&r, opts.gaiji)) {
+                    Some(v) => {
+                        sink.text(&v)?;
+                        skip_depth = 1;
+                        buf.clear();
+                        continue;
+                    }
+                    None => Close::Nothing,
+                }
+            } else if name == b"note" {
+                if md {
+                    note_depth = 1;
+                    note_buf.clear();
+                    Close::Footnote
+                } else {
+                    sink.space();
+                    sink.word("[注]")?;
+                    sink.space();
+                    Close::InlineNote
+                }
+            } else if name == b"choice" {
+                choice_depth += 1;
+                Close::Choice
+            } else if name == b"div" {
+                div_depth += 1;
+                block!();
+                Close::Div
+            } else if BLOCKS.contains(&name) {
+                block!();
+                if md && name == b"head" {
+                    sink.open(&format!("{} ", "#".repeat(div_depth.clamp(1, 6))))?;
+                }
+                if md && name == b"lg" {
+                    quote_depth += 1;
+                    sink.prefix = "> ";
+                    Close::Quote
+                } else {
+                    Close::Block
+                }
+            } else if LINES.contains(&name) {
+                if breaks == LineBreaks::None {
+                    sink.separator();
+                } else {
+                    sink.newline(1);
+                }
+                if md && name == b"item" {
+                    sink.open("- ")?;
+                }
+                Close::Nothing
+            } else if md && name == b"hi" {
+                let rend = attr_val(&e, b"rend")
+                    .unwrap_or_default()
+                    .to_ascii_lowercase();
+                let m = if rend.contains("bold") {
+                    "**"
+                } else if rend.contains("italic") {
+                    "*"
+                } else {
+                    ""
+                };
+                if !m.is_empty() {
+                    sink.open(m)?;
+                }
+                Close::Mark(m)
+            } else {
+                Close::Nothing
+            };
+            stack.push(close);
+            buf.clear();
+            continue;
+        }
+        match ev {
+            Event::End(e) => {
+                let name_owned = e.name().as_ref().to_owned();
+                let name = local_name(&name_owned);
+                if header_depth > 0 {
+                    header_depth -= 1;
+                    gaiji.end(name);
+                } else if skip_depth > 0 {
+                    skip_depth -= 1;
+                } else if note_depth > 1 {
+                    note_depth -= 1;
+                } else {
+                    match stack.pop() {
+                        Some(Close::Block) => block!(),
+                        Some(Close::Div) => {
+                            div_depth = div_depth.saturating_sub(1);
+                            block!();
+                        }
+                        Some(Close::Choice) => choice_depth = choice_depth.saturating_sub(1),
+                        Some(Close::Quote) => {
+                            quote_depth = quote_depth.saturating_sub(1);
+                            if quote_depth == 0 {
+                                sink.prefix = "";
+                            }
+                            block!();
+                        }
+                        Some(Close::Mark(m)) if !m.is_empty() => sink.close(m)?,
+                        Some(Close::InlineNote) => sink.separator(),
+                        Some(Close::Footnote) => {
+                            note_depth = 0;
+                            let t = note_buf.split_whitespace().collect::<Vec<_>>().join(" ");
+                            if !t.is_empty() {
+                                footnotes.push(t);
+                                sink.close(&format!("[^{}]", footnotes.len()))?;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Event::Text(ref t) => {
+                let s = text_of(t, t.decode());
+                if header_depth > 0 {
+                    gaiji.text(&s);
+                } else if skip_depth > 0 {
+                } else if note_depth > 0 {
+                    note_buf.push_str(&s);
+                } else {
+                    sink.text(&s)?;
+                }
+            }
+            Event::CData(ref t) if header_depth == 0 && skip_depth == 0 => {
+                let s = String::from_utf8_lossy(t).into_owned();
+                if note_depth > 0 {
+                    note_buf.push_str(&s);
+                } else {
+                    sink.text(&s)?;
+                }
+            }
+            Event::GeneralRef(ref r) if skip_depth == 0 => {
+                let s = match r.resolve_char_ref() {
+                    Ok(Some(c)) => c.to_string(),
+                    _ => {
+                        let n = text_of(r, r.decode());
+                        resolve_predefined_entity(&n)
+                            .unwrap_or_default()
+                            .to_string()
+                    }
+                };
+                if header_depth > 0 {
+                    gaiji.text(&s);
+                } else if note_depth > 0 {
+                    note_buf.push_str(&s);
+                } else {
+                    sink.text(&s)?;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    if sink.started {
+        sink.out.write_all(b"\n")?;
+    }
+    if !footnotes.is_empty() {
+        sink.out.write_all(b"\n")?;
+        for (i, f) in footnotes.iter().enumerate() {
+            writeln!(sink.out, "[^{}]: {}", i + 1, f)?;
+        }
+    }
+    sink.out.flush()
+}
+
+/// [`convert`] a string.
+pub fn convert_str(xml: &str, opts: &TextOptions) -> String {
+    let mut out = Vec::new();
+    // Writing to a Vec cannot fail; a parse error keeps the text converted so far.
+    let _ = convert(xml.as_bytes(), &mut out, opts);
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEI: &str = r##"<TEI><teiHeader><fileDesc><title>HEADER</title></fileDesc>
+<encodingDesc><charDecl>
+<char xml:id="CB00178"><charProp><localName>composition</localName><value>[王*番]</value></charProp>
+<mapping type="unicode">U+24D36</mapping></char>
+<char xml:id="CB00416"><charProp><localName>composition</localName><value>[仁-二+巿]</value></charProp></char>
+</charDecl></encodingDesc></teiHeader>
+<text><body><div><head>序品</head><cb:mulu>序品</cb:mulu>
+<p><lb n="0001a01"/>如是<g ref="#CB00178"/>聞<lb n="0001a02"/>一時<note>宋本無</note>佛
+<app><lem>住</lem><rdg>在</rdg></app></p>
+<lg><l>諸法從緣生</l><l>亦從因緣滅</l></lg>
+<p>Tom &amp; <hi rend="italic">Jerry</hi> <g ref="#CB00416"/></p></div></body></text></TEI>"##;
+
+    #[test]
+    fn streams_text_and_markdown_with_gaiji_and_notes() {
+        let one = convert_str(TEI, &TextOptions::default());
+        assert_eq!(
+            one,
+            "序品 如是\u{24D36}聞一時佛住 諸法從緣生 亦從因緣滅 Tom & Jerry [仁-二+巿]\n"
+        );
+
+        // Typed spaces stay; source line breaks vanish between CJK characters.
+        assert_eq!(
+            convert_str("<p>姚秦 鳩摩羅什\n譯</p>", &TextOptions::default()),
+            "姚秦 鳩摩羅什譯\n"
+        );
+
+        let lines = convert_str(
+            TEI,
+            &TextOptions {
+                include_notes: true,
+                line_breaks: LineBreaks::Lb,
+                gaiji: GaijiMode::Ref,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            lines,
+            "序品\n\n如是[CB00178]聞\n一時 [注] 宋本無 佛住\n\n諸法從緣生\n亦從因緣滅\n\nTom & Jerry [CB00416]\n"
+        );
+
+        let md = convert_str(
+            TEI,
+            &TextOptions {
+                include_notes: true,
+                gaiji: GaijiMode::Composition,
+                format: TextFormat::Markdown,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            md,
+            "# 序品\n\n如是[王*番]聞一時[^1]佛住\n\n> 諸法從緣生\n> 亦從因緣滅\n\nTom & *Jerry* [仁-二+巿]\n\n[^1]: 宋本無\n"
+        );
+    }
+}
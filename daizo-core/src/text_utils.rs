@@ -1,3 +1,4 @@
+use crate::variants::variant_table;
 use crate::IndexEntry;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
@@ -7,36 +8,13 @@ use unicode_normalization::UnicodeNormalization;
 /// Normalize string for general matching: NFKD + lower + CJK harmonization + alnum only
 pub fn normalized(s: &str) -> String {
     // Hot path: avoid repeated `String::replace` passes (O(n * variants)).
-    // We normalize by: NFKD -> lowercase -> map common CJK variants -> keep alnum only.
+    // We normalize by: NFKD -> lowercase -> map CJK variants -> keep alnum only.
+    let variants = variant_table();
     let mut out = String::with_capacity(s.len());
     for ch in s.nfkd() {
         for lc in ch.to_lowercase() {
-            // Map common simplified/variant forms to traditional used frequently in corpora.
-            let mapped = match lc {
-                '経' | '经' => '經',
-                '観' | '观' => '觀',
-                '仏' => '佛',
-                '圣' => '聖',
-                '会' => '會',
-                '后' => '後',
-                '国' => '國',
-                '灵' => '靈',
-                '广' => '廣',
-                '龙' => '龍',
-                '台' => '臺',
-                '体' => '體',
-                '訳' | '译' => '譯',
-                '蔵' => '藏',
-                '禅' => '禪',
-                '浄' | '净' => '淨',
-                '証' | '证' => '證',
-                '覚' | '觉' => '覺',
-                // Common Buddhist variants
-                '弥' => '彌',
-                '倶' => '俱',
-                '舎' => '舍',
-                _ => lc,
-            };
+            // Map simplified/shinjitai/variant forms to the canonical (traditional) form.
+            let mapped = variants.canonical(lc);
             if mapped.is_alphanumeric() {
                 out.push(mapped);
             }
@@ -46,33 +24,9 @@ pub fn normalized(s: &str) -> String {
 }
 
 fn cjk_variant_group(ch: char) -> Option<&'static str> {
-    // Minimal CJK variant groups useful for Buddhist corpora.
-    // Use in regex character classes to match both forms.
-    match ch {
-        '経' | '經' | '经' => Some("經経经"),
-        '観' | '觀' | '观' => Some("觀観观"),
-        '仏' | '佛' => Some("佛仏"),
-        '訳' | '譯' | '译' => Some("譯訳译"),
-        '蔵' | '藏' => Some("藏蔵"),
-        '禅' | '禪' => Some("禪禅"),
-        '浄' | '淨' | '净' => Some("淨浄净"),
-        '証' | '證' | '证' => Some("證証证"),
-        '覚' | '覺' | '觉' => Some("覺覚觉"),
-        '圣' | '聖' => Some("聖圣"),
-        '会' | '會' => Some("會会"),
-        '后' | '後' => Some("後后"),
-        '国' | '國' => Some("國国"),
-        '灵' | '靈' => Some("靈灵"),
-        '广' | '廣' => Some("廣广"),
-        '龙' | '龍' => Some("龍龙"),
-        '台' | '臺' => Some("臺台"),
-        '体' | '體' => Some("體体"),
-        // Common Buddhist variants
-        '弥' | '彌' => Some("彌弥"),
-        '倶' | '俱' => Some("俱倶"),
-        '舎' | '舍' => Some("舍舎"),
-        _ => None,
-    }
+    // Variant groups from `daizo_core::variants` (built-in table plus DAIZO_VARIANTS),
+    // used in regex character classes to match all forms.
+    variant_table().group(ch)
 }
 
 /// Collapse consecutive whitespace into `\\s*` and expand known CJK variants into
@@ -106,6 +60,25 @@ pub fn ws_cjk_variant_fuzzy_regex_literal(s: &str) -> String {
     out
 }
 
+/// `s` as a regex matching it literally, any run of whitespace matching any other (or none).
+pub fn to_whitespace_fuzzy_literal(s: &str) -> String {
+    // 連続した空白（改行含む）を \\s* に畳み込み、それ以外はリテラルとしてエスケープ
+    let mut out = String::new();
+    let mut in_ws = false;
+    for ch in s.chars() {
+        if ch.is_whitespace() {
+            if !in_ws {
+                out.push_str("\\s*");
+                in_ws = true;
+            }
+        } else {
+            in_ws = false;
+            out.push_str(&regex::escape(&ch.to_string()));
+        }
+    }
+    out
+}
+
 /// Normalize while preserving token boundaries (non-alnum -> space, then squash)
 pub fn normalized_with_spaces(s: &str) -> String {
     // Hot path: avoid intermediate Vec allocations from split/join.
@@ -202,6 +175,259 @@ pub fn normalized_sanskrit(s: &str) -> String {
     out
 }
 
+/// Romanization/script schemes for Sanskrit queries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SanskritScheme {
+    Iast,
+    #[serde(rename = "hk")]
+    HarvardKyoto,
+    Slp1,
+    Devanagari,
+}
+
+impl SanskritScheme {
+    pub const ALL: [SanskritScheme; 4] = [
+        SanskritScheme::Iast,
+        SanskritScheme::HarvardKyoto,
+        SanskritScheme::Slp1,
+        SanskritScheme::Devanagari,
+    ];
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PhonKind {
+    Vowel,
+    Consonant,
+    Mark,
+}
+
+/// (IAST, Harvard-Kyoto, SLP1, Devanagari letter, Devanagari vowel sign, kind)
+type Phoneme = (
+    &'static str,
+    &'static str,
+    &'static str,
+    &'static str,
+    &'static str,
+    PhonKind,
+);
+
+static PHONEMES: &[Phoneme] = &[
+    ("a", "a", "a", "अ", "", PhonKind::Vowel),
+    ("ā", "A", "A", "आ", "ा", PhonKind::Vowel),
+    ("i", "i", "i", "इ", "ि", PhonKind::Vowel),
+    ("ī", "I", "I", "ई", "ी", PhonKind::Vowel),
+    ("u", "u", "u", "उ", "ु", PhonKind::Vowel),
+    ("ū", "U", "U", "ऊ", "ू", PhonKind::Vowel),
+    ("ṛ", "R", "f", "ऋ", "ृ", PhonKind::Vowel),
+    ("ṝ", "RR", "F", "ॠ", "ॄ", PhonKind::Vowel),
+    ("ḷ", "lR", "x", "ऌ", "ॢ", PhonKind::Vowel),
+    ("ḹ", "lRR", "X", "ॡ", "ॣ", PhonKind::Vowel),
+    ("e", "e", "e", "ए", "े", PhonKind::Vowel),
+    ("ai", "ai", "E", "ऐ", "ै", PhonKind::Vowel),
+    ("o", "o", "o", "ओ", "ो", PhonKind::Vowel),
+    ("au", "au", "O", "औ", "ौ", PhonKind::Vowel),
+    ("ṃ", "M", "M", "ं", "", PhonKind::Mark),
+    ("ḥ", "H", "H", "ः", "", PhonKind::Mark),
+    ("'", "'", "'", "ऽ", "", PhonKind::Mark),
+    ("k", "k", "k", "क", "", PhonKind::Consonant),
+    ("kh", "kh", "K", "ख", "", PhonKind::Consonant),
+    ("g", "g", "g", "ग", "", PhonKind::Consonant),
+    ("gh", "gh", "G", "घ", "", PhonKind::Consonant),
+    ("ṅ", "G", "N", "ङ", "", PhonKind::Consonant),
+    ("c", "c", "c", "च", "", PhonKind::Consonant),
+    ("ch", "ch", "C", "छ", "", PhonKind::Consonant),
+    ("j", "j", "j", "ज", "", PhonKind::Consonant),
+    ("jh", "jh", "J", "झ", "", PhonKind::Consonant),
+    ("ñ", "J", "Y", "ञ", "", PhonKind::Consonant),
+    ("ṭ", "T", "w", "ट", "", PhonKind::Consonant),
+    ("ṭh", "Th", "W", "ठ", "", PhonKind::Consonant),
+    ("ḍ", "D", "q", "ड", "", PhonKind::Consonant),
+    ("ḍh", "Dh", "Q", "ढ", "", PhonKind::Consonant),
+    ("ṇ", "N", "R", "ण", "", PhonKind::Consonant),
+    ("t", "t", "t", "त", "", PhonKind::Consonant),
+    ("th", "th", "T", "थ", "", PhonKind::Consonant),
+    ("d", "d", "d", "द", "", PhonKind::Consonant),
+    ("dh", "dh", "D", "ध", "", PhonKind::Consonant),
+    ("n", "n", "n", "न", "", PhonKind::Consonant),
+    ("p", "p", "p", "प", "", PhonKind::Consonant),
+    ("ph", "ph", "P", "फ", "", PhonKind::Consonant),
+    ("b", "b", "b", "ब", "", PhonKind::Consonant),
+    ("bh", "bh", "B", "भ", "", PhonKind::Consonant),
+    ("m", "m", "m", "म", "", PhonKind::Consonant),
+    ("y", "y", "y", "य", "", PhonKind::Consonant),
+    ("r", "r", "r", "र", "", PhonKind::Consonant),
+    ("l", "l", "l", "ल", "", PhonKind::Consonant),
+    ("v", "v", "v", "व", "", PhonKind::Consonant),
+    ("ś", "z", "S", "श", "", PhonKind::Consonant),
+    ("ṣ", "S", "z", "ष", "", PhonKind::Consonant),
+    ("s", "s", "s", "स", "", PhonKind::Consonant),
+    ("h", "h", "h", "ह", "", PhonKind::Consonant),
+];
+
+const VIRAMA: char = '्';
+
+#[derive(Clone, Copy)]
+enum SktToken {
+    Phon(usize),
+    Other(char),
+}
+
+fn phoneme_str(p: &Phoneme, scheme: SanskritScheme) -> &'static str {
+    match scheme {
+        SanskritScheme::Iast => p.0,
+        SanskritScheme::HarvardKyoto => p.1,
+        SanskritScheme::Slp1 => p.2,
+        SanskritScheme::Devanagari => p.3,
+    }
+}
+
+fn tokenize_roman(s: &str, scheme: SanskritScheme) -> Vec<SktToken> {
+    // IAST is case-insensitive (and ṁ is a common spelling of ṃ); HK and SLP1 are not.
+    let s: String = match scheme {
+        SanskritScheme::Iast => s.nfc().flat_map(char::to_lowercase).collect::<String>(),
+        _ => s.to_string(),
+    }
+    .replace('ṁ', "ṃ");
+    let mut out = Vec::new();
+    let mut rest = s.as_str();
+    while let Some(c) = rest.chars().next() {
+        let best = PHONEMES
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (i, phoneme_str(p, scheme)))
+            .filter(|(_, k)| !k.is_empty() && rest.starts_with(k))
+            .max_by_key(|(_, k)| k.len());
+        match best {
+            Some((i, k)) => {
+                out.push(SktToken::Phon(i));
+                rest = &rest[k.len()..];
+            }
+            None => {
+                out.push(SktToken::Other(c));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    out
+}
+
+fn tokenize_devanagari(s: &str) -> Vec<SktToken> {
+    let find = |c: char, sign: bool| {
+        PHONEMES.iter().position(|p| {
+            let k = if sign { p.4 } else { p.3 };
+            !k.is_empty() && k.chars().eq(std::iter::once(c))
+        })
+    };
+    let mut out = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let Some(i) = find(c, false) else {
+            out.push(SktToken::Other(c));
+            continue;
+        };
+        out.push(SktToken::Phon(i));
+        if PHONEMES[i].5 != PhonKind::Consonant {
+            continue;
+        }
+        match chars.peek().copied() {
+            Some(VIRAMA) => {
+                chars.next();
+            }
+            Some(n) if find(n, true).is_some() => {
+                chars.next();
+                out.push(SktToken::Phon(find(n, true).unwrap()));
+            }
+            // inherent vowel
+            _ => out.push(SktToken::Phon(0)),
+        }
+    }
+    out
+}
+
+fn render_tokens(tokens: &[SktToken], scheme: SanskritScheme) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        match tokens[i] {
+            SktToken::Other(c) => out.push(c),
+            SktToken::Phon(p) => {
+                let ph = &PHONEMES[p];
+                out.push_str(phoneme_str(ph, scheme));
+                if scheme == SanskritScheme::Devanagari && ph.5 == PhonKind::Consonant {
+                    match tokens.get(i + 1) {
+                        Some(SktToken::Phon(v)) if PHONEMES[*v].5 == PhonKind::Vowel => {
+                            out.push_str(PHONEMES[*v].4);
+                            i += 1;
+                        }
+                        _ => out.push(VIRAMA),
+                    }
+                }
+            }
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Guess the scheme of a Sanskrit string. Plain lowercase ASCII is reported as IAST
+/// (it reads the same in IAST and Harvard-Kyoto); a capital at the start of a word is
+/// taken as ordinary capitalization.
+pub fn detect_sanskrit_scheme(s: &str) -> SanskritScheme {
+    if s.chars().any(|c| ('\u{0900}'..='\u{097F}').contains(&c)) {
+        return SanskritScheme::Devanagari;
+    }
+    if s.nfc().any(|c| !c.is_ascii() && c.is_alphabetic()) {
+        return SanskritScheme::Iast;
+    }
+    let mut hk = false;
+    for w in s.split(|c: char| !c.is_ascii_alphabetic()) {
+        for (i, c) in w.char_indices() {
+            if i == 0 && c.is_ascii_uppercase() && w[1..].chars().all(|d| d.is_ascii_lowercase()) {
+                continue;
+            }
+            if "fFxXwWqQYKCPBEOL".contains(c) {
+                return SanskritScheme::Slp1;
+            }
+            if c.is_ascii_uppercase() || c == 'z' {
+                hk = true;
+            }
+        }
+    }
+    if hk {
+        SanskritScheme::HarvardKyoto
+    } else {
+        SanskritScheme::Iast
+    }
+}
+
+/// Convert a Sanskrit string between IAST, Harvard-Kyoto, SLP1 and Devanagari.
+/// Characters outside the scheme (spaces, punctuation, digits) are passed through.
+pub fn transliterate_sanskrit(s: &str, from: SanskritScheme, to: SanskritScheme) -> String {
+    if from == to {
+        return s.to_string();
+    }
+    let tokens = match from {
+        SanskritScheme::Devanagari => tokenize_devanagari(s),
+        _ => tokenize_roman(s, from),
+    };
+    render_tokens(&tokens, to)
+}
+
+/// The query followed by its spellings in the other schemes (deduplicated), with the
+/// source scheme detected by [`detect_sanskrit_scheme`].
+pub fn sanskrit_scheme_variants(q: &str) -> (SanskritScheme, Vec<String>) {
+    let from = detect_sanskrit_scheme(q);
+    let mut out = vec![q.to_string()];
+    for to in SanskritScheme::ALL {
+        let v = transliterate_sanskrit(q, from, to);
+        if !v.trim().is_empty() && !out.contains(&v) {
+            out.push(v);
+        }
+    }
+    (from, out)
+}
+
 /// Compute match score with Sanskrit diacritic folding
 pub fn compute_match_score_sanskrit(entry: &IndexEntry, q: &str) -> f32 {
     let nq = normalized(q);
@@ -247,13 +473,12 @@ pub fn compute_match_score_sanskrit(entry: &IndexEntry, q: &str) -> f32 {
     let nalias = normalized_with_spaces(alias).replace(' ', "");
     let nalias_fold = normalized_sanskrit(alias);
     let nq_nospace = normalized_with_spaces(q).replace(' ', "");
-    if !nalias.is_empty() {
-        if nalias.split_whitespace().any(|a| a == nq_nospace)
+    if !nalias.is_empty()
+        && (nalias.split_whitespace().any(|a| a == nq_nospace)
             || nalias.contains(&nq_nospace)
-            || (!nalias_fold.is_empty() && nalias_fold.contains(&normalized_sanskrit(q)))
-        {
-            score = score.max(0.95);
-        }
+            || (!nalias_fold.is_empty() && nalias_fold.contains(&normalized_sanskrit(q))))
+    {
+        score = score.max(0.95);
     }
     if q.chars().any(|c| c.is_ascii_digit()) {
         let hws = normalized_with_spaces(&hay_all);
@@ -465,13 +690,12 @@ pub fn compute_match_score(entry: &IndexEntry, q: &str, use_pali: bool) -> f32 {
         String::new()
     };
     let nq_nospace = normalized_with_spaces(q).replace(' ', "");
-    if !nalias.is_empty() {
-        if nalias.split_whitespace().any(|a| a == nq_nospace)
+    if !nalias.is_empty()
+        && (nalias.split_whitespace().any(|a| a == nq_nospace)
             || nalias.contains(&nq_nospace)
-            || (use_pali && nalias_pali.contains(&normalized_pali(q)))
-        {
-            score = score.max(0.95);
-        }
+            || (use_pali && nalias_pali.contains(&normalized_pali(q))))
+    {
+        score = score.max(0.95);
     }
 
     // numeric pattern boost (e.g., 12.2)
@@ -570,17 +794,16 @@ pub fn compute_match_score_precomputed(entry: &IndexEntry, q: &PrecomputedQuery)
     } else {
         String::new()
     };
-    if !nalias.is_empty() {
-        if nalias.split_whitespace().any(|a| a == q.nq_nospace)
+    if !nalias.is_empty()
+        && (nalias.split_whitespace().any(|a| a == q.nq_nospace)
             || nalias.contains(&q.nq_nospace)
             || (q.use_pali
                 && q.nq_pali
                     .as_deref()
                     .map(|p| !p.is_empty() && nalias_pali.contains(p))
-                    .unwrap_or(false))
-        {
-            score = score.max(0.95);
-        }
+                    .unwrap_or(false)))
+    {
+        score = score.max(0.95);
     }
 
     // numeric pattern boost (e.g., 12.2)
@@ -618,7 +841,7 @@ pub fn compute_match_score_precomputed_with_hay(
         let mut uniq: Vec<&str> = Vec::new();
         let mut inter = 0usize;
         for tok in hay_ws.split_whitespace() {
-            if uniq.iter().any(|t| *t == tok) {
+            if uniq.contains(&tok) {
                 continue;
             }
             if q.q_tokens.contains(tok) {
@@ -654,10 +877,10 @@ pub fn compute_match_score_precomputed_with_hay(
         .and_then(|m| m.get("alias").map(|s| s.as_str()))
         .unwrap_or("");
     let nalias = normalized_with_spaces(alias).replace(' ', "");
-    if !nalias.is_empty() {
-        if nalias.split_whitespace().any(|a| a == q.nq_nospace) || nalias.contains(&q.nq_nospace) {
-            score = score.max(0.95);
-        }
+    if !nalias.is_empty()
+        && (nalias.split_whitespace().any(|a| a == q.nq_nospace) || nalias.contains(&q.nq_nospace))
+    {
+        score = score.max(0.95);
     }
 
     if q.has_digit && !q.nq_ws.is_empty() && hay_ws.contains(&q.nq_ws) {
@@ -798,4 +1021,36 @@ mod tests {
         let s = compute_match_score(&e, "DN1", true);
         assert!(s >= 0.95, "expected alias boost >= 0.95, got {}", s);
     }
+
+    #[test]
+    fn sanskrit_schemes_round_trip() {
+        use SanskritScheme::*;
+        let iast = "prajñāpāramitā hṛdaya";
+        assert_eq!(
+            transliterate_sanskrit(iast, Iast, HarvardKyoto),
+            "prajJApAramitA hRdaya"
+        );
+        assert_eq!(
+            transliterate_sanskrit(iast, Iast, Slp1),
+            "prajYApAramitA hfdaya"
+        );
+        assert_eq!(
+            transliterate_sanskrit(iast, Iast, Devanagari),
+            "प्रज्ञापारमिता हृदय"
+        );
+        for to in SanskritScheme::ALL {
+            let v = transliterate_sanskrit(iast, Iast, to);
+            assert_eq!(detect_sanskrit_scheme(&v), to, "{}", v);
+            assert_eq!(transliterate_sanskrit(&v, to, Iast), iast);
+        }
+        assert_eq!(
+            transliterate_sanskrit("zAstra", HarvardKyoto, Iast),
+            "śāstra"
+        );
+        assert_eq!(detect_sanskrit_scheme("Buddha"), Iast);
+        let (from, vs) = sanskrit_scheme_variants("dharmakSetre");
+        assert_eq!(from, HarvardKyoto);
+        assert!(vs.contains(&"dharmakṣetre".to_string()));
+        assert!(vs.contains(&"धर्मक्षेत्रे".to_string()));
+    }
 }
@@ -0,0 +1,185 @@
+//! Per-call time spent by phase.
+//!
+//! A tool call runs under [`record`], which collects how long the calling thread spent loading
+//! indexes, scanning files, extracting text from XML and waiting on the network. The code
+//! doing that work marks it with a [`Span`] from [`Phase::start`]; outside a recording the
+//! spans cost nothing. Phases nest exclusively: a span started inside another pauses the
+//! outer one, so an extraction inside an index build counts once, as extraction.
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+/// Where a call spends its time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    IndexLoad,
+    Scan,
+    Extract,
+    Network,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 4] = [
+        Phase::IndexLoad,
+        Phase::Scan,
+        Phase::Extract,
+        Phase::Network,
+    ];
+
+    /// Name in `_meta.timing` (`indexLoad`, `scan`, `extract`, `network`).
+    pub fn key(self) -> &'static str {
+        match self {
+            Phase::IndexLoad => "indexLoad",
+            Phase::Scan => "scan",
+            Phase::Extract => "extract",
+            Phase::Network => "network",
+        }
+    }
+
+    /// Count the time until the returned span is dropped towards this phase.
+    pub fn start(self) -> Span {
+        let active = STATE.with(|s| {
+            let mut s = s.borrow_mut();
+            let Some(st) = s.as_mut() else {
+                return false;
+            };
+            let now = Instant::now();
+            st.pause(now);
+            st.stack.push((self, now));
+            true
+        });
+        Span {
+            active,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+/// Time spent by phase during a [`record`]ed call.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timings {
+    pub total: Duration,
+    phases: [Duration; 4],
+}
+
+impl Timings {
+    pub fn get(&self, phase: Phase) -> Duration {
+        self.phases[phase as usize]
+    }
+
+    /// Time not spent in any phase (argument handling, formatting the response).
+    pub fn other(&self) -> Duration {
+        self.total
+            .saturating_sub(self.phases.iter().sum::<Duration>())
+    }
+}
+
+struct State {
+    phases: [Duration; 4],
+    stack: Vec<(Phase, Instant)>,
+}
+
+impl State {
+    /// Book the innermost open span up to `now`.
+    fn pause(&mut self, now: Instant) {
+        if let Some((p, since)) = self.stack.last_mut() {
+            self.phases[*p as usize] += now - *since;
+            *since = now;
+        }
+    }
+}
+
+thread_local! {
+    static STATE: RefCell<Option<State>> = const { RefCell::new(None) };
+}
+
+/// An open phase; its time is booked when it is dropped. Tied to the thread it started on.
+pub struct Span {
+    active: bool,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if !self.active {
+            return;
+        }
+        STATE.with(|s| {
+            if let Some(st) = s.borrow_mut().as_mut() {
+                let now = Instant::now();
+                st.pause(now);
+                st.stack.pop();
+                // The enclosing span resumes from here.
+                if let Some((_, since)) = st.stack.last_mut() {
+                    *since = now;
+                }
+            }
+        });
+    }
+}
+
+/// Run `f` and return how its time on this thread divided into phases. A recording nested
+/// in another (a tool calling a tool) adds its phases to the outer one as well.
+pub fn record<R>(f: impl FnOnce() -> R) -> (R, Timings) {
+    let prev = STATE.with(|s| {
+        let mut s = s.borrow_mut();
+        if let Some(outer) = s.as_mut() {
+            outer.pause(Instant::now());
+        }
+        s.replace(State {
+            phases: Default::default(),
+            stack: Vec::new(),
+        })
+    });
+    let t0 = Instant::now();
+    let out = f();
+    let total = t0.elapsed();
+    let phases = STATE.with(|s| {
+        let mut s = s.borrow_mut();
+        let phases = s.take().map(|st| st.phases).unwrap_or_default();
+        *s = prev;
+        if let Some(outer) = s.as_mut() {
+            for (o, p) in outer.phases.iter_mut().zip(phases) {
+                *o += p;
+            }
+            // The nested call's time is booked; the outer span resumes from here.
+            if let Some((_, since)) = outer.stack.last_mut() {
+                *since = Instant::now();
+            }
+        }
+        phases
+    });
+    (out, Timings { total, phases })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn busy(ms: u64) {
+        std::thread::sleep(Duration::from_millis(ms));
+    }
+
+    #[test]
+    fn nested_phases_count_once() {
+        // Outside a recording spans are inert.
+        drop(Phase::Scan.start());
+
+        let ((), t) = record(|| {
+            let _scan = Phase::Scan.start();
+            busy(20);
+            {
+                let _x = Phase::Extract.start();
+                busy(30);
+            }
+            busy(20);
+        });
+        let ms = |p| t.get(p).as_millis();
+        assert!(ms(Phase::Extract) >= 30);
+        assert!(ms(Phase::Scan) >= 40 && ms(Phase::Scan) < 40 + ms(Phase::Extract));
+        assert_eq!(t.get(Phase::Network), Duration::ZERO);
+        assert!(t.total >= t.get(Phase::Scan) + t.get(Phase::Extract));
+        assert!(t.other() < t.total);
+    }
+}
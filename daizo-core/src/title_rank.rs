@@ -0,0 +1,227 @@
+//! Ranking title-search hits, with the reason each one ranked where it did.
+//!
+//! The corpus scorers in the title searches are forgiving (bigram overlap, subsequences over
+//! the whole entry) and good at finding candidates, but their scores say little about why an
+//! entry matched. [`rank`] scores a candidate by the best way the query meets it — its id, an
+//! alias, one of its titles (normalized Levenshtein similarity and token overlap) or another
+//! meta field — and reports which one as a [`MatchReason`].
+
+use crate::text_utils::{normalized, normalized_with_spaces};
+use crate::titles::{META_ENGLISH, META_PINYIN, META_ROMAJI, META_ROMANIZED};
+use crate::IndexEntry;
+use serde::Serialize;
+
+/// How a title-search hit matched, reported as `matchReason`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchReason {
+    /// The query is the entry's id or file stem.
+    ExactId,
+    /// The query is, or is part of, one of the entry's aliases (`DN 1`, user aliases).
+    Alias,
+    /// The query resembles one of the entry's titles.
+    FuzzyTitle,
+    /// The query appears in another field: author, translator, nikāya, headings, …
+    MetaField,
+}
+
+/// Meta fields searched for [`MatchReason::MetaField`].
+const META_FIELDS: &[&str] = &[
+    "author",
+    "translator",
+    "editor",
+    "nikaya",
+    "book",
+    "genre",
+    "headsPreview",
+    "muluPreview",
+];
+
+/// A query prepared for [`rank`].
+#[derive(Clone, Debug)]
+pub struct TitleQuery {
+    norm: String,
+    chars: Vec<char>,
+    tokens: Vec<String>,
+}
+
+impl TitleQuery {
+    pub fn new(q: &str) -> Self {
+        let norm = normalized(q);
+        TitleQuery {
+            chars: norm.chars().collect(),
+            tokens: normalized_with_spaces(q)
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+            norm,
+        }
+    }
+}
+
+/// Score in 0..=1 and reason of a hit.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Rank {
+    pub score: f32,
+    pub reason: MatchReason,
+}
+
+/// Edit distance in characters.
+pub fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let sub = prev[j] + usize::from(ca != cb);
+            cur[j + 1] = sub.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+/// 1 − distance / longer length.
+fn similarity(a: &[char], b: &[char]) -> f32 {
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 0.0;
+    }
+    1.0 - levenshtein(a, b) as f32 / longest as f32
+}
+
+fn title_score(q: &TitleQuery, title: &str) -> f32 {
+    let tn = normalized(title);
+    if tn.is_empty() {
+        return 0.0;
+    }
+    if tn == q.norm {
+        return 1.0;
+    }
+    let tchars: Vec<char> = tn.chars().collect();
+    let lev = similarity(&q.chars, &tchars);
+    let ttokens = normalized_with_spaces(title);
+    let ttokens: Vec<&str> = ttokens.split_whitespace().collect();
+    let overlap = if q.tokens.is_empty() {
+        0.0
+    } else {
+        q.tokens
+            .iter()
+            .filter(|t| ttokens.contains(&t.as_str()))
+            .count() as f32
+            / q.tokens.len() as f32
+    };
+    let contained = if tn.contains(&q.norm) {
+        0.8 + 0.15 * q.chars.len() as f32 / tchars.len() as f32
+    } else {
+        0.0
+    };
+    // Token overlap only helps: a CJK title is a single token.
+    contained.max(lev).max(0.5 * lev + 0.5 * overlap)
+}
+
+fn alias_score(q: &TitleQuery, alias: &str) -> f32 {
+    // Aliases are space-separated, and some have a space of their own (`DN 1`).
+    let tokens: Vec<String> = alias.split_whitespace().map(normalized).collect();
+    let exact = tokens.contains(&q.norm)
+        || tokens
+            .windows(2)
+            .any(|w| format!("{}{}", w[0], w[1]) == q.norm);
+    if exact {
+        0.98
+    } else if q.chars.len() >= 3 && tokens.concat().contains(&q.norm) {
+        0.9
+    } else {
+        0.0
+    }
+}
+
+/// How well `q` matches `e`, by the best of its id, aliases, titles and meta fields.
+pub fn rank(e: &IndexEntry, q: &TitleQuery) -> Rank {
+    let mut best = Rank {
+        score: 0.0,
+        reason: MatchReason::FuzzyTitle,
+    };
+    if q.norm.is_empty() {
+        return best;
+    }
+    let mut consider = |score: f32, reason: MatchReason| {
+        if score > best.score {
+            best = Rank { score, reason };
+        }
+    };
+    let stem = e.id.split('.').next().unwrap_or(&e.id);
+    if normalized(&e.id) == q.norm || normalized(stem) == q.norm {
+        consider(1.0, MatchReason::ExactId);
+    }
+    let meta = e.meta.as_ref();
+    if let Some(alias) = meta.and_then(|m| m.get("alias")) {
+        consider(alias_score(q, alias), MatchReason::Alias);
+    }
+    let other_titles = [META_ENGLISH, META_ROMANIZED, META_PINYIN, META_ROMAJI]
+        .into_iter()
+        .filter_map(|k| meta.and_then(|m| m.get(k)).map(|s| s.as_str()));
+    for t in std::iter::once(e.title.as_str())
+        .chain(e.title.split(" · "))
+        .chain(other_titles.flat_map(|t| t.split(" · ")))
+    {
+        consider(title_score(q, t), MatchReason::FuzzyTitle);
+    }
+    if q.chars.len() >= 2 {
+        for k in META_FIELDS {
+            let Some(v) = meta.and_then(|m| m.get(*k)) else {
+                continue;
+            };
+            let vn = normalized(v);
+            if vn.contains(&q.norm) {
+                let share = q.chars.len() as f32 / vn.chars().count() as f32;
+                consider(0.6 + 0.1 * share, MatchReason::MetaField);
+            }
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, title: &str, meta: &[(&str, &str)]) -> IndexEntry {
+        IndexEntry {
+            id: id.to_string(),
+            title: title.to_string(),
+            path: String::new(),
+            meta: Some(
+                meta.iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            ),
+        }
+    }
+
+    #[test]
+    fn reasons_follow_the_best_match() {
+        assert_eq!(levenshtein(&['a', 'b', 'c'], &['a', 'c']), 1);
+
+        let heart = entry("T08n0251", "般若波羅蜜多心經", &[("author", "唐 玄奘譯")]);
+        let dn = entry(
+            "s0101m.mul",
+            "Dīghanikāyo · Sīlakkhandhavaggapāḷi",
+            &[("alias", "DN 1 DN1 dn 1")],
+        );
+        let r = |e, q| rank(e, &TitleQuery::new(q));
+
+        assert_eq!(r(&heart, "t08n0251").reason, MatchReason::ExactId);
+        assert_eq!(r(&dn, "s0101m").reason, MatchReason::ExactId);
+        assert_eq!(r(&dn, "DN 1").reason, MatchReason::Alias);
+        assert_eq!(r(&heart, "玄奘").reason, MatchReason::MetaField);
+
+        let exact = r(&heart, "般若波羅蜜多心經");
+        let typo = r(&heart, "般若波羅密多心經");
+        let partial = r(&heart, "心經");
+        assert_eq!(exact.reason, MatchReason::FuzzyTitle);
+        assert_eq!(exact.score, 1.0);
+        assert!(typo.score > partial.score && partial.score > r(&heart, "玄奘").score);
+        assert_eq!(r(&dn, "silakkhandhavagga").reason, MatchReason::FuzzyTitle);
+    }
+}
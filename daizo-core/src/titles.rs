@@ -0,0 +1,159 @@
+//! Titles in the forms a client may want to show.
+//!
+//! An index entry has one `title`, the one its search is tuned for: the Chinese title of a
+//! CBETA text, the romanized one of a Pāli or Sanskrit text. Index builders that find other
+//! forms in a header keep them in the entry's meta (`titleEn`, `titleLatn`), and [`of_entry`]
+//! gathers them into a [`Titles`], adding an IAST reading of a Devanagari title.
+
+use crate::text_utils::{transliterate_sanskrit, SanskritScheme};
+use crate::{stem_from, GrepResult, IndexEntry};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Meta key of an English title.
+pub const META_ENGLISH: &str = "titleEn";
+/// Meta key of a title in Latin script (pinyin, IAST, …) when the main title is not.
+pub const META_ROMANIZED: &str = "titleLatn";
+/// Meta key of the pinyin reading of a CJK title (see [`crate::cjk_readings`]).
+pub const META_PINYIN: &str = "titlePinyin";
+/// Meta key of the Japanese reading of a CJK title, in romaji.
+pub const META_ROMAJI: &str = "titleRomaji";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Titles {
+    /// The title as the text gives it.
+    pub original: String,
+    /// ISO 15924 code of its script: `Hani`, `Deva`, `Tibt` or `Latn`.
+    pub script: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub romanized: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub english: Option<String>,
+}
+
+/// Script of `s`, by the first letter that is not Latin or common.
+pub fn script_of(s: &str) -> &'static str {
+    for c in s.chars() {
+        match c {
+            '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '\u{20000}'..='\u{2FFFF}' => {
+                return "Hani"
+            }
+            '\u{0900}'..='\u{097F}' => return "Deva",
+            '\u{0F00}'..='\u{0FFF}' => return "Tibt",
+            _ => {}
+        }
+    }
+    "Latn"
+}
+
+/// The title of `e` with the other forms its index recorded.
+pub fn of_entry(e: &IndexEntry) -> Titles {
+    let meta = e.meta.as_ref();
+    let get = |k: &str| {
+        meta.and_then(|m| m.get(k))
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+    let script = script_of(&e.title);
+    let romanized = get(META_ROMANIZED).or_else(|| match script {
+        "Latn" => Some(e.title.clone()),
+        "Hani" => get(META_PINYIN).map(|p| p.split(" · ").next().unwrap_or_default().to_string()),
+        "Deva" => Some(transliterate_sanskrit(
+            &e.title,
+            SanskritScheme::Devanagari,
+            SanskritScheme::Iast,
+        )),
+        _ => None,
+    });
+    Titles {
+        original: e.title.clone(),
+        script: script.to_string(),
+        romanized,
+        english: get(META_ENGLISH).filter(|t| *t != e.title),
+    }
+}
+
+/// Record the other forms found in a header: an English title and a romanized one. A
+/// romanization equal to the title itself is not worth keeping.
+pub fn record(
+    meta: &mut BTreeMap<String, String>,
+    title: &str,
+    english: Option<String>,
+    romanized: Option<String>,
+) {
+    if let Some(en) = english.filter(|t| t != title) {
+        meta.insert(META_ENGLISH.to_string(), en);
+    }
+    if let Some(r) = romanized.filter(|t| t != title) {
+        meta.insert(META_ROMANIZED.to_string(), r);
+    }
+}
+
+/// Set `titles` on search results whose file is in the index.
+pub fn apply_index_titles(results: &mut [GrepResult], idx: &[IndexEntry]) {
+    let by_stem: HashMap<String, &IndexEntry> = idx
+        .iter()
+        .map(|e| (stem_from(Path::new(&e.path)), e))
+        .collect();
+    for r in results.iter_mut() {
+        if let Some(e) = by_stem.get(&r.file_id) {
+            r.titles = Some(of_entry(e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(title: &str, meta: &[(&str, &str)]) -> IndexEntry {
+        IndexEntry {
+            id: "x".into(),
+            title: title.into(),
+            path: "/c/T08n0251.xml".into(),
+            meta: Some(
+                meta.iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            ),
+        }
+    }
+
+    #[test]
+    fn titles_gather_the_recorded_forms() {
+        let mut meta = BTreeMap::new();
+        record(
+            &mut meta,
+            "般若波羅蜜多心經",
+            Some("Heart Sūtra".into()),
+            Some("Bore boluomiduo xin jing".into()),
+        );
+        let m: Vec<(&str, &str)> = meta.iter().map(|(k, v)| (&**k, &**v)).collect();
+        let t = of_entry(&entry("般若波羅蜜多心經", &m));
+        assert_eq!(t.script, "Hani");
+        assert_eq!(t.romanized.as_deref(), Some("Bore boluomiduo xin jing"));
+        assert_eq!(t.english.as_deref(), Some("Heart Sūtra"));
+
+        let pali = of_entry(&entry("Sīlakkhandhavaggapāḷi", &[]));
+        assert_eq!(pali.script, "Latn");
+        assert_eq!(pali.romanized.as_deref(), Some("Sīlakkhandhavaggapāḷi"));
+        assert_eq!(pali.english, None);
+
+        let deva = of_entry(&entry("धर्म", &[]));
+        assert_eq!(deva.script, "Deva");
+        assert_eq!(deva.romanized.as_deref(), Some("dharma"));
+
+        let mut results = vec![GrepResult {
+            file_id: "T08n0251".into(),
+            ..Default::default()
+        }];
+        apply_index_titles(&mut results, &[entry("般若波羅蜜多心經", &m)]);
+        assert_eq!(
+            results[0].titles.as_ref().map(|t| t.script.as_str()),
+            Some("Hani")
+        );
+        let v = serde_json::to_value(&pali).unwrap();
+        assert!(v.get("english").is_none());
+    }
+}
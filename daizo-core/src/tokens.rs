@@ -0,0 +1,63 @@
+//! Cheap token-count estimate for output budgets (`maxTokens`). Not any model's tokenizer:
+//! a per-character cost that is close enough for CJK, romanized Pāli/Sanskrit and Indic
+//! scripts to plan slices.
+
+/// Cost of `c` in quarter tokens.
+fn cost(c: char) -> usize {
+    match c as u32 {
+        // Rare ideographs (extension B and beyond) usually take two tokens.
+        0x20000..=0x3134F => 8,
+        // CJK ideographs, kana, hangul, CJK and full-width punctuation: about one each.
+        0x3000..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF => 4,
+        0xF900..=0xFAFF | 0xFF00..=0xFFEF => 4,
+        // Spaces fold into the following word.
+        0x20 | 0x09 | 0x0D => 0,
+        0x0A => 1,
+        // English-like words: about four characters per token.
+        0x30..=0x39 | 0x41..=0x5A | 0x61..=0x7A => 1,
+        0x21..=0x7E => 4,
+        // Latin with diacritics (IAST, Pāli) and combining marks split words more often.
+        0x80..=0x24F | 0x300..=0x36F | 0x1E00..=0x1EFF => 2,
+        // Other scripts (Devanagari, Tibetan, …): about one per character.
+        _ => 4,
+    }
+}
+
+/// Estimated number of tokens in `text`.
+pub fn estimate(text: &str) -> usize {
+    text.chars().map(cost).sum::<usize>().div_ceil(4)
+}
+
+/// Number of leading chars of `text` whose estimate fits in `tokens`.
+pub fn chars_within(text: &str, tokens: usize) -> usize {
+    let limit = tokens.saturating_mul(4);
+    let mut used = 0usize;
+    let mut n = 0usize;
+    for c in text.chars() {
+        used += cost(c);
+        if used > limit {
+            break;
+        }
+        n += 1;
+    }
+    n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_by_script_and_fits_a_budget() {
+        assert_eq!(estimate(""), 0);
+        assert_eq!(estimate("如是我聞。"), 5);
+        assert_eq!(estimate("evam me sutam"), 3);
+        assert_eq!(estimate("evaṃ me sutaṃ"), 4);
+        assert!(estimate("𠀀") > estimate("一"));
+        let text = "如是我聞一時佛在";
+        assert_eq!(chars_within(text, 3), 3);
+        assert_eq!(chars_within(text, 100), 8);
+        assert_eq!(chars_within(text, 0), 0);
+        assert!(estimate(&text.chars().take(chars_within(text, 5)).collect::<String>()) <= 5);
+    }
+}
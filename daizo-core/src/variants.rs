@@ -0,0 +1,223 @@
+use crate::path_resolver::daizo_home;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Built-in variant groups (traditional / shinjitai / simplified), see `data/variants.txt`.
+const BUILTIN_VARIANTS: &str = include_str!("../data/variants.txt");
+
+/// CJK variant-character table (異体字・繁簡字).
+///
+/// Each character maps to its variant group (for regex character classes) and to a
+/// canonical form (for normalized comparison). Groups are not closed transitively:
+/// a simplified character shared by two traditional ones (发 → 發/髮) matches both,
+/// but 發 does not match 髮.
+#[derive(Clone, Debug, Default)]
+pub struct VariantTable {
+    canon: HashMap<char, char>,
+    groups: HashMap<char, String>,
+}
+
+impl VariantTable {
+    /// Number of characters that have at least one variant.
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Table with the built-in groups only.
+    pub fn builtin() -> Self {
+        let mut t = VariantTable::default();
+        t.merge_str(BUILTIN_VARIANTS);
+        t
+    }
+
+    /// Register a group; the first character is the canonical form.
+    /// An existing canonical mapping is kept, so earlier sources take precedence.
+    pub fn add_group(&mut self, chars: &[char]) {
+        let Some(&head) = chars.first() else {
+            return;
+        };
+        for &c in chars {
+            let g = self.groups.entry(c).or_insert_with(|| c.to_string());
+            for &v in chars {
+                if !g.contains(v) {
+                    g.push(v);
+                }
+            }
+            if c != head {
+                self.canon.entry(c).or_insert(head);
+            }
+        }
+        for &c in chars {
+            if self
+                .groups
+                .get(&c)
+                .map(|g| g.chars().count() < 2)
+                .unwrap_or(false)
+            {
+                self.groups.remove(&c);
+            }
+        }
+    }
+
+    /// All forms of `ch` (including `ch` itself), or None when it has no variants.
+    pub fn group(&self, ch: char) -> Option<&str> {
+        self.groups.get(&ch).map(|s| s.as_str())
+    }
+
+    /// Canonical form of `ch` (itself when unknown).
+    pub fn canonical(&self, ch: char) -> char {
+        self.canon.get(&ch).copied().unwrap_or(ch)
+    }
+
+    /// Map every character to its canonical form.
+    pub fn normalize(&self, s: &str) -> String {
+        s.chars().map(|c| self.canonical(c)).collect()
+    }
+
+    /// Merge groups from text; returns the number of groups read.
+    ///
+    /// Two formats are accepted, line by line:
+    /// - `經 経 经` — one group per line, canonical form first (`#` starts a comment);
+    /// - Unihan_Variants.txt records (tab-separated), e.g. `U+8AAA kSimplifiedVariant U+8BF4`
+    ///   (traditional/simplified, Z- and semantic variants are used).
+    pub fn merge_str(&mut self, s: &str) -> usize {
+        let mut n = 0usize;
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with("U+") {
+                if self.merge_unihan_line(line) {
+                    n += 1;
+                }
+                continue;
+            }
+            let chars: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+            if chars.len() >= 2 {
+                self.add_group(&chars);
+                n += 1;
+            }
+        }
+        n
+    }
+
+    fn merge_unihan_line(&mut self, line: &str) -> bool {
+        fn cp(tok: &str) -> Option<char> {
+            let hex = tok.strip_prefix("U+")?.split('<').next()?;
+            char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+        }
+        let mut fields = line.split('\t');
+        let (Some(src), Some(kind), Some(vals)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return false;
+        };
+        let Some(src) = cp(src) else {
+            return false;
+        };
+        let targets: Vec<char> = vals
+            .split_whitespace()
+            .filter_map(cp)
+            .filter(|&t| t != src)
+            .collect();
+        if targets.is_empty() {
+            return false;
+        }
+        match kind {
+            // src is simplified; a single traditional target becomes its canonical form.
+            "kTraditionalVariant" => {
+                for &t in &targets {
+                    if targets.len() == 1 {
+                        self.add_group(&[t, src]);
+                    } else {
+                        self.add_group(&[src, t]);
+                    }
+                }
+            }
+            "kSimplifiedVariant" => {
+                for &t in &targets {
+                    self.add_group(&[src, t]);
+                }
+            }
+            "kZVariant" | "kSemanticVariant" => {
+                // Group only: no direction is implied, so neither side becomes canonical.
+                for &t in &targets {
+                    for (a, b) in [(src, t), (t, src)] {
+                        let g = self.groups.entry(a).or_insert_with(|| a.to_string());
+                        if !g.contains(b) {
+                            g.push(b);
+                        }
+                    }
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Merge a variant file (either format, see [`VariantTable::merge_str`]).
+    pub fn merge_file(&mut self, path: &Path) -> Result<usize, String> {
+        let s = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(self.merge_str(&s))
+    }
+}
+
+/// User variant file: `DAIZO_VARIANTS` if set, else `~/.daizo/variants.txt` when present.
+pub fn variants_path() -> Option<PathBuf> {
+    if let Ok(p) = std::env::var("DAIZO_VARIANTS") {
+        if !p.trim().is_empty() {
+            return Some(PathBuf::from(p));
+        }
+    }
+    let p = daizo_home().join("variants.txt");
+    p.exists().then_some(p)
+}
+
+static VARIANT_TABLE: OnceLock<VariantTable> = OnceLock::new();
+
+/// Process-wide table: built-in groups plus the user file, loaded on first use.
+/// A missing or unreadable user file falls back to the built-in groups.
+pub fn variant_table() -> &'static VariantTable {
+    VARIANT_TABLE.get_or_init(|| {
+        let mut t = VariantTable::builtin();
+        if let Some(p) = variants_path() {
+            let _ = t.merge_file(&p);
+        }
+        t
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_groups_and_canonical_forms() {
+        let t = VariantTable::builtin();
+        assert!(t.len() > 1000);
+        assert_eq!(t.normalize("观经"), "觀經");
+        assert_eq!(t.normalize("説法"), "說法");
+        let g = t.group('发').unwrap();
+        assert!(g.contains('發') && g.contains('髮'));
+        assert!(!t.group('發').unwrap().contains('髮'));
+        assert_eq!(t.canonical('发'), '發');
+        assert!(t.group('空').is_none());
+    }
+
+    #[test]
+    fn unihan_records() {
+        let mut t = VariantTable::default();
+        let n = t.merge_str(
+            "# comment\nU+8AAA\tkSimplifiedVariant\tU+8BF4\nU+8BF4\tkTraditionalVariant\tU+8AAA\nU+4F5B\tkSemanticVariant\tU+4ECF<kMatthews\n",
+        );
+        assert_eq!(n, 3);
+        assert_eq!(t.canonical('说'), '說');
+        assert!(t.group('仏').unwrap().contains('佛'));
+        assert_eq!(t.canonical('仏'), '仏');
+    }
+}
@@ -0,0 +1,120 @@
+//! Warm-up of a freshly started server.
+//!
+//! Loading the indexes is the part of the first call a server can do ahead of time; the other
+//! part is the texts themselves, which come off disk cold. [`HotFiles`] remembers the files
+//! fetched most recently (in `~/.daizo/cache/hot-files.json`, so the list outlives the process)
+//! and [`touch`] maps one into memory and faults its pages in, leaving it in the page cache for
+//! the fetch that follows.
+
+use crate::fetch_cache::write_atomic;
+use crate::path_resolver::cache_dir;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Default number of recently fetched files a warm-up reads in.
+pub const DEFAULT_HOT_FILES: usize = 64;
+
+/// Where the list is kept by default.
+pub fn default_path() -> PathBuf {
+    cache_dir().join("hot-files.json")
+}
+
+/// Recently fetched files, most recent first.
+pub struct HotFiles {
+    path: PathBuf,
+    max: usize,
+    files: Mutex<Vec<PathBuf>>,
+}
+
+impl HotFiles {
+    /// The list stored at `path` (empty when there is none), capped at `max`.
+    pub fn load(path: PathBuf, max: usize) -> Self {
+        let mut files: Vec<PathBuf> = std::fs::read(&path)
+            .ok()
+            .and_then(|b| serde_json::from_slice(&b).ok())
+            .unwrap_or_default();
+        files.truncate(max);
+        HotFiles {
+            path,
+            max,
+            files: Mutex::new(files),
+        }
+    }
+
+    /// Move `file` to the front. The list is written when `file` is new to it; a reorder
+    /// alone only changes which file drops out next.
+    pub fn note(&self, file: &Path) {
+        if self.max == 0 {
+            return;
+        }
+        let mut g = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        let known = match g.iter().position(|p| p == file) {
+            Some(i) => {
+                let p = g.remove(i);
+                g.insert(0, p);
+                true
+            }
+            None => {
+                g.insert(0, file.to_path_buf());
+                g.truncate(self.max);
+                false
+            }
+        };
+        if !known {
+            if let Ok(b) = serde_json::to_vec(&*g) {
+                let _ = write_atomic(&self.path, &b);
+            }
+        }
+    }
+
+    pub fn list(&self) -> Vec<PathBuf> {
+        self.files.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Map `path` and read one byte per page so the file is in the page cache. Returns its size.
+pub fn touch(path: &Path) -> std::io::Result<u64> {
+    let f = std::fs::File::open(path)?;
+    let len = f.metadata()?.len();
+    if len == 0 {
+        return Ok(0);
+    }
+    // SAFETY: the map is read-only and dropped before returning; a file truncated meanwhile
+    // can at worst fault this warm-up thread, the same exposure as grep's memory maps.
+    let map = unsafe { memmap2::Mmap::map(&f)? };
+    #[cfg(unix)]
+    let _ = map.advise(memmap2::Advice::WillNeed);
+    let mut sum = 0u8;
+    for i in (0..map.len()).step_by(4096) {
+        sum = sum.wrapping_add(map[i]);
+    }
+    std::hint::black_box(sum);
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hot_files_keep_the_most_recent() {
+        let dir = tempfile::tempdir().unwrap();
+        let list = dir.path().join("hot-files.json");
+        let hot = HotFiles::load(list.clone(), 2);
+        assert!(hot.list().is_empty());
+        for f in ["a.xml", "b.xml", "a.xml", "c.xml"] {
+            hot.note(Path::new(f));
+        }
+        assert_eq!(hot.list(), [PathBuf::from("c.xml"), PathBuf::from("a.xml")]);
+        // Reloaded from disk with a smaller cap.
+        let again = HotFiles::load(list, 1);
+        assert_eq!(again.list(), [PathBuf::from("c.xml")]);
+
+        let f = dir.path().join("T08n0251.xml");
+        std::fs::write(&f, "x".repeat(10_000)).unwrap();
+        assert_eq!(touch(&f).unwrap(), 10_000);
+        std::fs::write(&f, "").unwrap();
+        assert_eq!(touch(&f).unwrap(), 0);
+        assert!(touch(&dir.path().join("missing.xml")).is_err());
+    }
+}
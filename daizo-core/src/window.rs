@@ -0,0 +1,195 @@
+//! Fetch windows sized to the text unit around a search match.
+//!
+//! Search suggestions name a line and `contextBefore`/`contextAfter` in XML lines. A fixed
+//! 1/3 window cuts verses and long sentences in half, so the window is widened to the stanza
+//! (`<lg>`, Pāli `gatha1`…`gathalast`) or paragraph that holds the match, and to the sentence
+//! when the paragraph is too long to fetch whole.
+
+use serde::Serialize;
+use std::path::Path;
+
+/// Most lines a suggested window reaches before / after the match.
+pub const MAX_BEFORE: usize = 8;
+pub const MAX_AFTER: usize = 12;
+
+/// Suggested `contextBefore` / `contextAfter` (XML lines) and the unit they cover.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct ContextWindow {
+    pub before: usize,
+    pub after: usize,
+    /// `verse`, `paragraph`, `sentence`, `heading`, or `lines` (fallback).
+    pub unit: &'static str,
+}
+
+impl Default for ContextWindow {
+    fn default() -> Self {
+        ContextWindow {
+            before: 1,
+            after: 3,
+            unit: "lines",
+        }
+    }
+}
+
+fn text_of(line: &str) -> String {
+    let mut out = String::new();
+    let mut in_tag = false;
+    for c in line.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+fn ends_sentence(line: &str) -> bool {
+    text_of(line)
+        .chars()
+        .any(|c| matches!(c, '。' | '！' | '？' | '।' | '॥' | '|' | '.' | '?' | '!'))
+}
+
+fn opens_verse(l: &str) -> bool {
+    l.contains("<lg") || l.contains("rend=\"gatha1\"")
+}
+
+fn closes_verse(l: &str) -> bool {
+    l.contains("</lg>") || l.contains("rend=\"gathalast\"")
+}
+
+fn is_verse_line(l: &str) -> bool {
+    l.contains("<l>") || l.contains("<l ") || l.contains("rend=\"gatha")
+}
+
+fn opens_paragraph(l: &str) -> bool {
+    l.contains("<p>") || l.contains("<p ")
+}
+
+fn is_heading(l: &str) -> bool {
+    ["<head", "<cb:jhead", "<cb:mulu", "<trailer"]
+        .iter()
+        .any(|t| l.contains(t))
+}
+
+/// Index of the nearest line at or before `i` (within `max` lines) satisfying `f`.
+fn back(lines: &[&str], i: usize, max: usize, f: impl Fn(&str) -> bool) -> Option<usize> {
+    (i.saturating_sub(max)..=i).rev().find(|&j| f(lines[j]))
+}
+
+/// Index of the nearest line at or after `i` (within `max` lines) satisfying `f`.
+fn forward(lines: &[&str], i: usize, max: usize, f: impl Fn(&str) -> bool) -> Option<usize> {
+    (i..lines.len().min(i + max + 1)).find(|&j| f(lines[j]))
+}
+
+/// Window around 1-based `line` of an XML text.
+pub fn suggest(lines: &[&str], line: usize) -> ContextWindow {
+    if line == 0 || line > lines.len() {
+        return ContextWindow::default();
+    }
+    let i = line - 1;
+    let cur = lines[i];
+
+    if is_heading(cur) {
+        return ContextWindow {
+            before: 0,
+            after: 2,
+            unit: "heading",
+        };
+    }
+
+    if is_verse_line(cur) || opens_verse(cur) {
+        let start = back(lines, i, MAX_BEFORE, opens_verse).unwrap_or(i);
+        let end = forward(lines, i, MAX_AFTER, closes_verse).unwrap_or(i);
+        return ContextWindow {
+            before: i - start,
+            after: end - i,
+            unit: "verse",
+        };
+    }
+
+    let p_start = back(lines, i, MAX_BEFORE, opens_paragraph);
+    let p_end = forward(lines, i, MAX_AFTER, |l| l.contains("</p>"));
+    // A paragraph opened after a closed one is a different paragraph.
+    let p_start = p_start.filter(|&s| s == i || !lines[s..i].iter().any(|l| l.contains("</p>")));
+    if let (Some(s), Some(e)) = (p_start, p_end) {
+        return ContextWindow {
+            before: i - s,
+            after: e - i,
+            unit: "paragraph",
+        };
+    }
+
+    // Too long to fetch whole: from the line where the sentence starts to where it ends.
+    let s = if i > 0 {
+        back(lines, i - 1, MAX_BEFORE, ends_sentence).unwrap_or(i.saturating_sub(1))
+    } else {
+        i
+    };
+    let e = forward(lines, i, MAX_AFTER, ends_sentence).unwrap_or((i + 3).min(lines.len() - 1));
+    ContextWindow {
+        before: i - s,
+        after: e - i,
+        unit: "sentence",
+    }
+}
+
+/// Window around 1-based `line` of the file at `path` (UTF-8 or UTF-16); the fixed 1/3 window
+/// when the file cannot be read.
+pub fn suggest_for_file(path: &Path, line: usize) -> ContextWindow {
+    match crate::read_file_with_encoding(path) {
+        Some(s) => suggest(&s.lines().collect::<Vec<_>>(), line),
+        None => ContextWindow::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(s: &str) -> Vec<&str> {
+        s.lines().collect()
+    }
+
+    #[test]
+    fn windows_follow_the_unit_around_the_match() {
+        // Tipitaka stanza: match in the second line, the stanza has four.
+        let pali = lines(
+            r#"<p rend="bodytext" n="1">Evaṃ me sutaṃ.</p>
+<p rend="gatha1">Manopubbaṅgamā dhammā,</p>
+<p rend="gatha2">manoseṭṭhā manomayā;</p>
+<p rend="gatha3">Manasā ce paduṭṭhena,</p>
+<p rend="gathalast">bhāsati vā karoti vā;</p>
+<p rend="bodytext" n="2">Tato naṃ dukkhamanveti.</p>"#,
+        );
+        let w = suggest(&pali, 3);
+        assert_eq!((w.before, w.after, w.unit), (1, 2, "verse"));
+        let w = suggest(&pali, 6);
+        assert_eq!((w.before, w.after, w.unit), (0, 0, "paragraph"));
+
+        // CBETA paragraph over several <lb> lines.
+        let cbeta = lines(
+            r#"<p xml:id="pT01p0001a0101"><lb n="0001a01"/>如是我聞。一時佛在
+<lb n="0001a02"/>舍衛國祇樹給孤
+<lb n="0001a03"/>獨園。</p>
+<lb n="0001a04"/><head>序品</head>"#,
+        );
+        let w = suggest(&cbeta, 2);
+        assert_eq!((w.before, w.after, w.unit), (1, 1, "paragraph"));
+        assert_eq!(suggest(&cbeta, 4).unit, "heading");
+
+        // A paragraph longer than the caps falls back to the sentence.
+        let mut long = vec![r#"<p>"#];
+        long.extend(std::iter::repeat_n("<lb/>而說偈言", 10));
+        long.push("<lb/>云何為十。");
+        long.extend(std::iter::repeat_n("<lb/>爾時世尊", 10));
+        long.push("<lb/>即說此經。");
+        long.extend(std::iter::repeat_n("<lb/>後說", 20));
+        long.push("</p>");
+        let w = suggest(&long, 17);
+        // From the line holding the previous 。 to the one that ends the sentence.
+        assert_eq!((w.before, w.after, w.unit), (5, 6, "sentence"));
+        assert_eq!(suggest(&long, 999), ContextWindow::default());
+    }
+}
@@ -0,0 +1,222 @@
+//! Which corpora and tools this server exposes.
+//!
+//! `--enable` / `--disable` (or `DAIZO_ENABLE` / `DAIZO_DISABLE`) take comma-separated group
+//! names. A tool belongs to the corpus its name starts with (`cbeta_fetch` → `cbeta`), to `web`
+//! when it reaches an online source, and to `admin` when it writes files or changes server
+//! state. `--enable` lists the corpora to serve; tools of other corpora are hidden, general
+//! tools stay. `--disable` hides every tool in the named groups. Without `web` the server also
+//! never clones or downloads missing corpus data, so `--disable web,admin` is a network-free,
+//! read-only deployment.
+
+use std::collections::BTreeSet;
+
+/// Corpora read from local checkouts.
+pub const LOCAL_CORPORA: &[&str] = &["cbeta", "tipitaka", "gretil", "sarit", "muktabodha"];
+/// Corpora queried online with tools of their own; the sources registered in
+/// `daizo_core::remote` are online corpora too (see [`remote_corpora`]).
+pub const REMOTE_CORPORA: &[&str] = &["sat", "jozen", "tibetan"];
+/// Groups that are not corpora.
+pub const OTHER_GROUPS: &[&str] = &["web", "admin"];
+
+const ADMIN_TOOLS: &[&str] = &[
+    "index_rebuild",
+    "daizo_reload",
+    "daizo_aliases_reload",
+    "research_bundle_record",
+    "annotation_add",
+    "collection_create",
+    "collection_add",
+];
+
+/// Corpora queried online: [`REMOTE_CORPORA`] and the registered remote sources.
+pub fn remote_corpora() -> impl Iterator<Item = &'static str> {
+    REMOTE_CORPORA.iter().copied().chain(
+        daizo_core::remote::sources()
+            .iter()
+            .map(|s| s.name())
+            .filter(|n| !REMOTE_CORPORA.contains(n)),
+    )
+}
+
+fn is_remote(g: &str) -> bool {
+    remote_corpora().any(|c| c == g)
+}
+
+/// Whether `g` names a corpus (local or online).
+pub fn is_corpus(g: &str) -> bool {
+    LOCAL_CORPORA.contains(&g) || is_remote(g)
+}
+
+/// The corpus a tool serves, from its name prefix.
+pub fn tool_corpus(tool: &str) -> Option<&'static str> {
+    let prefix = tool.split_once('_')?.0;
+    LOCAL_CORPORA
+        .iter()
+        .copied()
+        .chain(remote_corpora())
+        .find(|c| *c == prefix)
+}
+
+/// Every group `tool` belongs to.
+pub fn tool_groups(tool: &str) -> Vec<&'static str> {
+    let mut g = Vec::new();
+    if let Some(c) = tool_corpus(tool) {
+        g.push(c);
+        if is_remote(c) {
+            g.push("web");
+        }
+    }
+    if ADMIN_TOOLS.contains(&tool) {
+        g.push("admin");
+    }
+    g
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Corpora to serve; None serves all.
+    enabled: Option<BTreeSet<String>>,
+    disabled: BTreeSet<String>,
+}
+
+fn parse_list(s: &str) -> Result<BTreeSet<String>, String> {
+    let mut out = BTreeSet::new();
+    for name in s.split(',').map(|n| n.trim().to_lowercase()) {
+        if name.is_empty() {
+            continue;
+        }
+        if !is_corpus(&name) && !OTHER_GROUPS.contains(&name.as_str()) {
+            let known: Vec<&str> = LOCAL_CORPORA
+                .iter()
+                .copied()
+                .chain(remote_corpora())
+                .chain(OTHER_GROUPS.iter().copied())
+                .collect();
+            return Err(format!(
+                "unknown group '{}' (expected one of: {})",
+                name,
+                known.join(", ")
+            ));
+        }
+        out.insert(name);
+    }
+    Ok(out)
+}
+
+impl Capabilities {
+    pub fn parse(enable: Option<&str>, disable: Option<&str>) -> Result<Self, String> {
+        let enabled = match enable {
+            Some(s) => {
+                let set = parse_list(s)?;
+                if let Some(g) = set.iter().find(|g| !is_corpus(g)) {
+                    return Err(format!(
+                        "--enable takes corpora; '{}' can only be disabled",
+                        g
+                    ));
+                }
+                Some(set)
+            }
+            None => None,
+        };
+        Ok(Capabilities {
+            enabled,
+            disabled: disable.map(parse_list).transpose()?.unwrap_or_default(),
+        })
+    }
+
+    /// Whether a group is served: not disabled, and for a corpus, enabled.
+    pub fn allows(&self, group: &str) -> bool {
+        if self.disabled.contains(group) {
+            return false;
+        }
+        if is_remote(group) && !self.network() {
+            return false;
+        }
+        match &self.enabled {
+            Some(set) if is_corpus(group) => set.contains(group),
+            _ => true,
+        }
+    }
+
+    /// Whether the server may go online at all (remote corpora, cloning missing data).
+    pub fn network(&self) -> bool {
+        !self.disabled.contains("web")
+    }
+
+    pub fn allows_tool(&self, tool: &str) -> bool {
+        tool_groups(tool).iter().all(|g| self.allows(g))
+    }
+
+    /// Whether any restriction is in effect.
+    pub fn restricted(&self) -> bool {
+        self.enabled.is_some() || !self.disabled.is_empty()
+    }
+
+    /// `corpora cbeta, tipitaka; disabled web, admin; network off`.
+    pub fn summary(&self) -> String {
+        let join = |s: &BTreeSet<String>| s.iter().cloned().collect::<Vec<_>>().join(", ");
+        let mut parts = vec![match &self.enabled {
+            Some(s) => format!("corpora {}", join(s)),
+            None => "all corpora".to_string(),
+        }];
+        if !self.disabled.is_empty() {
+            parts.push(format!("disabled {}", join(&self.disabled)));
+        }
+        parts.push(format!(
+            "network {}",
+            if self.network() { "on" } else { "off" }
+        ));
+        parts.join("; ")
+    }
+
+    /// Enabled and disabled groups, for reporting.
+    pub fn describe(&self) -> serde_json::Value {
+        serde_json::json!({
+            "enabled": self.enabled,
+            "disabled": self.disabled,
+            "network": self.network(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enable_lists_corpora_and_disable_hides_groups() {
+        let all = Capabilities::default();
+        assert!(all.allows_tool("sat_search") && all.allows_tool("index_rebuild"));
+        assert!(all.network() && !all.restricted());
+
+        let c = Capabilities::parse(Some("cbeta, Tipitaka"), None).unwrap();
+        assert!(c.allows_tool("cbeta_fetch") && c.allows_tool("tipitaka_search"));
+        assert!(!c.allows_tool("gretil_fetch") && !c.allows_tool("sat_search"));
+        assert!(c.allows_tool("daizo_version") && c.allows_tool("corpus_stats"));
+        assert!(!c.allows("sat") && c.network());
+
+        let ro = Capabilities::parse(None, Some("sat,web,admin")).unwrap();
+        assert!(ro.allows_tool("cbeta_search") && ro.allows_tool("daizo_status"));
+        for t in [
+            "sat_fetch",
+            "jozen_search",
+            "tibetan_search",
+            "index_rebuild",
+        ] {
+            assert!(!ro.allows_tool(t), "{t}");
+        }
+        assert!(!ro.network());
+        assert_eq!(
+            ro.summary(),
+            "all corpora; disabled admin, sat, web; network off"
+        );
+
+        let nosat = Capabilities::parse(None, Some("sat")).unwrap();
+        assert!(nosat.allows_tool("jozen_fetch") && nosat.allows("web"));
+
+        assert!(Capabilities::parse(Some("cbeta,nope"), None).is_err());
+        assert!(Capabilities::parse(Some("web"), None).is_err());
+        assert_eq!(tool_groups("sat_pipeline"), ["sat", "web"]);
+        assert_eq!(tool_groups("regex_explain"), Vec::<&str>::new());
+    }
+}
@@ -0,0 +1,224 @@
+//! Machine-readable tool failures.
+//!
+//! Protocol problems (an unknown tool) are answered with a JSON-RPC error. Failures while a
+//! tool runs become results with `isError: true`, so the client model can read them, and carry
+//! `_meta.error = {code, message, ...}` with one of the stable codes below.
+
+use daizo_core::pattern::PatternError;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+
+/// JSON-RPC "Invalid params", which the MCP spec uses for unknown tools.
+const INVALID_PARAMS: i64 = -32602;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ToolError {
+    UnknownTool(String),
+    /// The id or query did not resolve to a text (or the text is empty).
+    TextNotFound {
+        corpus: String,
+        id: String,
+    },
+    /// The corpus is not installed and could not be fetched.
+    IndexUnavailable {
+        corpus: String,
+        path: String,
+    },
+    InvalidRegex {
+        pattern: String,
+        error: PatternError,
+    },
+    /// A `queryMode: "boolean"` expression that does not parse.
+    InvalidQuery {
+        query: String,
+        reason: String,
+    },
+    /// A `matchId` this server did not hand out, or whose text is gone from the file.
+    UnknownMatch {
+        match_id: String,
+        reason: String,
+    },
+    /// An argument outside the values a tool accepts, such as a path-like `id` that could
+    /// name a file outside the corpus (`../x`, `/etc/x`, `a/b`).
+    InvalidArgument {
+        name: String,
+        value: String,
+        expected: String,
+    },
+    /// A corpus or tool group this server was started without (`--enable` / `--disable`).
+    Disabled {
+        group: String,
+    },
+    /// A remote source (SAT, Jodo Shu Zensho, BUDA, Adarshah) could not be reached.
+    NetworkError {
+        source: String,
+        reason: String,
+    },
+}
+
+impl ToolError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ToolError::UnknownTool(_) => "UNKNOWN_TOOL",
+            ToolError::TextNotFound { .. } => "TEXT_NOT_FOUND",
+            ToolError::IndexUnavailable { .. } => "INDEX_UNAVAILABLE",
+            ToolError::InvalidRegex { .. } => "INVALID_REGEX",
+            ToolError::InvalidQuery { .. } => "INVALID_QUERY",
+            ToolError::UnknownMatch { .. } => "UNKNOWN_MATCH",
+            ToolError::InvalidArgument { .. } => "INVALID_ARGUMENT",
+            ToolError::Disabled { .. } => "DISABLED",
+            ToolError::NetworkError { .. } => "NETWORK_ERROR",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ToolError::UnknownTool(name) => format!("Unknown tool: {}", name),
+            ToolError::TextNotFound { corpus, id } => {
+                format!("{}: text not found: {}", corpus, id)
+            }
+            ToolError::IndexUnavailable { corpus, path } => format!(
+                "{}: corpus data unavailable at {} (run `daizo-cli init` or check the network)",
+                corpus, path
+            ),
+            ToolError::InvalidRegex { pattern, error } => {
+                format!("invalid regex '{}': {}", pattern, error)
+            }
+            ToolError::InvalidQuery { query, reason } => {
+                format!("invalid boolean query '{}': {}", query, reason)
+            }
+            ToolError::UnknownMatch { match_id, reason } => {
+                format!("matchId '{}': {}", match_id, reason)
+            }
+            ToolError::InvalidArgument {
+                name,
+                value,
+                expected,
+            } => format!("invalid {} '{}': expected {}", name, value, expected),
+            ToolError::Disabled { group } => {
+                format!("{} is disabled on this server", group)
+            }
+            ToolError::NetworkError { source, reason } => {
+                format!("{}: request failed: {}", source, reason)
+            }
+        }
+    }
+
+    fn data(&self) -> Value {
+        let mut data = match self {
+            ToolError::UnknownTool(name) => json!({ "tool": name }),
+            ToolError::TextNotFound { corpus, id } => json!({ "corpus": corpus, "id": id }),
+            ToolError::IndexUnavailable { corpus, path } => {
+                json!({ "corpus": corpus, "path": path })
+            }
+            ToolError::InvalidRegex { pattern, error } => json!({
+                "pattern": pattern,
+                "reason": error.message,
+                "position": error.position,
+                "suggestion": error.suggestion,
+            }),
+            ToolError::InvalidQuery { query, reason } => {
+                json!({ "query": query, "reason": reason })
+            }
+            ToolError::UnknownMatch { match_id, reason } => {
+                json!({ "matchId": match_id, "reason": reason })
+            }
+            ToolError::InvalidArgument {
+                name,
+                value,
+                expected,
+            } => json!({ "argument": name, "value": value, "expected": expected }),
+            ToolError::Disabled { group } => json!({ "group": group }),
+            ToolError::NetworkError { source, reason } => {
+                json!({ "source": source, "reason": reason })
+            }
+        };
+        data["code"] = json!(self.code());
+        data["message"] = json!(self.message());
+        data
+    }
+
+    pub fn response(&self, id: &Value) -> Value {
+        match self {
+            ToolError::UnknownTool(_) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": INVALID_PARAMS, "message": self.message(), "data": self.data() }
+            }),
+            _ => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": {
+                    "content": [{ "type": "text", "text": self.message() }],
+                    "isError": true,
+                    "_meta": { "error": self.data() }
+                }
+            }),
+        }
+    }
+}
+
+pub fn is_error_response(resp: &Value) -> bool {
+    resp.get("error").is_some() || resp["result"]["isError"] == json!(true)
+}
+
+thread_local! {
+    static NETWORK_FAILURE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Remember why the last remote request of this call failed. Tool calls run on their own
+/// thread, so handlers can tell "the source has no such text" from "the source was down".
+pub fn note_network_failure(url: &str, reason: impl std::fmt::Display) {
+    let reason = reason.to_string();
+    // reqwest errors already name the URL.
+    let reason = if reason.contains(url) {
+        reason
+    } else {
+        format!("{} ({})", reason, url)
+    };
+    NETWORK_FAILURE.with(|f| *f.borrow_mut() = Some(reason));
+}
+
+pub fn take_network_failure() -> Option<String> {
+    NETWORK_FAILURE.with(|f| f.borrow_mut().take())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_tools_are_protocol_errors_and_the_rest_tool_errors() {
+        let r = ToolError::UnknownTool("nope".into()).response(&json!(3));
+        assert_eq!(r["error"]["code"], INVALID_PARAMS);
+        assert_eq!(r["error"]["data"]["code"], "UNKNOWN_TOOL");
+        assert!(is_error_response(&r));
+
+        let r = ToolError::TextNotFound {
+            corpus: "cbeta".into(),
+            id: "T9999".into(),
+        }
+        .response(&json!(4));
+        assert!(r.get("error").is_none());
+        assert_eq!(r["result"]["isError"], true);
+        assert_eq!(r["result"]["_meta"]["error"]["code"], "TEXT_NOT_FOUND");
+        assert_eq!(r["result"]["_meta"]["error"]["id"], "T9999");
+        assert!(is_error_response(&r));
+        assert!(!is_error_response(&json!({"result": {"content": []}})));
+
+        note_network_failure("https://example.org", "HTTP 503");
+        assert_eq!(
+            take_network_failure().as_deref(),
+            Some("HTTP 503 (https://example.org)")
+        );
+        assert_eq!(take_network_failure(), None);
+        note_network_failure(
+            "https://example.org",
+            "error sending request for url (https://example.org)",
+        );
+        assert_eq!(
+            take_network_failure().as_deref(),
+            Some("error sending request for url (https://example.org)")
+        );
+    }
+}
@@ -0,0 +1,188 @@
+// ============ Remote HTTP ============
+//
+// Requests to the remote sources (SAT, Jodo Shu Zensho, Adarshah, BUDA) go through one async
+// reqwest client on a small tokio runtime. At most `[network] concurrency` requests are in
+// flight, and each host is treated politely (`daizo_core::politeness`): its robots.txt is read
+// once a day and obeyed, requests to it are spaced by `host_interval_ms` (or its Crawl-delay)
+// and capped at `max_per_minute`. Each request reserves the next free slot of its host before
+// it waits, so concurrent callers queue in order instead of racing. Tool handlers stay
+// synchronous and block on `get`, `get_all`, `post_form` or `post`.
+
+use crate::{config, setting_usize};
+use daizo_core::politeness::{self, Policy};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tokio::sync::{Mutex, Semaphore};
+
+fn runtime() -> &'static Runtime {
+    static RT: OnceLock<Runtime> = OnceLock::new();
+    RT.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("daizo-http")
+            .enable_all()
+            .build()
+            .expect("tokio runtime")
+    })
+}
+
+/// The User-Agent is `Policy::current()`'s at the first request.
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .user_agent(Policy::current().user_agent)
+            .connect_timeout(Duration::from_secs(5))
+            .timeout(Duration::from_secs(12))
+            .build()
+            .expect("reqwest client")
+    })
+}
+
+/// Requests in flight at once (`DAIZO_HTTP_CONCURRENCY`; read at the first request).
+fn permits() -> &'static Semaphore {
+    static PERMITS: OnceLock<Semaphore> = OnceLock::new();
+    PERMITS.get_or_init(|| {
+        let n = setting_usize(
+            "DAIZO_HTTP_CONCURRENCY",
+            config::current().network.concurrency,
+            4,
+        );
+        Semaphore::new(n.max(1))
+    })
+}
+
+/// What a request sends; rebuilt for each attempt.
+enum Body {
+    Get,
+    Form(Vec<(String, String)>),
+    Raw(Vec<(String, String)>, String),
+}
+
+impl Body {
+    fn build(&self, url: &str) -> reqwest::RequestBuilder {
+        match self {
+            Body::Get => client().get(url),
+            Body::Form(params) => client().post(url).form(params),
+            Body::Raw(headers, body) => headers
+                .iter()
+                .fold(client().post(url), |r, (k, v)| r.header(k, v))
+                .body(body.clone()),
+        }
+    }
+}
+
+/// Read the robots.txt of `url`'s host unless a recent copy is known. A missing or unreadable
+/// file allows everything until the next check.
+async fn check_robots(url: &str, policy: &Policy) -> Result<(), String> {
+    // One robots.txt request at a time, so concurrent callers do not fetch it twice.
+    static FETCHING: Mutex<()> = Mutex::const_new(());
+    if politeness::shared()
+        .robots_due(url, policy, Instant::now())?
+        .is_none()
+    {
+        return Ok(());
+    }
+    let _one = FETCHING.lock().await;
+    let Some(robots) = politeness::shared().robots_due(url, policy, Instant::now())? else {
+        return Ok(());
+    };
+    let at = politeness::shared().reserve(&robots.host, policy, Instant::now());
+    tokio::time::sleep_until(at.into()).await;
+    let body = match client().get(&robots.url).send().await {
+        Ok(r) if r.status().is_success() => r.text().await.ok(),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::debug!(url = %robots.url, error = %e, "robots.txt not fetched");
+            None
+        }
+    };
+    politeness::shared().set_robots(
+        &robots.host,
+        body.as_deref(),
+        &policy.user_agent,
+        Instant::now(),
+    );
+    Ok(())
+}
+
+/// Send a request to `url`, retrying 429 and server errors with exponential backoff.
+async fn send(url: String, body: Body, max_retries: u32) -> Result<String, String> {
+    let _permit = permits().acquire().await.map_err(|e| e.to_string())?;
+    let policy = Policy::current();
+    check_robots(&url, &policy).await?;
+    let mut attempt = 0u32;
+    let mut backoff = 500u64; // ms
+    loop {
+        let at = politeness::shared().admit(&url, &policy, Instant::now())?;
+        tokio::time::sleep_until(at.into()).await;
+        let last_error = match body.build(&url).send().await {
+            Ok(resp) => {
+                let status = resp.status();
+                if status.is_success() {
+                    return resp.text().await.map_err(|e| e.to_string());
+                }
+                if status.as_u16() != 429 && !status.is_server_error() {
+                    return Err(format!("HTTP {}", status));
+                }
+                format!("HTTP {}", status)
+            }
+            Err(e) => {
+                tracing::debug!(attempt = attempt + 1, error = %e, "request failed");
+                e.to_string()
+            }
+        };
+        attempt += 1;
+        if attempt > max_retries {
+            return Err(last_error);
+        }
+        tokio::time::sleep(Duration::from_millis(backoff)).await;
+        backoff = (backoff.saturating_mul(2)).min(8000);
+    }
+}
+
+/// GET one page; the error is the last failure seen.
+pub fn get(url: &str, max_retries: u32) -> Result<String, String> {
+    runtime().block_on(send(url.to_string(), Body::Get, max_retries))
+}
+
+/// GET several pages concurrently, within the concurrency and per-host limits. Results are in
+/// the order of `urls`.
+pub fn get_all(urls: &[String], max_retries: u32) -> Vec<Result<String, String>> {
+    let rt = runtime();
+    let tasks: Vec<_> = urls
+        .iter()
+        .map(|u| rt.spawn(send(u.clone(), Body::Get, max_retries)))
+        .collect();
+    rt.block_on(async {
+        let mut out = Vec::with_capacity(tasks.len());
+        for t in tasks {
+            out.push(t.await.unwrap_or_else(|e| Err(e.to_string())));
+        }
+        out
+    })
+}
+
+/// POST `params` as a form; the error is the last failure seen.
+pub fn post_form(url: &str, params: &[(&str, String)], max_retries: u32) -> Result<String, String> {
+    let params = params
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.clone()))
+        .collect();
+    runtime().block_on(send(url.to_string(), Body::Form(params), max_retries))
+}
+
+/// POST a raw `body` with extra `headers`.
+pub fn post(
+    url: &str,
+    headers: &[(&str, &str)],
+    body: String,
+    max_retries: u32,
+) -> Result<String, String> {
+    let headers = headers
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    runtime().block_on(send(url.to_string(), Body::Raw(headers, body), max_retries))
+}
@@ -0,0 +1,161 @@
+//! Server log: `tracing` events go to `daizo-mcp.log` in the data directory, rotated by size.
+//! Nothing is ever written to stdout, which carries the stdio transport.
+
+use daizo_core::config;
+use daizo_core::path_resolver::daizo_home;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_LEVEL: &str = "warn";
+const DEFAULT_MAX_MB: u64 = 16;
+const DEFAULT_KEEP: usize = 3;
+
+/// Append-only log file that moves itself to `<name>.1` (and `.1` to `.2`, …) once a record
+/// would take it past `max_bytes`, keeping `keep` old files.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    /// Open file and its current length; opened on the first record.
+    file: Mutex<Option<(File, u64)>>,
+}
+
+impl RotatingFile {
+    pub fn new(path: PathBuf, max_bytes: u64, keep: usize) -> Self {
+        RotatingFile {
+            path,
+            max_bytes,
+            keep,
+            file: Mutex::new(None),
+        }
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        if self.keep == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+        let _ = std::fs::remove_file(self.rotated(self.keep));
+        for n in (1..self.keep).rev() {
+            let _ = std::fs::rename(self.rotated(n), self.rotated(n + 1));
+        }
+        std::fs::rename(&self.path, self.rotated(1))
+    }
+
+    fn open(path: &Path) -> io::Result<(File, u64)> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let f = OpenOptions::new().create(true).append(true).open(path)?;
+        let len = f.metadata()?.len();
+        Ok((f, len))
+    }
+
+    /// Write one formatted record, rotating first when it would not fit.
+    fn append(&self, buf: &[u8]) -> io::Result<()> {
+        let mut guard = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if guard.is_none() {
+            *guard = Some(Self::open(&self.path)?);
+        }
+        let len = guard.as_ref().map(|(_, n)| *n).unwrap_or(0);
+        if len > 0 && len + buf.len() as u64 > self.max_bytes {
+            *guard = None;
+            self.rotate()?;
+            *guard = Some(Self::open(&self.path)?);
+        }
+        if let Some((f, n)) = guard.as_mut() {
+            f.write_all(buf)?;
+            *n += buf.len() as u64;
+        }
+        Ok(())
+    }
+}
+
+impl Write for &RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.append(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `DAIZO_LOG`, else `debug` for the older `DAIZO_DEBUG=1`, else `[log] level`, else `warn`.
+fn level() -> String {
+    let env = |k: &str| std::env::var(k).ok().filter(|v| !v.trim().is_empty());
+    env("DAIZO_LOG")
+        .or_else(|| (env("DAIZO_DEBUG").as_deref() == Some("1")).then(|| "debug".to_string()))
+        .or_else(|| config::current().log.level.clone())
+        .unwrap_or_else(|| DEFAULT_LEVEL.to_string())
+}
+
+/// Install the global subscriber. Log settings are read once here; SIGHUP does not change them.
+pub fn init() {
+    let level = level();
+    if level.trim().eq_ignore_ascii_case("off") {
+        return;
+    }
+    let filter = EnvFilter::try_new(&level).unwrap_or_else(|e| {
+        eprintln!(
+            "daizo-mcp: DAIZO_LOG '{}': {}; using {}",
+            level, e, DEFAULT_LEVEL
+        );
+        EnvFilter::new(DEFAULT_LEVEL)
+    });
+    let cfg = config::current();
+    let max_mb = std::env::var("DAIZO_LOG_MAX_MB")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .or(cfg.log.max_mb)
+        .unwrap_or(DEFAULT_MAX_MB);
+    let file = Arc::new(RotatingFile::new(
+        daizo_home().join("daizo-mcp.log"),
+        max_mb.max(1) * 1024 * 1024,
+        cfg.log.keep.unwrap_or(DEFAULT_KEEP),
+    ));
+    let json = std::env::var("DAIZO_LOG_FORMAT")
+        .ok()
+        .or_else(|| cfg.log.format.clone())
+        .is_some_and(|f| f.trim().eq_ignore_ascii_case("json"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(file)
+        .with_ansi(false)
+        .with_thread_names(true);
+    let _ = if json {
+        builder.json().try_init()
+    } else {
+        builder.try_init()
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_at_the_size_cap() {
+        let dir = std::env::temp_dir().join(format!("daizo-log-rotate-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let log = RotatingFile::new(dir.join("daizo-mcp.log"), 10, 2);
+        for rec in ["aaaaaa\n", "bbbbbb\n", "cccccc\n", "dddddd\n"] {
+            (&log).write_all(rec.as_bytes()).unwrap();
+        }
+        let read = |p: PathBuf| std::fs::read_to_string(p).unwrap();
+        assert_eq!(read(dir.join("daizo-mcp.log")), "dddddd\n");
+        assert_eq!(read(dir.join("daizo-mcp.log.1")), "cccccc\n");
+        assert_eq!(read(dir.join("daizo-mcp.log.2")), "bbbbbb\n");
+        assert!(!dir.join("daizo-mcp.log.3").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,28 +1,60 @@
 use anyhow::Result;
+use daizo_core::authority::{self, AuthorityRecord};
+use daizo_core::cancel::CancelToken;
+use daizo_core::colophon::{list_colophons, Colophon, ColophonKind};
+use daizo_core::config;
+use daizo_core::entity::{self, Entity, EntityKind, Mention};
+use daizo_core::fetch::{
+    self, apply_fetch_pipeline, char_at_line, line_at_char, slice_text_bounds, FetchOptions,
+    FetchSlice, Fetched,
+};
+use daizo_core::filters::{CbetaFilter, GretilFilter, TipitakaFilter};
+use daizo_core::grep_cache::{GrepCache, GrepKey};
+use daizo_core::highlight::Highlighter;
+use daizo_core::index_cache::{Derived, Index, IndexCache};
+use daizo_core::juan_map::{attribute_juans, juan_map_cached, JuanMap};
+use daizo_core::notes::{list_notes, Note, NoteCategory};
+use daizo_core::paging::{self, PageBy};
+use daizo_core::prefetch::{PrefetchSettings, Prefetcher};
+use daizo_core::progress::{Progress, Reporter};
+use daizo_core::relevance::SortBy;
+use daizo_core::remote::{self, normalize_ws, RemoteSource, SAT};
 use daizo_core::text_utils::{
-    compute_match_score_sanskrit, find_highlight_positions, is_subsequence, jaccard, normalized,
-    token_jaccard, ws_cjk_variant_fuzzy_regex_literal,
+    compute_match_score_sanskrit, detect_sanskrit_scheme, find_highlight_positions, is_subsequence,
+    jaccard, normalized, sanskrit_scheme_variants, to_whitespace_fuzzy_literal, token_jaccard,
+    ws_cjk_variant_fuzzy_regex_literal, HighlightPos,
 };
+use daizo_core::timing::Phase;
+use daizo_core::title_rank::{self, MatchReason, TitleQuery};
 use daizo_core::{
     build_cbeta_index, build_gretil_index, build_muktabodha_index, build_sarit_index,
-    build_tipitaka_index, cbeta_gaiji_map_fast, cbeta_grep, extract_cbeta_juan,
-    extract_cbeta_juan_plain, extract_cbeta_plain_from_snippet, extract_text,
-    extract_text_around_line_asymmetric, extract_text_opts, gretil_grep, list_heads_cbeta,
-    list_heads_generic, muktabodha_grep, sarit_grep, tipitaka_grep, IndexEntry,
+    build_tipitaka_index, cbeta_gaiji_map_fast, cbeta_grep, cbeta_grep_notes,
+    extract_cbeta_juan_plain_with_map, extract_cbeta_juan_with_map,
+    extract_cbeta_plain_from_snippet, extract_text, extract_text_around_line_asymmetric,
+    extract_text_opts, gretil_grep, list_heads_cbeta, list_heads_generic, list_mulu_cbeta,
+    muktabodha_grep, sarit_grep, tipitaka_grep, tipitaka_grep_folded, IndexEntry, MuluEntry,
 };
 use encoding_rs::Encoding;
 use ewts::EwtsConverter;
 use regex::Regex;
-use reqwest::blocking::Client;
 use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sha1::{Digest, Sha1};
+use std::collections::VecDeque;
 use std::fs;
-use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, Instant};
+mod capabilities;
+mod errors;
+mod http;
+mod logging;
+mod shaping;
+mod transport;
+use capabilities::Capabilities;
+use errors::ToolError;
+use transport::{StdioTransport, Transport};
 
 /// Version constant for the MCP server
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -33,22 +65,11 @@ use daizo_core::path_resolver::{
     resolve_tipitaka_by_id, sarit_root, tipitaka_root,
 };
 
-fn to_whitespace_fuzzy_literal(s: &str) -> String {
-    // 連続した空白（改行含む）を \\s* に畳み込み、それ以外はリテラルとしてエスケープ
-    let mut out = String::new();
-    let mut in_ws = false;
-    for ch in s.chars() {
-        if ch.is_whitespace() {
-            if !in_ws {
-                out.push_str("\\s*");
-                in_ws = true;
-            }
-        } else {
-            in_ws = false;
-            out.push_str(&regex::escape(&ch.to_string()));
-        }
-    }
-    out
+/// `transliterate` argument of the GRETIL search tools (default: true).
+fn arg_transliterate(args: &serde_json::Value) -> bool {
+    args.get("transliterate")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true)
 }
 
 fn cbeta_extract_lb_from_line(line: &str) -> Option<String> {
@@ -58,145 +79,125 @@ fn cbeta_extract_lb_from_line(line: &str) -> Option<String> {
         .and_then(|c| c.get(1).map(|m| m.as_str().to_string()))
 }
 
-// ============ MCP stdio framing ============
-
-fn dbg_enabled() -> bool {
-    std::env::var("DAIZO_DEBUG").ok().as_deref() == Some("1")
-}
-fn dbg_log(msg: &str) {
-    if !dbg_enabled() {
-        return;
-    }
-    let path = daizo_home().join("daizo-mcp.log");
-    if let Ok(mut f) = std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(path)
-    {
-        let _ = writeln!(f, "{}", msg);
+/// " [juan 001 · 序品第一]" for a CBETA match, or "" when neither is known.
+fn cbeta_match_place(m: &daizo_core::GrepMatch) -> String {
+    let parts: Vec<String> = m
+        .juan_number
+        .as_deref()
+        .map(|n| format!("juan {}", n))
+        .into_iter()
+        .chain(m.section.clone())
+        .collect();
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", parts.join(" · "))
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-enum FramingMode {
-    Lsp,
-    Lines,
+fn env_usize(key: &str, default_v: usize) -> usize {
+    std::env::var(key)
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(default_v)
 }
 
-static MODE: OnceLock<FramingMode> = OnceLock::new();
-
-fn set_mode(m: FramingMode) {
-    let _ = MODE.set(m);
-}
-fn get_mode() -> FramingMode {
-    *MODE.get().unwrap_or(&FramingMode::Lsp)
+/// Env var, else the `[output]` value of config.toml, else `default_v`.
+fn setting_usize(key: &str, configured: Option<usize>, default_v: usize) -> usize {
+    std::env::var(key)
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .or(configured)
+        .unwrap_or(default_v)
 }
 
-fn read_message(stdin: &mut impl BufRead) -> Result<Option<serde_json::Value>> {
-    // Try to read one logical unit. Support two modes:
-    // 1) LSP-style headers with Content-Length and blank line
-    // 2) Single-line JSON (newline-delimited JSON)
-
-    let mut line = String::new();
-    let n = stdin.read_line(&mut line)?;
-    if n == 0 {
-        return Ok(None);
-    }
-
-    let trimmed = line.trim_start();
-    if trimmed.starts_with('{') {
-        // newline-delimited JSON
-        set_mode(FramingMode::Lines);
-        dbg_log(&format!("[lines] {}", line.trim_end()));
-        let v: serde_json::Value = serde_json::from_str(line.trim_end())?;
-        return Ok(Some(v));
-    }
-
-    // Otherwise, collect headers until blank line, include the first line we read
-    let mut headers = String::new();
-    headers.push_str(&line);
-    loop {
-        if line == "\n" || line == "\r\n" || line.trim().is_empty() {
-            break;
-        }
-        line.clear();
-        let n = stdin.read_line(&mut line)?;
-        if n == 0 {
-            break;
-        }
-        headers.push_str(&line);
-        if line == "\n" || line == "\r\n" || line.trim().is_empty() {
-            break;
-        }
-    }
-    set_mode(FramingMode::Lsp);
-    dbg_log(&format!(
-        "[hdr]{}",
-        headers.replace('\r', "\\r").replace('\n', "\\n")
-    ));
-
-    // parse Content-Length
-    let mut content_length = 0usize;
-    for hline in headers.lines() {
-        let h = hline.trim();
-        if h.to_lowercase().starts_with("content-length:") {
-            if let Some(v) = h.split(':').nth(1) {
-                content_length = v.trim().parse().unwrap_or(0);
-            }
-        }
-    }
-    if content_length == 0 {
-        dbg_log("[body] skip len=0");
-        return Ok(Some(serde_json::Value::Null));
+/// String output setting (highlight/snippet markers, provenance mode): env var, else config.toml.
+fn setting_str(key: &str) -> Option<String> {
+    if let Ok(v) = std::env::var(key) {
+        return Some(v);
     }
-    let mut content = vec![0u8; content_length];
-    stdin.read_exact(&mut content)?;
-    dbg_log(&format!("[body-bytes]{}", content_length));
-    let v: serde_json::Value = serde_json::from_slice(&content)?;
-    Ok(Some(v))
-}
-
-fn write_message(stdout: &mut impl Write, v: &serde_json::Value) -> Result<()> {
-    match get_mode() {
-        FramingMode::Lines => {
-            let body = serde_json::to_string(v)?;
-            writeln!(stdout, "{}", body)?;
-            stdout.flush()?;
-            dbg_log(&format!("[send-lines] {} chars", body.len()));
-        }
-        FramingMode::Lsp => {
-            let body = serde_json::to_vec(v)?;
-            write!(
-                stdout,
-                "Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\n",
-                body.len()
-            )?;
-            stdout.write_all(&body)?;
-            stdout.flush()?;
-            dbg_log(&format!("[send-lsp] {} bytes", body.len()));
-        }
+    let out = &config::current().output;
+    match key {
+        "DAIZO_HL_STYLE" => out.highlight_style.clone(),
+        "DAIZO_HL_PREFIX" => out.highlight_prefix.clone(),
+        "DAIZO_HL_SUFFIX" => out.highlight_suffix.clone(),
+        "DAIZO_SNIPPET_PREFIX" => out.snippet_prefix.clone(),
+        "DAIZO_SNIPPET_SUFFIX" => out.snippet_suffix.clone(),
+        "DAIZO_PROVENANCE" => out.provenance.clone(),
+        "DAIZO_META_MIRROR" => out.meta_mirror.map(|b| b.to_string()),
+        _ => None,
     }
-    Ok(())
 }
 
-fn env_usize(key: &str, default_v: usize) -> usize {
-    std::env::var(key)
-        .ok()
-        .and_then(|s| s.parse::<usize>().ok())
-        .unwrap_or(default_v)
+/// Highlight markers of a call: `highlightPrefix`/`highlightSuffix`, else the preset named by
+/// `highlightStyle`, else the configured markers (`DAIZO_HL_PREFIX`/`DAIZO_HL_SUFFIX`, then
+/// `DAIZO_HL_STYLE`), else `>>> ` / ` <<<`.
+fn highlighter(args: &serde_json::Value) -> Highlighter {
+    let arg = |k: &str| args.get(k).and_then(|v| v.as_str()).map(String::from);
+    let configured = Highlighter::resolve(
+        setting_str("DAIZO_HL_STYLE").as_deref(),
+        setting_str("DAIZO_HL_PREFIX"),
+        setting_str("DAIZO_HL_SUFFIX"),
+        &Highlighter::default(),
+    );
+    Highlighter::resolve(
+        arg("highlightStyle").as_deref(),
+        arg("highlightPrefix"),
+        arg("highlightSuffix"),
+        &configured,
+    )
 }
 
 fn default_max_chars() -> usize {
-    env_usize("DAIZO_MCP_MAX_CHARS", 6000)
+    setting_usize(
+        "DAIZO_MCP_MAX_CHARS",
+        config::current().output.max_chars,
+        6000,
+    )
+}
+/// Ceiling of a per-call budget (`DAIZO_MCP_MAX_CHARS_LIMIT`).
+fn max_chars_limit() -> usize {
+    setting_usize(
+        "DAIZO_MCP_MAX_CHARS_LIMIT",
+        config::current().output.max_chars_limit,
+        100_000,
+    )
+}
+/// Chars one call may return: its `maxChars` up to the ceiling, the ceiling when it only
+/// sets `maxTokens` (the token budget then decides), else DAIZO_MCP_MAX_CHARS.
+fn call_max_chars(args: &serde_json::Value) -> usize {
+    match args.get("maxChars").and_then(|v| v.as_u64()) {
+        Some(n) => (n as usize).min(max_chars_limit()),
+        None if call_max_tokens(args).is_some() => max_chars_limit(),
+        None => default_max_chars(),
+    }
+}
+/// The call's `maxTokens`, as estimated by `daizo_core::tokens`.
+fn call_max_tokens(args: &serde_json::Value) -> Option<usize> {
+    args.get("maxTokens")
+        .and_then(|v| v.as_u64())
+        .map(|n| n as usize)
 }
 fn default_snippet_len() -> usize {
-    env_usize("DAIZO_MCP_SNIPPET_LEN", 120)
+    setting_usize(
+        "DAIZO_MCP_SNIPPET_LEN",
+        config::current().output.snippet_len,
+        120,
+    )
 }
 fn default_auto_files() -> usize {
-    env_usize("DAIZO_MCP_AUTO_FILES", 1)
+    setting_usize(
+        "DAIZO_MCP_AUTO_FILES",
+        config::current().output.auto_files,
+        1,
+    )
 }
 fn default_auto_matches() -> usize {
-    env_usize("DAIZO_MCP_AUTO_MATCHES", 1)
+    setting_usize(
+        "DAIZO_MCP_AUTO_MATCHES",
+        config::current().output.auto_matches,
+        1,
+    )
 }
 
 #[derive(Deserialize)]
@@ -212,16 +213,45 @@ fn ensure_dir(p: &Path) {
     let _ = fs::create_dir_all(p);
 }
 
+static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+
+/// Corpora and tools this server exposes (`--enable` / `--disable`); everything by default.
+fn capabilities() -> &'static Capabilities {
+    CAPABILITIES.get_or_init(Capabilities::default)
+}
+
+// Missing data is cloned on first use unless the server runs with `--disable web`.
 fn ensure_cbeta_data() {
-    let _ = daizo_core::repo::ensure_cbeta_data_at(&cbeta_root());
+    if capabilities().network() {
+        let _ = daizo_core::repo::ensure_cbeta_data_at(&cbeta_root());
+    }
+}
+
+/// Check out CBETA `canon` when the data is a sparse checkout without it.
+fn ensure_cbeta_canon(canon: &str) {
+    if capabilities().network() {
+        let _ = daizo_core::repo::ensure_cbeta_canon_at(&cbeta_root(), canon);
+    }
+}
+
+/// `_meta.checkedOutCanons` of a CBETA search over a sparse checkout: hits can only come
+/// from these canons.
+fn cbeta_sparse_meta(meta: &mut serde_json::Value) {
+    if let Some(dirs) = daizo_core::repo::sparse_dirs(&cbeta_root()) {
+        meta["checkedOutCanons"] = json!(dirs);
+    }
 }
 
 fn ensure_tipitaka_data() {
-    let _ = daizo_core::repo::ensure_tipitaka_data_at(&daizo_home().join("tipitaka-xml"));
+    if capabilities().network() {
+        let _ = daizo_core::repo::ensure_tipitaka_data_at(&daizo_home().join("tipitaka-xml"));
+    }
 }
 
 fn ensure_sarit_data() {
-    let _ = daizo_core::repo::ensure_sarit_data_at(&sarit_root());
+    if capabilities().network() {
+        let _ = daizo_core::repo::ensure_sarit_data_at(&sarit_root());
+    }
 }
 
 fn ensure_muktabodha_dir() {
@@ -234,20 +264,31 @@ fn load_index(path: &Path) -> Option<Vec<IndexEntry>> {
         .and_then(|b| serde_json::from_slice(&b).ok())
 }
 
-fn save_index(path: &Path, entries: &Vec<IndexEntry>) -> Result<()> {
+/// Write an index cache and record the revision of `root` it was built from.
+fn save_index(path: &Path, root: &Path, entries: &Vec<IndexEntry>) -> Result<()> {
     ensure_dir(path.parent().unwrap());
     fs::write(path, serde_json::to_vec(entries)?)?;
+    daizo_core::repo::record_index_revision(path, root);
     Ok(())
 }
 
 // ============ Tool handlers ============
 
-fn handle_initialize(id: serde_json::Value) -> serde_json::Value {
+/// Protocol revisions this server speaks, newest first. `structuredContent` in tool results
+/// is part of 2025-06-18; older clients ignore it.
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+fn handle_initialize(id: serde_json::Value, params: &serde_json::Value) -> serde_json::Value {
+    let requested = params.get("protocolVersion").and_then(|v| v.as_str());
+    let version = PROTOCOL_VERSIONS
+        .iter()
+        .find(|v| Some(**v) == requested)
+        .unwrap_or(&"2024-11-05");
     json!({
         "jsonrpc": "2.0",
         "id": id,
         "result": {
-            "protocolVersion": "2024-11-05",
+            "protocolVersion": version,
             "capabilities": {
                 "tools": {},
                 "resources": {},
@@ -256,7 +297,7 @@ fn handle_initialize(id: serde_json::Value) -> serde_json::Value {
                         "name": "low-token-guide",
                         "description": "Guidance for AI to use search→fetch (lineNumber) instead of pipeline by default to minimize tokens.",
                         "messages": [
-                            {"role": "system", "content": "Prefer low-token flow: 0) If corpus/ID is unknown, call daizo_resolve({query}) and follow its recommended fetch tool+args. 1) If ID is known, call *_fetch directly. 2) Otherwise use *_search, read _meta.fetchSuggestions, then call *_fetch with {id, lineNumber, contextBefore:1, contextAfter:3, highlight:<query>, format:\"plain\"}. 3) Use *_pipeline only for cross-file summary; set autoFetch=false by default."}
+                            {"role": "system", "content": "Prefer low-token flow: 0) If corpus/ID is unknown, call daizo_resolve({query}) and follow its recommended fetch tool+args. 1) If ID is known, call *_fetch directly. 2) Otherwise use *_search, read _meta.fetchSuggestions, then call *_fetch with the suggested args (id, lineNumber, a contextBefore/contextAfter window sized to the verse, paragraph or sentence, highlight:<query>, format:\"plain\"). 3) Use *_pipeline only for cross-file summary; set autoFetch=false by default."}
                         ]
                     }
                 },
@@ -270,7 +311,22 @@ fn handle_initialize(id: serde_json::Value) -> serde_json::Value {
 fn tools_list() -> Vec<serde_json::Value> {
     vec![
         tool("daizo_version", "Get daizo-mcp server version and build information. Use this to check compatibility and troubleshoot issues.", json!({"type":"object","properties":{}})),
+        tool("daizo_status", "Data and index health: which local corpora are downloaded and at which revision (git commit, clone date), each index cache's entry count, build time and whether it was built from that revision, cache sizes per directory and the server's memory use. Check before heavy queries whether an index needs rebuilding, or to cite the exact data version.", json!({"type":"object","properties":{}})),
         tool("daizo_usage", "Usage guidance for AI (low-token). FAST PATH: use direct IDs when known. Local corpora: CBETA (T0001/T0262), Tipitaka (DN1/MN1), GRETIL (saddharmapuNDarIka), SARIT (file stem), MUKTABODHA (file stem). Online: SAT, JOZEN, Tibetan (tibetan_search).", json!({"type":"object","properties":{}})),
+        tool("daizo_aliases_reload", "Reload the user alias file (DAIZO_ALIASES or ~/.daizo/aliases.toml|yaml) without restarting. Aliases expand whole-query shorthand in all search tools; _meta.aliasExpansion shows when one was applied.", json!({"type":"object","properties":{
+            "list":{"type":"boolean","description":"Include the loaded alias table in _meta (default: false)"}
+        }})),
+        tool("daizo_metrics", "Per-tool call counts, latency (p50/p95/max), returned text size (mean/max chars) and cache hit rates (index, grep, prefetch, remote), to tune DAIZO_MCP_MAX_CHARS and caching. Recording is opt-in (DAIZO_METRICS=1 or [metrics] enabled = true); calls are also appended to ~/.daizo/metrics/requests.jsonl.", json!({"type":"object","properties":{
+            "scope":{"type":"string","enum":["session","log"],"description":"session: calls of this server process (default); log: the request log, all sessions"},
+            "tool":{"type":"string","description":"Only this tool"}
+        }})),
+        tool("daizo_reload", "Re-read config.toml (DAIZO_CONFIG or ~/.daizo/config.toml: [scoring] weights, [output] defaults, [corpora.<name>] roots) and the alias file without restarting the server. SIGHUP does the same. Environment variables still take precedence over the file.", json!({"type":"object","properties":{
+            "show":{"type":"boolean","description":"Include the effective configuration in _meta.config (default: false)"}
+        }})),
+        tool("index_rebuild", "Rebuild the index cache of a local corpus from the data on disk (after `git pull` or a new download) without the CLI. dryRun reports each index's state and what would be rebuilt. Corpora that are not installed are skipped, not downloaded.", json!({"type":"object","properties":{
+            "source":{"type":"string","enum":["cbeta","tipitaka","gretil","sarit","muktabodha","all"],"description":"Corpus to reindex, or all installed corpora"},
+            "dryRun":{"type":"boolean","description":"Only report what would be rebuilt (default: false)"}
+        },"required":["source"]})),
         tool("daizo_profile", "Run an in-process benchmark for a tool call and return timing stats (warm cache). Use for performance measurement.", json!({"type":"object","properties":{
             "tool":{"type":"string","description":"Tool name to call (e.g., cbeta_search, cbeta_fetch, daizo_resolve)."},
             "arguments":{"type":"object","description":"Arguments object passed to the tool."},
@@ -278,6 +334,74 @@ fn tools_list() -> Vec<serde_json::Value> {
             "warmup":{"type":"number","description":"Warmup iterations (default: 1)."},
             "includeSamples":{"type":"boolean","description":"Include per-iteration samples in _meta (default: false)."}
         },"required":["tool","arguments"]})),
+        tool("regex_explain", "Show how a content search rewrites a query into the regex it greps for (escaping, whitespace -> \\s*, CJK variant classes, transliteration variants, aliases, boolean terms) and whether the result compiles; invalid patterns report the character position and a fix.", json!({"type":"object","properties":{
+            "query":{"type":"string"},
+            "corpus":{"type":"string","description":"cbeta (default), tipitaka, gretil, sarit, muktabodha"},
+            "queryMode":{"type":"string","description":"\"boolean\" to explain an AND/OR/NOT query"},
+            "variants":{"type":"boolean","description":"CBETA: expand CJK variants (default: true)"},
+            "transliterate":{"type":"boolean","description":"GRETIL: add transliteration variants (default: true)"},
+            "foldDiacritics":{"type":"boolean","description":"Tipitaka: fold diacritics as tipitaka_search does"}
+        },"required":["query"]})),
+        tool("research_bundle_record", "Run a sequence of tool calls and save them as a named research bundle: arguments, text output with its SHA-1, reported ids/offsets, and the commit of each local corpus. Replay it later with research_bundle_verify to check that quoted passages still match.", json!({"type":"object","properties":{
+            "name":{"type":"string","description":"Bundle name (letters, digits, '-', '_', '.'); saved as ~/.daizo/bundles/<name>.json"},
+            "steps":{"type":"array","items":{"type":"object","properties":{"tool":{"type":"string"},"arguments":{"type":"object"}},"required":["tool"]},"description":"Tool calls in order, e.g. [{\"tool\":\"cbeta_fetch\",\"arguments\":{\"id\":\"T0262\",\"lb\":\"0009b21\"}}]"},
+            "note":{"type":"string","description":"Free-text note stored with the bundle"},
+            "overwrite":{"type":"boolean","description":"Replace an existing bundle of the same name (default: false)"}
+        },"required":["name","steps"]})),
+        tool("research_bundle_verify", "Replay a research bundle and report, per step, whether the output is unchanged, changed (with the first differing character) or now fails; also lists corpora whose checkout moved since recording. Without a name, lists saved bundles.", json!({"type":"object","properties":{
+            "name":{"type":"string","description":"Bundle name"}
+        }})),
+        tool("annotation_add", "Attach a note (or, without one, a bookmark) to a char range of a text, kept across sessions in ~/.daizo/annotations. The range is counted in the plain text *_fetch returns (CBETA: format=plain, per juan with part), e.g. the startChar/endChar of a fetch slice or a concordance line; the quoted text is stored with the note.", json!({"type":"object","properties":{
+            "source":{"type":"string","enum":["cbeta","tipitaka","gretil","sarit","muktabodha"],"description":"Corpus (default: cbeta)"},
+            "id":{"type":"string","description":"Text id (e.g. T0262)"},
+            "part":{"type":"string","description":"CBETA juan the range is counted in (e.g. '001')"},
+            "startChar":{"type":"number"},
+            "endChar":{"type":"number","description":"End of the range (default: startChar)"},
+            "note":{"type":"string","description":"The note; omit for a bookmark"},
+            "tags":{"type":"array","items":{"type":"string"}}
+        },"required":["id","startChar"]})),
+        tool("annotation_list", "List saved annotations and bookmarks, ordered by text and position, each with a *_fetch suggestion for its range. Filter by source, id, part, an overlapping startChar/endChar range or a tag; without filters lists all.", json!({"type":"object","properties":{
+            "source":{"type":"string"},
+            "id":{"type":"string"},
+            "part":{"type":"string"},
+            "startChar":{"type":"number"},"endChar":{"type":"number"},
+            "tag":{"type":"string"},
+            "limit":{"type":"number","description":"Annotations returned (default: 100)"}
+        }})),
+        tool("annotation_search", "Search saved annotations: case-insensitive match of the query in the note, the quoted text or a tag, optionally within one source or text.", json!({"type":"object","properties":{
+            "query":{"type":"string"},
+            "source":{"type":"string"},
+            "id":{"type":"string"},
+            "tag":{"type":"string"},
+            "limit":{"type":"number","description":"Annotations returned (default: 100)"}
+        },"required":["query"]})),
+        tool("collection_create", "Create a named collection of texts to read together (translations of one sūtra, a root text and its commentaries, the passages of a paper), saved as ~/.daizo/collections/<name>.json. Items name a text and optionally a juan (part) or a section by heading; read them with collection_fetch.", json!({"type":"object","properties":{
+            "name":{"type":"string","description":"Collection name (letters, digits, '-', '_', '.')"},
+            "note":{"type":"string","description":"Free-text note stored with the collection"},
+            "items":{"type":"array","items":{"type":"object","properties":{"source":{"type":"string","description":"cbeta (default), tipitaka, gretil, sarit, muktabodha"},"id":{"type":"string"},"part":{"type":"string"},"section":{"type":"string","description":"Section heading, as *_fetch headQuery"},"note":{"type":"string"}},"required":["id"]},"description":"Initial items, e.g. [{\"id\":\"T0235\"},{\"id\":\"T0262\",\"section\":\"方便品\"}]"},
+            "overwrite":{"type":"boolean","description":"Replace an existing collection of the same name (default: false)"}
+        },"required":["name"]})),
+        tool("collection_add", "Add texts, juans or sections to a collection made with collection_create; items already in it are skipped.", json!({"type":"object","properties":{
+            "name":{"type":"string"},
+            "items":{"type":"array","items":{"type":"object","properties":{"source":{"type":"string"},"id":{"type":"string"},"part":{"type":"string"},"section":{"type":"string"},"note":{"type":"string"}},"required":["id"]}},
+            "source":{"type":"string","description":"Single item: corpus (default: cbeta)"},
+            "id":{"type":"string","description":"Single item: text id"},
+            "part":{"type":"string"},
+            "section":{"type":"string"}
+        },"required":["name"]})),
+        tool("collection_fetch", "Read a collection through the *_fetch tools, item after item, in pages of maxChars: each page continues where the last ended, inside an item or at the next one (_meta.nextCursor, or item + startChar). Without a name, lists the saved collections; with listOnly, lists the items.", json!({"type":"object","properties":{
+            "name":{"type":"string"},
+            "item":{"type":"number","description":"0-based item to start at (default: 0)"},
+            "startChar":{"type":"number","description":"Char offset inside that item"},
+            "maxChars":{"type":"number","description":"Characters per page across items"},
+            "maxTokens":{"type":"number","description":"Estimated tokens per page across items"},
+            "listOnly":{"type":"boolean","description":"List the items instead of fetching them"},
+            "cursor":{"type":"string","description":"_meta.nextCursor from a previous call"}
+        }})),
+        tool("daizo_selftest", "Verify an installation: run known-good fetches and searches against each installed corpus (e.g. CBETA T0001 juan 1, Tipitaka DN 22) and report pass/fail with timings. Corpora that are not installed are skipped, never downloaded.", json!({"type":"object","properties":{
+            "corpora":{"type":"array","items":{"type":"string"},"description":"Limit to these corpora: cbeta, tipitaka, gretil, sarit, muktabodha, sat, jozen (default: all)"},
+            "online":{"type":"boolean","description":"Also check the online sources (SAT, Jodo Shu Zensho); default: false"}
+        }})),
         tool("daizo_resolve", "Resolve a user query (title/alias/ID) to candidate corpus IDs and recommended next tool calls. Use this when you don't know which corpus/ID to use.", json!({"type":"object","properties":{
             "query":{"type":"string","description":"User query (title/alias/ID). Examples: '法華経', 'T0262', 'DN1', 'vajracchedikA'."},
             "sources":{"type":"array","items":{"type":"string","enum":["cbeta","tipitaka","gretil","sarit","muktabodha"]},"description":"Search scope. Default: ['cbeta','tipitaka','gretil','sarit','muktabodha']."},
@@ -286,39 +410,179 @@ fn tools_list() -> Vec<serde_json::Value> {
             "preferSource":{"type":"string","description":"Optional bias: cbeta|tipitaka|gretil|sarit|muktabodha"},
             "minScore":{"type":"number","description":"Filter out candidates below this score (default: 0.1)"}
         },"required":["query"]})),
+        tool("cite", "Ready-to-paste citation of a text or passage with edition details from the index: Taishō style for CBETA (T 262, 9:1c14–62b1), PTS volume and page for the Tipiṭaka (MN 10 (M I 55)), the source edition the file names for GRETIL, SARIT and Muktabodha. Formats: plain, bibtex, csl-json, or all (default: plain); _meta.citation has the parts.", json!({"type":"object","properties":{
+            "source":{"type":"string","enum":["cbeta","tipitaka","gretil","sarit","muktabodha"],"description":"Corpus (default: cbeta)"},
+            "id":{"type":"string","description":"Text id (e.g. T0262, s0201m.mul, sa_azvaghoSa-buddhacarita)"},
+            "part":{"type":"string","description":"CBETA juan to cite (e.g. '001')"},
+            "lb":{"type":"string","description":"CBETA line to cite (e.g. '0001c14')"},
+            "lineNumber":{"type":"number","description":"XML line of the passage (Tipiṭaka: cites the PTS page it falls on)"},
+            "reference":{"type":"string","description":"Canonical reference put before the page (e.g. 'MN 10')"},
+            "format":{"type":"string","enum":["plain","bibtex","csl-json","all"],"description":"Output format (default: plain)"}
+        },"required":["id"]})),
         tool("cbeta_fetch", "Retrieve CBETA text by ID/part. FAST: If Taisho number is known (e.g. T0001, T0262 for Lotus Sutra), use id directly without search. Supports low-cost slices via id+lb (preferred) or id+lineNumber (XML line). TIP: Always pass 'highlight' with search term when fetching context!", json!({"type":"object","properties":{
+            "provenance":{"type":"boolean","description":"Append a provenance footer (corpus, snapshot commit, retrieval date, tool version) and return _meta.provenance; default from DAIZO_PROVENANCE"},
+            "cursor":{"type":"string","description":"_meta.nextCursor from a previous call: continue exactly where that slice ended"},
+            "matchId":{"type":"string","description":"match_id from a *_search result: fetches around that match (sets id, lineNumber and highlight; the line is looked up again, so it follows the text if the file changed)"},
             "id":{"type":"string","description":"Taisho number (e.g. T0001, T0262). Use this directly if known - much faster than query!"},
+            "reference":{"type":"string","description":"Citation by volume, page and column: 'T 2076, 51:196b', 'T51, no. 2076, p. 196b12' or a line head 'T51n2076_p0196b12'. Replaces id and lb with the file and first line of the cited column (_meta.reference)"},
             "query":{"type":"string","description":"Fuzzy title search (slower). Prefer id if Taisho number is known."},
             "part":{"type":"string","description":"Juan/part number (e.g. '001'). Use for long texts."},
-            "lb":{"type":"string","description":"CBETA line break marker n=... (e.g. '0114b27'). More stable than XML lineNumber."},
+            "lb":{"type":"string","description":"CBETA line break marker n=... (e.g. '0114b27'). More stable than XML lineNumber; _meta.structure gives its juan and heading chain"},
             "headIndex":{"type":"number","description":"Extract section by <head> index (0-based)."},
-            "headQuery":{"type":"string","description":"Extract section by <head> substring match (e.g., '方便品')."},
+            "headQuery":{"type":"string","description":"Extract section by title: <cb:mulu> TOC entries first, then <head> substring match (e.g., '方便品')."},
             "includeNotes":{"type":"boolean"},
             "format":{"type":"string","description":"Output format. Use 'plain' for readable plain text (gaiji resolved, teiHeader excluded, line breaks preserved). Default keeps current behavior."},
             "full":{"type":"boolean","description":"Return full text without slicing"},
             "focusHighlight":{"type":"boolean","description":"If highlight is provided and no lb/lineNumber is specified, focus output around the first highlight match (default true)."},
-            "highlight":{"type":"string","description":"Highlight string or regex pattern (used with lineNumber-based context)"},
+            "highlight":{"type":["string","array"],"items":{"type":["string","object"],"properties":{"pattern":{"type":"string"},"regex":{"type":"boolean"},"prefix":{"type":"string"},"suffix":{"type":"string"}},"required":["pattern"]},"description":"Highlight string or regex pattern (used with lineNumber-based context); an array of {pattern, prefix, suffix, regex} entries (or strings) highlights each pattern with its own markers (unset markers follow highlightStyle) and groups _meta.highlightPositions by pattern"},
             "highlightRegex":{"type":"boolean","description":"Interpret highlight as regex (default false)"},
+            "highlightStyle":{"type":"string","enum":["arrows","markdown-bold","html-mark","brackets","none"],"description":"Highlight marker preset: arrows (>>> <<<, default), markdown-bold (**), html-mark (<mark>), brackets (⟦⟧), none (text unchanged; positions in _meta only). highlightPrefix/highlightSuffix override it"},
             "highlightPrefix":{"type":"string","description":"Prefix marker for highlights (default \">>> \")"},
             "highlightSuffix":{"type":"string","description":"Suffix marker for highlights (default \" <<<\")"},
             "headingsLimit":{"type":"number"},
-            "startChar":{"type":"number"},"endChar":{"type":"number"},"maxChars":{"type":"number"},
-            "page":{"type":"number"},"pageSize":{"type":"number"},
-            "lineNumber":{"type":"number","description":"Target XML line number for context extraction (from *_search). Prefer lb when available."},
+            "startChar":{"type":"number"},"endChar":{"type":"number"},"maxChars":{"type":"number"},"maxTokens":{"type":"number","description":"Token budget (estimated, CJK-aware): the slice ends where it is used up; _meta.budget reports what was granted"},
+            "startLine":{"type":"number","description":"1-based line of the extracted text to start at (instead of startChar); _meta.lineMap maps the returned lines to char offsets"},"endLine":{"type":"number","description":"Last line to return, inclusive (instead of endChar)"},
+            "onOverflow":{"type":"string","enum":["truncate","outline"],"description":"When the requested text (juan, section, full, endChar/page) exceeds the budget: truncate (default; continue with _meta.nextCursor) or outline (section titles, the first outlineChars chars of each and a cursor per section)"},
+            "snap":{"type":"string","enum":["none","sentence"],"description":"sentence: move the slice ends (at most 200 chars) onto sentence boundaries (。？！, danda ।/॥, shad, Pāli/Sanskrit full stops) so the text starts and ends with whole sentences; an explicit startChar (and so a cursor) is kept and only the end snaps; continuation cursors follow the snapped end"},
+            "outlineChars":{"type":"number","description":"Preview length per section with onOverflow:\"outline\" (default 200)"},
+            "page":{"type":"number","description":"0-based page"},"pageSize":{"type":"number","description":"Chars per page, or units per page with pageBy"},
+            "pageBy":{"type":"string","enum":["head","juan","paragraph"],"description":"Page by structural unit instead of characters: page/pageSize then count headed sections, juans or paragraphs (pageSize default 1), so pages never cut a sentence or verse; _meta.pageBy gives pageCount, titles and nextPage"},
+            "lineNumber":{"type":"number","description":"Target XML line number for context extraction (from *_search). Prefer lb when available.; _meta.structure gives the juan and heading chain the line falls under (label e.g. '卷第三 > 譬喻品第三')"},
             "contextBefore":{"type":"number","description":"Number of lines before target line (default: 10)"},
             "contextAfter":{"type":"number","description":"Number of lines after target line (default: 100)"},
             "contextLines":{"type":"number","description":"Number of lines before/after target line (deprecated, use contextBefore/contextAfter)"}
         }})),
+        fetch_batch_tool("cbeta", "[{\"id\":\"T0262\",\"part\":\"2\"},{\"id\":\"T0263\",\"part\":\"2\"}]"),
         tool("cbeta_search", "Fast regex search over CBETA; returns _meta.fetchSuggestions (use cbeta_fetch with id+lineNumber+highlight). IMPORTANT: When fetching, always include highlight param with search term!", json!({"type":"object","properties":{
-            "query":{"type":"string","description":"Regular expression pattern to search for"},
+            "query":{"type":["string","array"],"items":{"type":"string"},"description":"Regular expression pattern to search for; an array searches for several patterns in one pass and reports which matched each hit"},
             "maxResults":{"type":"number","description":"Maximum number of files to return (default: 20)"},
-            "maxMatchesPerFile":{"type":"number","description":"Maximum matches per file (default: 5)"}
+            "maxMatchesPerFile":{"type":"number","description":"Maximum matches per file (default: 5)"},
+            "maxParallelism":{"type":"number","description":"Worker threads for this search (default: DAIZO_THREADS or all cores; never more than DAIZO_THREADS)"},
+            "sortBy":{"type":"string","enum":["relevance","matches","id"],"description":"Result order: relevance score (match density, title match, matches in headings, early juan), match count, or file id (default: relevance)"},
+            "mergeWithin":{"type":"number","description":"Join matches on adjacent lines within this many characters into one snippet with highlight ranges, and fold repeated lines (default: 80; 0 = one match per line)"},
+            "textOnly":{"type":"boolean","description":"Drop matches that only hit tags, attribute values or entities (e.g. 'rend'), keeping hits in the text (default: false)"},
+            "scope":{"type":["string","array"],"items":{"type":"string"},"description":"Search only these texts: an id or a list of ids (e.g. from title search) instead of the whole corpus"},
+            "canon":{"type":["string","array"],"items":{"type":"string"},"description":"Only texts of these canons: a code or list of codes such as T (Taishō), X (Xuzangjing), J (Jiaxing)"},
+            "nnumRange":{"type":"string","description":"Only texts whose number falls in this inclusive range, e.g. '220-262' or '251' (suffix letters ignored: 0220a counts as 220)"},
+            "juanCountMin":{"type":"number","description":"Only texts with at least this many juans"},
+            "juanCountMax":{"type":"number","description":"Only texts with at most this many juans"},
+            "variants":{"type":"boolean","description":"Match traditional/simplified/shinjitai variant characters for literal queries, e.g. 观经 finds 觀經 (default: true)"},
+            "notesOnly":{"type":"boolean","description":"Search only inside <note> content (editorial comments, variant notes); matches report the note text and section 'note:<type>'; regex mode only (default: false)"},
+            "queryMode":{"type":"string","enum":["regex","boolean"],"description":"'boolean' parses AND/OR/NOT, (…), \"phrases\" and 'a NEAR/20 b' (default: regex)"},
+            "maxCandidates":{"type":"number","description":"Boolean mode: files prefiltered by the positive terms before evaluation (default: 1000)"}
+        },"required":["query"]})),
+        tool("cbeta_term_timeline", "Usage-over-time profile of a term in CBETA: counts matches per work and buckets them by the translation period (dynasty) of each work, derived from index bylines. Returns a compact table; _meta.buckets has the rows.", json!({"type":"object","properties":{
+            "query":{"type":"string","description":"Term or regular expression to profile"},
+            "bucketBy":{"type":"string","enum":["dynasty","century"],"description":"Group by translation period label (default) or by century of the period midpoint"},
+            "maxResults":{"type":"number","description":"Maximum number of works to scan (default: 500)"},
+            "maxMatchesPerFile":{"type":"number","description":"Cap on counted matches per work (default: 200)"},
+            "maxParallelism":{"type":"number","description":"Worker threads for this search (default: DAIZO_THREADS or all cores; never more than DAIZO_THREADS)"},
+            "includeWorkIds":{"type":"boolean","description":"Include per-bucket work ids in _meta (default: false)"}
+        },"required":["query"]})),
+        tool("corpus_stats", "Word/character frequency, bigram/trigram counts and hapax legomena for a text (id), a CBETA juan (id+part), or a whole canon division (division). Uses the same plain-text extraction as the fetch tools.", json!({"type":"object","properties":{
+            "source":{"type":"string","enum":["cbeta","tipitaka","gretil","sarit","muktabodha"],"description":"Corpus (default: cbeta)"},
+            "id":{"type":"string","description":"Work id (e.g. T0262, s0101m.mul, file stem)"},
+            "part":{"type":"string","description":"CBETA juan number (e.g. '001'); requires id"},
+            "division":{"type":"string","description":"Whole division instead of id: CBETA canon/volume ('T', 'T08') or a file-stem prefix for other corpora"},
+            "top":{"type":"number","description":"Entries per top-N list (default: 20)"},
+            "hapaxLimit":{"type":"number","description":"Maximum hapax legomena listed (default: 50)"},
+            "includeNotes":{"type":"boolean","description":"CBETA: include notes in the counted text (default: false)"}
+        }})),
+        tool("daizo_similar_works", "Works most similar to a given work by vocabulary (cosine over tf-idf weighted term profiles: Han character bigrams, words elsewhere). Finds alternate translations, related treatises and commentaries within one corpus. Profiles are built from the index on first use per corpus and cached; a large corpus takes a while the first time.", json!({"type":"object","properties":{
+            "source":{"type":"string","enum":["cbeta","tipitaka","gretil","sarit","muktabodha"],"description":"Corpus (default: cbeta)"},
+            "id":{"type":"string","description":"Work id (e.g. T0251, s0101m.mul, file stem)"},
+            "limit":{"type":"number","description":"Similar works returned (default: 10)"}
+        },"required":["id"]})),
+        tool("semantic_search", "Passages similar to a query, from an embedding index built beforehand with `daizo-cli embed-index --source <corpus>`. Similarity depends on the index's backend (_meta.similarity): lexical (the default; shared characters and words, no model) or semantic (an http embedding model, closeness in meaning). Use it when the wording is unknown or only partly known; results point into the text as *_fetch {id, lineNumber} (see _meta.fetchSuggestions). Fall back to *_search for exact wording.", json!({"type":"object","properties":{
+            "query":{"type":"string","description":"What the passage is about, in any language the embedding model reads"},
+            "source":{"type":"string","enum":["cbeta","tipitaka","gretil","sarit","muktabodha"],"description":"Corpus (default: cbeta)"},
+            "limit":{"type":"number","description":"Passages returned (default: 10)"},
+            "nprobe":{"type":"number","description":"Index lists scanned; higher is slower and more exact (default: 8)"}
         },"required":["query"]})),
-        tool("cbeta_title_search", "Title-based search in CBETA corpus. Note: If Taisho number is already known (e.g. T0262), skip search and use cbeta_fetch directly with id!", json!({"type":"object","properties":{"query":{"type":"string","description":"Title to search. If you already know Taisho number, use cbeta_fetch with id instead."},"limit":{"type":"number"}},"required":["query"]})),
+        tool("text_diff", "Character-level diff of two texts: two translations or editions (T0235 vs T0236), a root text and its commentary (s0101m.mul vs s0101a.att), or two juans. The texts are extracted as *_fetch format=plain does, aligned on runs they share uniquely (_meta.anchors) and compared character by character between them; changes are shown as [-a-]{+b+}. _meta.hunks has every change with char offsets into both texts (usable as *_fetch startChar).", json!({"type":"object","properties":{
+            "source":{"type":"string","enum":["cbeta","tipitaka","gretil","sarit","muktabodha"],"description":"Corpus of text a (default: cbeta)"},
+            "a":{"type":"string","description":"Id of the first text (e.g. T0235)"},
+            "b":{"type":"string","description":"Id of the second text (e.g. T0236)"},
+            "sourceB":{"type":"string","enum":["cbeta","tipitaka","gretil","sarit","muktabodha"],"description":"Corpus of text b (default: source)"},
+            "partA":{"type":"string","description":"CBETA juan of text a (e.g. '001')"},
+            "partB":{"type":"string","description":"CBETA juan of text b"},
+            "ignorePunctuation":{"type":"boolean","description":"Skip punctuation and whitespace (default: true)"},
+            "foldVariants":{"type":"boolean","description":"Treat variant characters (經/経) as equal (default: true)"},
+            "context":{"type":"number","description":"Unchanged characters shown around each change (default: 20)"},
+            "maxHunks":{"type":"number","description":"Changes listed in _meta.hunks (default: 200)"},
+            "maxChars":{"type":"number","description":"Length cap of the rendered diff"}
+        },"required":["a","b"]})),
+        tool("cbeta_collate", "A CBETA text as one witness reads it: every apparatus entry (<app>) is resolved to the reading (<rdg>) of the chosen edition siglum, e.g. 宋, 元, 明, 宮 (【宋】 works too), or to the base text (<lem>) where that edition agrees with it; the result is extracted as cbeta_fetch format=plain does. _meta.readings lists the places where the witness departs from the base text (lem, reading, lb). Without witness, lists the sigla the text's apparatus names.", json!({"type":"object","properties":{
+            "id":{"type":"string","description":"CBETA id (e.g. T0001)"},
+            "witness":{"type":"string","description":"Edition siglum (e.g. 宋 or 【宋】); omit to list the witnesses"},
+            "part":{"type":"string","description":"Juan to collate (e.g. '001')"},
+            "includeNotes":{"type":"boolean","description":"Keep <note> content in the text (default: false)"},
+            "maxReadings":{"type":"number","description":"Readings listed in _meta.readings (default: 200)"},
+            "cursor":{"type":"string","description":"_meta.nextCursor from a previous call: continue exactly where that slice ended"},
+            "startChar":{"type":"number"},"endChar":{"type":"number"},"maxChars":{"type":"number"},"maxTokens":{"type":"number","description":"Token budget (estimated, CJK-aware): the slice ends where it is used up; _meta.budget reports what was granted"},
+            "startLine":{"type":"number","description":"1-based line of the extracted text to start at (instead of startChar); _meta.lineMap maps the returned lines to char offsets"},"endLine":{"type":"number","description":"Last line to return, inclusive (instead of endChar)"}
+        },"required":["id"]})),
+        tool("cbeta_colophons", "The records around a CBETA text rather than in it: trailers (<trailer>, the closing title, often with a 題記 or 跋), document numbers (<cb:docNumber>), elements typed as colophons, and each division of the back matter (<back>, e.g. donation and engraving records; the apparatus is left to cbeta_notes). Each comes with its XML line, lb, juan, element, type and head, for historians of transmission and printing.", json!({"type":"object","properties":{
+            "id":{"type":"string","description":"CBETA id (e.g. T0001)"},
+            "part":{"type":"string","description":"Only the records of this juan (e.g. '001'); back matter belongs to the last juan"},
+            "kind":{"type":"string","enum":["trailer","docNumber","colophon","back"],"description":"Only records of this kind"}
+        },"required":["id"]})),
+        tool("cbeta_notes", "The notes (<note>) of a CBETA text or juan, listed apart from the text: each with the line it annotates (anchorLine: the <anchor> a back-matter footnote points to, else the note itself), the lb there, its n and type, and a category: inline (small-print glosses in the running text), apparatus (Taishō and CBETA textual footnotes: variant readings, emendations) or editorial (other editors' notes). Study the annotations on their own instead of inlining them with includeNotes.", json!({"type":"object","properties":{
+            "id":{"type":"string","description":"CBETA id (e.g. T0001)"},
+            "part":{"type":"string","description":"Only the notes of this juan (e.g. '001')"},
+            "category":{"type":"string","enum":["inline","apparatus","editorial"],"description":"Only notes of this category"},
+            "maxNotes":{"type":"number","description":"Notes returned (default: 200)"},
+            "offset":{"type":"number","description":"Notes to skip; _meta.nextOffset continues a truncated list"}
+        },"required":["id"]})),
+        tool("tei_notes", "cbeta_notes for any local TEI corpus: the <note> elements of a file with their anchor line numbers, lb, type and category (inline, apparatus, editorial).", json!({"type":"object","properties":{
+            "source":{"type":"string","enum":["cbeta","tipitaka","gretil","sarit","muktabodha"],"description":"Corpus (default: cbeta)"},
+            "id":{"type":"string","description":"Text id in that corpus"},
+            "part":{"type":"string","description":"Only the notes of this juan (CBETA)"},
+            "category":{"type":"string","enum":["inline","apparatus","editorial"],"description":"Only notes of this category"},
+            "maxNotes":{"type":"number","description":"Notes returned (default: 200)"},
+            "offset":{"type":"number","description":"Notes to skip; _meta.nextOffset continues a truncated list"}
+        },"required":["id"]})),
+        tool("entities", "The people, places and organizations a text names: its <persName>, <placeName>, <geogName>, <orgName> and typed <name> elements (cb: ones included; the teiHeader and variant readings are not read), grouped by authority key (key or ref) where one is given, else by normalized name. Each comes with its mention count, the forms it is written in, and its first occurrence (XML line, lb), most mentioned first, for quick prosopographical surveys.", json!({"type":"object","properties":{
+            "source":{"type":"string","enum":["cbeta","tipitaka","gretil","sarit","muktabodha"],"description":"Corpus (default: cbeta)"},
+            "id":{"type":"string","description":"Text id in that corpus (e.g. T2059)"},
+            "part":{"type":"string","description":"Only this juan (CBETA, e.g. '001')"},
+            "kind":{"type":"string","enum":["person","place","org","other"],"description":"Only entities of this kind"},
+            "limit":{"type":"number","description":"Entities returned (default: 100)"},
+            "authority":{"type":"boolean","description":"Look the persons and places up in the DILA Authority Database (by DILA id where the text gives one, else by name; cached) and add each match — id, name, altNames, born/died, dynasty, coordinates, url — as entities[].authority (default: false; needs online access)"},
+            "maxLookups":{"type":"number","description":"Authority lookups per call, most mentioned entities first (default: 20)"}
+        },"required":["id"]})),
+        tool("find_quotations", "Where a passage is quoted across CBETA: near-duplicate regions found through winnowed 5-character shingle fingerprints of every text (punctuation, notes and variant forms ignored), scored by containment (share of the passage's shingles found; 1.0 = verbatim). Traces sutra lines into commentaries and anthologies. Passages of 20+ Han characters are always found where they occur verbatim; shorter ones may be missed. The fingerprint index is built on first use after each index build (a few minutes for all of CBETA) and cached.", json!({"type":"object","properties":{
+            "text":{"type":"string","description":"The passage (Chinese; punctuation is ignored)"},
+            "limit":{"type":"number","description":"Regions returned (default: 10)"},
+            "minScore":{"type":"number","description":"Least containment, 0-1 (default: 0.5)"},
+            "excludeId":{"type":"string","description":"Leave out this work (e.g. the text the passage comes from: T0251)"}
+        },"required":["text"]})),
+        tool("reading_list", "Works of a collection in canonical order with ids, titles and sizes, for reading a corpus systematically. Collections: Taishō divisions (阿含部 / Āgama, 般若部, 律部, ...), CBETA canons and volumes (T, X, T08), Pāli collections (DN, MN, SN, AN, KN, Vin, Abh or their names: root texts), or a file-stem prefix in any corpus. Pass the last id read as `after` to continue; _meta.next is the value for the following page.", json!({"type":"object","properties":{
+            "source":{"type":"string","enum":["cbeta","tipitaka","gretil","sarit","muktabodha"],"description":"Corpus (default: cbeta)"},
+            "collection":{"type":"string","description":"Collection name (default: the whole corpus)"},
+            "after":{"type":"string","description":"Id of the last work read; the list continues with the work after it"},
+            "limit":{"type":"number","description":"Works returned (default: 20)"}
+        }})),
+        tool("cbeta_concordance", "Keyword-in-context (KWIC) concordance of a term across CBETA: one line per occurrence with left/right context in plain text, sortable by left or right context. format=csv|json for export; _meta.lines has the rows (charOffset works as cbeta_fetch startChar).", json!({"type":"object","properties":{
+            "provenance":{"type":"boolean","description":"Append a provenance footer (corpus, snapshot commit, retrieval date, tool version) and return _meta.provenance; default from DAIZO_PROVENANCE"},
+            "query":{"type":"string","description":"Term or regular expression"},
+            "window":{"type":"number","description":"Context characters on each side (default: 20)"},
+            "sortBy":{"type":"string","enum":["position","right","left"],"description":"Line order (default: position)"},
+            "format":{"type":"string","enum":["text","csv","json"],"description":"Rendering of the text content (default: text)"},
+            "maxResults":{"type":"number","description":"Maximum number of files to scan (default: 50)"},
+            "maxMatchesPerFile":{"type":"number","description":"Maximum lines per file (default: 50)"},
+            "maxParallelism":{"type":"number","description":"Worker threads for this search (default: DAIZO_THREADS or all cores; never more than DAIZO_THREADS)"},
+            "maxLines":{"type":"number","description":"Maximum lines returned after sorting (default: 200)"}
+        },"required":["query"]})),
+        tool("cbeta_title_search", "Title-based search in CBETA corpus. Note: If Taisho number is already known (e.g. T0262), skip search and use cbeta_fetch directly with id!", json!({"type":"object","properties":{"query":{"type":"string","description":"Title to search. If you already know Taisho number, use cbeta_fetch with id instead."},"limit":{"type":"number"},
+            "canon":{"type":["string","array"],"items":{"type":"string"},"description":"Only texts of these canons, e.g. T or [\"T\",\"X\"]"},
+            "nnumRange":{"type":"string","description":"Only texts numbered in this inclusive range, e.g. '220-262'"},
+            "juanCountMin":{"type":"number"},"juanCountMax":{"type":"number"}},"required":["query"]})),
         tool("cbeta_pipeline", "CBETA summarize/context pipeline; set autoFetch=false for summary-only (see cbeta_search _meta.pipelineHint)", json!({"type":"object","properties":{
             "query":{"type":"string"},
             "maxResults":{"type":"number"},
             "maxMatchesPerFile":{"type":"number"},
+            "maxParallelism":{"type":"number","description":"Worker threads for this search (default: DAIZO_THREADS or all cores; never more than DAIZO_THREADS)"},
             "contextBefore":{"type":"number"},
             "contextAfter":{"type":"number"},
             "autoFetch":{"type":"boolean"},
@@ -329,16 +593,39 @@ fn tools_list() -> Vec<serde_json::Value> {
             "snippetSuffix":{"type":"string","description":"Suffix for highlight snippets in pipeline (default '')"},
             "highlight":{"type":"string","description":"Highlight string or regex pattern inside contexts"},
             "highlightRegex":{"type":"boolean","description":"Interpret highlight as regex (default false)"},
+            "highlightStyle":{"type":"string","enum":["arrows","markdown-bold","html-mark","brackets","none"],"description":"Highlight marker preset: arrows (>>> <<<, default), markdown-bold (**), html-mark (<mark>), brackets (⟦⟧), none (text unchanged; positions in _meta only). highlightPrefix/highlightSuffix override it"},
             "highlightPrefix":{"type":"string","description":"Prefix marker for highlights (default from env or \">>> \")"},
             "highlightSuffix":{"type":"string","description":"Suffix marker for highlights (default from env or \" <<<\")"},
             "full":{"type":"boolean"},
             "includeNotes":{"type":"boolean"}
         },"required":["query"]})),
-        tool("sat_detail", "Fetch SAT detail by useid", json!({"type":"object","properties":{"useid":{"type":"string"},"key":{"type":"string"},"startChar":{"type":"number"},"maxChars":{"type":"number"}},"required":["useid"]})),
+        tool("sat_detail", "Fetch SAT detail by useid", json!({"type":"object","properties":{"provenance":{"type":"boolean","description":"Append a provenance footer (corpus, snapshot commit, retrieval date, tool version) and return _meta.provenance; default from DAIZO_PROVENANCE"},"cursor":{"type":"string","description":"_meta.nextCursor from a previous call: continue exactly where that slice ended"},"useid":{"type":"string"},"key":{"type":"string"},"startChar":{"type":"number"},"startLine":{"type":"number","description":"1-based line of the extracted text to start at (instead of startChar); _meta.lineMap maps the returned lines to char offsets"},"maxChars":{"type":"number"}},"required":["useid"]})),
+        tool("sat_to_cbeta", "Convert between SAT line ids and CBETA (both follow Taishō numbering). With useid (a SAT startid such as 0262_,09,0001c14): the CBETA file, lb and juan it falls on in the local copy, with a cbeta_fetch call that reads it without network access. With id (+ lb or part): the SAT useid of that CBETA line or juan, with sat_fetch / sat_fetch_range calls.", json!({"type":"object","properties":{
+            "useid":{"type":"string","description":"SAT line id (startid/endid)"},
+            "id":{"type":"string","description":"CBETA id (T0262 or T09n0262)"},
+            "lb":{"type":"string","description":"CBETA line (e.g. 0001c14)"},
+            "part":{"type":"string","description":"CBETA juan (e.g. '001'); gives the juan's first and last line"}
+        }})),
+        tool("sat_fetch_range", "A whole SAT fascicle (or any startid..endid range) assembled from consecutive detail pages: each page is read from the last line of the one before, overlapping lines are dropped, and reading stops at endid. Pass startid/endid from sat_search or sat_pipeline (_meta.chosen). _meta.pages gives each page's first and last line and where it starts in the text.", json!({"type":"object","properties":{
+            "provenance":{"type":"boolean","description":"Append a provenance footer (corpus, snapshot commit, retrieval date, tool version) and return _meta.provenance; default from DAIZO_PROVENANCE"},
+            "cursor":{"type":"string","description":"_meta.nextCursor from a previous call: continue exactly where that slice ended"},
+            "useid":{"type":"string","description":"First line (startid, e.g. 0262_,09,0001c14)"},
+            "endid":{"type":"string","description":"Last line (endid); default: read until the text number changes"},
+            "maxPages":{"type":"number","description":"Detail pages read at most (default: 30)"},
+            "startChar":{"type":"number"},
+            "startLine":{"type":"number","description":"1-based line of the extracted text to start at (instead of startChar); _meta.lineMap maps the returned lines to char offsets"},"endLine":{"type":"number","description":"Last line to return, inclusive (instead of endChar)"},
+            "maxChars":{"type":"number"},"maxTokens":{"type":"number","description":"Token budget (estimated, CJK-aware): the slice ends where it is used up; _meta.budget reports what was granted"},
+            "onOverflow":{"type":"string","enum":["truncate","outline"],"description":"When the requested text (juan, section, full, endChar/page) exceeds the budget: truncate (default; continue with _meta.nextCursor) or outline (section titles, the first outlineChars chars of each and a cursor per section)"},
+            "snap":{"type":"string","enum":["none","sentence"],"description":"sentence: move the slice ends (at most 200 chars) onto sentence boundaries (。？！, danda ।/॥, shad, Pāli/Sanskrit full stops) so the text starts and ends with whole sentences; an explicit startChar (and so a cursor) is kept and only the end snaps; continuation cursors follow the snapped end"},
+            "outlineChars":{"type":"number","description":"Preview length per section with onOverflow:\"outline\" (default 200)"}
+        },"required":["useid"]})),
         tool("sat_fetch", "Fetch SAT page (prefer useid to detail URL)", json!({"type":"object","properties":{
+            "provenance":{"type":"boolean","description":"Append a provenance footer (corpus, snapshot commit, retrieval date, tool version) and return _meta.provenance; default from DAIZO_PROVENANCE"},
+            "cursor":{"type":"string","description":"_meta.nextCursor from a previous call: continue exactly where that slice ended"},
             "url":{"type":"string"},
             "useid":{"type":"string"},
             "startChar":{"type":"number"},
+            "startLine":{"type":"number","description":"1-based line of the extracted text to start at (instead of startChar); _meta.lineMap maps the returned lines to char offsets"},
             "maxChars":{"type":"number"}
         }})),
         tool("sat_pipeline", "Search wrap7, pick best title, then fetch detail", json!({"type":"object","properties":{
@@ -349,7 +636,9 @@ fn tools_list() -> Vec<serde_json::Value> {
             "fields":{"type":"string"},
             "fq":{"type":"array","items":{"type":"string"}},
             "startChar":{"type":"number"},
-            "maxChars":{"type":"number"}
+            "startLine":{"type":"number","description":"1-based line of the extracted text to start at (instead of startChar); _meta.lineMap maps the returned lines to char offsets"},
+            "maxChars":{"type":"number"},
+            "candidates":{"type":"number","description":"Documents fetched in parallel (best pick first, then wrap7 order); the first whose text contains the query is returned (default: 3, max: 10)"}
         },"required":["query"]})),
 	        tool("sat_search", "Search SAT wrap7.php", json!({"type":"object","properties":{
 	            "query":{"type":"string"},
@@ -368,8 +657,11 @@ fn tools_list() -> Vec<serde_json::Value> {
 	            "maxSnippetChars":{"type":"number","description":"Max snippet length in characters. Default: DAIZO_MCP_SNIPPET_LEN or 120."}
 	        },"required":["query"]})),
 	        tool("jozen_fetch", "Fetch Jodo Shu Zensho detail page by lineno (online). Returns page text with line IDs.", json!({"type":"object","properties":{
+            "provenance":{"type":"boolean","description":"Append a provenance footer (corpus, snapshot commit, retrieval date, tool version) and return _meta.provenance; default from DAIZO_PROVENANCE"},
+            "cursor":{"type":"string","description":"_meta.nextCursor from a previous call: continue exactly where that slice ended"},
 	            "lineno":{"type":"string","description":"Line/page id (e.g., 'J01_0200B19' or 'J01_0200')"},
 	            "startChar":{"type":"number"},
+	            "startLine":{"type":"number","description":"1-based line of the extracted text to start at (instead of startChar); _meta.lineMap maps the returned lines to char offsets"},
 	            "maxChars":{"type":"number"}
 	        },"required":["lineno"]})),
 	        tool("tibetan_search", "Full-text search over Tibetan corpora (online). Use this when you want Tibetan full-text search without downloading corpora. Sources: adarshah, buda.", json!({"type":"object","properties":{
@@ -380,62 +672,122 @@ fn tools_list() -> Vec<serde_json::Value> {
             "maxSnippetChars":{"type":"number","description":"Max snippet length in characters (default: 240). Use 0 to disable truncation."},
             "wildcard":{"type":"boolean","description":"Adarshah-only: wildcard search (default false)."}
         },"required":["query"]})),
-        tool("tipitaka_fetch", "Retrieve Tipitaka text. FAST: Use Nikāya codes directly (DN, MN, SN, AN, KN) without search. Examples: DN1, MN1, SN1, AN1. Or use file stems like s0101m.mul.", json!({"type":"object","properties":{
+        tool("tipitaka_fetch", "Retrieve Tipitaka text. FAST: Use Nikāya codes directly (DN, MN, SN, AN, KN) without search. Examples: DN1, MN1, SN1, AN1. reference fetches a single sutta or verse by its citation (SN 56.11, Dhp 183). Or use file stems like s0101m.mul. Works split over numbered files (s0404m1.mul0, s0404m1.mul1, ...) continue across files via _meta.nextCursor.", json!({"type":"object","properties":{
+            "provenance":{"type":"boolean","description":"Append a provenance footer (corpus, snapshot commit, retrieval date, tool version) and return _meta.provenance; default from DAIZO_PROVENANCE"},
+            "cursor":{"type":"string","description":"_meta.nextCursor from a previous call: continue exactly where that slice ended"},
+            "matchId":{"type":"string","description":"match_id from a *_search result: fetches around that match (sets id, lineNumber and highlight; the line is looked up again, so it follows the text if the file changed)"},
             "id":{"type":"string","description":"Nikāya code (DN, MN, SN, AN, KN) with optional number (e.g., DN1, MN1) or file stem (e.g., s0101m.mul). Use directly for fast access!"},
+            "reference":{"type":"string","description":"Citation of a sutta or verse: DN 2, MN 10, SN 56.11, AN 4.10, Ud 1.1, Iti 1, Snp 1.8, Dhp 183, Thag 1. Fetches just that section (its lines; _meta.reference reports them) and replaces id"},
             "query":{"type":"string","description":"Fuzzy title search (slower). Prefer id if Nikāya code is known."},
             "headIndex":{"type":"number"},
             "headQuery":{"type":"string"},
             "headingsLimit":{"type":"number"},
-            "highlight":{"type":"string","description":"Highlight string or regex pattern (used with lineNumber-based context)"},
+            "full":{"type":"boolean","description":"Return full text without slicing"},
+            "highlight":{"type":["string","array"],"items":{"type":["string","object"],"properties":{"pattern":{"type":"string"},"regex":{"type":"boolean"},"prefix":{"type":"string"},"suffix":{"type":"string"}},"required":["pattern"]},"description":"Highlight string or regex pattern (used with lineNumber-based context); an array of {pattern, prefix, suffix, regex} entries (or strings) highlights each pattern with its own markers (unset markers follow highlightStyle) and groups _meta.highlightPositions by pattern"},
             "highlightRegex":{"type":"boolean","description":"Interpret highlight as regex (default false)"},
+            "highlightStyle":{"type":"string","enum":["arrows","markdown-bold","html-mark","brackets","none"],"description":"Highlight marker preset: arrows (>>> <<<, default), markdown-bold (**), html-mark (<mark>), brackets (⟦⟧), none (text unchanged; positions in _meta only). highlightPrefix/highlightSuffix override it"},
             "highlightPrefix":{"type":"string","description":"Prefix marker for highlights (default from env or '>>> ')"},
             "highlightSuffix":{"type":"string","description":"Suffix marker for highlights (default from env or ' <<<')"},
             "startChar":{"type":"number"},
+            "startLine":{"type":"number","description":"1-based line of the extracted text to start at (instead of startChar); _meta.lineMap maps the returned lines to char offsets"},"endLine":{"type":"number","description":"Last line to return, inclusive (instead of endChar)"},
             "endChar":{"type":"number"},
-            "maxChars":{"type":"number"},
-            "page":{"type":"number"},
-            "pageSize":{"type":"number"},
-            "lineNumber":{"type":"number","description":"Target line number for context extraction"},
+            "maxChars":{"type":"number"},"maxTokens":{"type":"number","description":"Token budget (estimated, CJK-aware): the slice ends where it is used up; _meta.budget reports what was granted"},
+            "onOverflow":{"type":"string","enum":["truncate","outline"],"description":"When the requested text (juan, section, full, endChar/page) exceeds the budget: truncate (default; continue with _meta.nextCursor) or outline (section titles, the first outlineChars chars of each and a cursor per section)"},
+            "snap":{"type":"string","enum":["none","sentence"],"description":"sentence: move the slice ends (at most 200 chars) onto sentence boundaries (。？！, danda ।/॥, shad, Pāli/Sanskrit full stops) so the text starts and ends with whole sentences; an explicit startChar (and so a cursor) is kept and only the end snaps; continuation cursors follow the snapped end"},
+            "outlineChars":{"type":"number","description":"Preview length per section with onOverflow:\"outline\" (default 200)"},
+            "page":{"type":"number","description":"0-based page"},"pageSize":{"type":"number","description":"Chars per page, or units per page with pageBy"},
+            "pageBy":{"type":"string","enum":["head","juan","paragraph"],"description":"Page by structural unit instead of characters: page/pageSize then count headed sections, juans or paragraphs (pageSize default 1), so pages never cut a sentence or verse; _meta.pageBy gives pageCount, titles and nextPage"},
+            "lineNumber":{"type":"number","description":"Target line number for context extraction; _meta.structure gives the juan and heading chain the line falls under (label e.g. '卷第三 > 譬喻品第三')"},
             "contextBefore":{"type":"number","description":"Number of lines before target line (default: 10)"},
             "contextAfter":{"type":"number","description":"Number of lines after target line (default: 100)"},
             "contextLines":{"type":"number","description":"Number of lines before/after target line (deprecated, use contextBefore/contextAfter)"}
         }})),
+        fetch_batch_tool("tipitaka", "[{\"reference\":\"SN 56.11\"},{\"id\":\"s0101m.mul\",\"lineNumber\":120}]"),
+        tool("tipitaka_concordance", "Keyword-in-context (KWIC) concordance of a term across the Tipitaka (romanized Pāli), sortable by left or right context; format=csv|json for export. Same parameters as cbeta_concordance.", json!({"type":"object","properties":{
+            "provenance":{"type":"boolean","description":"Append a provenance footer (corpus, snapshot commit, retrieval date, tool version) and return _meta.provenance; default from DAIZO_PROVENANCE"},
+            "query":{"type":"string","description":"Term or regular expression"},
+            "window":{"type":"number","description":"Context characters on each side (default: 40)"},
+            "sortBy":{"type":"string","enum":["position","right","left"],"description":"Line order (default: position)"},
+            "format":{"type":"string","enum":["text","csv","json"],"description":"Rendering of the text content (default: text)"},
+            "maxResults":{"type":"number","description":"Maximum number of files to scan (default: 50)"},
+            "maxMatchesPerFile":{"type":"number","description":"Maximum lines per file (default: 50)"},
+            "maxParallelism":{"type":"number","description":"Worker threads for this search (default: DAIZO_THREADS or all cores; never more than DAIZO_THREADS)"},
+            "maxLines":{"type":"number","description":"Maximum lines returned after sorting (default: 200)"}
+        },"required":["query"]})),
         tool("tipitaka_search", "Fast regex search over Tipitaka; returns _meta.fetchSuggestions (use tipitaka_fetch with id+lineNumber+highlight). Always include highlight param when fetching!", json!({"type":"object","properties":{
-            "query":{"type":"string","description":"Regular expression pattern to search for"},
+            "query":{"type":["string","array"],"items":{"type":"string"},"description":"Regular expression pattern to search for; an array searches for several patterns in one pass and reports which matched each hit"},
             "maxResults":{"type":"number","description":"Maximum number of files to return (default: 20)"},
-            "maxMatchesPerFile":{"type":"number","description":"Maximum matches per file (default: 5)"}
+            "maxMatchesPerFile":{"type":"number","description":"Maximum matches per file (default: 5)"},
+            "maxParallelism":{"type":"number","description":"Worker threads for this search (default: DAIZO_THREADS or all cores; never more than DAIZO_THREADS)"},
+            "sortBy":{"type":"string","enum":["relevance","matches","id"],"description":"Result order: relevance score (match density, title match, matches in headings, early juan), match count, or file id (default: relevance)"},
+            "mergeWithin":{"type":"number","description":"Join matches on adjacent lines within this many characters into one snippet with highlight ranges, and fold repeated lines (default: 80; 0 = one match per line)"},
+            "textOnly":{"type":"boolean","description":"Drop matches that only hit tags, attribute values or entities (e.g. 'rend'), keeping hits in the text (default: false)"},
+            "scope":{"type":["string","array"],"items":{"type":"string"},"description":"Search only these texts: an id or a list of ids (e.g. from title search) instead of the whole corpus"},
+            "basket":{"type":["string","array"],"items":{"type":"string","enum":["vinaya","sutta","abhidhamma"]},"description":"Only texts of these piṭakas"},
+            "nikaya":{"type":["string","array"],"items":{"type":"string"},"description":"Only texts of these nikāyas: a code (DN, MN, SN, AN, KN) or part of the name (e.g. 'Dīgha')"},
+            "edition":{"type":["string","array"],"items":{"type":"string","enum":["mul","att","tik"]},"description":"Only these layers: mul (root text), att (aṭṭhakathā), tik (ṭīkā); e.g. 'mul' leaves out the commentaries"},
+            "foldDiacritics":{"type":"boolean","description":"Ignore Pāli diacritics: 'panna' matches 'paññā'. Matches are reported with the original text (default: false)"},
+            "queryMode":{"type":"string","enum":["regex","boolean"],"description":"'boolean' parses AND/OR/NOT, (…), \"phrases\" and 'a NEAR/20 b' (default: regex)"},
+            "maxCandidates":{"type":"number","description":"Boolean mode: files prefiltered by the positive terms before evaluation (default: 1000)"}
         },"required":["query"]})),
-        tool("tipitaka_title_search", "Title-based search in Tipitaka corpus. Note: If Nikāya code is known (DN, MN, SN, AN, KN), skip search and use tipitaka_fetch directly with id!", json!({"type":"object","properties":{"query":{"type":"string","description":"Title to search. If you know Nikāya code, use tipitaka_fetch with id instead."},"limit":{"type":"number"}},"required":["query"]})),
+        tool("tipitaka_title_search", "Title-based search in Tipitaka corpus. Note: If Nikāya code is known (DN, MN, SN, AN, KN), skip search and use tipitaka_fetch directly with id!", json!({"type":"object","properties":{"query":{"type":"string","description":"Title to search. If you know Nikāya code, use tipitaka_fetch with id instead."},"limit":{"type":"number"},
+            "basket":{"type":["string","array"],"items":{"type":"string","enum":["vinaya","sutta","abhidhamma"]}},
+            "nikaya":{"type":["string","array"],"items":{"type":"string"},"description":"Nikāya code (DN, MN, SN, AN, KN) or part of its name"},
+            "edition":{"type":["string","array"],"items":{"type":"string","enum":["mul","att","tik"]},"description":"mul (root text), att (aṭṭhakathā) or tik (ṭīkā)"}},"required":["query"]})),
         // GRETIL (Sanskrit TEI)
-        tool("gretil_title_search", "Title-based search in GRETIL corpus. Note: If text name is known, skip search and use gretil_fetch directly with id!", json!({"type":"object","properties":{"query":{"type":"string","description":"Title to search. If you know the file stem (e.g., 'saddharmapuNDarIka'), use gretil_fetch with id instead."},"limit":{"type":"number"}},"required":["query"]})),
+        tool("gretil_title_search", "Title-based search in GRETIL corpus. Note: If text name is known, skip search and use gretil_fetch directly with id!", json!({"type":"object","properties":{"query":{"type":"string","description":"Title to search (IAST, Harvard-Kyoto, SLP1 or Devanagari). If you know the file stem (e.g., 'saddharmapuNDarIka'), use gretil_fetch with id instead."},"limit":{"type":"number"},"transliterate":{"type":"boolean","description":"Also match the query's spellings in the other transliteration schemes (default: true)"},
+            "author":{"type":["string","array"],"items":{"type":"string"},"description":"Only works whose author matches (part of the name, diacritics ignored)"},
+            "genre":{"type":["string","array"],"items":{"type":"string"},"description":"Only works whose genre, keywords or classification match, e.g. 'kavya', 'epic'"},
+            "century":{"type":["string","number"],"description":"Only works composed in this century or range, from the header's creation date: 5, '4-6', -2 for BCE; undated works are left out"}},"required":["query"]})),
         tool("gretil_search", "Fast regex search over GRETIL; returns _meta.fetchSuggestions (use gretil_fetch with id+lineNumber+highlight). Always include highlight param when fetching!", json!({"type":"object","properties":{
-            "query":{"type":"string","description":"Regular expression pattern to search for"},
+            "query":{"type":["string","array"],"items":{"type":"string"},"description":"Regular expression pattern to search for; an array searches for several patterns in one pass and reports which matched each hit"},
             "maxResults":{"type":"number","description":"Maximum number of files to return (default: 20)"},
-            "maxMatchesPerFile":{"type":"number","description":"Maximum matches per file (default: 5)"}
+            "maxMatchesPerFile":{"type":"number","description":"Maximum matches per file (default: 5)"},
+            "maxParallelism":{"type":"number","description":"Worker threads for this search (default: DAIZO_THREADS or all cores; never more than DAIZO_THREADS)"},
+            "sortBy":{"type":"string","enum":["relevance","matches","id"],"description":"Result order: relevance score (match density, title match, matches in headings, early juan), match count, or file id (default: relevance)"},
+            "mergeWithin":{"type":"number","description":"Join matches on adjacent lines within this many characters into one snippet with highlight ranges, and fold repeated lines (default: 80; 0 = one match per line)"},
+            "textOnly":{"type":"boolean","description":"Drop matches that only hit tags, attribute values or entities (e.g. 'rend'), keeping hits in the text (default: false)"},
+            "scope":{"type":["string","array"],"items":{"type":"string"},"description":"Search only these texts: an id or a list of ids (e.g. from title search) instead of the whole corpus"},
+            "queryMode":{"type":"string","enum":["regex","boolean"],"description":"'boolean' parses AND/OR/NOT, (…), \"phrases\" and 'a NEAR/20 b' (default: regex)"},
+            "maxCandidates":{"type":"number","description":"Boolean mode: files prefiltered by the positive terms before evaluation (default: 1000)"},
+            "transliterate":{"type":"boolean","description":"Literal queries also match their IAST / Harvard-Kyoto / SLP1 / Devanagari spellings (default: true)"},
+            "groupDuplicates":{"type":"boolean","description":"Collapse other encodings of the same work into the best-ranked file's 'duplicates' (default: true)"}
         },"required":["query"]})),
         tool("gretil_fetch", "Retrieve GRETIL Sanskrit text by ID. FAST ACCESS: Use id directly (e.g., 'saddharmapuNDarIka', 'vajracchedikA', 'prajJApAramitAhRdayasUtra'). File stems follow sa_<textname>.xml pattern; you can omit 'sa_' prefix.", json!({"type":"object","properties":{
+            "provenance":{"type":"boolean","description":"Append a provenance footer (corpus, snapshot commit, retrieval date, tool version) and return _meta.provenance; default from DAIZO_PROVENANCE"},
+            "cursor":{"type":"string","description":"_meta.nextCursor from a previous call: continue exactly where that slice ended"},
+            "matchId":{"type":"string","description":"match_id from a *_search result: fetches around that match (sets id, lineNumber and highlight; the line is looked up again, so it follows the text if the file changed)"},
             "id":{"type":"string"},
             "query":{"type":"string"},
             "headIndex":{"type":"number","description":"Extract section by <head> index (0-based)."},
             "headQuery":{"type":"string","description":"Extract section by <head> substring match."},
             "includeNotes":{"type":"boolean"},
+            "preferRichest":{"type":"boolean","description":"If the work exists in several encodings, fetch the fullest one (default: true for query, false for id; ignored with lineNumber)"},
             "full":{"type":"boolean","description":"Return full text without slicing"},
-            "highlight":{"type":"string","description":"Highlight string or regex pattern (used with lineNumber-based context)"},
+            "highlight":{"type":["string","array"],"items":{"type":["string","object"],"properties":{"pattern":{"type":"string"},"regex":{"type":"boolean"},"prefix":{"type":"string"},"suffix":{"type":"string"}},"required":["pattern"]},"description":"Highlight string or regex pattern (used with lineNumber-based context); an array of {pattern, prefix, suffix, regex} entries (or strings) highlights each pattern with its own markers (unset markers follow highlightStyle) and groups _meta.highlightPositions by pattern"},
             "highlightRegex":{"type":"boolean","description":"Interpret highlight as regex (default false)"},
+            "highlightStyle":{"type":"string","enum":["arrows","markdown-bold","html-mark","brackets","none"],"description":"Highlight marker preset: arrows (>>> <<<, default), markdown-bold (**), html-mark (<mark>), brackets (⟦⟧), none (text unchanged; positions in _meta only). highlightPrefix/highlightSuffix override it"},
             "highlightPrefix":{"type":"string","description":"Prefix marker for highlights (default '>>> ')"},
             "highlightSuffix":{"type":"string","description":"Suffix marker for highlights (default ' <<<')"},
             "headingsLimit":{"type":"number"},
-            "startChar":{"type":"number"},"endChar":{"type":"number"},"maxChars":{"type":"number"},
-            "page":{"type":"number"},"pageSize":{"type":"number"},
-            "lineNumber":{"type":"number","description":"Target line number for context extraction"},
+            "startChar":{"type":"number"},"endChar":{"type":"number"},"maxChars":{"type":"number"},"maxTokens":{"type":"number","description":"Token budget (estimated, CJK-aware): the slice ends where it is used up; _meta.budget reports what was granted"},
+            "startLine":{"type":"number","description":"1-based line of the extracted text to start at (instead of startChar); _meta.lineMap maps the returned lines to char offsets"},"endLine":{"type":"number","description":"Last line to return, inclusive (instead of endChar)"},
+            "onOverflow":{"type":"string","enum":["truncate","outline"],"description":"When the requested text (juan, section, full, endChar/page) exceeds the budget: truncate (default; continue with _meta.nextCursor) or outline (section titles, the first outlineChars chars of each and a cursor per section)"},
+            "snap":{"type":"string","enum":["none","sentence"],"description":"sentence: move the slice ends (at most 200 chars) onto sentence boundaries (。？！, danda ।/॥, shad, Pāli/Sanskrit full stops) so the text starts and ends with whole sentences; an explicit startChar (and so a cursor) is kept and only the end snaps; continuation cursors follow the snapped end"},
+            "outlineChars":{"type":"number","description":"Preview length per section with onOverflow:\"outline\" (default 200)"},
+            "page":{"type":"number","description":"0-based page"},"pageSize":{"type":"number","description":"Chars per page, or units per page with pageBy"},
+            "pageBy":{"type":"string","enum":["head","juan","paragraph"],"description":"Page by structural unit instead of characters: page/pageSize then count headed sections, juans or paragraphs (pageSize default 1), so pages never cut a sentence or verse; _meta.pageBy gives pageCount, titles and nextPage"},
+            "lineNumber":{"type":"number","description":"Target line number for context extraction; _meta.structure gives the juan and heading chain the line falls under (label e.g. '卷第三 > 譬喻品第三')"},
             "contextBefore":{"type":"number","description":"Number of lines before target line (default: 10)"},
             "contextAfter":{"type":"number","description":"Number of lines after target line (default: 100)"},
             "contextLines":{"type":"number","description":"Number of lines before/after target line (deprecated, use contextBefore/contextAfter)"}
         }})),
+        fetch_batch_tool("gretil", "[{\"id\":\"saddharmapuNDarIka\",\"lineNumber\":200}]"),
         tool("gretil_pipeline", "GRETIL summarize/context pipeline; set autoFetch=false for summary-only (see gretil_search _meta.pipelineHint)", json!({"type":"object","properties":{
             "query":{"type":"string"},
             "maxResults":{"type":"number"},
             "maxMatchesPerFile":{"type":"number"},
+            "maxParallelism":{"type":"number","description":"Worker threads for this search (default: DAIZO_THREADS or all cores; never more than DAIZO_THREADS)"},
             "contextBefore":{"type":"number"},
             "contextAfter":{"type":"number"},
             "autoFetch":{"type":"boolean"},
@@ -446,6 +798,7 @@ fn tools_list() -> Vec<serde_json::Value> {
             "snippetSuffix":{"type":"string","description":"Suffix for highlight snippets in pipeline (default '')"},
             "highlight":{"type":"string","description":"Highlight string or regex pattern inside contexts"},
             "highlightRegex":{"type":"boolean","description":"Interpret highlight as regex (default false)"},
+            "highlightStyle":{"type":"string","enum":["arrows","markdown-bold","html-mark","brackets","none"],"description":"Highlight marker preset: arrows (>>> <<<, default), markdown-bold (**), html-mark (<mark>), brackets (⟦⟧), none (text unchanged; positions in _meta only). highlightPrefix/highlightSuffix override it"},
             "highlightPrefix":{"type":"string","description":"Prefix marker for highlights (default from env or '>>> ')"},
             "highlightSuffix":{"type":"string","description":"Suffix marker for highlights (default from env or ' <<<')"},
             "full":{"type":"boolean"},
@@ -454,33 +807,51 @@ fn tools_list() -> Vec<serde_json::Value> {
         // SARIT (TEI P5)
         tool("sarit_title_search", "Title-based search in SARIT corpus. Note: If file stem is known, skip search and use sarit_fetch directly with id!", json!({"type":"object","properties":{"query":{"type":"string","description":"Title to search. If you know the file stem (e.g., 'asvaghosa-buddhacarita'), use sarit_fetch with id instead."},"limit":{"type":"number"}},"required":["query"]})),
         tool("sarit_search", "Fast regex search over SARIT; returns _meta.fetchSuggestions (use sarit_fetch with id+lineNumber+highlight). Always include highlight param when fetching!", json!({"type":"object","properties":{
-            "query":{"type":"string","description":"Regular expression pattern to search for"},
+            "query":{"type":["string","array"],"items":{"type":"string"},"description":"Regular expression pattern to search for; an array searches for several patterns in one pass and reports which matched each hit"},
             "maxResults":{"type":"number","description":"Maximum number of files to return (default: 20)"},
-            "maxMatchesPerFile":{"type":"number","description":"Maximum matches per file (default: 5)"}
+            "maxMatchesPerFile":{"type":"number","description":"Maximum matches per file (default: 5)"},
+            "maxParallelism":{"type":"number","description":"Worker threads for this search (default: DAIZO_THREADS or all cores; never more than DAIZO_THREADS)"},
+            "sortBy":{"type":"string","enum":["relevance","matches","id"],"description":"Result order: relevance score (match density, title match, matches in headings, early juan), match count, or file id (default: relevance)"},
+            "mergeWithin":{"type":"number","description":"Join matches on adjacent lines within this many characters into one snippet with highlight ranges, and fold repeated lines (default: 80; 0 = one match per line)"},
+            "textOnly":{"type":"boolean","description":"Drop matches that only hit tags, attribute values or entities (e.g. 'rend'), keeping hits in the text (default: false)"},
+            "scope":{"type":["string","array"],"items":{"type":"string"},"description":"Search only these texts: an id or a list of ids (e.g. from title search) instead of the whole corpus"},
+            "queryMode":{"type":"string","enum":["regex","boolean"],"description":"'boolean' parses AND/OR/NOT, (…), \"phrases\" and 'a NEAR/20 b' (default: regex)"},
+            "maxCandidates":{"type":"number","description":"Boolean mode: files prefiltered by the positive terms before evaluation (default: 1000)"}
         },"required":["query"]})),
         tool("sarit_fetch", "Retrieve SARIT TEI P5 text by ID. FAST ACCESS: Use id directly (file stem). Tries both repository root and transliterated/ subdir.", json!({"type":"object","properties":{
+            "provenance":{"type":"boolean","description":"Append a provenance footer (corpus, snapshot commit, retrieval date, tool version) and return _meta.provenance; default from DAIZO_PROVENANCE"},
+            "cursor":{"type":"string","description":"_meta.nextCursor from a previous call: continue exactly where that slice ended"},
+            "matchId":{"type":"string","description":"match_id from a *_search result: fetches around that match (sets id, lineNumber and highlight; the line is looked up again, so it follows the text if the file changed)"},
             "id":{"type":"string"},
             "query":{"type":"string"},
             "headIndex":{"type":"number","description":"Extract section by <head> index (0-based)."},
             "headQuery":{"type":"string","description":"Extract section by <head> substring match."},
             "includeNotes":{"type":"boolean"},
             "full":{"type":"boolean","description":"Return full text without slicing"},
-            "highlight":{"type":"string","description":"Highlight string or regex pattern (used with lineNumber-based context)"},
+            "highlight":{"type":["string","array"],"items":{"type":["string","object"],"properties":{"pattern":{"type":"string"},"regex":{"type":"boolean"},"prefix":{"type":"string"},"suffix":{"type":"string"}},"required":["pattern"]},"description":"Highlight string or regex pattern (used with lineNumber-based context); an array of {pattern, prefix, suffix, regex} entries (or strings) highlights each pattern with its own markers (unset markers follow highlightStyle) and groups _meta.highlightPositions by pattern"},
             "highlightRegex":{"type":"boolean","description":"Interpret highlight as regex (default false)"},
+            "highlightStyle":{"type":"string","enum":["arrows","markdown-bold","html-mark","brackets","none"],"description":"Highlight marker preset: arrows (>>> <<<, default), markdown-bold (**), html-mark (<mark>), brackets (⟦⟧), none (text unchanged; positions in _meta only). highlightPrefix/highlightSuffix override it"},
             "highlightPrefix":{"type":"string","description":"Prefix marker for highlights (default '>>> ')"},
             "highlightSuffix":{"type":"string","description":"Suffix marker for highlights (default ' <<<')"},
             "headingsLimit":{"type":"number"},
-            "startChar":{"type":"number"},"endChar":{"type":"number"},"maxChars":{"type":"number"},
-            "page":{"type":"number"},"pageSize":{"type":"number"},
-            "lineNumber":{"type":"number","description":"Target line number for context extraction"},
+            "startChar":{"type":"number"},"endChar":{"type":"number"},"maxChars":{"type":"number"},"maxTokens":{"type":"number","description":"Token budget (estimated, CJK-aware): the slice ends where it is used up; _meta.budget reports what was granted"},
+            "startLine":{"type":"number","description":"1-based line of the extracted text to start at (instead of startChar); _meta.lineMap maps the returned lines to char offsets"},"endLine":{"type":"number","description":"Last line to return, inclusive (instead of endChar)"},
+            "onOverflow":{"type":"string","enum":["truncate","outline"],"description":"When the requested text (juan, section, full, endChar/page) exceeds the budget: truncate (default; continue with _meta.nextCursor) or outline (section titles, the first outlineChars chars of each and a cursor per section)"},
+            "snap":{"type":"string","enum":["none","sentence"],"description":"sentence: move the slice ends (at most 200 chars) onto sentence boundaries (。？！, danda ।/॥, shad, Pāli/Sanskrit full stops) so the text starts and ends with whole sentences; an explicit startChar (and so a cursor) is kept and only the end snaps; continuation cursors follow the snapped end"},
+            "outlineChars":{"type":"number","description":"Preview length per section with onOverflow:\"outline\" (default 200)"},
+            "page":{"type":"number","description":"0-based page"},"pageSize":{"type":"number","description":"Chars per page, or units per page with pageBy"},
+            "pageBy":{"type":"string","enum":["head","juan","paragraph"],"description":"Page by structural unit instead of characters: page/pageSize then count headed sections, juans or paragraphs (pageSize default 1), so pages never cut a sentence or verse; _meta.pageBy gives pageCount, titles and nextPage"},
+            "lineNumber":{"type":"number","description":"Target line number for context extraction; _meta.structure gives the juan and heading chain the line falls under (label e.g. '卷第三 > 譬喻品第三')"},
             "contextBefore":{"type":"number","description":"Number of lines before target line (default: 10)"},
             "contextAfter":{"type":"number","description":"Number of lines after target line (default: 100)"},
             "contextLines":{"type":"number","description":"Number of lines before/after target line (deprecated, use contextBefore/contextAfter)"}
         }})),
+        fetch_batch_tool("sarit", "[{\"id\":\"<stem>\",\"lineNumber\":100}]"),
         tool("sarit_pipeline", "SARIT summarize/context pipeline; set autoFetch=false for summary-only (see sarit_search _meta.pipelineHint)", json!({"type":"object","properties":{
             "query":{"type":"string"},
             "maxResults":{"type":"number"},
             "maxMatchesPerFile":{"type":"number"},
+            "maxParallelism":{"type":"number","description":"Worker threads for this search (default: DAIZO_THREADS or all cores; never more than DAIZO_THREADS)"},
             "contextBefore":{"type":"number"},
             "contextAfter":{"type":"number"},
             "autoFetch":{"type":"boolean"},
@@ -491,6 +862,7 @@ fn tools_list() -> Vec<serde_json::Value> {
             "snippetSuffix":{"type":"string","description":"Suffix for highlight snippets in pipeline (default '')"},
             "highlight":{"type":"string","description":"Highlight string or regex pattern inside contexts"},
             "highlightRegex":{"type":"boolean","description":"Interpret highlight as regex (default false)"},
+            "highlightStyle":{"type":"string","enum":["arrows","markdown-bold","html-mark","brackets","none"],"description":"Highlight marker preset: arrows (>>> <<<, default), markdown-bold (**), html-mark (<mark>), brackets (⟦⟧), none (text unchanged; positions in _meta only). highlightPrefix/highlightSuffix override it"},
             "highlightPrefix":{"type":"string","description":"Prefix marker for highlights (default from env or '>>> ')"},
             "highlightSuffix":{"type":"string","description":"Suffix marker for highlights (default from env or ' <<<')"},
             "full":{"type":"boolean"},
@@ -499,31 +871,49 @@ fn tools_list() -> Vec<serde_json::Value> {
         // MUKTABODHA
         tool("muktabodha_title_search", "Title-based search in MUKTABODHA Sanskrit library (IAST). If file stem is known, use muktabodha_fetch with id.", json!({"type":"object","properties":{"query":{"type":"string"},"limit":{"type":"number"}},"required":["query"]})),
         tool("muktabodha_search", "Fast regex search over MUKTABODHA; returns _meta.fetchSuggestions (use muktabodha_fetch with id+lineNumber+highlight).", json!({"type":"object","properties":{
-            "query":{"type":"string","description":"Regular expression pattern to search for"},
+            "query":{"type":["string","array"],"items":{"type":"string"},"description":"Regular expression pattern to search for; an array searches for several patterns in one pass and reports which matched each hit"},
             "maxResults":{"type":"number","description":"Maximum number of files to return (default: 20)"},
-            "maxMatchesPerFile":{"type":"number","description":"Maximum matches per file (default: 5)"}
+            "maxMatchesPerFile":{"type":"number","description":"Maximum matches per file (default: 5)"},
+            "maxParallelism":{"type":"number","description":"Worker threads for this search (default: DAIZO_THREADS or all cores; never more than DAIZO_THREADS)"},
+            "sortBy":{"type":"string","enum":["relevance","matches","id"],"description":"Result order: relevance score (match density, title match, matches in headings, early juan), match count, or file id (default: relevance)"},
+            "mergeWithin":{"type":"number","description":"Join matches on adjacent lines within this many characters into one snippet with highlight ranges, and fold repeated lines (default: 80; 0 = one match per line)"},
+            "textOnly":{"type":"boolean","description":"Drop matches that only hit tags, attribute values or entities (e.g. 'rend'), keeping hits in the text (default: false)"},
+            "scope":{"type":["string","array"],"items":{"type":"string"},"description":"Search only these texts: an id or a list of ids (e.g. from title search) instead of the whole corpus"},
+            "queryMode":{"type":"string","enum":["regex","boolean"],"description":"'boolean' parses AND/OR/NOT, (…), \"phrases\" and 'a NEAR/20 b' (default: regex)"},
+            "maxCandidates":{"type":"number","description":"Boolean mode: files prefiltered by the positive terms before evaluation (default: 1000)"}
         },"required":["query"]})),
         tool("muktabodha_fetch", "Retrieve MUKTABODHA text by ID (file stem). Supports both .xml (TEI) and .txt files.", json!({"type":"object","properties":{
+            "provenance":{"type":"boolean","description":"Append a provenance footer (corpus, snapshot commit, retrieval date, tool version) and return _meta.provenance; default from DAIZO_PROVENANCE"},
+            "cursor":{"type":"string","description":"_meta.nextCursor from a previous call: continue exactly where that slice ended"},
+            "matchId":{"type":"string","description":"match_id from a *_search result: fetches around that match (sets id, lineNumber and highlight; the line is looked up again, so it follows the text if the file changed)"},
             "id":{"type":"string"},
             "query":{"type":"string"},
             "includeNotes":{"type":"boolean"},
             "full":{"type":"boolean"},
-            "highlight":{"type":"string"},
+            "highlight":{"type":["string","array"],"items":{"type":["string","object"],"properties":{"pattern":{"type":"string"},"regex":{"type":"boolean"},"prefix":{"type":"string"},"suffix":{"type":"string"}},"required":["pattern"]},"description":"Highlight string or regex pattern; an array of {pattern, prefix, suffix, regex} entries (or strings) highlights each pattern with its own markers (unset markers follow highlightStyle) and groups _meta.highlightPositions by pattern"},
             "highlightRegex":{"type":"boolean"},
+            "highlightStyle":{"type":"string","enum":["arrows","markdown-bold","html-mark","brackets","none"],"description":"Highlight marker preset: arrows (>>> <<<, default), markdown-bold (**), html-mark (<mark>), brackets (⟦⟧), none (text unchanged; positions in _meta only). highlightPrefix/highlightSuffix override it"},
             "highlightPrefix":{"type":"string"},
             "highlightSuffix":{"type":"string"},
             "headingsLimit":{"type":"number"},
-            "startChar":{"type":"number"},"endChar":{"type":"number"},"maxChars":{"type":"number"},
-            "page":{"type":"number"},"pageSize":{"type":"number"},
-            "lineNumber":{"type":"number"},
+            "startChar":{"type":"number"},"endChar":{"type":"number"},"maxChars":{"type":"number"},"maxTokens":{"type":"number","description":"Token budget (estimated, CJK-aware): the slice ends where it is used up; _meta.budget reports what was granted"},
+            "startLine":{"type":"number","description":"1-based line of the extracted text to start at (instead of startChar); _meta.lineMap maps the returned lines to char offsets"},"endLine":{"type":"number","description":"Last line to return, inclusive (instead of endChar)"},
+            "onOverflow":{"type":"string","enum":["truncate","outline"],"description":"When the requested text (juan, section, full, endChar/page) exceeds the budget: truncate (default; continue with _meta.nextCursor) or outline (section titles, the first outlineChars chars of each and a cursor per section)"},
+            "snap":{"type":"string","enum":["none","sentence"],"description":"sentence: move the slice ends (at most 200 chars) onto sentence boundaries (。？！, danda ।/॥, shad, Pāli/Sanskrit full stops) so the text starts and ends with whole sentences; an explicit startChar (and so a cursor) is kept and only the end snaps; continuation cursors follow the snapped end"},
+            "outlineChars":{"type":"number","description":"Preview length per section with onOverflow:\"outline\" (default 200)"},
+            "page":{"type":"number","description":"0-based page"},"pageSize":{"type":"number","description":"Chars per page, or units per page with pageBy"},
+            "pageBy":{"type":"string","enum":["head","juan","paragraph"],"description":"Page by structural unit instead of characters: page/pageSize then count headed sections, juans or paragraphs (pageSize default 1), so pages never cut a sentence or verse; _meta.pageBy gives pageCount, titles and nextPage"},
+            "lineNumber":{"type":"number","description":"Target line number for context extraction; _meta.structure gives the juan and heading chain the line falls under (label e.g. '卷第三 > 譬喻品第三')"},
             "contextBefore":{"type":"number"},
             "contextAfter":{"type":"number"},
             "contextLines":{"type":"number"}
         }})),
+        fetch_batch_tool("muktabodha", "[{\"id\":\"<stem>\",\"lineNumber\":100}]"),
         tool("muktabodha_pipeline", "MUKTABODHA summarize/context pipeline; set autoFetch=false for summary-only.", json!({"type":"object","properties":{
             "query":{"type":"string"},
             "maxResults":{"type":"number"},
             "maxMatchesPerFile":{"type":"number"},
+            "maxParallelism":{"type":"number","description":"Worker threads for this search (default: DAIZO_THREADS or all cores; never more than DAIZO_THREADS)"},
             "contextBefore":{"type":"number"},
             "contextAfter":{"type":"number"},
             "autoFetch":{"type":"boolean"},
@@ -534,6 +924,7 @@ fn tools_list() -> Vec<serde_json::Value> {
             "snippetSuffix":{"type":"string"},
             "highlight":{"type":"string"},
             "highlightRegex":{"type":"boolean"},
+            "highlightStyle":{"type":"string","enum":["arrows","markdown-bold","html-mark","brackets","none"],"description":"Highlight marker preset: arrows (>>> <<<, default), markdown-bold (**), html-mark (<mark>), brackets (⟦⟧), none (text unchanged; positions in _meta only). highlightPrefix/highlightSuffix override it"},
             "highlightPrefix":{"type":"string"},
             "highlightSuffix":{"type":"string"},
             "full":{"type":"boolean"},
@@ -547,41 +938,266 @@ fn tool(name: &str, description: &str, input_schema: serde_json::Value) -> serde
 }
 
 fn handle_tools_list(id: serde_json::Value) -> serde_json::Value {
-    json!({"jsonrpc":"2.0","id":id,"result": {"tools": tools_list()}})
+    let tools: Vec<serde_json::Value> = tools_list()
+        .into_iter()
+        .chain(remote_tool_schemas())
+        .filter(|t| capabilities().allows_tool(t["name"].as_str().unwrap_or("")))
+        .collect();
+    json!({"jsonrpc":"2.0","id":id,"result": {"tools": tools}})
+}
+
+// ============ Resources ============
+
+/// Local corpora exposed as `daizo://<corpus>/<id>` resources, with the fetch tool that reads them.
+const RESOURCE_CORPORA: &[(&str, &str)] = &[
+    ("cbeta", "cbeta_fetch"),
+    ("tipitaka", "tipitaka_fetch"),
+    ("gretil", "gretil_fetch"),
+    ("sarit", "sarit_fetch"),
+    ("muktabodha", "muktabodha_fetch"),
+];
+
+const RESOURCES_PAGE_SIZE: usize = 500;
+/// Most cursor slices read for one resource; a longer text is returned cut short
+/// (`truncated: true`), as is one whose cursors stop making progress.
+const RESOURCE_MAX_SLICES: usize = 1000;
+
+/// Index of a local corpus, or None when its data directory is missing
+/// (so listing resources never triggers a clone or an empty index build).
+fn resource_corpus_index(corpus: &str) -> Option<Index> {
+    let (root, load): (PathBuf, fn() -> Index) = match corpus {
+        "cbeta" => (cbeta_root(), load_or_build_cbeta_index),
+        "tipitaka" => (tipitaka_root(), load_or_build_tipitaka_index),
+        "gretil" => (gretil_root(), load_or_build_gretil_index),
+        "sarit" => (sarit_root(), load_or_build_sarit_index),
+        "muktabodha" => (muktabodha_root(), load_or_build_muktabodha_index),
+        _ => return None,
+    };
+    root.exists().then(load)
+}
+
+/// Resource id of an index entry: CBETA file ids drop the volume (`T09n0262` → `T0262`),
+/// other corpora use the index id as is.
+fn resource_id(corpus: &str, e: &IndexEntry) -> String {
+    if corpus == "cbeta" {
+        let canon: String =
+            e.id.chars()
+                .take_while(|c| c.is_ascii_alphabetic())
+                .collect();
+        if let Some(pos) = e.id[canon.len()..].find('n') {
+            let num = &e.id[canon.len() + pos + 1..];
+            if !canon.is_empty() && num.starts_with(|c: char| c.is_ascii_digit()) {
+                return format!("{}{}", canon, num);
+            }
+        }
+    }
+    e.id.clone()
+}
+
+fn resource_for_entry(corpus: &str, e: &IndexEntry) -> serde_json::Value {
+    let meta = e.meta.as_ref();
+    let by = meta
+        .and_then(|m| m.get("translator").or_else(|| m.get("author")))
+        .map(|s| s.as_str());
+    let description = match by {
+        Some(b) => format!("{} · {} · {}", corpus, e.title, b),
+        None => format!("{} · {}", corpus, e.title),
+    };
+    json!({
+        "uri": format!("daizo://{}/{}", corpus, resource_id(corpus, e)),
+        "name": e.id,
+        "title": e.title,
+        "description": description,
+        "mimeType": "text/plain",
+        "_meta": meta,
+    })
+}
+
+fn handle_resources_list(id: serde_json::Value, params: &serde_json::Value) -> serde_json::Value {
+    // The cursor is the offset into the concatenation of all available corpora.
+    let offset = params
+        .get("cursor")
+        .and_then(|v| v.as_str())
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut resources: Vec<serde_json::Value> = Vec::new();
+    let mut seen = 0usize;
+    let mut more = false;
+    for (corpus, _) in RESOURCE_CORPORA {
+        if !capabilities().allows(corpus) {
+            continue;
+        }
+        let Some(idx) = resource_corpus_index(corpus) else {
+            continue;
+        };
+        if seen + idx.len() <= offset {
+            seen += idx.len();
+            continue;
+        }
+        let skip = offset.saturating_sub(seen);
+        for e in idx.iter().skip(skip) {
+            if resources.len() == RESOURCES_PAGE_SIZE {
+                more = true;
+                break;
+            }
+            resources.push(resource_for_entry(corpus, e));
+        }
+        seen += idx.len();
+        if more {
+            break;
+        }
+    }
+    let mut result = json!({ "resources": resources });
+    if more {
+        result["nextCursor"] = json!((offset + RESOURCES_PAGE_SIZE).to_string());
+    }
+    json!({"jsonrpc":"2.0","id": id, "result": result})
+}
+
+fn handle_resource_templates_list(id: serde_json::Value) -> serde_json::Value {
+    let mut templates = vec![json!({
+        "uriTemplate": "daizo://cbeta/{id}/{part}",
+        "name": "CBETA juan",
+        "description": "One juan (fascicle) of a CBETA text, e.g. daizo://cbeta/T0262/002",
+        "mimeType": "text/plain",
+    })];
+    for (corpus, _) in RESOURCE_CORPORA {
+        templates.push(json!({
+            "uriTemplate": format!("daizo://{}/{{id}}", corpus),
+            "name": format!("{} text", corpus),
+            "mimeType": "text/plain",
+        }));
+    }
+    json!({"jsonrpc":"2.0","id": id, "result": { "resourceTemplates": templates }})
+}
+
+fn resource_not_found(id: serde_json::Value, uri: &str) -> serde_json::Value {
+    json!({"jsonrpc":"2.0","id": id, "error": {"code": -32002, "message": "Resource not found", "data": {"uri": uri}}})
+}
+
+/// Read `daizo://<corpus>/<id>[/<part>]` through the corpus fetch tool (plain text for CBETA).
+fn handle_resources_read(id: serde_json::Value, params: &serde_json::Value) -> serde_json::Value {
+    let uri = params.get("uri").and_then(|v| v.as_str()).unwrap_or("");
+    let Some(rest) = uri.strip_prefix("daizo://") else {
+        return resource_not_found(id, uri);
+    };
+    let mut segs = rest.split('/').filter(|s| !s.is_empty());
+    let (Some(corpus), Some(text_id)) = (segs.next(), segs.next()) else {
+        return resource_not_found(id, uri);
+    };
+    let part = segs.next();
+    let Some(&(_, tool_name)) = RESOURCE_CORPORA.iter().find(|(c, _)| *c == corpus) else {
+        return resource_not_found(id, uri);
+    };
+    let entry = resource_corpus_index(corpus).and_then(|idx| {
+        idx.iter()
+            .find(|e| e.id == text_id || resource_id(corpus, e) == text_id)
+            .cloned()
+    });
+    let Some(entry) = entry else {
+        return resource_not_found(id, uri);
+    };
+    if part.is_some() && corpus != "cbeta" {
+        return resource_not_found(id, uri);
+    }
+    // Resource text is concatenated from cursor slices, so no per-slice footers.
+    let mut args = json!({ "id": entry.id, "full": true, "provenance": false });
+    if corpus == "cbeta" {
+        args["format"] = json!("plain");
+        if let Some(p) = part {
+            args["part"] = json!(p);
+        }
+    }
+    let resp = dispatch_call(id.clone(), &json!({ "name": tool_name, "arguments": args }));
+    let mut text = resp
+        .pointer("/result/content/0/text")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    if errors::is_error_response(&resp) || text.trim().is_empty() {
+        return resource_not_found(id, uri);
+    }
+    // Fetches are capped at DAIZO_MCP_MAX_CHARS; follow the cursors to read the whole text.
+    let mut meta = resp.pointer("/result/_meta").cloned().unwrap_or(json!({}));
+    let mut cursor = meta
+        .get("nextCursor")
+        .and_then(|v| v.as_str())
+        .map(String::from);
+    let mut end = meta.get("returnedEnd").and_then(|v| v.as_u64());
+    let mut slices = 1;
+    let mut truncated = false;
+    while let Some(c) = cursor.take() {
+        truncated = true;
+        if slices >= RESOURCE_MAX_SLICES {
+            break;
+        }
+        let mut next_args = json!({ "cursor": c });
+        if apply_cursor(tool_name, &mut next_args).is_err() {
+            break;
+        }
+        let next = dispatch_call(
+            id.clone(),
+            &json!({ "name": tool_name, "arguments": next_args }),
+        );
+        // A slice that does not move past the previous one would loop forever.
+        let next_end = next
+            .pointer("/result/_meta/returnedEnd")
+            .and_then(|v| v.as_u64());
+        if errors::is_error_response(&next) || next_end.is_none() || next_end <= end {
+            break;
+        }
+        text.push_str(
+            next.pointer("/result/content/0/text")
+                .and_then(|v| v.as_str())
+                .unwrap_or(""),
+        );
+        cursor = next
+            .pointer("/result/_meta/nextCursor")
+            .and_then(|v| v.as_str())
+            .map(String::from);
+        end = next_end;
+        meta["returnedEnd"] = json!(next_end);
+        slices += 1;
+        truncated = false;
+    }
+    meta["truncated"] = json!(truncated);
+    meta["nextCursor"] = serde_json::Value::Null;
+    json!({"jsonrpc":"2.0","id": id, "result": { "contents": [{
+        "uri": uri,
+        "mimeType": "text/plain",
+        "text": text,
+        "_meta": meta,
+    }]}})
 }
 
 // normalization and token similarity helpers are provided by daizo_core::text_utils
 
 // メモリキャッシュ: プロセス内でインデックスを再利用し、毎回のJSONパースを回避
-static CBETA_INDEX_CACHE: OnceLock<Vec<IndexEntry>> = OnceLock::new();
+static CBETA_INDEX_CACHE: IndexCache = IndexCache::new();
 
-fn load_or_build_cbeta_index() -> &'static Vec<IndexEntry> {
+fn load_or_build_cbeta_index() -> Index {
     // NOTE: Do not clone the entire index on every call; keep a single in-process instance.
-    CBETA_INDEX_CACHE.get_or_init(|| {
-        let out = cache_dir().join("cbeta-index.json");
+    let out = cache_dir().join("cbeta-index.json");
+    CBETA_INDEX_CACHE.get_or_load(&out, || {
         if let Some(v) = load_index(&out) {
-            // 既存インデックスの健全性を軽くチェック（パスの存在 + メタの有無）
-            let missing = v
-                .iter()
-                .take(10)
-                .filter(|e| !Path::new(&e.path).exists())
-                .count();
+            // 既存インデックスの健全性を軽くチェック（リビジョンまたはパスの存在 + メタの有無）
+            let fresh = daizo_core::repo::index_is_fresh(&out, &cbeta_root(), &v);
             let lacks_meta = v.iter().take(10).any(|e| e.meta.is_none());
             let lacks_ver = v.iter().take(10).any(|e| {
                 e.meta
                     .as_ref()
                     .and_then(|m| m.get("indexVersion"))
-                    .map(|s| s.as_str() != "cbeta_index_v2")
+                    .map(|s| s.as_str() != "cbeta_index_v7")
                     .unwrap_or(true)
             });
-            if !v.is_empty() && missing == 0 && !lacks_meta && !lacks_ver {
+            if !v.is_empty() && fresh && !lacks_meta && !lacks_ver {
                 return v;
             }
         }
         // Ensure data exists (clone if needed)
         ensure_cbeta_data();
         let entries = build_cbeta_index(&cbeta_root());
-        let _ = save_index(&out, &entries);
+        let _ = save_index(&out, &cbeta_root(), &entries);
         entries
     })
 }
@@ -591,16 +1207,10 @@ struct TitleHayCache {
     hay_ws: Vec<String>,
 }
 
-static CBETA_TITLE_HAY_CACHE: OnceLock<TitleHayCache> = OnceLock::new();
+static CBETA_TITLE_HAY_CACHE: Derived<TitleHayCache> = Derived::new();
 
-fn cbeta_title_hay_cache(entries: &[IndexEntry]) -> Option<&'static TitleHayCache> {
-    if let Some(c) = CBETA_TITLE_HAY_CACHE.get() {
-        if c.hay_norm.len() == entries.len() && c.hay_ws.len() == entries.len() {
-            return Some(c);
-        }
-        return None;
-    }
-    let c = CBETA_TITLE_HAY_CACHE.get_or_init(|| {
+fn cbeta_title_hay_cache(entries: &[IndexEntry]) -> Option<Arc<TitleHayCache>> {
+    CBETA_TITLE_HAY_CACHE.get(&CBETA_INDEX_CACHE, entries, |entries| {
         let mut hay_norm: Vec<String> = Vec::with_capacity(entries.len());
         let mut hay_ws: Vec<String> = Vec::with_capacity(entries.len());
         for e in entries.iter() {
@@ -623,27 +1233,18 @@ fn cbeta_title_hay_cache(entries: &[IndexEntry]) -> Option<&'static TitleHayCach
             hay_ws.push(daizo_core::text_utils::normalized_with_spaces(&hay_all));
         }
         TitleHayCache { hay_norm, hay_ws }
-    });
-    if c.hay_norm.len() == entries.len() && c.hay_ws.len() == entries.len() {
-        Some(c)
-    } else {
-        None
-    }
+    })
 }
 // メモリキャッシュ: Tipitakaインデックス
-static TIPITAKA_INDEX_CACHE: OnceLock<Vec<IndexEntry>> = OnceLock::new();
+static TIPITAKA_INDEX_CACHE: IndexCache = IndexCache::new();
 
-fn load_or_build_tipitaka_index() -> &'static Vec<IndexEntry> {
+fn load_or_build_tipitaka_index() -> Index {
     // NOTE: Do not clone the entire index on every call; keep a single in-process instance.
-    TIPITAKA_INDEX_CACHE.get_or_init(|| {
-        let out = cache_dir().join("tipitaka-index.json");
+    let out = cache_dir().join("tipitaka-index.json");
+    TIPITAKA_INDEX_CACHE.get_or_load(&out, || {
         if let Some(mut v) = load_index(&out) {
             v.retain(|e| !e.path.ends_with(".toc.xml"));
-            let missing = v
-                .iter()
-                .take(10)
-                .filter(|e| !Path::new(&e.path).exists())
-                .count();
+            let fresh = daizo_core::repo::index_is_fresh(&out, &tipitaka_root(), &v);
             let lacks_meta = v.iter().take(10).any(|e| e.meta.is_none());
             let lacks_heads = v.iter().take(20).any(|e| {
                 e.meta
@@ -655,7 +1256,7 @@ fn load_or_build_tipitaka_index() -> &'static Vec<IndexEntry> {
                 e.meta
                     .as_ref()
                     .and_then(|m| m.get("indexVersion"))
-                    .map(|s| s.as_str() != "tipitaka_index_v2")
+                    .map(|s| s.as_str() != "tipitaka_index_v4")
                     .unwrap_or(true)
             });
             let lacks_composite = v.iter().take(50).any(|e| {
@@ -668,7 +1269,7 @@ fn load_or_build_tipitaka_index() -> &'static Vec<IndexEntry> {
                 false
             });
             if !v.is_empty()
-                && missing == 0
+                && fresh
                 && !lacks_meta
                 && !lacks_heads
                 && !lacks_ver
@@ -680,115 +1281,518 @@ fn load_or_build_tipitaka_index() -> &'static Vec<IndexEntry> {
         ensure_tipitaka_data();
         let mut entries = build_tipitaka_index(&tipitaka_root());
         entries.retain(|e| !e.path.ends_with(".toc.xml"));
-        let _ = save_index(&out, &entries);
+        let _ = save_index(&out, &tipitaka_root(), &entries);
         entries
     })
 }
 // メモリキャッシュ: GRETILインデックス
-static GRETIL_INDEX_CACHE: OnceLock<Vec<IndexEntry>> = OnceLock::new();
+static GRETIL_INDEX_CACHE: IndexCache = IndexCache::new();
 
-fn load_or_build_gretil_index() -> &'static Vec<IndexEntry> {
+fn load_or_build_gretil_index() -> Index {
     // NOTE: Do not clone the entire index on every call; keep a single in-process instance.
-    GRETIL_INDEX_CACHE.get_or_init(|| {
-        let out = cache_dir().join("gretil-index.json");
+    let out = cache_dir().join("gretil-index.json");
+    GRETIL_INDEX_CACHE.get_or_load(&out, || {
         if let Some(v) = load_index(&out) {
-            let missing = v
-                .iter()
-                .take(10)
-                .filter(|e| !Path::new(&e.path).exists())
-                .count();
-            if !v.is_empty() && missing == 0 {
+            let fresh = daizo_core::repo::index_is_fresh(&out, &gretil_root(), &v);
+            // v2 added duplicate groups (dupGroup/dupMembers), v3 text sizes, v4 language/genre/
+            // source/creationDate; older indexes are rebuilt.
+            let lacks_ver = v.iter().take(10).any(|e| {
+                e.meta
+                    .as_ref()
+                    .and_then(|m| m.get("indexVersion"))
+                    .map(|s| s.as_str() != "gretil_index_v4")
+                    .unwrap_or(true)
+            });
+            if !v.is_empty() && fresh && !lacks_ver {
                 return v;
             }
         }
         let entries = build_gretil_index(&gretil_root());
-        let _ = save_index(&out, &entries);
+        let _ = save_index(&out, &gretil_root(), &entries);
         entries
     })
 }
 
+/// Duplicate group of a GRETIL file (see `daizo_core::dedup`): the richest copy and the
+/// ids of all members, or None when the file has no known duplicates.
+fn gretil_duplicates<'a>(
+    idx: &'a [IndexEntry],
+    path: &Path,
+) -> Option<(&'a IndexEntry, Vec<String>)> {
+    let canon = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let e = idx.iter().find(|e| Path::new(&e.path) == canon)?;
+    let meta = e.meta.as_ref()?;
+    let group = meta.get("dupGroup")?;
+    let richest = idx.iter().find(|x| &x.id == group)?;
+    let members = meta
+        .get("dupMembers")
+        .map(|m| m.split(" | ").map(|s| s.to_string()).collect())
+        .unwrap_or_default();
+    Some((richest, members))
+}
+
+/// Collapse GRETIL search results that are copies of the same work: the first (best-ranked)
+/// file of each duplicate group is kept, and the others are listed as its duplicates.
+fn group_gretil_results(
+    results: Vec<daizo_core::GrepResult>,
+    idx: &[IndexEntry],
+) -> (Vec<daizo_core::GrepResult>, Vec<Vec<String>>) {
+    let group_of: std::collections::HashMap<String, &str> = idx
+        .iter()
+        .filter_map(|e| {
+            let g = e.meta.as_ref()?.get("dupGroup")?;
+            let stem = Path::new(&e.path)
+                .file_stem()?
+                .to_string_lossy()
+                .into_owned();
+            Some((stem, g.as_str()))
+        })
+        .collect();
+    let mut kept: Vec<daizo_core::GrepResult> = Vec::new();
+    let mut dups: Vec<Vec<String>> = Vec::new();
+    let mut slot: std::collections::HashMap<&str, usize> = Default::default();
+    for r in results {
+        match group_of.get(&r.file_id) {
+            Some(g) if slot.contains_key(g) => dups[slot[g]].push(r.file_id.clone()),
+            g => {
+                if let Some(g) = g {
+                    slot.insert(g, kept.len());
+                }
+                kept.push(r);
+                dups.push(Vec::new());
+            }
+        }
+    }
+    (kept, dups)
+}
+
 // メモリキャッシュ: SARITインデックス
-static SARIT_INDEX_CACHE: OnceLock<Vec<IndexEntry>> = OnceLock::new();
+static SARIT_INDEX_CACHE: IndexCache = IndexCache::new();
 
-fn load_or_build_sarit_index() -> &'static Vec<IndexEntry> {
-    SARIT_INDEX_CACHE.get_or_init(|| {
-        let out = cache_dir().join("sarit-index.json");
+fn load_or_build_sarit_index() -> Index {
+    let out = cache_dir().join("sarit-index.json");
+    SARIT_INDEX_CACHE.get_or_load(&out, || {
         if let Some(v) = load_index(&out) {
-            let missing = v
-                .iter()
-                .take(10)
-                .filter(|e| !Path::new(&e.path).exists())
-                .count();
-            if !v.is_empty() && missing == 0 {
+            let fresh = daizo_core::repo::index_is_fresh(&out, &sarit_root(), &v);
+            // v2 records text sizes (fileSize/textChars).
+            let lacks_ver = v.iter().take(10).any(|e| {
+                e.meta
+                    .as_ref()
+                    .and_then(|m| m.get("indexVersion"))
+                    .map(|s| s.as_str() != "sarit_index_v3")
+                    .unwrap_or(true)
+            });
+            if !v.is_empty() && fresh && !lacks_ver {
                 return v;
             }
         }
         ensure_sarit_data();
         let entries = build_sarit_index(&sarit_root());
-        let _ = save_index(&out, &entries);
+        let _ = save_index(&out, &sarit_root(), &entries);
         entries
     })
 }
 
 // メモリキャッシュ: MUKTABODHAインデックス
-static MUKTABODHA_INDEX_CACHE: OnceLock<Vec<IndexEntry>> = OnceLock::new();
+static MUKTABODHA_INDEX_CACHE: IndexCache = IndexCache::new();
 
-fn load_or_build_muktabodha_index() -> &'static Vec<IndexEntry> {
-    MUKTABODHA_INDEX_CACHE.get_or_init(|| {
-        let out = cache_dir().join("muktabodha-index.json");
+fn load_or_build_muktabodha_index() -> Index {
+    let out = cache_dir().join("muktabodha-index.json");
+    MUKTABODHA_INDEX_CACHE.get_or_load(&out, || {
         if let Some(v) = load_index(&out) {
-            let missing = v
-                .iter()
-                .take(10)
-                .filter(|e| !Path::new(&e.path).exists())
-                .count();
-            if !v.is_empty() && missing == 0 {
+            let fresh = daizo_core::repo::index_is_fresh(&out, &muktabodha_root(), &v);
+            // v2 records text sizes (fileSize/textChars).
+            let lacks_ver = v.iter().take(10).any(|e| {
+                e.meta
+                    .as_ref()
+                    .and_then(|m| m.get("indexVersion"))
+                    .map(|s| s.as_str() != "muktabodha_index_v2")
+                    .unwrap_or(true)
+            });
+            if !v.is_empty() && fresh && !lacks_ver {
                 return v;
             }
         }
         ensure_muktabodha_dir();
         let entries = build_muktabodha_index(&muktabodha_root());
-        let _ = save_index(&out, &entries);
+        let _ = save_index(&out, &muktabodha_root(), &entries);
         entries
     })
 }
 
-struct GretilHayCache {
-    hay_norm: Vec<String>,
-    hay_ws: Vec<String>,
-    hay_fold: Vec<String>,
+/// Index of `corpus` if it is already loaded or cached on disk. Search-time helpers use this
+/// instead of the `load_or_build_*` functions so a first search does not wait on indexing.
+fn cached_index(corpus: &str) -> Option<Index> {
+    let (cache, file, load): (&IndexCache, _, fn() -> Index) = match corpus {
+        "cbeta" => (
+            &CBETA_INDEX_CACHE,
+            "cbeta-index.json",
+            load_or_build_cbeta_index,
+        ),
+        "tipitaka" => (
+            &TIPITAKA_INDEX_CACHE,
+            "tipitaka-index.json",
+            load_or_build_tipitaka_index,
+        ),
+        "gretil" => (
+            &GRETIL_INDEX_CACHE,
+            "gretil-index.json",
+            load_or_build_gretil_index,
+        ),
+        "sarit" => (
+            &SARIT_INDEX_CACHE,
+            "sarit-index.json",
+            load_or_build_sarit_index,
+        ),
+        "muktabodha" => (
+            &MUKTABODHA_INDEX_CACHE,
+            "muktabodha-index.json",
+            load_or_build_muktabodha_index,
+        ),
+        _ => return None,
+    };
+    if cache.loaded().is_none() && !cache_dir().join(file).exists() {
+        return None;
+    }
+    Some(load())
+}
+
+/// Build the index of a local corpus from its data on disk, write the cache file and serve
+/// the new entries from now on.
+fn rebuild_index(corpus: &str) -> Option<Index> {
+    let root = local_source_root(corpus)?;
+    let entries = match corpus {
+        "cbeta" => build_cbeta_index(&root),
+        "tipitaka" => {
+            let mut v = build_tipitaka_index(&root);
+            v.retain(|e| !e.path.ends_with(".toc.xml"));
+            v
+        }
+        "gretil" => build_gretil_index(&root),
+        "sarit" => build_sarit_index(&root),
+        "muktabodha" => build_muktabodha_index(&root),
+        _ => return None,
+    };
+    let out = cache_dir().join(format!("{}-index.json", corpus));
+    save_index(&out, &root, &entries).ok()?;
+    Some(index_cache(corpus)?.store(&out, entries))
 }
 
-static GRETIL_TITLE_HAY_CACHE: OnceLock<GretilHayCache> = OnceLock::new();
+/// Index of a local corpus, loaded or built.
+fn corpus_index(corpus: &str) -> Option<Index> {
+    Some(match corpus {
+        "cbeta" => load_or_build_cbeta_index(),
+        "tipitaka" => load_or_build_tipitaka_index(),
+        "gretil" => load_or_build_gretil_index(),
+        "sarit" => load_or_build_sarit_index(),
+        "muktabodha" => load_or_build_muktabodha_index(),
+        _ => return None,
+    })
+}
 
-fn gretil_title_hay_cache(entries: &[IndexEntry]) -> Option<&'static GretilHayCache> {
-    if let Some(c) = GRETIL_TITLE_HAY_CACHE.get() {
-        if c.hay_norm.len() == entries.len()
-            && c.hay_ws.len() == entries.len()
-            && c.hay_fold.len() == entries.len()
-        {
-            return Some(c);
+static PROFILES: [Derived<daizo_core::similar::Profiles>; 5] = [const { Derived::new() }; 5];
+
+/// Vocabulary profiles of a corpus, for the index it has now.
+fn corpus_profiles(corpus: &str, idx: &Index) -> Option<Arc<daizo_core::similar::Profiles>> {
+    let slot = capabilities::LOCAL_CORPORA
+        .iter()
+        .position(|c| *c == corpus)?;
+    PROFILES[slot].get(index_cache(corpus)?, idx, |entries| {
+        daizo_core::similar::Profiles::load_or_build(
+            corpus,
+            entries,
+            &cache_dir().join(format!("{}-profiles.json", corpus)),
+            &cache_dir().join(format!("{}-index.json", corpus)),
+        )
+    })
+}
+
+static QUOTES: Derived<daizo_core::quotations::QuoteIndex> = Derived::new();
+
+/// Passage fingerprints of CBETA, for the index it has now.
+fn cbeta_quotes(idx: &Index) -> Option<Arc<daizo_core::quotations::QuoteIndex>> {
+    QUOTES.get(index_cache("cbeta")?, idx, |entries| {
+        daizo_core::quotations::QuoteIndex::load_or_build(
+            entries,
+            &cache_dir().join("cbeta-quotes.bin"),
+            &cache_dir().join("cbeta-index.json"),
+        )
+    })
+}
+
+type SemanticSlot = (
+    std::time::SystemTime,
+    Arc<daizo_core::embeddings::VectorIndex>,
+);
+static SEMANTIC: Mutex<Vec<(String, SemanticSlot)>> = Mutex::new(Vec::new());
+
+/// The embedding index of a corpus, reloaded when `embed-index` has replaced it. None when
+/// none was built.
+fn semantic_index(
+    corpus: &str,
+) -> Option<Result<Arc<daizo_core::embeddings::VectorIndex>, String>> {
+    let (meta, vectors) = daizo_core::embeddings::index_paths(corpus);
+    let modified = fs::metadata(&meta).and_then(|m| m.modified()).ok()?;
+    let mut slots = SEMANTIC.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((_, (m, idx))) = slots.iter().find(|(c, _)| c == corpus) {
+        if *m == modified {
+            return Some(Ok(idx.clone()));
         }
-        return None;
     }
-    let c = GRETIL_TITLE_HAY_CACHE.get_or_init(|| {
-        let mut hay_norm: Vec<String> = Vec::with_capacity(entries.len());
-        let mut hay_ws: Vec<String> = Vec::with_capacity(entries.len());
-        let mut hay_fold: Vec<String> = Vec::with_capacity(entries.len());
-        for e in entries.iter() {
-            let meta_str = e
-                .meta
-                .as_ref()
-                .map(|m| {
-                    let mut s = String::new();
-                    for v in m.values() {
-                        if !s.is_empty() {
-                            s.push(' ');
-                        }
-                        s.push_str(v);
-                    }
-                    s
-                })
+    let idx = match daizo_core::embeddings::VectorIndex::load(&meta, &vectors) {
+        Ok(i) => Arc::new(i),
+        Err(e) => return Some(Err(e)),
+    };
+    slots.retain(|(c, _)| c != corpus);
+    slots.push((corpus.to_string(), (modified, idx.clone())));
+    Some(Ok(idx))
+}
+
+fn warmup_enabled() -> bool {
+    match std::env::var("DAIZO_WARMUP")
+        .ok()
+        .map(|v| v.trim().to_ascii_lowercase())
+    {
+        Some(v) => matches!(v.as_str(), "1" | "true" | "on" | "yes"),
+        None => config::current().warmup.enabled.unwrap_or(false),
+    }
+}
+
+static HOT_FILES: OnceLock<Option<daizo_core::warmup::HotFiles>> = OnceLock::new();
+
+/// Recently fetched files, kept only when warm-up is on.
+fn hot_files() -> Option<&'static daizo_core::warmup::HotFiles> {
+    HOT_FILES
+        .get_or_init(|| {
+            warmup_enabled().then(|| {
+                let max = setting_usize(
+                    "DAIZO_WARMUP_HOT_FILES",
+                    config::current().warmup.hot_files,
+                    daizo_core::warmup::DEFAULT_HOT_FILES,
+                );
+                daizo_core::warmup::HotFiles::load(daizo_core::warmup::default_path(), max)
+            })
+        })
+        .as_ref()
+}
+
+fn note_hot_file(path: &Path) {
+    if let Some(h) = hot_files() {
+        h.note(path);
+    }
+}
+
+static WARMUP: OnceLock<Mutex<serde_json::Value>> = OnceLock::new();
+
+/// What the warm-up thread has done so far (`daizo_status`).
+fn warmup_report() -> serde_json::Value {
+    WARMUP
+        .get()
+        .and_then(|m| m.lock().ok().map(|v| v.clone()))
+        .unwrap_or_else(|| json!({"enabled": warmup_enabled(), "started": false}))
+}
+
+/// With `DAIZO_WARMUP` on, load the indexes and title haystacks of the installed corpora and
+/// read the recently fetched files into the page cache on a background thread, once.
+fn start_warmup() {
+    if !warmup_enabled()
+        || WARMUP
+            .set(Mutex::new(
+                json!({"enabled": true, "started": true, "done": false}),
+            ))
+            .is_err()
+    {
+        return;
+    }
+    let _ = std::thread::Builder::new().name("warmup".into()).spawn(|| {
+        let set = |k: &str, v: serde_json::Value| {
+            if let Some(Ok(mut m)) = WARMUP.get().map(|m| m.lock()) {
+                m[k] = v;
+            }
+        };
+        let t0 = Instant::now();
+        let mut loaded = Vec::new();
+        for c in capabilities::LOCAL_CORPORA {
+            if !capabilities().allows(c) || !corpus_present(c) {
+                continue;
+            }
+            let n = match *c {
+                "cbeta" => {
+                    let idx = load_or_build_cbeta_index();
+                    let _ = cbeta_title_hay_cache(&idx);
+                    idx.len()
+                }
+                "tipitaka" => load_or_build_tipitaka_index().len(),
+                "gretil" => {
+                    let idx = load_or_build_gretil_index();
+                    let _ = gretil_title_hay_cache(&idx);
+                    idx.len()
+                }
+                "sarit" => {
+                    let idx = load_or_build_sarit_index();
+                    let _ = sarit_title_hay_cache(&idx);
+                    idx.len()
+                }
+                _ => {
+                    let idx = load_or_build_muktabodha_index();
+                    let _ = muktabodha_title_hay_cache(&idx);
+                    idx.len()
+                }
+            };
+            loaded.push(json!({"corpus": c, "entries": n}));
+        }
+        let index_ms = t0.elapsed().as_millis() as u64;
+        set("indexes", json!(loaded));
+        set("indexesMs", json!(index_ms));
+        let (mut files, mut bytes) = (0u64, 0u64);
+        for f in hot_files().map(|h| h.list()).unwrap_or_default() {
+            // The list is a file on disk; only read what still lies inside a served corpus.
+            let inside = capabilities::LOCAL_CORPORA
+                .iter()
+                .any(|c| capabilities().allows(c) && in_corpus(c, &f));
+            if let (true, Ok(n)) = (inside, daizo_core::warmup::touch(&f)) {
+                files += 1;
+                bytes += n;
+            }
+        }
+        set("hotFiles", json!(files));
+        set("hotBytes", json!(bytes));
+        set("elapsedMs", json!(t0.elapsed().as_millis() as u64));
+        set("done", json!(true));
+        tracing::info!(
+            indexes = loaded.len(),
+            index_ms,
+            hot_files = files,
+            hot_bytes = bytes,
+            "warmup done"
+        );
+    });
+}
+
+/// Search results with their fetch sizes taken from the corpus index (plain-text chars and,
+/// for CBETA, per-juan sizes) and their `titles`. Without an index the results keep the XML
+/// size and have no `titles`.
+fn with_index_sizes(
+    corpus: &str,
+    mut results: Vec<daizo_core::GrepResult>,
+) -> Vec<daizo_core::GrepResult> {
+    if results.is_empty() {
+        return results;
+    }
+    if let Some(idx) = cached_index(corpus) {
+        daizo_core::sizes::apply_index_sizes(&mut results, &idx);
+        daizo_core::titles::apply_index_titles(&mut results, &idx);
+    }
+    results
+}
+
+static MATCH_IDS: OnceLock<daizo_core::match_ids::Registry> = OnceLock::new();
+
+fn match_registry() -> &'static daizo_core::match_ids::Registry {
+    MATCH_IDS.get_or_init(|| {
+        daizo_core::match_ids::Registry::new(daizo_core::match_ids::DEFAULT_CAPACITY)
+    })
+}
+
+/// Give the matches of a search their `match_id` and remember where each one points.
+fn register_match_ids(corpus: &str, results: &mut [daizo_core::GrepResult]) {
+    match_registry().insert(daizo_core::match_ids::assign(corpus, results));
+}
+
+/// ` m1a2b3c4d5e6` after a match in a search summary.
+fn match_id_tag(m: &daizo_core::GrepMatch) -> String {
+    m.match_id
+        .as_deref()
+        .map(|id| format!(" {}", id))
+        .unwrap_or_default()
+}
+
+/// `mergeWithin` (chars, default 80; 0 keeps one match per line): join matches on adjacent
+/// lines whose occurrences of `pattern` are that close, and fold repeated lines.
+fn merge_snippets(results: &mut [daizo_core::GrepResult], pattern: &str, args: &serde_json::Value) {
+    let within = args
+        .get("mergeWithin")
+        .and_then(|v| v.as_u64())
+        .map(|n| n as usize)
+        .unwrap_or(daizo_core::snippets::DEFAULT_WITHIN);
+    if within == 0 {
+        return;
+    }
+    if let Ok(re) = regex::RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .multi_line(true)
+        .build()
+    {
+        daizo_core::snippets::merge_matches(results, &re, within);
+    }
+}
+
+/// `textOnly:true`: drop matches that fell inside markup.
+fn filter_text_only(
+    results: &mut Vec<daizo_core::GrepResult>,
+    pattern: &str,
+    args: &serde_json::Value,
+) {
+    if !args
+        .get("textOnly")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+    {
+        return;
+    }
+    if let Ok(re) = regex::RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .multi_line(true)
+        .build()
+    {
+        daizo_core::plain::retain_text_matches(results, &re);
+    }
+}
+
+/// Score search results and order them by `sortBy` (`relevance`, `matches`, `id`). Index
+/// titles, when available, feed the title-match signal.
+fn sort_results(corpus: &str, results: &mut [daizo_core::GrepResult], args: &serde_json::Value) {
+    let sort = SortBy::parse(args.get("sortBy").and_then(|v| v.as_str()));
+    let titles: std::collections::HashMap<String, String> = match cached_index(corpus) {
+        Some(idx) if !results.is_empty() => idx
+            .iter()
+            .filter_map(|e| {
+                let stem = Path::new(&e.path).file_stem()?.to_string_lossy();
+                Some((stem.into_owned(), e.title.clone()))
+            })
+            .collect(),
+        _ => Default::default(),
+    };
+    daizo_core::relevance::rank(results, &titles, sort);
+}
+
+struct GretilHayCache {
+    hay_norm: Vec<String>,
+    hay_ws: Vec<String>,
+    hay_fold: Vec<String>,
+}
+
+static GRETIL_TITLE_HAY_CACHE: Derived<GretilHayCache> = Derived::new();
+
+fn gretil_title_hay_cache(entries: &[IndexEntry]) -> Option<Arc<GretilHayCache>> {
+    GRETIL_TITLE_HAY_CACHE.get(&GRETIL_INDEX_CACHE, entries, |entries| {
+        let mut hay_norm: Vec<String> = Vec::with_capacity(entries.len());
+        let mut hay_ws: Vec<String> = Vec::with_capacity(entries.len());
+        let mut hay_fold: Vec<String> = Vec::with_capacity(entries.len());
+        for e in entries.iter() {
+            let meta_str = e
+                .meta
+                .as_ref()
+                .map(|m| {
+                    let mut s = String::new();
+                    for v in m.values() {
+                        if !s.is_empty() {
+                            s.push(' ');
+                        }
+                        s.push_str(v);
+                    }
+                    s
+                })
                 .unwrap_or_default();
             let hay_all = format!("{} {} {}", e.title, e.id, meta_str);
             hay_norm.push(daizo_core::text_utils::normalized(&hay_all));
@@ -800,30 +1804,13 @@ fn gretil_title_hay_cache(entries: &[IndexEntry]) -> Option<&'static GretilHayCa
             hay_ws,
             hay_fold,
         }
-    });
-    if c.hay_norm.len() == entries.len()
-        && c.hay_ws.len() == entries.len()
-        && c.hay_fold.len() == entries.len()
-    {
-        Some(c)
-    } else {
-        None
-    }
+    })
 }
 
-static SARIT_TITLE_HAY_CACHE: OnceLock<GretilHayCache> = OnceLock::new();
+static SARIT_TITLE_HAY_CACHE: Derived<GretilHayCache> = Derived::new();
 
-fn sarit_title_hay_cache(entries: &[IndexEntry]) -> Option<&'static GretilHayCache> {
-    if let Some(c) = SARIT_TITLE_HAY_CACHE.get() {
-        if c.hay_norm.len() == entries.len()
-            && c.hay_ws.len() == entries.len()
-            && c.hay_fold.len() == entries.len()
-        {
-            return Some(c);
-        }
-        return None;
-    }
-    let c = SARIT_TITLE_HAY_CACHE.get_or_init(|| {
+fn sarit_title_hay_cache(entries: &[IndexEntry]) -> Option<Arc<GretilHayCache>> {
+    SARIT_TITLE_HAY_CACHE.get(&SARIT_INDEX_CACHE, entries, |entries| {
         let mut hay_norm: Vec<String> = Vec::with_capacity(entries.len());
         let mut hay_ws: Vec<String> = Vec::with_capacity(entries.len());
         let mut hay_fold: Vec<String> = Vec::with_capacity(entries.len());
@@ -852,30 +1839,13 @@ fn sarit_title_hay_cache(entries: &[IndexEntry]) -> Option<&'static GretilHayCac
             hay_ws,
             hay_fold,
         }
-    });
-    if c.hay_norm.len() == entries.len()
-        && c.hay_ws.len() == entries.len()
-        && c.hay_fold.len() == entries.len()
-    {
-        Some(c)
-    } else {
-        None
-    }
+    })
 }
 
-static MUKTABODHA_TITLE_HAY_CACHE: OnceLock<GretilHayCache> = OnceLock::new();
+static MUKTABODHA_TITLE_HAY_CACHE: Derived<GretilHayCache> = Derived::new();
 
-fn muktabodha_title_hay_cache(entries: &[IndexEntry]) -> Option<&'static GretilHayCache> {
-    if let Some(c) = MUKTABODHA_TITLE_HAY_CACHE.get() {
-        if c.hay_norm.len() == entries.len()
-            && c.hay_ws.len() == entries.len()
-            && c.hay_fold.len() == entries.len()
-        {
-            return Some(c);
-        }
-        return None;
-    }
-    let c = MUKTABODHA_TITLE_HAY_CACHE.get_or_init(|| {
+fn muktabodha_title_hay_cache(entries: &[IndexEntry]) -> Option<Arc<GretilHayCache>> {
+    MUKTABODHA_TITLE_HAY_CACHE.get(&MUKTABODHA_INDEX_CACHE, entries, |entries| {
         let mut hay_norm: Vec<String> = Vec::with_capacity(entries.len());
         let mut hay_ws: Vec<String> = Vec::with_capacity(entries.len());
         let mut hay_fold: Vec<String> = Vec::with_capacity(entries.len());
@@ -904,15 +1874,7 @@ fn muktabodha_title_hay_cache(entries: &[IndexEntry]) -> Option<&'static GretilH
             hay_ws,
             hay_fold,
         }
-    });
-    if c.hay_norm.len() == entries.len()
-        && c.hay_ws.len() == entries.len()
-        && c.hay_fold.len() == entries.len()
-    {
-        Some(c)
-    } else {
-        None
-    }
+    })
 }
 
 #[derive(Clone)]
@@ -921,6 +1883,7 @@ struct CbetaFileCacheEntry {
     xml: Arc<String>,
     gaiji: Option<Arc<std::collections::HashMap<String, String>>>,
     heads: Option<Arc<Vec<String>>>,
+    mulu: Option<Arc<Vec<MuluEntry>>>,
 }
 
 static CBETA_FILE_CACHE: OnceLock<Mutex<Vec<CbetaFileCacheEntry>>> = OnceLock::new();
@@ -963,6 +1926,7 @@ fn cbeta_xml_cached(path: &Path) -> Arc<String> {
             xml: xml.clone(),
             gaiji: None,
             heads: None,
+            mulu: None,
         },
     );
     let cap = cbeta_file_cache_cap();
@@ -1033,11 +1997,166 @@ fn cbeta_heads_cached(path: &Path, xml: &str) -> Arc<Vec<String>> {
     h
 }
 
+fn cbeta_mulu_cached(path: &Path, xml: &str) -> Arc<Vec<MuluEntry>> {
+    let cache = CBETA_FILE_CACHE.get_or_init(|| Mutex::new(Vec::new()));
+    if let Some(m) = {
+        let guard = cache.lock().unwrap();
+        guard
+            .iter()
+            .find(|e| e.path == path)
+            .and_then(|e| e.mulu.clone())
+    } {
+        return m;
+    }
+
+    let m = Arc::new(list_mulu_cbeta(xml));
+    let mut guard = cache.lock().unwrap();
+    if let Some(pos) = guard.iter().position(|e| e.path == path) {
+        guard[pos].mulu = Some(m.clone());
+    }
+    m
+}
+
+// ============ Prefetch of the next section ============
+
+static PREFETCH: OnceLock<Prefetcher> = OnceLock::new();
+
+fn prefetcher() -> &'static Prefetcher {
+    PREFETCH.get_or_init(Prefetcher::new)
+}
+
+/// `DAIZO_PREFETCH` (off/0 disables), `DAIZO_PREFETCH_MAX_MB`, `DAIZO_PREFETCH_INTERVAL_MS`,
+/// else the `[prefetch]` section of config.toml.
+fn prefetch_settings() -> PrefetchSettings {
+    let cfg = config::current().prefetch.clone();
+    let enabled = match std::env::var("DAIZO_PREFETCH")
+        .ok()
+        .map(|v| v.trim().to_ascii_lowercase())
+    {
+        Some(v) => !matches!(v.as_str(), "0" | "false" | "off" | "no"),
+        None => cfg.enabled.unwrap_or(true),
+    };
+    let interval = setting_usize(
+        "DAIZO_PREFETCH_INTERVAL_MS",
+        cfg.min_interval_ms.map(|v| v as usize),
+        200,
+    );
+    PrefetchSettings {
+        enabled,
+        max_bytes: setting_usize("DAIZO_PREFETCH_MAX_MB", cfg.max_mb, 64) << 20,
+        min_interval: Duration::from_millis(interval as u64),
+    }
+}
+
+fn cbeta_part_key(path: &Path, part: &str, plain: bool, include_notes: bool) -> String {
+    format!(
+        "cbeta|{}|{}|{}|{}",
+        path.display(),
+        daizo_core::juan_map::normalize_juan_n(part),
+        plain,
+        include_notes
+    )
+}
+
+/// Text of one juan as `cbeta_fetch` with `part` returns it.
+fn cbeta_part_text(
+    path: &Path,
+    xml: &str,
+    part: &str,
+    plain: bool,
+    include_notes: bool,
+) -> Option<String> {
+    let jmap = juan_map_cached(path, Some(xml)).unwrap_or_default();
+    if plain {
+        let gaiji = cbeta_gaiji_cached(path, xml);
+        extract_cbeta_juan_plain_with_map(xml, &jmap, &gaiji, part, include_notes)
+    } else {
+        extract_cbeta_juan_with_map(xml, &jmap, part)
+    }
+}
+
+/// Extract the juan after `part` in the background.
+fn prefetch_cbeta_next_part(path: &Path, xml: &str, part: &str, plain: bool, include_notes: bool) {
+    let Some(jmap) = juan_map_cached(path, Some(xml)) else {
+        return;
+    };
+    let Some(next) = jmap.neighbors(part).1.map(String::from) else {
+        return;
+    };
+    let path = path.to_path_buf();
+    let key = cbeta_part_key(&path, &next, plain, include_notes);
+    prefetcher().schedule(key, prefetch_settings(), move || {
+        let xml = cbeta_xml_cached(&path);
+        cbeta_part_text(&path, &xml, &next, plain, include_notes)
+    });
+}
+
+fn tipitaka_full_key(path: &Path) -> String {
+    format!("tipitaka|{}|full", path.display())
+}
+
+/// Extract a whole Tipitaka file (the next part of a work) in the background.
+fn prefetch_tipitaka_part(path: PathBuf) {
+    prefetcher().schedule(tipitaka_full_key(&path), prefetch_settings(), move || {
+        let xml = fs::read(&path).map(|b| decode_xml_bytes(&b)).ok()?;
+        Some(extract_text(&xml)).filter(|t| !t.trim().is_empty())
+    });
+}
+
+/// Section bounds for a CBETA `headQuery`: the `<cb:mulu>` TOC titles are tried first
+/// (exact, then substring), then the `<head>` texts.
+fn cbeta_section_bounds(xml: &str, mulu: &[MuluEntry], q: &str) -> Option<(usize, usize)> {
+    let ql = q.trim().to_lowercase();
+    mulu.iter()
+        .find(|m| m.title.to_lowercase() == ql)
+        .or_else(|| mulu.iter().find(|m| m.title.to_lowercase().contains(&ql)))
+        .map(|m| (m.start, m.end))
+        .or_else(|| section_by_head_bounds(xml, None, Some(q)))
+}
+
 #[derive(Clone, Debug, Serialize)]
 struct ScoredHit<'a> {
     #[serde(skip_serializing)]
     entry: &'a IndexEntry,
     score: f32,
+    /// Set by [`rerank`].
+    #[serde(rename = "matchReason", skip_serializing_if = "Option::is_none")]
+    reason: Option<MatchReason>,
+}
+
+/// Title searches take this many times `limit` candidates from the corpus scorer to rerank.
+const RERANK_POOL: usize = 3;
+
+/// Order title-search candidates by [`daizo_core::title_rank::rank`] against the best of
+/// `queries` (spellings of one query), with a fifth of the corpus score kept as a tiebreaker
+/// (it carries biases such as the Taishō preference), and keep the first `limit`.
+fn rerank<'a>(hits: Vec<ScoredHit<'a>>, queries: &[String], limit: usize) -> Vec<ScoredHit<'a>> {
+    let queries: Vec<TitleQuery> = queries.iter().map(|q| TitleQuery::new(q)).collect();
+    let mut out: Vec<ScoredHit> = hits
+        .into_iter()
+        .map(|h| {
+            let r = queries
+                .iter()
+                .map(|q| title_rank::rank(h.entry, q))
+                .max_by(|a, b| a.score.total_cmp(&b.score))
+                .unwrap_or(title_rank::Rank {
+                    score: 0.0,
+                    reason: MatchReason::FuzzyTitle,
+                });
+            ScoredHit {
+                entry: h.entry,
+                score: 0.8 * r.score + 0.2 * h.score,
+                reason: Some(r.reason),
+            }
+        })
+        .collect();
+    out.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.entry.id.cmp(&b.entry.id))
+    });
+    out.truncate(limit);
+    out
 }
 
 fn scored_cmp(a: &(f32, &IndexEntry), b: &(f32, &IndexEntry)) -> std::cmp::Ordering {
@@ -1064,13 +2183,10 @@ fn topk_insert<'a>(
         }
     }
     // Find insertion point (limit is small; linear scan is faster than full sort).
-    let mut pos = top.len();
-    for i in 0..top.len() {
-        if scored_cmp(&cand, &top[i]) == std::cmp::Ordering::Less {
-            pos = i;
-            break;
-        }
-    }
+    let pos = top
+        .iter()
+        .position(|t| scored_cmp(&cand, t) == std::cmp::Ordering::Less)
+        .unwrap_or(top.len());
     if pos == top.len() {
         top.push(cand);
     } else {
@@ -1082,12 +2198,26 @@ fn topk_insert<'a>(
 }
 
 fn best_match<'a>(entries: &'a [IndexEntry], q: &str, limit: usize) -> Vec<ScoredHit<'a>> {
+    best_match_where(entries, q, limit, |_| true)
+}
+
+/// [`best_match`] over the entries that pass `keep`.
+fn best_match_where<'a>(
+    entries: &'a [IndexEntry],
+    q: &str,
+    limit: usize,
+    keep: impl Fn(&IndexEntry) -> bool,
+) -> Vec<ScoredHit<'a>> {
     let pq = daizo_core::text_utils::PrecomputedQuery::new(q, false);
     let nq = pq.normalized();
     let hay_cache = cbeta_title_hay_cache(entries);
+    let scoring = config::current().scoring.clone();
     let mut top: Vec<(f32, &IndexEntry)> = Vec::with_capacity(limit.min(32));
     for (i, e) in entries.iter().enumerate() {
-        let mut s = if let Some(cache) = hay_cache {
+        if !keep(e) {
+            continue;
+        }
+        let mut s = if let Some(cache) = &hay_cache {
             daizo_core::text_utils::compute_match_score_precomputed_with_hay(
                 e,
                 &cache.hay_norm[i],
@@ -1100,15 +2230,15 @@ fn best_match<'a>(entries: &'a [IndexEntry], q: &str, limit: usize) -> Vec<Score
         if let Some(meta) = &e.meta {
             // CBETA: bias toward Taisho canon by default (common user expectation).
             if meta.get("canon").map(|c| c.as_str()) == Some("T") {
-                s = (s + 0.02).min(1.2);
+                s = (s + scoring.taisho_bias).min(1.2);
             } else if e.id.starts_with('T') {
-                s = (s + 0.01).min(1.2);
+                s = (s + scoring.taisho_bias / 2.0).min(1.2);
             }
             for k in ["author", "editor", "translator", "publisher"].iter() {
                 if let Some(v) = meta.get(*k) {
                     let nv = normalized(v);
                     if !nv.is_empty() && (nv.contains(nq) || nq.contains(&nv)) {
-                        s = s.max(0.93);
+                        s = s.max(scoring.person_match);
                     }
                 }
             }
@@ -1116,23 +2246,51 @@ fn best_match<'a>(entries: &'a [IndexEntry], q: &str, limit: usize) -> Vec<Score
         topk_insert(&mut top, (s, e), limit);
     }
     top.into_iter()
-        .map(|(s, e)| ScoredHit { entry: e, score: s })
+        .map(|(s, e)| ScoredHit {
+            entry: e,
+            score: s,
+            reason: None,
+        })
         .collect()
 }
 
 fn best_match_tipitaka<'a>(entries: &'a [IndexEntry], q: &str, limit: usize) -> Vec<ScoredHit<'a>> {
+    best_match_tipitaka_where(entries, q, limit, |_| true)
+}
+
+/// [`best_match_tipitaka`] over the entries that pass `keep`.
+fn best_match_tipitaka_where<'a>(
+    entries: &'a [IndexEntry],
+    q: &str,
+    limit: usize,
+    keep: impl Fn(&IndexEntry) -> bool,
+) -> Vec<ScoredHit<'a>> {
     let mut top: Vec<(f32, &IndexEntry)> = Vec::with_capacity(limit.min(32));
     let pq = daizo_core::text_utils::PrecomputedQuery::new(q, true);
-    for e in entries.iter() {
+    for e in entries.iter().filter(|e| keep(e)) {
         let s = daizo_core::text_utils::compute_match_score_precomputed(e, &pq);
         topk_insert(&mut top, (s, e), limit);
     }
     top.into_iter()
-        .map(|(s, e)| ScoredHit { entry: e, score: s })
+        .map(|(s, e)| ScoredHit {
+            entry: e,
+            score: s,
+            reason: None,
+        })
         .collect()
 }
 
 fn best_match_gretil<'a>(entries: &'a [IndexEntry], q: &str, limit: usize) -> Vec<ScoredHit<'a>> {
+    best_match_gretil_where(entries, q, limit, |_| true)
+}
+
+/// [`best_match_gretil`] over the entries that pass `keep`.
+fn best_match_gretil_where<'a>(
+    entries: &'a [IndexEntry],
+    q: &str,
+    limit: usize,
+    keep: impl Fn(&IndexEntry) -> bool,
+) -> Vec<ScoredHit<'a>> {
     let nq = normalized(q);
     let nq_ws = daizo_core::text_utils::normalized_with_spaces(q);
     let nq_nospace = nq_ws.replace(' ', "");
@@ -1144,7 +2302,10 @@ fn best_match_gretil<'a>(entries: &'a [IndexEntry], q: &str, limit: usize) -> Ve
 
     let mut top: Vec<(f32, &IndexEntry)> = Vec::with_capacity(limit.min(32));
     for (i, e) in entries.iter().enumerate() {
-        let mut s = if let Some(cache) = hay_cache {
+        if !keep(e) {
+            continue;
+        }
+        let mut s = if let Some(cache) = &hay_cache {
             let hay = &cache.hay_norm[i];
             let hay_ws = &cache.hay_ws[i];
             let hay_fold = &cache.hay_fold[i];
@@ -1160,7 +2321,7 @@ fn best_match_gretil<'a>(entries: &'a [IndexEntry], q: &str, limit: usize) -> Ve
                     let mut uniq: Vec<&str> = Vec::new();
                     let mut inter = 0usize;
                     for tok in hay_ws.split_whitespace() {
-                        if uniq.iter().any(|t| *t == tok) {
+                        if uniq.contains(&tok) {
                             continue;
                         }
                         if q_tokens.contains(tok) {
@@ -1200,13 +2361,12 @@ fn best_match_gretil<'a>(entries: &'a [IndexEntry], q: &str, limit: usize) -> Ve
                 .unwrap_or("");
             let nalias = daizo_core::text_utils::normalized_with_spaces(alias).replace(' ', "");
             let nalias_fold = daizo_core::text_utils::normalized_sanskrit(alias);
-            if !nalias.is_empty() {
-                if nalias.split_whitespace().any(|a| a == nq_nospace)
+            if !nalias.is_empty()
+                && (nalias.split_whitespace().any(|a| a == nq_nospace)
                     || nalias.contains(&nq_nospace)
-                    || (!nalias_fold.is_empty() && nalias_fold.contains(&nq_fold))
-                {
-                    score = score.max(0.95);
-                }
+                    || (!nalias_fold.is_empty() && nalias_fold.contains(&nq_fold)))
+            {
+                score = score.max(0.95);
             }
 
             if has_digit && !nq_ws.is_empty() && hay_ws.contains(&nq_ws) {
@@ -1230,7 +2390,11 @@ fn best_match_gretil<'a>(entries: &'a [IndexEntry], q: &str, limit: usize) -> Ve
         topk_insert(&mut top, (s, e), limit);
     }
     top.into_iter()
-        .map(|(s, e)| ScoredHit { entry: e, score: s })
+        .map(|(s, e)| ScoredHit {
+            entry: e,
+            score: s,
+            reason: None,
+        })
         .collect()
 }
 
@@ -1248,7 +2412,7 @@ fn best_match_sarit<'a>(entries: &'a [IndexEntry], q: &str, limit: usize) -> Vec
 
     let mut top: Vec<(f32, &IndexEntry)> = Vec::with_capacity(limit.min(32));
     for (i, e) in entries.iter().enumerate() {
-        let mut s = if let Some(cache) = hay_cache {
+        let mut s = if let Some(cache) = &hay_cache {
             let hay = &cache.hay_norm[i];
             let hay_ws = &cache.hay_ws[i];
             let hay_fold = &cache.hay_fold[i];
@@ -1263,7 +2427,7 @@ fn best_match_sarit<'a>(entries: &'a [IndexEntry], q: &str, limit: usize) -> Vec
                     let mut uniq: Vec<&str> = Vec::new();
                     let mut inter = 0usize;
                     for tok in hay_ws.split_whitespace() {
-                        if uniq.iter().any(|t| *t == tok) {
+                        if uniq.contains(&tok) {
                             continue;
                         }
                         if q_tokens.contains(tok) {
@@ -1303,13 +2467,12 @@ fn best_match_sarit<'a>(entries: &'a [IndexEntry], q: &str, limit: usize) -> Vec
                 .unwrap_or("");
             let nalias = daizo_core::text_utils::normalized_with_spaces(alias).replace(' ', "");
             let nalias_fold = daizo_core::text_utils::normalized_sanskrit(alias);
-            if !nalias.is_empty() {
-                if nalias.split_whitespace().any(|a| a == nq_nospace)
+            if !nalias.is_empty()
+                && (nalias.split_whitespace().any(|a| a == nq_nospace)
                     || nalias.contains(&nq_nospace)
-                    || (!nalias_fold.is_empty() && nalias_fold.contains(&nq_fold))
-                {
-                    score = score.max(0.95);
-                }
+                    || (!nalias_fold.is_empty() && nalias_fold.contains(&nq_fold)))
+            {
+                score = score.max(0.95);
             }
 
             if has_digit && !nq_ws.is_empty() && hay_ws.contains(&nq_ws) {
@@ -1333,7 +2496,11 @@ fn best_match_sarit<'a>(entries: &'a [IndexEntry], q: &str, limit: usize) -> Vec
         topk_insert(&mut top, (s, e), limit);
     }
     top.into_iter()
-        .map(|(s, e)| ScoredHit { entry: e, score: s })
+        .map(|(s, e)| ScoredHit {
+            entry: e,
+            score: s,
+            reason: None,
+        })
         .collect()
 }
 
@@ -1355,7 +2522,7 @@ fn best_match_muktabodha<'a>(
 
     let mut top: Vec<(f32, &IndexEntry)> = Vec::with_capacity(limit.min(32));
     for (i, e) in entries.iter().enumerate() {
-        let mut s = if let Some(cache) = hay_cache {
+        let mut s = if let Some(cache) = &hay_cache {
             let hay = &cache.hay_norm[i];
             let hay_ws = &cache.hay_ws[i];
             let hay_fold = &cache.hay_fold[i];
@@ -1370,7 +2537,7 @@ fn best_match_muktabodha<'a>(
                     let mut uniq: Vec<&str> = Vec::new();
                     let mut inter = 0usize;
                     for tok in hay_ws.split_whitespace() {
-                        if uniq.iter().any(|t| *t == tok) {
+                        if uniq.contains(&tok) {
                             continue;
                         }
                         if q_tokens.contains(tok) {
@@ -1410,13 +2577,12 @@ fn best_match_muktabodha<'a>(
                 .unwrap_or("");
             let nalias = daizo_core::text_utils::normalized_with_spaces(alias).replace(' ', "");
             let nalias_fold = daizo_core::text_utils::normalized_sanskrit(alias);
-            if !nalias.is_empty() {
-                if nalias.split_whitespace().any(|a| a == nq_nospace)
+            if !nalias.is_empty()
+                && (nalias.split_whitespace().any(|a| a == nq_nospace)
                     || nalias.contains(&nq_nospace)
-                    || (!nalias_fold.is_empty() && nalias_fold.contains(&nq_fold))
-                {
-                    score = score.max(0.95);
-                }
+                    || (!nalias_fold.is_empty() && nalias_fold.contains(&nq_fold)))
+            {
+                score = score.max(0.95);
             }
 
             if has_digit && !nq_ws.is_empty() && hay_ws.contains(&nq_ws) {
@@ -1440,7 +2606,11 @@ fn best_match_muktabodha<'a>(
         topk_insert(&mut top, (s, e), limit);
     }
     top.into_iter()
-        .map(|(s, e)| ScoredHit { entry: e, score: s })
+        .map(|(s, e)| ScoredHit {
+            entry: e,
+            score: s,
+            reason: None,
+        })
         .collect()
 }
 
@@ -1646,7 +2816,7 @@ fn resolve_title_candidates_cbeta(
 ) -> Vec<(f32, serde_json::Value)> {
     let idx = load_or_build_cbeta_index();
     let mut out: Vec<(f32, serde_json::Value)> = Vec::new();
-    for h in best_match(idx, q, limit_per_source) {
+    for h in best_match(&idx, q, limit_per_source) {
         if h.score < min_score {
             continue;
         }
@@ -1677,7 +2847,7 @@ fn resolve_title_candidates_tipitaka(
 ) -> Vec<(f32, serde_json::Value)> {
     let idx = load_or_build_tipitaka_index();
     let mut out: Vec<(f32, serde_json::Value)> = Vec::new();
-    for h in best_match_tipitaka(idx, q, limit_per_source) {
+    for h in best_match_tipitaka(&idx, q, limit_per_source) {
         if h.score < min_score {
             continue;
         }
@@ -1714,7 +2884,7 @@ fn resolve_title_candidates_gretil(
 ) -> Vec<(f32, serde_json::Value)> {
     let idx = load_or_build_gretil_index();
     let mut out: Vec<(f32, serde_json::Value)> = Vec::new();
-    for h in best_match_gretil(idx, q, limit_per_source) {
+    for h in best_match_gretil(&idx, q, limit_per_source) {
         if h.score < min_score {
             continue;
         }
@@ -1746,7 +2916,7 @@ fn resolve_title_candidates_sarit(
 ) -> Vec<(f32, serde_json::Value)> {
     let idx = load_or_build_sarit_index();
     let mut out: Vec<(f32, serde_json::Value)> = Vec::new();
-    for h in best_match_sarit(idx, q, limit_per_source) {
+    for h in best_match_sarit(&idx, q, limit_per_source) {
         if h.score < min_score {
             continue;
         }
@@ -1778,7 +2948,7 @@ fn resolve_title_candidates_muktabodha(
 ) -> Vec<(f32, serde_json::Value)> {
     let idx = load_or_build_muktabodha_index();
     let mut out: Vec<(f32, serde_json::Value)> = Vec::new();
-    for h in best_match_muktabodha(idx, q, limit_per_source) {
+    for h in best_match_muktabodha(&idx, q, limit_per_source) {
         if h.score < min_score {
             continue;
         }
@@ -1808,472 +2978,3541 @@ fn resolve_title_candidates_muktabodha(
 
 // removed: unused helper
 
-fn slice_text(text: &str, args: &serde_json::Value) -> String {
-    // Slice by character positions (safe for UTF-8).
-    let default_max = 8000usize;
-    let start_char = args
-        .get("page")
-        .and_then(|v| v.as_u64())
-        .and_then(|p| {
-            args.get("pageSize")
-                .and_then(|s| s.as_u64())
-                .map(|ps| (p as usize) * (ps as usize))
-        })
-        .or_else(|| {
-            args.get("startChar")
-                .and_then(|v| v.as_u64().map(|x| x as usize))
-        })
-        .unwrap_or(0);
-    let total_chars = text.chars().count();
-    let start_char = std::cmp::min(start_char, total_chars);
-    let end_char = if let (Some(p), Some(ps)) = (
-        args.get("page").and_then(|v| v.as_u64()),
-        args.get("pageSize").and_then(|v| v.as_u64()),
-    ) {
-        Some((p as usize) * (ps as usize) + (ps as usize))
-    } else if let Some(ec) = args.get("endChar").and_then(|v| v.as_u64()) {
-        Some(ec as usize)
-    } else if let Some(mc) = args.get("maxChars").and_then(|v| v.as_u64()) {
-        Some(start_char + mc as usize)
-    } else {
-        None
-    };
-    let end_char = end_char
-        .map(|e| std::cmp::min(e, total_chars))
-        .unwrap_or_else(|| std::cmp::min(start_char + default_max, total_chars));
-    if start_char >= end_char {
-        return String::new();
+/// Local corpus root for a source name (cbeta/tipitaka/gretil/sarit/muktabodha).
+fn local_source_root(source: &str) -> Option<PathBuf> {
+    match source {
+        "cbeta" => Some(cbeta_root()),
+        "tipitaka" => Some(tipitaka_root()),
+        "gretil" => Some(gretil_root()),
+        "sarit" => Some(sarit_root()),
+        "muktabodha" => Some(muktabodha_root()),
+        _ => None,
     }
-    // Convert char indices to byte indices
-    let s_byte = text
-        .char_indices()
-        .nth(start_char)
-        .map(|(b, _)| b)
-        .unwrap_or(text.len());
-    let e_byte = text
-        .char_indices()
-        .nth(end_char)
-        .map(|(b, _)| b)
-        .unwrap_or(text.len());
-    if s_byte > e_byte {
-        return String::new();
+}
+
+/// The index of `source` if this process has already loaded it.
+fn loaded_index(source: &str) -> Option<Index> {
+    index_cache(source)?.loaded()
+}
+
+fn index_cache(source: &str) -> Option<&'static IndexCache> {
+    match source {
+        "cbeta" => Some(&CBETA_INDEX_CACHE),
+        "tipitaka" => Some(&TIPITAKA_INDEX_CACHE),
+        "gretil" => Some(&GRETIL_INDEX_CACHE),
+        "sarit" => Some(&SARIT_INDEX_CACHE),
+        "muktabodha" => Some(&MUKTABODHA_INDEX_CACHE),
+        _ => None,
     }
-    text[s_byte..e_byte].to_string()
 }
 
-fn slice_text_bounds(
-    text: &str,
-    start_char: usize,
-    max_chars: usize,
-) -> (String, usize, usize, usize) {
-    let total_chars = text.chars().count();
-    let effective_start = std::cmp::min(start_char, total_chars);
-    let target_end = effective_start.saturating_add(max_chars);
-    let effective_end = std::cmp::min(target_end, total_chars);
+/// Revision of each local corpus and the state of its index cache: the data it was built
+/// from, when, and how many entries it holds (from memory when loaded, else from the file).
+fn corpus_status() -> Vec<serde_json::Value> {
+    use daizo_core::health;
+    ["cbeta", "tipitaka", "gretil", "sarit", "muktabodha"]
+        .iter()
+        .map(|c| {
+            let root = local_source_root(c).unwrap_or_default();
+            let rev = daizo_core::repo::corpus_revision(&root).unwrap_or_default();
+            let index = cache_dir().join(format!("{}-index.json", c));
+            let present = corpus_present(c);
+            let exists = index.exists();
+            let current = daizo_core::repo::index_is_current(&index, &root);
+            let loaded = loaded_index(c);
+            let entries = match &loaded {
+                Some(v) => Some(v.len()),
+                None if exists => health::index_entry_count(&index),
+                None => None,
+            };
+            json!({
+                "corpus": c,
+                "root": root.to_string_lossy(),
+                "present": present,
+                "commit": rev.commit,
+                "cloned": rev.cloned,
+                "index": {
+                    "path": index.to_string_lossy(),
+                    "exists": exists,
+                    "entries": entries,
+                    "bytes": fs::metadata(&index).ok().map(|m| m.len()),
+                    "builtAt": health::modified_utc(&index),
+                    "builtFrom": daizo_core::repo::index_revision(&index),
+                    "current": current,
+                    "loaded": loaded.is_some(),
+                },
+                "rebuildRecommended": present && (!exists || current == Some(false)),
+            })
+        })
+        .collect()
+}
 
-    let start_byte = if effective_start == total_chars {
-        text.len()
-    } else {
-        text.char_indices()
-            .nth(effective_start)
-            .map(|(idx, _)| idx)
-            .unwrap_or(text.len())
-    };
-    let end_byte = if effective_end == total_chars {
-        text.len()
-    } else {
-        text.char_indices()
-            .nth(effective_end)
-            .map(|(idx, _)| idx)
-            .unwrap_or(text.len())
-    };
+/// Whether a local corpus has any data (MUKTABODHA's directory is created empty).
+fn corpus_present(source: &str) -> bool {
+    local_source_root(source)
+        .and_then(|root| fs::read_dir(root).ok())
+        .is_some_and(|mut d| d.next().is_some())
+}
 
-    let slice = if start_byte <= end_byte {
-        text[start_byte..end_byte].to_string()
-    } else {
-        String::new()
-    };
+/// `TextNotFound` for a fetch whose `id` (or `query`) resolved to nothing.
+fn text_not_found(source: &str, args: &serde_json::Value) -> ToolError {
+    let wanted = ["id", "query", "useid", "lineno", "url"]
+        .iter()
+        .find_map(|k| args.get(*k).and_then(|v| v.as_str()))
+        .unwrap_or("");
+    ToolError::TextNotFound {
+        corpus: source.to_string(),
+        id: wanted.to_string(),
+    }
+}
 
-    (slice, total_chars, effective_start, effective_end)
+/// Error for a remote text that came back empty: the recorded network failure if there was
+/// one, else `TextNotFound`.
+fn remote_fetch_error(source: &str, args: &serde_json::Value) -> ToolError {
+    match errors::take_network_failure() {
+        Some(reason) => ToolError::NetworkError {
+            source: source.to_string(),
+            reason,
+        },
+        None => text_not_found(source, args),
+    }
 }
 
-fn handle_call(id: serde_json::Value, params: &serde_json::Value) -> serde_json::Value {
-    let name = params.get("name").and_then(|v| v.as_str()).unwrap_or("");
-    let args = params.get("arguments").cloned().unwrap_or(json!({}));
-    let content_text = match name {
-        "daizo_version" => {
-            let data_status = json!({
-                "cbeta": cbeta_root().exists(),
-                "tipitaka": tipitaka_root().exists(),
-                "gretil": gretil_root().exists(),
-                "sarit": sarit_root().exists(),
-                "muktabodha": muktabodha_root().exists(),
-            });
-            let version_info = json!({
-                "version": VERSION,
-                "name": "daizo-mcp",
-                "description": "MCP server for Buddhist scripture retrieval (CBETA, Tipitaka, GRETIL, SARIT, MUKTABODHA, SAT, JOZEN, Tibetan online search)",
-                "homepage": "https://github.com/sinryo/daizo-mcp",
-                "data_available": data_status,
-                "data_path": daizo_home().to_string_lossy(),
-                "optimizations": [
-                    "ripgrep-based regex search",
-                    "ignore-based parallel file walking",
-                    "memory-mapped I/O",
-                    "LTO enabled"
-                ],
-                "features": [
-                    "CBETA Chinese Buddhist Canon (大正藏)",
-                    "Tipitaka Pāli Canon (パーリ聖典)",
-                    "GRETIL Sanskrit Texts (梵語文献)",
-                    "SARIT TEI P5 corpus",
-                    "MUKTABODHA Sanskrit library (IAST)",
-                    "SAT Database search",
-                    "Jodo Shu Zensho (浄土宗全書) search/fetch (online)",
-                    "Tibetan online full-text search (BUDA/BDRC, Adarshah)"
-                ]
-            });
-            return json!({
-                "jsonrpc": "2.0",
-                "id": id,
-                "result": {
-                    "content": [{"type": "text", "text": serde_json::to_string_pretty(&version_info).unwrap_or_default()}],
-                    "_meta": version_info
-                }
-            });
-        }
-        "daizo_usage" => {
-            let guide = r#"Daizo usage guide:
-
-## FASTEST: Direct ID access (no search needed!)
+/// Make sure a local corpus is present, cloning it when missing, before a tool reads it.
+fn require_corpus(source: &str) -> Result<(), ToolError> {
+    match source {
+        "cbeta" => ensure_cbeta_data(),
+        "tipitaka" => ensure_tipitaka_data(),
+        "sarit" => ensure_sarit_data(),
+        "muktabodha" => ensure_muktabodha_dir(),
+        _ => {}
+    }
+    match local_source_root(source) {
+        Some(root) if !corpus_present(source) => Err(ToolError::IndexUnavailable {
+            corpus: source.to_string(),
+            path: root.display().to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
 
-### CBETA (Chinese Buddhist Canon)
-If you know the Taisho number, use cbeta_fetch with id directly:
-- T0001 = 長阿含經 (Dirghagama)
-- T0099 = 雜阿含經 (Samyuktagama)
-- T0125 = 增壹阿含經 (Ekottaragama)
-- T0262 = 妙法蓮華經 (Lotus Sutra)
-- T0235 = 金剛般若波羅蜜經 (Diamond Sutra)
-- T0251 = 般若波羅蜜多心經 (Heart Sutra)
-- T0945 = 大佛頂首楞嚴經 (Shurangama Sutra)
-- T2076 = 景德傳燈錄
+/// Whether a resolved `path` is a file of the local corpus `source`. Index entries and
+/// directory scans can point anywhere a symlink leads; fetches only read inside the root.
+fn in_corpus(source: &str, path: &Path) -> bool {
+    local_source_root(source)
+        .is_some_and(|root| daizo_core::path_resolver::within_root(path, &root))
+}
 
-Example: cbeta_fetch({id: "T0262"}) - instant!
+/// Resolve an id to a file path for a local corpus: direct path resolution first, then the index.
+fn resolve_local_source_path(source: &str, id: &str) -> Option<PathBuf> {
+    let p = match source {
+        "cbeta" => daizo_core::path_resolver::resolve_cbeta_path_direct(id)
+            .or_else(|| {
+                load_or_build_cbeta_index()
+                    .iter()
+                    .find(|e| e.id == id)
+                    .map(|e| PathBuf::from(&e.path))
+            })
+            .or_else(|| resolve_cbeta_path_by_id(id)),
+        "tipitaka" => daizo_core::path_resolver::resolve_tipitaka_path_direct(id)
+            .or_else(|| resolve_tipitaka_by_id(&load_or_build_tipitaka_index(), id)),
+        "gretil" => daizo_core::path_resolver::resolve_gretil_path_direct(id).or_else(|| {
+            daizo_core::path_resolver::resolve_gretil_by_id(&load_or_build_gretil_index(), id)
+        }),
+        "sarit" => resolve_sarit_path_direct(id)
+            .or_else(|| resolve_sarit_by_id(&load_or_build_sarit_index(), id)),
+        "muktabodha" => resolve_muktabodha_path_direct(id)
+            .or_else(|| resolve_muktabodha_by_id(&load_or_build_muktabodha_index(), id)),
+        _ => None,
+    }?;
+    if in_corpus(source, &p) {
+        Some(p)
+    } else {
+        None
+    }
+}
 
-### Tipitaka (Pāli Canon)
-Use Nikāya codes directly with tipitaka_fetch:
-- DN = Dīghanikāya (長部) - e.g., DN1, DN2
-- MN = Majjhimanikāya (中部) - e.g., MN1, MN2
-- SN = Saṃyuttanikāya (相応部) - e.g., SN1
-- AN = Aṅguttaranikāya (増支部) - e.g., AN1
-- KN = Khuddakanikāya (小部)
+/// The slicing and highlighting arguments of a fetch call, within the call's budget.
+fn fetch_options(args: &serde_json::Value) -> FetchOptions {
+    FetchOptions::from_args(args, call_max_chars(args))
+}
 
-Examples:
-- tipitaka_fetch({id: "DN1"}) - Brahmajāla Sutta (梵網経)
-- tipitaka_fetch({id: "MN1"}) - Mūlapariyāya Sutta
-- tipitaka_fetch({id: "s0101m.mul"}) - Direct file access
+/// `_meta.highlightPositions` of a fetch: the matches in text order, or with an array
+/// `highlight`, `{pattern, positions}` for each of its patterns.
+fn highlight_positions(
+    args: &serde_json::Value,
+    highlights: &[HighlightPos],
+    by_pattern: &[Vec<HighlightPos>],
+) -> Vec<serde_json::Value> {
+    let pos = |p: &HighlightPos| json!({"startChar": p.start_char, "endChar": p.end_char});
+    let Some(entries) = args.get("highlight").and_then(|v| v.as_array()) else {
+        return highlights.iter().map(pos).collect();
+    };
+    entries
+        .iter()
+        .filter_map(fetch::highlight_spec)
+        .zip(by_pattern)
+        .map(|(spec, found)| {
+            json!({"pattern": spec.pattern, "positions": found.iter().map(pos).collect::<Vec<_>>()})
+        })
+        .collect()
+}
 
-### GRETIL (Sanskrit TEI)
-Use file stem directly with gretil_fetch:
-- saddharmapuNDarIka = 法華經 (Lotus Sutra)
-- vajracchedikA = 金剛般若經 (Diamond Sutra)
-- prajJApAramitAhRdayasUtra = 般若心經 (Heart Sutra)
-- azvaghoSa-buddhacarita = 佛所行讚 (Buddhacarita)
-- laGkAvatArasUtra = 楞伽經 (Lankavatara Sutra)
-- mahAparinirvANasUtra = 大般涅槃經 (Mahaparinirvana Sutra)
+/// Slice extracted text for a fetch (see [`fetch::slice`]); `full` reads to the end.
+fn fetch_slice(text: &str, args: &serde_json::Value, full: bool) -> FetchSlice {
+    let mut opts = fetch_options(args);
+    opts.full = full;
+    fetch::slice(text, &opts)
+}
 
-Examples:
-- gretil_fetch({id: "saddharmapuNDarIka"}) - Lotus Sutra Sanskrit
-- gretil_fetch({id: "vajracchedikA"}) - Diamond Sutra Sanskrit
-- gretil_fetch({id: "sa_azvaghoSa-buddhacarita"}) - with sa_ prefix also works
+/// A page of structural units (`pageBy`): its extracted text and what `_meta.pageBy` reports.
+struct UnitPage {
+    text: String,
+    method: String,
+    meta: serde_json::Value,
+}
 
-### SARIT (TEI P5)
-Use file stem directly with sarit_fetch:
-- sarit_fetch({id: "asvaghosa-buddhacarita"})
+/// With `pageBy`, page `page` (0-based, default 0) of `pageSize` units (default 1) of `xml`,
+/// extracted with `extract`. None without `pageBy`.
+fn unit_page(
+    args: &serde_json::Value,
+    xml: &str,
+    extract: impl Fn(&str) -> String,
+) -> Option<Result<UnitPage, ToolError>> {
+    let by = PageBy::parse(args.get("pageBy")?.as_str()?)?;
+    let arg = |k: &str| args.get(k).and_then(|v| v.as_u64()).map(|x| x as usize);
+    let page = arg("page").unwrap_or(0);
+    let page_size = arg("pageSize").unwrap_or(1).max(1);
+    let units = paging::units(xml, by);
+    if units.is_empty() {
+        return Some(Err(ToolError::InvalidArgument {
+            name: "pageBy".to_string(),
+            value: by.as_str().to_string(),
+            expected: format!("a unit this text has (it has no {} units)", by.as_str()),
+        }));
+    }
+    let page_count = units.len().div_ceil(page_size);
+    let Some((start, end)) = paging::page_range(&units, page, page_size) else {
+        return Some(Err(ToolError::InvalidArgument {
+            name: "page".to_string(),
+            value: page.to_string(),
+            expected: format!(
+                "0 to {} ({} units by {})",
+                page_count - 1,
+                units.len(),
+                page_size
+            ),
+        }));
+    };
+    let first = page * page_size;
+    let titles: Vec<&str> = units[first..(first + page_size).min(units.len())]
+        .iter()
+        .filter_map(|u| u.title.as_deref())
+        .collect();
+    Some(Ok(UnitPage {
+        text: extract(&xml[start..end]),
+        method: format!("page-by-{}", by.as_str()),
+        meta: json!({
+            "unit": by.as_str(),
+            "page": page,
+            "pageSize": page_size,
+            "pageCount": page_count,
+            "unitCount": units.len(),
+            "titles": titles,
+            "nextPage": (page + 1 < page_count).then_some(page + 1),
+        }),
+    }))
+}
 
-### MUKTABODHA (Sanskrit library; local files)
-Use file stem directly with muktabodha_fetch:
-- muktabodha_fetch({id: "<file-stem>"})
+/// `startChar`, else the first char of `startLine` (1-based, lines of the extracted text).
+fn start_char_arg(text: &str, args: &serde_json::Value) -> Option<usize> {
+    let arg = |k: &str| args.get(k).and_then(|v| v.as_u64()).map(|x| x as usize);
+    arg("startChar").or_else(|| arg("startLine").map(|l| char_at_line(text, l)))
+}
 
-## Online sources (no local data needed)
+/// `_meta.lineMap` of chars `start..end` of `text`: the 1-based line the slice starts on and
+/// the char offset at which it and each following line begins (the first is `start`). Line
+/// `firstLine + i` runs from `starts[i]` to `starts[i + 1]`, the last one to `end`.
+fn line_map(text: &str, start: usize, end: usize) -> serde_json::Value {
+    json!({"firstLine": line_at_char(text, start), "starts": fetch::line_starts(text, start, end)})
+}
 
-### SAT Database (online)
-Flow: sat_search -> sat_detail/sat_fetch (prefer useid)
-- sat_search({query: "法華経", exact: true})
-- sat_detail({useid: "..."})  # use startid/useid from SAT search
+/// Arguments that position a slice; a continuation cursor replaces them.
+const SLICE_ARGS: &[&str] = &[
+    "cursor",
+    "startChar",
+    "endChar",
+    "startLine",
+    "endLine",
+    "page",
+    "pageSize",
+];
 
-### Jodo Shu Zensho (浄土宗全書, online)
-Flow: jozen_search -> jozen_fetch (lineno)
-- jozen_search({query: "薬師"})
-- jozen_fetch({lineno: "J01_0200B19"})
+/// Opaque continuation token for the text after char `end`: the tool, its arguments without
+/// slice positions, and the char offset to resume at (hex-encoded JSON). None at the end.
+fn next_cursor(tool: &str, args: &serde_json::Value, end: usize, total: usize) -> Option<String> {
+    if end >= total {
+        return None;
+    }
+    let mut rest = args.clone();
+    let by_unit = args.get("pageBy").is_some();
+    if let Some(o) = rest.as_object_mut() {
+        for k in SLICE_ARGS {
+            // A unit page stays the same page; the cursor resumes inside it.
+            if !(by_unit && matches!(*k, "page" | "pageSize")) {
+                o.remove(*k);
+            }
+        }
+    }
+    let raw = json!({"tool": tool, "args": rest, "startChar": end}).to_string();
+    Some(raw.bytes().map(|b| format!("{:02x}", b)).collect())
+}
 
-### Tibetan full-text search (online)
-- tibetan_search({query: "བདེ་བ", sources: ["buda","adarshah"], exact: true})
-  - Tibetan Unicode or EWTS/Wylie accepted (best-effort auto-conversion)
+/// Expand `arguments.cursor` into the arguments it was issued for, resuming at its offset.
+/// Explicit arguments other than slice positions (e.g. `maxChars`, `highlight`) still apply.
+fn apply_cursor(name: &str, args: &mut serde_json::Value) -> Result<(), String> {
+    let Some(cursor) = args.get("cursor").and_then(|v| v.as_str()) else {
+        return Ok(());
+    };
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(cursor.get(i..i + 2).unwrap_or("zz"), 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| "malformed cursor".to_string())?;
+    let c: serde_json::Value = serde_json::from_slice(&bytes).map_err(|_| "malformed cursor")?;
+    if c.get("tool").and_then(|v| v.as_str()) != Some(name) {
+        return Err("cursor was issued by another tool".to_string());
+    }
+    let mut merged = c.get("args").cloned().unwrap_or(json!({}));
+    if let (Some(m), Some(explicit)) = (merged.as_object_mut(), args.as_object()) {
+        for (k, v) in explicit {
+            if !SLICE_ARGS.contains(&k.as_str()) {
+                m.insert(k.clone(), v.clone());
+            }
+        }
+        m.insert("startChar".to_string(), c["startChar"].clone());
+    }
+    *args = merged;
+    Ok(())
+}
 
-## If corpus/ID is unknown
-- Use daizo_resolve({query: "法華経"}) first, then follow the recommended fetch calls.
+// ============ Scoped search ============
 
-## Standard flow (when ID unknown)
-1. Use *_search -> read _meta.fetchSuggestions
-2. Call *_fetch with {id, lineNumber, contextBefore:1, contextAfter:3, highlight:"検索語", format:"plain"}
-3. IMPORTANT: Always include 'highlight' parameter with the search term!
-4. Use *_pipeline only for multi-file summary; set autoFetch=false by default
+/// Files a search is limited to by its `scope` argument (an id or a list of ids).
+struct SearchScope {
+    paths: Vec<PathBuf>,
+    /// Ids that did not resolve to a file (reported in `_meta.scopeUnresolved`).
+    unresolved: Vec<String>,
+}
 
-## Troubleshooting: If lineNumber doesn't work
-- ALWAYS pass 'highlight' param with search term for verification
-- Use larger contextBefore/contextAfter (e.g., 10-20 lines)
-- For very long texts, use part/juan parameter instead (CBETA): cbeta_fetch({id:"T0279", part:"001"})
-"#;
-            return json!({
-                "jsonrpc":"2.0",
-                "id": id,
-                "result": { "content": [{"type":"text","text": guide}], "_meta": {"source": "daizo_usage"} }
-            });
+/// Resolve `args.scope`. `Ok(None)` when there is no scope; an error when none of the ids
+/// names a text of `corpus`.
+fn search_scope(corpus: &str, args: &serde_json::Value) -> Result<Option<SearchScope>, ToolError> {
+    let ids: Vec<String> = match args.get("scope") {
+        Some(serde_json::Value::String(s)) => s
+            .split(',')
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect(),
+        Some(serde_json::Value::Array(a)) => a
+            .iter()
+            .filter_map(|v| v.as_str())
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect(),
+        _ => return Ok(None),
+    };
+    if ids.is_empty() {
+        return Ok(None);
+    }
+    let mut paths: Vec<PathBuf> = Vec::new();
+    let mut unresolved = Vec::new();
+    for i in &ids {
+        match resolve_local_source_path(corpus, i) {
+            Some(p) if !paths.contains(&p) => paths.push(p),
+            Some(_) => {}
+            None => unresolved.push(i.clone()),
         }
-        "daizo_profile" => {
-            let tool = args
-                .get("tool")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .trim()
-                .to_string();
-            let call_args = args.get("arguments").cloned().unwrap_or(json!({}));
-            let iterations = args
-                .get("iterations")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(10)
-                .clamp(1, 10_000) as usize;
-            let warmup = args
-                .get("warmup")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(1)
-                .clamp(0, 10_000) as usize;
-            let include_samples = args
-                .get("includeSamples")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(false);
+    }
+    if paths.is_empty() {
+        return Err(ToolError::TextNotFound {
+            corpus: corpus.to_string(),
+            id: ids.join(","),
+        });
+    }
+    Ok(Some(SearchScope { paths, unresolved }))
+}
 
-            if tool.is_empty() || tool == "daizo_profile" {
-                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "invalid tool"}], "_meta": {"tool": tool, "ok": false} }});
+/// Grep `corpus` for `pattern`, over the whole corpus or only the files in `scope`.
+fn corpus_grep(
+    corpus: &str,
+    scope: Option<&SearchScope>,
+    pattern: &str,
+    max_results: usize,
+    max_matches_per_file: usize,
+) -> Vec<daizo_core::GrepResult> {
+    if let Some(sc) = scope {
+        return match corpus {
+            "cbeta" => {
+                daizo_core::cbeta_grep_scoped(&sc.paths, pattern, max_results, max_matches_per_file)
             }
+            "tipitaka" => daizo_core::tipitaka_grep_scoped(
+                &sc.paths,
+                pattern,
+                max_results,
+                max_matches_per_file,
+                false,
+            ),
+            _ => daizo_core::grep_scoped(&sc.paths, pattern, max_results, max_matches_per_file),
+        };
+    }
+    let Some((cache, rev)) = grep_cache_for(corpus) else {
+        return corpus_grep_walk(corpus, pattern, max_results, max_matches_per_file);
+    };
+    let key = GrepKey::new(corpus, pattern, max_results, max_matches_per_file);
+    if let Some(hit) = cache.get(&key, &rev) {
+        return hit;
+    }
+    let results = corpus_grep_walk(corpus, pattern, max_results, max_matches_per_file);
+    // A cancelled search holds partial results.
+    if !daizo_core::cancel::CancelToken::current().is_cancelled() {
+        if let Err(e) = cache.put(&key, &rev, &results) {
+            tracing::warn!(corpus, error = %e, "grep cache write failed");
+        }
+    }
+    results
+}
 
-            let params = json!({"name": tool, "arguments": call_args});
+/// `DAIZO_GREP_CACHE` (off/0 disables) and `DAIZO_GREP_CACHE_MAX_ENTRIES`, else the
+/// `[grep_cache]` section of config.toml; with the revision of the corpus checkout.
+fn grep_cache_for(corpus: &str) -> Option<(GrepCache, String)> {
+    let cfg = config::current().grep_cache.clone();
+    let enabled = match std::env::var("DAIZO_GREP_CACHE")
+        .ok()
+        .map(|v| v.trim().to_ascii_lowercase())
+    {
+        Some(v) => !matches!(v.as_str(), "0" | "false" | "off" | "no"),
+        None => cfg.enabled.unwrap_or(true),
+    };
+    if !enabled {
+        return None;
+    }
+    let rev = daizo_core::grep_cache::revision(&local_source_root(corpus)?)?;
+    let max = setting_usize(
+        "DAIZO_GREP_CACHE_MAX_ENTRIES",
+        cfg.max_entries,
+        daizo_core::grep_cache::DEFAULT_MAX_ENTRIES,
+    );
+    Some((
+        GrepCache::new(daizo_core::grep_cache::default_dir(), max),
+        rev,
+    ))
+}
 
-            // Warmup (ignore timings)
-            for _ in 0..warmup {
-                let _ = handle_call(json!(0), &params);
-            }
+fn corpus_grep_walk(
+    corpus: &str,
+    pattern: &str,
+    max_results: usize,
+    max_matches_per_file: usize,
+) -> Vec<daizo_core::GrepResult> {
+    match corpus {
+        "cbeta" => cbeta_grep(&cbeta_root(), pattern, max_results, max_matches_per_file),
+        "tipitaka" => tipitaka_grep(&tipitaka_root(), pattern, max_results, max_matches_per_file),
+        "gretil" => gretil_grep(&gretil_root(), pattern, max_results, max_matches_per_file),
+        "sarit" => sarit_grep(&sarit_root(), pattern, max_results, max_matches_per_file),
+        "muktabodha" => muktabodha_grep(
+            &muktabodha_root(),
+            pattern,
+            max_results,
+            max_matches_per_file,
+        ),
+        _ => Vec::new(),
+    }
+}
 
-            let mut samples_ms: Vec<f64> = Vec::with_capacity(iterations);
-            for i in 0..iterations {
-                let t0 = Instant::now();
-                let _ = handle_call(json!(i as u64), &params);
-                let dt = t0.elapsed().as_secs_f64() * 1000.0;
-                samples_ms.push(dt);
-            }
+/// `_meta` fields describing the scope of a search (none when unscoped).
+fn scope_meta(meta: &mut serde_json::Value, scope: Option<&SearchScope>) {
+    if let Some(sc) = scope {
+        meta["scopeFiles"] = json!(sc.paths.len());
+        if !sc.unresolved.is_empty() {
+            meta["scopeUnresolved"] = json!(sc.unresolved);
+        }
+    }
+}
 
-            let mut sorted = samples_ms.clone();
-            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-            let mean_ms = if samples_ms.is_empty() {
-                0.0
-            } else {
-                samples_ms.iter().sum::<f64>() / (samples_ms.len() as f64)
+// ============ Metadata filters ============
+
+/// A string or array-of-strings argument, split on commas.
+fn str_list_arg(args: &serde_json::Value, key: &str) -> Vec<String> {
+    let items: Vec<&str> = match args.get(key) {
+        Some(serde_json::Value::String(s)) => s.split(',').collect(),
+        Some(serde_json::Value::Array(a)) => a.iter().filter_map(|v| v.as_str()).collect(),
+        _ => Vec::new(),
+    };
+    items
+        .into_iter()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// `canon`, `nnumRange` and `juanCountMin`/`juanCountMax` of the CBETA search tools.
+fn cbeta_filter(args: &serde_json::Value) -> Result<CbetaFilter, ToolError> {
+    let nnum = match args.get("nnumRange") {
+        None | Some(serde_json::Value::Null) => None,
+        Some(v) => {
+            let s = match v {
+                serde_json::Value::Number(n) => n.to_string(),
+                v => v.as_str().unwrap_or("").trim().to_string(),
             };
-            let pct = |p: f64| -> f64 {
-                if sorted.is_empty() {
-                    return 0.0;
+            match daizo_core::filters::parse_number_range(&s) {
+                Some(r) => Some(r),
+                None if s.is_empty() => None,
+                None => {
+                    return Err(ToolError::InvalidArgument {
+                        name: "nnumRange".to_string(),
+                        value: s,
+                        expected: "a text number or an inclusive range such as 220-262".to_string(),
+                    })
                 }
-                let n = sorted.len();
-                let idx = ((p * (n as f64 - 1.0)).round() as usize).min(n - 1);
-                sorted[idx]
-            };
-            let min_ms = *sorted.first().unwrap_or(&0.0);
-            let max_ms = *sorted.last().unwrap_or(&0.0);
-            let p50 = pct(0.50);
-            let p90 = pct(0.90);
-            let p95 = pct(0.95);
-            let p99 = pct(0.99);
-
-            let summary = format!(
-                "daizo_profile tool={}\niterations={} warmup={}\nmin={:.3}ms p50={:.3}ms mean={:.3}ms p90={:.3}ms p95={:.3}ms p99={:.3}ms max={:.3}ms\n",
-                tool, iterations, warmup, min_ms, p50, mean_ms, p90, p95, p99, max_ms
-            );
+            }
+        }
+    };
+    let count = |k: &str| args.get(k).and_then(|v| v.as_u64()).map(|n| n as u32);
+    Ok(CbetaFilter {
+        canons: str_list_arg(args, "canon"),
+        nnum,
+        juan_min: count("juanCountMin"),
+        juan_max: count("juanCountMax"),
+    })
+}
 
-            let meta = json!({
-                "tool": tool,
-                "iterations": iterations,
-                "warmup": warmup,
-                "minMs": min_ms,
-                "p50Ms": p50,
-                "meanMs": mean_ms,
-                "p90Ms": p90,
-                "p95Ms": p95,
-                "p99Ms": p99,
-                "maxMs": max_ms,
-                "samplesMs": if include_samples { Some(samples_ms) } else { None::<Vec<f64>> }
+/// `basket`, `nikaya` and `edition` of the Tipiṭaka search tools.
+fn tipitaka_filter(args: &serde_json::Value) -> Result<TipitakaFilter, ToolError> {
+    let one_of = |key: &str, allowed: &[&str]| -> Result<Vec<String>, ToolError> {
+        let values: Vec<String> = str_list_arg(args, key)
+            .into_iter()
+            .map(|v| v.to_ascii_lowercase())
+            .collect();
+        match values.iter().find(|v| !allowed.contains(&v.as_str())) {
+            Some(bad) => Err(ToolError::InvalidArgument {
+                name: key.to_string(),
+                value: bad.clone(),
+                expected: format!("one of {}", allowed.join(", ")),
+            }),
+            None => Ok(values),
+        }
+    };
+    Ok(TipitakaFilter {
+        basket: one_of("basket", &TipitakaFilter::BASKETS)?,
+        nikaya: str_list_arg(args, "nikaya"),
+        edition: one_of("edition", &TipitakaFilter::EDITIONS)?,
+    })
+}
+
+/// `author`, `genre` and `century` of `gretil_title_search`.
+fn gretil_filter(args: &serde_json::Value) -> Result<GretilFilter, ToolError> {
+    let century =
+        match args.get("century") {
+            None | Some(serde_json::Value::Null) => None,
+            Some(v) => {
+                let s = match v {
+                    serde_json::Value::Number(n) => n.to_string(),
+                    v => v.as_str().unwrap_or("").trim().to_string(),
+                };
+                match daizo_core::filters::parse_century_range(&s) {
+                    Some(r) => Some(r),
+                    None if s.is_empty() => None,
+                    None => return Err(ToolError::InvalidArgument {
+                        name: "century".to_string(),
+                        value: s,
+                        expected:
+                            "a century or range such as 5, 4-6 or -2 (BCE); there is no century 0"
+                                .to_string(),
+                    }),
+                }
+            }
+        };
+    Ok(GretilFilter {
+        author: str_list_arg(args, "author"),
+        genre: str_list_arg(args, "genre"),
+        century,
+    })
+}
+
+/// Restrict a search to the index entries that pass `keep`: the files of `scope` that do,
+/// or all of them when the search is unscoped.
+fn filter_scope(
+    scope: Option<SearchScope>,
+    entries: &[IndexEntry],
+    keep: impl Fn(&IndexEntry) -> bool,
+) -> SearchScope {
+    let kept = entries.iter().filter(|e| keep(e));
+    match scope {
+        Some(mut sc) => {
+            let stems: std::collections::HashSet<&str> = kept.map(|e| e.id.as_str()).collect();
+            sc.paths.retain(|p| {
+                p.file_stem()
+                    .and_then(|s| s.to_str())
+                    .is_some_and(|s| stems.contains(s))
             });
+            sc
+        }
+        None => SearchScope {
+            paths: kept.map(|e| PathBuf::from(&e.path)).collect(),
+            unresolved: Vec::new(),
+        },
+    }
+}
 
-            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary}], "_meta": meta }});
+// ============ Multi-pattern search ============
+
+/// `query` given as an array: each element is rewritten like a single query, and the corpus
+/// is walked once for the alternation of the results.
+struct MultiQuery {
+    queries: Vec<String>,
+    patterns: Vec<String>,
+    set: daizo_core::multi::PatternSet,
+}
+
+fn multi_query(corpus: &str, args: &serde_json::Value) -> Result<Option<MultiQuery>, ToolError> {
+    let Some(arr) = args.get("query").and_then(|v| v.as_array()) else {
+        return Ok(None);
+    };
+    let queries: Vec<String> = arr
+        .iter()
+        .filter_map(|v| v.as_str())
+        .map(|q| q.trim().to_string())
+        .filter(|q| !q.is_empty())
+        .collect();
+    if queries.is_empty() {
+        return Ok(None);
+    }
+    if query_mode_boolean(args) {
+        return Err(ToolError::InvalidQuery {
+            query: queries.join(" | "),
+            reason: "queryMode:\"boolean\" takes one query string; join the terms with OR"
+                .to_string(),
+        });
+    }
+    let patterns: Vec<String> = queries
+        .iter()
+        .map(|q| rewrite_query(corpus, q, args).pattern)
+        .collect();
+    let set = daizo_core::multi::PatternSet::new(&patterns).map_err(|(i, error)| {
+        ToolError::InvalidRegex {
+            pattern: queries[i].clone(),
+            error,
         }
-        "daizo_resolve" => {
-            let q = args
-                .get("query")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .trim();
-            if q.is_empty() {
-                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "query is empty"}], "_meta": {"query": q, "count": 0, "candidates": []} }});
+    })?;
+    Ok(Some(MultiQuery {
+        queries,
+        patterns,
+        set,
+    }))
+}
+
+impl MultiQuery {
+    fn pattern(&self) -> String {
+        daizo_core::multi::combined(&self.patterns)
+    }
+
+    /// "   Patterns: 般若 ×2, 空 ×1" for the patterns found among the matches shown for `r`.
+    fn summary_line(&self, r: &daizo_core::GrepResult) -> String {
+        let found: Vec<String> = self
+            .set
+            .counts(r)
+            .iter()
+            .zip(&self.queries)
+            .filter(|(n, _)| **n > 0)
+            .map(|(n, q)| format!("{} ×{}", q, n))
+            .collect();
+        if found.is_empty() {
+            return String::new();
+        }
+        format!("   Patterns: {}\n", found.join(", "))
+    }
+
+    /// `queries` and, per result, `patternCounts` (matches of each query among those shown);
+    /// `filesPerQuery` counts the files in which each query was found.
+    fn annotate_meta(&self, meta: &mut serde_json::Value, results: &[daizo_core::GrepResult]) {
+        let counts: Vec<Vec<usize>> = results.iter().map(|r| self.set.counts(r)).collect();
+        let files: Vec<usize> = (0..self.queries.len())
+            .map(|i| counts.iter().filter(|c| c[i] > 0).count())
+            .collect();
+        if let Some(arr) = meta.get_mut("results").and_then(|v| v.as_array_mut()) {
+            for (r, c) in arr.iter_mut().zip(&counts) {
+                r["patternCounts"] = json!(c);
             }
+        }
+        meta["queries"] = json!(self.queries);
+        meta["filesPerQuery"] = json!(files);
+    }
+}
 
-            let sources: Vec<String> = args
-                .get("sources")
-                .and_then(|v| v.as_array())
-                .map(|a| {
-                    a.iter()
-                        .filter_map(|x| x.as_str().map(|s| s.to_string()))
-                        .collect::<Vec<_>>()
-                })
-                .unwrap_or_else(|| {
-                    vec![
-                        "cbeta".to_string(),
-                        "tipitaka".to_string(),
-                        "gretil".to_string(),
-                        "sarit".to_string(),
-                        "muktabodha".to_string(),
-                    ]
-                });
-            let limit_per_source = args
-                .get("limitPerSource")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(5) as usize;
-            let limit_total = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
-            let prefer_source = args
-                .get("preferSource")
+// ============ Annotations ============
+
+/// Text and `_meta` for a list of annotations: one line each, with a fetch suggestion.
+fn annotations_response(
+    id: &serde_json::Value,
+    heading: String,
+    found: &[daizo_core::annotations::Annotation],
+    limit: usize,
+) -> serde_json::Value {
+    let mut lines = vec![heading];
+    let mut items = Vec::new();
+    for a in found.iter().take(limit) {
+        let part = a
+            .part
+            .as_deref()
+            .map(|p| format!(" juan {}", p))
+            .unwrap_or_default();
+        let tags: String = a.tags.iter().map(|t| format!(" #{}", t)).collect();
+        let note = if a.is_bookmark() {
+            "(bookmark)"
+        } else {
+            a.note.as_str()
+        };
+        lines.push(format!(
+            "{} {} {}{} [{}..{}]{} {} 「{}」",
+            a.id, a.source, a.text_id, part, a.start_char, a.end_char, tags, note, a.quote
+        ));
+        let mut args = json!({"id": a.text_id, "startChar": a.start_char, "endChar": a.end_char});
+        if a.source == "cbeta" {
+            args["format"] = json!("plain");
+        }
+        if let Some(p) = &a.part {
+            args["part"] = json!(p);
+        }
+        let mut item = json!(a);
+        item["fetch"] = json!({"tool": format!("{}_fetch", a.source), "args": args});
+        items.push(item);
+    }
+    let meta = json!({
+        "count": found.len(),
+        "truncated": found.len() > limit,
+        "annotations": items,
+    });
+    json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": lines.join("\n")}], "_meta": meta }})
+}
+
+// ============ Collections ============
+
+/// Items of a `collection_create` / `collection_add` call: `items`, or a single `id`.
+fn collection_items(
+    args: &serde_json::Value,
+) -> Result<Vec<daizo_core::collections::Item>, ToolError> {
+    let specs: Vec<serde_json::Value> = match args.get("items").and_then(|v| v.as_array()) {
+        Some(a) => a.clone(),
+        None if args.get("id").is_some() => vec![args.clone()],
+        None => Vec::new(),
+    };
+    let mut out = Vec::with_capacity(specs.len());
+    for spec in &specs {
+        let s = |k: &str| {
+            spec.get(k)
                 .and_then(|v| v.as_str())
-                .map(|s| s.to_string());
-            let min_score = args.get("minScore").and_then(|v| v.as_f64()).unwrap_or(0.1) as f32;
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let source = s("source")
+            .unwrap_or_else(|| "cbeta".to_string())
+            .to_lowercase();
+        if local_source_root(&source).is_none() {
+            return Err(ToolError::InvalidArgument {
+                name: "source".to_string(),
+                value: source,
+                expected: capabilities::LOCAL_CORPORA.join(", "),
+            });
+        }
+        let wid = s("id").unwrap_or_default();
+        if !daizo_core::path_resolver::safe_id(&wid) {
+            return Err(ToolError::InvalidArgument {
+                name: "id".to_string(),
+                value: wid,
+                expected: "a text id (T0262, s0101m.mul, file stem)".to_string(),
+            });
+        }
+        out.push(daizo_core::collections::Item {
+            source,
+            id: wid,
+            part: s("part"),
+            section: s("section"),
+            note: s("note"),
+        });
+    }
+    Ok(out)
+}
 
-            let mut cands_scored: Vec<(f32, serde_json::Value)> = Vec::new();
+fn collection_name_error(name: &str) -> ToolError {
+    ToolError::InvalidArgument {
+        name: "name".to_string(),
+        value: name.to_string(),
+        expected: "a collection name: letters, digits, '-', '_' or '.'".to_string(),
+    }
+}
 
-            // Direct ID detection (fast path)
-            let mut direct_id_mode = false;
-            let q_nospace = q.split_whitespace().collect::<String>();
-            let q_upper = q_nospace.to_ascii_uppercase();
-            // CBETA: T + 1-4 digits
-            if sources.iter().any(|s| s == "cbeta") && q_upper.starts_with('T') {
-                let digits: String = q_upper
-                    .chars()
-                    .skip(1)
-                    .take_while(|c| c.is_ascii_digit())
-                    .collect();
-                if !digits.is_empty() {
-                    if let Ok(n) = digits.parse::<u32>() {
-                        let id_norm = format!("T{:04}", n);
-                        let bias = if prefer_source.as_deref() == Some("cbeta") {
-                            0.02
-                        } else {
-                            0.0
-                        };
-                        let cand = json!({
-                                "source": "cbeta",
-                                "id": id_norm,
-                                "title": null,
-                                "score": 1.0,
-                                "fetch": {"tool": "cbeta_fetch", "args": {"id": format!("T{:04}", n)}},
-                                "resolvedBy": "direct-id"
-                        });
-                        cands_scored.push((1.20 + bias, cand));
-                        direct_id_mode = true;
-                    }
-                }
+// ============ Research bundles ============
+
+/// Commit of each local corpus checkout (corpora that are not git checkouts are left out).
+fn corpus_snapshots() -> std::collections::BTreeMap<String, String> {
+    ["cbeta", "tipitaka", "gretil", "sarit", "muktabodha"]
+        .iter()
+        .filter_map(|c| {
+            let root = local_source_root(c)?;
+            Some((c.to_string(), daizo_core::provenance::git_snapshot(&root)?))
+        })
+        .collect()
+}
+
+/// Run one bundle step in-process; returns its text and `_meta`. The provenance footer is
+/// left out because it carries the retrieval date.
+fn run_bundle_step(
+    tool: &str,
+    args: &serde_json::Value,
+) -> Result<(String, Option<serde_json::Value>), String> {
+    if tool.is_empty() || tool.starts_with("research_bundle") || tool == "daizo_profile" {
+        return Err(format!("tool '{}' cannot be recorded in a bundle", tool));
+    }
+    let mut args = args.clone();
+    if let Some(o) = args.as_object_mut() {
+        o.insert("provenance".to_string(), json!(false));
+    }
+    let resp = handle_call(json!(0), &json!({"name": tool, "arguments": args}));
+    let text = resp
+        .pointer("/result/content/0/text")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    if errors::is_error_response(&resp) {
+        let msg = resp
+            .pointer("/error/message")
+            .and_then(|v| v.as_str())
+            .map(String::from)
+            .unwrap_or(text);
+        return Err(msg);
+    }
+    Ok((text, resp.pointer("/result/_meta").cloned()))
+}
+
+// ============ Batch fetch ============
+
+/// Most specs one `<corpus>_fetch_batch` call takes.
+const FETCH_BATCH_MAX: usize = 50;
+
+/// Schema of `<corpus>_fetch_batch`: `items` of `<corpus>_fetch` arguments plus shared `defaults`.
+fn fetch_batch_tool(corpus: &str, example: &str) -> serde_json::Value {
+    tool(
+        &format!("{}_fetch_batch", corpus),
+        &format!("Run several {c}_fetch calls in one round trip: each item takes the {c}_fetch arguments (id with part, lineNumber, lb, matchId, …) and the result has one content block per item, in order. defaults apply to every item unless it sets the key itself. A failing item is reported in its block and _meta.items without failing the batch. Example items: {e}", c = corpus, e = example),
+        json!({"type":"object","properties":{
+            "items":{"type":"array","items":{"type":"object"},"maxItems":FETCH_BATCH_MAX,"description":format!("{}_fetch arguments, one object per slice (at most {})", corpus, FETCH_BATCH_MAX)},
+            "defaults":{"type":"object","description":"Arguments shared by all items, e.g. {\"maxChars\":2000,\"highlight\":\"...\"}"}
+        },"required":["items"]}),
+    )
+}
+
+/// `<corpus>_fetch_batch`: each item goes through the full `<corpus>_fetch` call path
+/// (cursor, matchId, reference, aliases), so it returns what the single call would.
+fn fetch_batch(id: serde_json::Value, name: &str, args: &serde_json::Value) -> serde_json::Value {
+    let corpus = name.trim_end_matches("_fetch_batch");
+    let Some(&(fetch, _)) = MATCH_ID_TOOLS.iter().find(|(_, c)| *c == corpus) else {
+        return ToolError::UnknownTool(name.to_string()).response(&id);
+    };
+    let items = match args.get("items").and_then(|v| v.as_array()) {
+        Some(a)
+            if !a.is_empty() && a.len() <= FETCH_BATCH_MAX && a.iter().all(|i| i.is_object()) =>
+        {
+            a
+        }
+        _ => {
+            return ToolError::InvalidArgument {
+                name: "items".to_string(),
+                value: args.get("items").map(|v| v.to_string()).unwrap_or_default(),
+                expected: format!("an array of 1 to {} argument objects", FETCH_BATCH_MAX),
             }
-            // Tipitaka: DN/MN/SN/AN/KN + digits
-            if sources.iter().any(|s| s == "tipitaka") {
-                for pref in ["DN", "MN", "SN", "AN", "KN"] {
-                    if q_upper.starts_with(pref) {
-                        let rest = q_upper[pref.len()..].to_string();
-                        let digits: String = rest.chars().filter(|c| c.is_ascii_digit()).collect();
-                        if !digits.is_empty() {
-                            if let Ok(n) = digits.parse::<u32>() {
-                                let id_norm = format!("{}{}", pref, n);
-                                let bias = if prefer_source.as_deref() == Some("tipitaka") {
-                                    0.02
-                                } else {
-                                    0.0
-                                };
-                                let cand = json!({
-                                    "source": "tipitaka",
-                                    "id": id_norm,
-                                    "title": null,
-                                    "score": 1.0,
-                                    "fetch": {"tool": "tipitaka_fetch", "args": {"id": format!("{}{}", pref, n)}},
-                                    "resolvedBy": "direct-id"
-                                });
-                                cands_scored.push((1.20 + bias, cand));
-                                direct_id_mode = true;
-                            }
-                        }
-                    }
-                }
+            .response(&id);
+        }
+    };
+    let defaults = args.get("defaults").and_then(|v| v.as_object());
+    let mut content = Vec::with_capacity(items.len());
+    let mut report = Vec::with_capacity(items.len());
+    let mut failed = 0;
+    for (i, item) in items.iter().enumerate() {
+        let mut call_args = item.clone();
+        if let (Some(o), Some(d)) = (call_args.as_object_mut(), defaults) {
+            for (k, v) in d {
+                o.entry(k.clone()).or_insert_with(|| v.clone());
             }
-            // Tipitaka file stem hint
-            if sources.iter().any(|s| s == "tipitaka")
-                && q_nospace.contains(".mul")
-                && !q_nospace.contains(' ')
-            {
-                let stem = q_nospace.clone();
-                let bias = if prefer_source.as_deref() == Some("tipitaka") {
-                    0.02
-                } else {
-                    0.0
-                };
-                let cand = json!({
-                    "source": "tipitaka",
-                    "id": stem,
-                    "title": null,
-                    "score": 1.0,
-                    "fetch": {"tool": "tipitaka_fetch", "args": {"id": q_nospace}},
-                    "resolvedBy": "direct-id"
-                });
-                cands_scored.push((1.20 + bias, cand));
-                direct_id_mode = true;
+        }
+        let label = ["id", "matchId", "reference"]
+            .iter()
+            .find_map(|k| call_args.get(*k).and_then(|v| v.as_str()))
+            .unwrap_or("?")
+            .to_string();
+        let at = ["part", "lb", "lineNumber", "headQuery"]
+            .iter()
+            .find_map(|k| {
+                let v = call_args.get(*k)?;
+                Some(format!(
+                    " {} {}",
+                    k,
+                    v.as_str()
+                        .map(String::from)
+                        .unwrap_or_else(|| v.to_string())
+                ))
+            })
+            .unwrap_or_default();
+        let resp = run_call(json!(i), &json!({"name": fetch, "arguments": call_args}));
+        let text = resp
+            .pointer("/result/content/0/text")
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        let mut entry = json!({"index": i, "label": label});
+        if errors::is_error_response(&resp) {
+            failed += 1;
+            let msg = resp
+                .pointer("/error/message")
+                .and_then(|v| v.as_str())
+                .unwrap_or(text);
+            entry["ok"] = json!(false);
+            entry["error"] = json!(msg);
+            content.push(json!({"type":"text","text": format!("## {}. {}{}\n\nerror: {}", i + 1, label, at, msg)}));
+        } else {
+            entry["ok"] = json!(true);
+            entry["chars"] = json!(text.chars().count());
+            if let Some(m) = resp.pointer("/result/structuredContent") {
+                let mut m = m.clone();
+                if let Some(o) = m.as_object_mut() {
+                    o.remove("schemaVersion");
+                    o.remove("tool");
+                }
+                entry["meta"] = m;
             }
+            content.push(
+                json!({"type":"text","text": format!("## {}. {}{}\n\n{}", i + 1, label, at, text)}),
+            );
+        }
+        report.push(entry);
+    }
+    let meta = json!({
+        "tool": fetch,
+        "count": items.len(),
+        "failed": failed,
+        "items": report,
+    });
+    let mut result = json!({ "content": content, "_meta": meta });
+    if failed == items.len() {
+        result["isError"] = json!(true);
+    }
+    json!({"jsonrpc":"2.0","id": id, "result": result})
+}
 
-            // Cheap cross-corpus aliases (no index load).
-            cands_scored.extend(resolve_crosswalk_candidates(
-                q,
-                &sources,
-                prefer_source.as_deref(),
-            ));
+// ============ Boolean query mode ============
 
-            // Title index resolution (CPU only). Parallelize across corpora to reduce tail latency.
-            if !direct_id_mode {
-                let prefer = prefer_source.as_deref();
-                let do_cbeta = sources.iter().any(|s| s == "cbeta");
-                let do_tipitaka = sources.iter().any(|s| s == "tipitaka");
-                let do_gretil = sources.iter().any(|s| s == "gretil");
-                let do_sarit = sources.iter().any(|s| s == "sarit");
-                let do_muktabodha = sources.iter().any(|s| s == "muktabodha");
-                let jobs = (do_cbeta as usize)
-                    + (do_tipitaka as usize)
-                    + (do_gretil as usize)
-                    + (do_sarit as usize)
-                    + (do_muktabodha as usize);
-                if jobs >= 2 {
-                    std::thread::scope(|scope| {
-                        let h_cbeta = if do_cbeta {
-                            Some(scope.spawn(|| {
-                                resolve_title_candidates_cbeta(
-                                    q,
-                                    limit_per_source,
-                                    min_score,
-                                    prefer,
-                                )
-                            }))
-                        } else {
-                            None
-                        };
+fn query_mode_boolean(args: &serde_json::Value) -> bool {
+    args.get("queryMode")
+        .and_then(|v| v.as_str())
+        .map(|s| s.eq_ignore_ascii_case("boolean"))
+        .unwrap_or(false)
+}
+
+/// `queryMode:"boolean"`: grep candidate files with the positive terms, then keep the files whose
+/// plain text satisfies the whole query. Returns the results and the prefilter pattern.
+fn boolean_grep(
+    source: &str,
+    q_raw: &str,
+    args: &serde_json::Value,
+    scope: Option<&SearchScope>,
+    max_results: usize,
+    max_matches_per_file: usize,
+) -> Result<(Vec<daizo_core::GrepResult>, String), String> {
+    let bq = daizo_core::query::BoolQuery::parse(q_raw)?;
+    let literal = |t: &str| {
+        if source == "cbeta" {
+            ws_cjk_variant_fuzzy_regex_literal(t)
+        } else {
+            to_whitespace_fuzzy_literal(t)
+        }
+    };
+    let compiled = bq.compile(literal)?;
+    let prefilter = bq.prefilter_regex(literal);
+    let max_candidates = args
+        .get("maxCandidates")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(1000) as usize;
+    let candidates = corpus_grep(
+        source,
+        scope,
+        &prefilter,
+        max_candidates,
+        max_matches_per_file,
+    );
+    let mut results = compiled.filter_results(source, candidates);
+    results.truncate(max_results);
+    Ok((results, prefilter))
+}
+
+/// The pattern a content search greps for, and how it was derived from `query`.
+struct QueryRewrite {
+    pattern: String,
+    /// (rule, result) in the order applied.
+    steps: Vec<(&'static str, String)>,
+}
+
+/// Turn a search `query` into the regex the `<corpus>_search` tool greps for. Queries with
+/// regex metacharacters are used as they are; literal queries are escaped, with whitespace
+/// made optional, CJK variants expanded (CBETA) or transliterations added (GRETIL).
+fn rewrite_query(corpus: &str, q_raw: &str, args: &serde_json::Value) -> QueryRewrite {
+    let mut steps = Vec::new();
+    let q = if corpus == "cbeta" {
+        q_raw.trim()
+    } else {
+        q_raw
+    };
+    let has_ws = q.chars().any(|c| c.is_whitespace());
+    let pattern = if daizo_core::pattern::looks_like_regex(q) {
+        steps.push(("regex (metacharacters present): used as is", q.to_string()));
+        q.to_string()
+    } else if corpus == "cbeta" {
+        if args
+            .get("variants")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true)
+        {
+            let p = ws_cjk_variant_fuzzy_regex_literal(q);
+            steps.push((
+                "ws_cjk_variant_fuzzy_regex_literal: escape, whitespace -> \\s*, CJK variants -> [..]",
+                p.clone(),
+            ));
+            p
+        } else {
+            let p = to_whitespace_fuzzy_literal(q);
+            steps.push((
+                "to_whitespace_fuzzy_literal: escape, whitespace -> \\s* (variants:false)",
+                p.clone(),
+            ));
+            p
+        }
+    } else {
+        let variants = if corpus == "gretil" && arg_transliterate(args) {
+            sanskrit_scheme_variants(q).1
+        } else {
+            Vec::new()
+        };
+        if variants.len() > 1 {
+            steps.push((
+                "transliteration variants (IAST/HK/SLP1/Devanagari)",
+                variants.join(" | "),
+            ));
+            let p = variants
+                .iter()
+                .map(|v| to_whitespace_fuzzy_literal(v))
+                .collect::<Vec<_>>()
+                .join("|");
+            steps.push((
+                "to_whitespace_fuzzy_literal on each variant, joined with |",
+                p.clone(),
+            ));
+            p
+        } else if has_ws {
+            let p = to_whitespace_fuzzy_literal(q);
+            steps.push((
+                "to_whitespace_fuzzy_literal: escape, whitespace -> \\s*",
+                p.clone(),
+            ));
+            p
+        } else {
+            steps.push(("literal without whitespace: used as is", q.to_string()));
+            q.to_string()
+        }
+    };
+    QueryRewrite { pattern, steps }
+}
+
+/// `regex_explain`: replay what `<corpus>_search` does with `query`, step by step.
+fn explain_query(args: &serde_json::Value) -> (String, serde_json::Value) {
+    let corpus = args
+        .get("corpus")
+        .and_then(|v| v.as_str())
+        .unwrap_or("cbeta")
+        .to_ascii_lowercase();
+    let tool_name = format!("{}_search", corpus);
+    let q_in = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
+    let mut args = args.clone();
+    let alias = apply_user_aliases(&tool_name, &mut args);
+    let q_raw = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
+    let mut steps: Vec<(&str, String)> = Vec::new();
+    if alias.is_some() {
+        steps.push(("alias expansion (aliases file)", q_raw.to_string()));
+    }
+    let (mode, pattern) = if query_mode_boolean(&args) {
+        let literal = |t: &str| {
+            if corpus == "cbeta" {
+                ws_cjk_variant_fuzzy_regex_literal(t)
+            } else {
+                to_whitespace_fuzzy_literal(t)
+            }
+        };
+        match daizo_core::query::BoolQuery::parse(q_raw) {
+            Ok(bq) => {
+                for t in &bq.terms {
+                    steps.push(("boolean term", format!("{} -> {}", t, literal(t))));
+                }
+                let pre = bq.prefilter_regex(literal);
+                steps.push((
+                    "prefilter: files must match one of the positive terms",
+                    pre.clone(),
+                ));
+                ("boolean", pre)
+            }
+            Err(e) => {
+                let err = ToolError::InvalidQuery {
+                    query: q_raw.to_string(),
+                    reason: e,
+                };
+                return (
+                    err.message(),
+                    json!({"query": q_in, "corpus": corpus, "mode": "boolean", "valid": false, "error": {"message": err.message()}}),
+                );
+            }
+        }
+    } else {
+        let rw = rewrite_query(&corpus, q_raw, &args);
+        steps.extend(rw.steps);
+        let mut pattern = rw.pattern;
+        if corpus == "tipitaka"
+            && args
+                .get("foldDiacritics")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false)
+        {
+            pattern = daizo_core::fold::fold_diacritics(&pattern);
+            steps.push(("fold_diacritics (foldDiacritics:true)", pattern.clone()));
+        }
+        let mode = if daizo_core::pattern::looks_like_regex(q_raw.trim()) {
+            "regex"
+        } else {
+            "literal"
+        };
+        (mode, pattern)
+    };
+    let check = daizo_core::pattern::validate(&pattern);
+    let mut text = format!("Query '{}' ({}, {} mode)\n", q_in, tool_name, mode);
+    for (i, (rule, result)) in steps.iter().enumerate() {
+        text.push_str(&format!("{}. {}\n   {}\n", i + 1, rule, result));
+    }
+    text.push_str(&format!("Pattern: {}\n", pattern));
+    match &check {
+        Ok(()) => text.push_str("Compiles: yes (case-insensitive, multi-line)"),
+        Err(e) => text.push_str(&format!("Compiles: no: {}", e)),
+    }
+    let engine = daizo_core::matcher::QueryMatcher::new(&pattern).map(|m| m.engine());
+    if let Some(e) = engine {
+        text.push_str(&format!("\nEngine: {}", e));
+    }
+    let meta = json!({
+        "query": q_in,
+        "corpus": corpus,
+        "tool": tool_name,
+        "mode": mode,
+        "aliasExpansion": alias.map(|a| json!({"alias": a.alias, "expansions": a.expansions})),
+        "steps": steps.iter().map(|(rule, result)| json!({"rule": rule, "result": result})).collect::<Vec<_>>(),
+        "pattern": pattern,
+        "valid": check.is_ok(),
+        "engine": engine,
+        "error": check.err(),
+    });
+    (text, meta)
+}
+
+fn boolean_query_error(id: &serde_json::Value, q_raw: &str, e: &str) -> serde_json::Value {
+    ToolError::InvalidQuery {
+        query: q_raw.to_string(),
+        reason: e.to_string(),
+    }
+    .response(id)
+}
+
+/// Tools whose `query` is used as a regex when it contains metacharacters.
+const REGEX_QUERY_TOOLS: &[&str] = &[
+    "cbeta_search",
+    "cbeta_pipeline",
+    "cbeta_concordance",
+    "cbeta_term_timeline",
+    "tipitaka_search",
+    "tipitaka_concordance",
+    "gretil_search",
+    "gretil_pipeline",
+    "sarit_search",
+    "sarit_pipeline",
+    "muktabodha_search",
+    "muktabodha_pipeline",
+];
+
+/// Reject regex queries and highlights that do not compile; the grep helpers would
+/// otherwise answer them with an empty result.
+fn check_patterns(name: &str, args: &serde_json::Value) -> Result<(), ToolError> {
+    if REGEX_QUERY_TOOLS.contains(&name) && !query_mode_boolean(args) {
+        if let Some(q) = args
+            .get("query")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|q| daizo_core::pattern::looks_like_regex(q))
+        {
+            daizo_core::pattern::validate(q).map_err(|error| ToolError::InvalidRegex {
+                pattern: q.to_string(),
+                error,
+            })?;
+        }
+    }
+    if args.get("highlightRegex").and_then(|v| v.as_bool()) == Some(true) {
+        if let Some(h) = args.get("highlight").and_then(|v| v.as_str()) {
+            daizo_core::pattern::validate(h).map_err(|error| ToolError::InvalidRegex {
+                pattern: h.to_string(),
+                error,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+// ============ User alias file ============
+
+static USER_ALIASES: OnceLock<Mutex<daizo_core::aliases::AliasTable>> = OnceLock::new();
+
+fn user_aliases() -> &'static Mutex<daizo_core::aliases::AliasTable> {
+    USER_ALIASES.get_or_init(|| {
+        let t = daizo_core::aliases::load_user_aliases().unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "user aliases not loaded");
+            Default::default()
+        });
+        Mutex::new(t)
+    })
+}
+
+/// Re-read config.toml and the alias file. Returns (ok, summary, `_meta`); on a parse error the
+/// previous settings stay in effect. Used by `daizo_reload` and SIGHUP.
+fn reload_settings() -> (bool, String, serde_json::Value) {
+    let before = config::current();
+    let (ok, mut msg, changed) = match config::reload() {
+        Ok(after) => {
+            let mut changed = Vec::new();
+            if before.scoring != after.scoring {
+                changed.push("scoring");
+            }
+            if before.output != after.output {
+                changed.push("output");
+            }
+            if before.corpora != after.corpora {
+                changed.push("corpora");
+            }
+            if before.prefetch != after.prefetch {
+                changed.push("prefetch");
+                // Texts cached under the old cap or setting are dropped.
+                prefetcher().clear();
+            }
+            let msg = match &after.source {
+                Some(p) => format!("Reloaded {}", p.display()),
+                None => format!(
+                    "No config file at {} (built-in defaults)",
+                    config::config_path().display()
+                ),
+            };
+            (true, msg, changed)
+        }
+        Err(e) => (
+            false,
+            format!("Config reload failed (previous settings kept): {}", e),
+            Vec::new(),
+        ),
+    };
+    if !changed.is_empty() {
+        msg.push_str(&format!("; changed: {}", changed.join(", ")));
+    }
+    if changed.contains(&"corpora") {
+        // Title indexes are built once per process from the root in effect at that time.
+        msg.push_str(" (new corpus roots apply to id lookups and searches; loaded title indexes are kept until restart)");
+    }
+    let aliases = match daizo_core::aliases::load_user_aliases() {
+        Ok(t) => {
+            let n = t.len();
+            if let Ok(mut g) = user_aliases().lock() {
+                *g = t;
+            }
+            json!({"ok": true, "count": n})
+        }
+        Err(e) => json!({"ok": false, "error": e}),
+    };
+    let meta = json!({
+        "ok": ok,
+        "configPath": config::config_path().display().to_string(),
+        "changed": changed,
+        "aliases": aliases,
+    });
+    (ok, msg, meta)
+}
+
+/// Local full-text search tools: an alias query becomes a regex alternation of all expansions.
+const ALIAS_REGEX_TOOLS: &[&str] = &[
+    "cbeta_search",
+    "cbeta_pipeline",
+    "cbeta_term_timeline",
+    "cbeta_concordance",
+    "tipitaka_search",
+    "tipitaka_concordance",
+    "gretil_search",
+    "gretil_pipeline",
+    "sarit_search",
+    "sarit_pipeline",
+    "muktabodha_search",
+    "muktabodha_pipeline",
+];
+/// Title/online search tools: an alias query is replaced by its first expansion.
+const ALIAS_CANONICAL_TOOLS: &[&str] = &[
+    "daizo_resolve",
+    "cbeta_title_search",
+    "tipitaka_title_search",
+    "gretil_title_search",
+    "sarit_title_search",
+    "muktabodha_title_search",
+    "sat_search",
+    "sat_pipeline",
+    "jozen_search",
+    "tibetan_search",
+];
+
+/// Rewrite `arguments.query` through the user alias table. Regex queries are left untouched.
+fn apply_user_aliases(
+    name: &str,
+    args: &mut serde_json::Value,
+) -> Option<daizo_core::aliases::AliasExpansion> {
+    let as_regex = ALIAS_REGEX_TOOLS.contains(&name);
+    if !as_regex && !ALIAS_CANONICAL_TOOLS.contains(&name) {
+        return None;
+    }
+    let q = args
+        .get("query")
+        .and_then(|v| v.as_str())?
+        .trim()
+        .to_string();
+    if q.is_empty() || q.chars().any(|c| ".+*?[](){}|\\".contains(c)) {
+        return None;
+    }
+    let exp = user_aliases().lock().ok()?.expand(&q)?;
+    args["query"] = if as_regex {
+        json!(exp.regex_alternation())
+    } else {
+        json!(exp.canonical())
+    };
+    Some(exp)
+}
+
+/// Run a tool call and report in `_meta.timing` where its time went; with metrics on, the
+/// call is also recorded (see [`record_call`]).
+fn handle_call(id: serde_json::Value, params: &serde_json::Value) -> serde_json::Value {
+    let name = params.get("name").and_then(|v| v.as_str()).unwrap_or("");
+    let span = tracing::info_span!("tool_call", tool = name, id = %id);
+    let _entered = span.enter();
+    let metrics = name != "daizo_metrics" && metrics_enabled();
+    let ((mut resp, t), cache) = if metrics {
+        daizo_core::metrics::record(|| daizo_core::timing::record(|| run_call(id, params)))
+    } else {
+        (
+            daizo_core::timing::record(|| run_call(id, params)),
+            Default::default(),
+        )
+    };
+    let failed =
+        errors::is_error_response(&resp) || resp.pointer("/result/isError") == Some(&json!(true));
+    if failed {
+        tracing::warn!(ms = t.total.as_millis() as u64, "tool call failed");
+    } else {
+        tracing::debug!(ms = t.total.as_millis() as u64, "tool call done");
+    }
+    if metrics {
+        record_call(name, &resp, t.total, cache);
+    }
+    if let Some(result) = resp.get_mut("result").and_then(|r| r.as_object_mut()) {
+        if let Some(meta) = result
+            .entry("_meta")
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+        {
+            meta.insert("timing".to_string(), timing_json(&t));
+        }
+    }
+    resp
+}
+
+// ============ Metrics ============
+
+/// `DAIZO_METRICS` (1/true/on), else `[metrics] enabled` of config.toml; off by default.
+fn metrics_enabled() -> bool {
+    match std::env::var("DAIZO_METRICS")
+        .ok()
+        .map(|v| v.trim().to_ascii_lowercase())
+    {
+        Some(v) => matches!(v.as_str(), "1" | "true" | "on" | "yes"),
+        None => config::current().metrics.enabled.unwrap_or(false),
+    }
+}
+
+/// Calls recorded by this server process, newest last, for `daizo_metrics`.
+static SESSION_CALLS: Mutex<VecDeque<daizo_core::metrics::CallRecord>> =
+    Mutex::new(VecDeque::new());
+const SESSION_CALLS_MAX: usize = 10_000;
+
+/// Keep a finished call in the session list and append it to the request log.
+fn record_call(
+    name: &str,
+    resp: &serde_json::Value,
+    elapsed: Duration,
+    cache: std::collections::BTreeMap<String, daizo_core::metrics::CacheCount>,
+) {
+    let texts = resp
+        .pointer("/result/content")
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|c| c.get("text").and_then(|t| t.as_str()));
+    let (mut bytes, mut chars) = (0, 0);
+    for t in texts {
+        bytes += t.len();
+        chars += t.chars().count();
+    }
+    let rec = daizo_core::metrics::CallRecord {
+        ts: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+        tool: name.to_string(),
+        ms: (elapsed.as_secs_f64() * 1e6).round() / 1e3,
+        bytes,
+        chars,
+        error: errors::is_error_response(resp)
+            || resp.pointer("/result/isError") == Some(&json!(true)),
+        cache,
+    };
+    if let Err(e) = daizo_core::metrics::append(&daizo_core::metrics::default_dir(), &rec) {
+        tracing::warn!(error = %e, "metrics append failed");
+    }
+    let mut calls = SESSION_CALLS.lock().unwrap_or_else(|e| e.into_inner());
+    if calls.len() >= SESSION_CALLS_MAX {
+        calls.pop_front();
+    }
+    calls.push_back(rec);
+}
+
+/// `{totalMs, indexLoadMs, scanMs, extractMs, networkMs, otherMs}`, to the microsecond.
+fn timing_json(t: &daizo_core::timing::Timings) -> serde_json::Value {
+    let ms = |d: Duration| (d.as_secs_f64() * 1e6).round() / 1e3;
+    let mut m = serde_json::Map::new();
+    m.insert("totalMs".to_string(), json!(ms(t.total)));
+    for p in Phase::ALL {
+        m.insert(format!("{}Ms", p.key()), json!(ms(t.get(p))));
+    }
+    m.insert("otherMs".to_string(), json!(ms(t.other())));
+    serde_json::Value::Object(m)
+}
+
+fn run_call(id: serde_json::Value, params: &serde_json::Value) -> serde_json::Value {
+    let name = params.get("name").and_then(|v| v.as_str()).unwrap_or("");
+    let mut args = params.get("arguments").cloned().unwrap_or(json!({}));
+    let cursor = args
+        .get("cursor")
+        .and_then(|v| v.as_str())
+        .map(String::from);
+    if let Err(reason) = apply_cursor(name, &mut args) {
+        let mut resp = ToolError::InvalidArgument {
+            name: "cursor".to_string(),
+            value: cursor.unwrap_or_default(),
+            expected: format!("the nextCursor of an earlier {} call ({})", name, reason),
+        }
+        .response(&id);
+        attach_structured_content(name, &mut resp, meta_mirror());
+        return resp;
+    }
+    if let Err(e) = check_patterns(name, &args) {
+        let mut resp = e.response(&id);
+        attach_structured_content(name, &mut resp, meta_mirror());
+        return resp;
+    }
+    if let Err(e) = check_ids(name, &args)
+        .and_then(|_| check_highlight_style(&args))
+        .and_then(|_| check_highlight(&args))
+        .and_then(|_| shaping::check_on_overflow(&args))
+        .and_then(|_| check_page_by(&args))
+        .and_then(|_| check_snap(&args))
+    {
+        let mut resp = e.response(&id);
+        attach_structured_content(name, &mut resp, meta_mirror());
+        return resp;
+    }
+    let matched = match apply_match_id(name, &mut args) {
+        Ok(m) => m,
+        Err(e) => {
+            let mut resp = e.response(&id);
+            attach_structured_content(name, &mut resp, meta_mirror());
+            return resp;
+        }
+    };
+    let cited = match apply_reference(name, &mut args) {
+        Ok(r) => r,
+        Err(e) => {
+            let mut resp = e.response(&id);
+            attach_structured_content(name, &mut resp, meta_mirror());
+            return resp;
+        }
+    };
+    let exp = apply_user_aliases(name, &mut args);
+    let max_parallelism = args
+        .get("maxParallelism")
+        .and_then(|v| v.as_u64())
+        .map(|n| n as usize);
+    let mut params = params.clone();
+    params["arguments"] = args;
+    let mut resp = match max_parallelism {
+        Some(n) => daizo_core::scan::with_max_parallelism(n, || dispatch_call(id, &params)),
+        None => dispatch_call(id, &params),
+    };
+    if let Some(m) = matched {
+        if let Some(meta) = resp
+            .pointer_mut("/result/_meta")
+            .and_then(|m| m.as_object_mut())
+        {
+            meta.insert("match".to_string(), m);
+        }
+    }
+    if let Some(r) = cited {
+        if let Some(meta) = resp
+            .pointer_mut("/result/_meta")
+            .and_then(|m| m.as_object_mut())
+        {
+            meta.insert("reference".to_string(), r);
+        }
+    }
+    if let Some(exp) = exp {
+        if let Some(meta) = resp
+            .pointer_mut("/result/_meta")
+            .and_then(|m| m.as_object_mut())
+        {
+            meta.insert(
+                "aliasExpansion".to_string(),
+                json!({"alias": exp.alias, "expansions": exp.expansions}),
+            );
+            // The title search ran on the expansion: its hits matched through the alias.
+            if let Some(results) = meta.get_mut("results").and_then(|r| r.as_array_mut()) {
+                for r in results {
+                    if r.get("matchReason").is_some_and(|v| !v.is_null()) {
+                        r["matchReason"] = json!(MatchReason::Alias);
+                    }
+                }
+            }
+        }
+    }
+    attach_budget(&params["arguments"], &mut resp);
+    attach_structured_content(name, &mut resp, meta_mirror());
+    resp
+}
+
+/// Report the returned text's estimated token count (`_meta.estTokens`) and, when the call
+/// set `maxChars` or `maxTokens`, the budget it was granted and whether the text fits it.
+fn attach_budget(args: &serde_json::Value, resp: &mut serde_json::Value) {
+    let (chars, tokens) = resp
+        .pointer("/result/content")
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|c| c.get("text").and_then(|t| t.as_str()))
+        .fold((0, 0), |(c, t), s| {
+            (c + s.chars().count(), t + daizo_core::tokens::estimate(s))
+        });
+    let Some(result) = resp.get_mut("result").and_then(|r| r.as_object_mut()) else {
+        return;
+    };
+    let Some(meta) = result
+        .entry("_meta")
+        .or_insert_with(|| json!({}))
+        .as_object_mut()
+    else {
+        return;
+    };
+    meta.insert("estTokens".to_string(), json!(tokens));
+    if args.get("maxChars").is_some() || args.get("maxTokens").is_some() {
+        let max_chars = call_max_chars(args);
+        let max_tokens = call_max_tokens(args);
+        meta.insert(
+            "budget".to_string(),
+            json!({
+                "maxChars": max_chars,
+                "maxTokens": max_tokens,
+                "returnedChars": chars,
+                "fits": chars <= max_chars && max_tokens.is_none_or(|t| tokens <= t),
+            }),
+        );
+    }
+}
+
+/// Fetch tools that take `matchId` in place of `id` + `lineNumber`, with their corpus.
+const MATCH_ID_TOOLS: &[(&str, &str)] = &[
+    ("cbeta_fetch", "cbeta"),
+    ("tipitaka_fetch", "tipitaka"),
+    ("gretil_fetch", "gretil"),
+    ("sarit_fetch", "sarit"),
+    ("muktabodha_fetch", "muktabodha"),
+];
+
+/// Refuse path-like `id`/`division` arguments of local-corpus tools before any handler
+/// joins them onto a corpus root.
+fn check_ids(name: &str, args: &serde_json::Value) -> Result<(), ToolError> {
+    let local = name == "corpus_stats"
+        || name == "daizo_similar_works"
+        || name
+            .split_once('_')
+            .is_some_and(|(c, _)| local_source_root(c).is_some());
+    if !local {
+        return Ok(());
+    }
+    for key in ["id", "division"] {
+        if let Some(v) = args.get(key).and_then(|v| v.as_str()) {
+            if !v.is_empty() && !daizo_core::path_resolver::safe_id(v) {
+                return Err(ToolError::InvalidArgument {
+                    name: key.to_string(),
+                    value: v.to_string(),
+                    expected: "a work id or file stem, not a path".to_string(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Refuse a `highlightStyle` that names no preset rather than highlight with the default.
+fn check_highlight_style(args: &serde_json::Value) -> Result<(), ToolError> {
+    match args.get("highlightStyle").and_then(|v| v.as_str()) {
+        Some(s) if daizo_core::highlight::preset(s).is_none() => {
+            let names: Vec<&str> = daizo_core::highlight::PRESETS
+                .iter()
+                .map(|(n, _, _)| *n)
+                .collect();
+            Err(ToolError::InvalidArgument {
+                name: "highlightStyle".to_string(),
+                value: s.to_string(),
+                expected: format!("one of {}", names.join(", ")),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Every entry of an array `highlight` names a pattern.
+fn check_highlight(args: &serde_json::Value) -> Result<(), ToolError> {
+    let entries = args.get("highlight").and_then(|v| v.as_array());
+    match entries.and_then(|a| a.iter().find(|e| fetch::highlight_spec(e).is_none())) {
+        Some(e) => Err(ToolError::InvalidArgument {
+            name: "highlight".to_string(),
+            value: e.to_string(),
+            expected:
+                "a pattern string or {pattern, prefix, suffix, regex} with a non-empty pattern"
+                    .to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Refuse a `pageBy` that names no unit rather than page by characters.
+fn check_page_by(args: &serde_json::Value) -> Result<(), ToolError> {
+    match args.get("pageBy").and_then(|v| v.as_str()) {
+        Some(s) if PageBy::parse(s).is_none() => Err(ToolError::InvalidArgument {
+            name: "pageBy".to_string(),
+            value: s.to_string(),
+            expected: format!("one of {}", PageBy::NAMES.join(", ")),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// `snap`: `none` (default) or `sentence`.
+fn check_snap(args: &serde_json::Value) -> Result<(), ToolError> {
+    match args.get("snap").and_then(|v| v.as_str()) {
+        None | Some("none") | Some("sentence") => Ok(()),
+        Some(s) => Err(ToolError::InvalidArgument {
+            name: "snap".to_string(),
+            value: s.to_string(),
+            expected: "none or sentence".to_string(),
+        }),
+    }
+}
+
+/// Replace `matchId` with the `id`, current `lineNumber` and `highlight` of the match it names.
+/// Returns what `_meta.match` reports.
+fn apply_match_id(
+    name: &str,
+    args: &mut serde_json::Value,
+) -> Result<Option<serde_json::Value>, ToolError> {
+    let Some(mid) = args
+        .get("matchId")
+        .and_then(|v| v.as_str())
+        .map(String::from)
+    else {
+        return Ok(None);
+    };
+    let Some((_, corpus)) = MATCH_ID_TOOLS.iter().find(|(t, _)| *t == name) else {
+        return Ok(None);
+    };
+    let unknown = |reason: String| ToolError::UnknownMatch {
+        match_id: mid.clone(),
+        reason,
+    };
+    let r = match_registry().get(&mid).ok_or_else(|| {
+        unknown(
+            "not issued by this server session (ids live until restart); run the search again"
+                .to_string(),
+        )
+    })?;
+    if r.corpus != *corpus {
+        return Err(unknown(format!("belongs to {}, not {}", r.corpus, corpus)));
+    }
+    let loc = daizo_core::match_ids::locate(&r)
+        .ok_or_else(|| unknown(format!("'{}' is no longer in {}", r.highlight, r.file_id)))?;
+    if let Some(o) = args.as_object_mut() {
+        o.remove("matchId");
+        o.remove("lb");
+        o.insert("id".to_string(), json!(r.file_id));
+        o.insert("lineNumber".to_string(), json!(loc.line_number));
+        if !o.contains_key("highlight") && !r.highlight.is_empty() {
+            o.insert("highlight".to_string(), json!(r.highlight));
+            o.insert("highlightRegex".to_string(), json!(false));
+        }
+    }
+    Ok(Some(json!({
+        "matchId": mid,
+        "id": r.file_id,
+        "lineNumber": loc.line_number,
+        "moved": loc.moved,
+    })))
+}
+
+/// Replace `reference` (a citation) with the `id` and position it names: `SN 56.11` or
+/// `Dhp 183` for `tipitaka_fetch`, `T 2076, 51:196b` for `cbeta_fetch`. Returns
+/// `_meta.reference`.
+fn apply_reference(
+    name: &str,
+    args: &mut serde_json::Value,
+) -> Result<Option<serde_json::Value>, ToolError> {
+    let Some(cite) = args
+        .get("reference")
+        .and_then(|v| v.as_str())
+        .map(String::from)
+    else {
+        return Ok(None);
+    };
+    match name {
+        "tipitaka_fetch" => apply_pali_reference(&cite, args).map(Some),
+        "cbeta_fetch" => apply_taisho_reference(&cite, args).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// The sutta or verse a Pāli citation names: its `id`, `lineNumber` and context lines. A
+/// citation whose section is not found in its volume fetches the volume.
+fn apply_pali_reference(
+    cite: &str,
+    args: &mut serde_json::Value,
+) -> Result<serde_json::Value, ToolError> {
+    let r = daizo_core::pali_ref::parse(cite).ok_or_else(|| ToolError::InvalidArgument {
+        name: "reference".to_string(),
+        value: cite.to_string(),
+        expected: "a citation such as DN 2, MN 10, SN 56.11, AN 4.10, Dhp 183 or Snp 1.8"
+            .to_string(),
+    })?;
+    let stem = r.file_stem();
+    let root = tipitaka_root();
+    // The volume, or the numbered parts it is split into (s0404m1.mul0.xml, …).
+    let mut files: Vec<PathBuf> = vec![root.join(format!("{}.xml", stem))];
+    if !files[0].exists() {
+        let mut parts: Vec<(u32, PathBuf)> = fs::read_dir(&root)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|e| {
+                let p = e.path();
+                let n = p
+                    .file_stem()?
+                    .to_str()?
+                    .strip_prefix(stem)?
+                    .parse::<u32>()
+                    .ok()?;
+                Some((n, p))
+            })
+            .collect();
+        parts.sort();
+        files = parts.into_iter().map(|(_, p)| p).collect();
+    }
+    let file_id = |p: &Path| {
+        p.file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| stem.to_string())
+    };
+    let found = files.iter().find_map(|p| {
+        let xml = fs::read(p).map(|b| decode_xml_bytes(&b)).ok()?;
+        daizo_core::pali_ref::locate(&xml, &r).map(|l| (p, l))
+    });
+    let mut meta = json!({"reference": r.canonical(), "located": found.is_some()});
+    let Some(o) = args.as_object_mut() else {
+        return Ok(meta);
+    };
+    o.remove("reference");
+    match found {
+        Some((p, l)) => {
+            o.insert("id".to_string(), json!(file_id(p)));
+            o.insert("lineNumber".to_string(), json!(l.line));
+            o.entry("contextBefore").or_insert(json!(0));
+            o.entry("contextAfter")
+                .or_insert(json!(l.lines.saturating_sub(1)));
+            meta["id"] = json!(file_id(p));
+            meta["lineNumber"] = json!(l.line);
+            meta["lines"] = json!(l.lines);
+            meta["head"] = json!(l.head);
+        }
+        None => {
+            let id = files
+                .first()
+                .map(|p| file_id(p))
+                .unwrap_or_else(|| stem.to_string());
+            o.insert("id".to_string(), json!(id));
+            meta["id"] = json!(id);
+        }
+    }
+    Ok(meta)
+}
+
+/// The file and line a CBETA citation names: the text's files (of the cited volume, when
+/// given) are tried in order for the page, and the first line marker at or after the cited
+/// column becomes `lb`. Without a page, or when no file holds it, the text is fetched whole.
+fn apply_taisho_reference(
+    cite: &str,
+    args: &mut serde_json::Value,
+) -> Result<serde_json::Value, ToolError> {
+    let r = daizo_core::taisho_ref::parse(cite).ok_or_else(|| ToolError::InvalidArgument {
+        name: "reference".to_string(),
+        value: cite.to_string(),
+        expected: "a citation such as T 2076, 51:196b or T51, no. 2076, p. 196b12".to_string(),
+    })?;
+    let (files, found) = taisho_location(&r);
+    let mut meta = json!({"reference": r.canonical(), "located": found.is_some()});
+    let Some(o) = args.as_object_mut() else {
+        return Ok(meta);
+    };
+    o.remove("reference");
+    match found {
+        Some((file_id, hit)) => {
+            o.insert("id".to_string(), json!(file_id));
+            o.insert("lb".to_string(), json!(hit.lb));
+            o.entry("contextBefore").or_insert(json!(0));
+            meta["id"] = json!(file_id);
+            meta["lb"] = json!(hit.lb);
+            meta["exact"] = json!(hit.exact);
+            meta["juan"] = json!(hit.juan);
+        }
+        None => {
+            let id = files.first().cloned().unwrap_or_else(|| r.id());
+            o.insert("id".to_string(), json!(id));
+            meta["id"] = json!(id);
+        }
+    }
+    Ok(meta)
+}
+
+/// The CBETA files of the cited text (of the cited volume, when given) in id order, and the
+/// file and line marker the citation lands on.
+fn taisho_location(
+    r: &daizo_core::taisho_ref::TaishoRef,
+) -> (Vec<String>, Option<(String, daizo_core::taisho_ref::LbHit)>) {
+    let idx = load_or_build_cbeta_index();
+    let vol_prefix = r.volume.map(|v| format!("{}{:02}n", r.canon, v));
+    let mut files: Vec<&IndexEntry> = idx
+        .iter()
+        .filter(|e| {
+            let Some(m) = e.meta.as_ref() else {
+                return false;
+            };
+            let nnum = m.get("nnum").map(|s| s.as_str()).unwrap_or("");
+            m.get("canon").map(|s| s.as_str()) == Some(r.canon.as_str())
+                && (nnum == r.number
+                    || nnum.trim_end_matches(|c: char| c.is_alphabetic()) == r.number)
+                && vol_prefix
+                    .as_ref()
+                    .is_none_or(|p| e.id.starts_with(p.as_str()))
+        })
+        .collect();
+    files.sort_by(|a, b| a.id.cmp(&b.id));
+    let found = files.iter().find_map(|e| {
+        let xml = fs::read(&e.path).map(|b| decode_xml_bytes(&b)).ok()?;
+        daizo_core::taisho_ref::locate(&xml, r).map(|hit| (e.id.clone(), hit))
+    });
+    (files.iter().map(|e| e.id.clone()).collect(), found)
+}
+
+/// Layout version of `structuredContent`; bumped when a field is renamed or removed.
+const STRUCTURED_SCHEMA_VERSION: u32 = 1;
+
+/// `DAIZO_META_MIRROR` / `output.meta_mirror`: keep the `_meta` copy (default) or drop it.
+fn meta_mirror() -> bool {
+    !matches!(
+        setting_str("DAIZO_META_MIRROR")
+            .map(|v| v.trim().to_ascii_lowercase())
+            .as_deref(),
+        Some("0" | "false" | "off" | "no")
+    )
+}
+
+/// Expose a tool's data as `structuredContent` = `{schemaVersion, tool, ...}`, leaving the
+/// text content as the human summary. Handlers build their data in `_meta`, which keeps a
+/// copy for older clients unless `mirror` is off.
+fn attach_structured_content(name: &str, resp: &mut serde_json::Value, mirror: bool) {
+    let Some(result) = resp.get_mut("result").and_then(|r| r.as_object_mut()) else {
+        return;
+    };
+    let data = if mirror {
+        result.get("_meta").cloned()
+    } else {
+        result.remove("_meta")
+    };
+    let mut sc = serde_json::Map::new();
+    sc.insert(
+        "schemaVersion".to_string(),
+        json!(STRUCTURED_SCHEMA_VERSION),
+    );
+    sc.insert("tool".to_string(), json!(name));
+    if let Some(serde_json::Value::Object(m)) = data {
+        sc.extend(m);
+    }
+    result.insert(
+        "structuredContent".to_string(),
+        serde_json::Value::Object(sc),
+    );
+}
+
+// ============ Self-test ============
+
+/// Canned known-good calls: (corpus, label, tool, arguments, `_meta` key that must be
+/// non-empty). Fetches check `totalChars`, searches their `results`.
+const SELFTEST_CHECKS: &[(&str, &str, &str, &str, &str)] = &[
+    (
+        "cbeta",
+        "fetch T0001 juan 1",
+        "cbeta_fetch",
+        r#"{"id":"T0001","part":"001","maxChars":500}"#,
+        "totalChars",
+    ),
+    (
+        "cbeta",
+        "title search 般若波羅蜜多心經",
+        "cbeta_title_search",
+        r#"{"query":"般若波羅蜜多心經","limit":3}"#,
+        "results",
+    ),
+    (
+        "cbeta",
+        "search 如是我聞",
+        "cbeta_search",
+        r#"{"query":"如是我聞","maxResults":1,"maxMatchesPerFile":1}"#,
+        "results",
+    ),
+    (
+        "tipitaka",
+        "fetch DN 22",
+        "tipitaka_fetch",
+        r#"{"id":"DN22","maxChars":500}"#,
+        "totalChars",
+    ),
+    (
+        "tipitaka",
+        "title search Brahmajāla",
+        "tipitaka_title_search",
+        r#"{"query":"Brahmajāla","limit":3}"#,
+        "results",
+    ),
+    (
+        "tipitaka",
+        "search evaṃ me sutaṃ",
+        "tipitaka_search",
+        r#"{"query":"evaṃ me sutaṃ","maxResults":1,"maxMatchesPerFile":1}"#,
+        "results",
+    ),
+    (
+        "gretil",
+        "title search Bhagavadgītā",
+        "gretil_title_search",
+        r#"{"query":"bhagavadgItA","limit":3}"#,
+        "results",
+    ),
+    (
+        "gretil",
+        "search dharmakṣetre",
+        "gretil_search",
+        r#"{"query":"dharmakṣetre","maxResults":1,"maxMatchesPerFile":1}"#,
+        "results",
+    ),
+    (
+        "sarit",
+        "title search nyāya",
+        "sarit_title_search",
+        r#"{"query":"nyaya","limit":3}"#,
+        "results",
+    ),
+    (
+        "muktabodha",
+        "title search tantra",
+        "muktabodha_title_search",
+        r#"{"query":"tantra","limit":3}"#,
+        "results",
+    ),
+    (
+        "sat",
+        "search 般若 (online)",
+        "sat_search",
+        r#"{"query":"般若","rows":1}"#,
+        "results",
+    ),
+    (
+        "jozen",
+        "search 念仏 (online)",
+        "jozen_search",
+        r#"{"query":"念仏","maxResults":1}"#,
+        "results",
+    ),
+];
+
+/// Whether a local corpus has data (None for online sources).
+fn corpus_installed(corpus: &str) -> Option<bool> {
+    local_source_root(corpus).map(|_| corpus_present(corpus))
+}
+
+/// Run the canned checks of the selected corpora. Local corpora that are not installed are
+/// skipped rather than cloned; online sources run only with `online`.
+fn run_selftest(corpora: Option<&[String]>, online: bool) -> (String, serde_json::Value) {
+    let cancel = CancelToken::current();
+    let mut lines = Vec::new();
+    let mut results = Vec::new();
+    let (mut passed, mut failed, mut skipped) = (0usize, 0usize, 0usize);
+    for &(corpus, label, tool_name, args, key) in SELFTEST_CHECKS {
+        if corpora.is_some_and(|c| !c.iter().any(|x| x.eq_ignore_ascii_case(corpus)))
+            || !capabilities().allows_tool(tool_name)
+        {
+            continue;
+        }
+        let skip = match corpus_installed(corpus) {
+            Some(false) => Some("not installed"),
+            None if !online => Some("online check (set online:true)"),
+            _ if cancel.is_cancelled() => Some("cancelled"),
+            _ => None,
+        };
+        if let Some(reason) = skip {
+            skipped += 1;
+            lines.push(format!("SKIP  {:<10} {} — {}", corpus, label, reason));
+            results.push(json!({"corpus": corpus, "check": label, "tool": tool_name, "status": "skip", "detail": reason}));
+            continue;
+        }
+        let mut args: serde_json::Value = serde_json::from_str(args).unwrap_or_default();
+        args["provenance"] = json!(false);
+        let t0 = Instant::now();
+        let resp = handle_call(
+            json!("selftest"),
+            &json!({"name": tool_name, "arguments": args}),
+        );
+        let ms = t0.elapsed().as_secs_f64() * 1000.0;
+        let meta = &resp["result"]["structuredContent"];
+        let ok = match &meta[key] {
+            serde_json::Value::Array(a) => !a.is_empty(),
+            serde_json::Value::Number(n) => n.as_f64().unwrap_or(0.0) > 0.0,
+            _ => false,
+        } && !errors::is_error_response(&resp);
+        let detail = if ok {
+            String::new()
+        } else {
+            resp["error"]["message"]
+                .as_str()
+                .or_else(|| resp["result"]["content"][0]["text"].as_str())
+                .map(|t| {
+                    t.lines()
+                        .next()
+                        .unwrap_or("")
+                        .chars()
+                        .take(160)
+                        .collect::<String>()
+                })
+                .filter(|t| !t.trim().is_empty())
+                .unwrap_or_else(|| format!("_meta.{} empty", key))
+        };
+        if ok {
+            passed += 1;
+            lines.push(format!("PASS  {:<10} {} ({:.0} ms)", corpus, label, ms));
+        } else {
+            failed += 1;
+            lines.push(format!(
+                "FAIL  {:<10} {} ({:.0} ms): {}",
+                corpus, label, ms, detail
+            ));
+        }
+        results.push(json!({
+            "corpus": corpus,
+            "check": label,
+            "tool": tool_name,
+            "status": if ok { "pass" } else { "fail" },
+            "ms": (ms * 10.0).round() / 10.0,
+            "detail": if ok { serde_json::Value::Null } else { json!(detail) },
+        }));
+    }
+    let text = format!(
+        "daizo_selftest: {} passed, {} failed, {} skipped\n{}",
+        passed,
+        failed,
+        skipped,
+        lines.join("\n")
+    );
+    let meta = json!({
+        "ok": failed == 0,
+        "passed": passed,
+        "failed": failed,
+        "skipped": skipped,
+        "results": results,
+    });
+    (text, meta)
+}
+
+/// Tools whose output is corpus text, with the corpus whose terms apply to it.
+const LICENSED_TOOLS: &[(&str, &str)] = &[
+    ("cbeta_fetch", "cbeta"),
+    ("cbeta_pipeline", "cbeta"),
+    ("cbeta_concordance", "cbeta"),
+    ("cbeta_collate", "cbeta"),
+    ("cbeta_notes", "cbeta"),
+    ("cbeta_colophons", "cbeta"),
+    ("tipitaka_fetch", "tipitaka"),
+    ("tipitaka_concordance", "tipitaka"),
+    ("gretil_fetch", "gretil"),
+    ("gretil_pipeline", "gretil"),
+    ("sarit_fetch", "sarit"),
+    ("sarit_pipeline", "sarit"),
+    ("muktabodha_fetch", "muktabodha"),
+    ("muktabodha_pipeline", "muktabodha"),
+    ("sat_fetch", "sat"),
+    ("sat_detail", "sat"),
+    ("sat_fetch_range", "sat"),
+    ("sat_pipeline", "sat"),
+    ("jozen_fetch", "jozen"),
+];
+
+/// Add `_meta.license` (corpus terms, plus the TEI header statements of `_meta.sourcePath`).
+fn attach_license(name: &str, resp: &mut serde_json::Value) {
+    let Some(&(_, corpus)) = LICENSED_TOOLS.iter().find(|(t, _)| *t == name) else {
+        return;
+    };
+    let Some(meta) = resp
+        .get_mut("result")
+        .and_then(|r| r.as_object_mut())
+        .and_then(|r| {
+            r.entry("_meta")
+                .or_insert_with(|| json!({}))
+                .as_object_mut()
+        })
+    else {
+        return;
+    };
+    let license = match meta.get("sourcePath").and_then(|v| v.as_str()) {
+        Some(p) => daizo_core::license::license_for_path(corpus, Path::new(p)),
+        None => daizo_core::license::corpus_license(corpus),
+    };
+    if let Some(l) = license {
+        meta.insert("license".to_string(), json!(l));
+    }
+}
+
+/// Whether a fetch/export result gets a provenance footer. `provenance` in the call decides;
+/// otherwise DAIZO_PROVENANCE: `on` for exports and fetches of at least
+/// DAIZO_PROVENANCE_MIN_CHARS (default 2000) chars, `always` for every fetch, off by default.
+fn provenance_wanted(name: &str, args: &serde_json::Value, text_chars: usize) -> bool {
+    if let Some(b) = args.get("provenance").and_then(|v| v.as_bool()) {
+        return b;
+    }
+    let export = name.ends_with("_concordance")
+        && matches!(
+            args.get("format").and_then(|v| v.as_str()),
+            Some("csv" | "json")
+        );
+    match setting_str("DAIZO_PROVENANCE").as_deref() {
+        Some("always") => true,
+        Some("on" | "1" | "true") => {
+            export || text_chars >= env_usize("DAIZO_PROVENANCE_MIN_CHARS", 2000)
+        }
+        _ => false,
+    }
+}
+
+/// Add `_meta.provenance` (corpus, source, corpus snapshot commit, retrieval date, tool
+/// version) and a one-line footer to the text of fetch and concordance results.
+fn attach_provenance(name: &str, args: &serde_json::Value, resp: &mut serde_json::Value) {
+    let Some(&(_, corpus)) = LICENSED_TOOLS.iter().find(|(t, _)| *t == name) else {
+        return;
+    };
+    if !(name.ends_with("_fetch")
+        || name.ends_with("_concordance")
+        || matches!(name, "sat_detail" | "sat_fetch_range"))
+    {
+        return;
+    }
+    let text_chars = resp
+        .pointer("/result/content/0/text")
+        .and_then(|v| v.as_str())
+        .map(|t| t.chars().count())
+        .unwrap_or(0);
+    if text_chars == 0 || !provenance_wanted(name, args, text_chars) {
+        return;
+    }
+    let meta = resp.pointer("/result/_meta");
+    let str_at = |k: &str| meta.and_then(|m| m.get(k)).and_then(|v| v.as_str());
+    let source = str_at("matchedId")
+        .or_else(|| args.get("id").and_then(|v| v.as_str()))
+        .map(String::from)
+        .or_else(|| {
+            str_at("sourcePath")
+                .and_then(|p| Path::new(p).file_stem())
+                .map(|s| s.to_string_lossy().into_owned())
+        })
+        .or_else(|| str_at("sourceUrl").map(String::from));
+    let prov = daizo_core::provenance::Provenance::new(
+        corpus,
+        local_source_root(corpus).as_deref(),
+        source,
+        &format!("daizo-mcp {}", VERSION),
+    );
+    let format = args.get("format").and_then(|v| v.as_str());
+    if format != Some("json") {
+        if let Some(serde_json::Value::String(t)) = resp.pointer_mut("/result/content/0/text") {
+            // CSV gets a comment row; text gets a paragraph of its own.
+            let csv = format == Some("csv");
+            let sep = match (csv, t.ends_with('\n')) {
+                (true, true) => "",
+                (true, false) => "\n",
+                (false, true) => "\n",
+                (false, false) => "\n\n",
+            };
+            t.push_str(sep);
+            t.push_str(if csv { "# " } else { "" });
+            t.push_str(&prov.footer());
+        }
+    }
+    if let Some(m) = resp
+        .pointer_mut("/result/_meta")
+        .and_then(|m| m.as_object_mut())
+    {
+        m.insert("provenance".to_string(), json!(prov));
+    }
+}
+
+fn dispatch_call(id: serde_json::Value, params: &serde_json::Value) -> serde_json::Value {
+    let name = params.get("name").and_then(|v| v.as_str()).unwrap_or("");
+    errors::take_network_failure();
+    if !capabilities().allows_tool(name) {
+        return ToolError::UnknownTool(name.to_string()).response(&id);
+    }
+    let args = params.get("arguments").unwrap_or(&serde_json::Value::Null);
+    // Tools that take the corpus as an argument (corpus_stats, regex_explain, …).
+    for key in ["source", "corpus"] {
+        if let Some(c) = args.get(key).and_then(|v| v.as_str()) {
+            if capabilities::is_corpus(c) && !capabilities().allows(c) {
+                return ToolError::Disabled {
+                    group: c.to_string(),
+                }
+                .response(&id);
+            }
+        }
+    }
+    let mut resp = dispatch_tool(id, params);
+    if errors::is_error_response(&resp) {
+        return resp;
+    }
+    attach_license(name, &mut resp);
+    attach_provenance(
+        name,
+        params.get("arguments").unwrap_or(&serde_json::Value::Null),
+        &mut resp,
+    );
+    resp
+}
+
+fn dispatch_tool(id: serde_json::Value, params: &serde_json::Value) -> serde_json::Value {
+    let name = params.get("name").and_then(|v| v.as_str()).unwrap_or("");
+    let args = params.get("arguments").cloned().unwrap_or(json!({}));
+    match name {
+        "daizo_version" => {
+            let data_status = json!({
+                "cbeta": cbeta_root().exists(),
+                "tipitaka": tipitaka_root().exists(),
+                "gretil": gretil_root().exists(),
+                "sarit": sarit_root().exists(),
+                "muktabodha": muktabodha_root().exists(),
+            });
+            let version_info = json!({
+                "version": VERSION,
+                "name": "daizo-mcp",
+                "description": "MCP server for Buddhist scripture retrieval (CBETA, Tipitaka, GRETIL, SARIT, MUKTABODHA, SAT, JOZEN, Tibetan online search)",
+                "homepage": "https://github.com/sinryo/daizo-mcp",
+                "data_available": data_status,
+                "data_path": daizo_home().to_string_lossy(),
+                "optimizations": [
+                    "ripgrep-based regex search",
+                    "ignore-based parallel file walking",
+                    "memory-mapped I/O",
+                    "LTO enabled"
+                ],
+                "features": [
+                    "CBETA Chinese Buddhist Canon (大正藏)",
+                    "Tipitaka Pāli Canon (パーリ聖典)",
+                    "GRETIL Sanskrit Texts (梵語文献)",
+                    "SARIT TEI P5 corpus",
+                    "MUKTABODHA Sanskrit library (IAST)",
+                    "SAT Database search",
+                    "Jodo Shu Zensho (浄土宗全書) search/fetch (online)",
+                    "Tibetan online full-text search (BUDA/BDRC, Adarshah)"
+                ]
+            });
+            return json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": {
+                    "content": [{"type": "text", "text": serde_json::to_string_pretty(&version_info).unwrap_or_default()}],
+                    "_meta": version_info
+                }
+            });
+        }
+        "daizo_usage" => {
+            let guide = r#"Daizo usage guide:
+
+## FASTEST: Direct ID access (no search needed!)
+
+### CBETA (Chinese Buddhist Canon)
+If you know the Taisho number, use cbeta_fetch with id directly:
+- T0001 = 長阿含經 (Dirghagama)
+- T0099 = 雜阿含經 (Samyuktagama)
+- T0125 = 增壹阿含經 (Ekottaragama)
+- T0262 = 妙法蓮華經 (Lotus Sutra)
+- T0235 = 金剛般若波羅蜜經 (Diamond Sutra)
+- T0251 = 般若波羅蜜多心經 (Heart Sutra)
+- T0945 = 大佛頂首楞嚴經 (Shurangama Sutra)
+- T2076 = 景德傳燈錄
+
+Example: cbeta_fetch({id: "T0262"}) - instant!
+
+### Tipitaka (Pāli Canon)
+Use Nikāya codes directly with tipitaka_fetch:
+- DN = Dīghanikāya (長部) - e.g., DN1, DN2
+- MN = Majjhimanikāya (中部) - e.g., MN1, MN2
+- SN = Saṃyuttanikāya (相応部) - e.g., SN1
+- AN = Aṅguttaranikāya (増支部) - e.g., AN1
+- KN = Khuddakanikāya (小部)
+
+Examples:
+- tipitaka_fetch({id: "DN1"}) - Brahmajāla Sutta (梵網経)
+- tipitaka_fetch({id: "MN1"}) - Mūlapariyāya Sutta
+- tipitaka_fetch({id: "s0101m.mul"}) - Direct file access
+
+### GRETIL (Sanskrit TEI)
+Use file stem directly with gretil_fetch:
+- saddharmapuNDarIka = 法華經 (Lotus Sutra)
+- vajracchedikA = 金剛般若經 (Diamond Sutra)
+- prajJApAramitAhRdayasUtra = 般若心經 (Heart Sutra)
+- azvaghoSa-buddhacarita = 佛所行讚 (Buddhacarita)
+- laGkAvatArasUtra = 楞伽經 (Lankavatara Sutra)
+- mahAparinirvANasUtra = 大般涅槃經 (Mahaparinirvana Sutra)
+
+Examples:
+- gretil_fetch({id: "saddharmapuNDarIka"}) - Lotus Sutra Sanskrit
+- gretil_fetch({id: "vajracchedikA"}) - Diamond Sutra Sanskrit
+- gretil_fetch({id: "sa_azvaghoSa-buddhacarita"}) - with sa_ prefix also works
+
+### SARIT (TEI P5)
+Use file stem directly with sarit_fetch:
+- sarit_fetch({id: "asvaghosa-buddhacarita"})
+
+### MUKTABODHA (Sanskrit library; local files)
+Use file stem directly with muktabodha_fetch:
+- muktabodha_fetch({id: "<file-stem>"})
+
+## Online sources (no local data needed)
+
+### SAT Database (online)
+Flow: sat_search -> sat_detail/sat_fetch (prefer useid)
+- sat_search({query: "法華経", exact: true})
+- sat_detail({useid: "..."})  # use startid/useid from SAT search
+
+### Jodo Shu Zensho (浄土宗全書, online)
+Flow: jozen_search -> jozen_fetch (lineno)
+- jozen_search({query: "薬師"})
+- jozen_fetch({lineno: "J01_0200B19"})
+
+### Tibetan full-text search (online)
+- tibetan_search({query: "བདེ་བ", sources: ["buda","adarshah"], exact: true})
+  - Tibetan Unicode or EWTS/Wylie accepted (best-effort auto-conversion)
+
+## If corpus/ID is unknown
+- Use daizo_resolve({query: "法華経"}) first, then follow the recommended fetch calls.
+
+## Standard flow (when ID unknown)
+1. Use *_search -> read _meta.fetchSuggestions
+2. Call *_fetch with the suggestion's args: {id, lineNumber, contextBefore, contextAfter, highlight:"検索語", format:"plain"} (the window covers the verse, paragraph or sentence; see `unit`)
+3. IMPORTANT: Always include 'highlight' parameter with the search term!
+4. Use *_pipeline only for multi-file summary; set autoFetch=false by default
+5. Several slices at once (parallel passages, many suggestions): *_fetch_batch({items:[{id, lineNumber}, ...], defaults:{highlight, maxChars}}) - one call, one block per item
+
+## Troubleshooting: If lineNumber doesn't work
+- ALWAYS pass 'highlight' param with search term for verification
+- Use larger contextBefore/contextAfter (e.g., 10-20 lines)
+- For very long texts, use part/juan parameter instead (CBETA): cbeta_fetch({id:"T0279", part:"001"})
+"#;
+            return json!({
+                "jsonrpc":"2.0",
+                "id": id,
+                "result": { "content": [{"type":"text","text": guide}], "_meta": {"source": "daizo_usage"} }
+            });
+        }
+        "daizo_aliases_reload" => {
+            let (ok, msg) = match daizo_core::aliases::load_user_aliases() {
+                Ok(t) => {
+                    let msg = match t.source.as_ref() {
+                        Some(p) => format!("Loaded {} aliases from {}", t.len(), p.display()),
+                        None => "No alias file found (set DAIZO_ALIASES or create ~/.daizo/aliases.toml)".to_string(),
+                    };
+                    if let Ok(mut g) = user_aliases().lock() {
+                        *g = t;
+                    }
+                    (true, msg)
+                }
+                Err(e) => (
+                    false,
+                    format!("Alias reload failed (previous table kept): {}", e),
+                ),
+            };
+            let g = user_aliases().lock().ok();
+            let list = args.get("list").and_then(|v| v.as_bool()).unwrap_or(false);
+            let aliases: serde_json::Map<String, serde_json::Value> = match (&g, list) {
+                (Some(t), true) => t.iter().map(|(a, e)| (a.to_string(), json!(e))).collect(),
+                _ => Default::default(),
+            };
+            let meta = json!({
+                "ok": ok,
+                "path": g.as_ref().and_then(|t| t.source.as_ref()).map(|p| p.to_string_lossy().to_string()),
+                "count": g.as_ref().map(|t| t.len()).unwrap_or(0),
+                "aliases": aliases,
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": msg}], "_meta": meta }});
+        }
+        "daizo_status" => {
+            let corpora = corpus_status();
+            let mut text = String::new();
+            for c in &corpora {
+                let name = c["corpus"].as_str().unwrap_or("");
+                if !c["present"].as_bool().unwrap_or(false) {
+                    text.push_str(&format!("{}: not installed\n", name));
+                    continue;
+                }
+                let commit = c["commit"]
+                    .as_str()
+                    .map(|h| h[..h.len().min(12)].to_string())
+                    .unwrap_or_else(|| "no git checkout".to_string());
+                let cloned = c["cloned"]
+                    .as_str()
+                    .map(|d| format!(", cloned {}", d))
+                    .unwrap_or_default();
+                let index = match (
+                    c["index"]["exists"].as_bool(),
+                    c["index"]["current"].as_bool(),
+                ) {
+                    (Some(false), _) => "not built",
+                    (_, Some(true)) => "current",
+                    (_, Some(false)) => "stale (built from another commit)",
+                    _ => "revision unknown",
+                };
+                let built = match (
+                    c["index"]["entries"].as_u64(),
+                    c["index"]["builtAt"].as_str(),
+                ) {
+                    (Some(n), Some(at)) => format!(", {} entries, built {}", n, at),
+                    (Some(n), None) => format!(", {} entries", n),
+                    _ => String::new(),
+                };
+                text.push_str(&format!(
+                    "{}: {}{}; index {}{}\n",
+                    name, commit, cloned, index, built
+                ));
+            }
+            let caches = daizo_core::health::cache_usage(&cache_dir());
+            let cache_total: u64 = caches.iter().map(|u| u.bytes).sum();
+            text.push_str(&format!(
+                "cache: {}",
+                daizo_core::health::human_bytes(cache_total)
+            ));
+            if !caches.is_empty() {
+                let parts: Vec<String> = caches
+                    .iter()
+                    .map(|u| format!("{} {}", u.name, daizo_core::health::human_bytes(u.bytes)))
+                    .collect();
+                text.push_str(&format!(" ({})", parts.join(", ")));
+            }
+            text.push('\n');
+            if capabilities().restricted() {
+                text.push_str(&format!("serving: {}\n", capabilities().summary()));
+            }
+            let scan = daizo_core::scan::ScanSettings::current();
+            if scan != daizo_core::scan::ScanSettings::default() {
+                let threads = match scan.threads {
+                    0 => "all cores".to_string(),
+                    n => format!("{} threads", n),
+                };
+                let io = match scan.io_bytes_per_sec >> 20 {
+                    0 => String::new(),
+                    mb => format!(", reads capped at {} MiB/s", mb),
+                };
+                let nice = if scan.nice { ", low priority" } else { "" };
+                text.push_str(&format!("scans: {}{}{}\n", threads, io, nice));
+            }
+            let rss = daizo_core::health::resident_bytes();
+            if let Some(b) = rss {
+                text.push_str(&format!(
+                    "memory: {} resident\n",
+                    daizo_core::health::human_bytes(b)
+                ));
+            }
+            let meta = json!({
+                "dataPath": daizo_home().to_string_lossy(),
+                "corpora": corpora,
+                "capabilities": capabilities().describe(),
+                "warmup": warmup_report(),
+                "scan": {"threads": scan.threads, "ioMbps": scan.io_bytes_per_sec >> 20, "nice": scan.nice},
+                "cache": {"path": cache_dir().to_string_lossy(), "bytes": cache_total, "dirs": caches},
+                "memory": {"residentBytes": rss},
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": meta }});
+        }
+        "daizo_metrics" => {
+            let scope = args
+                .get("scope")
+                .and_then(|v| v.as_str())
+                .unwrap_or("session");
+            let dir = daizo_core::metrics::default_dir();
+            let mut records: Vec<daizo_core::metrics::CallRecord> = match scope {
+                "session" => SESSION_CALLS
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .iter()
+                    .cloned()
+                    .collect(),
+                "log" => daizo_core::metrics::read_log(&dir),
+                other => {
+                    return ToolError::InvalidArgument {
+                        name: "scope".to_string(),
+                        value: other.to_string(),
+                        expected: "session or log".to_string(),
+                    }
+                    .response(&id);
+                }
+            };
+            if let Some(t) = args.get("tool").and_then(|v| v.as_str()) {
+                records.retain(|r| r.tool == t);
+            }
+            let summary = daizo_core::metrics::summarize(&records);
+            let enabled = metrics_enabled();
+            let mut text = String::new();
+            if !enabled {
+                text.push_str(
+                    "metrics are off; set DAIZO_METRICS=1 or [metrics] enabled = true to record calls\n",
+                );
+            }
+            text.push_str(&daizo_core::metrics::render(&summary));
+            let meta = json!({
+                "enabled": enabled,
+                "scope": scope,
+                "log": dir.join("requests.jsonl").to_string_lossy(),
+                "summary": summary,
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": meta }});
+        }
+        "index_rebuild" => {
+            let source = args.get("source").and_then(|v| v.as_str()).unwrap_or("");
+            let dry_run = args
+                .get("dryRun")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            let all = ["cbeta", "tipitaka", "gretil", "sarit", "muktabodha"];
+            let targets: Vec<&str> = match source {
+                "all" => all.to_vec(),
+                s if all.contains(&s) => vec![s],
+                _ => {
+                    return json!({"jsonrpc":"2.0","id": id, "error": {"code": -32602, "message": "source must be one of cbeta, tipitaka, gretil, sarit, muktabodha, all"}});
+                }
+            };
+            let status = corpus_status();
+            let cancel = daizo_core::cancel::CancelToken::current();
+            let mut text = String::new();
+            let mut reports = Vec::new();
+            for c in targets {
+                let Some(st) = status.iter().find(|s| s["corpus"] == c) else {
+                    continue;
+                };
+                let before = &st["index"];
+                if !corpus_present(c) {
+                    text.push_str(&format!("{}: not installed, skipped\n", c));
+                    reports
+                        .push(json!({"corpus": c, "action": "skipped", "reason": "not installed"}));
+                    continue;
+                }
+                if dry_run {
+                    let why = match (before["exists"].as_bool(), before["current"].as_bool()) {
+                        (Some(false), _) => "not built",
+                        (_, Some(true)) => "current",
+                        (_, Some(false)) => "stale",
+                        _ => "revision unknown",
+                    };
+                    text.push_str(&format!("{}: would rebuild (index {})\n", c, why));
+                    reports.push(json!({"corpus": c, "action": "wouldRebuild", "index": before}));
+                    continue;
+                }
+                if cancel.is_cancelled() {
+                    reports.push(json!({"corpus": c, "action": "cancelled"}));
+                    continue;
+                }
+                let t0 = Instant::now();
+                match rebuild_index(c) {
+                    Some(entries) => {
+                        let ms = t0.elapsed().as_millis() as u64;
+                        text.push_str(&format!(
+                            "{}: rebuilt, {} entries (was {}) in {} ms\n",
+                            c,
+                            entries.len(),
+                            before["entries"]
+                                .as_u64()
+                                .map(|n| n.to_string())
+                                .unwrap_or_else(|| "none".to_string()),
+                            ms,
+                        ));
+                        reports.push(json!({
+                            "corpus": c,
+                            "action": "rebuilt",
+                            "entries": entries.len(),
+                            "previousEntries": before["entries"],
+                            "elapsedMs": ms,
+                        }));
+                    }
+                    None => {
+                        text.push_str(&format!("{}: could not write the index\n", c));
+                        reports.push(json!({"corpus": c, "action": "failed"}));
+                    }
+                }
+            }
+            let meta = json!({"source": source, "dryRun": dry_run, "corpora": reports});
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": meta }});
+        }
+        "daizo_reload" => {
+            let (_, msg, mut meta) = reload_settings();
+            if args.get("show").and_then(|v| v.as_bool()).unwrap_or(false) {
+                meta["config"] = json!(*config::current());
+            }
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": msg}], "_meta": meta }});
+        }
+        "annotation_add" => {
+            let source = args
+                .get("source")
+                .and_then(|v| v.as_str())
+                .unwrap_or("cbeta")
+                .to_lowercase();
+            if local_source_root(&source).is_none() {
+                return ToolError::InvalidArgument {
+                    name: "source".to_string(),
+                    value: source,
+                    expected: capabilities::LOCAL_CORPORA.join(", "),
+                }
+                .response(&id);
+            }
+            if !capabilities().allows(&source) {
+                return ToolError::Disabled { group: source }.response(&id);
+            }
+            let wid = args.get("id").and_then(|v| v.as_str()).unwrap_or("").trim();
+            if wid.is_empty() || !daizo_core::path_resolver::safe_id(wid) {
+                return ToolError::InvalidArgument {
+                    name: "id".to_string(),
+                    value: wid.to_string(),
+                    expected: "a text id (T0262, s0101m.mul, file stem)".to_string(),
+                }
+                .response(&id);
+            }
+            if let Err(e) = require_corpus(&source) {
+                return e.response(&id);
+            }
+            let part = args.get("part").and_then(|v| v.as_str()).map(String::from);
+            let not_found = || ToolError::TextNotFound {
+                corpus: source.clone(),
+                id: wid.to_string(),
+            };
+            let Some(text) = resolve_local_source_path(&source, wid).and_then(|path| {
+                daizo_core::analysis::plain_text_for_source(&source, &path, part.as_deref(), false)
+            }) else {
+                return not_found().response(&id);
+            };
+            let start = args.get("startChar").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+            let end = args
+                .get("endChar")
+                .and_then(|v| v.as_u64())
+                .map(|n| n as usize)
+                .unwrap_or(start);
+            let total = text.chars().count();
+            if start > total || end > total {
+                return ToolError::InvalidArgument {
+                    name: if start > total {
+                        "startChar"
+                    } else {
+                        "endChar"
+                    }
+                    .to_string(),
+                    value: start.max(end).to_string(),
+                    expected: format!("a char offset up to {}", total),
+                }
+                .response(&id);
+            }
+            let tags = args
+                .get("tags")
+                .and_then(|v| v.as_array())
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|t| t.as_str())
+                        .map(|t| t.trim().trim_start_matches('#').to_string())
+                        .filter(|t| !t.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default();
+            let secs = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let a = daizo_core::annotations::Annotation {
+                source: source.clone(),
+                text_id: wid.to_string(),
+                part,
+                start_char: start,
+                end_char: end,
+                quote: text
+                    .chars()
+                    .skip(start)
+                    .take(end.saturating_sub(start))
+                    .collect(),
+                note: args
+                    .get("note")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .trim()
+                    .to_string(),
+                tags,
+                created: daizo_core::provenance::datetime_utc(secs),
+                ..Default::default()
+            };
+            let store =
+                daizo_core::annotations::Store::open(&daizo_core::annotations::annotations_dir());
+            let a = match store.add(a) {
+                Ok(a) => a,
+                Err(reason) => {
+                    return ToolError::InvalidArgument {
+                        name: "endChar".to_string(),
+                        value: end.to_string(),
+                        expected: reason,
+                    }
+                    .response(&id)
+                }
+            };
+            let heading = format!(
+                "Saved {} {} to {}",
+                if a.is_bookmark() {
+                    "bookmark"
+                } else {
+                    "annotation"
+                },
+                a.id,
+                store.path().display()
+            );
+            return annotations_response(&id, heading, std::slice::from_ref(&a), 1);
+        }
+        "annotation_list" | "annotation_search" => {
+            let s = |k: &str| {
+                args.get(k)
+                    .and_then(|v| v.as_str())
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+            };
+            let query = s("query");
+            if name == "annotation_search" && query.is_none() {
+                return ToolError::InvalidArgument {
+                    name: "query".to_string(),
+                    value: String::new(),
+                    expected: "text to look for in notes, quotes and tags".to_string(),
+                }
+                .response(&id);
+            }
+            let start = args.get("startChar").and_then(|v| v.as_u64());
+            let end = args.get("endChar").and_then(|v| v.as_u64());
+            let filter = daizo_core::annotations::Filter {
+                source: s("source").map(|v| v.to_lowercase()),
+                text_id: s("id"),
+                part: s("part"),
+                range: (start.is_some() || end.is_some()).then(|| {
+                    let st = start.unwrap_or(0) as usize;
+                    (st, end.map(|e| e as usize).unwrap_or(st))
+                }),
+                tag: s("tag").map(|t| t.trim_start_matches('#').to_string()),
+                query,
+            };
+            let store =
+                daizo_core::annotations::Store::open(&daizo_core::annotations::annotations_dir());
+            let found = store.list(&filter);
+            let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(100) as usize;
+            let heading = if found.is_empty() {
+                format!("No annotations found in {}", store.path().display())
+            } else {
+                format!("{} annotations", found.len())
+            };
+            return annotations_response(&id, heading, &found, limit);
+        }
+        "collection_create" | "collection_add" => {
+            let cname = args
+                .get("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .trim();
+            if !daizo_core::bundle::valid_name(cname) {
+                return collection_name_error(cname).response(&id);
+            }
+            let items = match collection_items(&args) {
+                Ok(items) => items,
+                Err(e) => return e.response(&id),
+            };
+            let dir = daizo_core::collections::collections_dir();
+            let exists = daizo_core::collections::exists(&dir, cname);
+            let mut c = if name == "collection_create" {
+                let overwrite = args
+                    .get("overwrite")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                if exists && !overwrite {
+                    return ToolError::InvalidArgument {
+                        name: "name".to_string(),
+                        value: cname.to_string(),
+                        expected: "a new collection name, or overwrite:true".to_string(),
+                    }
+                    .response(&id);
+                }
+                daizo_core::collections::Collection {
+                    name: cname.to_string(),
+                    note: args.get("note").and_then(|v| v.as_str()).map(String::from),
+                    created: daizo_core::provenance::today_utc(),
+                    items: Vec::new(),
+                }
+            } else {
+                if items.is_empty() {
+                    return ToolError::InvalidArgument {
+                        name: "items".to_string(),
+                        value: String::new(),
+                        expected: "items to add, or a single id".to_string(),
+                    }
+                    .response(&id);
+                }
+                match daizo_core::collections::Collection::load(&dir, cname) {
+                    Ok(c) => c,
+                    Err(_) => {
+                        return ToolError::InvalidArgument {
+                            name: "name".to_string(),
+                            value: cname.to_string(),
+                            expected: format!(
+                                "an existing collection ({}); create it with collection_create",
+                                daizo_core::collections::list(&dir).join(", ")
+                            ),
+                        }
+                        .response(&id)
+                    }
+                }
+            };
+            let added = c.add(items);
+            let path = match c.save(&dir) {
+                Ok(p) => p,
+                Err(e) => {
+                    return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": e}], "isError": true, "_meta": {"name": cname, "ok": false} }});
+                }
+            };
+            let mut text = format!(
+                "Collection '{}': {} items ({} added), saved to {}",
+                cname,
+                c.items.len(),
+                added,
+                path.display()
+            );
+            for (i, it) in c.items.iter().enumerate() {
+                text.push_str(&format!("\n{}. {} {}", i, it.source, it.label()));
+            }
+            let meta = json!({
+                "name": cname,
+                "ok": true,
+                "path": path.to_string_lossy(),
+                "added": added,
+                "items": c.items,
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": meta }});
+        }
+        "collection_fetch" => {
+            let dir = daizo_core::collections::collections_dir();
+            let cname = args
+                .get("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .trim();
+            if cname.is_empty() {
+                let names = daizo_core::collections::list(&dir);
+                let text = if names.is_empty() {
+                    format!("No collections in {}", dir.display())
+                } else {
+                    format!("Collections in {}:\n{}", dir.display(), names.join("\n"))
+                };
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": {"collections": names} }});
+            }
+            if !daizo_core::bundle::valid_name(cname) {
+                return collection_name_error(cname).response(&id);
+            }
+            let c = match daizo_core::collections::Collection::load(&dir, cname) {
+                Ok(c) => c,
+                Err(e) => {
+                    return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": e}], "isError": true, "_meta": {"name": cname, "ok": false} }});
+                }
+            };
+            if args
+                .get("listOnly")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false)
+            {
+                let mut text = format!("Collection '{}' ({} items)", c.name, c.items.len());
+                if let Some(n) = &c.note {
+                    text.push_str(&format!(": {}", n));
+                }
+                for (i, it) in c.items.iter().enumerate() {
+                    text.push_str(&format!("\n{}. {} {}", i, it.source, it.label()));
+                }
+                let meta =
+                    json!({"name": c.name, "note": c.note, "created": c.created, "items": c.items});
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": meta }});
+            }
+            let budget = call_max_chars(&args).max(1);
+            let token_budget = call_max_tokens(&args);
+            let mut used_tokens = 0usize;
+            let mut index = args.get("item").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+            let mut start = args.get("startChar").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+            let mut used = 0usize;
+            let mut content = Vec::new();
+            let mut report = Vec::new();
+            while index < c.items.len()
+                && used < budget
+                && token_budget.is_none_or(|t| used_tokens < t)
+            {
+                let it = &c.items[index];
+                let (tool, mut call_args) = it.fetch_call();
+                call_args["startChar"] = json!(start);
+                call_args["maxChars"] = json!(budget - used);
+                if let Some(t) = token_budget {
+                    call_args["maxTokens"] = json!(t - used_tokens);
+                }
+                let mut entry = json!({"index": index, "label": it.label(), "source": it.source, "startChar": start});
+                let resp = if capabilities().allows(&it.source) {
+                    run_call(json!(index), &json!({"name": tool, "arguments": call_args}))
+                } else {
+                    ToolError::Disabled {
+                        group: it.source.clone(),
+                    }
+                    .response(&json!(index))
+                };
+                let text = resp
+                    .pointer("/result/content/0/text")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+                let heading = format!("## {}. {} {}", index, it.source, it.label());
+                if errors::is_error_response(&resp) {
+                    let msg = resp
+                        .pointer("/error/message")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or(text);
+                    entry["ok"] = json!(false);
+                    entry["error"] = json!(msg);
+                    content.push(
+                        json!({"type":"text","text": format!("{}\n\nerror: {}", heading, msg)}),
+                    );
+                    report.push(entry);
+                    index += 1;
+                    start = 0;
+                    continue;
+                }
+                let meta = resp.pointer("/result/_meta");
+                let n = |k: &str| {
+                    meta.and_then(|m| m.get(k))
+                        .and_then(|v| v.as_u64())
+                        .map(|v| v as usize)
+                };
+                let chars = text.chars().count();
+                let end = n("returnedEnd").unwrap_or(start + chars);
+                let total = n("totalChars").unwrap_or(end);
+                used += end.saturating_sub(start).max(1);
+                used_tokens += daizo_core::tokens::estimate(text).max(1);
+                entry["ok"] = json!(true);
+                entry["returnedEnd"] = json!(end);
+                entry["totalChars"] = json!(total);
+                content.push(json!({"type":"text","text": format!("{}\n\n{}", heading, text)}));
+                report.push(entry);
+                if end < total {
+                    start = end;
+                    break;
+                }
+                index += 1;
+                start = 0;
+            }
+            let done = index >= c.items.len();
+            let mut next_args = args.clone();
+            if let Some(o) = next_args.as_object_mut() {
+                o.insert("item".to_string(), json!(index));
+            }
+            let cursor = if done {
+                None
+            } else {
+                next_cursor("collection_fetch", &next_args, start, start + 1)
+            };
+            if content.is_empty() {
+                content.push(json!({"type":"text","text": format!("Collection '{}' has no items from {}", c.name, index)}));
+            }
+            let meta = json!({
+                "name": c.name,
+                "count": c.items.len(),
+                "items": report,
+                "done": done,
+                "next": if done { serde_json::Value::Null } else { json!({"item": index, "startChar": start}) },
+                "nextCursor": cursor,
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": content, "_meta": meta }});
+        }
+        "research_bundle_record" => {
+            let name = args
+                .get("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .trim();
+            let steps = args
+                .get("steps")
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default();
+            let dir = daizo_core::bundle::bundles_dir();
+            let fail = |reason: String| json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": reason}], "isError": true, "_meta": {"name": name, "ok": false} }});
+            if !daizo_core::bundle::valid_name(name) {
+                return fail(format!(
+                    "invalid bundle name '{}': use letters, digits, '-', '_' or '.'",
+                    name
+                ));
+            }
+            if steps.is_empty() {
+                return fail("steps is empty".to_string());
+            }
+            let overwrite = args
+                .get("overwrite")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            if !overwrite && dir.join(format!("{}.json", name)).exists() {
+                return fail(format!(
+                    "bundle '{}' exists; pass overwrite:true to replace it",
+                    name
+                ));
+            }
+            let mut recorded = Vec::with_capacity(steps.len());
+            let mut summary = String::new();
+            for (i, st) in steps.iter().enumerate() {
+                let tool = st.get("tool").and_then(|v| v.as_str()).unwrap_or("");
+                let call_args = st.get("arguments").cloned().unwrap_or(json!({}));
+                match run_bundle_step(tool, &call_args) {
+                    Ok((text, meta)) => {
+                        let step =
+                            daizo_core::bundle::Step::new(tool, call_args, &text, meta.as_ref());
+                        summary.push_str(&format!(
+                            "{}. {} -> {} chars, sha1 {}\n",
+                            i + 1,
+                            tool,
+                            text.chars().count(),
+                            &step.text_hash[..12]
+                        ));
+                        recorded.push(step);
+                    }
+                    Err(e) => return fail(format!("step {} ({}) failed: {}", i + 1, tool, e)),
+                }
+            }
+            let bundle = daizo_core::bundle::Bundle {
+                name: name.to_string(),
+                note: args.get("note").and_then(|v| v.as_str()).map(String::from),
+                created: daizo_core::provenance::today_utc(),
+                tool: format!("daizo-mcp {}", VERSION),
+                snapshots: corpus_snapshots(),
+                steps: recorded,
+            };
+            let path = match bundle.save(&dir) {
+                Ok(p) => p,
+                Err(e) => return fail(e),
+            };
+            let text = format!(
+                "Recorded bundle '{}' ({} steps) to {}\n{}",
+                name,
+                bundle.steps.len(),
+                path.display(),
+                summary
+            );
+            let meta = json!({
+                "name": name,
+                "ok": true,
+                "path": path.to_string_lossy(),
+                "snapshots": bundle.snapshots,
+                "steps": bundle.steps.iter().map(|s| json!({
+                    "tool": s.tool,
+                    "textHash": s.text_hash,
+                    "refs": s.refs,
+                    "resultIds": s.result_ids,
+                })).collect::<Vec<_>>(),
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": meta }});
+        }
+        "research_bundle_verify" => {
+            let dir = daizo_core::bundle::bundles_dir();
+            let name = args
+                .get("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .trim();
+            if name.is_empty() {
+                let names = daizo_core::bundle::list(&dir);
+                let text = if names.is_empty() {
+                    format!("No bundles in {}", dir.display())
+                } else {
+                    format!("Bundles in {}:\n{}", dir.display(), names.join("\n"))
+                };
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": {"bundles": names} }});
+            }
+            let bundle = match daizo_core::bundle::Bundle::load(&dir, name) {
+                Ok(b) => b,
+                Err(e) => {
+                    return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": e}], "isError": true, "_meta": {"name": name, "ok": false} }});
+                }
+            };
+            let checks: Vec<daizo_core::bundle::StepCheck> = bundle
+                .steps
+                .iter()
+                .enumerate()
+                .map(|(i, st)| {
+                    let replay = run_bundle_step(&st.tool, &st.arguments).map(|(t, _)| t);
+                    st.check(i + 1, replay.as_deref().map_err(|e| e.clone()))
+                })
+                .collect();
+            let moved = bundle.moved_snapshots(&corpus_snapshots());
+            let same = checks.iter().filter(|c| c.status == "same").count();
+            let mut text = format!(
+                "Bundle '{}' (recorded {}): {}/{} steps unchanged\n",
+                name,
+                bundle.created,
+                same,
+                checks.len()
+            );
+            for c in &checks {
+                text.push_str(&format!("{}. {}: {}", c.step, c.tool, c.status));
+                if let Some(at) = c.first_difference {
+                    text.push_str(&format!(" (differs from character {})", at));
+                }
+                if let Some(e) = &c.error {
+                    text.push_str(&format!(" ({})", e));
+                }
+                text.push('\n');
+            }
+            for (corpus, was, now) in &moved {
+                text.push_str(&format!(
+                    "{} checkout moved: {} -> {}\n",
+                    corpus,
+                    &was[..was.len().min(12)],
+                    now.as_deref()
+                        .map(|s| &s[..s.len().min(12)])
+                        .unwrap_or("none")
+                ));
+            }
+            let meta = json!({
+                "name": name,
+                "created": bundle.created,
+                "ok": same == checks.len(),
+                "steps": checks,
+                "snapshotsMoved": moved.iter().map(|(c, was, now)| json!({"corpus": c, "recorded": was, "current": now})).collect::<Vec<_>>(),
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": meta }});
+        }
+        "regex_explain" => {
+            let (text, meta) = explain_query(&args);
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": meta }});
+        }
+        "daizo_selftest" => {
+            let corpora: Option<Vec<String>> =
+                args.get("corpora").and_then(|v| v.as_array()).map(|a| {
+                    a.iter()
+                        .filter_map(|x| x.as_str().map(|s| s.to_string()))
+                        .collect()
+                });
+            let online = args
+                .get("online")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            let (text, meta) = run_selftest(corpora.as_deref(), online);
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": meta }});
+        }
+        "daizo_profile" => {
+            let tool = args
+                .get("tool")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .trim()
+                .to_string();
+            let call_args = args.get("arguments").cloned().unwrap_or(json!({}));
+            let iterations = args
+                .get("iterations")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(10)
+                .clamp(1, 10_000) as usize;
+            let warmup = args
+                .get("warmup")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(1)
+                .clamp(0, 10_000) as usize;
+            let include_samples = args
+                .get("includeSamples")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+
+            if tool.is_empty() || tool == "daizo_profile" {
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "invalid tool"}], "_meta": {"tool": tool, "ok": false} }});
+            }
+
+            let params = json!({"name": tool, "arguments": call_args});
+
+            // Warmup (ignore timings)
+            for _ in 0..warmup {
+                let _ = handle_call(json!(0), &params);
+            }
+
+            let mut samples_ms: Vec<f64> = Vec::with_capacity(iterations);
+            for i in 0..iterations {
+                let t0 = Instant::now();
+                let _ = handle_call(json!(i as u64), &params);
+                let dt = t0.elapsed().as_secs_f64() * 1000.0;
+                samples_ms.push(dt);
+            }
+
+            let mut sorted = samples_ms.clone();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            let mean_ms = if samples_ms.is_empty() {
+                0.0
+            } else {
+                samples_ms.iter().sum::<f64>() / (samples_ms.len() as f64)
+            };
+            let pct = |p: f64| -> f64 {
+                if sorted.is_empty() {
+                    return 0.0;
+                }
+                let n = sorted.len();
+                let idx = ((p * (n as f64 - 1.0)).round() as usize).min(n - 1);
+                sorted[idx]
+            };
+            let min_ms = *sorted.first().unwrap_or(&0.0);
+            let max_ms = *sorted.last().unwrap_or(&0.0);
+            let p50 = pct(0.50);
+            let p90 = pct(0.90);
+            let p95 = pct(0.95);
+            let p99 = pct(0.99);
+
+            let summary = format!(
+                "daizo_profile tool={}\niterations={} warmup={}\nmin={:.3}ms p50={:.3}ms mean={:.3}ms p90={:.3}ms p95={:.3}ms p99={:.3}ms max={:.3}ms\n",
+                tool, iterations, warmup, min_ms, p50, mean_ms, p90, p95, p99, max_ms
+            );
+
+            let meta = json!({
+                "tool": tool,
+                "iterations": iterations,
+                "warmup": warmup,
+                "minMs": min_ms,
+                "p50Ms": p50,
+                "meanMs": mean_ms,
+                "p90Ms": p90,
+                "p95Ms": p95,
+                "p99Ms": p99,
+                "maxMs": max_ms,
+                "samplesMs": if include_samples { Some(samples_ms) } else { None::<Vec<f64>> }
+            });
+
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary}], "_meta": meta }});
+        }
+        "cite" => {
+            let src = args
+                .get("source")
+                .and_then(|v| v.as_str())
+                .unwrap_or("cbeta");
+            if !capabilities::LOCAL_CORPORA.contains(&src) {
+                return ToolError::InvalidArgument {
+                    name: "source".to_string(),
+                    value: src.to_string(),
+                    expected: "cbeta, tipitaka, gretil, sarit or muktabodha".to_string(),
+                }
+                .response(&id);
+            }
+            if let Err(e) = require_corpus(src) {
+                return e.response(&id);
+            }
+            let wid = args.get("id").and_then(|v| v.as_str()).unwrap_or("").trim();
+            let format = args
+                .get("format")
+                .and_then(|v| v.as_str())
+                .unwrap_or("plain");
+            if !["plain", "bibtex", "csl-json", "all"].contains(&format) {
+                return ToolError::InvalidArgument {
+                    name: "format".to_string(),
+                    value: format.to_string(),
+                    expected: "plain, bibtex, csl-json or all".to_string(),
+                }
+                .response(&id);
+            }
+            let Some(path) = Some(wid)
+                .filter(|w| !w.is_empty() && daizo_core::path_resolver::safe_id(w))
+                .and_then(|w| resolve_local_source_path(src, w))
+                .filter(|p| p.is_file())
+            else {
+                return text_not_found(src, &args).response(&id);
+            };
+            let xml = match src {
+                "cbeta" => cbeta_xml_cached(&path).as_str().to_string(),
+                _ => fs::read(&path)
+                    .map(|b| decode_xml_bytes(&b))
+                    .unwrap_or_default(),
+            };
+            let index = corpus_index(src);
+            let entry = index.as_ref().and_then(|idx| {
+                idx.iter()
+                    .find(|e| Path::new(&e.path) == path)
+                    .or_else(|| idx.iter().find(|e| e.id == wid))
+            });
+            let part = args.get("part").and_then(|v| v.as_str());
+            if let Some(p) = part.filter(|_| src == "cbeta") {
+                if juan_map_cached(&path, Some(&xml)).is_none_or(|m| m.get(p).is_none()) {
+                    return ToolError::InvalidArgument {
+                        name: "part".to_string(),
+                        value: p.to_string(),
+                        expected: "a juan of the text (e.g. '001')".to_string(),
+                    }
+                    .response(&id);
+                }
+            }
+            let target = daizo_core::cite::Target {
+                part,
+                lb: args.get("lb").and_then(|v| v.as_str()),
+                line: args
+                    .get("lineNumber")
+                    .and_then(|v| v.as_u64())
+                    .map(|n| n as usize),
+                reference: args.get("reference").and_then(|v| v.as_str()),
+            };
+            let c = daizo_core::cite::cite(
+                src,
+                wid,
+                entry,
+                &xml,
+                &target,
+                &daizo_core::provenance::today_utc(),
+            );
+            let bibtex = || c.bibtex();
+            let csl = || serde_json::to_string_pretty(&json!([c.csl_json()])).unwrap_or_default();
+            let text = match format {
+                "bibtex" => bibtex(),
+                "csl-json" => csl(),
+                "all" => format!("{}\n\n{}\n\n{}", c.plain(), bibtex(), csl()),
+                _ => c.plain(),
+            };
+            let meta = json!({
+                "source": src,
+                "id": c.id,
+                "format": format,
+                "citation": c,
+                "sourcePath": path.to_string_lossy(),
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": meta }});
+        }
+        "daizo_resolve" => {
+            let q = args
+                .get("query")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .trim();
+            if q.is_empty() {
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "query is empty"}], "_meta": {"query": q, "count": 0, "candidates": []} }});
+            }
+
+            let sources: Vec<String> = args
+                .get("sources")
+                .and_then(|v| v.as_array())
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|x| x.as_str().map(|s| s.to_string()))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_else(|| {
+                    vec![
+                        "cbeta".to_string(),
+                        "tipitaka".to_string(),
+                        "gretil".to_string(),
+                        "sarit".to_string(),
+                        "muktabodha".to_string(),
+                    ]
+                });
+            let limit_per_source = args
+                .get("limitPerSource")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(5) as usize;
+            let limit_total = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
+            let prefer_source = args
+                .get("preferSource")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+            let min_score = args
+                .get("minScore")
+                .and_then(|v| v.as_f64())
+                .map(|v| v as f32)
+                .unwrap_or(config::current().scoring.min_score);
+
+            let mut cands_scored: Vec<(f32, serde_json::Value)> = Vec::new();
+
+            // Direct ID detection (fast path)
+            let mut direct_id_mode = false;
+            let q_nospace = q.split_whitespace().collect::<String>();
+            let q_upper = q_nospace.to_ascii_uppercase();
+            // CBETA: T + 1-4 digits
+            if sources.iter().any(|s| s == "cbeta") && q_upper.starts_with('T') {
+                let digits: String = q_upper
+                    .chars()
+                    .skip(1)
+                    .take_while(|c| c.is_ascii_digit())
+                    .collect();
+                if !digits.is_empty() {
+                    if let Ok(n) = digits.parse::<u32>() {
+                        let id_norm = format!("T{:04}", n);
+                        let bias = if prefer_source.as_deref() == Some("cbeta") {
+                            0.02
+                        } else {
+                            0.0
+                        };
+                        let cand = json!({
+                                "source": "cbeta",
+                                "id": id_norm,
+                                "title": null,
+                                "score": 1.0,
+                                "fetch": {"tool": "cbeta_fetch", "args": {"id": format!("T{:04}", n)}},
+                                "resolvedBy": "direct-id"
+                        });
+                        cands_scored.push((1.20 + bias, cand));
+                        direct_id_mode = true;
+                    }
+                }
+            }
+            // Tipitaka: DN/MN/SN/AN/KN + digits
+            if sources.iter().any(|s| s == "tipitaka") {
+                for pref in ["DN", "MN", "SN", "AN", "KN"] {
+                    if let Some(rest) = q_upper.strip_prefix(pref) {
+                        let digits: String = rest.chars().filter(|c| c.is_ascii_digit()).collect();
+                        if !digits.is_empty() {
+                            if let Ok(n) = digits.parse::<u32>() {
+                                let id_norm = format!("{}{}", pref, n);
+                                let bias = if prefer_source.as_deref() == Some("tipitaka") {
+                                    0.02
+                                } else {
+                                    0.0
+                                };
+                                let cand = json!({
+                                    "source": "tipitaka",
+                                    "id": id_norm,
+                                    "title": null,
+                                    "score": 1.0,
+                                    "fetch": {"tool": "tipitaka_fetch", "args": {"id": format!("{}{}", pref, n)}},
+                                    "resolvedBy": "direct-id"
+                                });
+                                cands_scored.push((1.20 + bias, cand));
+                                direct_id_mode = true;
+                            }
+                        }
+                    }
+                }
+            }
+            // Tipitaka file stem hint
+            if sources.iter().any(|s| s == "tipitaka")
+                && q_nospace.contains(".mul")
+                && !q_nospace.contains(' ')
+            {
+                let stem = q_nospace.clone();
+                let bias = if prefer_source.as_deref() == Some("tipitaka") {
+                    0.02
+                } else {
+                    0.0
+                };
+                let cand = json!({
+                    "source": "tipitaka",
+                    "id": stem,
+                    "title": null,
+                    "score": 1.0,
+                    "fetch": {"tool": "tipitaka_fetch", "args": {"id": q_nospace}},
+                    "resolvedBy": "direct-id"
+                });
+                cands_scored.push((1.20 + bias, cand));
+                direct_id_mode = true;
+            }
+
+            // Cheap cross-corpus aliases (no index load).
+            cands_scored.extend(resolve_crosswalk_candidates(
+                q,
+                &sources,
+                prefer_source.as_deref(),
+            ));
+
+            // Title index resolution (CPU only). Parallelize across corpora to reduce tail latency.
+            if !direct_id_mode {
+                let prefer = prefer_source.as_deref();
+                let do_cbeta = sources.iter().any(|s| s == "cbeta");
+                let do_tipitaka = sources.iter().any(|s| s == "tipitaka");
+                let do_gretil = sources.iter().any(|s| s == "gretil");
+                let do_sarit = sources.iter().any(|s| s == "sarit");
+                let do_muktabodha = sources.iter().any(|s| s == "muktabodha");
+                let jobs = (do_cbeta as usize)
+                    + (do_tipitaka as usize)
+                    + (do_gretil as usize)
+                    + (do_sarit as usize)
+                    + (do_muktabodha as usize);
+                if jobs >= 2 {
+                    std::thread::scope(|scope| {
+                        let h_cbeta = if do_cbeta {
+                            Some(scope.spawn(|| {
+                                resolve_title_candidates_cbeta(
+                                    q,
+                                    limit_per_source,
+                                    min_score,
+                                    prefer,
+                                )
+                            }))
+                        } else {
+                            None
+                        };
                         let h_tipitaka = if do_tipitaka {
                             Some(scope.spawn(|| {
                                 resolve_title_candidates_tipitaka(
@@ -2337,1629 +6576,3347 @@ Flow: jozen_search -> jozen_fetch (lineno)
                         if let Some(h) = h_muktabodha {
                             cands_scored.extend(h.join().unwrap_or_default());
                         }
-                    });
+                    });
+                } else {
+                    if do_cbeta {
+                        cands_scored.extend(resolve_title_candidates_cbeta(
+                            q,
+                            limit_per_source,
+                            min_score,
+                            prefer,
+                        ));
+                    }
+                    if do_tipitaka {
+                        cands_scored.extend(resolve_title_candidates_tipitaka(
+                            q,
+                            limit_per_source,
+                            min_score,
+                            prefer,
+                        ));
+                    }
+                    if do_gretil {
+                        cands_scored.extend(resolve_title_candidates_gretil(
+                            q,
+                            limit_per_source,
+                            min_score,
+                            prefer,
+                        ));
+                    }
+                    if do_sarit {
+                        cands_scored.extend(resolve_title_candidates_sarit(
+                            q,
+                            limit_per_source,
+                            min_score,
+                            prefer,
+                        ));
+                    }
+                    if do_muktabodha {
+                        cands_scored.extend(resolve_title_candidates_muktabodha(
+                            q,
+                            limit_per_source,
+                            min_score,
+                            prefer,
+                        ));
+                    }
+                }
+            }
+
+            cands_scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+            let mut candidates: Vec<serde_json::Value> = Vec::new();
+            let mut seen: std::collections::HashSet<(String, String)> =
+                std::collections::HashSet::new();
+            for (_s, v) in cands_scored.into_iter() {
+                let src = v
+                    .get("source")
+                    .and_then(|x| x.as_str())
+                    .unwrap_or("")
+                    .to_string();
+                let cid = v
+                    .get("id")
+                    .and_then(|x| x.as_str())
+                    .unwrap_or("")
+                    .to_string();
+                if src.is_empty() || cid.is_empty() {
+                    continue;
+                }
+                if seen.insert((src, cid)) {
+                    candidates.push(v);
+                }
+                if candidates.len() >= limit_total {
+                    break;
+                }
+            }
+            let pick = candidates.first().cloned();
+
+            let mut summary = format!("Candidates for '{}':\n", q);
+            for (i, c) in candidates.iter().enumerate() {
+                let src = c.get("source").and_then(|v| v.as_str()).unwrap_or("");
+                let cid = c.get("id").and_then(|v| v.as_str()).unwrap_or("");
+                let title = c.get("title").and_then(|v| v.as_str()).unwrap_or("");
+                let sc = c.get("score").and_then(|v| v.as_f64()).unwrap_or(0.0);
+                if title.is_empty() {
+                    summary.push_str(&format!("{}. [{}] {} (score {:.3})\n", i + 1, src, cid, sc));
+                } else {
+                    summary.push_str(&format!(
+                        "{}. [{}] {}  {} (score {:.3})\n",
+                        i + 1,
+                        src,
+                        cid,
+                        title,
+                        sc
+                    ));
+                }
+            }
+            if candidates.is_empty() {
+                summary.push_str("0 candidates\n");
+            }
+
+            let meta = json!({
+                "query": q,
+                "sources": sources,
+                "count": candidates.len(),
+                "candidates": candidates,
+                "pick": pick
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary}], "_meta": meta }});
+        }
+        "cbeta_title_search" => {
+            if let Err(e) = require_corpus("cbeta") {
+                return e.response(&id);
+            }
+            let q = args
+                .get("query")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .trim()
+                .to_string();
+            let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
+            let filter = match cbeta_filter(&args) {
+                Ok(f) => f,
+                Err(e) => return e.response(&id),
+            };
+            let idx = load_or_build_cbeta_index();
+            let hits = best_match_where(&idx, &q, limit * RERANK_POOL, |e| filter.matches(e));
+            let hits = rerank(hits, std::slice::from_ref(&q), limit);
+            let summary = hits
+                .iter()
+                .enumerate()
+                .map(|(i, h)| {
+                    let tr = h
+                        .entry
+                        .meta
+                        .as_ref()
+                        .and_then(|m| m.get("translator"))
+                        .map(|s| s.as_str())
+                        .unwrap_or("");
+                    if tr.is_empty() {
+                        format!("{}. {}  {}", i + 1, h.entry.id, h.entry.title)
+                    } else {
+                        format!("{}. {}  {}  [{}]", i + 1, h.entry.id, h.entry.title, tr)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let results: Vec<_> = hits
+                .iter()
+                .map(|h| {
+                    let meta = h.entry.meta.as_ref();
+                    let author = meta.and_then(|m| m.get("author").cloned());
+                    let translator = meta.and_then(|m| m.get("translator").cloned());
+                    json!({
+                        "id": h.entry.id,
+                        "title": h.entry.title,
+                        "titles": daizo_core::titles::of_entry(h.entry),
+                        "path": h.entry.path,
+                        "score": h.score,
+                        "matchReason": h.reason,
+                        "author": author,
+                        "translator": translator,
+                        "meta": {
+                            "author": meta.and_then(|m| m.get("author").cloned()),
+                            "editor": meta.and_then(|m| m.get("editor").cloned()),
+                            "translator": meta.and_then(|m| m.get("translator").cloned()),
+                            "publisher": meta.and_then(|m| m.get("publisher").cloned()),
+                            "date": meta.and_then(|m| m.get("date").cloned()),
+                            "idno": meta.and_then(|m| m.get("idno").cloned()),
+                            "canon": meta.and_then(|m| m.get("canon").cloned()),
+                            "nnum": meta.and_then(|m| m.get("nnum").cloned()),
+                            "juanCount": meta.and_then(|m| m.get("juanCount").cloned()),
+                            "fileSize": meta.and_then(|m| m.get("fileSize").cloned()),
+                            "textChars": meta.and_then(|m| m.get("textChars").cloned()),
+                            "juanChars": daizo_core::sizes::of_entry(h.entry).2,
+                            "headsPreview": meta.and_then(|m| m.get("headsPreview").cloned()),
+                            "muluPreview": meta.and_then(|m| m.get("muluPreview").cloned()),
+                            "respAll": meta.and_then(|m| m.get("respAll").cloned()),
+                        }
+                    })
+                })
+                .collect();
+            let mut meta = json!({
+                "count": results.len(),
+                "results": results
+            });
+            if !filter.is_empty() {
+                meta["filter"] = json!(filter);
+            }
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary }], "_meta": meta }});
+        }
+        "cbeta_fetch" => {
+            if let Some(src) = remote_fallback("cbeta") {
+                return cbeta_online_fetch(id, src, &args);
+            }
+            if let Err(e) = require_corpus("cbeta") {
+                return e.response(&id);
+            }
+            if let Some(c) = args
+                .get("id")
+                .and_then(|v| v.as_str())
+                .and_then(daizo_core::repo::canon_of)
+            {
+                ensure_cbeta_canon(c);
+            }
+            let mut matched_id: Option<String> = None;
+            let mut matched_title: Option<String> = None;
+            let mut matched_score: Option<f32> = None;
+            let mut path: PathBuf = PathBuf::new();
+            // 高速化: IDが指定されている場合は直接パス解決を試み、インデックスのロードを回避
+            if let Some(id) = args.get("id").and_then(|v| v.as_str()) {
+                // まず直接パス解決を試みる（インデックス不要、高速）
+                if let Some(direct_path) = daizo_core::path_resolver::resolve_cbeta_path_direct(id)
+                {
+                    matched_id = Some(id.to_string());
+                    path = direct_path;
+                    // タイトルは後でインデックスから取得（オプショナル、遅延ロード）
                 } else {
-                    if do_cbeta {
-                        cands_scored.extend(resolve_title_candidates_cbeta(
-                            q,
-                            limit_per_source,
-                            min_score,
-                            prefer,
-                        ));
+                    // フォールバック: インデックスから検索
+                    let idx = load_or_build_cbeta_index();
+                    if let Some(hit) = idx.iter().find(|e| e.id == id) {
+                        matched_id = Some(hit.id.clone());
+                        matched_title = Some(hit.title.clone());
+                        path = PathBuf::from(&hit.path);
+                    } else {
+                        // 最終フォールバック: WalkDir検索
+                        path = resolve_cbeta_path_by_id(id).unwrap_or_else(|| PathBuf::from(""));
+                        matched_id = Some(id.to_string());
                     }
-                    if do_tipitaka {
-                        cands_scored.extend(resolve_title_candidates_tipitaka(
-                            q,
-                            limit_per_source,
-                            min_score,
-                            prefer,
-                        ));
+                }
+            } else if let Some(q) = args.get("query").and_then(|v| v.as_str()) {
+                let idx = load_or_build_cbeta_index();
+                if let Some(hit) = best_match(&idx, q, 1).into_iter().next() {
+                    matched_id = Some(hit.entry.id.clone());
+                    matched_title = Some(hit.entry.title.clone());
+                    matched_score = Some(hit.score);
+                    path = PathBuf::from(&hit.entry.path);
+                }
+            }
+            if !path.is_file() || !in_corpus("cbeta", &path) {
+                return text_not_found("cbeta", &args).response(&id);
+            }
+            note_hot_file(&path);
+            let xml_arc = cbeta_xml_cached(&path);
+            let xml = xml_arc.as_str();
+            // includeNotes support
+            let include_notes = args
+                .get("includeNotes")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            let is_plain = args
+                .get("format")
+                .and_then(|v| v.as_str())
+                .map(|s| s.eq_ignore_ascii_case("plain"))
+                .unwrap_or(false);
+
+            // Effective highlight pattern (also used for focusHighlight).
+            let hl_in = args
+                .get("highlight")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+            let mut hl_use_re = args
+                .get("highlightRegex")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            let mut hl_pat: Option<String> = None;
+            if let Some(h0) = &hl_in {
+                let looks_like = h0.chars().any(|c| ".+*?[](){}|\\".contains(c));
+                if looks_like && !hl_use_re {
+                    hl_use_re = true;
+                    hl_pat = Some(h0.clone());
+                } else if h0.chars().any(|c| c.is_whitespace()) && !looks_like && !hl_use_re {
+                    hl_use_re = true;
+                    hl_pat = Some(ws_cjk_variant_fuzzy_regex_literal(h0));
+                } else if !hl_use_re && !looks_like {
+                    // Expand variants and escape as regex for CBETA by default.
+                    hl_use_re = true;
+                    hl_pat = Some(ws_cjk_variant_fuzzy_regex_literal(h0));
+                } else {
+                    hl_pat = Some(h0.clone());
+                }
+            }
+
+            let mut gaiji: Option<Arc<std::collections::HashMap<String, String>>> = None;
+            let mut ensure_gaiji = || {
+                if gaiji.is_none() {
+                    gaiji = Some(cbeta_gaiji_cached(&path, xml));
+                }
+            };
+
+            // Set when the text comes from the prefetch cache.
+            let mut prefetched = false;
+            let mut opts = fetch_options(&args);
+            let paged = match unit_page(&args, xml, |frag| {
+                if is_plain {
+                    extract_cbeta_plain_from_snippet(
+                        frag,
+                        &cbeta_gaiji_cached(&path, xml),
+                        include_notes,
+                    )
+                } else {
+                    extract_text_opts(frag, include_notes)
+                }
+            }) {
+                Some(Ok(p)) => Some(p),
+                Some(Err(e)) => return e.response(&id),
+                None => None,
+            };
+            let page_by = paged.as_ref().map(|p| p.meta.clone());
+            // The XML line a lineNumber or lb fetch is centred on.
+            let mut target_line = opts.line_number;
+            // lineNumber/lb/part/head指定時の処理
+            let (mut text, mut extraction_method, part_matched) = if let Some(p) = paged {
+                (p.text, p.method, false)
+            } else if let Some(lb) = args
+                .get("lb")
+                .and_then(|v| v.as_str())
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+            {
+                let (context_before, context_after) = (opts.context_before, opts.context_after);
+                let pat = format!(r#"<lb\b[^>]*\bn\s*=\s*["']{}["']"#, regex::escape(&lb));
+                if let Ok(re) = Regex::new(&pat) {
+                    if let Some(m) = re.find(xml) {
+                        let xml_line = xml[..m.start()].lines().count() + 1;
+                        target_line = Some(xml_line);
+                        if is_plain {
+                            ensure_gaiji();
+                            let raw = extract_text_around_line_asymmetric(
+                                xml,
+                                xml_line,
+                                context_before,
+                                context_after,
+                            );
+                            let context_text = extract_cbeta_plain_from_snippet(
+                                &raw,
+                                gaiji.as_ref().unwrap(),
+                                include_notes,
+                            );
+                            (
+                                context_text,
+                                format!(
+                                    "plain-lb-context-{}-{}-{}",
+                                    lb, context_before, context_after
+                                ),
+                                false,
+                            )
+                        } else {
+                            let context_text = daizo_core::extract_xml_around_line_asymmetric(
+                                xml,
+                                xml_line,
+                                context_before,
+                                context_after,
+                            );
+                            (
+                                context_text,
+                                format!("lb-context-{}-{}-{}", lb, context_before, context_after),
+                                false,
+                            )
+                        }
+                    } else {
+                        if is_plain {
+                            ensure_gaiji();
+                            let t = extract_cbeta_plain_from_snippet(
+                                xml,
+                                gaiji.as_ref().unwrap(),
+                                include_notes,
+                            );
+                            (t, "plain-full".to_string(), false)
+                        } else {
+                            (
+                                extract_text_opts(xml, include_notes),
+                                "full".to_string(),
+                                false,
+                            )
+                        }
                     }
-                    if do_gretil {
-                        cands_scored.extend(resolve_title_candidates_gretil(
-                            q,
-                            limit_per_source,
-                            min_score,
-                            prefer,
-                        ));
+                } else {
+                    if is_plain {
+                        ensure_gaiji();
+                        let t = extract_cbeta_plain_from_snippet(
+                            xml,
+                            gaiji.as_ref().unwrap(),
+                            include_notes,
+                        );
+                        (t, "plain-full".to_string(), false)
+                    } else {
+                        (
+                            extract_text_opts(xml, include_notes),
+                            "full".to_string(),
+                            false,
+                        )
                     }
-                    if do_sarit {
-                        cands_scored.extend(resolve_title_candidates_sarit(
-                            q,
-                            limit_per_source,
-                            min_score,
-                            prefer,
-                        ));
+                }
+            } else if let Some((line_num, context_before, context_after)) = opts.line_context() {
+                if is_plain {
+                    ensure_gaiji();
+                    let raw = extract_text_around_line_asymmetric(
+                        xml,
+                        line_num,
+                        context_before,
+                        context_after,
+                    );
+                    let context_text = extract_cbeta_plain_from_snippet(
+                        &raw,
+                        gaiji.as_ref().unwrap(),
+                        include_notes,
+                    );
+                    (
+                        context_text,
+                        format!(
+                            "plain-line-context-{}-{}-{}",
+                            line_num, context_before, context_after
+                        ),
+                        false,
+                    )
+                } else {
+                    let context_text = daizo_core::extract_xml_around_line_asymmetric(
+                        xml,
+                        line_num,
+                        context_before,
+                        context_after,
+                    );
+                    (
+                        context_text,
+                        format!(
+                            "line-context-{}-{}-{}",
+                            line_num, context_before, context_after
+                        ),
+                        false,
+                    )
+                }
+            } else if let Some(part) = args.get("part").and_then(|v| v.as_str()) {
+                let key = cbeta_part_key(&path, part, is_plain, include_notes);
+                let sec = match prefetcher().get(&key) {
+                    Some(t) => {
+                        prefetched = true;
+                        Some(t.as_str().to_string())
                     }
-                    if do_muktabodha {
-                        cands_scored.extend(resolve_title_candidates_muktabodha(
-                            q,
-                            limit_per_source,
-                            min_score,
-                            prefer,
-                        ));
+                    None => cbeta_part_text(&path, xml, part, is_plain, include_notes),
+                };
+                if let Some(sec) = sec {
+                    prefetch_cbeta_next_part(&path, xml, part, is_plain, include_notes);
+                    let method = if is_plain {
+                        "plain-cbeta-juan"
+                    } else {
+                        "cbeta-juan"
+                    };
+                    (sec, method.to_string(), true)
+                } else if is_plain {
+                    ensure_gaiji();
+                    let t = extract_cbeta_plain_from_snippet(
+                        xml,
+                        gaiji.as_ref().unwrap(),
+                        include_notes,
+                    );
+                    (t, "plain-full".to_string(), false)
+                } else {
+                    (
+                        extract_text_opts(xml, include_notes),
+                        "full".to_string(),
+                        false,
+                    )
+                }
+            } else if let Some(hq) = args.get("headQuery").and_then(|v| v.as_str()) {
+                let mulu = cbeta_mulu_cached(&path, xml);
+                let bounds = cbeta_section_bounds(xml, &mulu, hq);
+                if is_plain {
+                    if let Some((start, end)) = bounds {
+                        ensure_gaiji();
+                        let sec_xml = &xml[start..end];
+                        let t = extract_cbeta_plain_from_snippet(
+                            sec_xml,
+                            gaiji.as_ref().unwrap(),
+                            include_notes,
+                        );
+                        (t, "plain-head-query".to_string(), false)
+                    } else {
+                        ensure_gaiji();
+                        let t = extract_cbeta_plain_from_snippet(
+                            xml,
+                            gaiji.as_ref().unwrap(),
+                            include_notes,
+                        );
+                        (t, "plain-full".to_string(), false)
                     }
+                } else {
+                    (
+                        bounds
+                            .map(|(start, end)| extract_text_opts(&xml[start..end], include_notes))
+                            .unwrap_or_else(|| extract_text_opts(xml, include_notes)),
+                        "head-query".to_string(),
+                        false,
+                    )
                 }
-            }
-
-            cands_scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
-            let mut candidates: Vec<serde_json::Value> = Vec::new();
-            let mut seen: std::collections::HashSet<(String, String)> =
-                std::collections::HashSet::new();
-            for (_s, v) in cands_scored.into_iter() {
-                let src = v
-                    .get("source")
-                    .and_then(|x| x.as_str())
-                    .unwrap_or("")
-                    .to_string();
-                let cid = v
-                    .get("id")
-                    .and_then(|x| x.as_str())
-                    .unwrap_or("")
-                    .to_string();
-                if src.is_empty() || cid.is_empty() {
-                    continue;
+            } else if let Some(hi) = args.get("headIndex").and_then(|v| v.as_u64()) {
+                if is_plain {
+                    if let Some((start, end)) = section_by_head_bounds(xml, Some(hi as usize), None)
+                    {
+                        ensure_gaiji();
+                        let sec_xml = &xml[start..end];
+                        let t = extract_cbeta_plain_from_snippet(
+                            sec_xml,
+                            gaiji.as_ref().unwrap(),
+                            include_notes,
+                        );
+                        (t, "plain-head-index".to_string(), false)
+                    } else {
+                        ensure_gaiji();
+                        let t = extract_cbeta_plain_from_snippet(
+                            xml,
+                            gaiji.as_ref().unwrap(),
+                            include_notes,
+                        );
+                        (t, "plain-full".to_string(), false)
+                    }
+                } else {
+                    (
+                        extract_section_by_head(xml, Some(hi as usize), None, include_notes)
+                            .unwrap_or_else(|| extract_text_opts(xml, include_notes)),
+                        "head-index".to_string(),
+                        false,
+                    )
                 }
-                if seen.insert((src, cid)) {
-                    candidates.push(v);
+            } else {
+                if is_plain {
+                    ensure_gaiji();
+                    let t = extract_cbeta_plain_from_snippet(
+                        xml,
+                        gaiji.as_ref().unwrap(),
+                        include_notes,
+                    );
+                    (t, "plain-full".to_string(), false)
+                } else {
+                    (
+                        extract_text_opts(xml, include_notes),
+                        "full".to_string(),
+                        false,
+                    )
                 }
-                if candidates.len() >= limit_total {
-                    break;
+            };
+
+            // If highlight is provided but lb/lineNumber isn't, focus output around the first match.
+            // This avoids "start of text only" when the match is far from the beginning.
+            let full_flag = args.get("full").and_then(|v| v.as_bool()).unwrap_or(false);
+            let focus_hl = args
+                .get("focusHighlight")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true);
+            let has_target = args.get("lb").is_some() || args.get("lineNumber").is_some();
+            let has_slice = opts.has_slice() || args.get("pageBy").is_some();
+            let mut focused_meta: Option<serde_json::Value> = None;
+            if !full_flag && focus_hl && !has_target && !has_slice {
+                if let Some(pat) = hl_pat.as_deref() {
+                    let before = args
+                        .get("contextBefore")
+                        .and_then(|v| v.as_u64())
+                        .unwrap_or(
+                            args.get("contextLines")
+                                .and_then(|v| v.as_u64())
+                                .unwrap_or(3),
+                        ) as usize;
+                    let after = args.get("contextAfter").and_then(|v| v.as_u64()).unwrap_or(
+                        args.get("contextLines")
+                            .and_then(|v| v.as_u64())
+                            .unwrap_or(20),
+                    ) as usize;
+                    let span = if hl_use_re {
+                        Regex::new(pat)
+                            .ok()
+                            .and_then(|re| re.find(&text).map(|m| (m.start(), m.end())))
+                    } else {
+                        text.find(pat).map(|sb| (sb, sb + pat.len()))
+                    };
+                    if let Some((sb, _eb)) = span {
+                        let line_no = text[..sb].lines().count() + 1;
+                        let ctx = daizo_core::extract_text_around_line_asymmetric(
+                            &text, line_no, before, after,
+                        );
+                        if !ctx.trim().is_empty() {
+                            focused_meta = Some(json!({
+                                "pattern": pat,
+                                "patternIsRegex": hl_use_re,
+                                "line": line_no,
+                                "contextBefore": before,
+                                "contextAfter": after
+                            }));
+                            text = ctx;
+                            extraction_method =
+                                format!("highlight-context-{}-{}-{}", line_no, before, after);
+                        }
+                    }
                 }
             }
-            let pick = candidates.first().cloned();
 
-            let mut summary = format!("Candidates for '{}':\n", q);
-            for (i, c) in candidates.iter().enumerate() {
-                let src = c.get("source").and_then(|v| v.as_str()).unwrap_or("");
-                let cid = c.get("id").and_then(|v| v.as_str()).unwrap_or("");
-                let title = c.get("title").and_then(|v| v.as_str()).unwrap_or("");
-                let sc = c.get("score").and_then(|v| v.as_f64()).unwrap_or(0.0);
-                if title.is_empty() {
-                    summary.push_str(&format!("{}. [{}] {} (score {:.3})\n", i + 1, src, cid, sc));
-                } else {
-                    summary.push_str(&format!(
-                        "{}. [{}] {}  {} (score {:.3})\n",
-                        i + 1,
-                        src,
-                        cid,
-                        title,
-                        sc
-                    ));
-                }
+            opts.highlight = hl_pat.filter(|p| !p.is_empty());
+            opts.highlight_regex = hl_use_re;
+            // Literal patterns of an array also match their variant characters.
+            for h in opts.highlights.iter_mut().filter(|h| !h.regex) {
+                h.pattern = ws_cjk_variant_fuzzy_regex_literal(&h.pattern);
+                h.regex = true;
             }
-            if candidates.is_empty() {
-                summary.push_str("0 candidates\n");
+            let Fetched {
+                span,
+                text: sliced,
+                highlights,
+                by_pattern,
+            } = apply_fetch_pipeline(&text, &opts, &highlighter(&args));
+            if let Some(resp) =
+                shaping::overflow_outline(&id, "cbeta_fetch", &args, &text, &span, || {
+                    cbeta_heads_cached(&path, xml).to_vec()
+                })
+            {
+                return resp;
             }
-
+            let highlight_count = highlights.len();
+            let highlight_positions = highlight_positions(&args, &highlights, &by_pattern);
+            let heads = cbeta_heads_cached(&path, xml);
+            let mulu = cbeta_mulu_cached(&path, xml);
+            // Juan navigation: the requested juan's anchors and its neighbours.
+            let juan_nav = args.get("part").and_then(|v| v.as_str()).and_then(|part| {
+                let jmap = juan_map_cached(&path, Some(xml))?;
+                let j = jmap.get(part)?;
+                let (prev, next) = jmap.neighbors(part);
+                Some(json!({
+                    "n": j.n,
+                    "title": j.title,
+                    "firstLb": j.first_lb,
+                    "lastLb": j.last_lb,
+                    "pages": j.pages.len(),
+                    "bylines": j.bylines,
+                    "period": j
+                        .bylines
+                        .iter()
+                        .find_map(|b| daizo_core::dating::parse_byline_period(&b.text))
+                        .map(|d| d.label),
+                    "prev": prev,
+                    "next": next,
+                    "juanCount": jmap.len(),
+                }))
+            });
+            let hl = args
+                .get("headingsLimit")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(10) as usize;
             let meta = json!({
-                "query": q,
-                "sources": sources,
-                "count": candidates.len(),
-                "candidates": candidates,
-                "pick": pick
+                "totalLength": text.len(),
+                "totalChars": span.total,
+                "returnedStart": span.start,
+                "returnedEnd": span.end,
+                "truncated": span.end < span.total,
+                "nextCursor": next_cursor("cbeta_fetch", &args, span.end, span.total),
+                "nextLine": span.next_line,
+                "lineMap": line_map(&text, span.start, span.end),
+                "sourcePath": path.to_string_lossy(),
+                "format": if is_plain { "plain" } else { "default" },
+                "extractionMethod": extraction_method,
+                "partMatched": part_matched,
+                "pageBy": page_by,
+                "structure": target_line.and_then(|l| juan_map_cached(&path, Some(xml))?.context_at_line(l)),
+                "prefetched": prefetched,
+                "juan": juan_nav,
+                "headingsTotal": heads.len(),
+                "headingsPreview": heads.iter().take(hl).cloned().collect::<Vec<_>>(),
+                "tocTotal": mulu.len(),
+                "toc": mulu.iter().take(hl).collect::<Vec<_>>(),
+                "matchedId": matched_id,
+                "matchedTitle": matched_title,
+                "matchedScore": matched_score,
+                "focused": focused_meta,
+                "highlighted": if highlight_count > 0 { Some(highlight_count) } else { None::<usize> },
+                "highlightPositions": if highlight_positions.is_empty() { None::<Vec<serde_json::Value>> } else { Some(highlight_positions) },
             });
-            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary}], "_meta": meta }});
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": sliced}], "_meta": meta }});
         }
-        "cbeta_title_search" => {
+        "tipitaka_title_search" => {
+            if let Err(e) = require_corpus("tipitaka") {
+                return e.response(&id);
+            }
             let q = args
                 .get("query")
                 .and_then(|v| v.as_str())
                 .unwrap_or("")
-                .trim()
-                .to_string();
+                .trim();
             let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
-            let idx = load_or_build_cbeta_index();
-            let hits = best_match(idx, &q, limit);
+            let filter = match tipitaka_filter(&args) {
+                Ok(f) => f,
+                Err(e) => return e.response(&id),
+            };
+            let idx = load_or_build_tipitaka_index();
+            let hits =
+                best_match_tipitaka_where(&idx, q, limit * RERANK_POOL, |e| filter.matches(e));
+            let hits = rerank(hits, &[q.to_string()], limit);
             let summary = hits
                 .iter()
                 .enumerate()
                 .map(|(i, h)| {
-                    let tr = h
-                        .entry
-                        .meta
-                        .as_ref()
-                        .and_then(|m| m.get("translator"))
-                        .map(|s| s.as_str())
-                        .unwrap_or("");
-                    if tr.is_empty() {
-                        format!("{}. {}  {}", i + 1, h.entry.id, h.entry.title)
-                    } else {
-                        format!("{}. {}  {}  [{}]", i + 1, h.entry.id, h.entry.title, tr)
-                    }
+                    let stem = Path::new(&h.entry.path)
+                        .file_stem()
+                        .and_then(|s| s.to_str())
+                        .unwrap_or(&h.entry.id);
+                    format!("{}. {}  {}", i + 1, stem, h.entry.title)
                 })
                 .collect::<Vec<_>>()
                 .join("\n");
             let results: Vec<_> = hits
                 .iter()
                 .map(|h| {
-                    let meta = h.entry.meta.as_ref();
-                    let author = meta.and_then(|m| m.get("author").cloned());
-                    let translator = meta.and_then(|m| m.get("translator").cloned());
+                    let stem = Path::new(&h.entry.path)
+                        .file_stem()
+                        .and_then(|s| s.to_str())
+                        .unwrap_or(&h.entry.id)
+                        .to_string();
                     json!({
-                        "id": h.entry.id,
+                        "id": stem,
                         "title": h.entry.title,
+                        "titles": daizo_core::titles::of_entry(h.entry),
                         "path": h.entry.path,
                         "score": h.score,
-                        "author": author,
-                        "translator": translator,
-                        "meta": {
-                            "author": meta.and_then(|m| m.get("author").cloned()),
-                            "editor": meta.and_then(|m| m.get("editor").cloned()),
-                            "translator": meta.and_then(|m| m.get("translator").cloned()),
-                            "publisher": meta.and_then(|m| m.get("publisher").cloned()),
-                            "date": meta.and_then(|m| m.get("date").cloned()),
-                            "idno": meta.and_then(|m| m.get("idno").cloned()),
-                            "canon": meta.and_then(|m| m.get("canon").cloned()),
-                            "nnum": meta.and_then(|m| m.get("nnum").cloned()),
-                            "juanCount": meta.and_then(|m| m.get("juanCount").cloned()),
-                            "headsPreview": meta.and_then(|m| m.get("headsPreview").cloned()),
-                            "respAll": meta.and_then(|m| m.get("respAll").cloned()),
-                        }
+                        "matchReason": h.reason,
+                        "meta": h.entry.meta
                     })
                 })
                 .collect();
-            let meta = json!({
-                "count": results.len(),
-                "results": results
-            });
+            let mut meta = json!({ "count": results.len(), "results": results });
+            if !filter.is_empty() {
+                meta["filter"] = json!(filter);
+            }
             return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary }], "_meta": meta }});
         }
-        "cbeta_fetch" => {
-            ensure_cbeta_data();
+        "tipitaka_fetch" => {
+            if let Err(e) = require_corpus("tipitaka") {
+                return e.response(&id);
+            }
             let mut matched_id: Option<String> = None;
             let mut matched_title: Option<String> = None;
             let mut matched_score: Option<f32> = None;
-            let mut path: PathBuf = PathBuf::new();
             // 高速化: IDが指定されている場合は直接パス解決を試み、インデックスのロードを回避
-            if let Some(id) = args.get("id").and_then(|v| v.as_str()) {
+            let mut path: PathBuf = if let Some(id) = args.get("id").and_then(|v| v.as_str()) {
                 // まず直接パス解決を試みる（インデックス不要、高速）
-                if let Some(direct_path) = daizo_core::path_resolver::resolve_cbeta_path_direct(id)
+                if let Some(direct_path) =
+                    daizo_core::path_resolver::resolve_tipitaka_path_direct(id)
                 {
-                    matched_id = Some(id.to_string());
-                    path = direct_path;
-                    // タイトルは後でインデックスから取得（オプショナル、遅延ロード）
+                    matched_id = Path::new(&direct_path)
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().into_owned());
+                    direct_path
                 } else {
                     // フォールバック: インデックスから検索
-                    let idx = load_or_build_cbeta_index();
-                    if let Some(hit) = idx.iter().find(|e| e.id == id) {
-                        matched_id = Some(hit.id.clone());
-                        matched_title = Some(hit.title.clone());
-                        path = PathBuf::from(&hit.path);
+                    let idx = load_or_build_tipitaka_index();
+                    if let Some(p) = resolve_tipitaka_by_id(&idx, id) {
+                        matched_id = Path::new(&p)
+                            .file_stem()
+                            .map(|s| s.to_string_lossy().into_owned());
+                        if let Some(e) = idx.iter().find(|e| e.path == p.to_string_lossy()) {
+                            matched_title = Some(e.title.clone());
+                        }
+                        p
                     } else {
-                        // 最終フォールバック: WalkDir検索
-                        path = resolve_cbeta_path_by_id(id).unwrap_or_else(|| PathBuf::from(""));
-                        matched_id = Some(id.to_string());
+                        PathBuf::new()
                     }
                 }
             } else if let Some(q) = args.get("query").and_then(|v| v.as_str()) {
-                let idx = load_or_build_cbeta_index();
-                if let Some(hit) = best_match(idx, q, 1).into_iter().next() {
-                    matched_id = Some(hit.entry.id.clone());
+                let idx = load_or_build_tipitaka_index();
+                if let Some(hit) = best_match_tipitaka(&idx, q, 1).into_iter().next() {
                     matched_title = Some(hit.entry.title.clone());
                     matched_score = Some(hit.score);
-                    path = PathBuf::from(&hit.entry.path);
+                    matched_id = Path::new(&hit.entry.path)
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().into_owned());
+                    PathBuf::from(&hit.entry.path)
+                } else {
+                    PathBuf::new()
+                }
+            } else {
+                PathBuf::new()
+            };
+
+            // まだ見つからない場合は、厳密に `<id>.xml` を探す
+            if path.as_os_str().is_empty() || !path.exists() {
+                let fname = format!(
+                    "{}.xml",
+                    args.get("id").and_then(|v| v.as_str()).unwrap_or_default()
+                );
+                if let Some(p) = find_exact_file_by_name(&tipitaka_root(), &fname) {
+                    path = p;
+                }
+            }
+            if path.as_os_str().is_empty() || !in_corpus("tipitaka", &path) {
+                return text_not_found("tipitaka", &args).response(&id);
+            }
+            note_hot_file(&path);
+            // If we matched a TOC file (e.g., s0404m1.mul.toc.xml), try to open the first content part (e.g., s0404m1.mul0.xml)
+            if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
+                if name.ends_with(".toc.xml") {
+                    if let Some(stem) = Path::new(name).file_stem().and_then(|s| s.to_str()) {
+                        let base = stem.trim_end_matches(".toc");
+                        // Prefer base0.xml, then any base*.xml (non-TOC)
+                        let dir: PathBuf = path
+                            .parent()
+                            .map(|p| p.to_path_buf())
+                            .unwrap_or_else(tipitaka_root);
+                        let mut candidate: Option<PathBuf> = None;
+                        let base0 = dir.join(format!("{}0.xml", base));
+                        if base0.exists() {
+                            candidate = Some(base0);
+                        }
+                        if candidate.is_none() {
+                            // Scan directory for base*.xml excluding *.toc.xml
+                            if let Ok(read_dir) = fs::read_dir(&dir) {
+                                for entry in read_dir.flatten() {
+                                    let p = entry.path();
+                                    if p.extension().and_then(|s| s.to_str()) == Some("xml") {
+                                        if let Some(stem2) = p.file_stem().and_then(|s| s.to_str())
+                                        {
+                                            if stem2.starts_with(base) && !stem2.ends_with(".toc") {
+                                                candidate = Some(p.clone());
+                                                break;
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        if let Some(c) = candidate {
+                            path = c;
+                        }
+                    }
+                }
+            }
+            // 読み取り時にエンコーディング問題で空になるのを避けるため、バイト読み + UTF-8(代替) に変更
+            let mut cur_path = path.clone();
+            let mut xml = fs::read(&cur_path)
+                .map(|b| decode_xml_bytes(&b))
+                .unwrap_or_default();
+            let mut prefetched = false;
+            let opts = fetch_options(&args);
+            let paged = match unit_page(&args, &xml, extract_text) {
+                Some(Ok(p)) => Some(p),
+                Some(Err(e)) => return e.response(&id),
+                None => None,
+            };
+            let page_by = paged.as_ref().map(|p| p.meta.clone());
+            let (mut text, mut extraction_method) = if let Some(p) = paged {
+                (p.text, p.method)
+            } else if let Some((line_num, context_before, context_after)) = opts.line_context() {
+                let context_text = daizo_core::extract_xml_around_line_asymmetric(
+                    &xml,
+                    line_num,
+                    context_before,
+                    context_after,
+                );
+                (
+                    context_text,
+                    format!(
+                        "line-context-{}-{}-{}",
+                        line_num, context_before, context_after
+                    ),
+                )
+            } else if let Some(hq) = args.get("headQuery").and_then(|v| v.as_str()) {
+                (
+                    extract_section_by_head(&xml, None, Some(hq), false)
+                        .unwrap_or_else(|| extract_text(&xml)),
+                    "head-query".to_string(),
+                )
+            } else if let Some(hi) = args.get("headIndex").and_then(|v| v.as_u64()) {
+                (
+                    extract_section_by_head(&xml, Some(hi as usize), None, false)
+                        .unwrap_or_else(|| extract_text(&xml)),
+                    "head-index".to_string(),
+                )
+            } else if let Some(t) = prefetcher().get(&tipitaka_full_key(&cur_path)) {
+                prefetched = true;
+                (t.as_str().to_string(), "full".to_string())
+            } else {
+                (extract_text(&xml), "full".to_string())
+            };
+            // フォールバックA：抽出が空で、同ベースの連番ファイルがある場合は最小番号を開く
+            if text.trim().is_empty() {
+                if let Some(stem) = cur_path.file_stem().and_then(|s| s.to_str()) {
+                    let ends_with_digit = stem
+                        .chars()
+                        .last()
+                        .map(|c| c.is_ascii_digit())
+                        .unwrap_or(false);
+                    if !ends_with_digit {
+                        if let Some(candidate) = find_tipitaka_content_for_base(stem) {
+                            if candidate != cur_path {
+                                cur_path = candidate.clone();
+                                xml = fs::read(&candidate)
+                                    .map(|b| decode_xml_bytes(&b))
+                                    .unwrap_or_default();
+                                let (t2, m2) = if let Some(hq) =
+                                    args.get("headQuery").and_then(|v| v.as_str())
+                                {
+                                    (
+                                        extract_section_by_head(&xml, None, Some(hq), false)
+                                            .unwrap_or_else(|| extract_text(&xml)),
+                                        "head-query+base-fallback".to_string(),
+                                    )
+                                } else if let Some(hi) =
+                                    args.get("headIndex").and_then(|v| v.as_u64())
+                                {
+                                    (
+                                        extract_section_by_head(
+                                            &xml,
+                                            Some(hi as usize),
+                                            None,
+                                            false,
+                                        )
+                                        .unwrap_or_else(|| extract_text(&xml)),
+                                        "head-index+base-fallback".to_string(),
+                                    )
+                                } else {
+                                    (extract_text(&xml), "full+base-fallback".to_string())
+                                };
+                                text = t2;
+                                extraction_method = m2;
+                            }
+                        }
+                    }
+                }
+            }
+            // フォールバックB：それでも空ならタグ除去の素朴処理
+            if text.trim().is_empty() && !xml.trim().is_empty() {
+                if let Ok(re) = regex::Regex::new(r"<[^>]+>") {
+                    let t = re.replace_all(&xml, " ");
+                    let t = t.split_whitespace().collect::<Vec<_>>().join(" ");
+                    if !t.trim().is_empty() {
+                        text = t;
+                        extraction_method = "plain-strip-tags".to_string();
+                    }
+                }
+            }
+            let Fetched {
+                span,
+                text: sliced,
+                highlights,
+                by_pattern,
+            } = apply_fetch_pipeline(&text, &opts, &highlighter(&args));
+            if let Some(resp) =
+                shaping::overflow_outline(&id, "tipitaka_fetch", &args, &text, &span, || {
+                    list_heads_generic(&xml)
+                })
+            {
+                return resp;
+            }
+            let highlight_count = highlights.len();
+            let highlight_positions = highlight_positions(&args, &highlights, &by_pattern);
+            let heads = list_heads_generic(&xml);
+            let hl = args
+                .get("headingsLimit")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(10) as usize;
+            let mut meta = json!({
+                "totalLength": text.len(),
+                "totalChars": span.total,
+                "returnedStart": span.start,
+                "returnedEnd": span.end,
+                "truncated": span.end < span.total,
+                "nextCursor": next_cursor("tipitaka_fetch", &args, span.end, span.total),
+                "nextLine": span.next_line,
+                "lineMap": line_map(&text, span.start, span.end),
+                "sourcePath": cur_path.to_string_lossy(),
+                "extractionMethod": extraction_method,
+                "pageBy": page_by,
+                "structure": opts.line_number.and_then(|l| JuanMap::build(&xml).context_at_line(l)),
+                "prefetched": prefetched,
+                "headingsTotal": heads.len(),
+                "headingsPreview": heads.into_iter().take(hl).collect::<Vec<_>>(),
+                "matchedId": matched_id,
+                "matchedTitle": matched_title,
+                "matchedScore": matched_score,
+                "biblio": tipitaka_biblio(&xml),
+                "highlighted": if highlight_count > 0 { Some(highlight_count) } else { None::<usize> },
+                "highlightPositions": if highlight_positions.is_empty() { None::<Vec<serde_json::Value>> } else { Some(highlight_positions) },
+            });
+            if extraction_method.starts_with("full") {
+                if let Some(next) =
+                    tipitaka_work_meta(&mut meta, &cur_path, &args, span.end >= span.total)
+                {
+                    prefetch_tipitaka_part(next);
                 }
             }
-            let xml_arc = cbeta_xml_cached(&path);
-            let xml = xml_arc.as_str();
-            // includeNotes support
-            let include_notes = args
-                .get("includeNotes")
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": sliced}], "_meta": meta }});
+        }
+        "sat_search" => {
+            let q = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
+            let rows = args.get("rows").and_then(|v| v.as_u64()).unwrap_or(100) as usize;
+            let offs = args.get("offs").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+            let exact = args.get("exact").and_then(|v| v.as_bool()).unwrap_or(true);
+            let titles_only = args
+                .get("titlesOnly")
                 .and_then(|v| v.as_bool())
                 .unwrap_or(false);
-            let is_plain = args
-                .get("format")
-                .and_then(|v| v.as_str())
-                .map(|s| s.eq_ignore_ascii_case("plain"))
-                .unwrap_or(false);
-
-            // Effective highlight pattern (also used for focusHighlight).
-            let hl_in = args
-                .get("highlight")
+            let fields = args
+                .get("fields")
                 .and_then(|v| v.as_str())
-                .map(|s| s.to_string());
-            let mut hl_use_re = args
-                .get("highlightRegex")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(false);
-            let mut hl_pat: Option<String> = None;
-            if let Some(h0) = &hl_in {
-                let looks_like = h0.chars().any(|c| ".+*?[](){}|\\".contains(c));
-                if looks_like && !hl_use_re {
-                    hl_use_re = true;
-                    hl_pat = Some(h0.clone());
-                } else if h0.chars().any(|c| c.is_whitespace()) && !looks_like && !hl_use_re {
-                    hl_use_re = true;
-                    hl_pat = Some(ws_cjk_variant_fuzzy_regex_literal(h0));
-                } else if !hl_use_re && !looks_like {
-                    // Expand variants and escape as regex for CBETA by default.
-                    hl_use_re = true;
-                    hl_pat = Some(ws_cjk_variant_fuzzy_regex_literal(h0));
+                .unwrap_or("id,fascnm,startid,endid");
+            let fq: Vec<String> = args
+                .get("fq")
+                .and_then(|v| v.as_array())
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|x| x.as_str().map(|s| s.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default();
+            let qt = q.trim();
+            let q_param = if exact && !qt.is_empty() {
+                if qt.starts_with('\"') && qt.ends_with('\"') && qt.len() >= 2 {
+                    qt.to_string()
                 } else {
-                    hl_pat = Some(h0.clone());
-                }
-            }
-
-            let mut gaiji: Option<Arc<std::collections::HashMap<String, String>>> = None;
-            let mut ensure_gaiji = || {
-                if gaiji.is_none() {
-                    gaiji = Some(cbeta_gaiji_cached(&path, xml));
+                    format!("\"{}\"", qt)
                 }
+            } else {
+                qt.to_string()
             };
-
-            // lineNumber/lb/part/head指定時の処理
-            let (mut text, mut extraction_method, part_matched) = if let Some(lb) = args
-                .get("lb")
-                .and_then(|v| v.as_str())
-                .map(|s| s.trim().to_string())
-                .filter(|s| !s.is_empty())
-            {
-                let context_before = args
-                    .get("contextBefore")
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(
-                        args.get("contextLines")
-                            .and_then(|v| v.as_u64())
-                            .unwrap_or(10),
-                    ) as usize;
-                let context_after = args.get("contextAfter").and_then(|v| v.as_u64()).unwrap_or(
-                    args.get("contextLines")
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(100),
-                ) as usize;
-                let pat = format!(r#"<lb\b[^>]*\bn\s*=\s*["']{}["']"#, regex::escape(&lb));
-                if let Ok(re) = Regex::new(&pat) {
-                    if let Some(m) = re.find(&xml) {
-                        let xml_line = xml[..m.start()].lines().count() + 1;
-                        if is_plain {
-                            ensure_gaiji();
-                            let raw = extract_text_around_line_asymmetric(
-                                &xml,
-                                xml_line,
-                                context_before,
-                                context_after,
-                            );
-                            let context_text = extract_cbeta_plain_from_snippet(
-                                &raw,
-                                gaiji.as_ref().unwrap(),
-                                include_notes,
-                            );
-                            (
-                                context_text,
-                                format!(
-                                    "plain-lb-context-{}-{}-{}",
-                                    lb, context_before, context_after
-                                ),
-                                false,
-                            )
-                        } else {
-                            let context_text = daizo_core::extract_xml_around_line_asymmetric(
-                                &xml,
-                                xml_line,
-                                context_before,
-                                context_after,
-                            );
-                            (
-                                context_text,
-                                format!("lb-context-{}-{}-{}", lb, context_before, context_after),
-                                false,
-                            )
-                        }
-                    } else {
-                        if is_plain {
-                            ensure_gaiji();
-                            let t = extract_cbeta_plain_from_snippet(
-                                &xml,
-                                gaiji.as_ref().unwrap(),
-                                include_notes,
-                            );
-                            (t, "plain-full".to_string(), false)
-                        } else {
-                            (
-                                extract_text_opts(&xml, include_notes),
-                                "full".to_string(),
-                                false,
-                            )
-                        }
-                    }
-                } else {
-                    if is_plain {
-                        ensure_gaiji();
-                        let t = extract_cbeta_plain_from_snippet(
-                            &xml,
-                            gaiji.as_ref().unwrap(),
-                            include_notes,
-                        );
-                        (t, "plain-full".to_string(), false)
-                    } else {
-                        (
-                            extract_text_opts(&xml, include_notes),
-                            "full".to_string(),
-                            false,
-                        )
-                    }
-                }
-            } else if let Some(line_num) = args.get("lineNumber").and_then(|v| v.as_u64()) {
-                // 新しいパラメータを優先、fallbackで古いパラメータを使用
-                let context_before = args
-                    .get("contextBefore")
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(
-                        args.get("contextLines")
-                            .and_then(|v| v.as_u64())
-                            .unwrap_or(10),
-                    ) as usize;
-                let context_after = args.get("contextAfter").and_then(|v| v.as_u64()).unwrap_or(
-                    args.get("contextLines")
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(100),
-                ) as usize;
-                if is_plain {
-                    ensure_gaiji();
-                    let raw = extract_text_around_line_asymmetric(
-                        &xml,
-                        line_num as usize,
-                        context_before,
-                        context_after,
-                    );
-                    let context_text = extract_cbeta_plain_from_snippet(
-                        &raw,
-                        gaiji.as_ref().unwrap(),
-                        include_notes,
-                    );
-                    (
-                        context_text,
-                        format!(
-                            "plain-line-context-{}-{}-{}",
-                            line_num, context_before, context_after
-                        ),
-                        false,
-                    )
-                } else {
-                    let context_text = daizo_core::extract_xml_around_line_asymmetric(
-                        &xml,
-                        line_num as usize,
-                        context_before,
-                        context_after,
-                    );
-                    (
-                        context_text,
-                        format!(
-                            "line-context-{}-{}-{}",
-                            line_num, context_before, context_after
-                        ),
-                        false,
-                    )
-                }
-            } else if let Some(part) = args.get("part").and_then(|v| v.as_str()) {
-                if is_plain {
-                    if let Some(sec) = extract_cbeta_juan_plain(&xml, part, include_notes) {
-                        (sec, "plain-cbeta-juan".to_string(), true)
-                    } else {
-                        ensure_gaiji();
-                        let t = extract_cbeta_plain_from_snippet(
-                            &xml,
-                            gaiji.as_ref().unwrap(),
-                            include_notes,
-                        );
-                        (t, "plain-full".to_string(), false)
+            let query = remote::SearchQuery {
+                query: q_param.clone(),
+                rows,
+                offset: offs,
+                fields: Some(fields.to_string()),
+                filters: fq.clone(),
+            };
+            if let Some(page) = remote_search(&SAT, &query) {
+                let count = page.total;
+                let local = sat_local_suggestions(
+                    page.docs
+                        .iter()
+                        .filter_map(|d| d.get("startid").and_then(|v| v.as_str())),
+                );
+                let docs_v = json!(page.docs);
+                let meta_base = json!({ "count": count, "results": docs_v, "titlesOnly": titles_only, "q": qt, "qSent": q_param, "exact": exact, "fl": fields, "fq": fq, "fetchSuggestions": local });
+                let auto = args
+                    .get("autoFetch")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                if auto {
+                    let docs = page.docs;
+                    if docs.is_empty() {
+                        let summary = "0 results".to_string();
+                        return json!({ "jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary }], "_meta": meta_base }});
                     }
-                } else if let Some(sec) = extract_cbeta_juan(&xml, part) {
-                    (sec, "cbeta-juan".to_string(), true)
-                } else {
-                    (
-                        extract_text_opts(&xml, include_notes),
-                        "full".to_string(),
-                        false,
-                    )
-                }
-            } else if let Some(hq) = args.get("headQuery").and_then(|v| v.as_str()) {
-                if is_plain {
-                    if let Some((start, end)) = section_by_head_bounds(&xml, None, Some(hq)) {
-                        ensure_gaiji();
-                        let sec_xml = &xml[start..end];
-                        let t = extract_cbeta_plain_from_snippet(
-                            sec_xml,
-                            gaiji.as_ref().unwrap(),
-                            include_notes,
-                        );
-                        (t, "plain-head-query".to_string(), false)
-                    } else {
-                        ensure_gaiji();
-                        let t = extract_cbeta_plain_from_snippet(
-                            &xml,
-                            gaiji.as_ref().unwrap(),
-                            include_notes,
-                        );
-                        (t, "plain-full".to_string(), false)
+                    let mut best_i = 0usize;
+                    let mut best_sc = -1f32;
+                    for (i, d) in docs.iter().enumerate() {
+                        let title = d.get("fascnm").and_then(|v| v.as_str()).unwrap_or("");
+                        let sc = title_score(title, q);
+                        if sc > best_sc {
+                            best_sc = sc;
+                            best_i = i;
+                        }
                     }
+                    let chosen = &docs[best_i];
+                    let useid = chosen.get("startid").and_then(|v| v.as_str()).unwrap_or("");
+                    let url = SAT.detail_url(useid);
+                    let t = sat_fetch(&url);
+                    let span = fetch_slice(&t, &args, false);
+                    let mut meta = meta_base;
+                    meta["chosen"] = chosen.clone();
+                    meta["titleScore"] = json!(best_sc);
+                    meta["sourceUrl"] = json!(url);
+                    meta["returnedStart"] = json!(span.start);
+                    meta["returnedEnd"] = json!(span.end);
+                    meta["totalLength"] = json!(t.len());
+                    meta["totalChars"] = json!(span.total);
+                    meta["truncated"] = json!(span.end < span.total);
+                    meta["nextLine"] = json!(span.next_line);
+                    meta["lineMap"] = line_map(&t, span.start, span.end);
+                    meta["extractionMethod"] = json!("sat-detail-extract");
+                    return json!({ "jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": span.text }], "_meta": meta }});
                 } else {
-                    (
-                        extract_section_by_head(&xml, None, Some(hq), include_notes)
-                            .unwrap_or_else(|| extract_text_opts(&xml, include_notes)),
-                        "head-query".to_string(),
-                        false,
-                    )
-                }
-            } else if let Some(hi) = args.get("headIndex").and_then(|v| v.as_u64()) {
-                if is_plain {
-                    if let Some((start, end)) =
-                        section_by_head_bounds(&xml, Some(hi as usize), None)
-                    {
-                        ensure_gaiji();
-                        let sec_xml = &xml[start..end];
-                        let t = extract_cbeta_plain_from_snippet(
-                            sec_xml,
-                            gaiji.as_ref().unwrap(),
-                            include_notes,
-                        );
-                        (t, "plain-head-index".to_string(), false)
+                    let summary = if titles_only {
+                        format!("{} titles; see _meta.results", count)
                     } else {
-                        ensure_gaiji();
-                        let t = extract_cbeta_plain_from_snippet(
-                            &xml,
-                            gaiji.as_ref().unwrap(),
-                            include_notes,
-                        );
-                        (t, "plain-full".to_string(), false)
+                        format!("{} results; see _meta.results", count)
+                    };
+                    return json!({ "jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary }], "_meta": meta_base }});
+                }
+            } else {
+                let hits = sat_search_results(q, rows, offs, exact, titles_only);
+                if hits.is_empty() {
+                    if let Some(reason) = errors::take_network_failure() {
+                        return ToolError::NetworkError {
+                            source: "sat".to_string(),
+                            reason,
+                        }
+                        .response(&id);
                     }
+                }
+                let local = sat_local_suggestions(hits.iter().map(|h| h.startid.as_str()));
+                let meta = json!({ "count": hits.len(), "results": hits, "titlesOnly": titles_only, "fetchSuggestions": local });
+                let summary = if titles_only {
+                    format!(
+                        "{} titles; see _meta.results",
+                        meta["count"].as_u64().unwrap_or(0)
+                    )
                 } else {
-                    (
-                        extract_section_by_head(&xml, Some(hi as usize), None, include_notes)
-                            .unwrap_or_else(|| extract_text_opts(&xml, include_notes)),
-                        "head-index".to_string(),
-                        false,
+                    format!(
+                        "{} results; see _meta.results",
+                        meta["count"].as_u64().unwrap_or(0)
                     )
-                }
+                };
+                return json!({ "jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary }], "_meta": meta }});
+            }
+        }
+        "sat_fetch" => {
+            // Prefer building URL from useid (startid). Fallback to direct url.
+            let url = if let Some(uid) = args.get("useid").and_then(|v| v.as_str()) {
+                SAT.detail_url(uid)
             } else {
-                if is_plain {
-                    ensure_gaiji();
-                    let t = extract_cbeta_plain_from_snippet(
-                        &xml,
-                        gaiji.as_ref().unwrap(),
-                        include_notes,
-                    );
-                    (t, "plain-full".to_string(), false)
+                args.get("url")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string()
+            };
+            let t = sat_fetch(&url);
+            if t.is_empty() {
+                return remote_fetch_error("sat", &args).response(&id);
+            }
+            let span = fetch_slice(&t, &args, false);
+            let meta = json!({
+                "totalLength": t.len(),
+                "totalChars": span.total,
+                "returnedStart": span.start,
+                "returnedEnd": span.end,
+                "truncated": span.end < span.total,
+                "nextCursor": next_cursor("sat_fetch", &args, span.end, span.total),
+                "nextLine": span.next_line,
+                "lineMap": line_map(&t, span.start, span.end),
+                "sourceUrl": url,
+                "extractionMethod": "sat-detail-extract"
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": span.text}], "_meta": meta }});
+        }
+        "sat_detail" => {
+            let useid = args.get("useid").and_then(|v| v.as_str()).unwrap_or("");
+            // Fixed params per observation: mode=detail, ob=1, mode2=2. useid is the key.
+            let url = format!("https://21dzk.l.u-tokyo.ac.jp/SAT2018/satdb2018pre.php?mode=detail&ob=1&mode2=2&useid={}", urlencoding::encode(useid));
+            let t = sat_fetch(&url);
+            if t.is_empty() {
+                return remote_fetch_error("sat", &args).response(&id);
+            }
+            let span = fetch_slice(&t, &args, false);
+            let meta = json!({
+                "totalLength": t.len(),
+                "totalChars": span.total,
+                "returnedStart": span.start,
+                "returnedEnd": span.end,
+                "truncated": span.end < span.total,
+                "nextCursor": next_cursor("sat_detail", &args, span.end, span.total),
+                "nextLine": span.next_line,
+                "lineMap": line_map(&t, span.start, span.end),
+                "sourceUrl": url,
+                "extractionMethod": "sat-detail-extract"
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": span.text}], "_meta": meta }});
+        }
+        "sat_to_cbeta" => {
+            let arg = |k: &str| {
+                args.get(k)
+                    .and_then(|v| v.as_str())
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+            };
+            if let Some(useid) = arg("useid") {
+                let Some(r) = daizo_core::taisho_ref::parse_sat(useid) else {
+                    return ToolError::InvalidArgument {
+                        name: "useid".to_string(),
+                        value: useid.to_string(),
+                        expected: "a SAT line id (e.g. 0262_,09,0001c14)".to_string(),
+                    }
+                    .response(&id);
+                };
+                let local = capabilities().allows("cbeta") && corpus_present("cbeta");
+                let (files, found) = if local {
+                    taisho_location(&r)
                 } else {
-                    (
-                        extract_text_opts(&xml, include_notes),
-                        "full".to_string(),
-                        false,
-                    )
+                    (Vec::new(), None)
+                };
+                let file_id = found
+                    .as_ref()
+                    .map(|(f, _)| f.clone())
+                    .or_else(|| files.first().cloned())
+                    .unwrap_or_else(|| r.id());
+                let hit = found.as_ref().map(|(_, h)| h);
+                let fetch = match hit {
+                    Some(h) => json!({"tool": "cbeta_fetch", "args": {"id": file_id, "lb": h.lb}}),
+                    None => json!({"tool": "cbeta_fetch", "args": {"reference": r.canonical()}}),
+                };
+                let mut text = format!("{} = {} ({})", useid, r.canonical(), file_id);
+                if let Some(h) = hit {
+                    text.push_str(&format!(
+                        " lb {}{}",
+                        h.lb,
+                        h.juan
+                            .as_ref()
+                            .map(|j| format!(", juan {}", j))
+                            .unwrap_or_default()
+                    ));
+                } else if !local {
+                    text.push_str("; CBETA is not installed locally");
+                }
+                let meta = json!({
+                    "useid": useid,
+                    "reference": r.canonical(),
+                    "cbetaId": file_id,
+                    "lb": hit.map(|h| &h.lb),
+                    "juan": hit.and_then(|h| h.juan.as_ref()),
+                    "exact": hit.map(|h| h.exact),
+                    "located": hit.is_some(),
+                    "fetchSuggestions": [fetch],
+                });
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": meta }});
+            }
+            let Some(wid) = arg("id") else {
+                return ToolError::InvalidArgument {
+                    name: "useid".to_string(),
+                    value: String::new(),
+                    expected: "a SAT useid, or a CBETA id (with lb or part)".to_string(),
                 }
+                .response(&id);
             };
-
-            // If highlight is provided but lb/lineNumber isn't, focus output around the first match.
-            // This avoids "start of text only" when the match is far from the beginning.
-            let full_flag = args.get("full").and_then(|v| v.as_bool()).unwrap_or(false);
-            let focus_hl = args
-                .get("focusHighlight")
+            if let Err(e) = require_corpus("cbeta") {
+                return e.response(&id);
+            }
+            let Some(path) = Some(wid)
+                .filter(|w| daizo_core::path_resolver::safe_id(w))
+                .and_then(|w| resolve_local_source_path("cbeta", w))
+                .filter(|p| p.is_file())
+            else {
+                return text_not_found("cbeta", &args).response(&id);
+            };
+            let stem = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let xml = cbeta_xml_cached(&path);
+            let map = juan_map_cached(&path, Some(xml.as_str()));
+            let part = arg("part");
+            let juan = match part {
+                Some(p) => match map.as_ref().and_then(|m| m.get(p)) {
+                    Some(j) => Some(j),
+                    None => {
+                        return ToolError::InvalidArgument {
+                            name: "part".to_string(),
+                            value: p.to_string(),
+                            expected: "a juan of the text (e.g. '001')".to_string(),
+                        }
+                        .response(&id)
+                    }
+                },
+                None => None,
+            };
+            let first_lb = map
+                .as_ref()
+                .and_then(|m| m.juans.first())
+                .and_then(|j| j.first_lb.clone());
+            let lb = arg("lb")
+                .map(String::from)
+                .or_else(|| juan.and_then(|j| j.first_lb.clone()))
+                .or(first_lb);
+            let sat_of = |lb: &str| {
+                daizo_core::taisho_ref::parse(&format!("{}_p{}", stem, lb)).and_then(|r| r.sat_id())
+            };
+            let Some(useid) = lb.as_deref().and_then(sat_of) else {
+                return ToolError::InvalidArgument {
+                    name: "id".to_string(),
+                    value: wid.to_string(),
+                    expected: "a Taishō (T) text with line markers; SAT covers the Taishō only"
+                        .to_string(),
+                }
+                .response(&id);
+            };
+            let endid = juan.and_then(|j| j.last_lb.as_deref()).and_then(sat_of);
+            let mut suggestions = vec![json!({"tool": "sat_fetch", "args": {"useid": useid}})];
+            if let Some(e) = &endid {
+                suggestions
+                    .push(json!({"tool": "sat_fetch_range", "args": {"useid": useid, "endid": e}}));
+            }
+            let text = match &endid {
+                Some(e) => format!("{} {} = SAT {} .. {}", stem, part.unwrap_or(""), useid, e),
+                None => format!(
+                    "{} lb {} = SAT {}",
+                    stem,
+                    lb.as_deref().unwrap_or(""),
+                    useid
+                ),
+            };
+            let meta = json!({
+                "id": stem,
+                "lb": lb,
+                "part": part,
+                "useid": useid,
+                "endid": endid,
+                "sourceUrl": SAT.detail_url(&useid),
+                "fetchSuggestions": suggestions,
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": meta }});
+        }
+        "sat_fetch_range" => {
+            let useid = args
+                .get("useid")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .trim();
+            let endid = args
+                .get("endid")
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty());
+            if sat_line_key(useid).is_none() {
+                return ToolError::InvalidArgument {
+                    name: "useid".to_string(),
+                    value: useid.to_string(),
+                    expected: "a SAT line id (e.g. 0262_,09,0001c14)".to_string(),
+                }
+                .response(&id);
+            }
+            if let Some(e) = endid.filter(|e| sat_line_key(e).is_none()) {
+                return ToolError::InvalidArgument {
+                    name: "endid".to_string(),
+                    value: e.to_string(),
+                    expected: "a SAT line id (e.g. 0262_,09,0062b01)".to_string(),
+                }
+                .response(&id);
+            }
+            let max_pages = args
+                .get("maxPages")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(30)
+                .max(1) as usize;
+            let range = sat_fetch_range(useid, endid, max_pages);
+            if range.text.is_empty() {
+                return remote_fetch_error("sat", &args).response(&id);
+            }
+            // A page that failed after others were read ends the range early.
+            let warning = errors::take_network_failure();
+            let span = fetch_slice(&range.text, &args, false);
+            if let Some(resp) = shaping::overflow_outline(
+                &id,
+                "sat_fetch_range",
+                &args,
+                &range.text,
+                &span,
+                Vec::new,
+            ) {
+                return resp;
+            }
+            let meta = json!({
+                "useid": useid,
+                "endid": endid,
+                "pageCount": range.pages.len(),
+                "pages": range.pages,
+                "lastLine": range.last_line,
+                "complete": range.stop == "end",
+                "stopReason": range.stop,
+                "warning": warning,
+                "totalLength": span.total,
+                "returnedStart": span.start,
+                "returnedEnd": span.end,
+                "truncated": span.end < span.total,
+                "nextCursor": next_cursor("sat_fetch_range", &args, span.end, span.total),
+                "nextLine": span.next_line,
+                "lineMap": line_map(&range.text, span.start, span.end),
+                "sourceUrl": SAT.detail_url(useid),
+                "extractionMethod": "sat-detail-lines"
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": span.text}], "_meta": meta }});
+        }
+        "tibetan_search" => {
+            let q = args
+                .get("query")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .trim()
+                .to_string();
+            if q.is_empty() {
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "query is empty"}], "_meta": {"query": q, "count": 0, "results": []} }});
+            }
+            let sources: Vec<String> = args
+                .get("sources")
+                .and_then(|v| v.as_array())
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|x| x.as_str().map(|s| s.to_string()))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_else(|| vec!["adarshah".to_string(), "buda".to_string()]);
+            let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
+            let exact = args.get("exact").and_then(|v| v.as_bool()).unwrap_or(true);
+            let max_snippet_chars = args
+                .get("maxSnippetChars")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(240) as usize;
+            let wildcard = args
+                .get("wildcard")
                 .and_then(|v| v.as_bool())
-                .unwrap_or(true);
-            let has_target = args.get("lb").is_some() || args.get("lineNumber").is_some();
-            let has_slice = args.get("startChar").is_some()
-                || args.get("endChar").is_some()
-                || args.get("page").is_some()
-                || args.get("pageSize").is_some()
-                || args.get("maxChars").is_some();
-            let mut focused_meta: Option<serde_json::Value> = None;
-            if !full_flag && focus_hl && !has_target && !has_slice {
-                if let Some(pat) = hl_pat.as_deref() {
-                    let before = args
-                        .get("contextBefore")
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(
-                            args.get("contextLines")
-                                .and_then(|v| v.as_u64())
-                                .unwrap_or(3),
-                        ) as usize;
-                    let after = args.get("contextAfter").and_then(|v| v.as_u64()).unwrap_or(
-                        args.get("contextLines")
-                            .and_then(|v| v.as_u64())
-                            .unwrap_or(20),
-                    ) as usize;
-                    let span = if hl_use_re {
-                        Regex::new(pat)
-                            .ok()
-                            .and_then(|re| re.find(&text).map(|m| (m.start(), m.end())))
-                    } else {
-                        text.find(pat).map(|sb| (sb, sb + pat.len()))
-                    };
-                    if let Some((sb, _eb)) = span {
-                        let line_no = text[..sb].lines().count() + 1;
-                        let ctx = daizo_core::extract_text_around_line_asymmetric(
-                            &text, line_no, before, after,
-                        );
-                        if !ctx.trim().is_empty() {
-                            focused_meta = Some(json!({
-                                "pattern": pat,
-                                "patternIsRegex": hl_use_re,
-                                "line": line_no,
-                                "contextBefore": before,
-                                "contextAfter": after
-                            }));
-                            text = ctx;
-                            extraction_method =
-                                format!("highlight-context-{}-{}-{}", line_no, before, after);
+                .unwrap_or(false);
+
+            let mut variants: Vec<String> = vec![q.clone()];
+            if let Some(u) = ewts_to_unicode_best_effort(&q) {
+                if !variants.iter().any(|x| x == &u) {
+                    variants.push(u);
+                }
+            }
+
+            let mut results: Vec<serde_json::Value> = Vec::new();
+            let mut warnings: Vec<String> = Vec::new();
+
+            for src in sources.iter() {
+                errors::take_network_failure();
+                match src.as_str() {
+                    "adarshah" => {
+                        for vq in variants.iter() {
+                            let mut r =
+                                adarshah_search_fulltext(vq, wildcard, limit, max_snippet_chars);
+                            results.append(&mut r);
+                        }
+                    }
+                    "buda" => {
+                        for vq in variants.iter() {
+                            let mut r = buda_search_fulltext(vq, exact, limit, max_snippet_chars);
+                            results.append(&mut r);
                         }
                     }
+                    _ => {}
+                }
+                if let Some(reason) = errors::take_network_failure() {
+                    warnings.push(format!("{}: {}", src, reason));
+                }
+            }
+            if results.is_empty() && !sources.is_empty() && warnings.len() == sources.len() {
+                return ToolError::NetworkError {
+                    source: sources.join(","),
+                    reason: warnings.join("; "),
+                }
+                .response(&id);
+            }
+
+            let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
+            let mut uniq: Vec<serde_json::Value> = Vec::new();
+            for r in results.into_iter() {
+                let key = r
+                    .get("url")
+                    .and_then(|u| u.as_str())
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| serde_json::to_string(&r).unwrap_or_default());
+                if seen.insert(key) {
+                    uniq.push(r);
+                }
+                if uniq.len() >= limit {
+                    break;
+                }
+            }
+
+            let mut summary = format!("Tibetan search for '{}'\n", q);
+            if uniq.is_empty() {
+                summary.push_str("0 results\n");
+            } else {
+                for (i, r) in uniq.iter().enumerate() {
+                    let src = r.get("source").and_then(|v| v.as_str()).unwrap_or("");
+                    let title = r.get("title").and_then(|v| v.as_str()).unwrap_or("");
+                    let snip = r.get("snippet").and_then(|v| v.as_str()).unwrap_or("");
+                    let url = r.get("url").and_then(|v| v.as_str()).unwrap_or("");
+                    if !title.is_empty() {
+                        summary.push_str(&format!("{}. [{}] {}  {}\n", i + 1, src, title, url));
+                    } else {
+                        summary.push_str(&format!("{}. [{}] {}\n", i + 1, src, url));
+                    }
+                    if !snip.is_empty() {
+                        summary.push_str(&format!("   {}\n", snip.replace('\n', " ")));
+                    }
                 }
             }
 
-            let mut sliced = if full_flag {
-                text.clone()
+            let meta = json!({
+                "query": q,
+                "variants": variants,
+                "sources": sources,
+                "exact": exact,
+                "maxSnippetChars": max_snippet_chars,
+                "warnings": if warnings.is_empty() { None::<Vec<String>> } else { Some(warnings) },
+                "count": uniq.len(),
+                "results": uniq
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary}], "_meta": meta }});
+        }
+        "sat_pipeline" => {
+            let q = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
+            let exact = args.get("exact").and_then(|v| v.as_bool()).unwrap_or(true);
+            let rows = args.get("rows").and_then(|v| v.as_u64()).unwrap_or(100) as usize;
+            let offs = args.get("offs").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+            let fields_requested = args
+                .get("fields")
+                .and_then(|v| v.as_str())
+                .unwrap_or("id,fascnm,startid,endid,body");
+            // sat_pipeline selection relies on `body` and fetching relies on `startid`.
+            // Ensure required fields are present even if the user customizes `fields`.
+            let fields_used = sat_wrap7_ensure_fields(
+                fields_requested,
+                &["id", "fascnm", "startid", "endid", "body"],
+            );
+            let fq: Vec<String> = args
+                .get("fq")
+                .and_then(|v| v.as_array())
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|x| x.as_str().map(|s| s.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default();
+            let start_char_provided =
+                args.get("startChar").is_some() || args.get("startLine").is_some();
+            let qt = q.trim();
+            let q_param = if exact && !qt.is_empty() {
+                if qt.starts_with('"') && qt.ends_with('"') && qt.len() >= 2 {
+                    qt.to_string()
+                } else {
+                    format!("\"{}\"", qt)
+                }
             } else {
-                slice_text(&text, &args)
-            };
-            // Optional highlight across sliced text
-            let mut highlight_count = 0usize;
-            let mut highlight_positions: Vec<serde_json::Value> = Vec::new();
-            if let Some(hpat) = hl_pat.as_deref().or_else(|| hl_in.as_deref()) {
-                let use_re = hl_use_re;
-                let hpre = args
-                    .get("highlightPrefix")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or(">>> ");
-                let hsuf = args
-                    .get("highlightSuffix")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or(" <<<");
-                let original = sliced.clone();
-                if use_re {
-                    if let Ok(re) = regex::Regex::new(hpat) {
-                        for m in re.find_iter(&original) {
-                            let sb = m.start();
-                            let eb = m.end();
-                            let sc = original[..sb].chars().count();
-                            let ec = sc + original[sb..eb].chars().count();
-                            highlight_positions.push(json!({"startChar": sc, "endChar": ec}));
+                qt.to_string()
+            };
+            let query = remote::SearchQuery {
+                query: q_param.clone(),
+                rows,
+                offset: offs,
+                fields: Some(fields_used.clone()),
+                filters: fq.clone(),
+            };
+            if let Some(page) = remote_search(&SAT, &query) {
+                let docs = page.docs;
+                if docs.is_empty() {
+                    return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "no results"}], "_meta": {"count": 0} }});
+                }
+                let (best_i, mut chosen_by, mut best_sc) = sat_pick_best_doc(&docs, qt);
+                // The best pick and the next documents in wrap7 order, one per startid, are
+                // fetched together; the first whose text has the query is returned.
+                let startid = |i: usize| {
+                    docs[i]
+                        .get("startid")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                };
+                let n_candidates = args
+                    .get("candidates")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(3)
+                    .clamp(1, 10) as usize;
+                let mut seen = std::collections::HashSet::new();
+                let mut cand: Vec<usize> = std::iter::once(best_i)
+                    .chain((0..docs.len()).filter(|&i| i != best_i))
+                    .filter(|&i| !startid(i).is_empty() && seen.insert(startid(i)))
+                    .take(n_candidates)
+                    .collect();
+                if cand.is_empty() {
+                    cand.push(best_i);
+                }
+                let urls: Vec<String> = cand.iter().map(|&i| SAT.detail_url(startid(i))).collect();
+                let texts = remote_texts(&SAT, &urls);
+                let q_focus = qt
+                    .trim()
+                    .strip_prefix('"')
+                    .and_then(|s| s.strip_suffix('"'))
+                    .unwrap_or(qt)
+                    .trim();
+                let pat = ws_cjk_variant_fuzzy_regex_literal(q_focus);
+                let first_hits: Vec<_> = texts
+                    .iter()
+                    .map(|t| {
+                        if q_focus.is_empty() {
+                            None
+                        } else {
+                            find_highlight_positions(t, &pat, true).into_iter().next()
                         }
-                        let mut count = 0usize;
-                        let replaced = re.replace_all(&sliced, |caps: &regex::Captures| {
-                            count += 1;
-                            format!("{}{}{}", hpre, &caps[0], hsuf)
+                    })
+                    .collect();
+                let pick = if start_char_provided {
+                    0
+                } else {
+                    first_hits.iter().position(Option::is_some).unwrap_or(0)
+                };
+                let chosen = &docs[cand[pick]];
+                if pick > 0 {
+                    chosen_by = "textContains";
+                    best_sc = title_score(
+                        chosen.get("fascnm").and_then(|v| v.as_str()).unwrap_or(""),
+                        qt,
+                    );
+                }
+                let candidates_meta: Vec<serde_json::Value> = cand
+                    .iter()
+                    .zip(&texts)
+                    .zip(&first_hits)
+                    .map(|((&i, t), hit)| {
+                        json!({
+                            "startid": startid(i),
+                            "fascnm": docs[i].get("fascnm"),
+                            "totalChars": t.chars().count(),
+                            "hasQuery": hit.is_some(),
+                        })
+                    })
+                    .collect();
+                let url = urls[pick].clone();
+                let t = &texts[pick];
+                let mut focus = json!({"enabled": false});
+                let start_eff = match &first_hits[pick] {
+                    Some(p0) if !start_char_provided => {
+                        let s = p0.start_char.saturating_sub(50);
+                        focus = json!({
+                            "enabled": true,
+                            "query": q_focus,
+                            "pattern": pat,
+                            "matchStartChar": p0.start_char as u64,
+                            "matchEndChar": p0.end_char as u64,
+                            "startChar": s as u64
                         });
-                        sliced = replaced.into_owned();
-                        highlight_count = count;
-                    }
-                } else if !hpat.is_empty() {
-                    let mut i = 0usize;
-                    while let Some(pos) = original[i..].find(hpat) {
-                        let abs = i + pos;
-                        let sc = original[..abs].chars().count();
-                        let ec = sc + hpat.chars().count();
-                        highlight_positions.push(json!({"startChar": sc, "endChar": ec}));
-                        i = abs + hpat.len();
+                        s
                     }
-                    let mut out = String::with_capacity(sliced.len());
-                    let mut j = 0usize;
-                    while let Some(pos) = sliced[j..].find(hpat) {
-                        let abs = j + pos;
-                        out.push_str(&sliced[j..abs]);
-                        out.push_str(hpre);
-                        out.push_str(hpat);
-                        out.push_str(hsuf);
-                        j = abs + hpat.len();
-                        highlight_count += 1;
+                    _ => start_char_arg(t, &args).unwrap_or(0),
+                };
+                let mut slice_args = args.clone();
+                if let Some(o) = slice_args.as_object_mut() {
+                    o.remove("startLine");
+                    o.insert("startChar".to_string(), json!(start_eff));
+                }
+                let span = fetch_slice(t, &slice_args, false);
+                let count = page.total;
+                let meta = json!({
+                    "totalLength": t.len(),
+                    "totalChars": span.total,
+                    "returnedStart": span.start,
+                    "returnedEnd": span.end,
+                    "truncated": span.end < span.total,
+                    "nextLine": span.next_line,
+                    "lineMap": line_map(t, span.start, span.end),
+                    "sourceUrl": url,
+                    "extractionMethod": "sat-detail-extract",
+                    "search": {"q": qt, "qSent": q_param, "exact": exact, "rows": rows, "offs": offs, "flRequested": fields_requested, "flUsed": fields_used, "fq": fq, "count": count},
+                    "chosen": chosen,
+                    "chosenBy": chosen_by,
+                    "candidates": candidates_meta,
+                    "titleScore": best_sc,
+                    "focus": focus,
+                    "startCharRequested": start_char_arg(t, &args).unwrap_or(0) as u64
+                });
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": span.text}], "_meta": meta }});
+            } else {
+                if let Some(reason) = errors::take_network_failure() {
+                    return ToolError::NetworkError {
+                        source: "sat".to_string(),
+                        reason,
                     }
-                    out.push_str(&sliced[j..]);
-                    sliced = out;
+                    .response(&id);
                 }
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "no results"}], "_meta": {"count": 0} }});
             }
-            let heads = cbeta_heads_cached(&path, xml);
-            let hl = args
-                .get("headingsLimit")
+        }
+        "jozen_search" => {
+            let q_raw0 = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
+            let q = q_raw0.trim().to_string();
+            let page = args.get("page").and_then(|v| v.as_u64()).unwrap_or(1) as usize;
+            let page = page.max(1);
+            let max_results = args
+                .get("maxResults")
                 .and_then(|v| v.as_u64())
-                .unwrap_or(10) as usize;
-            // enforce output cap
-            let cap = default_max_chars();
-            if sliced.chars().count() > cap {
-                sliced = sliced.chars().take(cap).collect();
+                .unwrap_or(20) as usize;
+            let max_results = std::cmp::min(max_results, 50);
+            let max_snippet_chars = args
+                .get("maxSnippetChars")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as usize)
+                .unwrap_or(default_snippet_len());
+
+            if q.is_empty() {
+                let meta = json!({
+                    "source": "jozen",
+                    "query": q,
+                    "page": page,
+                    "pageSize": 50,
+                    "totalCount": 0,
+                    "totalPages": 0,
+                    "results": [],
+                    "fetchSuggestions": []
+                });
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "0 results"}], "_meta": meta }});
+            }
+
+            let Some(html) = jozen_search_html(&q, page) else {
+                return ToolError::NetworkError {
+                    source: "jozen".to_string(),
+                    reason: errors::take_network_failure()
+                        .unwrap_or_else(|| "fetch failed".to_string()),
+                }
+                .response(&id);
+            };
+
+            let parsed = jozen_parse_search_html(&html, &q, max_results, max_snippet_chars);
+            let hint_top = std::env::var("DAIZO_HINT_TOP")
+                .ok()
+                .and_then(|s| s.parse::<usize>().ok())
+                .unwrap_or(1);
+            let mut fetch_suggestions: Vec<serde_json::Value> = Vec::new();
+            for r in parsed.results.iter().take(hint_top) {
+                if !r.lineno.is_empty() {
+                    fetch_suggestions.push(json!({
+                        "tool": "jozen_fetch",
+                        "args": {"lineno": r.lineno.clone()}
+                    }));
+                }
             }
+
+            let total = parsed.total_count.unwrap_or(parsed.results.len());
+            let total_pages = parsed.total_pages.unwrap_or(0);
+            let summary = if total_pages > 0 {
+                format!(
+                    "{} results (page {}/{}); see _meta.results",
+                    total, page, total_pages
+                )
+            } else {
+                format!("{} results (page {}); see _meta.results", total, page)
+            };
             let meta = json!({
-                "totalLength": text.len(),
-                "returnedStart": args.get("startChar").and_then(|v| v.as_u64()).unwrap_or( args.get("page").and_then(|v| v.as_u64()).and_then(|p| args.get("pageSize").and_then(|s| s.as_u64()).map(|ps| p*ps)).unwrap_or(0) ) ,
-                "returnedEnd": args.get("startChar").and_then(|v| v.as_u64()).unwrap_or( args.get("page").and_then(|v| v.as_u64()).and_then(|p| args.get("pageSize").and_then(|s| s.as_u64()).map(|ps| p*ps)).unwrap_or(0) ) + (sliced.len() as u64),
-                "truncated": if full_flag { (sliced.len() as u64) < (text.len() as u64) } else { (sliced.len() as u64) < (text.len() as u64) },
-                "sourcePath": path.to_string_lossy(),
-                "format": if is_plain { "plain" } else { "default" },
-                "extractionMethod": extraction_method,
-                "partMatched": part_matched,
-                "headingsTotal": heads.len(),
-                "headingsPreview": heads.iter().take(hl).cloned().collect::<Vec<_>>(),
-                "matchedId": matched_id,
-                "matchedTitle": matched_title,
-                "matchedScore": matched_score,
-                "focused": focused_meta,
-                "highlighted": if highlight_count > 0 { Some(highlight_count) } else { None::<usize> },
-                "highlightPositions": if highlight_positions.is_empty() { None::<Vec<serde_json::Value>> } else { Some(highlight_positions) },
+                "source": "jozen",
+                "query": q,
+                "page": page,
+                "pageSize": parsed.page_size.unwrap_or(50),
+                "displayedCount": parsed.displayed_count,
+                "totalCount": parsed.total_count.unwrap_or(0),
+                "totalPages": parsed.total_pages.unwrap_or(0),
+                "results": parsed.results,
+                "fetchSuggestions": fetch_suggestions
             });
-            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": sliced}], "_meta": meta }});
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary}], "_meta": meta }});
         }
-        "tipitaka_title_search" => {
-            let q = args
-                .get("query")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .trim();
-            let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
-            let idx = load_or_build_tipitaka_index();
-            let hits = best_match_tipitaka(idx, q, limit);
-            let summary = hits
-                .iter()
-                .enumerate()
-                .map(|(i, h)| {
-                    let stem = Path::new(&h.entry.path)
-                        .file_stem()
-                        .and_then(|s| s.to_str())
-                        .unwrap_or(&h.entry.id);
-                    format!("{}. {}  {}", i + 1, stem, h.entry.title)
-                })
-                .collect::<Vec<_>>()
-                .join("\n");
-            let results: Vec<_> = hits
-                .iter()
-                .map(|h| {
-                    let stem = Path::new(&h.entry.path)
-                        .file_stem()
-                        .and_then(|s| s.to_str())
-                        .unwrap_or(&h.entry.id)
-                        .to_string();
-                    json!({
-                        "id": stem,
-                        "title": h.entry.title,
-                        "path": h.entry.path,
-                        "score": h.score,
-                        "meta": h.entry.meta
-                    })
-                })
-                .collect();
-            let meta = json!({ "count": results.len(), "results": results });
-            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary }], "_meta": meta }});
+        "jozen_fetch" => {
+            let lineno0 = args.get("lineno").and_then(|v| v.as_str()).unwrap_or("");
+            let lineno = lineno0.trim().to_string();
+            if lineno.is_empty() {
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "lineno is empty"}], "_meta": {"source":"jozen"} }});
+            }
+            let maxc = args
+                .get("maxChars")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as usize)
+                .unwrap_or(default_max_chars());
+
+            let source_url = jozen_detail_url(&lineno);
+            let Some(html) = jozen_detail_html(&lineno) else {
+                return remote_fetch_error("jozen", &args).response(&id);
+            };
+            let detail = jozen_extract_detail(&html, &source_url);
+            let start = start_char_arg(&detail.content, &args).unwrap_or(0);
+            let (sliced, total_chars, returned_start, returned_end) =
+                slice_text_bounds(&detail.content, start, maxc);
+            let meta = json!({
+                "source": "jozen",
+                "sourceUrl": detail.source_url,
+                "workHeader": detail.work_header,
+                "textno": detail.textno,
+                "pagePrev": detail.page_prev,
+                "pageNext": detail.page_next,
+                "lineCount": detail.line_ids.len(),
+                "lineIds": detail.line_ids,
+                "totalLength": total_chars as u64,
+                "returnedStart": returned_start as u64,
+                "returnedEnd": returned_end as u64,
+                "truncated": returned_end < total_chars,
+                "nextCursor": next_cursor("jozen_fetch", &args, returned_end, total_chars),
+                "nextLine": line_at_char(&detail.content, returned_end),
+                "lineMap": line_map(&detail.content, returned_start, returned_end),
+                "extractionMethod": "jozen-detail-extract"
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": sliced}], "_meta": meta }});
         }
-        "tipitaka_fetch" => {
-            ensure_tipitaka_data();
-            let mut matched_id: Option<String> = None;
-            let mut matched_title: Option<String> = None;
-            let mut matched_score: Option<f32> = None;
-            // 高速化: IDが指定されている場合は直接パス解決を試み、インデックスのロードを回避
-            let mut path: PathBuf = if let Some(id) = args.get("id").and_then(|v| v.as_str()) {
-                // まず直接パス解決を試みる（インデックス不要、高速）
-                if let Some(direct_path) =
-                    daizo_core::path_resolver::resolve_tipitaka_path_direct(id)
-                {
-                    matched_id = Path::new(&direct_path)
-                        .file_stem()
-                        .map(|s| s.to_string_lossy().into_owned());
-                    direct_path
-                } else {
-                    // フォールバック: インデックスから検索
-                    let idx = load_or_build_tipitaka_index();
-                    if let Some(p) = resolve_tipitaka_by_id(&idx, id) {
-                        matched_id = Path::new(&p)
-                            .file_stem()
-                            .map(|s| s.to_string_lossy().into_owned());
-                        if let Some(e) = idx.iter().find(|e| e.path == p.to_string_lossy()) {
-                            matched_title = Some(e.title.clone());
-                        }
-                        p
-                    } else {
-                        PathBuf::new()
-                    }
-                }
-            } else if let Some(q) = args.get("query").and_then(|v| v.as_str()) {
-                let idx = load_or_build_tipitaka_index();
-                if let Some(hit) = best_match_tipitaka(idx, q, 1).into_iter().next() {
-                    matched_title = Some(hit.entry.title.clone());
-                    matched_score = Some(hit.score);
-                    matched_id = Path::new(&hit.entry.path)
-                        .file_stem()
-                        .map(|s| s.to_string_lossy().into_owned());
-                    PathBuf::from(&hit.entry.path)
-                } else {
-                    PathBuf::new()
+        "cbeta_search" => {
+            let q_raw0 = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
+            let q_raw = q_raw0.trim();
+            let multi = match multi_query("cbeta", &args) {
+                Ok(m) => m,
+                Err(e) => return e.response(&id),
+            };
+            let multi_display = multi.as_ref().map(|m| m.queries.join(" | "));
+            let q_raw = multi_display.as_deref().unwrap_or(q_raw);
+            let q = match &multi {
+                Some(m) => m.pattern(),
+                None => rewrite_query("cbeta", q_raw, &args).pattern,
+            };
+            let (q_display, hl_pat, hl_regex) = (q_raw.to_string(), q.clone(), true);
+            let max_results = args
+                .get("maxResults")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(20) as usize;
+            let max_matches_per_file = args
+                .get("maxMatchesPerFile")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(5) as usize;
+
+            let notes_only = args
+                .get("notesOnly")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+
+            if let Some(src) = remote_fallback("cbeta") {
+                return cbeta_online_search(id, src, q_raw, &args);
+            }
+            if let Err(e) = require_corpus("cbeta") {
+                return e.response(&id);
+            }
+            let scope = match search_scope("cbeta", &args) {
+                Ok(s) => s,
+                Err(e) => return e.response(&id),
+            };
+            let filter = match cbeta_filter(&args) {
+                Ok(f) => f,
+                Err(e) => return e.response(&id),
+            };
+            for c in &filter.canons {
+                ensure_cbeta_canon(&c.to_uppercase());
+            }
+            let scope = if filter.is_empty() {
+                scope
+            } else {
+                let idx = load_or_build_cbeta_index();
+                Some(filter_scope(scope, &idx, |e| filter.matches(e)))
+            };
+            let (results, q, hl_pat) = if query_mode_boolean(&args) {
+                match boolean_grep(
+                    "cbeta",
+                    q_raw,
+                    &args,
+                    scope.as_ref(),
+                    max_results,
+                    max_matches_per_file,
+                ) {
+                    Ok((r, pre)) => (r, pre.clone(), pre),
+                    Err(e) => return boolean_query_error(&id, q_raw, &e),
                 }
+            } else if notes_only {
+                let r = match &scope {
+                    Some(sc) => daizo_core::cbeta_grep_notes_scoped(
+                        &sc.paths,
+                        &q,
+                        max_results,
+                        max_matches_per_file,
+                    ),
+                    None => cbeta_grep_notes(&cbeta_root(), &q, max_results, max_matches_per_file),
+                };
+                (r, q, hl_pat)
             } else {
-                PathBuf::new()
+                (
+                    corpus_grep(
+                        "cbeta",
+                        scope.as_ref(),
+                        &q,
+                        max_results,
+                        max_matches_per_file,
+                    ),
+                    q,
+                    hl_pat,
+                )
             };
+            let mut results = with_index_sizes("cbeta", results);
+            if let Some(m) = &multi {
+                m.set.tag(&mut results);
+            }
+            attribute_juans(&mut results);
+            filter_text_only(&mut results, &q, &args);
+            merge_snippets(&mut results, &q, &args);
+            sort_results("cbeta", &mut results, &args);
+            register_match_ids("cbeta", &mut results);
 
-            // まだ見つからない場合は、厳密に `<id>.xml` を探す
-            if path.as_os_str().is_empty() || !path.exists() {
-                let fname = format!(
-                    "{}.xml",
-                    args.get("id").and_then(|v| v.as_str()).unwrap_or_default()
-                );
-                if let Some(p) = find_exact_file_by_name(&tipitaka_root(), &fname) {
-                    path = p;
+            let mut summary = format!(
+                "Found {} files with {} for '{}':\n\n",
+                results.len(),
+                if notes_only {
+                    "note matches"
+                } else {
+                    "matches"
+                },
+                q_display
+            );
+            for (i, result) in results.iter().enumerate() {
+                summary.push_str(&format!(
+                    "{}. {} ({})\n",
+                    i + 1,
+                    result.title,
+                    result.file_id
+                ));
+                summary.push_str(&format!(
+                    "   {} matches, {}{}\n",
+                    result.total_matches,
+                    result
+                        .fetch_hints
+                        .total_content_size
+                        .as_deref()
+                        .unwrap_or("unknown size"),
+                    result
+                        .score
+                        .map(|s| format!(", relevance {:.2}", s))
+                        .unwrap_or_default()
+                ));
+                if let Some(m) = &multi {
+                    summary.push_str(&m.summary_line(result));
+                }
+
+                for (j, m) in result.matches.iter().enumerate().take(2) {
+                    summary.push_str(&format!(
+                        "   Match {}{}{}: ...{}...\n",
+                        j + 1,
+                        cbeta_match_place(m),
+                        match_id_tag(m),
+                        m.context.chars().take(100).collect::<String>()
+                    ));
+                }
+                if result.matches.len() > 2 {
+                    summary.push_str(&format!(
+                        "   ... and {} more matches\n",
+                        result.matches.len() - 2
+                    ));
+                }
+
+                if !result.fetch_hints.recommended_parts.is_empty() {
+                    let parts: Vec<String> = result
+                        .fetch_hints
+                        .recommended_parts
+                        .iter()
+                        .map(|n| {
+                            match result.fetch_hints.part_chars.iter().find(|p| &p.part == n) {
+                                Some(p) => format!("{} ({} chars)", n, p.chars),
+                                None => n.clone(),
+                            }
+                        })
+                        .collect();
+                    summary.push_str(&format!("   Recommended parts: {}\n", parts.join(", ")));
                 }
+                summary.push('\n');
             }
-            // 見つからない場合は空に近い応答
-            if path.as_os_str().is_empty() {
-                path = PathBuf::from("");
+            if results.len() >= max_results {
+                summary.push_str("NOTE: Results may be truncated by maxResults. Increase maxResults to see more.\n");
             }
-            // If we matched a TOC file (e.g., s0404m1.mul.toc.xml), try to open the first content part (e.g., s0404m1.mul0.xml)
-            if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
-                if name.ends_with(".toc.xml") {
-                    if let Some(stem) = Path::new(name).file_stem().and_then(|s| s.to_str()) {
-                        let base = stem.trim_end_matches(".toc");
-                        // Prefer base0.xml, then any base*.xml (non-TOC)
-                        let dir: PathBuf = path
-                            .parent()
-                            .map(|p| p.to_path_buf())
-                            .unwrap_or_else(|| tipitaka_root());
-                        let mut candidate: Option<PathBuf> = None;
-                        let base0 = dir.join(format!("{}0.xml", base));
-                        if base0.exists() {
-                            candidate = Some(base0);
-                        }
-                        if candidate.is_none() {
-                            // Scan directory for base*.xml excluding *.toc.xml
-                            if let Ok(read_dir) = fs::read_dir(&dir) {
-                                for entry in read_dir.flatten() {
-                                    let p = entry.path();
-                                    if p.extension().and_then(|s| s.to_str()) == Some("xml") {
-                                        if let Some(stem2) = p.file_stem().and_then(|s| s.to_str())
-                                        {
-                                            if stem2.starts_with(base) && !stem2.ends_with(".toc") {
-                                                candidate = Some(p.clone());
-                                                break;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        if let Some(c) = candidate {
-                            path = c;
-                        }
+            // Lightweight next-call hints for AI clients (low token cost)
+            let hint_top = std::env::var("DAIZO_HINT_TOP")
+                .ok()
+                .and_then(|s| s.parse::<usize>().ok())
+                .unwrap_or(1);
+            let mut fetch_suggestions: Vec<serde_json::Value> = Vec::new();
+            for r in results.iter().take(hint_top) {
+                if let Some(m) = r.matches.first() {
+                    if let Some(lb) =
+                        m.lb.clone()
+                            .or_else(|| cbeta_extract_lb_from_line(&m.context))
+                    {
+                        let w = daizo_core::window::suggest_for_file(
+                            Path::new(&r.file_path),
+                            m.line_number.unwrap_or(0),
+                        );
+                        fetch_suggestions.push(json!({
+                            "tool": "cbeta_fetch",
+                            "args": {"id": r.file_id, "lb": lb, "contextBefore": w.before, "contextAfter": w.after, "highlight": hl_pat, "highlightRegex": hl_regex, "format": "plain"},
+                            "mode": "low-cost",
+                            "unit": w.unit,
+                            "juan": m.juan_number,
+                            "section": m.section
+                        }));
+                    } else if let Some(ln) = m.line_number {
+                        let w = daizo_core::window::suggest_for_file(Path::new(&r.file_path), ln);
+                        fetch_suggestions.push(json!({
+                            "tool": "cbeta_fetch",
+                            "args": {"id": r.file_id, "lineNumber": ln, "contextBefore": w.before, "contextAfter": w.after, "highlight": hl_pat, "highlightRegex": hl_regex, "format": "plain"},
+                            "mode": "low-cost",
+                            "unit": w.unit,
+                            "juan": m.juan_number,
+                            "section": m.section
+                        }));
                     }
                 }
             }
-            // 読み取り時にエンコーディング問題で空になるのを避けるため、バイト読み + UTF-8(代替) に変更
-            let mut cur_path = path.clone();
-            let mut xml = fs::read(&cur_path)
-                .map(|b| decode_xml_bytes(&b))
-                .unwrap_or_default();
-            let (mut text, mut extraction_method) =
-                if let Some(line_num) = args.get("lineNumber").and_then(|v| v.as_u64()) {
-                    // 新しいパラメータを優先、fallbackで古いパラメータを使用
-                    let context_before = args
-                        .get("contextBefore")
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(
-                            args.get("contextLines")
-                                .and_then(|v| v.as_u64())
-                                .unwrap_or(10),
-                        ) as usize;
-                    let context_after = args.get("contextAfter").and_then(|v| v.as_u64()).unwrap_or(
-                        args.get("contextLines")
-                            .and_then(|v| v.as_u64())
-                            .unwrap_or(100),
-                    ) as usize;
-                    let context_text = daizo_core::extract_xml_around_line_asymmetric(
-                        &xml,
-                        line_num as usize,
-                        context_before,
-                        context_after,
-                    );
-                    (
-                        context_text,
-                        format!(
-                            "line-context-{}-{}-{}",
-                            line_num, context_before, context_after
-                        ),
-                    )
-                } else if let Some(hq) = args.get("headQuery").and_then(|v| v.as_str()) {
-                    (
-                        extract_section_by_head(&xml, None, Some(hq), false)
-                            .unwrap_or_else(|| extract_text(&xml)),
-                        "head-query".to_string(),
-                    )
-                } else if let Some(hi) = args.get("headIndex").and_then(|v| v.as_u64()) {
-                    (
-                        extract_section_by_head(&xml, Some(hi as usize), None, false)
-                            .unwrap_or_else(|| extract_text(&xml)),
-                        "head-index".to_string(),
-                    )
-                } else {
-                    (extract_text(&xml), "full".to_string())
-                };
-            // フォールバックA：抽出が空で、同ベースの連番ファイルがある場合は最小番号を開く
-            if text.trim().is_empty() {
-                if let Some(stem) = cur_path.file_stem().and_then(|s| s.to_str()) {
-                    let ends_with_digit = stem
-                        .chars()
-                        .last()
-                        .map(|c| c.is_ascii_digit())
-                        .unwrap_or(false);
-                    if !ends_with_digit {
-                        if let Some(candidate) = find_tipitaka_content_for_base(stem) {
-                            if candidate != cur_path {
-                                cur_path = candidate.clone();
-                                xml = fs::read(&candidate)
-                                    .map(|b| decode_xml_bytes(&b))
-                                    .unwrap_or_default();
-                                let (t2, m2) = if let Some(hq) =
-                                    args.get("headQuery").and_then(|v| v.as_str())
-                                {
-                                    (
-                                        extract_section_by_head(&xml, None, Some(hq), false)
-                                            .unwrap_or_else(|| extract_text(&xml)),
-                                        "head-query+base-fallback".to_string(),
-                                    )
-                                } else if let Some(hi) =
-                                    args.get("headIndex").and_then(|v| v.as_u64())
-                                {
-                                    (
-                                        extract_section_by_head(
-                                            &xml,
-                                            Some(hi as usize),
-                                            None,
-                                            false,
-                                        )
-                                        .unwrap_or_else(|| extract_text(&xml)),
-                                        "head-index+base-fallback".to_string(),
-                                    )
-                                } else {
-                                    (extract_text(&xml), "full+base-fallback".to_string())
-                                };
-                                text = t2;
-                                extraction_method = m2;
+
+            // Enrich structured results with CBETA lb (more stable than XML line numbers).
+            // Keep the original `results` structs for summary rendering; only mutate JSON for `_meta`.
+            let mut results_meta = serde_json::to_value(&results).unwrap_or(json!([]));
+            if let Some(arr) = results_meta.as_array_mut() {
+                for r in arr.iter_mut() {
+                    if let Some(ms) = r.get_mut("matches").and_then(|v| v.as_array_mut()) {
+                        for m in ms.iter_mut() {
+                            if m.get("lb").is_some() {
+                                continue;
+                            }
+                            let ctx = m.get("context").and_then(|v| v.as_str()).unwrap_or("");
+                            if let Some(lb) = cbeta_extract_lb_from_line(ctx) {
+                                m["lb"] = json!(lb);
                             }
                         }
                     }
                 }
             }
-            // フォールバックB：それでも空ならタグ除去の素朴処理
-            if text.trim().is_empty() && !xml.trim().is_empty() {
-                if let Ok(re) = regex::Regex::new(r"<[^>]+>") {
-                    let t = re.replace_all(&xml, " ");
-                    let t = t.split_whitespace().collect::<Vec<_>>().join(" ");
-                    if !t.trim().is_empty() {
-                        text = t;
-                        extraction_method = "plain-strip-tags".to_string();
-                    }
-                }
+            let mut meta = json!({
+                "queryRaw": q_display,
+                "searchPattern": q,
+                "totalFiles": results.len(),
+                "results": results_meta,
+                "hint": "Use cbeta_fetch (id + lineNumber) for low-cost context; cbeta_pipeline with autoFetch=false to summarize",
+                "fetchSuggestions": fetch_suggestions,
+                "truncatedByMaxResults": results.len() >= max_results,
+                "notesOnly": notes_only,
+            });
+            scope_meta(&mut meta, scope.as_ref());
+            cbeta_sparse_meta(&mut meta);
+            if !filter.is_empty() {
+                meta["filter"] = json!(filter);
             }
-            let mut sliced = slice_text(&text, &args);
-            // enforce output cap
-            let cap = default_max_chars();
-            if sliced.chars().count() > cap {
-                sliced = sliced.chars().take(cap).collect();
+            if let Some(m) = &multi {
+                m.annotate_meta(&mut meta, &results);
             }
-            // Optional highlight for Tipitaka
-            let hl_in = args.get("highlight").and_then(|v| v.as_str());
-            let mut hl_regex = args
-                .get("highlightRegex")
+            // Optional pipeline hint (kept minimal)
+            meta["pipelineHint"] = json!({
+                "tool": "cbeta_pipeline",
+                "args": {"query": q_display, "autoFetch": false, "maxResults": 5, "maxMatchesPerFile": 1, "includeHighlightSnippet": false, "includeMatchLine": true }
+            });
+
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary}], "_meta": meta }});
+        }
+        "cbeta_term_timeline" => {
+            let q_raw = args
+                .get("query")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .trim();
+            if q_raw.is_empty() {
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "query is empty"}], "_meta": {"query": q_raw, "buckets": []} }});
+            }
+            let looks_like_regex = q_raw.chars().any(|c| ".+*?[](){}|\\".contains(c));
+            let q = if looks_like_regex {
+                q_raw.to_string()
+            } else {
+                ws_cjk_variant_fuzzy_regex_literal(q_raw)
+            };
+            let by_century = args
+                .get("bucketBy")
+                .and_then(|v| v.as_str())
+                .map(|s| s.eq_ignore_ascii_case("century"))
+                .unwrap_or(false);
+            let max_results = args
+                .get("maxResults")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(500) as usize;
+            let max_matches_per_file = args
+                .get("maxMatchesPerFile")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(200) as usize;
+            let include_ids = args
+                .get("includeWorkIds")
                 .and_then(|v| v.as_bool())
                 .unwrap_or(false);
-            let hpre = args
-                .get("highlightPrefix")
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string())
-                .or_else(|| std::env::var("DAIZO_HL_PREFIX").ok())
-                .unwrap_or_else(|| ">>> ".to_string());
-            let hsuf = args
-                .get("highlightSuffix")
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string())
-                .or_else(|| std::env::var("DAIZO_HL_SUFFIX").ok())
-                .unwrap_or_else(|| " <<<".to_string());
-            let mut highlight_positions: Vec<serde_json::Value> = Vec::new();
-            let mut highlight_count = 0usize;
-            if let Some(hpat0) = hl_in {
-                let looks_like_regex = hpat0.chars().any(|c| ".+*?[](){}|\\".contains(c));
-                let hpat =
-                    if hpat0.chars().any(|c| c.is_whitespace()) && !looks_like_regex && !hl_regex {
-                        hl_regex = true;
-                        to_whitespace_fuzzy_literal(hpat0)
-                    } else {
-                        hpat0.to_string()
-                    };
-                if hl_regex {
-                    if let Ok(re) = Regex::new(&hpat) {
-                        for m in re.find_iter(&sliced) {
-                            let sb = m.start();
-                            let eb = m.end();
-                            let sc = sliced[..sb].chars().count();
-                            let ec = sc + sliced[sb..eb].chars().count();
-                            highlight_positions.push(json!({"startChar": sc, "endChar": ec}));
-                        }
-                        let mut count = 0usize;
-                        let replaced = re.replace_all(&sliced, |caps: &regex::Captures| {
-                            count += 1;
-                            format!("{}{}{}", hpre, &caps[0], hsuf)
-                        });
-                        sliced = replaced.into_owned();
-                        highlight_count = count;
-                    }
-                } else if !hpat.is_empty() {
-                    let mut i = 0usize;
-                    while let Some(pos) = sliced[i..].find(&hpat) {
-                        let abs = i + pos;
-                        let sc = sliced[..abs].chars().count();
-                        let ec = sc + hpat.chars().count();
-                        highlight_positions.push(json!({"startChar": sc, "endChar": ec}));
-                        i = abs + hpat.len();
-                    }
-                    let mut out = String::with_capacity(sliced.len());
-                    let mut j = 0usize;
-                    while let Some(pos) = sliced[j..].find(&hpat) {
-                        let abs = j + pos;
-                        out.push_str(&sliced[j..abs]);
-                        out.push_str(&hpre);
-                        out.push_str(&hpat);
-                        out.push_str(&hsuf);
-                        j = abs + hpat.len();
-                        highlight_count += 1;
-                    }
-                    out.push_str(&sliced[j..]);
-                    sliced = out;
-                }
+
+            if let Err(e) = require_corpus("cbeta") {
+                return e.response(&id);
             }
-            let heads = list_heads_generic(&xml);
-            let hl = args
-                .get("headingsLimit")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(10) as usize;
+            let results = cbeta_grep(&cbeta_root(), &q, max_results, max_matches_per_file);
+            let idx = load_or_build_cbeta_index();
+            let buckets = daizo_core::dating::temporal_profile(&results, &idx, by_century);
+            let total_matches: usize = buckets.iter().map(|b| b.matches).sum();
+
+            let mut summary = format!(
+                "Usage profile for '{}' ({} works, {} matches):\n\n",
+                q_raw,
+                results.len(),
+                total_matches
+            );
+            summary
+                .push_str("| period | years | works | matches | share |\n|---|---|---|---|---|\n");
+            for b in buckets.iter() {
+                let years = match (b.start_year, b.end_year) {
+                    (Some(s), Some(e)) => format!("{}–{}", s, e),
+                    _ => "-".to_string(),
+                };
+                let share = if total_matches > 0 {
+                    (b.matches as f64) * 100.0 / (total_matches as f64)
+                } else {
+                    0.0
+                };
+                summary.push_str(&format!(
+                    "| {} | {} | {} | {} | {:.1}% |\n",
+                    b.label, years, b.works, b.matches, share
+                ));
+            }
+            if results.len() >= max_results {
+                summary.push_str("\nNOTE: Scan stopped at maxResults works; increase maxResults for a complete profile.\n");
+            }
+            let rows: Vec<serde_json::Value> = buckets
+                .iter()
+                .map(|b| {
+                    let mut v = json!({
+                        "label": b.label,
+                        "startYear": b.start_year,
+                        "endYear": b.end_year,
+                        "works": b.works,
+                        "matches": b.matches,
+                    });
+                    if include_ids {
+                        v["workIds"] = json!(b.work_ids);
+                    }
+                    v
+                })
+                .collect();
             let meta = json!({
-                "totalLength": text.len(),
-                "returnedStart": args.get("startChar").and_then(|v| v.as_u64()).unwrap_or( args.get("page").and_then(|v| v.as_u64()).and_then(|p| args.get("pageSize").and_then(|s| s.as_u64()).map(|ps| p*ps)).unwrap_or(0) ) ,
-                "returnedEnd": args.get("startChar").and_then(|v| v.as_u64()).unwrap_or( args.get("page").and_then(|v| v.as_u64()).and_then(|p| args.get("pageSize").and_then(|s| s.as_u64()).map(|ps| p*ps)).unwrap_or(0) ) + (sliced.len() as u64),
-                "truncated": (sliced.len() as u64) < (text.len() as u64),
-                "sourcePath": cur_path.to_string_lossy(),
-                "extractionMethod": extraction_method,
-                "headingsTotal": heads.len(),
-                "headingsPreview": heads.into_iter().take(hl).collect::<Vec<_>>(),
-                "matchedId": matched_id,
-                "matchedTitle": matched_title,
-                "matchedScore": matched_score,
-                "biblio": tipitaka_biblio(&xml),
-                "highlighted": if highlight_count > 0 { Some(highlight_count) } else { None::<usize> },
-                "highlightPositions": if highlight_positions.is_empty() { None::<Vec<serde_json::Value>> } else { Some(highlight_positions) },
+                "queryRaw": q_raw,
+                "searchPattern": q,
+                "bucketBy": if by_century { "century" } else { "dynasty" },
+                "totalWorks": results.len(),
+                "totalMatches": total_matches,
+                "buckets": rows,
+                "truncatedByMaxResults": results.len() >= max_results,
+                "hint": "Dates come from CBETA bylines (translator/author); use cbeta_search to inspect individual works"
             });
-            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": sliced}], "_meta": meta }});
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary}], "_meta": meta }});
         }
-        "sat_search" => {
-            let q = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
-            let rows = args.get("rows").and_then(|v| v.as_u64()).unwrap_or(100) as usize;
-            let offs = args.get("offs").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-            let exact = args.get("exact").and_then(|v| v.as_bool()).unwrap_or(true);
-            let titles_only = args
-                .get("titlesOnly")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(false);
-            let fields = args
-                .get("fields")
+        "cbeta_concordance" | "tipitaka_concordance" => {
+            let is_cbeta = name == "cbeta_concordance";
+            let q_raw = args
+                .get("query")
                 .and_then(|v| v.as_str())
-                .unwrap_or("id,fascnm,startid,endid");
-            let fq: Vec<String> = args
-                .get("fq")
-                .and_then(|v| v.as_array())
-                .map(|a| {
-                    a.iter()
-                        .filter_map(|x| x.as_str().map(|s| s.to_string()))
-                        .collect()
-                })
-                .unwrap_or_default();
-            let qt = q.trim();
-            let q_param = if exact && !qt.is_empty() {
-                if qt.starts_with('\"') && qt.ends_with('\"') && qt.len() >= 2 {
-                    qt.to_string()
-                } else {
-                    format!("\"{}\"", qt)
-                }
+                .unwrap_or("")
+                .trim();
+            if q_raw.is_empty() {
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "query is empty"}], "_meta": {"query": q_raw, "lines": []} }});
+            }
+            let looks_like_regex = q_raw.chars().any(|c| ".+*?[](){}|\\".contains(c));
+            let q = if looks_like_regex {
+                q_raw.to_string()
+            } else if is_cbeta {
+                ws_cjk_variant_fuzzy_regex_literal(q_raw)
             } else {
-                qt.to_string()
+                to_whitespace_fuzzy_literal(q_raw)
             };
-            if let Some(jsonv) = sat_wrap7_search_json(&q_param, rows, offs, fields, &fq) {
-                let docs_v = jsonv
-                    .get("response")
-                    .and_then(|r| r.get("docs"))
-                    .cloned()
-                    .unwrap_or(json!([]));
-                let count = jsonv
-                    .get("response")
-                    .and_then(|r| r.get("numFound"))
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0);
-                let meta_base = json!({ "count": count, "results": docs_v, "titlesOnly": titles_only, "q": qt, "qSent": q_param, "exact": exact, "fl": fields, "fq": fq });
-                let auto = args
-                    .get("autoFetch")
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(false);
-                if auto {
-                    let docs = jsonv
-                        .get("response")
-                        .and_then(|r| r.get("docs"))
-                        .and_then(|v| v.as_array())
-                        .cloned()
-                        .unwrap_or_default();
-                    if docs.is_empty() {
-                        let summary = "0 results".to_string();
-                        return json!({ "jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary }], "_meta": meta_base }});
-                    }
-                    let mut best_i = 0usize;
-                    let mut best_sc = -1f32;
-                    for (i, d) in docs.iter().enumerate() {
-                        let title = d.get("fascnm").and_then(|v| v.as_str()).unwrap_or("");
-                        let sc = title_score(title, q);
-                        if sc > best_sc {
-                            best_sc = sc;
-                            best_i = i;
+            let re = match Regex::new(&format!("(?i){}", q)) {
+                Ok(r) => r,
+                Err(e) => {
+                    let error = daizo_core::pattern::validate(&q).err().unwrap_or_else(|| {
+                        daizo_core::pattern::PatternError {
+                            message: e.to_string(),
+                            position: None,
+                            suggestion: None,
                         }
-                    }
-                    let chosen = &docs[best_i];
-                    let useid = chosen.get("startid").and_then(|v| v.as_str()).unwrap_or("");
-                    let url = sat_detail_build_url(useid);
-                    let t = sat_fetch(&url);
-                    let start =
-                        args.get("startChar").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-                    let maxc = args
-                        .get("maxChars")
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(8000) as usize;
-                    let (sliced, total_chars, returned_start, returned_end) =
-                        slice_text_bounds(&t, start, maxc);
-                    let mut meta = meta_base;
-                    meta["chosen"] = chosen.clone();
-                    meta["titleScore"] = json!(best_sc);
-                    meta["sourceUrl"] = json!(url);
-                    meta["returnedStart"] = json!(returned_start as u64);
-                    meta["returnedEnd"] = json!(returned_end as u64);
-                    meta["totalLength"] = json!(total_chars as u64);
-                    meta["truncated"] = json!(returned_end < total_chars);
-                    meta["extractionMethod"] = json!("sat-detail-extract");
-                    return json!({ "jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": sliced }], "_meta": meta }});
-                } else {
-                    let summary = if titles_only {
-                        format!("{} titles; see _meta.results", count)
-                    } else {
-                        format!("{} results; see _meta.results", count)
-                    };
-                    return json!({ "jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary }], "_meta": meta_base }});
+                    });
+                    return ToolError::InvalidRegex { pattern: q, error }.response(&id);
                 }
+            };
+            let window = args
+                .get("window")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(if is_cbeta { 20 } else { 40 }) as usize;
+            let sort = daizo_core::concordance::KwicSort::parse(
+                args.get("sortBy")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("position"),
+            );
+            let format = args
+                .get("format")
+                .and_then(|v| v.as_str())
+                .unwrap_or("text")
+                .to_ascii_lowercase();
+            let max_results = args
+                .get("maxResults")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(50) as usize;
+            let max_per_file = args
+                .get("maxMatchesPerFile")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(50) as usize;
+            let max_lines = args.get("maxLines").and_then(|v| v.as_u64()).unwrap_or(200) as usize;
+
+            if let Err(e) = require_corpus(if is_cbeta { "cbeta" } else { "tipitaka" }) {
+                return e.response(&id);
+            }
+            let (source, results) = if is_cbeta {
+                ("cbeta", cbeta_grep(&cbeta_root(), &q, max_results, 1))
             } else {
-                let hits = sat_search_results(q, rows, offs, exact, titles_only);
-                let meta =
-                    json!({ "count": hits.len(), "results": hits, "titlesOnly": titles_only });
-                let summary = if titles_only {
-                    format!(
-                        "{} titles; see _meta.results",
-                        meta["count"].as_u64().unwrap_or(0)
-                    )
-                } else {
-                    format!(
-                        "{} results; see _meta.results",
-                        meta["count"].as_u64().unwrap_or(0)
-                    )
+                (
+                    "tipitaka",
+                    tipitaka_grep(&tipitaka_root(), &q, max_results, 1),
+                )
+            };
+            let opts = daizo_core::concordance::KwicOptions {
+                window,
+                max_per_file,
+                strip_whitespace: is_cbeta,
+            };
+            let mut lines =
+                daizo_core::concordance::concordance_for_results(source, &results, &re, &opts);
+            let total_lines = lines.len();
+            daizo_core::concordance::sort_kwic(&mut lines, sort);
+            lines.truncate(max_lines);
+
+            let body = match format.as_str() {
+                "csv" => daizo_core::concordance::kwic_to_csv(&lines),
+                "json" => serde_json::to_string_pretty(&lines).unwrap_or_default(),
+                _ => daizo_core::concordance::kwic_to_text(&lines, window),
+            };
+            let summary = if format == "text" {
+                format!(
+                    "Concordance for '{}' ({} lines in {} files{}):\n\n{}",
+                    q_raw,
+                    total_lines,
+                    results.len(),
+                    if total_lines > lines.len() {
+                        format!(", showing {}", lines.len())
+                    } else {
+                        String::new()
+                    },
+                    body
+                )
+            } else {
+                body
+            };
+            let meta = json!({
+                "queryRaw": q_raw,
+                "searchPattern": q,
+                "source": source,
+                "window": window,
+                "sortBy": match sort {
+                    daizo_core::concordance::KwicSort::Position => "position",
+                    daizo_core::concordance::KwicSort::Right => "right",
+                    daizo_core::concordance::KwicSort::Left => "left",
+                },
+                "format": format,
+                "filesScanned": results.len(),
+                "totalLines": total_lines,
+                "returnedLines": lines.len(),
+                "truncatedByMaxResults": results.len() >= max_results,
+                "lines": lines,
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary}], "_meta": meta }});
+        }
+        "reading_list" => {
+            let source = args
+                .get("source")
+                .and_then(|v| v.as_str())
+                .unwrap_or("cbeta")
+                .to_lowercase();
+            if local_source_root(&source).is_none() {
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": format!("unknown source: {}", source)}], "_meta": {"source": source} }});
+            }
+            if let Err(e) = require_corpus(&source) {
+                return e.response(&id);
+            }
+            let name = args
+                .get("collection")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            let after = args
+                .get("after")
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty());
+            let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(20) as usize;
+            let Some(collection) = daizo_core::reading::resolve(&source, name) else {
+                let known: Vec<String> = daizo_core::reading::TAISHO_DIVISIONS
+                    .iter()
+                    .map(|d| format!("{} ({})", d.zh, d.en))
+                    .collect();
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": format!("unknown collection '{}'; use a canon or volume code (T, T08) or one of: {}", name, known.join(", "))}], "_meta": {"source": source, "collection": name} }});
+            };
+            let Some(idx) = corpus_index(&source) else {
+                return text_not_found(&source, &args).response(&id);
+            };
+            let works = daizo_core::reading::members(&source, &collection, &idx);
+            let start = match after {
+                Some(a) => match works
+                    .iter()
+                    .position(|e| daizo_core::reading::names(&source, e, a))
+                {
+                    Some(i) => i + 1,
+                    None => return text_not_found(&source, &json!({ "id": a })).response(&id),
+                },
+                None => 0,
+            };
+            let page = &works[start.min(works.len())..(start + limit).min(works.len())];
+            let mut lines = vec![if page.is_empty() {
+                format!(
+                    "{}: {} works, none after {}",
+                    collection.label,
+                    works.len(),
+                    after.unwrap_or("the start")
+                )
+            } else {
+                format!(
+                    "{}: works {}–{} of {}",
+                    collection.label,
+                    start + 1,
+                    start + page.len(),
+                    works.len()
+                )
+            }];
+            let mut results = Vec::new();
+            for (i, e) in page.iter().enumerate() {
+                let meta = e.meta.as_ref();
+                let text_chars = meta
+                    .and_then(|m| m.get("textChars"))
+                    .and_then(|s| s.parse::<u64>().ok());
+                let juan = meta
+                    .and_then(|m| m.get("juanCount"))
+                    .and_then(|s| s.parse::<u64>().ok());
+                let size = match (juan, text_chars) {
+                    (Some(j), Some(n)) => format!("  ({} juan, {} chars)", j, n),
+                    (None, Some(n)) => format!("  ({} chars)", n),
+                    _ => String::new(),
                 };
-                return json!({ "jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary }], "_meta": meta }});
+                lines.push(format!("{}. {}  {}{}", start + i + 1, e.id, e.title, size));
+                results.push(json!({
+                    "position": start + i + 1,
+                    "id": e.id,
+                    "title": e.title,
+                    "titles": daizo_core::titles::of_entry(e),
+                    "path": e.path,
+                    "textChars": text_chars,
+                    "juanCount": juan,
+                }));
             }
+            let next = (start + page.len() < works.len())
+                .then(|| page.last().map(|e| e.id.clone()))
+                .flatten();
+            if let Some(n) = &next {
+                lines.push(format!("next: after={}", n));
+            }
+            let meta = json!({
+                "source": source,
+                "collection": {"name": name, "label": collection.label},
+                "total": works.len(),
+                "start": start + 1,
+                "count": results.len(),
+                "results": results,
+                "next": next,
+                "done": next.is_none(),
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": lines.join("\n")}], "_meta": meta }});
         }
-        "sat_fetch" => {
-            // Prefer building URL from useid (startid). Fallback to direct url.
-            let url = if let Some(uid) = args.get("useid").and_then(|v| v.as_str()) {
-                sat_detail_build_url(uid)
-            } else {
-                args.get("url")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string()
+        "daizo_similar_works" => {
+            let source = args
+                .get("source")
+                .and_then(|v| v.as_str())
+                .unwrap_or("cbeta")
+                .to_lowercase();
+            if local_source_root(&source).is_none() {
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": format!("unknown source: {}", source)}], "_meta": {"source": source} }});
+            }
+            if let Err(e) = require_corpus(&source) {
+                return e.response(&id);
+            }
+            let wid = args.get("id").and_then(|v| v.as_str()).unwrap_or("").trim();
+            let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
+            let Some(path) = resolve_local_source_path(&source, wid) else {
+                return text_not_found(&source, &args).response(&id);
             };
-            let start = args.get("startChar").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-            let maxc = args
-                .get("maxChars")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(8000) as usize;
-            let t = sat_fetch(&url);
-            let (sliced, total_chars, returned_start, returned_end) =
-                slice_text_bounds(&t, start, maxc);
+            let canon = fs::canonicalize(&path).unwrap_or(path);
+            let Some(idx) = corpus_index(&source) else {
+                return text_not_found(&source, &args).response(&id);
+            };
+            let Some(entry) = idx.iter().find(|e| Path::new(&e.path) == canon) else {
+                return text_not_found(&source, &args).response(&id);
+            };
+            let Some(profiles) = corpus_profiles(&source, &idx) else {
+                return text_not_found(&source, &args).response(&id);
+            };
+            let similar = profiles.similar(&entry.path, limit).unwrap_or_default();
+            let by_path: std::collections::HashMap<&str, &IndexEntry> =
+                idx.iter().map(|e| (e.path.as_str(), e)).collect();
+            let mut lines = vec![format!("Works similar to {} {}:", entry.id, entry.title)];
+            let mut results = Vec::new();
+            for (i, s) in similar.iter().enumerate() {
+                let Some(e) = by_path.get(s.path.as_str()) else {
+                    continue;
+                };
+                lines.push(format!(
+                    "{}. {}  {}  {:.3}  ({})",
+                    i + 1,
+                    e.id,
+                    e.title,
+                    s.score,
+                    s.shared.join(", ")
+                ));
+                results.push(json!({
+                    "id": e.id,
+                    "title": e.title,
+                    "titles": daizo_core::titles::of_entry(e),
+                    "path": e.path,
+                    "score": s.score,
+                    "shared": s.shared,
+                }));
+            }
+            if results.is_empty() {
+                lines.push("(no work shares enough vocabulary)".to_string());
+            }
             let meta = json!({
-                "totalLength": total_chars as u64,
-                "returnedStart": returned_start as u64,
-                "returnedEnd": returned_end as u64,
-                "truncated": returned_end < total_chars,
-                "sourceUrl": url,
-                "extractionMethod": "sat-detail-extract"
+                "source": source,
+                "id": entry.id,
+                "title": entry.title,
+                "profiles": profiles.len(),
+                "count": results.len(),
+                "results": results,
             });
-            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": sliced}], "_meta": meta }});
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": lines.join("\n")}], "_meta": meta }});
         }
-        "sat_detail" => {
-            let useid = args.get("useid").and_then(|v| v.as_str()).unwrap_or("");
-            // Fixed params per observation: mode=detail, ob=1, mode2=2. useid is the key.
-            let url = format!("https://21dzk.l.u-tokyo.ac.jp/SAT2018/satdb2018pre.php?mode=detail&ob=1&mode2=2&useid={}", urlencoding::encode(useid));
-            let start = args.get("startChar").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-            let maxc = args
+        "text_diff" => {
+            let source = args
+                .get("source")
+                .and_then(|v| v.as_str())
+                .unwrap_or("cbeta")
+                .to_lowercase();
+            let source_b = args
+                .get("sourceB")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_lowercase())
+                .unwrap_or_else(|| source.clone());
+            let mut sides = Vec::new();
+            for (src, key, part_key) in [(&source, "a", "partA"), (&source_b, "b", "partB")] {
+                let wid = args.get(key).and_then(|v| v.as_str()).unwrap_or("").trim();
+                if local_source_root(src).is_none() {
+                    return ToolError::InvalidArgument {
+                        name: if key == "a" { "source" } else { "sourceB" }.to_string(),
+                        value: src.to_string(),
+                        expected: capabilities::LOCAL_CORPORA.join(", "),
+                    }
+                    .response(&id);
+                }
+                if !capabilities().allows(src) {
+                    return ToolError::Disabled {
+                        group: src.to_string(),
+                    }
+                    .response(&id);
+                }
+                if wid.is_empty() || !daizo_core::path_resolver::safe_id(wid) {
+                    return ToolError::InvalidArgument {
+                        name: key.to_string(),
+                        value: wid.to_string(),
+                        expected: "a text id (T0235, s0101m.mul, file stem)".to_string(),
+                    }
+                    .response(&id);
+                }
+                if let Err(e) = require_corpus(src) {
+                    return e.response(&id);
+                }
+                let part = args.get(part_key).and_then(|v| v.as_str());
+                let not_found = || ToolError::TextNotFound {
+                    corpus: src.to_string(),
+                    id: wid.to_string(),
+                };
+                let Some(path) = resolve_local_source_path(src, wid) else {
+                    return not_found().response(&id);
+                };
+                let Some(text) =
+                    daizo_core::analysis::plain_text_for_source(src, &path, part, false)
+                        .filter(|t| !t.trim().is_empty())
+                else {
+                    return not_found().response(&id);
+                };
+                let title = corpus_index(src)
+                    .and_then(|idx| {
+                        let canon = fs::canonicalize(&path).unwrap_or(path.clone());
+                        idx.iter()
+                            .find(|e| Path::new(&e.path) == canon)
+                            .map(|e| e.title.clone())
+                    })
+                    .unwrap_or_default();
+                sides.push((src.clone(), wid.to_string(), part, title, text));
+            }
+            let opts = daizo_core::diff::DiffOptions {
+                ignore_punctuation: args
+                    .get("ignorePunctuation")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true),
+                fold_variants: args
+                    .get("foldVariants")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true),
+                ..Default::default()
+            };
+            let d = daizo_core::diff::diff(&sides[0].4, &sides[1].4, &opts);
+            let context = args.get("context").and_then(|v| v.as_u64()).unwrap_or(20) as usize;
+            let max_hunks = args.get("maxHunks").and_then(|v| v.as_u64()).unwrap_or(200) as usize;
+            let max_chars = args
                 .get("maxChars")
                 .and_then(|v| v.as_u64())
-                .unwrap_or(8000) as usize;
-            let t = sat_fetch(&url);
-            let (sliced, total_chars, returned_start, returned_end) =
-                slice_text_bounds(&t, start, maxc);
+                .map(|n| n as usize)
+                .unwrap_or_else(default_max_chars);
+            let side = |i: usize, chars: usize| {
+                let (src, wid, part, title, _) = &sides[i];
+                json!({"source": src, "id": wid, "part": part, "title": title, "chars": chars})
+            };
+            let changes: Vec<&daizo_core::diff::Hunk> = d.changes().collect();
+            let rendered = d.render(context);
+            let mut text = format!(
+                "--- {} {}\n+++ {} {}\nsimilarity {:.3}, {} changes, {} anchors\n\n",
+                sides[0].1,
+                sides[0].3,
+                sides[1].1,
+                sides[1].3,
+                d.similarity(),
+                changes.len(),
+                d.anchors.len()
+            );
+            let truncated = rendered.chars().count() > max_chars;
+            if truncated {
+                text.extend(rendered.chars().take(max_chars));
+                text.push_str("\n… (diff truncated; raise maxChars or compare single juans)");
+            } else {
+                text.push_str(&rendered);
+            }
             let meta = json!({
-                "totalLength": total_chars as u64,
-                "returnedStart": returned_start as u64,
-                "returnedEnd": returned_end as u64,
-                "truncated": returned_end < total_chars,
-                "sourceUrl": url,
-                "extractionMethod": "sat-detail-extract"
+                "a": side(0, d.a_len),
+                "b": side(1, d.b_len),
+                "similarity": d.similarity(),
+                "equalChars": d.equal,
+                "changes": changes.len(),
+                "anchorCount": d.anchors.len(),
+                "anchors": d.anchors.iter().take(max_hunks).collect::<Vec<_>>(),
+                "hunks": changes.iter().take(max_hunks).collect::<Vec<_>>(),
+                "truncated": truncated || changes.len() > max_hunks,
             });
-            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": sliced}], "_meta": meta }});
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": meta }});
         }
-        "tibetan_search" => {
-            let q = args
-                .get("query")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .trim()
-                .to_string();
-            if q.is_empty() {
-                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "query is empty"}], "_meta": {"query": q, "count": 0, "results": []} }});
+        "cbeta_collate" => {
+            if let Err(e) = require_corpus("cbeta") {
+                return e.response(&id);
             }
-            let sources: Vec<String> = args
-                .get("sources")
-                .and_then(|v| v.as_array())
-                .map(|a| {
-                    a.iter()
-                        .filter_map(|x| x.as_str().map(|s| s.to_string()))
+            let wid = args.get("id").and_then(|v| v.as_str()).unwrap_or("").trim();
+            if wid.is_empty() || !daizo_core::path_resolver::safe_id(wid) {
+                return ToolError::InvalidArgument {
+                    name: "id".to_string(),
+                    value: wid.to_string(),
+                    expected: "a CBETA id (e.g. T0001)".to_string(),
+                }
+                .response(&id);
+            }
+            let Some(path) = resolve_local_source_path("cbeta", wid)
+                .filter(|p| p.is_file() && in_corpus("cbeta", p))
+            else {
+                return text_not_found("cbeta", &args).response(&id);
+            };
+            note_hot_file(&path);
+            let xml_arc = cbeta_xml_cached(&path);
+            let xml = xml_arc.as_str();
+            let part = args.get("part").and_then(|v| v.as_str());
+            let range = match part {
+                Some(p) => match juan_map_cached(&path, Some(xml))
+                    .and_then(|m| m.get(p).map(|j| j.start..j.end))
+                {
+                    Some(r) => r,
+                    None => {
+                        return ToolError::InvalidArgument {
+                            name: "part".to_string(),
+                            value: p.to_string(),
+                            expected: "a juan of the text (e.g. '001')".to_string(),
+                        }
+                        .response(&id)
+                    }
+                },
+                None => 0..xml.len(),
+            };
+            let wits = daizo_core::collate::witnesses(xml);
+            let wits_meta: Vec<serde_json::Value> = wits
+                .iter()
+                .map(|(s, n)| json!({"siglum": s, "apps": n}))
+                .collect();
+            let title = load_or_build_cbeta_index()
+                .iter()
+                .find(|e| e.id == wid)
+                .map(|e| e.title.clone())
+                .unwrap_or_default();
+            let witness = args
+                .get("witness")
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty());
+            let Some(witness) = witness else {
+                let mut lines = vec![format!("{} {}: {} witnesses", wid, title, wits.len())];
+                lines.extend(wits.iter().map(|(s, n)| format!("{}\t{} apps", s, n)));
+                let meta = json!({"id": wid, "title": title, "witnesses": wits_meta});
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": lines.join("\n")}], "_meta": meta }});
+            };
+            let siglum = daizo_core::collate::normalize_siglum(witness);
+            if !wits.iter().any(|(s, _)| *s == siglum) {
+                return ToolError::InvalidArgument {
+                    name: "witness".to_string(),
+                    value: witness.to_string(),
+                    expected: wits
+                        .iter()
+                        .map(|(s, _)| s.as_str())
                         .collect::<Vec<_>>()
-                })
-                .unwrap_or_else(|| vec!["adarshah".to_string(), "buda".to_string()]);
-            let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
-            let exact = args.get("exact").and_then(|v| v.as_bool()).unwrap_or(true);
-            let max_snippet_chars = args
-                .get("maxSnippetChars")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(240) as usize;
-            let wildcard = args
-                .get("wildcard")
+                        .join(" "),
+                }
+                .response(&id);
+            }
+            let c = daizo_core::collate::collate(xml, &siglum);
+            let include_notes = args
+                .get("includeNotes")
                 .and_then(|v| v.as_bool())
                 .unwrap_or(false);
-
-            let mut variants: Vec<String> = vec![q.clone()];
-            if let Some(u) = ewts_to_unicode_best_effort(&q) {
-                if !variants.iter().any(|x| x == &u) {
-                    variants.push(u);
+            let text = match part {
+                Some(p) => daizo_core::extract_cbeta_juan_plain(&c.xml, p, include_notes)
+                    .unwrap_or_default(),
+                None => daizo_core::extract_cbeta_plain_from_xml(&c.xml, include_notes),
+            };
+            let readings: Vec<&daizo_core::collate::Reading> = c
+                .readings
+                .iter()
+                .filter(|r| range.contains(&r.offset))
+                .collect();
+            let max_readings = args
+                .get("maxReadings")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(200) as usize;
+            let span = fetch_slice(&text, &args, false);
+            let meta = json!({
+                "id": wid,
+                "title": title,
+                "part": part,
+                "witness": siglum,
+                "witnesses": wits_meta,
+                "readingCount": readings.len(),
+                "readings": readings.iter().take(max_readings).collect::<Vec<_>>(),
+                "readingsTruncated": readings.len() > max_readings,
+                "totalChars": span.total,
+                "returnedStart": span.start,
+                "returnedEnd": span.end,
+                "truncated": span.end < span.total,
+                "nextCursor": next_cursor("cbeta_collate", &args, span.end, span.total),
+                "nextLine": span.next_line,
+                "lineMap": line_map(&text, span.start, span.end),
+                "sourcePath": path.to_string_lossy(),
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": span.text}], "_meta": meta }});
+        }
+        "cbeta_colophons" => {
+            if let Err(e) = require_corpus("cbeta") {
+                return e.response(&id);
+            }
+            let wid = args.get("id").and_then(|v| v.as_str()).unwrap_or("").trim();
+            if wid.is_empty() || !daizo_core::path_resolver::safe_id(wid) {
+                return ToolError::InvalidArgument {
+                    name: "id".to_string(),
+                    value: wid.to_string(),
+                    expected: "a CBETA id (e.g. T0001)".to_string(),
                 }
+                .response(&id);
             }
-
-            let mut results: Vec<serde_json::Value> = Vec::new();
-            let warnings: Vec<String> = Vec::new();
-
-            for src in sources.iter() {
-                match src.as_str() {
-                    "adarshah" => {
-                        for vq in variants.iter() {
-                            let mut r =
-                                adarshah_search_fulltext(vq, wildcard, limit, max_snippet_chars);
-                            results.append(&mut r);
+            let Some(path) = resolve_local_source_path("cbeta", wid)
+                .filter(|p| p.is_file() && in_corpus("cbeta", p))
+            else {
+                return text_not_found("cbeta", &args).response(&id);
+            };
+            note_hot_file(&path);
+            let xml_arc = cbeta_xml_cached(&path);
+            let xml = xml_arc.as_str();
+            let map = juan_map_cached(&path, Some(xml));
+            let part = args.get("part").and_then(|v| v.as_str());
+            let part_n = match part {
+                Some(p) => match map.as_ref().and_then(|m| m.get(p)) {
+                    Some(j) => Some(j.n.clone()),
+                    None => {
+                        return ToolError::InvalidArgument {
+                            name: "part".to_string(),
+                            value: p.to_string(),
+                            expected: "a juan of the text (e.g. '001')".to_string(),
                         }
+                        .response(&id)
                     }
-                    "buda" => {
-                        for vq in variants.iter() {
-                            let mut r = buda_search_fulltext(vq, exact, limit, max_snippet_chars);
-                            results.append(&mut r);
+                },
+                None => None,
+            };
+            let kind = match args.get("kind").and_then(|v| v.as_str()) {
+                Some(k) => match ColophonKind::parse(k) {
+                    Some(k) => Some(k),
+                    None => {
+                        return ToolError::InvalidArgument {
+                            name: "kind".to_string(),
+                            value: k.to_string(),
+                            expected: ColophonKind::NAMES.join(", "),
                         }
+                        .response(&id)
                     }
-                    _ => {}
+                },
+                None => None,
+            };
+            let title = load_or_build_cbeta_index()
+                .iter()
+                .find(|e| e.id == wid)
+                .map(|e| e.title.clone())
+                .unwrap_or_default();
+            // Back matter and what follows a juan's close belong to the juan before them.
+            let found: Vec<(Colophon, Option<String>)> = list_colophons(xml)
+                .into_iter()
+                .filter(|c| kind.is_none_or(|k| c.kind == k))
+                .map(|c| {
+                    let juan = map
+                        .as_ref()
+                        .and_then(|m| m.juan_at_offset(c.offset))
+                        .map(|j| j.n.clone());
+                    (c, juan)
+                })
+                .filter(|(_, juan)| part_n.is_none() || *juan == part_n)
+                .collect();
+            let mut lines = vec![format!("{} {}: {} colophons", wid, title, found.len())];
+            lines.extend(found.iter().map(|(c, _)| {
+                let text = match &c.head {
+                    Some(h) => format!("{}: {}", h, c.text),
+                    None => c.text.clone(),
+                };
+                format!(
+                    "{}\t{}\t{}\t{}",
+                    c.line,
+                    c.lb.as_deref().unwrap_or("-"),
+                    c.kind.as_str(),
+                    text
+                )
+            }));
+            let entries: Vec<serde_json::Value> = found
+                .iter()
+                .map(|(c, juan)| {
+                    let mut v = json!(c);
+                    v["juan"] = json!(juan);
+                    v
+                })
+                .collect();
+            let meta = json!({
+                "id": wid,
+                "title": title,
+                "part": part,
+                "count": entries.len(),
+                "colophons": entries,
+                "sourcePath": path.to_string_lossy(),
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": lines.join("\n")}], "_meta": meta }});
+        }
+        "cbeta_notes" | "tei_notes" => {
+            let source = match name {
+                "cbeta_notes" => "cbeta".to_string(),
+                _ => args
+                    .get("source")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("cbeta")
+                    .to_lowercase(),
+            };
+            if local_source_root(&source).is_none() {
+                return ToolError::InvalidArgument {
+                    name: "source".to_string(),
+                    value: source,
+                    expected: "cbeta, tipitaka, gretil, sarit or muktabodha".to_string(),
                 }
+                .response(&id);
             }
-
-            let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
-            let mut uniq: Vec<serde_json::Value> = Vec::new();
-            for r in results.into_iter() {
-                let key = r
-                    .get("url")
-                    .and_then(|u| u.as_str())
-                    .map(|s| s.to_string())
-                    .unwrap_or_else(|| serde_json::to_string(&r).unwrap_or_default());
-                if seen.insert(key) {
-                    uniq.push(r);
+            if let Err(e) = require_corpus(&source) {
+                return e.response(&id);
+            }
+            let wid = args.get("id").and_then(|v| v.as_str()).unwrap_or("").trim();
+            if wid.is_empty() || !daizo_core::path_resolver::safe_id(wid) {
+                return ToolError::InvalidArgument {
+                    name: "id".to_string(),
+                    value: wid.to_string(),
+                    expected: "a text id of the corpus (e.g. T0001)".to_string(),
                 }
-                if uniq.len() >= limit {
-                    break;
+                .response(&id);
+            }
+            let Some(path) = resolve_local_source_path(&source, wid)
+                .filter(|p| p.is_file() && in_corpus(&source, p))
+            else {
+                return text_not_found(&source, &args).response(&id);
+            };
+            note_hot_file(&path);
+            let xml_arc = if source == "cbeta" {
+                cbeta_xml_cached(&path)
+            } else {
+                Arc::new(fs::read_to_string(&path).unwrap_or_default())
+            };
+            let xml = xml_arc.as_str();
+            let part = args.get("part").and_then(|v| v.as_str());
+            let range = match part {
+                Some(p) => match juan_map_cached(&path, Some(xml))
+                    .and_then(|m| m.get(p).map(|j| j.start..j.end))
+                {
+                    Some(r) => r,
+                    None => {
+                        return ToolError::InvalidArgument {
+                            name: "part".to_string(),
+                            value: p.to_string(),
+                            expected: "a juan of the text (e.g. '001')".to_string(),
+                        }
+                        .response(&id)
+                    }
+                },
+                None => 0..xml.len(),
+            };
+            let category = match args.get("category").and_then(|v| v.as_str()) {
+                Some(c) => match NoteCategory::parse(c) {
+                    Some(c) => Some(c),
+                    None => {
+                        return ToolError::InvalidArgument {
+                            name: "category".to_string(),
+                            value: c.to_string(),
+                            expected: NoteCategory::NAMES.join(", "),
+                        }
+                        .response(&id)
+                    }
+                },
+                None => None,
+            };
+            let notes: Vec<Note> = list_notes(xml)
+                .into_iter()
+                .filter(|n| range.contains(&n.offset))
+                .filter(|n| category.is_none_or(|c| n.category == c))
+                .collect();
+            let offset = args.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+            let max_notes = args.get("maxNotes").and_then(|v| v.as_u64()).unwrap_or(200) as usize;
+            let page = &notes[offset.min(notes.len())..(offset + max_notes).min(notes.len())];
+            let next_offset = (offset + page.len() < notes.len()).then_some(offset + page.len());
+            let mut counts = serde_json::Map::new();
+            for c in NoteCategory::NAMES {
+                let n = notes.iter().filter(|n| n.category.as_str() == *c).count();
+                counts.insert(c.to_string(), json!(n));
+            }
+            let mut lines = vec![format!("{} {}: {} notes", source, wid, notes.len())];
+            lines.extend(page.iter().map(|n| {
+                format!(
+                    "{}\t{}\t{}\t{}",
+                    n.anchor_line,
+                    n.lb.as_deref().or(n.n.as_deref()).unwrap_or("-"),
+                    n.category.as_str(),
+                    n.text
+                )
+            }));
+            let meta = json!({
+                "source": source,
+                "id": wid,
+                "part": part,
+                "count": notes.len(),
+                "categories": counts,
+                "notes": page,
+                "offset": offset,
+                "truncated": next_offset.is_some(),
+                "nextOffset": next_offset,
+                "sourcePath": path.to_string_lossy(),
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": lines.join("\n")}], "_meta": meta }});
+        }
+        "entities" => {
+            let source = args
+                .get("source")
+                .and_then(|v| v.as_str())
+                .unwrap_or("cbeta")
+                .to_lowercase();
+            if local_source_root(&source).is_none() {
+                return ToolError::InvalidArgument {
+                    name: "source".to_string(),
+                    value: source,
+                    expected: "cbeta, tipitaka, gretil, sarit or muktabodha".to_string(),
                 }
+                .response(&id);
             }
-
-            let mut summary = format!("Tibetan search for '{}'\n", q);
-            if uniq.is_empty() {
-                summary.push_str("0 results\n");
+            if let Err(e) = require_corpus(&source) {
+                return e.response(&id);
+            }
+            let wid = args.get("id").and_then(|v| v.as_str()).unwrap_or("").trim();
+            if wid.is_empty() || !daizo_core::path_resolver::safe_id(wid) {
+                return ToolError::InvalidArgument {
+                    name: "id".to_string(),
+                    value: wid.to_string(),
+                    expected: "a text id of the corpus (e.g. T0001)".to_string(),
+                }
+                .response(&id);
+            }
+            let Some(path) = resolve_local_source_path(&source, wid)
+                .filter(|p| p.is_file() && in_corpus(&source, p))
+            else {
+                return text_not_found(&source, &args).response(&id);
+            };
+            note_hot_file(&path);
+            let xml_arc = if source == "cbeta" {
+                cbeta_xml_cached(&path)
             } else {
-                for (i, r) in uniq.iter().enumerate() {
-                    let src = r.get("source").and_then(|v| v.as_str()).unwrap_or("");
-                    let title = r.get("title").and_then(|v| v.as_str()).unwrap_or("");
-                    let snip = r.get("snippet").and_then(|v| v.as_str()).unwrap_or("");
-                    let url = r.get("url").and_then(|v| v.as_str()).unwrap_or("");
-                    if !title.is_empty() {
-                        summary.push_str(&format!("{}. [{}] {}  {}\n", i + 1, src, title, url));
-                    } else {
-                        summary.push_str(&format!("{}. [{}] {}\n", i + 1, src, url));
+                Arc::new(fs::read_to_string(&path).unwrap_or_default())
+            };
+            let xml = xml_arc.as_str();
+            let part = args.get("part").and_then(|v| v.as_str());
+            let range = match part {
+                Some(p) => match juan_map_cached(&path, Some(xml))
+                    .and_then(|m| m.get(p).map(|j| j.start..j.end))
+                {
+                    Some(r) => r,
+                    None => {
+                        return ToolError::InvalidArgument {
+                            name: "part".to_string(),
+                            value: p.to_string(),
+                            expected: "a juan of the text (e.g. '001')".to_string(),
+                        }
+                        .response(&id)
                     }
-                    if !snip.is_empty() {
-                        summary.push_str(&format!("   {}\n", snip.replace('\n', " ")));
+                },
+                None => 0..xml.len(),
+            };
+            let kind = match args.get("kind").and_then(|v| v.as_str()) {
+                Some(k) => match EntityKind::parse(k) {
+                    Some(k) => Some(k),
+                    None => {
+                        return ToolError::InvalidArgument {
+                            name: "kind".to_string(),
+                            value: k.to_string(),
+                            expected: EntityKind::NAMES.join(", "),
+                        }
+                        .response(&id)
+                    }
+                },
+                None => None,
+            };
+            let found: Vec<Mention> = entity::mentions(xml)
+                .into_iter()
+                .filter(|m| range.contains(&m.offset))
+                .filter(|m| kind.is_none_or(|k| m.kind == k))
+                .collect();
+            let entities = entity::tally(&found);
+            let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(100) as usize;
+            let shown = &entities[..limit.min(entities.len())];
+            // Authority records of the persons and places shown, the most mentioned first.
+            let mut records: Vec<Option<AuthorityRecord>> = vec![None; shown.len()];
+            let mut authority_meta = serde_json::Value::Null;
+            if args.get("authority").and_then(|v| v.as_bool()) == Some(true) {
+                if capabilities().network() {
+                    let max_lookups = args
+                        .get("maxLookups")
+                        .and_then(|v| v.as_u64())
+                        .unwrap_or(20) as usize;
+                    let (mut looked, mut failed) = (0usize, 0usize);
+                    for (e, r) in shown.iter().zip(records.iter_mut()) {
+                        if authority::authority_type(e.kind).is_none() || looked == max_lookups {
+                            continue;
+                        }
+                        looked += 1;
+                        match dila_authority(e) {
+                            Ok(rec) => *r = rec,
+                            Err(()) => failed += 1,
+                        }
                     }
+                    let lookable = shown
+                        .iter()
+                        .filter(|e| authority::authority_type(e.kind).is_some())
+                        .count();
+                    authority_meta = json!({
+                        "source": "DILA Authority Database",
+                        "looked": looked,
+                        "matched": records.iter().filter(|r| r.is_some()).count(),
+                        "failed": failed,
+                        "skipped": lookable - looked,
+                    });
+                } else {
+                    authority_meta = json!({
+                        "source": "DILA Authority Database",
+                        "note": "not looked up: online access is disabled (web)",
+                    });
                 }
             }
-
+            let mut lines = vec![format!(
+                "{} {}: {} entities, {} mentions",
+                source,
+                wid,
+                entities.len(),
+                found.len()
+            )];
+            lines.extend(shown.iter().zip(&records).map(|(e, r)| {
+                format!(
+                    "{}\t{}\t{}\t{}\tline {}{}{}",
+                    e.count,
+                    e.kind.as_str(),
+                    e.forms.join(" / "),
+                    e.key.as_deref().unwrap_or("-"),
+                    e.first.line,
+                    e.first
+                        .lb
+                        .as_deref()
+                        .map(|lb| format!(" ({})", lb))
+                        .unwrap_or_default(),
+                    r.as_ref()
+                        .map(|r| format!("\t= {} {}", r.id, r.name))
+                        .unwrap_or_default()
+                )
+            }));
+            let entities_meta: Vec<serde_json::Value> = shown
+                .iter()
+                .zip(&records)
+                .map(|(e, r)| {
+                    let mut v = json!(e);
+                    if !authority_meta.is_null() {
+                        v["authority"] = json!(r);
+                    }
+                    v
+                })
+                .collect();
             let meta = json!({
-                "query": q,
-                "variants": variants,
-                "sources": sources,
-                "exact": exact,
-                "maxSnippetChars": max_snippet_chars,
-                "warnings": if warnings.is_empty() { None::<Vec<String>> } else { Some(warnings) },
-                "count": uniq.len(),
-                "results": uniq
+                "source": source,
+                "id": wid,
+                "part": part,
+                "count": entities.len(),
+                "mentions": found.len(),
+                "entities": entities_meta,
+                "truncated": entities.len() > limit,
+                "authority": authority_meta,
+                "sourcePath": path.to_string_lossy(),
             });
-            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary}], "_meta": meta }});
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": lines.join("\n")}], "_meta": meta }});
         }
-	        "sat_pipeline" => {
-            let q = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
-            let exact = args.get("exact").and_then(|v| v.as_bool()).unwrap_or(true);
-            let rows = args.get("rows").and_then(|v| v.as_u64()).unwrap_or(100) as usize;
-            let offs = args.get("offs").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-            let fields_requested = args
-                .get("fields")
+        "find_quotations" => {
+            if !capabilities().allows("cbeta") {
+                return ToolError::Disabled {
+                    group: "cbeta".to_string(),
+                }
+                .response(&id);
+            }
+            let text = args.get("text").and_then(|v| v.as_str()).unwrap_or("");
+            let han = daizo_core::quotations::fold(text).len();
+            if han < daizo_core::quotations::SHINGLE {
+                return ToolError::InvalidArgument {
+                    name: "text".to_string(),
+                    value: text.to_string(),
+                    expected: format!(
+                        "a passage of at least {} Han characters",
+                        daizo_core::quotations::SHINGLE
+                    ),
+                }
+                .response(&id);
+            }
+            if let Err(e) = require_corpus("cbeta") {
+                return e.response(&id);
+            }
+            let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
+            let min_score = args.get("minScore").and_then(|v| v.as_f64()).unwrap_or(0.5) as f32;
+            let exclude = args
+                .get("excludeId")
                 .and_then(|v| v.as_str())
-                .unwrap_or("id,fascnm,startid,endid,body");
-            // sat_pipeline selection relies on `body` and fetching relies on `startid`.
-            // Ensure required fields are present even if the user customizes `fields`.
-            let fields_used = sat_wrap7_ensure_fields(
-                fields_requested,
-                &["id", "fascnm", "startid", "endid", "body"],
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty());
+            let idx = load_or_build_cbeta_index();
+            let Some(quotes) = cbeta_quotes(&idx) else {
+                return text_not_found("cbeta", &args).response(&id);
+            };
+            let by_path: std::collections::HashMap<&str, &IndexEntry> =
+                idx.iter().map(|e| (e.path.as_str(), e)).collect();
+            // Ask for extra regions so that leaving out `excludeId` still fills `limit`.
+            let found = quotes.find(
+                text,
+                limit + if exclude.is_some() { 20 } else { 0 },
+                min_score,
             );
-            let fq: Vec<String> = args
-                .get("fq")
-                .and_then(|v| v.as_array())
-                .map(|a| {
-                    a.iter()
-                        .filter_map(|x| x.as_str().map(|s| s.to_string()))
-                        .collect()
-                })
-                .unwrap_or_default();
-            let start_char_opt = args
-                .get("startChar")
-                .and_then(|v| v.as_u64())
-                .map(|v| v as usize);
-            let start_char_provided = start_char_opt.is_some();
-            let start_requested = start_char_opt.unwrap_or(0);
-            let maxc = args
-                .get("maxChars")
+            let mut lines = vec![format!(
+                "Regions quoting the passage ({} Han characters):",
+                han
+            )];
+            let mut results = Vec::new();
+            let mut fetch_suggestions = Vec::new();
+            for q in found {
+                let Some(e) = by_path.get(q.path.as_str()) else {
+                    continue;
+                };
+                let work = e
+                    .meta
+                    .as_ref()
+                    .and_then(|m| Some(format!("{}{}", m.get("canon")?, m.get("nnum")?)));
+                if let Some(x) = &exclude {
+                    if e.id.eq_ignore_ascii_case(x)
+                        || work.as_deref().is_some_and(|w| w.eq_ignore_ascii_case(x))
+                    {
+                        continue;
+                    }
+                }
+                if results.len() >= limit {
+                    break;
+                }
+                let juan = juan_map_cached(Path::new(&q.path), None)
+                    .and_then(|m| m.juan_at_line(q.start_line).map(|s| s.n.clone()));
+                lines.push(format!(
+                    "{}. {} {}{}  lines {}-{}  {:.2}\n   {}",
+                    results.len() + 1,
+                    e.id,
+                    e.title,
+                    juan.as_deref()
+                        .map(|j| format!(" (juan {})", j))
+                        .unwrap_or_default(),
+                    q.start_line,
+                    q.end_line,
+                    q.score,
+                    q.text.chars().take(120).collect::<String>()
+                ));
+                fetch_suggestions.push(json!({
+                    "tool": "cbeta_fetch",
+                    "args": {"id": e.id, "lineNumber": q.start_line, "contextBefore": 1, "contextAfter": q.end_line - q.start_line + 1, "format": "plain"},
+                    "mode": "low-cost",
+                    "juan": juan,
+                }));
+                results.push(json!({
+                    "id": e.id,
+                    "title": e.title,
+                    "juan": juan,
+                    "startLine": q.start_line,
+                    "endLine": q.end_line,
+                    "score": q.score,
+                    "hits": q.hits,
+                    "text": q.text,
+                }));
+            }
+            if results.is_empty() {
+                lines.push("(no quotations found)".to_string());
+            }
+            let meta = json!({
+                "source": "cbeta",
+                "passageChars": han,
+                "guaranteedChars": daizo_core::quotations::GUARANTEED,
+                "minScore": min_score,
+                "fingerprints": quotes.len(),
+                "count": results.len(),
+                "results": results,
+                "fetchSuggestions": fetch_suggestions,
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": lines.join("\n")}], "_meta": meta }});
+        }
+        "semantic_search" => {
+            let source = args
+                .get("source")
+                .and_then(|v| v.as_str())
+                .unwrap_or("cbeta")
+                .to_lowercase();
+            if local_source_root(&source).is_none() {
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": format!("unknown source: {}", source)}], "_meta": {"source": source} }});
+            }
+            let q = args
+                .get("query")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .trim();
+            if q.is_empty() {
+                return ToolError::InvalidArgument {
+                    name: "query".to_string(),
+                    value: String::new(),
+                    expected: "a non-empty query".to_string(),
+                }
+                .response(&id);
+            }
+            let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
+            let nprobe = args
+                .get("nprobe")
                 .and_then(|v| v.as_u64())
-                .unwrap_or(8000) as usize;
-            let qt = q.trim();
-            let q_param = if exact && !qt.is_empty() {
-                if qt.starts_with('"') && qt.ends_with('"') && qt.len() >= 2 {
-                    qt.to_string()
-                } else {
-                    format!("\"{}\"", qt)
+                .map(|n| n as usize)
+                .unwrap_or(daizo_core::embeddings::NPROBE);
+            let idx = match semantic_index(&source) {
+                Some(Ok(i)) => i,
+                Some(Err(e)) => {
+                    return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": format!("{}: embedding index unreadable ({}); rebuild it with `daizo-cli embed-index --source {}`", source, e, source)}], "_meta": {"source": source, "indexed": false}, "isError": true }});
+                }
+                None => {
+                    return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": format!("{}: no embedding index; build one with `daizo-cli embed-index --source {}` (add --collection to embed part of the corpus)", source, source)}], "_meta": {"source": source, "indexed": false} }});
                 }
-            } else {
-                qt.to_string()
             };
-            if let Some(jsonv) = sat_wrap7_search_json(&q_param, rows, offs, &fields_used, &fq) {
-                let docs = jsonv
-                    .get("response")
-                    .and_then(|r| r.get("docs"))
-                    .and_then(|v| v.as_array())
-                    .cloned()
-                    .unwrap_or_default();
-                if docs.is_empty() {
-                    return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "no results"}], "_meta": {"count": 0} }});
+            if daizo_core::embeddings::is_remote(&idx.model) && !capabilities().network() {
+                return ToolError::Disabled {
+                    group: "web".to_string(),
                 }
-                let (best_i, chosen_by, best_sc) = sat_pick_best_doc(&docs, qt);
-                let chosen = &docs[best_i];
-                let useid = chosen.get("startid").and_then(|v| v.as_str()).unwrap_or("");
-                let url = sat_detail_build_url(useid);
-                let t = sat_fetch(&url);
-                let q_focus = qt
-                    .trim()
-                    .strip_prefix('"')
-                    .and_then(|s| s.strip_suffix('"'))
-                    .unwrap_or(qt)
-                    .trim();
-                let mut focus = json!({"enabled": false});
-                let start_eff = if start_char_provided || q_focus.is_empty() {
-                    start_requested
-                } else {
-                    let pat = ws_cjk_variant_fuzzy_regex_literal(q_focus);
-                    let pos = find_highlight_positions(&t, &pat, true).into_iter().next();
-                    if let Some(p0) = pos {
-                        let s = p0.start_char.saturating_sub(50);
-                        focus = json!({
-                            "enabled": true,
-                            "query": q_focus,
-                            "pattern": pat,
-                            "matchStartChar": p0.start_char as u64,
-                            "matchEndChar": p0.end_char as u64,
-                            "startChar": s as u64
-                        });
-                        s
-                    } else {
-                        start_requested
+                .response(&id);
+            }
+            let query_vec = match daizo_core::embeddings::for_model(&idx.model)
+                .and_then(|e| e.embed(&[q.to_string()]))
+            {
+                Ok(mut v) if !v.is_empty() => v.remove(0),
+                Ok(_) => Vec::new(),
+                Err(reason) => {
+                    return ToolError::NetworkError {
+                        source: "embeddings".to_string(),
+                        reason,
                     }
-                };
-                let (sliced, total_chars, returned_start, returned_end) =
-                    slice_text_bounds(&t, start_eff, maxc);
-                let count = jsonv
-                    .get("response")
-                    .and_then(|r| r.get("numFound"))
+                    .response(&id);
+                }
+            };
+            let hits = idx.search(&query_vec, limit, nprobe);
+            let fetch_tool = format!("{}_fetch", source);
+            let mut lines = vec![format!(
+                "Passages near \"{}\" in {} ({}, {} passages):",
+                q,
+                source,
+                idx.collection,
+                idx.len()
+            )];
+            let mut results = Vec::new();
+            let mut fetch_suggestions = Vec::new();
+            for (i, h) in hits.iter().enumerate() {
+                let p = &h.passage;
+                lines.push(format!(
+                    "{}. {} {}{}  line {}  {:.3}\n   {}…",
+                    i + 1,
+                    p.id,
+                    p.title,
+                    p.juan
+                        .as_deref()
+                        .map(|j| format!(" (juan {})", j))
+                        .unwrap_or_default(),
+                    p.line,
+                    h.score,
+                    p.preview
+                ));
+                results.push(json!({
+                    "id": p.id,
+                    "title": p.title,
+                    "juan": p.juan,
+                    "lineNumber": p.line,
+                    "score": h.score,
+                    "preview": p.preview,
+                }));
+                fetch_suggestions.push(json!({
+                    "tool": fetch_tool,
+                    "args": {"id": p.id, "lineNumber": p.line, "contextBefore": 2, "contextAfter": 12},
+                    "mode": "low-cost",
+                    "juan": p.juan,
+                }));
+            }
+            if results.is_empty() {
+                lines.push("(no passages)".to_string());
+            }
+            let meta = json!({
+                "source": source,
+                "query": q,
+                "model": idx.model,
+                "similarity": if daizo_core::embeddings::is_lexical(&idx.model) { "lexical" } else { "semantic" },
+                "collection": idx.collection,
+                "passages": idx.len(),
+                "count": results.len(),
+                "results": results,
+                "fetchSuggestions": fetch_suggestions,
+            });
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": lines.join("\n")}], "_meta": meta }});
+        }
+        "corpus_stats" => {
+            let source = args
+                .get("source")
+                .and_then(|v| v.as_str())
+                .unwrap_or("cbeta")
+                .to_lowercase();
+            let Some(root) = local_source_root(&source) else {
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": format!("unknown source: {}", source)}], "_meta": {"source": source} }});
+            };
+            let work_id = args.get("id").and_then(|v| v.as_str()).map(|s| s.trim());
+            let division = args
+                .get("division")
+                .and_then(|v| v.as_str())
+                .map(|s| s.trim());
+            let part = args.get("part").and_then(|v| v.as_str());
+            let include_notes = args
+                .get("includeNotes")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            let opts = daizo_core::analysis::StatsOptions {
+                top: args.get("top").and_then(|v| v.as_u64()).unwrap_or(20) as usize,
+                hapax_limit: args
+                    .get("hapaxLimit")
                     .and_then(|v| v.as_u64())
-                    .unwrap_or(0);
-                let meta = json!({
-                    "totalLength": total_chars as u64,
-                    "returnedStart": returned_start as u64,
-                    "returnedEnd": returned_end as u64,
-                    "truncated": returned_end < total_chars,
-                    "sourceUrl": url,
-                    "extractionMethod": "sat-detail-extract",
-                    "search": {"q": qt, "qSent": q_param, "exact": exact, "rows": rows, "offs": offs, "flRequested": fields_requested, "flUsed": fields_used, "fq": fq, "count": count},
-                    "chosen": chosen,
-                    "chosenBy": chosen_by,
-                    "titleScore": best_sc,
-                    "focus": focus,
-                    "startCharRequested": start_requested as u64
-                });
-                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": sliced}], "_meta": meta }});
-            } else {
-                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "no results"}], "_meta": {"count": 0} }});
-	            }
-	        }
-	        "jozen_search" => {
-	            let q_raw0 = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
-	            let q = q_raw0.trim().to_string();
-	            let page = args.get("page").and_then(|v| v.as_u64()).unwrap_or(1) as usize;
-	            let page = page.max(1);
-	            let max_results = args
-	                .get("maxResults")
-	                .and_then(|v| v.as_u64())
-	                .unwrap_or(20) as usize;
-	            let max_results = std::cmp::min(max_results, 50);
-	            let max_snippet_chars = args
-	                .get("maxSnippetChars")
-	                .and_then(|v| v.as_u64())
-	                .map(|v| v as usize)
-	                .unwrap_or(default_snippet_len());
-	
-	            if q.is_empty() {
-	                let meta = json!({
-	                    "source": "jozen",
-	                    "query": q,
-	                    "page": page,
-	                    "pageSize": 50,
-	                    "totalCount": 0,
-	                    "totalPages": 0,
-	                    "results": [],
-	                    "fetchSuggestions": []
-	                });
-	                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "0 results"}], "_meta": meta }});
-	            }
-	
-	            let Some(html) = jozen_search_html(&q, page) else {
-	                let meta = json!({
-	                    "source": "jozen",
-	                    "query": q,
-	                    "page": page,
-	                    "results": [],
-	                    "error": "fetch failed"
-	                });
-	                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "0 results"}], "_meta": meta }});
-	            };
-	
-	            let parsed = jozen_parse_search_html(&html, &q, max_results, max_snippet_chars);
-	            let hint_top = std::env::var("DAIZO_HINT_TOP")
-	                .ok()
-	                .and_then(|s| s.parse::<usize>().ok())
-	                .unwrap_or(1);
-	            let mut fetch_suggestions: Vec<serde_json::Value> = Vec::new();
-	            for r in parsed.results.iter().take(hint_top) {
-	                if !r.lineno.is_empty() {
-	                    fetch_suggestions.push(json!({
-	                        "tool": "jozen_fetch",
-	                        "args": {"lineno": r.lineno.clone()}
-	                    }));
-	                }
-	            }
-	
-	            let total = parsed.total_count.unwrap_or(parsed.results.len());
-	            let total_pages = parsed.total_pages.unwrap_or(0);
-	            let summary = if total_pages > 0 {
-	                format!(
-	                    "{} results (page {}/{}); see _meta.results",
-	                    total, page, total_pages
-	                )
-	            } else {
-	                format!("{} results (page {}); see _meta.results", total, page)
-	            };
-	            let meta = json!({
-	                "source": "jozen",
-	                "query": q,
-	                "page": page,
-	                "pageSize": parsed.page_size.unwrap_or(50),
-	                "displayedCount": parsed.displayed_count,
-	                "totalCount": parsed.total_count.unwrap_or(0),
-	                "totalPages": parsed.total_pages.unwrap_or(0),
-	                "results": parsed.results,
-	                "fetchSuggestions": fetch_suggestions
-	            });
-	            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary}], "_meta": meta }});
-	        }
-	        "jozen_fetch" => {
-	            let lineno0 = args.get("lineno").and_then(|v| v.as_str()).unwrap_or("");
-	            let lineno = lineno0.trim().to_string();
-	            if lineno.is_empty() {
-	                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "lineno is empty"}], "_meta": {"source":"jozen"} }});
-	            }
-	            let start = args.get("startChar").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-	            let maxc = args
-	                .get("maxChars")
-	                .and_then(|v| v.as_u64())
-	                .map(|v| v as usize)
-	                .unwrap_or(default_max_chars());
-	
-	            let source_url = jozen_detail_url(&lineno);
-	            let Some(html) = jozen_detail_html(&lineno) else {
-	                let meta = json!({
-	                    "source": "jozen",
-	                    "lineno": lineno,
-	                    "sourceUrl": source_url,
-	                    "error": "fetch failed"
-	                });
-	                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": ""}], "_meta": meta }});
-	            };
-	            let detail = jozen_extract_detail(&html, &source_url);
-	            let (sliced, total_chars, returned_start, returned_end) =
-	                slice_text_bounds(&detail.content, start, maxc);
-	            let meta = json!({
-	                "source": "jozen",
-	                "sourceUrl": detail.source_url,
-	                "workHeader": detail.work_header,
-	                "textno": detail.textno,
-	                "pagePrev": detail.page_prev,
-	                "pageNext": detail.page_next,
-	                "lineCount": detail.line_ids.len(),
-	                "lineIds": detail.line_ids,
-	                "totalLength": total_chars as u64,
-	                "returnedStart": returned_start as u64,
-	                "returnedEnd": returned_end as u64,
-	                "truncated": returned_end < total_chars,
-	                "extractionMethod": "jozen-detail-extract"
-	            });
-	            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": sliced}], "_meta": meta }});
-	        }
-	        "cbeta_search" => {
-	            let q_raw0 = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
-	            let q_raw = q_raw0.trim();
-	            let looks_like_regex = q_raw.chars().any(|c| ".+*?[](){}|\\".contains(c));
-            let (q, q_display, hl_pat, hl_regex) = if looks_like_regex {
+                    .unwrap_or(50) as usize,
+            };
+            if let Err(e) = require_corpus(&source) {
+                return e.response(&id);
+            }
+
+            let (scope, stats) = if let Some(div) = division.filter(|d| !d.is_empty()) {
+                let paths = daizo_core::analysis::division_paths(&source, &root, div);
+                if paths.is_empty() {
+                    return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": format!("no files found for division '{}' in {}", div, source)}], "_meta": {"source": source, "division": div} }});
+                }
                 (
-                    q_raw.to_string(),
-                    q_raw.to_string(),
-                    q_raw.to_string(),
-                    true,
+                    format!("division {}", div),
+                    daizo_core::analysis::stats_for_paths(&source, &paths, include_notes, &opts),
                 )
+            } else if let Some(wid) = work_id.filter(|w| !w.is_empty()) {
+                let Some(path) = resolve_local_source_path(&source, wid) else {
+                    return text_not_found(&source, &args).response(&id);
+                };
+                let part = if source == "cbeta" { part } else { None };
+                let Some(text) = daizo_core::analysis::plain_text_for_source(
+                    &source,
+                    &path,
+                    part,
+                    include_notes,
+                ) else {
+                    return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": format!("could not extract text: {} part {}", wid, part.unwrap_or("-"))}], "_meta": {"source": source, "id": wid, "part": part} }});
+                };
+                let mut counter = daizo_core::analysis::FreqCounter::new();
+                counter.add_text(&text);
+                let scope = match part {
+                    Some(p) => format!("{} juan {}", wid, p),
+                    None => wid.to_string(),
+                };
+                (scope, counter.finish(&opts))
             } else {
-                let qv = ws_cjk_variant_fuzzy_regex_literal(q_raw);
-                (qv.clone(), q_raw.to_string(), qv, true)
+                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "specify id or division"}], "_meta": {"source": source} }});
             };
-            let max_results = args
-                .get("maxResults")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(20) as usize;
-            let max_matches_per_file = args
-                .get("maxMatchesPerFile")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(5) as usize;
-
-            ensure_cbeta_data();
-            let results = cbeta_grep(&cbeta_root(), &q, max_results, max_matches_per_file);
 
+            let list = |items: &[daizo_core::analysis::FreqItem]| {
+                items
+                    .iter()
+                    .map(|f| format!("{} {}", f.term, f.count))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
             let mut summary = format!(
-                "Found {} files with matches for '{}':\n\n",
-                results.len(),
-                q_display
+                "Corpus stats for {} ({}; texts: {})\n\nchars: {} total, {} distinct\ntokens: {} total, {} distinct\nhapax: {}\n",
+                scope,
+                source,
+                stats.texts,
+                stats.total_chars,
+                stats.distinct_chars,
+                stats.total_tokens,
+                stats.distinct_tokens,
+                stats.hapax_count
             );
-            for (i, result) in results.iter().enumerate() {
-                summary.push_str(&format!(
-                    "{}. {} ({})\n",
-                    i + 1,
-                    result.title,
-                    result.file_id
-                ));
+            summary.push_str(&format!("\nTop chars: {}\n", list(&stats.top_chars)));
+            summary.push_str(&format!("Top tokens: {}\n", list(&stats.top_tokens)));
+            summary.push_str(&format!("Top bigrams: {}\n", list(&stats.top_bigrams)));
+            summary.push_str(&format!("Top trigrams: {}\n", list(&stats.top_trigrams)));
+            if !stats.hapax.is_empty() {
                 summary.push_str(&format!(
-                    "   {} matches, {}\n",
-                    result.total_matches,
-                    result
-                        .fetch_hints
-                        .total_content_size
-                        .as_deref()
-                        .unwrap_or("unknown size")
+                    "Hapax ({} of {}): {}\n",
+                    stats.hapax.len(),
+                    stats.hapax_count,
+                    stats.hapax.join(" ")
                 ));
-
-                for (j, m) in result.matches.iter().enumerate().take(2) {
-                    summary.push_str(&format!(
-                        "   Match {}: ...{}...\n",
-                        j + 1,
-                        m.context.chars().take(100).collect::<String>()
-                    ));
-                }
-                if result.matches.len() > 2 {
-                    summary.push_str(&format!(
-                        "   ... and {} more matches\n",
-                        result.matches.len() - 2
-                    ));
-                }
-
-                if !result.fetch_hints.recommended_parts.is_empty() {
-                    summary.push_str(&format!(
-                        "   Recommended parts: {}\n",
-                        result.fetch_hints.recommended_parts.join(", ")
-                    ));
-                }
-                summary.push('\n');
-            }
-            if results.len() >= max_results {
-                summary.push_str("NOTE: Results may be truncated by maxResults. Increase maxResults to see more.\n");
-            }
-            // Lightweight next-call hints for AI clients (low token cost)
-            let hint_top = std::env::var("DAIZO_HINT_TOP")
-                .ok()
-                .and_then(|s| s.parse::<usize>().ok())
-                .unwrap_or(1);
-            let mut fetch_suggestions: Vec<serde_json::Value> = Vec::new();
-            for r in results.iter().take(hint_top) {
-                if let Some(m) = r.matches.first() {
-                    if let Some(lb) = cbeta_extract_lb_from_line(&m.context) {
-                        fetch_suggestions.push(json!({
-                            "tool": "cbeta_fetch",
-                            "args": {"id": r.file_id, "lb": lb, "contextBefore": 1, "contextAfter": 3, "highlight": hl_pat, "highlightRegex": hl_regex, "format": "plain"},
-                            "mode": "low-cost"
-                        }));
-                    } else if let Some(ln) = m.line_number {
-                        fetch_suggestions.push(json!({
-                            "tool": "cbeta_fetch",
-                            "args": {"id": r.file_id, "lineNumber": ln, "contextBefore": 1, "contextAfter": 3, "highlight": hl_pat, "highlightRegex": hl_regex, "format": "plain"},
-                            "mode": "low-cost"
-                        }));
-                    }
-                }
-            }
-
-            // Enrich structured results with CBETA lb (more stable than XML line numbers).
-            // Keep the original `results` structs for summary rendering; only mutate JSON for `_meta`.
-            let mut results_meta = serde_json::to_value(&results).unwrap_or(json!([]));
-            if let Some(arr) = results_meta.as_array_mut() {
-                for r in arr.iter_mut() {
-                    if let Some(ms) = r.get_mut("matches").and_then(|v| v.as_array_mut()) {
-                        for m in ms.iter_mut() {
-                            if m.get("lb").is_some() {
-                                continue;
-                            }
-                            let ctx = m.get("context").and_then(|v| v.as_str()).unwrap_or("");
-                            if let Some(lb) = cbeta_extract_lb_from_line(ctx) {
-                                m["lb"] = json!(lb);
-                            }
-                        }
-                    }
-                }
             }
-            let mut meta = json!({
-                "queryRaw": q_display,
-                "searchPattern": q,
-                "totalFiles": results.len(),
-                "results": results_meta,
-                "hint": "Use cbeta_fetch (id + lineNumber) for low-cost context; cbeta_pipeline with autoFetch=false to summarize",
-                "fetchSuggestions": fetch_suggestions,
-                "truncatedByMaxResults": results.len() >= max_results
-            });
-            // Optional pipeline hint (kept minimal)
-            meta["pipelineHint"] = json!({
-                "tool": "cbeta_pipeline",
-                "args": {"query": q_display, "autoFetch": false, "maxResults": 5, "maxMatchesPerFile": 1, "includeHighlightSnippet": false, "includeMatchLine": true }
+            let meta = json!({
+                "source": source,
+                "id": work_id,
+                "part": part,
+                "division": division,
+                "texts": stats.texts,
+                "totalChars": stats.total_chars,
+                "totalTokens": stats.total_tokens,
+                "distinctChars": stats.distinct_chars,
+                "distinctTokens": stats.distinct_tokens,
+                "topChars": stats.top_chars,
+                "topTokens": stats.top_tokens,
+                "topBigrams": stats.top_bigrams,
+                "topTrigrams": stats.top_trigrams,
+                "hapaxCount": stats.hapax_count,
+                "hapax": stats.hapax,
             });
-
             return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary}], "_meta": meta }});
         }
         "cbeta_pipeline" => {
@@ -4030,29 +9987,21 @@ Flow: jozen_search -> jozen_fetch (lineno)
                     p.to_string()
                 }
             });
-            let hl_pre = args
-                .get("highlightPrefix")
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string())
-                .or_else(|| std::env::var("DAIZO_HL_PREFIX").ok())
-                .unwrap_or_else(|| ">>> ".to_string());
-            let hl_suf = args
-                .get("highlightSuffix")
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string())
-                .or_else(|| std::env::var("DAIZO_HL_SUFFIX").ok())
-                .unwrap_or_else(|| " <<<".to_string());
+            let Highlighter {
+                prefix: hl_pre,
+                suffix: hl_suf,
+            } = highlighter(&args);
             let snip_pre = args
                 .get("snippetPrefix")
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_string())
-                .or_else(|| std::env::var("DAIZO_SNIPPET_PREFIX").ok())
+                .or_else(|| setting_str("DAIZO_SNIPPET_PREFIX"))
                 .unwrap_or_else(|| ">>> ".to_string());
             let snip_suf = args
                 .get("snippetSuffix")
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_string())
-                .or_else(|| std::env::var("DAIZO_SNIPPET_SUFFIX").ok())
+                .or_else(|| setting_str("DAIZO_SNIPPET_SUFFIX"))
                 .unwrap_or_else(String::new);
             let full = args.get("full").and_then(|v| v.as_bool()).unwrap_or(false);
             let include_notes = args
@@ -4060,8 +10009,11 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 .and_then(|v| v.as_bool())
                 .unwrap_or(false);
 
-            ensure_cbeta_data();
-            let results = cbeta_grep(&cbeta_root(), &q, max_results, max_matches_per_file);
+            if let Err(e) = require_corpus("cbeta") {
+                return e.response(&id);
+            }
+            let mut results = cbeta_grep(&cbeta_root(), &q, max_results, max_matches_per_file);
+            attribute_juans(&mut results);
 
             // Build summary and suggestions
             let mut summary = format!(
@@ -4080,18 +10032,24 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 summary.push_str(&format!("   {} matches\n", result.total_matches));
                 for (j, m) in result.matches.iter().enumerate().take(2) {
                     summary.push_str(&format!(
-                        "   Match {}: ...{}...\n",
+                        "   Match {}{}: ...{}...\n",
                         j + 1,
+                        cbeta_match_place(m),
                         m.context.chars().take(100).collect::<String>()
                     ));
                 }
                 if let Some(m) = result.matches.first() {
-                    if let Some(lb) = cbeta_extract_lb_from_line(&m.context) {
+                    if let Some(lb) =
+                        m.lb.clone()
+                            .or_else(|| cbeta_extract_lb_from_line(&m.context))
+                    {
                         let sug_hl = hl_pat.clone().unwrap_or_else(|| q.to_string());
                         let sug_hl_regex = if hl_pat.is_some() { hl_regex } else { true };
                         suggestions.push(json!({
                             "tool": "cbeta_fetch",
-                            "args": {"id": result.file_id, "lb": lb, "contextBefore": context_before, "contextAfter": context_after, "highlight": sug_hl, "highlightRegex": sug_hl_regex}
+                            "args": {"id": result.file_id, "lb": lb, "contextBefore": context_before, "contextAfter": context_after, "highlight": sug_hl, "highlightRegex": sug_hl_regex},
+                            "juan": m.juan_number,
+                            "section": m.section
                         }));
                     } else if let Some(ln) = m.line_number {
                         let sug_hl = hl_pat.clone().unwrap_or_else(|| q.to_string());
@@ -4103,7 +10061,9 @@ Flow: jozen_search -> jozen_fetch (lineno)
                         };
                         suggestions.push(json!({
                             "tool": "cbeta_fetch",
-                            "args": {"id": result.file_id, "lineNumber": ln, "contextBefore": context_before, "contextAfter": context_after, "highlight": sug_hl, "highlightRegex": sug_hl_regex}
+                            "args": {"id": result.file_id, "lineNumber": ln, "contextBefore": context_before, "contextAfter": context_after, "highlight": sug_hl, "highlightRegex": sug_hl_regex},
+                            "juan": m.juan_number,
+                            "section": m.section
                         }));
                     }
                 }
@@ -4276,6 +10236,9 @@ Flow: jozen_search -> jozen_fetch (lineno)
             return json!({"jsonrpc":"2.0","id": id, "result": { "content": content_items, "_meta": meta }});
         }
         "gretil_title_search" => {
+            if let Err(e) = require_corpus("gretil") {
+                return e.response(&id);
+            }
             let q = args
                 .get("query")
                 .and_then(|v| v.as_str())
@@ -4283,8 +10246,29 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 .trim()
                 .to_string();
             let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
+            let filter = match gretil_filter(&args) {
+                Ok(f) => f,
+                Err(e) => return e.response(&id),
+            };
             let idx = load_or_build_gretil_index();
-            let hits = best_match_gretil(idx, &q, limit);
+            let (scheme, variants) = if arg_transliterate(&args) {
+                sanskrit_scheme_variants(&q)
+            } else {
+                (detect_sanskrit_scheme(&q), vec![q.clone()])
+            };
+            // Score every spelling of the query and keep each entry's best score.
+            let mut hits: Vec<ScoredHit> = Vec::new();
+            for v in &variants {
+                for h in
+                    best_match_gretil_where(&idx, v, limit * RERANK_POOL, |e| filter.matches(e))
+                {
+                    match hits.iter_mut().find(|x| x.entry.path == h.entry.path) {
+                        Some(x) => x.score = x.score.max(h.score),
+                        None => hits.push(h),
+                    }
+                }
+            }
+            let hits = rerank(hits, &variants, limit);
             let summary = hits
                 .iter()
                 .enumerate()
@@ -4294,18 +10278,40 @@ Flow: jozen_search -> jozen_fetch (lineno)
             let results: Vec<_> = hits
                 .iter()
                 .map(|h| {
+                    let meta = h.entry.meta.as_ref();
+                    let field = |k: &str| meta.and_then(|m| m.get(k).cloned());
                     json!({
                         "id": h.entry.id,
                         "title": h.entry.title,
+                        "titles": daizo_core::titles::of_entry(h.entry),
                         "path": h.entry.path,
-                        "score": h.score
+                        "score": h.score,
+                        "matchReason": h.reason,
+                        "meta": {
+                            "author": field("author"),
+                            "genre": field("genre"),
+                            "language": field("language"),
+                            "source": field("source"),
+                            "creationDate": field("creationDate"),
+                        }
                     })
                 })
                 .collect();
-            let meta = json!({ "count": results.len(), "results": results });
+            let mut meta = json!({
+                "count": results.len(),
+                "results": results,
+                "scheme": scheme,
+                "schemeVariants": variants,
+            });
+            if !filter.is_empty() {
+                meta["filter"] = json!(filter);
+            }
             return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary }], "_meta": meta }});
         }
         "gretil_fetch" => {
+            if let Err(e) = require_corpus("gretil") {
+                return e.response(&id);
+            }
             let mut matched_id: Option<String> = None;
             let mut matched_title: Option<String> = None;
             let mut matched_score: Option<f32> = None;
@@ -4316,8 +10322,8 @@ Flow: jozen_search -> jozen_fetch (lineno)
                     path = p.clone();
                     matched_id = Some(id_str.to_string());
                     // タイトルはキャッシュがあれば取得（なくても問題なし）
-                    if let Some(idx) = GRETIL_INDEX_CACHE.get() {
-                        if let Some(e) = idx.iter().find(|e| Path::new(&e.path) == &p) {
+                    if let Some(idx) = GRETIL_INDEX_CACHE.loaded() {
+                        if let Some(e) = idx.iter().find(|e| Path::new(&e.path) == p) {
                             matched_title = Some(e.title.clone());
                         }
                     }
@@ -4336,131 +10342,105 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 }
             } else if let Some(q) = args.get("query").and_then(|v| v.as_str()) {
                 let idx = load_or_build_gretil_index();
-                if let Some(hit) = best_match_gretil(idx, q, 1).into_iter().next() {
+                if let Some(hit) = best_match_gretil(&idx, q, 1).into_iter().next() {
                     matched_title = Some(hit.entry.title.clone());
                     matched_score = Some(hit.score);
                     matched_id = Path::new(&hit.entry.path)
-                        .file_stem()
-                        .map(|s| s.to_string_lossy().into_owned());
-                    path = PathBuf::from(&hit.entry.path);
-                }
-            }
-            if path.as_os_str().is_empty() {
-                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "not found"}] }});
-            }
-            let xml = fs::read_to_string(&path).unwrap_or_default();
-            let include_notes = args
-                .get("includeNotes")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(false);
-            let (text, extraction_method) =
-                if let Some(line_num) = args.get("lineNumber").and_then(|v| v.as_u64()) {
-                    let before = args
-                        .get("contextBefore")
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(
-                            args.get("contextLines")
-                                .and_then(|v| v.as_u64())
-                                .unwrap_or(10),
-                        ) as usize;
-                    let after = args.get("contextAfter").and_then(|v| v.as_u64()).unwrap_or(
-                        args.get("contextLines")
-                            .and_then(|v| v.as_u64())
-                            .unwrap_or(100),
-                    ) as usize;
-                    let context_text = daizo_core::extract_xml_around_line_asymmetric(
-                        &xml,
-                        line_num as usize,
-                        before,
-                        after,
-                    );
-                    (
-                        context_text,
-                        format!("line-context-{}-{}-{}", line_num, before, after),
-                    )
-                } else if let Some(hq) = args.get("headQuery").and_then(|v| v.as_str()) {
-                    (
-                        extract_section_by_head(&xml, None, Some(hq), include_notes)
-                            .unwrap_or_else(|| extract_text_opts(&xml, include_notes)),
-                        "head-query".to_string(),
-                    )
-                } else if let Some(hi) = args.get("headIndex").and_then(|v| v.as_u64()) {
-                    (
-                        extract_section_by_head(&xml, Some(hi as usize), None, include_notes)
-                            .unwrap_or_else(|| extract_text_opts(&xml, include_notes)),
-                        "head-index".to_string(),
-                    )
-                } else {
-                    (extract_text_opts(&xml, include_notes), "full".to_string())
-                };
-            let full_flag = args.get("full").and_then(|v| v.as_bool()).unwrap_or(false);
-            let mut sliced = if full_flag {
-                text.clone()
-            } else {
-                slice_text(&text, &args)
-            };
-            let mut highlight_count = 0usize;
-            let mut highlight_positions: Vec<serde_json::Value> = Vec::new();
-            if let Some(hpat) = args.get("highlight").and_then(|v| v.as_str()) {
-                let use_re = args
-                    .get("highlightRegex")
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(false);
-                let hpre = args
-                    .get("highlightPrefix")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or(">>> ");
-                let hsuf = args
-                    .get("highlightSuffix")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or(" <<<");
-                let original = sliced.clone();
-                if use_re {
-                    if let Ok(re) = regex::Regex::new(hpat) {
-                        for m in re.find_iter(&original) {
-                            let sb = m.start();
-                            let eb = m.end();
-                            let sc = original[..sb].chars().count();
-                            let ec = sc + original[sb..eb].chars().count();
-                            highlight_positions.push(json!({"startChar": sc, "endChar": ec}));
-                        }
-                        let mut count = 0usize;
-                        let replaced = re.replace_all(&sliced, |caps: &regex::Captures| {
-                            count += 1;
-                            format!("{}{}{}", hpre, &caps[0], hsuf)
-                        });
-                        sliced = replaced.into_owned();
-                        highlight_count = count;
-                    }
-                } else if !hpat.is_empty() {
-                    let mut i = 0usize;
-                    while let Some(pos) = original[i..].find(hpat) {
-                        let abs = i + pos;
-                        let sc = original[..abs].chars().count();
-                        let ec = sc + hpat.chars().count();
-                        highlight_positions.push(json!({"startChar": sc, "endChar": ec}));
-                        i = abs + hpat.len();
-                    }
-                    let mut out = String::with_capacity(sliced.len());
-                    let mut j = 0usize;
-                    while let Some(pos) = sliced[j..].find(hpat) {
-                        let abs = j + pos;
-                        out.push_str(&sliced[j..abs]);
-                        out.push_str(hpre);
-                        out.push_str(hpat);
-                        out.push_str(hsuf);
-                        j = abs + hpat.len();
-                        highlight_count += 1;
-                    }
-                    out.push_str(&sliced[j..]);
-                    sliced = out;
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().into_owned());
+                    path = PathBuf::from(&hit.entry.path);
+                }
+            }
+            if path.as_os_str().is_empty() || !in_corpus("gretil", &path) {
+                return text_not_found("gretil", &args).response(&id);
+            }
+            note_hot_file(&path);
+            // Another encoding of the same work may be fuller; switch to it unless the caller
+            // asked for a specific file (explicit id) or a line of this file.
+            let prefer_richest = args
+                .get("preferRichest")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(args.get("id").is_none())
+                && args.get("lineNumber").is_none();
+            let dup_idx = if prefer_richest {
+                Some(load_or_build_gretil_index())
+            } else {
+                GRETIL_INDEX_CACHE.loaded()
+            };
+            let mut duplicates_meta: Option<serde_json::Value> = None;
+            if let Some((richest, members)) = dup_idx
+                .as_deref()
+                .and_then(|idx| gretil_duplicates(idx, &path))
+            {
+                let rich_path = PathBuf::from(&richest.path);
+                let switched_from = (prefer_richest
+                    && fs::canonicalize(&path).ok().as_ref() != Some(&rich_path))
+                .then(|| matched_id.clone());
+                if switched_from.is_some() {
+                    matched_id = rich_path
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().into_owned());
+                    matched_title = Some(richest.title.clone());
+                    path = rich_path;
                 }
+                duplicates_meta = Some(json!({
+                    "members": members,
+                    "richest": richest.id,
+                    "switchedFrom": switched_from.flatten(),
+                }));
             }
-            // cap output
-            let cap = default_max_chars();
-            if sliced.chars().count() > cap {
-                sliced = sliced.chars().take(cap).collect();
+            let xml = fs::read_to_string(&path).unwrap_or_default();
+            let include_notes = args
+                .get("includeNotes")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            let opts = fetch_options(&args);
+            let paged = match unit_page(&args, &xml, |frag| extract_text_opts(frag, include_notes))
+            {
+                Some(Ok(p)) => Some(p),
+                Some(Err(e)) => return e.response(&id),
+                None => None,
+            };
+            let page_by = paged.as_ref().map(|p| p.meta.clone());
+            let (text, extraction_method) = if let Some(p) = paged {
+                (p.text, p.method)
+            } else if let Some((line_num, before, after)) = opts.line_context() {
+                let context_text =
+                    daizo_core::extract_xml_around_line_asymmetric(&xml, line_num, before, after);
+                (
+                    context_text,
+                    format!("line-context-{}-{}-{}", line_num, before, after),
+                )
+            } else if let Some(hq) = args.get("headQuery").and_then(|v| v.as_str()) {
+                (
+                    extract_section_by_head(&xml, None, Some(hq), include_notes)
+                        .unwrap_or_else(|| extract_text_opts(&xml, include_notes)),
+                    "head-query".to_string(),
+                )
+            } else if let Some(hi) = args.get("headIndex").and_then(|v| v.as_u64()) {
+                (
+                    extract_section_by_head(&xml, Some(hi as usize), None, include_notes)
+                        .unwrap_or_else(|| extract_text_opts(&xml, include_notes)),
+                    "head-index".to_string(),
+                )
+            } else {
+                (extract_text_opts(&xml, include_notes), "full".to_string())
+            };
+            let Fetched {
+                span,
+                text: sliced,
+                highlights,
+                by_pattern,
+            } = apply_fetch_pipeline(&text, &opts, &highlighter(&args));
+            if let Some(resp) =
+                shaping::overflow_outline(&id, "gretil_fetch", &args, &text, &span, || {
+                    list_heads_generic(&xml)
+                })
+            {
+                return resp;
             }
+            let highlight_count = highlights.len();
+            let highlight_positions = highlight_positions(&args, &highlights, &by_pattern);
             let heads = list_heads_generic(&xml);
             let hl = args
                 .get("headingsLimit")
@@ -4468,28 +10448,45 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 .unwrap_or(10) as usize;
             let meta = json!({
                 "totalLength": text.len(),
-                "returnedStart": args.get("startChar").and_then(|v| v.as_u64()).unwrap_or( args.get("page").and_then(|v| v.as_u64()).and_then(|p| args.get("pageSize").and_then(|s| s.as_u64()).map(|ps| p*ps)).unwrap_or(0) ),
-                "returnedEnd": args.get("startChar").and_then(|v| v.as_u64()).unwrap_or( args.get("page").and_then(|v| v.as_u64()).and_then(|p| args.get("pageSize").and_then(|s| s.as_u64()).map(|ps| p*ps)).unwrap_or(0) ) + (sliced.len() as u64),
-                "truncated": if full_flag { false } else { (sliced.len() as u64) < (text.len() as u64) },
+                "totalChars": span.total,
+                "returnedStart": span.start,
+                "returnedEnd": span.end,
+                "truncated": span.end < span.total,
+                "nextCursor": next_cursor("gretil_fetch", &args, span.end, span.total),
+                "nextLine": span.next_line,
+                "lineMap": line_map(&text, span.start, span.end),
                 "sourcePath": path.to_string_lossy(),
                 "extractionMethod": extraction_method,
+                "pageBy": page_by,
+                "structure": opts.line_number.and_then(|l| JuanMap::build(&xml).context_at_line(l)),
                 "headingsTotal": heads.len(),
                 "headingsPreview": heads.into_iter().take(hl).collect::<Vec<_>>(),
                 "matchedId": matched_id,
                 "matchedTitle": matched_title,
                 "matchedScore": matched_score,
+                "duplicates": duplicates_meta,
                 "highlighted": if highlight_count > 0 { Some(highlight_count) } else { None::<usize> },
                 "highlightPositions": if highlight_positions.is_empty() { None::<Vec<serde_json::Value>> } else { Some(highlight_positions) },
             });
             return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": sliced}], "_meta": meta }});
         }
         "gretil_search" => {
+            if let Err(e) = require_corpus("gretil") {
+                return e.response(&id);
+            }
             let q_raw = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
+            let multi = match multi_query("gretil", &args) {
+                Ok(m) => m,
+                Err(e) => return e.response(&id),
+            };
+            let multi_display = multi.as_ref().map(|m| m.queries.join(" | "));
+            let q_raw = multi_display.as_deref().unwrap_or(q_raw);
             let looks_like_regex = q_raw.chars().any(|c| ".+*?[](){}|\\".contains(c));
-            let q = if q_raw.chars().any(|c| c.is_whitespace()) && !looks_like_regex {
-                to_whitespace_fuzzy_literal(q_raw)
-            } else {
-                q_raw.to_string()
+            let (scheme, variants) = sanskrit_scheme_variants(q_raw);
+            let use_variants = !looks_like_regex && arg_transliterate(&args) && variants.len() > 1;
+            let q = match &multi {
+                Some(m) => m.pattern(),
+                None => rewrite_query("gretil", q_raw, &args).pattern,
             };
             let max_results = args
                 .get("maxResults")
@@ -4499,7 +10496,52 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 .get("maxMatchesPerFile")
                 .and_then(|v| v.as_u64())
                 .unwrap_or(5) as usize;
-            let results = gretil_grep(&gretil_root(), &q, max_results, max_matches_per_file);
+            let scope = match search_scope("gretil", &args) {
+                Ok(s) => s,
+                Err(e) => return e.response(&id),
+            };
+            let (results, q) = if query_mode_boolean(&args) {
+                match boolean_grep(
+                    "gretil",
+                    q_raw,
+                    &args,
+                    scope.as_ref(),
+                    max_results,
+                    max_matches_per_file,
+                ) {
+                    Ok(v) => v,
+                    Err(e) => return boolean_query_error(&id, q_raw, &e),
+                }
+            } else {
+                (
+                    corpus_grep(
+                        "gretil",
+                        scope.as_ref(),
+                        &q,
+                        max_results,
+                        max_matches_per_file,
+                    ),
+                    q,
+                )
+            };
+            let mut results = with_index_sizes("gretil", results);
+            if let Some(m) = &multi {
+                m.set.tag(&mut results);
+            }
+            filter_text_only(&mut results, &q, &args);
+            merge_snippets(&mut results, &q, &args);
+            sort_results("gretil", &mut results, &args);
+            register_match_ids("gretil", &mut results);
+            let group_dups = args
+                .get("groupDuplicates")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true);
+            let (results, duplicates) = if group_dups && !results.is_empty() {
+                group_gretil_results(results, &load_or_build_gretil_index())
+            } else {
+                let n = results.len();
+                (results, vec![Vec::new(); n])
+            };
             let mut summary = format!(
                 "Found {} files with matches for '{}':\n\n",
                 results.len(),
@@ -4513,18 +10555,26 @@ Flow: jozen_search -> jozen_fetch (lineno)
                     result.file_id
                 ));
                 summary.push_str(&format!(
-                    "   {} matches, {}\n",
+                    "   {} matches, {}{}\n",
                     result.total_matches,
                     result
                         .fetch_hints
                         .total_content_size
                         .as_deref()
-                        .unwrap_or("unknown size")
+                        .unwrap_or("unknown size"),
+                    result
+                        .score
+                        .map(|s| format!(", relevance {:.2}", s))
+                        .unwrap_or_default()
                 ));
+                if let Some(m) = &multi {
+                    summary.push_str(&m.summary_line(result));
+                }
                 for (j, m) in result.matches.iter().enumerate().take(2) {
                     summary.push_str(&format!(
-                        "   Match {}: ...{}...\n",
+                        "   Match {}{}: ...{}...\n",
                         j + 1,
+                        match_id_tag(m),
                         m.context.chars().take(100).collect::<String>()
                     ));
                 }
@@ -4534,6 +10584,9 @@ Flow: jozen_search -> jozen_fetch (lineno)
                         result.matches.len() - 2
                     ));
                 }
+                if !duplicates[i].is_empty() {
+                    summary.push_str(&format!("   Also in: {}\n", duplicates[i].join(", ")));
+                }
                 summary.push('\n');
             }
             // Lightweight next-call hints (low token) for GRETIL
@@ -4546,21 +10599,37 @@ Flow: jozen_search -> jozen_fetch (lineno)
             for r in results.iter().take(hint_top) {
                 if let Some(m) = r.matches.first() {
                     if let Some(ln) = m.line_number {
+                        let w = daizo_core::window::suggest_for_file(Path::new(&r.file_path), ln);
                         fetch_suggestions.push(json!({
                         "tool": "gretil_fetch",
-                        "args": {"id": r.file_id, "lineNumber": ln, "contextBefore": 1, "contextAfter": 3, "highlight": q, "highlightRegex": hl_regex},
-                        "mode": "low-cost"
+                        "args": {"id": r.file_id, "lineNumber": ln, "contextBefore": w.before, "contextAfter": w.after, "highlight": q, "highlightRegex": hl_regex},
+                        "mode": "low-cost",
+                        "unit": w.unit
                     }));
                     }
                 }
             }
+            let mut results_meta = serde_json::to_value(&results).unwrap_or(json!([]));
+            if let Some(arr) = results_meta.as_array_mut() {
+                for (r, d) in arr.iter_mut().zip(&duplicates) {
+                    if !d.is_empty() {
+                        r["duplicates"] = json!(d);
+                    }
+                }
+            }
             let mut meta = json!({
                 "searchPattern": q,
                 "totalFiles": results.len(),
-                "results": results,
+                "results": results_meta,
                 "hint": "Use gretil_fetch (id + lineNumber) for low-cost context; gretil_pipeline with autoFetch=false to summarize",
-                "fetchSuggestions": fetch_suggestions
+                "fetchSuggestions": fetch_suggestions,
+                "scheme": scheme,
+                "schemeVariants": if use_variants { Some(&variants) } else { None },
             });
+            scope_meta(&mut meta, scope.as_ref());
+            if let Some(m) = &multi {
+                m.annotate_meta(&mut meta, &results);
+            }
             meta["pipelineHint"] = json!({
                 "tool": "gretil_pipeline",
                 "args": {"query": q, "autoFetch": false, "maxResults": 5, "maxMatchesPerFile": 1, "includeMatchLine": true }
@@ -4568,6 +10637,9 @@ Flow: jozen_search -> jozen_fetch (lineno)
             return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary}], "_meta": meta }});
         }
         "gretil_pipeline" => {
+            if let Err(e) = require_corpus("gretil") {
+                return e.response(&id);
+            }
             let q_raw = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
             let looks_like_regex = q_raw.chars().any(|c| ".+*?[](){}|\\".contains(c));
             let q = if q_raw.chars().any(|c| c.is_whitespace()) && !looks_like_regex {
@@ -4622,29 +10694,21 @@ Flow: jozen_search -> jozen_fetch (lineno)
                     .unwrap_or(1) as usize;
                 let tf = tf.min(results.len());
                 let mut fetched: Vec<serde_json::Value> = Vec::new();
-                let hl_pre = args
-                    .get("highlightPrefix")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .or_else(|| std::env::var("DAIZO_HL_PREFIX").ok())
-                    .unwrap_or_else(|| ">>> ".to_string());
-                let hl_suf = args
-                    .get("highlightSuffix")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .or_else(|| std::env::var("DAIZO_HL_SUFFIX").ok())
-                    .unwrap_or_else(|| " <<<".to_string());
+                let Highlighter {
+                    prefix: hl_pre,
+                    suffix: hl_suf,
+                } = highlighter(&args);
                 let sn_pre = args
                     .get("snippetPrefix")
                     .and_then(|v| v.as_str())
                     .map(|s| s.to_string())
-                    .or_else(|| std::env::var("DAIZO_SNIPPET_PREFIX").ok())
+                    .or_else(|| setting_str("DAIZO_SNIPPET_PREFIX"))
                     .unwrap_or_else(|| ">>> ".to_string());
                 let sn_suf = args
                     .get("snippetSuffix")
                     .and_then(|v| v.as_str())
                     .map(|s| s.to_string())
-                    .or_else(|| std::env::var("DAIZO_SNIPPET_SUFFIX").ok())
+                    .or_else(|| setting_str("DAIZO_SNIPPET_SUFFIX"))
                     .unwrap_or_else(|| "".to_string());
                 let mut file_highlights_all: Vec<Vec<serde_json::Value>> = Vec::new();
                 for r in results.iter().take(tf) {
@@ -4801,7 +10865,11 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 .to_string();
             let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
             let idx = load_or_build_sarit_index();
-            let hits = best_match_sarit(idx, &q, limit);
+            let hits = rerank(
+                best_match_sarit(&idx, &q, limit * RERANK_POOL),
+                std::slice::from_ref(&q),
+                limit,
+            );
             let summary = hits
                 .iter()
                 .enumerate()
@@ -4814,8 +10882,10 @@ Flow: jozen_search -> jozen_fetch (lineno)
                     json!({
                         "id": h.entry.id,
                         "title": h.entry.title,
+                        "titles": daizo_core::titles::of_entry(h.entry),
                         "path": h.entry.path,
                         "score": h.score,
+                        "matchReason": h.reason,
                         "meta": h.entry.meta
                     })
                 })
@@ -4824,7 +10894,9 @@ Flow: jozen_search -> jozen_fetch (lineno)
             return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary }], "_meta": meta }});
         }
         "sarit_fetch" => {
-            ensure_sarit_data();
+            if let Err(e) = require_corpus("sarit") {
+                return e.response(&id);
+            }
             let mut matched_id: Option<String> = None;
             let mut matched_title: Option<String> = None;
             let mut matched_score: Option<f32> = None;
@@ -4834,8 +10906,8 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 if let Some(p) = resolve_sarit_path_direct(id_str) {
                     path = p.clone();
                     matched_id = Some(id_str.to_string());
-                    if let Some(idx) = SARIT_INDEX_CACHE.get() {
-                        if let Some(e) = idx.iter().find(|e| Path::new(&e.path) == &p) {
+                    if let Some(idx) = SARIT_INDEX_CACHE.loaded() {
+                        if let Some(e) = idx.iter().find(|e| Path::new(&e.path) == p) {
                             matched_title = Some(e.title.clone());
                         }
                     }
@@ -4848,7 +10920,7 @@ Flow: jozen_search -> jozen_fetch (lineno)
                                 .map(|s| s.to_string_lossy().into_owned())
                                 .unwrap_or_else(|| id_str.to_string()),
                         );
-                        if let Some(e) = idx.iter().find(|e| Path::new(&e.path) == &p) {
+                        if let Some(e) = idx.iter().find(|e| Path::new(&e.path) == p) {
                             matched_title = Some(e.title.clone());
                         }
                         path = p;
@@ -4856,7 +10928,7 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 }
             } else if let Some(q) = args.get("query").and_then(|v| v.as_str()) {
                 let idx = load_or_build_sarit_index();
-                if let Some(hit) = best_match_sarit(idx, q, 1).into_iter().next() {
+                if let Some(hit) = best_match_sarit(&idx, q, 1).into_iter().next() {
                     matched_title = Some(hit.entry.title.clone());
                     matched_score = Some(hit.score);
                     matched_id = Some(hit.entry.id.clone());
@@ -4864,105 +10936,64 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 }
             }
 
-            if path.as_os_str().is_empty() {
-                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "not found"}] }});
+            if path.as_os_str().is_empty() || !in_corpus("sarit", &path) {
+                return text_not_found("sarit", &args).response(&id);
             }
+            note_hot_file(&path);
 
             let xml = fs::read_to_string(&path).unwrap_or_default();
             let include_notes = args
                 .get("includeNotes")
                 .and_then(|v| v.as_bool())
                 .unwrap_or(false);
-            let (text, extraction_method) =
-                if let Some(line_num) = args.get("lineNumber").and_then(|v| v.as_u64()) {
-                    let before = args
-                        .get("contextBefore")
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(
-                            args.get("contextLines")
-                                .and_then(|v| v.as_u64())
-                                .unwrap_or(10),
-                        ) as usize;
-                    let after = args.get("contextAfter").and_then(|v| v.as_u64()).unwrap_or(
-                        args.get("contextLines")
-                            .and_then(|v| v.as_u64())
-                            .unwrap_or(100),
-                    ) as usize;
-                    let context_text = daizo_core::extract_xml_around_line_asymmetric(
-                        &xml,
-                        line_num as usize,
-                        before,
-                        after,
-                    );
-                    (
-                        context_text,
-                        format!("line-context-{}-{}-{}", line_num, before, after),
-                    )
-                } else if let Some(hq) = args.get("headQuery").and_then(|v| v.as_str()) {
-                    (
-                        extract_section_by_head(&xml, None, Some(hq), include_notes)
-                            .unwrap_or_else(|| extract_text_opts(&xml, include_notes)),
-                        "head-query".to_string(),
-                    )
-                } else if let Some(hi) = args.get("headIndex").and_then(|v| v.as_u64()) {
-                    (
-                        extract_section_by_head(&xml, Some(hi as usize), None, include_notes)
-                            .unwrap_or_else(|| extract_text_opts(&xml, include_notes)),
-                        "head-index".to_string(),
-                    )
-                } else {
-                    (extract_text_opts(&xml, include_notes), "full".to_string())
-                };
-
-            let full_flag = args.get("full").and_then(|v| v.as_bool()).unwrap_or(false);
-            let mut sliced = if full_flag {
-                text.clone()
+            let opts = fetch_options(&args);
+            let paged = match unit_page(&args, &xml, |frag| extract_text_opts(frag, include_notes))
+            {
+                Some(Ok(p)) => Some(p),
+                Some(Err(e)) => return e.response(&id),
+                None => None,
+            };
+            let page_by = paged.as_ref().map(|p| p.meta.clone());
+            let (text, extraction_method) = if let Some(p) = paged {
+                (p.text, p.method)
+            } else if let Some((line_num, before, after)) = opts.line_context() {
+                let context_text =
+                    daizo_core::extract_xml_around_line_asymmetric(&xml, line_num, before, after);
+                (
+                    context_text,
+                    format!("line-context-{}-{}-{}", line_num, before, after),
+                )
+            } else if let Some(hq) = args.get("headQuery").and_then(|v| v.as_str()) {
+                (
+                    extract_section_by_head(&xml, None, Some(hq), include_notes)
+                        .unwrap_or_else(|| extract_text_opts(&xml, include_notes)),
+                    "head-query".to_string(),
+                )
+            } else if let Some(hi) = args.get("headIndex").and_then(|v| v.as_u64()) {
+                (
+                    extract_section_by_head(&xml, Some(hi as usize), None, include_notes)
+                        .unwrap_or_else(|| extract_text_opts(&xml, include_notes)),
+                    "head-index".to_string(),
+                )
             } else {
-                slice_text(&text, &args)
+                (extract_text_opts(&xml, include_notes), "full".to_string())
             };
 
-            let mut highlight_count = 0usize;
-            let mut highlight_positions: Vec<serde_json::Value> = Vec::new();
-            if let Some(hpat) = args.get("highlight").and_then(|v| v.as_str()) {
-                let use_re = args
-                    .get("highlightRegex")
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(false);
-                let hpre = args
-                    .get("highlightPrefix")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or(">>> ");
-                let hsuf = args
-                    .get("highlightSuffix")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or(" <<<");
-                let original = sliced.clone();
-                if use_re {
-                    if let Ok(re) = regex::Regex::new(hpat) {
-                        for m in re.find_iter(&original) {
-                            let sb = m.start();
-                            let eb = m.end();
-                            let sc = original[..sb].chars().count();
-                            let ec = sc + original[sb..eb].chars().count();
-                            highlight_positions.push(json!({"startChar": sc, "endChar": ec}));
-                        }
-                        let rep = re.replace_all(&sliced, |caps: &regex::Captures| {
-                            highlight_count += 1;
-                            format!("{}{}{}", hpre, &caps[0], hsuf)
-                        });
-                        sliced = rep.into_owned();
-                    }
-                } else if !hpat.is_empty() {
-                    let (decorated, count, positions) =
-                        daizo_core::text_utils::highlight_text(&sliced, hpat, false, hpre, hsuf);
-                    sliced = decorated;
-                    highlight_count = count;
-                    highlight_positions = positions
-                        .into_iter()
-                        .map(|p| json!({"startChar": p.start_char, "endChar": p.end_char}))
-                        .collect();
-                }
+            let Fetched {
+                span,
+                text: sliced,
+                highlights,
+                by_pattern,
+            } = apply_fetch_pipeline(&text, &opts, &highlighter(&args));
+            if let Some(resp) =
+                shaping::overflow_outline(&id, "sarit_fetch", &args, &text, &span, || {
+                    list_heads_generic(&xml)
+                })
+            {
+                return resp;
             }
+            let highlight_count = highlights.len();
+            let highlight_positions = highlight_positions(&args, &highlights, &by_pattern);
 
             let heads = list_heads_generic(&xml);
             let headings_limit = args
@@ -4971,10 +11002,17 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 .unwrap_or(20) as usize;
             let meta = json!({
                 "totalLength": text.chars().count(),
-                "returnedStart": args.get("startChar").and_then(|v| v.as_u64()).unwrap_or(0),
-                "returnedEnd": args.get("endChar").and_then(|v| v.as_u64()).unwrap_or(sliced.chars().count() as u64),
+                "totalChars": span.total,
+                "returnedStart": span.start,
+                "returnedEnd": span.end,
+                "truncated": span.end < span.total,
+                "nextCursor": next_cursor("sarit_fetch", &args, span.end, span.total),
+                "nextLine": span.next_line,
+                "lineMap": line_map(&text, span.start, span.end),
                 "sourcePath": path.to_string_lossy(),
                 "extractionMethod": extraction_method,
+                "pageBy": page_by,
+                "structure": opts.line_number.and_then(|l| JuanMap::build(&xml).context_at_line(l)),
                 "headingsTotal": heads.len(),
                 "headingsPreview": heads.into_iter().take(headings_limit).collect::<Vec<_>>(),
                 "matchedId": matched_id,
@@ -4987,11 +11025,16 @@ Flow: jozen_search -> jozen_fetch (lineno)
         }
         "sarit_search" => {
             let q_raw = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
+            let multi = match multi_query("sarit", &args) {
+                Ok(m) => m,
+                Err(e) => return e.response(&id),
+            };
+            let multi_display = multi.as_ref().map(|m| m.queries.join(" | "));
+            let q_raw = multi_display.as_deref().unwrap_or(q_raw);
             let looks_like_regex = q_raw.chars().any(|c| ".+*?[](){}|\\".contains(c));
-            let q = if q_raw.chars().any(|c| c.is_whitespace()) && !looks_like_regex {
-                to_whitespace_fuzzy_literal(q_raw)
-            } else {
-                q_raw.to_string()
+            let q = match &multi {
+                Some(m) => m.pattern(),
+                None => rewrite_query("sarit", q_raw, &args).pattern,
             };
             let max_results = args
                 .get("maxResults")
@@ -5002,8 +11045,45 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 .and_then(|v| v.as_u64())
                 .unwrap_or(5) as usize;
 
-            ensure_sarit_data();
-            let results = sarit_grep(&sarit_root(), &q, max_results, max_matches_per_file);
+            if let Err(e) = require_corpus("sarit") {
+                return e.response(&id);
+            }
+            let scope = match search_scope("sarit", &args) {
+                Ok(s) => s,
+                Err(e) => return e.response(&id),
+            };
+            let (results, q) = if query_mode_boolean(&args) {
+                match boolean_grep(
+                    "sarit",
+                    q_raw,
+                    &args,
+                    scope.as_ref(),
+                    max_results,
+                    max_matches_per_file,
+                ) {
+                    Ok(v) => v,
+                    Err(e) => return boolean_query_error(&id, q_raw, &e),
+                }
+            } else {
+                (
+                    corpus_grep(
+                        "sarit",
+                        scope.as_ref(),
+                        &q,
+                        max_results,
+                        max_matches_per_file,
+                    ),
+                    q,
+                )
+            };
+            let mut results = with_index_sizes("sarit", results);
+            if let Some(m) = &multi {
+                m.set.tag(&mut results);
+            }
+            filter_text_only(&mut results, &q, &args);
+            merge_snippets(&mut results, &q, &args);
+            sort_results("sarit", &mut results, &args);
+            register_match_ids("sarit", &mut results);
 
             let mut summary = format!(
                 "Found {} files with matches for '{}':\n\n",
@@ -5018,18 +11098,26 @@ Flow: jozen_search -> jozen_fetch (lineno)
                     result.file_id
                 ));
                 summary.push_str(&format!(
-                    "   {} matches, {}\n",
+                    "   {} matches, {}{}\n",
                     result.total_matches,
                     result
                         .fetch_hints
                         .total_content_size
                         .as_deref()
-                        .unwrap_or("unknown size")
+                        .unwrap_or("unknown size"),
+                    result
+                        .score
+                        .map(|s| format!(", relevance {:.2}", s))
+                        .unwrap_or_default()
                 ));
+                if let Some(m) = &multi {
+                    summary.push_str(&m.summary_line(result));
+                }
                 for (j, m) in result.matches.iter().enumerate().take(2) {
                     summary.push_str(&format!(
-                        "   Match {}: ...{}...\n",
+                        "   Match {}{}: ...{}...\n",
                         j + 1,
+                        match_id_tag(m),
                         m.context.chars().take(100).collect::<String>()
                     ));
                 }
@@ -5052,10 +11140,12 @@ Flow: jozen_search -> jozen_fetch (lineno)
             for r in results.iter().take(hint_top) {
                 if let Some(m) = r.matches.first() {
                     if let Some(ln) = m.line_number {
+                        let w = daizo_core::window::suggest_for_file(Path::new(&r.file_path), ln);
                         fetch_suggestions.push(json!({
                             "tool": "sarit_fetch",
-                            "args": {"id": r.file_id, "lineNumber": ln, "contextBefore": 1, "contextAfter": 3, "highlight": q, "highlightRegex": hl_regex},
-                            "mode": "low-cost"
+                            "args": {"id": r.file_id, "lineNumber": ln, "contextBefore": w.before, "contextAfter": w.after, "highlight": q, "highlightRegex": hl_regex},
+                            "mode": "low-cost",
+                            "unit": w.unit
                         }));
                     }
                 }
@@ -5067,6 +11157,10 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 "hint": "Use sarit_fetch (id + lineNumber) for low-cost context; sarit_pipeline with autoFetch=false to summarize",
                 "fetchSuggestions": fetch_suggestions
             });
+            scope_meta(&mut meta, scope.as_ref());
+            if let Some(m) = &multi {
+                m.annotate_meta(&mut meta, &results);
+            }
             meta["pipelineHint"] = json!({
                 "tool": "sarit_pipeline",
                 "args": {"query": q, "autoFetch": false, "maxResults": 5, "maxMatchesPerFile": 1, "includeMatchLine": true }
@@ -5101,7 +11195,9 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 .get("includeMatchLine")
                 .and_then(|v| v.as_bool())
                 .unwrap_or(true);
-            ensure_sarit_data();
+            if let Err(e) = require_corpus("sarit") {
+                return e.response(&id);
+            }
             let results = sarit_grep(&sarit_root(), &q, max_results, max_matches_per_file);
             let mut content_items: Vec<serde_json::Value> = Vec::new();
             let mut meta =
@@ -5129,29 +11225,21 @@ Flow: jozen_search -> jozen_fetch (lineno)
                     .unwrap_or(1) as usize;
                 let tf = tf.min(results.len());
                 let mut fetched: Vec<serde_json::Value> = Vec::new();
-                let hl_pre = args
-                    .get("highlightPrefix")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .or_else(|| std::env::var("DAIZO_HL_PREFIX").ok())
-                    .unwrap_or_else(|| ">>> ".to_string());
-                let hl_suf = args
-                    .get("highlightSuffix")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .or_else(|| std::env::var("DAIZO_HL_SUFFIX").ok())
-                    .unwrap_or_else(|| " <<<".to_string());
+                let Highlighter {
+                    prefix: hl_pre,
+                    suffix: hl_suf,
+                } = highlighter(&args);
                 let sn_pre = args
                     .get("snippetPrefix")
                     .and_then(|v| v.as_str())
                     .map(|s| s.to_string())
-                    .or_else(|| std::env::var("DAIZO_SNIPPET_PREFIX").ok())
+                    .or_else(|| setting_str("DAIZO_SNIPPET_PREFIX"))
                     .unwrap_or_else(|| ">>> ".to_string());
                 let sn_suf = args
                     .get("snippetSuffix")
                     .and_then(|v| v.as_str())
                     .map(|s| s.to_string())
-                    .or_else(|| std::env::var("DAIZO_SNIPPET_SUFFIX").ok())
+                    .or_else(|| setting_str("DAIZO_SNIPPET_SUFFIX"))
                     .unwrap_or_else(|| "".to_string());
                 for r in results.iter().take(tf) {
                     let per_file_limit = args
@@ -5297,7 +11385,9 @@ Flow: jozen_search -> jozen_fetch (lineno)
             return json!({"jsonrpc":"2.0","id": id, "result": { "content": content_items, "_meta": meta }});
         }
         "muktabodha_title_search" => {
-            ensure_muktabodha_dir();
+            if let Err(e) = require_corpus("muktabodha") {
+                return e.response(&id);
+            }
             let q = args
                 .get("query")
                 .and_then(|v| v.as_str())
@@ -5306,7 +11396,11 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 .to_string();
             let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
             let idx = load_or_build_muktabodha_index();
-            let hits = best_match_muktabodha(idx, &q, limit);
+            let hits = rerank(
+                best_match_muktabodha(&idx, &q, limit * RERANK_POOL),
+                std::slice::from_ref(&q),
+                limit,
+            );
             let summary = hits
                 .iter()
                 .enumerate()
@@ -5319,8 +11413,10 @@ Flow: jozen_search -> jozen_fetch (lineno)
                     json!({
                         "id": h.entry.id,
                         "title": h.entry.title,
+                        "titles": daizo_core::titles::of_entry(h.entry),
                         "path": h.entry.path,
                         "score": h.score,
+                        "matchReason": h.reason,
                         "meta": h.entry.meta
                     })
                 })
@@ -5329,7 +11425,9 @@ Flow: jozen_search -> jozen_fetch (lineno)
             return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary }], "_meta": meta }});
         }
         "muktabodha_fetch" => {
-            ensure_muktabodha_dir();
+            if let Err(e) = require_corpus("muktabodha") {
+                return e.response(&id);
+            }
             let mut matched_id: Option<String> = None;
             let mut matched_title: Option<String> = None;
             let mut matched_score: Option<f32> = None;
@@ -5339,8 +11437,8 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 if let Some(p) = resolve_muktabodha_path_direct(id_str) {
                     path = p.clone();
                     matched_id = Some(id_str.to_string());
-                    if let Some(idx) = MUKTABODHA_INDEX_CACHE.get() {
-                        if let Some(e) = idx.iter().find(|e| Path::new(&e.path) == &p) {
+                    if let Some(idx) = MUKTABODHA_INDEX_CACHE.loaded() {
+                        if let Some(e) = idx.iter().find(|e| Path::new(&e.path) == p) {
                             matched_title = Some(e.title.clone());
                         }
                     }
@@ -5353,7 +11451,7 @@ Flow: jozen_search -> jozen_fetch (lineno)
                                 .map(|s| s.to_string_lossy().into_owned())
                                 .unwrap_or_else(|| id_str.to_string()),
                         );
-                        if let Some(e) = idx.iter().find(|e| Path::new(&e.path) == &p) {
+                        if let Some(e) = idx.iter().find(|e| Path::new(&e.path) == p) {
                             matched_title = Some(e.title.clone());
                         }
                         path = p;
@@ -5361,7 +11459,7 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 }
             } else if let Some(q) = args.get("query").and_then(|v| v.as_str()) {
                 let idx = load_or_build_muktabodha_index();
-                if let Some(hit) = best_match_muktabodha(idx, q, 1).into_iter().next() {
+                if let Some(hit) = best_match_muktabodha(&idx, q, 1).into_iter().next() {
                     matched_title = Some(hit.entry.title.clone());
                     matched_score = Some(hit.score);
                     matched_id = Some(hit.entry.id.clone());
@@ -5369,9 +11467,10 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 }
             }
 
-            if path.as_os_str().is_empty() {
-                return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "not found"}] }});
+            if path.as_os_str().is_empty() || !in_corpus("muktabodha", &path) {
+                return text_not_found("muktabodha", &args).response(&id);
             }
+            note_hot_file(&path);
 
             let bytes = fs::read(&path).unwrap_or_default();
             let xml = decode_xml_bytes(&bytes);
@@ -5385,71 +11484,47 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 .map(|s| s.eq_ignore_ascii_case("xml"))
                 .unwrap_or(false);
 
-            let (text, extraction_method) =
-                if let Some(line_num) = args.get("lineNumber").and_then(|v| v.as_u64()) {
-                    let before = args
-                        .get("contextBefore")
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(
-                            args.get("contextLines")
-                                .and_then(|v| v.as_u64())
-                                .unwrap_or(10),
-                        ) as usize;
-                    let after = args.get("contextAfter").and_then(|v| v.as_u64()).unwrap_or(
-                        args.get("contextLines")
-                            .and_then(|v| v.as_u64())
-                            .unwrap_or(100),
-                    ) as usize;
-                    let context_text = daizo_core::extract_xml_around_line_asymmetric(
-                        &xml,
-                        line_num as usize,
-                        before,
-                        after,
-                    );
-                    (
-                        context_text,
-                        format!("line-context-{}-{}-{}", line_num, before, after),
-                    )
-                } else if is_xml {
-                    (
-                        extract_text_opts(&xml, include_notes),
-                        "full-xml".to_string(),
-                    )
-                } else {
-                    (xml.clone(), "full-txt".to_string())
-                };
-
-            let full_flag = args.get("full").and_then(|v| v.as_bool()).unwrap_or(false);
-            let mut sliced = if full_flag {
-                text.clone()
+            let opts = fetch_options(&args);
+            let paged = match unit_page(&args, &xml, |frag| extract_text_opts(frag, include_notes))
+            {
+                Some(Ok(p)) => Some(p),
+                Some(Err(e)) => return e.response(&id),
+                None => None,
+            };
+            let page_by = paged.as_ref().map(|p| p.meta.clone());
+            let (text, extraction_method) = if let Some(p) = paged {
+                (p.text, p.method)
+            } else if let Some((line_num, before, after)) = opts.line_context() {
+                let context_text =
+                    daizo_core::extract_xml_around_line_asymmetric(&xml, line_num, before, after);
+                (
+                    context_text,
+                    format!("line-context-{}-{}-{}", line_num, before, after),
+                )
+            } else if is_xml {
+                (
+                    extract_text_opts(&xml, include_notes),
+                    "full-xml".to_string(),
+                )
             } else {
-                slice_text(&text, &args)
+                (xml.clone(), "full-txt".to_string())
             };
 
-            let mut highlight_count = 0usize;
-            let mut highlight_positions: Vec<serde_json::Value> = Vec::new();
-            if let Some(hpat) = args.get("highlight").and_then(|v| v.as_str()) {
-                let use_re = args
-                    .get("highlightRegex")
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(false);
-                let hpre = args
-                    .get("highlightPrefix")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or(">>> ");
-                let hsuf = args
-                    .get("highlightSuffix")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or(" <<<");
-                let (decorated, count, positions) =
-                    daizo_core::text_utils::highlight_text(&sliced, hpat, use_re, hpre, hsuf);
-                sliced = decorated;
-                highlight_count = count;
-                highlight_positions = positions
-                    .into_iter()
-                    .map(|p| json!({"startChar": p.start_char, "endChar": p.end_char}))
-                    .collect();
+            let Fetched {
+                span,
+                text: sliced,
+                highlights,
+                by_pattern,
+            } = apply_fetch_pipeline(&text, &opts, &highlighter(&args));
+            if let Some(resp) =
+                shaping::overflow_outline(&id, "muktabodha_fetch", &args, &text, &span, || {
+                    list_heads_generic(&xml)
+                })
+            {
+                return resp;
             }
+            let highlight_count = highlights.len();
+            let highlight_positions = highlight_positions(&args, &highlights, &by_pattern);
 
             let heads = if is_xml {
                 list_heads_generic(&xml)
@@ -5462,8 +11537,17 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 .unwrap_or(20) as usize;
             let meta = json!({
                 "totalLength": text.chars().count(),
+                "totalChars": span.total,
+                "returnedStart": span.start,
+                "returnedEnd": span.end,
+                "truncated": span.end < span.total,
+                "nextCursor": next_cursor("muktabodha_fetch", &args, span.end, span.total),
+                "nextLine": span.next_line,
+                "lineMap": line_map(&text, span.start, span.end),
                 "sourcePath": path.to_string_lossy(),
                 "extractionMethod": extraction_method,
+                "pageBy": page_by,
+                "structure": opts.line_number.and_then(|l| JuanMap::build(&xml).context_at_line(l)),
                 "headingsTotal": heads.len(),
                 "headingsPreview": heads.into_iter().take(headings_limit).collect::<Vec<_>>(),
                 "matchedId": matched_id,
@@ -5476,11 +11560,16 @@ Flow: jozen_search -> jozen_fetch (lineno)
         }
         "muktabodha_search" => {
             let q_raw = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
+            let multi = match multi_query("muktabodha", &args) {
+                Ok(m) => m,
+                Err(e) => return e.response(&id),
+            };
+            let multi_display = multi.as_ref().map(|m| m.queries.join(" | "));
+            let q_raw = multi_display.as_deref().unwrap_or(q_raw);
             let looks_like_regex = q_raw.chars().any(|c| ".+*?[](){}|\\".contains(c));
-            let q = if q_raw.chars().any(|c| c.is_whitespace()) && !looks_like_regex {
-                to_whitespace_fuzzy_literal(q_raw)
-            } else {
-                q_raw.to_string()
+            let q = match &multi {
+                Some(m) => m.pattern(),
+                None => rewrite_query("muktabodha", q_raw, &args).pattern,
             };
             let max_results = args
                 .get("maxResults")
@@ -5491,9 +11580,45 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 .and_then(|v| v.as_u64())
                 .unwrap_or(5) as usize;
 
-            ensure_muktabodha_dir();
-            let results =
-                muktabodha_grep(&muktabodha_root(), &q, max_results, max_matches_per_file);
+            if let Err(e) = require_corpus("muktabodha") {
+                return e.response(&id);
+            }
+            let scope = match search_scope("muktabodha", &args) {
+                Ok(s) => s,
+                Err(e) => return e.response(&id),
+            };
+            let (results, q) = if query_mode_boolean(&args) {
+                match boolean_grep(
+                    "muktabodha",
+                    q_raw,
+                    &args,
+                    scope.as_ref(),
+                    max_results,
+                    max_matches_per_file,
+                ) {
+                    Ok(v) => v,
+                    Err(e) => return boolean_query_error(&id, q_raw, &e),
+                }
+            } else {
+                (
+                    corpus_grep(
+                        "muktabodha",
+                        scope.as_ref(),
+                        &q,
+                        max_results,
+                        max_matches_per_file,
+                    ),
+                    q,
+                )
+            };
+            let mut results = with_index_sizes("muktabodha", results);
+            if let Some(m) = &multi {
+                m.set.tag(&mut results);
+            }
+            filter_text_only(&mut results, &q, &args);
+            merge_snippets(&mut results, &q, &args);
+            sort_results("muktabodha", &mut results, &args);
+            register_match_ids("muktabodha", &mut results);
 
             let mut summary = format!(
                 "Found {} files with matches for '{}':\n\n",
@@ -5508,18 +11633,26 @@ Flow: jozen_search -> jozen_fetch (lineno)
                     result.file_id
                 ));
                 summary.push_str(&format!(
-                    "   {} matches, {}\n",
+                    "   {} matches, {}{}\n",
                     result.total_matches,
                     result
                         .fetch_hints
                         .total_content_size
                         .as_deref()
-                        .unwrap_or("unknown size")
+                        .unwrap_or("unknown size"),
+                    result
+                        .score
+                        .map(|s| format!(", relevance {:.2}", s))
+                        .unwrap_or_default()
                 ));
+                if let Some(m) = &multi {
+                    summary.push_str(&m.summary_line(result));
+                }
                 for (j, m) in result.matches.iter().enumerate().take(2) {
                     summary.push_str(&format!(
-                        "   Match {}: ...{}...\n",
+                        "   Match {}{}: ...{}...\n",
                         j + 1,
+                        match_id_tag(m),
                         m.context.chars().take(100).collect::<String>()
                     ));
                 }
@@ -5536,10 +11669,12 @@ Flow: jozen_search -> jozen_fetch (lineno)
             if let Some(r) = results.first() {
                 if let Some(m) = r.matches.first() {
                     if let Some(ln) = m.line_number {
+                        let w = daizo_core::window::suggest_for_file(Path::new(&r.file_path), ln);
                         fetch_suggestions.push(json!({
                             "tool": "muktabodha_fetch",
-                            "args": {"id": r.file_id, "lineNumber": ln, "contextBefore": 1, "contextAfter": 3, "highlight": q, "highlightRegex": hl_regex},
-                            "mode": "low-cost"
+                            "args": {"id": r.file_id, "lineNumber": ln, "contextBefore": w.before, "contextAfter": w.after, "highlight": q, "highlightRegex": hl_regex},
+                            "mode": "low-cost",
+                            "unit": w.unit
                         }));
                     }
                 }
@@ -5551,6 +11686,10 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 "hint": "Use muktabodha_fetch (id + lineNumber) for low-cost context; muktabodha_pipeline with autoFetch=false to summarize",
                 "fetchSuggestions": fetch_suggestions
             });
+            scope_meta(&mut meta, scope.as_ref());
+            if let Some(m) = &multi {
+                m.annotate_meta(&mut meta, &results);
+            }
             meta["pipelineHint"] = json!({
                 "tool": "muktabodha_pipeline",
                 "args": {"query": q, "autoFetch": false, "maxResults": 5, "maxMatchesPerFile": 1, "includeMatchLine": true }
@@ -5586,7 +11725,9 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 .and_then(|v| v.as_bool())
                 .unwrap_or(true);
 
-            ensure_muktabodha_dir();
+            if let Err(e) = require_corpus("muktabodha") {
+                return e.response(&id);
+            }
             let results =
                 muktabodha_grep(&muktabodha_root(), &q, max_results, max_matches_per_file);
             let mut content_items: Vec<serde_json::Value> = Vec::new();
@@ -5685,11 +11826,16 @@ Flow: jozen_search -> jozen_fetch (lineno)
         }
         "tipitaka_search" => {
             let q_raw = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
+            let multi = match multi_query("tipitaka", &args) {
+                Ok(m) => m,
+                Err(e) => return e.response(&id),
+            };
+            let multi_display = multi.as_ref().map(|m| m.queries.join(" | "));
+            let q_raw = multi_display.as_deref().unwrap_or(q_raw);
             let looks_like_regex = q_raw.chars().any(|c| ".+*?[](){}|\\".contains(c));
-            let q = if q_raw.chars().any(|c| c.is_whitespace()) && !looks_like_regex {
-                to_whitespace_fuzzy_literal(q_raw)
-            } else {
-                q_raw.to_string()
+            let q = match &multi {
+                Some(m) => m.pattern(),
+                None => rewrite_query("tipitaka", q_raw, &args).pattern,
             };
             let max_results = args
                 .get("maxResults")
@@ -5699,9 +11845,70 @@ Flow: jozen_search -> jozen_fetch (lineno)
                 .get("maxMatchesPerFile")
                 .and_then(|v| v.as_u64())
                 .unwrap_or(5) as usize;
+            let fold_diacritics = args
+                .get("foldDiacritics")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
 
-            ensure_tipitaka_data();
-            let results = tipitaka_grep(&tipitaka_root(), &q, max_results, max_matches_per_file);
+            if let Err(e) = require_corpus("tipitaka") {
+                return e.response(&id);
+            }
+            let scope = match search_scope("tipitaka", &args) {
+                Ok(s) => s,
+                Err(e) => return e.response(&id),
+            };
+            let filter = match tipitaka_filter(&args) {
+                Ok(f) => f,
+                Err(e) => return e.response(&id),
+            };
+            let scope = if filter.is_empty() {
+                scope
+            } else {
+                let idx = load_or_build_tipitaka_index();
+                Some(filter_scope(scope, &idx, |e| filter.matches(e)))
+            };
+            let (results, q) = if query_mode_boolean(&args) {
+                match boolean_grep(
+                    "tipitaka",
+                    q_raw,
+                    &args,
+                    scope.as_ref(),
+                    max_results,
+                    max_matches_per_file,
+                ) {
+                    Ok(v) => v,
+                    Err(e) => return boolean_query_error(&id, q_raw, &e),
+                }
+            } else if let Some(sc) = &scope {
+                (
+                    daizo_core::tipitaka_grep_scoped(
+                        &sc.paths,
+                        &q,
+                        max_results,
+                        max_matches_per_file,
+                        fold_diacritics,
+                    ),
+                    q,
+                )
+            } else if fold_diacritics {
+                (
+                    tipitaka_grep_folded(&tipitaka_root(), &q, max_results, max_matches_per_file),
+                    q,
+                )
+            } else {
+                (
+                    tipitaka_grep(&tipitaka_root(), &q, max_results, max_matches_per_file),
+                    q,
+                )
+            };
+            let mut results = with_index_sizes("tipitaka", results);
+            if let Some(m) = &multi {
+                m.set.tag(&mut results);
+            }
+            filter_text_only(&mut results, &q, &args);
+            merge_snippets(&mut results, &q, &args);
+            sort_results("tipitaka", &mut results, &args);
+            register_match_ids("tipitaka", &mut results);
 
             let mut summary = format!(
                 "Found {} files with matches for '{}':\n\n",
@@ -5716,19 +11923,27 @@ Flow: jozen_search -> jozen_fetch (lineno)
                     result.file_id
                 ));
                 summary.push_str(&format!(
-                    "   {} matches, {}\n",
+                    "   {} matches, {}{}\n",
                     result.total_matches,
                     result
                         .fetch_hints
                         .total_content_size
                         .as_deref()
-                        .unwrap_or("unknown size")
+                        .unwrap_or("unknown size"),
+                    result
+                        .score
+                        .map(|s| format!(", relevance {:.2}", s))
+                        .unwrap_or_default()
                 ));
+                if let Some(m) = &multi {
+                    summary.push_str(&m.summary_line(result));
+                }
 
                 for (j, m) in result.matches.iter().enumerate().take(2) {
                     summary.push_str(&format!(
-                        "   Match {}: ...{}...\n",
+                        "   Match {}{}: ...{}...\n",
                         j + 1,
+                        match_id_tag(m),
                         m.context.chars().take(100).collect::<String>()
                     ));
                 }
@@ -5757,43 +11972,480 @@ Flow: jozen_search -> jozen_fetch (lineno)
             for r in results.iter().take(hint_top) {
                 if let Some(m) = r.matches.first() {
                     if let Some(ln) = m.line_number {
+                        // Folded queries do not match the original text; highlight what was found.
+                        let (hl, hl_re) = if fold_diacritics && !m.highlight.is_empty() {
+                            (m.highlight.clone(), false)
+                        } else {
+                            (q.clone(), hl_regex)
+                        };
+                        let w = daizo_core::window::suggest_for_file(Path::new(&r.file_path), ln);
                         fetch_suggestions.push(json!({
                         "tool": "tipitaka_fetch",
-                        "args": {"id": r.file_id, "lineNumber": ln, "contextBefore": 1, "contextAfter": 3, "highlight": q, "highlightRegex": hl_regex},
-                        "mode": "low-cost"
+                        "args": {"id": r.file_id, "lineNumber": ln, "contextBefore": w.before, "contextAfter": w.after, "highlight": hl, "highlightRegex": hl_re},
+                        "mode": "low-cost",
+                        "unit": w.unit
                     }));
                     }
                 }
             }
-            let meta = json!({
+            let mut meta = json!({
                 "searchPattern": q,
+                "foldDiacritics": fold_diacritics,
                 "totalFiles": results.len(),
                 "results": results,
                 "hint": "Use tipitaka_fetch (id + lineNumber) for low-cost context",
                 "fetchSuggestions": fetch_suggestions
             });
+            scope_meta(&mut meta, scope.as_ref());
+            if !filter.is_empty() {
+                meta["filter"] = json!(filter);
+            }
+            if let Some(m) = &multi {
+                m.annotate_meta(&mut meta, &results);
+            }
 
             return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary}], "_meta": meta }});
         }
-        _ => format!("unknown tool: {}", name),
+        n if n.ends_with("_fetch_batch") => return fetch_batch(id, n, &args),
+        n if remote_tool(n).is_some() => return remote_tool_call(id, n, &args),
+        _ => {}
+    }
+    ToolError::UnknownTool(name.to_string()).response(&id)
+}
+
+// find_tipitaka_content_for_base and find_exact_file_by_name moved to daizo_core::path_resolver
+
+/// `_meta.work` for a Tipitaka file that is one part of a work split over numbered files: the
+/// parts in order and the neighbours of this one. When the slice reaches the end of the part,
+/// `nextCursor` continues at the start of the next file and `partTransition` says so.
+/// Returns the path of the next part.
+fn tipitaka_work_meta(
+    meta: &mut serde_json::Value,
+    path: &Path,
+    args: &serde_json::Value,
+    at_end: bool,
+) -> Option<PathBuf> {
+    let parts = daizo_core::path_resolver::tipitaka_work_parts(path);
+    if parts.len() < 2 {
+        return None;
+    }
+    let stem = |p: &Path| {
+        p.file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    let ids: Vec<String> = parts.iter().map(|p| stem(p)).collect();
+    let i = parts.iter().position(|p| p == path)?;
+    let base = daizo_core::path_resolver::tipitaka_part_of(&ids[i])
+        .map(|(b, _)| b.to_string())
+        .unwrap_or_default();
+    let next = ids.get(i + 1);
+    meta["work"] = json!({
+        "base": base,
+        "parts": ids,
+        "part": i + 1,
+        "totalParts": ids.len(),
+        "prevPartId": i.checked_sub(1).map(|j| &ids[j]),
+        "nextPartId": next,
+    });
+    if let (true, Some(next)) = (at_end, next) {
+        let mut rest = args.clone();
+        if let Some(o) = rest.as_object_mut() {
+            o.remove("query");
+            o.insert("id".to_string(), json!(next));
+        }
+        meta["nextCursor"] = json!(next_cursor("tipitaka_fetch", &rest, 0, 1));
+        meta["partTransition"] = json!({"from": ids[i], "to": next});
+    }
+    parts.get(i + 1).cloned()
+}
+
+static REMOTE_INFLIGHT: OnceLock<daizo_core::fetch_cache::Coalescer<Option<String>>> =
+    OnceLock::new();
+
+/// Read-through cache for remote responses. Concurrent misses on the same cache file share a
+/// single network request, and the entry is written atomically (temp file + rename).
+fn cached_remote(cpath: &Path, fetch: impl FnOnce() -> Option<String>) -> Option<String> {
+    if let Ok(s) = fs::read_to_string(cpath) {
+        daizo_core::metrics::cache_hit("remote");
+        return Some(s);
+    }
+    daizo_core::metrics::cache_miss("remote");
+    let key = cpath.to_string_lossy().to_string();
+    REMOTE_INFLIGHT
+        .get_or_init(daizo_core::fetch_cache::Coalescer::new)
+        .run(&key, || {
+            // Another caller may have filled the entry while we waited for the slot.
+            if let Ok(s) = fs::read_to_string(cpath) {
+                return Some(s);
+            }
+            let txt = fetch()?;
+            if let Err(e) = daizo_core::fetch_cache::write_atomic(cpath, txt.as_bytes()) {
+                tracing::warn!(path = %cpath.display(), error = %e, "fetch cache write failed");
+            }
+            Some(txt)
+        })
+}
+
+/// Remote stand-in for a missing local corpus: CBETA Online for `cbeta` when
+/// `DAIZO_ALLOW_REMOTE=1`, CBETA is not installed and online sources are allowed. The local
+/// checkout is then neither cloned nor required.
+fn remote_fallback(corpus: &str) -> Option<&'static dyn RemoteSource> {
+    let allowed =
+        std::env::var("DAIZO_ALLOW_REMOTE").is_ok_and(|v| matches!(v.trim(), "1" | "true" | "yes"));
+    if !allowed || corpus != "cbeta" || corpus_present(corpus) {
+        return None;
+    }
+    let src: &'static dyn RemoteSource = &remote::CBETA_ONLINE;
+    (capabilities().network() && capabilities().allows(src.name())).then_some(src)
+}
+
+/// `cbeta_fetch` from CBETA Online: one juan (`part`, default 1) as plain text. Options that
+/// need the local XML (`lb`, `lineNumber`, `includeNotes`, heading lookups) are not available.
+fn cbeta_online_fetch(
+    id: serde_json::Value,
+    src: &dyn RemoteSource,
+    args: &serde_json::Value,
+) -> serde_json::Value {
+    let Some(work) = args
+        .get("id")
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+    else {
+        return ToolError::InvalidArgument {
+            name: "id".to_string(),
+            value: String::new(),
+            expected: "a CBETA work id such as T0262 (CBETA Online cannot resolve titles)"
+                .to_string(),
+        }
+        .response(&id);
     };
-    json!({
-        "jsonrpc":"2.0",
-        "id": id,
-        "result": { "content": [{"type":"text","text": content_text }] }
-    })
+    let juan = args
+        .get("part")
+        .and_then(|v| {
+            v.as_u64()
+                .or_else(|| v.as_str().and_then(|s| s.trim().parse().ok()))
+        })
+        .unwrap_or(1)
+        .max(1) as u32;
+    let url = src.detail_url(&remote::CbetaOnline::juan_id(work, juan));
+    let text = remote_text(src, &url);
+    if text.is_empty() {
+        return remote_fetch_error("cbeta", args).response(&id);
+    }
+    let span = fetch_slice(&text, args, false);
+    let mut meta = json!({
+        "matchedId": work,
+        "part": format!("{:03}", juan),
+        "remoteSource": src.name(),
+        "totalLength": span.total,
+        "returnedStart": span.start,
+        "returnedEnd": span.end,
+        "truncated": span.end < span.total,
+        "nextCursor": next_cursor("cbeta_fetch", args, span.end, span.total),
+        "nextLine": span.next_line,
+        "lineMap": line_map(&text, span.start, span.end),
+        "sourceUrl": url,
+        "extractionMethod": "cbeta-online-juan",
+        "note": "Read from CBETA Online because the local CBETA corpus is not installed (DAIZO_ALLOW_REMOTE=1)"
+    });
+    if span.end >= span.total {
+        meta["hint"] = json!(format!(
+            "End of juan {}; pass part \"{:03}\" for the next one",
+            juan,
+            juan + 1
+        ));
+    }
+    json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": span.text}], "_meta": meta }})
 }
 
-// find_tipitaka_content_for_base and find_exact_file_by_name moved to daizo_core::path_resolver
+/// `cbeta_search` from CBETA Online: works and juans containing `q`, with their hit counts.
+fn cbeta_online_search(
+    id: serde_json::Value,
+    src: &dyn RemoteSource,
+    q: &str,
+    args: &serde_json::Value,
+) -> serde_json::Value {
+    let rows = args
+        .get("maxResults")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(20) as usize;
+    let canons: Vec<&str> = match args.get("canon") {
+        Some(serde_json::Value::String(c)) => vec![c.as_str()],
+        Some(serde_json::Value::Array(a)) => a.iter().filter_map(|v| v.as_str()).collect(),
+        _ => Vec::new(),
+    };
+    let query = remote::SearchQuery {
+        query: q.to_string(),
+        rows,
+        offset: 0,
+        fields: None,
+        filters: canons.iter().map(|c| format!("canon:{}", c)).collect(),
+    };
+    let Some(page) = remote_search(src, &query) else {
+        return match errors::take_network_failure() {
+            Some(reason) => ToolError::NetworkError {
+                source: src.name().to_string(),
+                reason,
+            }
+            .response(&id),
+            None => text_not_found("cbeta", args).response(&id),
+        };
+    };
+    let mut lines = Vec::new();
+    let mut results = Vec::new();
+    let mut suggestions = Vec::new();
+    for d in &page.docs {
+        let Some((work, juan)) = src.doc_id(d).map(|i| remote::CbetaOnline::split_id(&i)) else {
+            continue;
+        };
+        let part = format!("{:03}", juan);
+        let hits = d.get("term_hits").and_then(|v| v.as_u64()).unwrap_or(0);
+        let title = src.doc_title(d);
+        lines.push(format!("{} part {}  {} ({} hits)", work, part, title, hits));
+        results.push(json!({
+            "id": work,
+            "part": part,
+            "title": title,
+            "hits": hits,
+            "byline": d.get("byline").or_else(|| d.get("creators")),
+        }));
+        suggestions.push(
+            json!({"tool": "cbeta_fetch", "args": {"id": work, "part": part, "highlight": q}}),
+        );
+    }
+    let summary = format!(
+        "{} juans match (CBETA Online); {} listed\n{}",
+        page.total,
+        results.len(),
+        lines.join("\n")
+    );
+    let meta = json!({
+        "searchPattern": q,
+        "count": page.total,
+        "results": results,
+        "fetchSuggestions": suggestions,
+        "remoteSource": src.name(),
+        "sourceUrl": src.search_url(&query),
+        "note": "Searched CBETA Online because the local CBETA corpus is not installed (DAIZO_ALLOW_REMOTE=1); the query is sent as plain text, not a regex"
+    });
+    json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": summary}], "_meta": meta }})
+}
+
+// ============ Remote sources ============
+//
+// Requests, caching and text extraction for the sources of `daizo_core::remote`. Search
+// responses are cached as received and detail pages as extracted text, under
+// `cache/<policy dir>/<sha1 of the key>.txt`; entries older than the source's TTL are fetched
+// again.
 
-fn cache_path_for(url: &str) -> PathBuf {
+fn remote_cache_path(src: &dyn RemoteSource, key: &str) -> PathBuf {
     let mut hasher = Sha1::new();
-    hasher.update(url.as_bytes());
-    let h = hasher.finalize();
-    let fname = format!("{:x}.txt", h);
-    let dir = cache_dir().join("sat");
+    hasher.update(key.as_bytes());
+    let dir = cache_dir().join(src.cache_policy().dir);
     ensure_dir(&dir);
-    dir.join(fname)
+    dir.join(format!("{:x}.txt", hasher.finalize()))
+}
+
+/// `cached_remote` under `src`'s cache policy.
+fn remote_cached(
+    src: &dyn RemoteSource,
+    key: &str,
+    fetch: impl FnOnce() -> Option<String>,
+) -> Option<String> {
+    let cpath = remote_cache_path(src, key);
+    if let Some(ttl) = src.cache_policy().ttl {
+        let stale = fs::metadata(&cpath)
+            .and_then(|m| m.modified())
+            .is_ok_and(|t| t.elapsed().is_ok_and(|age| age > ttl));
+        if stale {
+            let _ = fs::remove_file(&cpath);
+        }
+    }
+    cached_remote(&cpath, fetch)
+}
+
+fn remote_search(src: &dyn RemoteSource, q: &remote::SearchQuery) -> Option<remote::SearchPage> {
+    let url = src.search_url(q);
+    let body = remote_cached(src, &url, || http_get_with_retry(&url, 3))?;
+    src.parse_search(&body)
+}
+
+/// Text of the detail page at `url`; empty when it could not be fetched.
+fn remote_text(src: &dyn RemoteSource, url: &str) -> String {
+    remote_cached(src, url, || {
+        http_get_with_retry(url, 3).map(|html| src.extract_text(&html))
+    })
+    .unwrap_or_default()
+}
+
+/// `remote_text` for several pages, requesting the uncached ones concurrently.
+fn remote_texts(src: &dyn RemoteSource, urls: &[String]) -> Vec<String> {
+    let missing: Vec<String> = urls
+        .iter()
+        .filter(|u| !remote_cache_path(src, u).exists())
+        .cloned()
+        .collect();
+    let mut fetched: std::collections::HashMap<String, Result<String, String>> =
+        if missing.is_empty() {
+            Default::default()
+        } else {
+            let _t = Phase::Network.start();
+            missing
+                .iter()
+                .cloned()
+                .zip(http::get_all(&missing, 3))
+                .collect()
+        };
+    urls.iter()
+        .map(|u| {
+            let got = fetched.remove(u);
+            remote_cached(src, u, || match got? {
+                Ok(html) => Some(src.extract_text(&html)),
+                Err(e) => {
+                    errors::note_network_failure(u, e);
+                    None
+                }
+            })
+            .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// The registered source and operation (`search` / `fetch`) of a generic remote tool.
+fn remote_tool(name: &str) -> Option<(&'static dyn RemoteSource, &'static str)> {
+    let (prefix, op) = name
+        .strip_suffix("_search")
+        .map(|p| (p, "search"))
+        .or_else(|| name.strip_suffix("_fetch").map(|p| (p, "fetch")))?;
+    remote::source(prefix)
+        .filter(|s| s.generic_tools())
+        .map(|s| (s, op))
+}
+
+/// Schemas of the generic tools of the registered sources.
+fn remote_tool_schemas() -> Vec<serde_json::Value> {
+    let mut out = Vec::new();
+    for src in remote::sources().iter().filter(|s| s.generic_tools()) {
+        out.push(tool(&format!("{}_search", src.name()), &format!("Search {} (online). Returns _meta.results and _meta.fetchSuggestions (use {}_fetch with id).", src.label(), src.name()), json!({"type":"object","properties":{
+            "query":{"type":"string"},
+            "rows":{"type":"number","description":"Results per page (default: 20)"},
+            "offs":{"type":"number","description":"Offset of the first result (default: 0)"},
+            "fields":{"type":"string","description":"Fields to return, comma-separated (if the source supports it)"},
+            "fq":{"type":"array","items":{"type":"string"},"description":"Filter expressions (if the source supports them)"}
+        },"required":["query"]})));
+        out.push(tool(&format!("{}_fetch", src.name()), &format!("Fetch a {} page as text (online), by id from {}_search or by URL.", src.label(), src.name()), json!({"type":"object","properties":{
+            "cursor":{"type":"string","description":"_meta.nextCursor from a previous call: continue exactly where that slice ended"},
+            "id":{"type":"string"},
+            "url":{"type":"string"},
+            "startChar":{"type":"number"},
+            "startLine":{"type":"number","description":"1-based line of the extracted text to start at (instead of startChar); _meta.lineMap maps the returned lines to char offsets"},
+            "maxChars":{"type":"number"}
+        }})));
+    }
+    out
+}
+
+/// `<name>_search` and `<name>_fetch` for a source without tools of its own.
+fn remote_tool_call(
+    id: serde_json::Value,
+    name: &str,
+    args: &serde_json::Value,
+) -> serde_json::Value {
+    let Some((src, op)) = remote_tool(name) else {
+        return ToolError::UnknownTool(name.to_string()).response(&id);
+    };
+    let arg_str = |k: &str| args.get(k).and_then(|v| v.as_str()).unwrap_or("");
+    if op == "search" {
+        let q = remote::SearchQuery {
+            query: arg_str("query").trim().to_string(),
+            rows: args.get("rows").and_then(|v| v.as_u64()).unwrap_or(20) as usize,
+            offset: args.get("offs").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+            fields: args
+                .get("fields")
+                .and_then(|v| v.as_str())
+                .map(str::to_string),
+            filters: args
+                .get("fq")
+                .and_then(|v| v.as_array())
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|x| x.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        };
+        let Some(page) = remote_search(src, &q) else {
+            if let Some(reason) = errors::take_network_failure() {
+                return ToolError::NetworkError {
+                    source: src.name().to_string(),
+                    reason,
+                }
+                .response(&id);
+            }
+            return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": "0 results"}], "_meta": {"count": 0, "results": []} }});
+        };
+        let fetch_tool = format!("{}_fetch", src.name());
+        let suggestions: Vec<_> = page
+            .docs
+            .iter()
+            .filter_map(|d| src.doc_id(d))
+            .map(|i| json!({"tool": fetch_tool, "args": {"id": i}}))
+            .collect();
+        let lines: Vec<String> = page
+            .docs
+            .iter()
+            .map(|d| {
+                format!(
+                    "{}  {}",
+                    src.doc_id(d).unwrap_or_default(),
+                    src.doc_title(d)
+                )
+            })
+            .collect();
+        let text = format!(
+            "{} results; {} on this page\n{}",
+            page.total,
+            page.docs.len(),
+            lines.join("\n")
+        );
+        let meta = json!({"count": page.total, "results": page.docs, "q": q.query, "fetchSuggestions": suggestions});
+        return json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": text}], "_meta": meta }});
+    }
+    let url = match arg_str("id") {
+        "" => arg_str("url").to_string(),
+        i => src.detail_url(i),
+    };
+    if url.is_empty() {
+        return ToolError::InvalidArgument {
+            name: "id".to_string(),
+            value: String::new(),
+            expected: format!("an id from {}_search, or url", src.name()),
+        }
+        .response(&id);
+    }
+    let t = remote_text(src, &url);
+    if t.is_empty() {
+        return remote_fetch_error(src.name(), args).response(&id);
+    }
+    let maxc = args
+        .get("maxChars")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(8000) as usize;
+    let start = start_char_arg(&t, args).unwrap_or(0);
+    let (sliced, total_chars, returned_start, returned_end) = slice_text_bounds(&t, start, maxc);
+    let meta = json!({
+        "totalLength": total_chars as u64,
+        "returnedStart": returned_start as u64,
+        "returnedEnd": returned_end as u64,
+        "truncated": returned_end < total_chars,
+        "nextCursor": next_cursor(name, args, returned_end, total_chars),
+        "nextLine": line_at_char(&t, returned_end),
+        "lineMap": line_map(&t, returned_start, returned_end),
+        "sourceUrl": url
+    });
+    json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": sliced}], "_meta": meta }})
 }
 
 fn decode_xml_bytes(bytes: &[u8]) -> String {
@@ -5865,61 +12517,32 @@ fn find_subslice(hay: &[u8], needle: &[u8]) -> Option<usize> {
     hay.windows(needle.len()).position(|w| w == needle)
 }
 
-fn http_client() -> &'static Client {
-    static CLIENT: OnceLock<Client> = OnceLock::new();
-    CLIENT.get_or_init(|| {
-        Client::builder()
-            .user_agent("daizo-mcp/0.1 (+https://github.com/sinryo/daizo-mcp)")
-            .connect_timeout(Duration::from_secs(5))
-            .timeout(Duration::from_secs(12))
-            .build()
-            .expect("reqwest client")
-    })
-}
-
-fn throttle(ms: u64) {
-    static LAST: OnceLock<Mutex<Instant>> = OnceLock::new();
-    let m = LAST.get_or_init(|| Mutex::new(Instant::now() - Duration::from_millis(ms)));
-    let mut last = m.lock().unwrap();
-    let elapsed = last.elapsed();
-    if elapsed < Duration::from_millis(ms) {
-        std::thread::sleep(Duration::from_millis(ms) - elapsed);
-    }
-    *last = Instant::now();
+/// The DILA authority record of an entity (`daizo_core::authority`), looked up by its DILA
+/// id or its most frequent form. Responses are cached under `cache/dila`; `Err` when the
+/// request failed.
+fn dila_authority(e: &Entity) -> Result<Option<AuthorityRecord>, ()> {
+    let api = std::env::var("DAIZO_DILA_AUTHORITY_URL")
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| authority::DEFAULT_API.to_string());
+    let Some(url) = authority::lookup_url(&api, e.kind, e.key.as_deref(), &e.name) else {
+        return Ok(None);
+    };
+    let mut hasher = Sha1::new();
+    hasher.update(url.as_bytes());
+    let dir = cache_dir().join("dila");
+    ensure_dir(&dir);
+    let cpath = dir.join(format!("{:x}.json", hasher.finalize()));
+    let body = cached_remote(&cpath, || http_get_with_retry(&url, 1)).ok_or(())?;
+    let records = authority::parse_response(e.kind, &body);
+    Ok(authority::best_match(&records, e.key.as_deref(), &e.forms).cloned())
 }
 
 fn http_get_with_retry(url: &str, max_retries: u32) -> Option<String> {
-    let client = http_client();
-    let mut attempt = 0u32;
-    let mut backoff = 500u64; // ms
-    loop {
-        throttle(500);
-        match client.get(url).send() {
-            Ok(resp) => {
-                let status = resp.status();
-                if status.is_success() {
-                    match resp.text() {
-                        Ok(t) => return Some(t),
-                        Err(_) => {}
-                    }
-                }
-                if status.as_u16() == 429 || status.is_server_error() {
-                    // retry
-                } else {
-                    return None;
-                }
-            }
-            Err(e) => {
-                dbg_log(&format!("[http] error attempt={} err={}", attempt + 1, e));
-            }
-        }
-        attempt += 1;
-        if attempt > max_retries {
-            return None;
-        }
-        std::thread::sleep(Duration::from_millis(backoff));
-        backoff = (backoff.saturating_mul(2)).min(8000);
-    }
+    let _t = Phase::Network.start();
+    http::get(url, max_retries)
+        .map_err(|e| errors::note_network_failure(url, e))
+        .ok()
 }
 
 fn tibetan_ewts_converter() -> &'static EwtsConverter {
@@ -6008,9 +12631,7 @@ fn adarshah_search_fulltext(
     const URL: &str =
         "https://api.adarshah.org/plugins/adarshaplugin/file_servlet/search/esSearch?";
 
-    let client = http_client();
-    throttle(200);
-
+    let _t = Phase::Network.start();
     let params: Vec<(&str, String)> = vec![
         ("apiKey", API_KEY.to_string()),
         ("token", "".to_string()),
@@ -6021,15 +12642,12 @@ fn adarshah_search_fulltext(
         ),
     ];
 
-    let resp = client.post(URL).form(&params).send();
-    let Ok(resp) = resp else {
-        return Vec::new();
-    };
-    if !resp.status().is_success() {
-        return Vec::new();
-    }
-    let Ok(body) = resp.text() else {
-        return Vec::new();
+    let body = match http::post_form(URL, &params, 0) {
+        Ok(b) => b,
+        Err(e) => {
+            errors::note_network_failure(URL, e);
+            return Vec::new();
+        }
     };
     let Ok(v) = serde_json::from_str::<serde_json::Value>(&body) else {
         return Vec::new();
@@ -6203,22 +12821,17 @@ fn buda_search_fulltext(
         serde_json::to_string(&q_obj).unwrap_or_else(|_| "{}".to_string())
     );
 
-    let client = http_client();
-    throttle(200);
-    let resp = client
-        .post(URL)
-        .header("Authorization", AUTH_BASIC)
-        .header("Content-Type", "application/x-ndjson")
-        .body(body)
-        .send();
-    let Ok(resp) = resp else {
-        return Vec::new();
-    };
-    if !resp.status().is_success() {
-        return Vec::new();
-    }
-    let Ok(text) = resp.text() else {
-        return Vec::new();
+    let _t = Phase::Network.start();
+    let headers = [
+        ("Authorization", AUTH_BASIC),
+        ("Content-Type", "application/x-ndjson"),
+    ];
+    let text = match http::post(URL, &headers, body, 0) {
+        Ok(t) => t,
+        Err(e) => {
+            errors::note_network_failure(URL, e);
+            return Vec::new();
+        }
     };
     let Ok(v) = serde_json::from_str::<serde_json::Value>(&text) else {
         return Vec::new();
@@ -6267,441 +12880,368 @@ fn buda_search_fulltext(
 
         let root = buda_extract_id_from_hit(&h).unwrap_or_default();
 
-        let url = if !root.is_empty() {
-            Some(format!(
-                "https://library.bdrc.io/show/bdr:{}",
-                urlencoding::encode(&root)
-            ))
+        let url = if !root.is_empty() {
+            Some(format!(
+                "https://library.bdrc.io/show/bdr:{}",
+                urlencoding::encode(&root)
+            ))
+        } else {
+            None
+        };
+
+        out.push(json!({
+            "source": "buda",
+            "score": score,
+            "query": q,
+            "qSent": qsent,
+            "exact": exact,
+            "title": if !title_bo.is_empty() { title_bo } else { title_ewts.clone() },
+            "title_ewts": if title_ewts.is_empty() { None::<String> } else { Some(title_ewts) },
+            "id": root,
+            "snippet": snippet,
+            "url": url
+        }));
+    }
+    out
+}
+
+#[derive(Serialize, Clone)]
+struct JozenHit {
+    lineno: String,
+    title: String,
+    author: String,
+    snippet: String,
+    #[serde(rename = "detailUrl")]
+    detail_url: String,
+    #[serde(rename = "imageUrl")]
+    image_url: String,
+}
+
+struct JozenSearchParsed {
+    total_count: Option<usize>,
+    displayed_count: Option<usize>,
+    total_pages: Option<usize>,
+    page_size: Option<usize>,
+    results: Vec<JozenHit>,
+}
+
+struct JozenDetail {
+    source_url: String,
+    work_header: String,
+    textno: Option<String>,
+    page_prev: Option<String>,
+    page_next: Option<String>,
+    line_ids: Vec<String>,
+    content: String,
+}
+
+fn jozen_cache_path_for(key: &str) -> PathBuf {
+    let mut hasher = Sha1::new();
+    hasher.update(key.as_bytes());
+    let h = hasher.finalize();
+    let fname = format!("{:x}.html", h);
+    let dir = cache_dir().join("jozen");
+    ensure_dir(&dir);
+    dir.join(fname)
+}
+
+fn http_post_form_with_retry(
+    url: &str,
+    params: &[(&str, String)],
+    max_retries: u32,
+) -> Option<String> {
+    let _t = Phase::Network.start();
+    http::post_form(url, params, max_retries)
+        .map_err(|e| errors::note_network_failure(url, e))
+        .ok()
+}
+
+fn jozen_search_html(query: &str, page: usize) -> Option<String> {
+    const URL: &str = "https://jodoshuzensho.jp/jozensearch_post/search/connect_jozen_DB.php";
+    let key = format!("POST|{}|keywd={}|page={}", URL, query, page);
+    let cpath = jozen_cache_path_for(&key);
+    let params: Vec<(&str, String)> =
+        vec![("keywd", query.to_string()), ("page", page.to_string())];
+    cached_remote(&cpath, || http_post_form_with_retry(URL, &params, 3))
+}
+
+fn jozen_detail_url(lineno: &str) -> String {
+    format!(
+        "https://jodoshuzensho.jp/jozensearch_post/search/detail.php?lineno={}",
+        urlencoding::encode(lineno.trim())
+    )
+}
+
+fn jozen_image_url(lineno: &str) -> String {
+    format!(
+        "https://jodoshuzensho.jp/jozensearch_post/search/image.php?lineno={}",
+        urlencoding::encode(lineno.trim())
+    )
+}
+
+fn jozen_detail_html(lineno: &str) -> Option<String> {
+    let url = jozen_detail_url(lineno);
+    let key = format!("GET|{}", url);
+    let cpath = jozen_cache_path_for(&key);
+    cached_remote(&cpath, || http_get_with_retry(&url, 3))
+}
+
+fn jozen_join_url(href: &str) -> String {
+    let t = href.trim();
+    if t.is_empty() {
+        return String::new();
+    }
+    if t.starts_with("http://") || t.starts_with("https://") {
+        return t.to_string();
+    }
+    let base = url::Url::parse("https://jodoshuzensho.jp/jozensearch_post/search/").unwrap();
+    base.join(t)
+        .map(|u| u.to_string())
+        .unwrap_or_else(|_| format!("https://jodoshuzensho.jp/jozensearch_post/search/{}", t))
+}
+
+fn jozen_extract_lineno_from_href(href: &str) -> Option<String> {
+    let full = jozen_join_url(href);
+    if full.is_empty() {
+        return None;
+    }
+    let Ok(u) = url::Url::parse(&full) else {
+        return None;
+    };
+    for (k, v) in u.query_pairs() {
+        if k == "lineno" {
+            let t = v.trim().to_string();
+            if !t.is_empty() {
+                return Some(t);
+            }
+        }
+    }
+    None
+}
+
+fn jozen_html_fragment_to_text_keep_lb(inner_html: &str) -> String {
+    static LB_RE: OnceLock<Regex> = OnceLock::new();
+    let re = LB_RE.get_or_init(|| Regex::new(r"(?is)<lb[^>]*>").unwrap());
+    let replaced = re.replace_all(inner_html, "\n");
+    let frag = Html::parse_fragment(replaced.as_ref());
+    let t = frag.root_element().text().collect::<Vec<_>>().join("");
+    normalize_ws(&t)
+}
+
+fn jozen_collect_text_compact(node: &scraper::ElementRef) -> String {
+    node.text()
+        .collect::<Vec<_>>()
+        .join("")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim()
+        .to_string()
+}
+
+fn jozen_parse_search_html(
+    html: &str,
+    _query: &str,
+    max_results: usize,
+    max_snippet_chars: usize,
+) -> JozenSearchParsed {
+    let dom = Html::parse_document(html);
+
+    let mut total_count: Option<usize> = None;
+    let mut displayed_count: Option<usize> = None;
+    let mut total_pages: Option<usize> = None;
+
+    if let Ok(sel) = Selector::parse("p.rlt1") {
+        if let Some(p) = dom.select(&sel).next() {
+            let t = p.text().collect::<Vec<_>>().join("");
+            static TOTAL_RE: OnceLock<Regex> = OnceLock::new();
+            static DISP_RE: OnceLock<Regex> = OnceLock::new();
+            let re_total = TOTAL_RE.get_or_init(|| Regex::new(r"全\s*([0-9,]+)\s*件").unwrap());
+            let re_disp = DISP_RE.get_or_init(|| Regex::new(r"([0-9,]+)\s*件を表示").unwrap());
+            if let Some(c) = re_total.captures(&t) {
+                if let Some(m) = c.get(1) {
+                    let n = m.as_str().replace(',', "");
+                    total_count = n.parse::<usize>().ok();
+                }
+            }
+            if let Some(c) = re_disp.captures(&t) {
+                if let Some(m) = c.get(1) {
+                    let n = m.as_str().replace(',', "");
+                    displayed_count = n.parse::<usize>().ok();
+                }
+            }
+        }
+    }
+
+    if let Ok(sel) = Selector::parse("form[name=lastpage] input[name=page]") {
+        if let Some(inp) = dom.select(&sel).next() {
+            if let Some(v) = inp.value().attr("value") {
+                total_pages = v.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let mut results: Vec<JozenHit> = Vec::new();
+    if max_results == 0 {
+        return JozenSearchParsed {
+            total_count,
+            displayed_count,
+            total_pages,
+            page_size: displayed_count.or(Some(50)),
+            results,
+        };
+    }
+
+    let tr_sel = Selector::parse("table.result_table tr").unwrap();
+    let th_sel = Selector::parse("th").unwrap();
+    let td_sel = Selector::parse("td").unwrap();
+    let a_sel = Selector::parse("a").unwrap();
+    for tr in dom.select(&tr_sel) {
+        if tr.select(&th_sel).next().is_some() {
+            continue;
+        }
+        let tds: Vec<_> = tr.select(&td_sel).collect();
+        if tds.len() < 5 {
+            continue;
+        }
+        let detail_href = tds[0]
+            .select(&a_sel)
+            .next()
+            .and_then(|a| a.value().attr("href"))
+            .unwrap_or("")
+            .to_string();
+        let image_href = tds[1]
+            .select(&a_sel)
+            .next()
+            .and_then(|a| a.value().attr("href"))
+            .unwrap_or("")
+            .to_string();
+        let mut lineno = jozen_collect_text_compact(&tds[0]);
+        if lineno.is_empty() {
+            lineno = jozen_extract_lineno_from_href(&detail_href).unwrap_or_default();
+        }
+        let title = jozen_collect_text_compact(&tds[2]);
+        let author = jozen_collect_text_compact(&tds[3]);
+        let snippet_html = tds[4].inner_html();
+        let mut snippet = jozen_html_fragment_to_text_keep_lb(&snippet_html);
+        if max_snippet_chars > 0 && snippet.chars().count() > max_snippet_chars {
+            snippet = truncate_chars(&snippet, max_snippet_chars);
+        }
+        let detail_url = if !lineno.is_empty() {
+            jozen_detail_url(&lineno)
+        } else {
+            jozen_join_url(&detail_href)
+        };
+        let image_url = if !lineno.is_empty() {
+            jozen_image_url(&lineno)
+        } else {
+            jozen_join_url(&image_href)
+        };
+        results.push(JozenHit {
+            lineno,
+            title,
+            author,
+            snippet,
+            detail_url,
+            image_url,
+        });
+        if results.len() >= max_results {
+            break;
+        }
+    }
+
+    JozenSearchParsed {
+        total_count,
+        displayed_count,
+        total_pages,
+        page_size: displayed_count.or(Some(50)),
+        results,
+    }
+}
+
+fn jozen_is_textno(token: &str) -> bool {
+    let b = token.as_bytes();
+    if b.len() != 5 {
+        return false;
+    }
+    b[0].is_ascii_alphabetic()
+        && b[1].is_ascii_digit()
+        && b[2].is_ascii_digit()
+        && b[3].is_ascii_digit()
+        && b[4].is_ascii_digit()
+}
+
+fn jozen_extract_detail(html: &str, source_url: &str) -> JozenDetail {
+    let dom = Html::parse_document(html);
+
+    let mut work_header = String::new();
+    if let Ok(sel) = Selector::parse("p.sdt01") {
+        if let Some(p) = dom.select(&sel).next() {
+            work_header = p.text().collect::<Vec<_>>().join("").trim().to_string();
+            if work_header.ends_with("画像") {
+                work_header = work_header.trim_end_matches("画像").trim().to_string();
+            }
+        }
+    }
+
+    let textno = work_header.split_whitespace().next().and_then(|t| {
+        if jozen_is_textno(t) {
+            Some(t.to_string())
         } else {
             None
-        };
+        }
+    });
 
-        out.push(json!({
-            "source": "buda",
-            "score": score,
-            "query": q,
-            "qSent": qsent,
-            "exact": exact,
-            "title": if !title_bo.is_empty() { title_bo } else { title_ewts.clone() },
-            "title_ewts": if title_ewts.is_empty() { None::<String> } else { Some(title_ewts) },
-            "id": root,
-            "snippet": snippet,
-            "url": url
-        }));
-	    }
-	    out
-	}
-
-	#[derive(Serialize, Clone)]
-	struct JozenHit {
-	    lineno: String,
-	    title: String,
-	    author: String,
-	    snippet: String,
-	    #[serde(rename = "detailUrl")]
-	    detail_url: String,
-	    #[serde(rename = "imageUrl")]
-	    image_url: String,
-	}
-	
-	struct JozenSearchParsed {
-	    total_count: Option<usize>,
-	    displayed_count: Option<usize>,
-	    total_pages: Option<usize>,
-	    page_size: Option<usize>,
-	    results: Vec<JozenHit>,
-	}
-	
-	struct JozenDetail {
-	    source_url: String,
-	    work_header: String,
-	    textno: Option<String>,
-	    page_prev: Option<String>,
-	    page_next: Option<String>,
-	    line_ids: Vec<String>,
-	    content: String,
-	}
-	
-	fn jozen_cache_path_for(key: &str) -> PathBuf {
-	    let mut hasher = Sha1::new();
-	    hasher.update(key.as_bytes());
-	    let h = hasher.finalize();
-	    let fname = format!("{:x}.html", h);
-	    let dir = cache_dir().join("jozen");
-	    ensure_dir(&dir);
-	    dir.join(fname)
-	}
-	
-	fn http_post_form_with_retry(
-	    url: &str,
-	    params: &[(&str, String)],
-	    max_retries: u32,
-	) -> Option<String> {
-	    let client = http_client();
-	    let mut attempt = 0u32;
-	    let mut backoff = 500u64; // ms
-	    loop {
-	        throttle(500);
-	        match client.post(url).form(&params).send() {
-	            Ok(resp) => {
-	                let status = resp.status();
-	                if status.is_success() {
-	                    if let Ok(t) = resp.text() {
-	                        return Some(t);
-	                    }
-	                }
-	                if status.as_u16() == 429 || status.is_server_error() {
-	                    // retry
-	                } else {
-	                    return None;
-	                }
-	            }
-	            Err(e) => {
-	                dbg_log(&format!("[http] post error attempt={} err={}", attempt + 1, e));
-	            }
-	        }
-	        attempt += 1;
-	        if attempt > max_retries {
-	            return None;
-	        }
-	        std::thread::sleep(Duration::from_millis(backoff));
-	        backoff = (backoff.saturating_mul(2)).min(8000);
-	    }
-	}
-	
-	fn jozen_search_html(query: &str, page: usize) -> Option<String> {
-	    const URL: &str = "https://jodoshuzensho.jp/jozensearch_post/search/connect_jozen_DB.php";
-	    let key = format!("POST|{}|keywd={}|page={}", URL, query, page);
-	    let cpath = jozen_cache_path_for(&key);
-	    if let Ok(s) = fs::read_to_string(&cpath) {
-	        return Some(s);
-	    }
-	    let params: Vec<(&str, String)> = vec![("keywd", query.to_string()), ("page", page.to_string())];
-	    if let Some(txt) = http_post_form_with_retry(URL, &params, 3) {
-	        let _ = fs::write(&cpath, &txt);
-	        return Some(txt);
-	    }
-	    None
-	}
-	
-		fn jozen_detail_url(lineno: &str) -> String {
-		    format!(
-		        "https://jodoshuzensho.jp/jozensearch_post/search/detail.php?lineno={}",
-		        urlencoding::encode(lineno.trim())
-		    )
-		}
-		
-		fn jozen_image_url(lineno: &str) -> String {
-		    format!(
-		        "https://jodoshuzensho.jp/jozensearch_post/search/image.php?lineno={}",
-		        urlencoding::encode(lineno.trim())
-		    )
-		}
-	
-	fn jozen_detail_html(lineno: &str) -> Option<String> {
-	    let url = jozen_detail_url(lineno);
-	    let key = format!("GET|{}", url);
-	    let cpath = jozen_cache_path_for(&key);
-	    if let Ok(s) = fs::read_to_string(&cpath) {
-	        return Some(s);
-	    }
-	    if let Some(txt) = http_get_with_retry(&url, 3) {
-	        let _ = fs::write(&cpath, &txt);
-	        return Some(txt);
-	    }
-	    None
-	}
-	
-	fn jozen_join_url(href: &str) -> String {
-	    let t = href.trim();
-	    if t.is_empty() {
-	        return String::new();
-	    }
-	    if t.starts_with("http://") || t.starts_with("https://") {
-	        return t.to_string();
-	    }
-	    let base = url::Url::parse("https://jodoshuzensho.jp/jozensearch_post/search/").unwrap();
-	    base.join(t)
-	        .map(|u| u.to_string())
-	        .unwrap_or_else(|_| format!("https://jodoshuzensho.jp/jozensearch_post/search/{}", t))
-	}
-	
-	fn jozen_extract_lineno_from_href(href: &str) -> Option<String> {
-	    let full = jozen_join_url(href);
-	    if full.is_empty() {
-	        return None;
-	    }
-	    let Ok(u) = url::Url::parse(&full) else {
-	        return None;
-	    };
-	    for (k, v) in u.query_pairs() {
-	        if k == "lineno" {
-	            let t = v.trim().to_string();
-	            if !t.is_empty() {
-	                return Some(t);
-	            }
-	        }
-	    }
-	    None
-	}
-	
-	fn jozen_html_fragment_to_text_keep_lb(inner_html: &str) -> String {
-	    static LB_RE: OnceLock<Regex> = OnceLock::new();
-	    let re = LB_RE.get_or_init(|| Regex::new(r"(?is)<lb[^>]*>").unwrap());
-	    let replaced = re.replace_all(inner_html, "\n");
-	    let frag = Html::parse_fragment(replaced.as_ref());
-	    let t = frag.root_element().text().collect::<Vec<_>>().join("");
-	    normalize_ws(&t)
-	}
-	
-	fn jozen_collect_text_compact(node: &scraper::ElementRef) -> String {
-	    node.text()
-	        .collect::<Vec<_>>()
-	        .join("")
-	        .split_whitespace()
-	        .collect::<Vec<_>>()
-	        .join(" ")
-	        .trim()
-	        .to_string()
-	}
-	
-	fn jozen_parse_search_html(
-	    html: &str,
-	    _query: &str,
-	    max_results: usize,
-	    max_snippet_chars: usize,
-	) -> JozenSearchParsed {
-	    let dom = Html::parse_document(html);
-	
-	    let mut total_count: Option<usize> = None;
-	    let mut displayed_count: Option<usize> = None;
-	    let mut total_pages: Option<usize> = None;
-	
-	    if let Ok(sel) = Selector::parse("p.rlt1") {
-	        if let Some(p) = dom.select(&sel).next() {
-	            let t = p.text().collect::<Vec<_>>().join("");
-	            static TOTAL_RE: OnceLock<Regex> = OnceLock::new();
-	            static DISP_RE: OnceLock<Regex> = OnceLock::new();
-		            let re_total = TOTAL_RE.get_or_init(|| Regex::new(r"全\s*([0-9,]+)\s*件").unwrap());
-		            let re_disp =
-		                DISP_RE.get_or_init(|| Regex::new(r"([0-9,]+)\s*件を表示").unwrap());
-	            if let Some(c) = re_total.captures(&t) {
-	                if let Some(m) = c.get(1) {
-	                    let n = m.as_str().replace(',', "");
-	                    total_count = n.parse::<usize>().ok();
-	                }
-	            }
-	            if let Some(c) = re_disp.captures(&t) {
-	                if let Some(m) = c.get(1) {
-	                    let n = m.as_str().replace(',', "");
-	                    displayed_count = n.parse::<usize>().ok();
-	                }
-	            }
-	        }
-	    }
-	
-	    if let Ok(sel) = Selector::parse("form[name=lastpage] input[name=page]") {
-	        if let Some(inp) = dom.select(&sel).next() {
-	            if let Some(v) = inp.value().attr("value") {
-	                total_pages = v.trim().parse::<usize>().ok();
-	            }
-	        }
-	    }
-	
-	    let mut results: Vec<JozenHit> = Vec::new();
-	    if max_results == 0 {
-	        return JozenSearchParsed {
-	            total_count,
-	            displayed_count,
-	            total_pages,
-	            page_size: displayed_count.or(Some(50)),
-	            results,
-	        };
-	    }
-	
-	    let tr_sel = Selector::parse("table.result_table tr").unwrap();
-	    let th_sel = Selector::parse("th").unwrap();
-	    let td_sel = Selector::parse("td").unwrap();
-	    let a_sel = Selector::parse("a").unwrap();
-	    for tr in dom.select(&tr_sel) {
-	        if tr.select(&th_sel).next().is_some() {
-	            continue;
-	        }
-	        let tds: Vec<_> = tr.select(&td_sel).collect();
-	        if tds.len() < 5 {
-	            continue;
-	        }
-	        let detail_href = tds[0]
-	            .select(&a_sel)
-	            .next()
-	            .and_then(|a| a.value().attr("href"))
-	            .unwrap_or("")
-	            .to_string();
-	        let image_href = tds[1]
-	            .select(&a_sel)
-	            .next()
-	            .and_then(|a| a.value().attr("href"))
-	            .unwrap_or("")
-	            .to_string();
-		        let mut lineno = jozen_collect_text_compact(&tds[0]);
-		        if lineno.is_empty() {
-		            lineno = jozen_extract_lineno_from_href(&detail_href).unwrap_or_default();
-		        }
-		        let title = jozen_collect_text_compact(&tds[2]);
-		        let author = jozen_collect_text_compact(&tds[3]);
-		        let snippet_html = tds[4].inner_html();
-		        let mut snippet = jozen_html_fragment_to_text_keep_lb(&snippet_html);
-		        if max_snippet_chars > 0 && snippet.chars().count() > max_snippet_chars {
-		            snippet = truncate_chars(&snippet, max_snippet_chars);
-		        }
-		        let detail_url = if !lineno.is_empty() {
-		            jozen_detail_url(&lineno)
-		        } else {
-		            jozen_join_url(&detail_href)
-		        };
-		        let image_url = if !lineno.is_empty() {
-		            jozen_image_url(&lineno)
-		        } else {
-		            jozen_join_url(&image_href)
-		        };
-		        results.push(JozenHit {
-		            lineno,
-		            title,
-		            author,
-	            snippet,
-	            detail_url,
-	            image_url,
-	        });
-	        if results.len() >= max_results {
-	            break;
-	        }
-	    }
-	
-	    JozenSearchParsed {
-	        total_count,
-	        displayed_count,
-	        total_pages,
-	        page_size: displayed_count.or(Some(50)),
-	        results,
-	    }
-	}
-	
-	fn jozen_is_textno(token: &str) -> bool {
-	    let b = token.as_bytes();
-	    if b.len() != 5 {
-	        return false;
-	    }
-	    b[0].is_ascii_alphabetic()
-	        && b[1].is_ascii_digit()
-	        && b[2].is_ascii_digit()
-	        && b[3].is_ascii_digit()
-	        && b[4].is_ascii_digit()
-	}
-	
-	fn jozen_extract_detail(html: &str, source_url: &str) -> JozenDetail {
-	    let dom = Html::parse_document(html);
-	
-	    let mut work_header = String::new();
-	    if let Ok(sel) = Selector::parse("p.sdt01") {
-	        if let Some(p) = dom.select(&sel).next() {
-	            work_header = p.text().collect::<Vec<_>>().join("").trim().to_string();
-	            if work_header.ends_with("画像") {
-	                work_header = work_header.trim_end_matches("画像").trim().to_string();
-	            }
-	        }
-	    }
-	
-	    let textno = work_header
-	        .split_whitespace()
-	        .next()
-	        .and_then(|t| if jozen_is_textno(t) { Some(t.to_string()) } else { None });
-	
-	    let page_prev = Selector::parse("a.tnbn_prev")
-	        .ok()
-	        .and_then(|sel| dom.select(&sel).next())
-	        .and_then(|a| a.value().attr("href"))
-	        .and_then(jozen_extract_lineno_from_href);
-	    let page_next = Selector::parse("a.tnbn_next")
-	        .ok()
-	        .and_then(|sel| dom.select(&sel).next())
-	        .and_then(|a| a.value().attr("href"))
-	        .and_then(jozen_extract_lineno_from_href);
-	
-	    let mut line_ids: Vec<String> = Vec::new();
-	    let mut out_lines: Vec<String> = Vec::new();
-	    let tr_sel = Selector::parse("table.sd_table tr").unwrap();
-	    let td1_sel = Selector::parse("td.sd_td1").unwrap();
-	    let td2_sel = Selector::parse("td.sd_td2").unwrap();
-	    for tr in dom.select(&tr_sel) {
-	        let Some(td1) = tr.select(&td1_sel).next() else {
-	            continue;
-	        };
-	        let Some(td2) = tr.select(&td2_sel).next() else {
-	            continue;
-	        };
-	        let id = jozen_collect_text_compact(&td1);
-	        let id = id.trim_end_matches(':').trim().to_string();
-	        if id.is_empty() {
-	            continue;
-	        }
-	        let text = td2.text().collect::<Vec<_>>().join("").trim().to_string();
-	        if text.is_empty() {
-	            continue;
-	        }
-	        line_ids.push(id.clone());
-	        out_lines.push(format!("[{}] {}", id, text));
-	    }
-	    let content = normalize_ws(&out_lines.join("\n"));
-	
-	    JozenDetail {
-	        source_url: source_url.to_string(),
-	        work_header,
-	        textno,
-	        page_prev,
-	        page_next,
-	        line_ids,
-	        content,
-	    }
-	}
-	
-	fn sat_fetch(url: &str) -> String {
-	    let cpath = cache_path_for(url);
-	    if let Ok(s) = fs::read_to_string(&cpath) {
-	        return s;
-    }
-    if let Some(txt) = http_get_with_retry(url, 3) {
-        let text = extract_sat_text(&txt);
-        let _ = fs::write(&cpath, &text);
-        return text;
-    }
-    "".to_string()
-}
-
-fn sat_wrap7_build_url(
-    q: &str,
-    rows: usize,
-    offs: usize,
-    fields: &str,
-    fq: &Vec<String>,
-) -> String {
-    let mut base = url::Url::parse("https://21dzk.l.u-tokyo.ac.jp/SAT2018/wrap7.php").unwrap();
-    base.query_pairs_mut().append_pair("regex", "off");
-    // Send the query as-is to wrap7 (caller may include quotes if needed)
-    base.query_pairs_mut().append_pair("q", q);
-    base.query_pairs_mut()
-        .append_pair("rows", &rows.to_string());
-    base.query_pairs_mut()
-        .append_pair("offs", &offs.to_string());
-    base.query_pairs_mut().append_pair("schop", "AND");
-    if !fields.trim().is_empty() {
-        base.query_pairs_mut().append_pair("fl", fields);
-    }
-    for f in fq {
-        if !f.trim().is_empty() {
-            base.query_pairs_mut().append_pair("fq", f);
+    let page_prev = Selector::parse("a.tnbn_prev")
+        .ok()
+        .and_then(|sel| dom.select(&sel).next())
+        .and_then(|a| a.value().attr("href"))
+        .and_then(jozen_extract_lineno_from_href);
+    let page_next = Selector::parse("a.tnbn_next")
+        .ok()
+        .and_then(|sel| dom.select(&sel).next())
+        .and_then(|a| a.value().attr("href"))
+        .and_then(jozen_extract_lineno_from_href);
+
+    let mut line_ids: Vec<String> = Vec::new();
+    let mut out_lines: Vec<String> = Vec::new();
+    let tr_sel = Selector::parse("table.sd_table tr").unwrap();
+    let td1_sel = Selector::parse("td.sd_td1").unwrap();
+    let td2_sel = Selector::parse("td.sd_td2").unwrap();
+    for tr in dom.select(&tr_sel) {
+        let Some(td1) = tr.select(&td1_sel).next() else {
+            continue;
+        };
+        let Some(td2) = tr.select(&td2_sel).next() else {
+            continue;
+        };
+        let id = jozen_collect_text_compact(&td1);
+        let id = id.trim_end_matches(':').trim().to_string();
+        if id.is_empty() {
+            continue;
+        }
+        let text = td2.text().collect::<Vec<_>>().join("").trim().to_string();
+        if text.is_empty() {
+            continue;
         }
+        line_ids.push(id.clone());
+        out_lines.push(format!("[{}] {}", id, text));
+    }
+    let content = normalize_ws(&out_lines.join("\n"));
+
+    JozenDetail {
+        source_url: source_url.to_string(),
+        work_header,
+        textno,
+        page_prev,
+        page_next,
+        line_ids,
+        content,
     }
-    base.to_string()
+}
+
+fn sat_fetch(url: &str) -> String {
+    remote_text(&SAT, url)
 }
 
 fn sat_wrap7_ensure_fields(fields: &str, required: &[&str]) -> String {
@@ -6719,31 +13259,6 @@ fn sat_wrap7_ensure_fields(fields: &str, required: &[&str]) -> String {
     out.join(",")
 }
 
-fn sat_wrap7_search_json(
-    q: &str,
-    rows: usize,
-    offs: usize,
-    fields: &str,
-    fq: &Vec<String>,
-) -> Option<serde_json::Value> {
-    let url = sat_wrap7_build_url(q, rows, offs, fields, fq);
-    let cpath = cache_path_for(&url);
-    let body = if let Ok(s) = fs::read_to_string(&cpath) {
-        s
-    } else {
-        if let Some(txt) = http_get_with_retry(&url, 3) {
-            let _ = fs::write(&cpath, &txt);
-            txt
-        } else {
-            String::new()
-        }
-    };
-    if body.is_empty() {
-        return None;
-    }
-    serde_json::from_str::<serde_json::Value>(&body).ok()
-}
-
 #[cfg(test)]
 mod tibetan_tests {
     use super::*;
@@ -6792,11 +13307,179 @@ mod tibetan_tests {
     }
 }
 
-fn sat_detail_build_url(useid: &str) -> String {
-    format!(
-        "https://21dzk.l.u-tokyo.ac.jp/SAT2018/satdb2018pre.php?mode=detail&ob=1&mode2=2&useid={}",
-        urlencoding::encode(useid)
-    )
+/// `cbeta_fetch` calls that read SAT hits (by `startid`) from the local CBETA copy, so they
+/// can be read without further requests to SAT. Empty when CBETA is not installed.
+fn sat_local_suggestions<'a>(startids: impl Iterator<Item = &'a str>) -> Vec<serde_json::Value> {
+    if !capabilities().allows("cbeta") || !corpus_present("cbeta") {
+        return Vec::new();
+    }
+    let mut seen = std::collections::HashSet::new();
+    startids
+        .filter(|sid| seen.insert(*sid))
+        .filter_map(|sid| {
+            let r = daizo_core::taisho_ref::parse_sat(sid)?;
+            Some(json!({
+                "tool": "cbeta_fetch",
+                "args": {"reference": r.canonical()},
+                "startid": sid,
+            }))
+        })
+        .take(10)
+        .collect()
+}
+
+/// `0262_,09,0001c14` as (text, volume, page, column, line), for ordering lines.
+fn sat_line_key(id: &str) -> Option<(String, u32, u32, char, u32)> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"^(\d{4}[A-Za-z_]?)[,.]+(\d{1,3})[,.]+(\d{4})([a-z])(\d{2})$").unwrap()
+    });
+    let c = re.captures(id.trim())?;
+    Some((
+        c[1].to_string(),
+        c[2].parse().ok()?,
+        c[3].parse().ok()?,
+        c[4].chars().next()?,
+        c[5].parse().ok()?,
+    ))
+}
+
+/// Lines of a SAT detail page as (line id, text), in page order. Text before the first
+/// line number is kept with an empty id.
+fn sat_detail_lines(html: &str) -> Vec<(String, String)> {
+    static LN: OnceLock<Regex> = OnceLock::new();
+    let ln =
+        LN.get_or_init(|| Regex::new(r"\d{4}[A-Za-z_]?[,.]+\d{1,3}[,.]+\d{4}[a-z]\d{2}").unwrap());
+    let doc = Html::parse_document(html);
+    let Ok(sel) = Selector::parse("span.ln, span.tx") else {
+        return Vec::new();
+    };
+    let mut out: Vec<(String, String)> = Vec::new();
+    for node in doc.select(&sel) {
+        let t = node.text().collect::<String>();
+        if node
+            .value()
+            .has_class("ln", scraper::CaseSensitivity::CaseSensitive)
+        {
+            let lid = node
+                .value()
+                .attr("id")
+                .and_then(|a| ln.find(a))
+                .or_else(|| ln.find(&t))
+                .map(|m| m.as_str().to_string());
+            if let Some(lid) = lid {
+                out.push((lid, String::new()));
+            }
+        } else {
+            let t = t.trim();
+            if t.is_empty() {
+                continue;
+            }
+            match out.last_mut() {
+                Some((_, text)) => text.push_str(t),
+                None => out.push((String::new(), t.to_string())),
+            }
+        }
+    }
+    out
+}
+
+/// A startid..endid range of SAT read across detail pages.
+struct SatRange {
+    text: String,
+    pages: Vec<serde_json::Value>,
+    last_line: Option<String>,
+    /// `end` (endid reached), `textChanged`, `maxPages`, `stalled` (a page added no line)
+    /// or `networkError`.
+    stop: &'static str,
+}
+
+/// Read detail pages from `startid`, each from the last line of the one before, until
+/// `endid` (or the next text) is reached.
+fn sat_fetch_range(startid: &str, endid: Option<&str>, max_pages: usize) -> SatRange {
+    let start_key = sat_line_key(startid);
+    let end_key = endid.and_then(sat_line_key);
+    let text_no = start_key.as_ref().map(|k| k.0.clone()).unwrap_or_default();
+    let mut lines: Vec<String> = Vec::new();
+    let mut chars = 0usize;
+    let mut pages = Vec::new();
+    let mut last: Option<String> = None;
+    let mut cur = startid.to_string();
+    let mut stop = "maxPages";
+    for _ in 0..max_pages {
+        let url = SAT.detail_url(&cur);
+        let cpath = remote_cache_path(&SAT, &format!("lines|{}", url));
+        let body = cached_remote(&cpath, || {
+            http_get_with_retry(&url, 3)
+                .and_then(|html| serde_json::to_string(&sat_detail_lines(&html)).ok())
+        });
+        let Some(page) = body.and_then(|b| serde_json::from_str::<Vec<(String, String)>>(&b).ok())
+        else {
+            stop = "networkError";
+            break;
+        };
+        let page_start = chars + usize::from(!lines.is_empty());
+        let mut first: Option<String> = None;
+        let mut added = 0usize;
+        let mut done = false;
+        for (lid, t) in page {
+            let Some(key) = sat_line_key(&lid) else {
+                continue;
+            };
+            let after_last = match last.as_deref().and_then(sat_line_key) {
+                Some(l) => key > l,
+                None => start_key.as_ref().is_none_or(|s| key >= *s),
+            };
+            if !after_last {
+                continue;
+            }
+            if key.0 != text_no || end_key.as_ref().is_some_and(|e| key > *e) {
+                stop = if key.0 != text_no {
+                    "textChanged"
+                } else {
+                    "end"
+                };
+                done = true;
+                break;
+            }
+            chars += t.chars().count() + usize::from(!lines.is_empty());
+            lines.push(t);
+            first.get_or_insert_with(|| lid.clone());
+            last = Some(lid);
+            added += 1;
+            if end_key.as_ref() == Some(&key) {
+                stop = "end";
+                done = true;
+                break;
+            }
+        }
+        if added > 0 {
+            pages.push(json!({
+                "useid": cur,
+                "firstLine": first,
+                "lastLine": last,
+                "lines": added,
+                "startChar": page_start,
+                "sourceUrl": url,
+            }));
+        }
+        if done {
+            break;
+        }
+        match &last {
+            Some(l) if added > 0 => cur = l.clone(),
+            _ => {
+                stop = "stalled";
+                break;
+            }
+        }
+    }
+    SatRange {
+        text: lines.join("\n"),
+        pages,
+        last_line: last,
+        stop,
+    }
 }
 
 fn title_score(title: &str, query: &str) -> f32 {
@@ -6847,43 +13530,6 @@ fn sat_pick_best_doc(docs: &[serde_json::Value], query: &str) -> (usize, &'stati
     }
 }
 
-fn extract_sat_text(html: &str) -> String {
-    let doc = Html::parse_document(html);
-    // Prefer SAT detail structure: lines are in span.tx; skip line numbers (.ln) and anchors
-    if let Ok(sel) = Selector::parse("span.tx") {
-        let mut lines: Vec<String> = Vec::new();
-        for node in doc.select(&sel) {
-            let t = node.text().collect::<Vec<_>>().join("");
-            let t = t.trim();
-            if !t.is_empty() {
-                lines.push(t.to_string());
-            }
-        }
-        let joined = lines.join("\n");
-        let joined = normalize_ws(&joined);
-        if joined.len() > 50 {
-            return joined;
-        }
-    }
-    // Fallbacks
-    let candidates = vec![
-        "#text", "#viewer", "#content", "main", ".content", ".article", "#main", "#result",
-        "#detail", "#sattext", "pre#text", "pre", ".text", "body",
-    ];
-    for sel in candidates {
-        if let Ok(selector) = Selector::parse(sel) {
-            if let Some(node) = doc.select(&selector).next() {
-                let t = node.text().collect::<Vec<_>>().join("\n");
-                let t = normalize_ws(&t);
-                if t.len() > 50 {
-                    return t;
-                }
-            }
-        }
-    }
-    String::new()
-}
-
 #[derive(Serialize, Clone)]
 struct SatHit {
     title: String,
@@ -6927,17 +13573,8 @@ fn sat_search_results(
     let url = base.to_string();
 
     // Cache raw JSON text with throttle + retry
-    let cpath = cache_path_for(&url);
-    let body = if let Ok(s) = fs::read_to_string(&cpath) {
-        s
-    } else {
-        if let Some(txt) = http_get_with_retry(&url, 3) {
-            let _ = fs::write(&cpath, &txt);
-            txt
-        } else {
-            String::new()
-        }
-    };
+    let cpath = remote_cache_path(&SAT, &url);
+    let body = cached_remote(&cpath, || http_get_with_retry(&url, 3)).unwrap_or_default();
     if body.is_empty() {
         return Vec::new();
     }
@@ -7112,13 +13749,11 @@ fn tipitaka_biblio(xml: &str) -> serde_json::Value {
                     current_buf.clear();
                 }
             }
-            Ok(quick_xml::events::Event::Text(t)) => {
-                if in_p {
-                    if let Ok(tx) = t.decode() {
-                        let s = tx.to_string();
-                        if !s.trim().is_empty() {
-                            current_buf.push_str(&s);
-                        }
+            Ok(quick_xml::events::Event::Text(t)) if in_p => {
+                if let Ok(tx) = t.decode() {
+                    let s = tx.to_string();
+                    if !s.trim().is_empty() {
+                        current_buf.push_str(&s);
                     }
                 }
             }
@@ -7135,24 +13770,274 @@ fn tipitaka_biblio(xml: &str) -> serde_json::Value {
     serde_json::to_value(out).unwrap_or(serde_json::json!({}))
 }
 
-fn normalize_ws(s: &str) -> String {
-    let mut t = s.replace("\r", "");
-    t = t
-        .split('\n')
-        .map(|l| l.trim())
-        .collect::<Vec<_>>()
-        .join("\n");
-    while t.contains("\n\n\n") {
-        t = t.replace("\n\n\n", "\n\n");
-    }
-    t
-}
-
 #[cfg(test)]
 mod tests {
-    use super::{jozen_extract_detail, jozen_parse_search_html, sat_pick_best_doc, slice_text_bounds};
+    use super::{
+        apply_cursor, apply_fetch_pipeline, attach_budget, attach_structured_content, call_key,
+        check_highlight, errors, explain_query, fetch_batch, fetch_options, fetch_slice,
+        handle_cancel, highlight_positions, jozen_extract_detail, jozen_parse_search_html,
+        line_map, next_cursor, register_call, release_call, resource_id, run_call, run_selftest,
+        sat_detail_lines, sat_line_key, sat_pick_best_doc, slice_text_bounds, unit_page,
+        Highlighter, IndexEntry,
+    };
     use serde_json::json;
 
+    #[test]
+    fn resource_ids_use_short_cbeta_numbers() {
+        let entry = |id: &str| IndexEntry {
+            id: id.to_string(),
+            title: String::new(),
+            path: String::new(),
+            meta: None,
+        };
+        assert_eq!(resource_id("cbeta", &entry("T09n0262")), "T0262");
+        assert_eq!(resource_id("cbeta", &entry("X01n0001a")), "X0001a");
+        assert_eq!(resource_id("cbeta", &entry("weird")), "weird");
+        assert_eq!(resource_id("tipitaka", &entry("s0101m.mul")), "s0101m.mul");
+    }
+
+    #[test]
+    fn structured_content_carries_the_tool_data() {
+        let resp = || json!({"jsonrpc": "2.0", "id": 1, "result": {"content": [{"type": "text", "text": "2 results"}], "_meta": {"count": 2, "results": [1, 2]}}});
+        let mut r = resp();
+        attach_structured_content("sat_search", &mut r, true);
+        let sc = &r["result"]["structuredContent"];
+        assert_eq!(
+            (sc["schemaVersion"].as_u64(), sc["tool"].as_str()),
+            (Some(1), Some("sat_search"))
+        );
+        assert_eq!(sc["results"], json!([1, 2]));
+        assert_eq!(r["result"]["_meta"]["count"], 2);
+        let mut r = resp();
+        attach_structured_content("sat_search", &mut r, false);
+        assert!(r["result"].get("_meta").is_none());
+        assert_eq!(r["result"]["structuredContent"]["count"], 2);
+        let mut e = json!({"jsonrpc": "2.0", "id": 1, "error": {"code": -32602}});
+        attach_structured_content("nope", &mut e, true);
+        assert!(e.get("result").is_none());
+    }
+
+    #[test]
+    fn regex_explain_replays_the_search_rewrite() {
+        let (_, meta) = explain_query(&json!({"query": "如是 我聞", "variants": false}));
+        assert_eq!(meta["mode"], "literal");
+        assert_eq!(meta["pattern"], "如是\\s*我聞");
+        assert_eq!(meta["valid"], true);
+        let (text, meta) = explain_query(&json!({"query": "如是(我聞", "corpus": "sarit"}));
+        assert_eq!(meta["tool"], "sarit_search");
+        assert_eq!(meta["valid"], false);
+        assert_eq!(meta["error"]["position"], 2);
+        assert!(text.contains("Compiles: no: unclosed group at character 2"));
+    }
+
+    #[test]
+    fn fetch_batch_reports_each_item() {
+        let bad = fetch_batch(json!(1), "cbeta_fetch_batch", &json!({"items": []}));
+        assert!(errors::is_error_response(&bad));
+        let unknown = fetch_batch(json!(1), "sat_fetch_batch", &json!({"items": [{}]}));
+        assert!(errors::is_error_response(&unknown));
+        let resp = fetch_batch(
+            json!(1),
+            "gretil_fetch_batch",
+            &json!({"items": [{"id": "../a"}, {"id": "b/c"}], "defaults": {"lineNumber": 3}}),
+        );
+        let meta = &resp["result"]["_meta"];
+        assert_eq!(
+            (meta["count"].as_u64(), meta["failed"].as_u64()),
+            (Some(2), Some(2))
+        );
+        assert_eq!(resp["result"]["isError"], true);
+        let first = resp["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(
+            first.starts_with("## 1. ../a lineNumber 3\n\nerror: "),
+            "{}",
+            first
+        );
+    }
+
+    #[test]
+    fn selftest_skips_online_checks_unless_asked() {
+        let (text, meta) = run_selftest(Some(&["SAT".to_string(), "jozen".to_string()]), false);
+        assert_eq!(
+            (meta["skipped"].as_u64(), meta["passed"].as_u64()),
+            (Some(2), Some(0))
+        );
+        assert_eq!(meta["results"][0]["status"], "skip");
+        assert!(text.starts_with("daizo_selftest: 0 passed, 0 failed, 2 skipped"));
+    }
+
+    #[test]
+    fn fetch_cursor_resumes_where_the_slice_ended() {
+        let text = "一二三\n四五六\n七八九";
+        let args = json!({"id": "T0001", "maxChars": 5, "highlight": "五"});
+        let span = fetch_slice(text, &args, false);
+        assert_eq!(
+            (span.text.as_str(), span.end, span.next_line),
+            ("一二三\n四", 5, 2)
+        );
+        let cursor = next_cursor("cbeta_fetch", &args, span.end, span.total).unwrap();
+        let mut next = json!({"cursor": cursor, "maxChars": 100});
+        apply_cursor("cbeta_fetch", &mut next).unwrap();
+        assert_eq!(next["id"], "T0001");
+        assert_eq!(next["highlight"], "五");
+        let rest = fetch_slice(text, &next, false);
+        assert_eq!((rest.text.as_str(), rest.start), ("五六\n七八九", 5));
+        assert!(next_cursor("cbeta_fetch", &next, rest.end, rest.total).is_none());
+        let mut other = json!({"cursor": cursor});
+        assert!(apply_cursor("gretil_fetch", &mut other).is_err());
+        assert!(apply_cursor("cbeta_fetch", &mut json!({"cursor": "zz"})).is_err());
+    }
+
+    #[test]
+    fn snapped_cursors_reach_the_end_of_the_text() {
+        let text = "如是我聞。一時佛在舍衛國祇樹給孤獨園與大比丘眾千二百五十人俱。爾時世尊食時著衣持鉢入舍衛大城乞食";
+        let total = text.chars().count();
+        let mut args = json!({"id": "T0001", "startChar": 3, "maxChars": 10, "snap": "sentence"});
+        let mut covered = String::new();
+        let mut end = 3;
+        for _ in 0..total {
+            let span = fetch_slice(text, &args, false);
+            assert_eq!(
+                span.start, end,
+                "a continuation starts where the last slice ended"
+            );
+            assert!(span.end > span.start);
+            covered.push_str(&span.text);
+            end = span.end;
+            let Some(cursor) = next_cursor("cbeta_fetch", &args, span.end, span.total) else {
+                break;
+            };
+            args = json!({"cursor": cursor});
+            apply_cursor("cbeta_fetch", &mut args).unwrap();
+        }
+        assert_eq!(end, total);
+        assert_eq!(covered, text.chars().skip(3).collect::<String>());
+    }
+
+    #[test]
+    fn cancellations_stay_within_their_session() {
+        let a = call_key(Some("session-a"), &json!(1));
+        let b = call_key(Some("session-b"), &json!(1));
+        let ta = register_call(&a);
+        let tb = register_call(&b);
+        handle_cancel(Some("session-a"), &json!({"requestId": 1}));
+        assert!(ta.is_cancelled());
+        assert!(!tb.is_cancelled());
+        // A call that reused the id keeps its token when the earlier one finishes.
+        let tb2 = register_call(&b);
+        release_call(&b, &tb);
+        handle_cancel(Some("session-b"), &json!({"requestId": 1}));
+        assert!(tb2.is_cancelled() && !tb.is_cancelled());
+        release_call(&a, &ta);
+        release_call(&b, &tb2);
+    }
+
+    #[test]
+    fn bad_cursors_are_invalid_arguments() {
+        let resp = run_call(
+            json!(1),
+            &json!({"name": "cbeta_fetch", "arguments": {"cursor": "zz"}}),
+        );
+        assert!(resp.get("error").is_none());
+        assert_eq!(resp["result"]["isError"], true);
+        let err = &resp["result"]["_meta"]["error"];
+        assert_eq!(err["code"], "INVALID_ARGUMENT");
+        assert_eq!(err["argument"], "cursor");
+        assert_eq!(err["value"], "zz");
+    }
+
+    #[test]
+    fn fetch_slice_fits_a_token_budget() {
+        let text = "如是我聞一時佛在舍衛國";
+        let args = json!({"id": "T0001", "startChar": 2, "maxTokens": 4});
+        let span = fetch_slice(text, &args, false);
+        assert_eq!((span.text.as_str(), span.end), ("我聞一時", 6));
+        let both = json!({"id": "T0001", "maxTokens": 4, "maxChars": 3});
+        assert_eq!(fetch_slice(text, &both, false).text, "如是我");
+        let mut resp = json!({"result": {"content": [{"type": "text", "text": span.text}]}});
+        attach_budget(&args, &mut resp);
+        let meta = &resp["result"]["_meta"];
+        assert_eq!(meta["estTokens"], 4);
+        assert_eq!(meta["budget"]["maxTokens"], 4);
+        assert_eq!(meta["budget"]["fits"], true);
+    }
+
+    #[test]
+    fn fetch_slice_takes_lines_and_maps_them_to_chars() {
+        let text = "如是我聞\n一時佛在\n舍衛國\n祇樹給孤獨園";
+        let args = json!({"id": "T0001", "startLine": 2, "endLine": 3});
+        let span = fetch_slice(text, &args, false);
+        assert_eq!(span.text, "一時佛在\n舍衛國\n");
+        assert_eq!((span.start, span.end, span.requested_end), (5, 14, 14));
+        let map = line_map(text, span.start, span.end);
+        assert_eq!(map, json!({"firstLine": 2, "starts": [5, 10]}));
+        // startChar wins over startLine; a line past the end starts at the end.
+        let both = json!({"id": "T0001", "startChar": 1, "startLine": 3, "maxChars": 2});
+        assert_eq!(fetch_slice(text, &both, false).text, "是我");
+        let past = json!({"id": "T0001", "startLine": 9});
+        assert_eq!(fetch_slice(text, &past, false).start, text.chars().count());
+    }
+
+    #[test]
+    fn fetch_slice_snaps_to_sentences() {
+        let text = "如是我聞。一時佛在舍衛國。爾時世尊告諸比丘。";
+        let args = json!({"id": "T0001", "maxChars": 9, "snap": "sentence"});
+        let span = fetch_slice(text, &args, false);
+        assert_eq!(span.text, "如是我聞。");
+        let mut next = json!({"cursor": next_cursor("cbeta_fetch", &args, span.end, span.total)});
+        apply_cursor("cbeta_fetch", &mut next).unwrap();
+        assert_eq!(fetch_slice(text, &next, false).text, "一時佛在舍衛國。");
+        // An explicit startChar is kept; only the end snaps.
+        let mid = json!({"id": "T0001", "startChar": 7, "endChar": 20, "snap": "sentence"});
+        assert_eq!(fetch_slice(text, &mid, false).text, "佛在舍衛國。");
+    }
+
+    #[test]
+    fn array_highlight_groups_positions_by_pattern() {
+        let args = json!({"id": "T0001", "highlight": ["如是", {"pattern": "佛", "prefix": "<", "suffix": ">"}]});
+        assert!(check_highlight(&args).is_ok());
+        let opts = fetch_options(&args);
+        let out = apply_fetch_pipeline("如是我聞。一時佛在", &opts, &Highlighter::default());
+        assert_eq!(out.text, ">>> 如是 <<<我聞。一時<佛>在");
+        let groups = highlight_positions(&args, &out.highlights, &out.by_pattern);
+        assert_eq!(
+            groups[1],
+            json!({"pattern": "佛", "positions": [{"startChar": 7, "endChar": 8}]})
+        );
+        assert!(check_highlight(&json!({"highlight": [{"prefix": "<"}]})).is_err());
+    }
+
+    #[test]
+    fn page_by_steps_through_units() {
+        let xml = "<TEI><text><body><div><head>序品</head><p>如是我聞。</p></div>\
+<div><head>方便品</head><p>爾時世尊。</p><lg><l>諸佛智慧</l></lg></div></body></text></TEI>";
+        let extract = |frag: &str| daizo_core::extract_text_opts(frag, false);
+        let args = json!({"id": "X", "pageBy": "head", "page": 1});
+        let page = unit_page(&args, xml, extract).unwrap().unwrap();
+        assert_eq!(page.text, "方便品爾時世尊。諸佛智慧");
+        assert_eq!(page.method, "page-by-head");
+        assert_eq!(page.meta["pageCount"], 2);
+        assert_eq!(page.meta["titles"], json!(["方便品"]));
+        assert!(page.meta["nextPage"].is_null());
+        let paras = json!({"id": "X", "pageBy": "paragraph", "pageSize": 2});
+        let page = unit_page(&paras, xml, extract).unwrap().unwrap();
+        assert_eq!(page.text, "序品如是我聞。方便品爾時世尊。");
+        assert_eq!(page.meta["nextPage"], 1);
+        let past = json!({"id": "X", "pageBy": "juan", "page": 1});
+        assert!(unit_page(&past, xml, extract).unwrap().is_err());
+        // The cursor of a unit page resumes inside the same page.
+        let cursor = next_cursor("cbeta_fetch", &args, 3, 10).unwrap();
+        let mut next = json!({"cursor": cursor});
+        apply_cursor("cbeta_fetch", &mut next).unwrap();
+        assert_eq!(
+            (next["page"].clone(), next["startChar"].clone()),
+            (json!(1), json!(3))
+        );
+        assert!(fetch_slice("方便品爾時世尊", &next, false)
+            .text
+            .starts_with("爾時"));
+    }
+
     #[test]
     fn slice_text_bounds_handles_multibyte_characters() {
         let text = "大般若經初會序";
@@ -7184,6 +14069,25 @@ mod tests {
         assert_eq!(end_full, total_full);
     }
 
+    #[test]
+    fn sat_detail_lines_pair_line_ids_with_text() {
+        let html = r#"<div id="text">
+<span class="ln" id="0262_,09,0001c14">0262_,09,0001c14:</span><span class="tx">如是我聞。</span><br>
+<span class="ln">0262_,09,0001c15:</span><span class="tx">一時佛住</span><span class="tx">王舍城</span><br>
+</div>"#;
+        let lines = sat_detail_lines(html);
+        assert_eq!(
+            lines,
+            [
+                ("0262_,09,0001c14".to_string(), "如是我聞。".to_string()),
+                ("0262_,09,0001c15".to_string(), "一時佛住王舍城".to_string()),
+            ]
+        );
+        let a = sat_line_key("0262_,09,0001c15").unwrap();
+        assert!(a < sat_line_key("0262_,09,0002a01").unwrap());
+        assert!(sat_line_key("x").is_none());
+    }
+
     #[test]
     fn sat_pick_best_doc_prefers_body_contains() {
         let docs = vec![
@@ -7264,37 +14168,232 @@ mod tests {
         assert_eq!(d.textno.as_deref(), Some("J0100"));
         assert_eq!(d.page_prev.as_deref(), Some("J01_0199"));
         assert_eq!(d.page_next.as_deref(), Some("J01_0201"));
-        assert_eq!(d.line_ids, vec!["J01_0200A01".to_string(), "J01_0200B19".to_string()]);
+        assert_eq!(
+            d.line_ids,
+            vec!["J01_0200A01".to_string(), "J01_0200B19".to_string()]
+        );
         assert!(d.content.contains("[J01_0200A01] 般舟三昧經"));
         assert!(d.content.contains("[J01_0200B19] 藥師如來本願經"));
     }
 }
 
-fn main() -> Result<()> {
-    // Initialize optional repo policy from env (rate limits / future robots compliance)
-    daizo_core::repo::init_policy_from_env();
-    let stdin = std::io::stdin();
-    let mut stdin = BufReader::new(stdin.lock());
-    let mut stdout = std::io::stdout();
-    loop {
-        let Some(msg) = read_message(&mut stdin)? else {
-            break;
+// ============ Cancellation ============
+
+/// A call in flight: its session (`Mcp-Session-Id`, None over stdio) and JSON-RPC id. HTTP
+/// sessions number their requests independently, so the id alone does not name a call.
+type CallKey = (Option<String>, String);
+
+/// Cancellation tokens of the tool calls in flight.
+static IN_FLIGHT: OnceLock<Mutex<std::collections::HashMap<CallKey, CancelToken>>> =
+    OnceLock::new();
+/// Tool calls run on their own threads; `dispatch_tool` needs more than the default stack.
+const CALL_STACK_SIZE: usize = 16 * 1024 * 1024;
+
+fn in_flight() -> &'static Mutex<std::collections::HashMap<CallKey, CancelToken>> {
+    IN_FLIGHT.get_or_init(Default::default)
+}
+
+fn call_key(session: Option<&str>, id: &serde_json::Value) -> CallKey {
+    (session.map(str::to_string), id.to_string())
+}
+
+/// Record a new call under `key`, replacing any earlier call of the same key.
+fn register_call(key: &CallKey) -> CancelToken {
+    let token = CancelToken::new();
+    if let Ok(mut m) = in_flight().lock() {
+        m.insert(key.clone(), token.clone());
+    }
+    token
+}
+
+/// Forget the call of `token`; a later call that reused its key is left alone.
+fn release_call(key: &CallKey, token: &CancelToken) {
+    if let Ok(mut m) = in_flight().lock() {
+        if m.get(key).is_some_and(|t| t.is_same(token)) {
+            m.remove(key);
+        }
+    }
+}
+
+/// Cancel the call of `session` named by `notifications/cancelled` (`requestId`) or
+/// `$/cancelRequest` (`id`).
+fn handle_cancel(session: Option<&str>, params: &serde_json::Value) {
+    let Some(rid) = params.get("requestId").or_else(|| params.get("id")) else {
+        return;
+    };
+    if let Some(token) = in_flight()
+        .lock()
+        .ok()
+        .and_then(|m| m.get(&call_key(session, rid)).cloned())
+    {
+        tracing::debug!(id = %rid, "cancel");
+        token.cancel();
+    }
+}
+
+/// Reporter sending `notifications/progress` for a call that carried `_meta.progressToken`.
+/// MCP requires `progress` to increase, so each new task (clone phase, index build) continues
+/// from where the previous one ended.
+fn progress_reporter(params: &serde_json::Value, out: Arc<dyn Transport>) -> Option<Reporter> {
+    let token = params.get("_meta")?.get("progressToken")?.clone();
+    let state: Mutex<(String, u64, u64)> = Mutex::new((String::new(), 0, 0));
+    Some(Reporter::new(move |p: &Progress| {
+        let Ok(mut st) = state.lock() else {
+            return;
         };
+        let (task, base, last_end) = &mut *st;
+        if *task != p.task {
+            *base = *last_end;
+            *task = p.task.clone();
+        }
+        let progress = *base + p.done;
+        *last_end = *base + p.total.unwrap_or(p.done).max(p.done);
+        let mut params = json!({
+            "progressToken": token,
+            "progress": progress,
+            "message": p.message,
+        });
+        if p.total.is_some() {
+            params["total"] = json!(*last_end);
+        }
+        let note = json!({"jsonrpc": "2.0", "method": "notifications/progress", "params": params});
+        if let Err(e) = out.send(&note) {
+            tracing::debug!(error = %e, "progress notification not sent");
+        }
+    }))
+}
+
+/// Run a tools/call on its own thread so cancellations can be read while it works.
+/// A cancelled call gets no response.
+fn spawn_call(
+    req: Request,
+    out: Arc<dyn Transport>,
+) -> std::io::Result<std::thread::JoinHandle<()>> {
+    let key = call_key(out.session(), &req.id);
+    let token = register_call(&key);
+    std::thread::Builder::new()
+        .stack_size(CALL_STACK_SIZE)
+        .spawn(move || {
+            let call = || token.scope(|| handle_call(req.id, &req.params));
+            let resp = match progress_reporter(&req.params, out.clone()) {
+                Some(r) => r.scope(call),
+                None => call(),
+            };
+            release_call(&key, &token);
+            if token.is_cancelled() {
+                tracing::debug!(id = %key.1, "cancelled; response dropped");
+            } else if let Err(e) = out.send(&resp) {
+                tracing::warn!(id = %key.1, error = %e, "response not sent");
+            }
+        })
+}
+
+/// Answer the messages read from `t` on the same transport until it is exhausted.
+/// Shared by stdio and by each HTTP POST.
+fn serve(t: Arc<dyn Transport>) -> Result<()> {
+    let mut calls: Vec<std::thread::JoinHandle<()>> = Vec::new();
+    while let Some(msg) = t.recv()? {
+        let method = msg.get("method").and_then(|v| v.as_str()).unwrap_or("");
+        if method == "notifications/cancelled" || method == "$/cancelRequest" {
+            handle_cancel(t.session(), &msg["params"]);
+            continue;
+        }
         if let Ok(req) = serde_json::from_value::<Request>(msg.clone()) {
-            dbg_log(&format!("[recv] method={} id={}", req.method, req.id));
+            tracing::debug!(method = %req.method, id = %req.id, "recv");
+            if req.method == "tools/call" {
+                calls.retain(|h| !h.is_finished());
+                calls.push(spawn_call(req, t.clone())?);
+                continue;
+            }
             let resp = match req.method.as_str() {
-                "initialize" => handle_initialize(req.id),
+                "initialize" => handle_initialize(req.id, &req.params),
                 "tools/list" => handle_tools_list(req.id),
-                "tools/call" => handle_call(req.id, &req.params),
+                "resources/list" => handle_resources_list(req.id, &req.params),
+                "resources/templates/list" => handle_resource_templates_list(req.id),
+                "resources/read" => handle_resources_read(req.id, &req.params),
                 _ => {
                     json!({"jsonrpc":"2.0","id":req.id,"error":{"code": -32601, "message":"Method not found"}})
                 }
             };
-            write_message(&mut stdout, &resp)?;
+            t.send(&resp)?;
+            if req.method == "initialize" {
+                start_warmup();
+            }
         } else {
             // ignore non-request messages
-            dbg_log("[recv] non-request/ignored");
+            tracing::debug!("recv: non-request ignored");
         }
     }
+    // Let calls still running finish and answer before returning.
+    for h in calls {
+        let _ = h.join();
+    }
     Ok(())
 }
+
+/// Reload settings on SIGHUP. The handler only sets a flag; a watcher thread does the work.
+#[cfg(unix)]
+fn reload_on_sighup() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    static HUP: AtomicBool = AtomicBool::new(false);
+    extern "C" fn on_hup(_: libc::c_int) {
+        HUP.store(true, Ordering::SeqCst);
+    }
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+    unsafe {
+        libc::signal(libc::SIGHUP, on_hup as *const () as libc::sighandler_t);
+    }
+    let _ = std::thread::Builder::new()
+        .name("sighup".into())
+        .spawn(|| loop {
+            std::thread::sleep(Duration::from_millis(500));
+            if HUP.swap(false, Ordering::SeqCst) {
+                match reload_settings() {
+                    (true, msg, _) => tracing::info!("SIGHUP: {}", msg),
+                    (false, msg, _) => tracing::warn!("SIGHUP: {}", msg),
+                }
+            }
+        });
+}
+
+/// `--flag value` or `--flag=value`: None when the flag is absent, Some(None) when it has
+/// no value.
+fn flag_value(args: &[String], flag: &str) -> Option<Option<String>> {
+    args.iter()
+        .enumerate()
+        .find_map(|(i, a)| match a.strip_prefix(flag) {
+            Some("") => Some(args.get(i + 1).filter(|v| !v.starts_with("--")).cloned()),
+            Some(v) => v.strip_prefix('=').map(|v| Some(v.to_string())),
+            None => None,
+        })
+}
+
+fn main() -> Result<()> {
+    // Initialize optional repo policy from env (pacing of git clones and fetches; HTTP
+    // requests follow daizo_core::politeness)
+    daizo_core::repo::init_policy_from_env();
+    logging::init();
+    #[cfg(unix)]
+    reload_on_sighup();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let http =
+        flag_value(&args, "--http").map(|v| v.unwrap_or_else(|| "127.0.0.1:8080".to_string()));
+    let group_list = |flag: &str, env: &str| {
+        flag_value(&args, flag)
+            .map(|v| v.unwrap_or_default())
+            .or_else(|| std::env::var(env).ok().filter(|s| !s.is_empty()))
+    };
+    let caps = Capabilities::parse(
+        group_list("--enable", "DAIZO_ENABLE").as_deref(),
+        group_list("--disable", "DAIZO_DISABLE").as_deref(),
+    )
+    .unwrap_or_else(|e| {
+        eprintln!("daizo-mcp: {}", e);
+        std::process::exit(2)
+    });
+    let _ = CAPABILITIES.set(caps);
+    match http {
+        Some(addr) => transport::serve_http(&addr, serve),
+        None => serve(Arc::new(StdioTransport::new())),
+    }
+}
@@ -0,0 +1,222 @@
+// ============ Response shaping ============
+//
+// What a fetch returns when the requested text (a juan, a section, `full`, or an explicit
+// `endChar`/page) is larger than the call's budget. By default the slice is cut at the budget
+// and `_meta.nextCursor` continues it; `onOverflow: "outline"` returns the outline of the
+// requested range instead: each section's title and range, its first `outlineChars` chars and
+// a cursor that reads it.
+
+use crate::errors::ToolError;
+use crate::{call_max_chars, call_max_tokens, next_cursor, FetchSlice};
+use daizo_core::tokens;
+use serde_json::json;
+
+const OUTLINE_CHARS: usize = 200;
+/// Headings shorter than this (chars) are too likely to occur in running text to anchor on.
+const MIN_TITLE_CHARS: usize = 2;
+
+/// `onOverflow`: `truncate` (default) or `outline`.
+pub fn check_on_overflow(args: &serde_json::Value) -> Result<(), ToolError> {
+    match args.get("onOverflow").and_then(|v| v.as_str()) {
+        None | Some("truncate") | Some("outline") => Ok(()),
+        Some(s) => Err(ToolError::InvalidArgument {
+            name: "onOverflow".to_string(),
+            value: s.to_string(),
+            expected: "truncate or outline".to_string(),
+        }),
+    }
+}
+
+/// A titled range of extracted text, in chars.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section {
+    pub title: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Char offsets at which the headings `heads` (in document order) occur in `text`. Each is
+/// looked for after the previous one, so a title quoted before its section is not taken.
+pub fn heading_starts(text: &str, heads: &[String]) -> Vec<(String, usize)> {
+    let mut out = Vec::new();
+    let (mut byte, mut chars) = (0usize, 0usize);
+    for h in heads {
+        let title = h.trim();
+        if title.chars().count() < MIN_TITLE_CHARS {
+            continue;
+        }
+        if let Some(i) = text[byte..].find(title) {
+            chars += text[byte..byte + i].chars().count();
+            byte += i;
+            out.push((title.to_string(), chars));
+        }
+    }
+    out
+}
+
+/// Sections of chars `from..to` of `text`: one per heading that starts inside the range, the
+/// text before the first of them included. Without at least two headings the range is cut
+/// into pages of `page` chars, each titled by its first line.
+pub fn sections(text: &str, heads: &[String], from: usize, to: usize, page: usize) -> Vec<Section> {
+    let starts: Vec<(String, usize)> = heading_starts(text, heads)
+        .into_iter()
+        .filter(|(_, c)| *c >= from && *c < to)
+        .collect();
+    let mut out = Vec::new();
+    if starts.len() >= 2 {
+        if starts[0].1 > from {
+            out.push((first_line(text, from), from));
+        }
+        out.extend(starts);
+    } else {
+        out.extend(
+            (from..to)
+                .step_by(page.max(1))
+                .map(|c| (first_line(text, c), c)),
+        );
+    }
+    let ends: Vec<usize> = out.iter().skip(1).map(|(_, c)| *c).chain([to]).collect();
+    out.into_iter()
+        .zip(ends)
+        .map(|((title, start), end)| Section { title, start, end })
+        .collect()
+}
+
+fn first_line(text: &str, from: usize) -> String {
+    let line: String = text
+        .chars()
+        .skip(from)
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| *c != '\n')
+        .take(40)
+        .collect();
+    line.trim().to_string()
+}
+
+/// The outline response for a fetch whose requested range did not fit, or None when the
+/// call did not ask for one (`onOverflow: "outline"`) or everything fit. `heads` is only
+/// called when an outline is built.
+pub fn overflow_outline(
+    id: &serde_json::Value,
+    tool: &str,
+    args: &serde_json::Value,
+    text: &str,
+    span: &FetchSlice,
+    heads: impl FnOnce() -> Vec<String>,
+) -> Option<serde_json::Value> {
+    if args.get("onOverflow").and_then(|v| v.as_str()) != Some("outline")
+        || span.end >= span.requested_end
+    {
+        return None;
+    }
+    let budget = call_max_chars(args).max(1);
+    let secs = sections(text, &heads(), span.start, span.requested_end, budget);
+    let n = secs.len().max(1);
+    let wanted = args
+        .get("outlineChars")
+        .and_then(|v| v.as_u64())
+        .map_or(OUTLINE_CHARS, |v| v as usize);
+    // The previews share the budget; titles are always listed.
+    let per = wanted.min(budget / n);
+    let per_tokens = call_max_tokens(args).map(|t| t / n);
+    // Cursors read the section as text, not as another outline.
+    let mut read_args = args.clone();
+    if let Some(o) = read_args.as_object_mut() {
+        o.remove("onOverflow");
+        o.remove("full");
+    }
+    let mut body = format!(
+        "[outline] chars {}–{} ({} chars) do not fit the budget ({} chars{}): {} sections. Read one with its cursor (_meta.sections[i].cursor).\n",
+        span.start,
+        span.requested_end,
+        span.requested_end - span.start,
+        budget,
+        call_max_tokens(args).map_or(String::new(), |t| format!(", {} tokens", t)),
+        secs.len()
+    );
+    let mut listed = Vec::new();
+    for s in &secs {
+        let sec_text: String = text.chars().skip(s.start).take(s.end - s.start).collect();
+        let mut take = per.min(sec_text.chars().count());
+        if let Some(t) = per_tokens {
+            take = take.min(tokens::chars_within(&sec_text, t));
+        }
+        let preview: String = sec_text.chars().take(take).collect();
+        let preview = preview.trim();
+        body.push_str(&format!("\n## {} (chars {}–{})\n", s.title, s.start, s.end));
+        if !preview.is_empty() {
+            body.push_str(preview);
+            if take < sec_text.chars().count() {
+                body.push('…');
+            }
+            body.push('\n');
+        }
+        listed.push(json!({
+            "title": s.title,
+            "startChar": s.start,
+            "endChar": s.end,
+            "chars": s.end - s.start,
+            "estTokens": tokens::estimate(&sec_text),
+            "cursor": next_cursor(tool, &read_args, s.start, span.total),
+        }));
+    }
+    let meta = json!({
+        "onOverflow": "outline",
+        "totalChars": span.total,
+        "requestedStart": span.start,
+        "requestedEnd": span.requested_end,
+        "truncated": true,
+        "sections": listed,
+        "nextCursor": next_cursor(tool, &read_args, span.start, span.total),
+    });
+    Some(
+        json!({"jsonrpc":"2.0","id": id, "result": { "content": [{"type":"text","text": body}], "_meta": meta }}),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_follow_headings_or_fall_back_to_pages() {
+        let text = "序\n序品第一\n如是我聞\n方便品第二\n爾時世尊";
+        let heads = vec!["序品第一".to_string(), "方便品第二".to_string()];
+        let secs = sections(text, &heads, 0, text.chars().count(), 100);
+        let got: Vec<(&str, usize, usize)> = secs
+            .iter()
+            .map(|s| (s.title.as_str(), s.start, s.end))
+            .collect();
+        assert_eq!(
+            got,
+            vec![("序", 0, 2), ("序品第一", 2, 12), ("方便品第二", 12, 22)]
+        );
+        let pages = sections(text, &[], 0, 22, 10);
+        assert_eq!(pages.len(), 3);
+        assert_eq!((pages[1].start, pages[1].end), (10, 20));
+        assert_eq!(pages[1].title, "聞");
+    }
+
+    #[test]
+    fn outline_replaces_a_slice_that_does_not_fit() {
+        let text = "序品第一\n如是我聞一時佛住\n方便品第二\n爾時世尊從三昧";
+        let heads = || vec!["序品第一".to_string(), "方便品第二".to_string()];
+        let args =
+            json!({"id": "T0262", "maxChars": 12, "onOverflow": "outline", "outlineChars": 4});
+        let span = crate::fetch_slice(text, &args, false);
+        let resp = overflow_outline(&json!(1), "cbeta_fetch", &args, text, &span, heads).unwrap();
+        let meta = &resp["result"]["_meta"];
+        assert_eq!(meta["sections"].as_array().unwrap().len(), 2);
+        assert_eq!(meta["sections"][1]["startChar"], 14);
+        let body = resp["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(body.contains("## 方便品第二 (chars 14–27)\n方便品第…"));
+        // The cursor reads the section as text.
+        let mut next = json!({"cursor": meta["sections"][1]["cursor"]});
+        crate::apply_cursor("cbeta_fetch", &mut next).unwrap();
+        assert_eq!(next["startChar"], 14);
+        assert!(next.get("onOverflow").is_none());
+        let fits = json!({"id": "T0262", "onOverflow": "outline"});
+        let span = crate::fetch_slice(text, &fits, false);
+        assert!(overflow_outline(&json!(1), "cbeta_fetch", &fits, text, &span, heads).is_none());
+    }
+}
@@ -0,0 +1,540 @@
+// ============ MCP transports ============
+//
+// stdio (LSP-style Content-Length frames or newline-delimited JSON, detected from the first
+// message) and the MCP streamable-HTTP transport. Both hand messages to the same dispatcher
+// (`crate::serve`) through the `Transport` trait.
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::{HashSet, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// A bidirectional JSON-RPC channel.
+pub trait Transport: Send + Sync {
+    /// Next incoming message, or None once the peer has nothing more to send.
+    fn recv(&self) -> Result<Option<Value>>;
+    /// Send a response or notification. Safe to call from several threads.
+    fn send(&self, v: &Value) -> Result<()>;
+    /// The session the messages belong to (`Mcp-Session-Id`); None outside HTTP.
+    fn session(&self) -> Option<&str> {
+        None
+    }
+}
+
+// ---------- stdio ----------
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FramingMode {
+    Lsp,
+    Lines,
+}
+
+pub struct StdioTransport {
+    input: Mutex<BufReader<std::io::Stdin>>,
+    /// Framing of the first message; replies use the same one.
+    mode: OnceLock<FramingMode>,
+    /// Serializes messages written by concurrent tool calls.
+    output: Mutex<()>,
+}
+
+impl StdioTransport {
+    pub fn new() -> Self {
+        StdioTransport {
+            input: Mutex::new(BufReader::new(std::io::stdin())),
+            mode: OnceLock::new(),
+            output: Mutex::new(()),
+        }
+    }
+}
+
+impl Transport for StdioTransport {
+    fn recv(&self) -> Result<Option<Value>> {
+        let mut input = self.input.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(read_message(&mut *input)?.map(|(v, mode)| {
+            let _ = self.mode.set(mode);
+            v
+        }))
+    }
+
+    fn send(&self, v: &Value) -> Result<()> {
+        let _guard = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        let mode = *self.mode.get().unwrap_or(&FramingMode::Lsp);
+        write_message(&mut std::io::stdout(), mode, v)
+    }
+}
+
+/// Read one message and report the framing it came in.
+pub fn read_message(stdin: &mut impl BufRead) -> Result<Option<(Value, FramingMode)>> {
+    // Try to read one logical unit. Support two modes:
+    // 1) LSP-style headers with Content-Length and blank line
+    // 2) Single-line JSON (newline-delimited JSON)
+
+    let mut line = String::new();
+    let n = stdin.read_line(&mut line)?;
+    if n == 0 {
+        return Ok(None);
+    }
+
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('{') {
+        // newline-delimited JSON
+        tracing::trace!(line = line.trim_end(), "recv lines");
+        let v: Value = serde_json::from_str(line.trim_end())?;
+        return Ok(Some((v, FramingMode::Lines)));
+    }
+
+    // Otherwise, collect headers until blank line, include the first line we read
+    let mut headers = String::new();
+    headers.push_str(&line);
+    loop {
+        if line == "\n" || line == "\r\n" || line.trim().is_empty() {
+            break;
+        }
+        line.clear();
+        let n = stdin.read_line(&mut line)?;
+        if n == 0 {
+            break;
+        }
+        headers.push_str(&line);
+        if line == "\n" || line == "\r\n" || line.trim().is_empty() {
+            break;
+        }
+    }
+    tracing::trace!(headers = headers.trim_end(), "recv lsp headers");
+
+    // parse Content-Length
+    let mut content_length = 0usize;
+    for hline in headers.lines() {
+        let h = hline.trim();
+        if h.to_lowercase().starts_with("content-length:") {
+            if let Some(v) = h.split(':').nth(1) {
+                content_length = v.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    if content_length == 0 {
+        tracing::debug!("recv lsp: empty body skipped");
+        return Ok(Some((Value::Null, FramingMode::Lsp)));
+    }
+    let mut content = vec![0u8; content_length];
+    stdin.read_exact(&mut content)?;
+    tracing::trace!(bytes = content_length, "recv lsp body");
+    let v: Value = serde_json::from_slice(&content)?;
+    Ok(Some((v, FramingMode::Lsp)))
+}
+
+pub fn write_message(stdout: &mut impl Write, mode: FramingMode, v: &Value) -> Result<()> {
+    match mode {
+        FramingMode::Lines => {
+            let body = serde_json::to_string(v)?;
+            writeln!(stdout, "{}", body)?;
+            stdout.flush()?;
+            tracing::trace!(bytes = body.len(), "send lines");
+        }
+        FramingMode::Lsp => {
+            let body = serde_json::to_vec(v)?;
+            write!(
+                stdout,
+                "Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\n",
+                body.len()
+            )?;
+            stdout.write_all(&body)?;
+            stdout.flush()?;
+            tracing::trace!(bytes = body.len(), "send lsp");
+        }
+    }
+    Ok(())
+}
+
+// ---------- streamable HTTP ----------
+
+/// Path of the MCP endpoint.
+pub const HTTP_ENDPOINT: &str = "/mcp";
+/// Largest accepted request body.
+const MAX_BODY: usize = 8 * 1024 * 1024;
+/// Connection threads answer non-tool requests inline (resources/read can recurse deeply).
+const CONN_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+static SESSIONS: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+
+fn sessions() -> &'static Mutex<HashSet<String>> {
+    SESSIONS.get_or_init(Default::default)
+}
+
+/// 128 random bits from the OS, so one client cannot guess another's session.
+fn new_session_id() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("OS random number generator");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// One POST: the messages of its body, and where the replies go.
+struct HttpExchange {
+    incoming: Mutex<VecDeque<Value>>,
+    out: Mutex<HttpOut>,
+    /// The POST's session, or the one its `initialize` opens.
+    session: Option<String>,
+}
+
+enum HttpOut {
+    /// `text/event-stream`: every message (progress notifications too) becomes an SSE event.
+    Sse(TcpStream),
+    /// `application/json`: responses are collected and written once the exchange is done.
+    Buffered(Vec<Value>),
+}
+
+impl Transport for HttpExchange {
+    fn recv(&self) -> Result<Option<Value>> {
+        Ok(self
+            .incoming
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front())
+    }
+
+    fn send(&self, v: &Value) -> Result<()> {
+        match &mut *self.out.lock().unwrap_or_else(|e| e.into_inner()) {
+            HttpOut::Sse(stream) => {
+                write!(
+                    stream,
+                    "event: message\ndata: {}\n\n",
+                    serde_json::to_string(v)?
+                )?;
+                stream.flush()?;
+            }
+            HttpOut::Buffered(out) => {
+                if v.get("id").is_some() {
+                    out.push(v.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+    fn session(&self) -> Option<&str> {
+        self.session.as_deref()
+    }
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Read a request with a Content-Length body (chunked uploads are not supported).
+fn read_http_request(stream: &TcpStream) -> std::result::Result<HttpRequest, u16> {
+    let mut r = BufReader::new(stream);
+    let mut line = String::new();
+    r.read_line(&mut line).map_err(|_| 400u16)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(400);
+    };
+    let path = target.split('?').next().unwrap_or("").to_string();
+    let method = method.to_string();
+    let mut headers = Vec::new();
+    loop {
+        let mut h = String::new();
+        if r.read_line(&mut h).map_err(|_| 400u16)? == 0 {
+            return Err(400);
+        }
+        let h = h.trim_end();
+        if h.is_empty() {
+            break;
+        }
+        if let Some((k, v)) = h.split_once(':') {
+            headers.push((k.trim().to_string(), v.trim().to_string()));
+        }
+    }
+    let mut req = HttpRequest {
+        method,
+        path,
+        headers,
+        body: Vec::new(),
+    };
+    if req
+        .header("transfer-encoding")
+        .is_some_and(|v| v.eq_ignore_ascii_case("chunked"))
+    {
+        return Err(411);
+    }
+    let len: usize = req
+        .header("content-length")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    if len > MAX_BODY {
+        return Err(413);
+    }
+    req.body = vec![0u8; len];
+    r.read_exact(&mut req.body).map_err(|_| 400u16)?;
+    Ok(req)
+}
+
+fn status_text(code: u16) -> &'static str {
+    match code {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        _ => "Error",
+    }
+}
+
+fn write_http(
+    stream: &mut TcpStream,
+    code: u16,
+    headers: &[(&str, &str)],
+    body: Option<(&str, &[u8])>,
+) -> std::io::Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nConnection: close\r\n",
+        code,
+        status_text(code)
+    );
+    for (k, v) in headers {
+        head.push_str(&format!("{}: {}\r\n", k, v));
+    }
+    match body {
+        Some((ctype, b)) => head.push_str(&format!(
+            "Content-Type: {}\r\nContent-Length: {}\r\n\r\n",
+            ctype,
+            b.len()
+        )),
+        None => head.push_str("Content-Length: 0\r\n\r\n"),
+    }
+    stream.write_all(head.as_bytes())?;
+    if let Some((_, b)) = body {
+        stream.write_all(b)?;
+    }
+    stream.flush()
+}
+
+fn write_rpc_error(stream: &mut TcpStream, code: u16, rpc_code: i64, msg: &str) {
+    let body = json!({"jsonrpc": "2.0", "id": null, "error": {"code": rpc_code, "message": msg}});
+    let _ = write_http(
+        stream,
+        code,
+        &[],
+        Some(("application/json", body.to_string().as_bytes())),
+    );
+}
+
+/// Browsers send `Origin`; only local pages may talk to a local server (DNS rebinding).
+fn origin_allowed(origin: Option<&str>) -> bool {
+    let Some(o) = origin else {
+        return true;
+    };
+    let host = url::Url::parse(o)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()));
+    matches!(
+        host.as_deref(),
+        Some("localhost" | "127.0.0.1" | "[::1]" | "::1")
+    )
+}
+
+fn handle_connection(mut stream: TcpStream, serve: fn(Arc<dyn Transport>) -> Result<()>) {
+    let req = match read_http_request(&stream) {
+        Ok(r) => r,
+        Err(code) => {
+            let _ = write_http(&mut stream, code, &[], None);
+            return;
+        }
+    };
+    tracing::debug!(method = %req.method, path = %req.path, "http request");
+    if req.path != HTTP_ENDPOINT {
+        let _ = write_http(&mut stream, 404, &[], None);
+        return;
+    }
+    if !origin_allowed(req.header("origin")) {
+        let _ = write_http(&mut stream, 403, &[], None);
+        return;
+    }
+    let session = req.header("mcp-session-id").map(|s| s.to_string());
+    let known = |s: &str| sessions().lock().map(|m| m.contains(s)).unwrap_or(false);
+    match req.method.as_str() {
+        "POST" => {}
+        "DELETE" => {
+            let code = match &session {
+                Some(s) if sessions().lock().map(|mut m| m.remove(s)).unwrap_or(false) => 200,
+                _ => 404,
+            };
+            let _ = write_http(&mut stream, code, &[], None);
+            return;
+        }
+        // No server-initiated stream: everything is sent on the POST that caused it.
+        _ => {
+            let _ = write_http(&mut stream, 405, &[("Allow", "POST, DELETE")], None);
+            return;
+        }
+    }
+    if let Some(s) = &session {
+        if !known(s) {
+            write_rpc_error(&mut stream, 404, -32001, "Unknown session");
+            return;
+        }
+    }
+    let (messages, batch) = match serde_json::from_slice::<Value>(&req.body) {
+        Ok(Value::Array(a)) => (a, true),
+        Ok(v @ Value::Object(_)) => (vec![v], false),
+        _ => {
+            write_rpc_error(&mut stream, 400, -32700, "Parse error");
+            return;
+        }
+    };
+    let is_request = |m: &Value| m.get("method").is_some() && m.get("id").is_some();
+    let has_requests = messages.iter().any(is_request);
+    let mut headers: Vec<(&str, String)> = Vec::new();
+    let mut session = session;
+    if messages
+        .iter()
+        .any(|m| is_request(m) && m["method"] == "initialize")
+    {
+        let id = new_session_id();
+        if let Ok(mut m) = sessions().lock() {
+            m.insert(id.clone());
+        }
+        session = Some(id.clone());
+        headers.push(("Mcp-Session-Id", id));
+    }
+    let accept = req.header("accept").unwrap_or("*/*").to_ascii_lowercase();
+    let sse = has_requests && accept.contains("text/event-stream");
+    if has_requests && !sse && !accept.contains("application/json") && !accept.contains("*/*") {
+        let _ = write_http(&mut stream, 406, &[], None);
+        return;
+    }
+    let header_refs: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    let out = if sse {
+        let mut head =
+            "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n"
+                .to_string();
+        for (k, v) in &header_refs {
+            head.push_str(&format!("{}: {}\r\n", k, v));
+        }
+        head.push_str("\r\n");
+        if stream.write_all(head.as_bytes()).is_err() {
+            return;
+        }
+        match stream.try_clone() {
+            Ok(s) => HttpOut::Sse(s),
+            Err(_) => return,
+        }
+    } else {
+        HttpOut::Buffered(Vec::new())
+    };
+    let exchange = Arc::new(HttpExchange {
+        incoming: Mutex::new(messages.into()),
+        out: Mutex::new(out),
+        session,
+    });
+    if let Err(e) = serve(exchange.clone()) {
+        tracing::warn!(error = %e, "http exchange failed");
+    }
+    if sse {
+        return;
+    }
+    let replies = match &*exchange.out.lock().unwrap_or_else(|e| e.into_inner()) {
+        HttpOut::Buffered(v) => v.clone(),
+        HttpOut::Sse(_) => Vec::new(),
+    };
+    if !has_requests || replies.is_empty() {
+        let _ = write_http(&mut stream, 202, &header_refs, None);
+        return;
+    }
+    let body = if batch {
+        Value::Array(replies)
+    } else {
+        replies.into_iter().next().unwrap_or(Value::Null)
+    };
+    let _ = write_http(
+        &mut stream,
+        200,
+        &header_refs,
+        Some(("application/json", body.to_string().as_bytes())),
+    );
+}
+
+/// Serve the streamable-HTTP transport on `addr` (e.g. `127.0.0.1:8080`), one thread per
+/// connection. Each POST body is dispatched by `serve` like a short stdio session.
+pub fn serve_http(addr: &str, serve: fn(Arc<dyn Transport>) -> Result<()>) -> Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!(
+        "[daizo-mcp] listening on http://{}{}",
+        listener.local_addr()?,
+        HTTP_ENDPOINT
+    );
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let spawned = std::thread::Builder::new()
+            .stack_size(CONN_STACK_SIZE)
+            .spawn(move || handle_connection(stream, serve));
+        if let Err(e) = spawned {
+            tracing::warn!(error = %e, "http connection thread not spawned");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn framing_is_detected_per_message() {
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#;
+        let input = format!("{}\nContent-Length: {}\r\n\r\n{}", body, body.len(), body);
+        let mut r = std::io::Cursor::new(input.into_bytes());
+        let (v, mode) = read_message(&mut r).unwrap().unwrap();
+        assert_eq!((v["id"].as_i64(), mode), (Some(1), FramingMode::Lines));
+        let (_, mode) = read_message(&mut r).unwrap().unwrap();
+        assert_eq!(mode, FramingMode::Lsp);
+        assert!(read_message(&mut r).unwrap().is_none());
+
+        let mut out = Vec::new();
+        write_message(&mut out, FramingMode::Lsp, &json!({"id": 1})).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .starts_with("Content-Length: 8\r\n"));
+    }
+
+    #[test]
+    fn http_origin_and_buffered_replies() {
+        assert!(origin_allowed(None));
+        assert!(origin_allowed(Some("http://localhost:3000")));
+        assert!(!origin_allowed(Some("https://example.com")));
+
+        let ex = HttpExchange {
+            incoming: Mutex::new(vec![json!({"id": 1})].into()),
+            out: Mutex::new(HttpOut::Buffered(Vec::new())),
+            session: None,
+        };
+        assert!(ex.recv().unwrap().is_some());
+        assert!(ex.recv().unwrap().is_none());
+        ex.send(&json!({"method": "notifications/progress"}))
+            .unwrap();
+        ex.send(&json!({"id": 1, "result": {}})).unwrap();
+        let HttpOut::Buffered(v) = &*ex.out.lock().unwrap() else {
+            unreachable!()
+        };
+        assert_eq!(v.len(), 1);
+    }
+}
@@ -0,0 +1,92 @@
+//! The MCP server and the CLI must agree on where the data lives for the same environment.
+//! The CLI takes it from `daizo_core::path_resolver::daizo_home`, so the server is checked
+//! against the shared resolver run on the same variables.
+
+use daizo_core::path_resolver::resolve_daizo_home;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Environment for one case: HOME under `tmp` plus `vars`, nothing inherited that moves the
+/// data directory.
+fn command(bin: &Path, tmp: &Path, vars: &[(&str, String)]) -> Command {
+    let mut c = Command::new(bin);
+    c.env_remove("DAIZO_DIR")
+        .env_remove("XDG_DATA_HOME")
+        .env_remove("DAIZO_CONFIG")
+        .env("HOME", tmp.join("home"))
+        .current_dir(tmp)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    for (k, v) in vars {
+        c.env(k, v);
+    }
+    c
+}
+
+fn mcp_data_path(tmp: &Path, vars: &[(&str, String)]) -> PathBuf {
+    let mut child = command(Path::new(env!("CARGO_BIN_EXE_daizo-mcp")), tmp, vars)
+        .spawn()
+        .unwrap();
+    let req = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"daizo_version","arguments":{}}}"#;
+    writeln!(child.stdin.take().unwrap(), "{}", req).unwrap();
+    let out = child.wait_with_output().unwrap();
+    let line = String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .find(|l| l.contains("data_path"))
+        .unwrap()
+        .to_string();
+    let v: serde_json::Value = serde_json::from_str(&line).unwrap();
+    PathBuf::from(v["result"]["_meta"]["data_path"].as_str().unwrap())
+}
+
+/// What the shared resolver makes of `vars` with HOME under `tmp`.
+fn shared_data_path(tmp: &Path, vars: &[(&str, String)]) -> PathBuf {
+    let home = tmp.join("home");
+    resolve_daizo_home(
+        |k| match k {
+            "HOME" => Some(home.clone().into_os_string()),
+            _ => vars
+                .iter()
+                .find(|(name, _)| *name == k)
+                .map(|(_, v)| OsString::from(v)),
+        },
+        Path::is_dir,
+    )
+}
+
+#[test]
+fn server_and_shared_resolver_agree_on_the_data_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let tmp = dir.path();
+    std::fs::create_dir_all(tmp.join("home")).unwrap();
+    let home = tmp.join("home");
+    // A relative `want` is taken from the working directory: `tmp` for the server, this
+    // process's for the in-process resolver.
+    let cwd = std::env::current_dir().unwrap();
+    let cases: Vec<(Vec<(&str, String)>, PathBuf)> = vec![
+        (vec![], home.join(".daizo")),
+        (
+            vec![("DAIZO_DIR", tmp.join("abs").to_string_lossy().into_owned())],
+            tmp.join("abs"),
+        ),
+        (
+            vec![("DAIZO_DIR", "~/tilde".to_string())],
+            home.join("tilde"),
+        ),
+        (vec![("DAIZO_DIR", "rel".to_string())], PathBuf::from("rel")),
+        (
+            vec![(
+                "XDG_DATA_HOME",
+                tmp.join("xdg").to_string_lossy().into_owned(),
+            )],
+            tmp.join("xdg").join("daizo"),
+        ),
+    ];
+    for (vars, want) in &cases {
+        assert_eq!(mcp_data_path(tmp, vars), tmp.join(want), "{:?}", vars);
+        assert_eq!(shared_data_path(tmp, vars), cwd.join(want), "{:?}", vars);
+    }
+}
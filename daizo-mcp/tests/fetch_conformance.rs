@@ -0,0 +1,188 @@
+//! Every local fetch tool slices and highlights its text the same way: given the same text and
+//! the same options, they return the same slice and the same slice `_meta`. Line fetches also
+//! report where their line sits (`_meta.structure`).
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const TEI: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TEI xmlns="http://www.tei-c.org/ns/1.0"><text><body>
+<p>如是我聞。一時佛在舍衛國。</p>
+<p>爾時世尊 告諸比丘。汝等諦聽。</p>
+<p>善哉善哉。佛說是經已。</p>
+</body></text></TEI>
+"#;
+
+/// The same file under each corpus root of `dir`, with the id each fetch tool knows it by.
+fn write_corpora(dir: &Path) -> Vec<(&'static str, &'static str)> {
+    let files = [
+        ("cbeta_fetch", "T0001", "xml-p5/T/T01/T01n0001.xml"),
+        (
+            "tipitaka_fetch",
+            "s0101m.mul",
+            "tipitaka-xml/romn/s0101m.mul.xml",
+        ),
+        ("gretil_fetch", "sa_test", "GRETIL/1_sanskr/tei/sa_test.xml"),
+        ("sarit_fetch", "test", "SARIT-corpus/test.xml"),
+        ("muktabodha_fetch", "test", "MUKTABODHA/test.xml"),
+    ];
+    for (_, _, rel) in &files {
+        let p = dir.join(rel);
+        std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+        std::fs::write(p, TEI).unwrap();
+    }
+    files.iter().map(|(tool, id, _)| (*tool, *id)).collect()
+}
+
+/// Send `calls` to one server over DAIZO_DIR `dir`; the responses by request id.
+fn call_all(dir: &Path, calls: &[serde_json::Value]) -> HashMap<u64, serde_json::Value> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_daizo-mcp"))
+        .env_remove("XDG_DATA_HOME")
+        .env_remove("DAIZO_CONFIG")
+        .env_remove("DAIZO_MCP_MAX_CHARS")
+        .env_remove("DAIZO_PROVENANCE")
+        .env("HOME", dir.join("home"))
+        .env("DAIZO_DIR", dir)
+        .env("DAIZO_DISABLE", "web")
+        .env("DAIZO_PREFETCH", "off")
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for c in calls {
+        writeln!(stdin, "{}", c).unwrap();
+    }
+    drop(stdin);
+    let out = child.wait_with_output().unwrap();
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|l| serde_json::from_str::<serde_json::Value>(l).ok())
+        .filter_map(|v| Some((v["id"].as_u64()?, v)))
+        .collect()
+}
+
+/// What the fetch pipeline decides: the text and the slice and highlight `_meta`.
+fn pipeline_view(resp: &serde_json::Value) -> serde_json::Value {
+    let r = &resp["result"];
+    let m = &r["_meta"];
+    serde_json::json!({
+        "text": r["content"][0]["text"],
+        "totalChars": m["totalChars"],
+        "returnedStart": m["returnedStart"],
+        "returnedEnd": m["returnedEnd"],
+        "truncated": m["truncated"],
+        "continues": m["nextCursor"].is_string(),
+        "nextLine": m["nextLine"],
+        "lineMap": m["lineMap"],
+        "highlighted": m["highlighted"],
+        "highlightPositions": m["highlightPositions"],
+    })
+}
+
+#[test]
+fn fetch_tools_slice_and_highlight_alike() {
+    let tmp = std::env::temp_dir().join(format!("daizo-fetch-conf-{}", std::process::id()));
+    std::fs::create_dir_all(tmp.join("home")).unwrap();
+    let tools = write_corpora(&tmp);
+    let options = [
+        serde_json::json!({"startChar": 14, "maxChars": 12, "highlight": "世尊 告", "highlightStyle": "brackets"}),
+        serde_json::json!({"startLine": 1, "endLine": 1, "highlight": "佛說"}),
+        serde_json::json!({"maxChars": 16, "snap": "sentence"}),
+        serde_json::json!({"page": 1, "pageSize": 10, "highlight": "世.", "highlightRegex": true}),
+        serde_json::json!({"highlight": ["如是", {"pattern": "世尊 告", "prefix": "<", "suffix": ">"}]}),
+    ];
+    let mut calls = Vec::new();
+    for (o, opts) in options.iter().enumerate() {
+        for (t, (tool, id)) in tools.iter().enumerate() {
+            let mut args = opts.clone();
+            args["id"] = serde_json::json!(id);
+            calls.push(serde_json::json!({
+                "jsonrpc": "2.0", "id": o * 10 + t, "method": "tools/call",
+                "params": {"name": tool, "arguments": args}
+            }));
+        }
+    }
+    let responses = call_all(&tmp, &calls);
+    for (o, opts) in options.iter().enumerate() {
+        let views: Vec<(&str, serde_json::Value)> = tools
+            .iter()
+            .enumerate()
+            .map(|(t, (tool, _))| {
+                let resp = &responses[&((o * 10 + t) as u64)];
+                assert!(resp.get("error").is_none(), "{} {}: {}", tool, opts, resp);
+                (*tool, pipeline_view(resp))
+            })
+            .collect();
+        let (first, want) = &views[0];
+        assert!(
+            want["text"].as_str().is_some_and(|t| !t.is_empty()),
+            "{}",
+            opts
+        );
+        if opts.get("highlight").is_some() {
+            assert!(want["highlighted"].as_u64() >= Some(1), "{}", opts);
+        }
+        for (tool, got) in &views[1..] {
+            assert_eq!(got, want, "{} vs {} with {}", tool, first, opts);
+        }
+    }
+    let _ = std::fs::remove_dir_all(&tmp);
+}
+
+#[test]
+fn line_fetches_report_their_juan_and_headings() {
+    let tmp = std::env::temp_dir().join(format!("daizo-fetch-struct-{}", std::process::id()));
+    std::fs::create_dir_all(tmp.join("home")).unwrap();
+    let xml = r#"<TEI><text><body>
+<milestone unit="juan" n="2"/><juan fun="open" n="2"><jhead>妙法蓮華經卷第二</jhead></juan>
+<cb:div><cb:mulu level="1">譬喻品第三</cb:mulu>
+<p><lb n="0010b01"/>爾時舍利弗踊躍歡喜</p>
+</cb:div>
+</body></text></TEI>
+"#;
+    for rel in [
+        "xml-p5/T/T09/T09n0262.xml",
+        "GRETIL/1_sanskr/tei/sa_test.xml",
+    ] {
+        let p = tmp.join(rel);
+        std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+        std::fs::write(p, xml).unwrap();
+    }
+    let call = |n: u64, tool: &str, args: serde_json::Value| {
+        serde_json::json!({"jsonrpc": "2.0", "id": n, "method": "tools/call",
+            "params": {"name": tool, "arguments": args}})
+    };
+    let responses = call_all(
+        &tmp,
+        &[
+            call(
+                1,
+                "cbeta_fetch",
+                serde_json::json!({"id": "T0262", "lineNumber": 4}),
+            ),
+            call(
+                2,
+                "cbeta_fetch",
+                serde_json::json!({"id": "T0262", "lb": "0010b01"}),
+            ),
+            call(
+                3,
+                "gretil_fetch",
+                serde_json::json!({"id": "sa_test", "lineNumber": 4}),
+            ),
+            call(4, "cbeta_fetch", serde_json::json!({"id": "T0262"})),
+        ],
+    );
+    for n in 1..=3 {
+        let s = &responses[&n]["result"]["_meta"]["structure"];
+        assert_eq!(s["label"], "卷第二 > 譬喻品第三", "{}", responses[&n]);
+        assert_eq!(s["juan"], "002");
+    }
+    assert!(responses[&4]["result"]["_meta"]["structure"].is_null());
+    let _ = std::fs::remove_dir_all(&tmp);
+}